@@ -109,6 +109,7 @@ fn bench_reflexion_episodes(args: &Args) -> Result<BenchmarkResult> {
         storage_path: db_path.to_str().unwrap().to_string(),
         hnsw_config: Some(HnswConfig::default()),
         quantization: Some(QuantizationConfig::Scalar),
+        ..DbOptions::default()
     };
 
     let mem_profiler = MemoryProfiler::new();
@@ -214,6 +215,7 @@ fn bench_skill_library(args: &Args) -> Result<BenchmarkResult> {
         storage_path: db_path.to_str().unwrap().to_string(),
         hnsw_config: Some(HnswConfig::default()),
         quantization: Some(QuantizationConfig::Scalar),
+        ..DbOptions::default()
     };
 
     let mem_profiler = MemoryProfiler::new();
@@ -321,6 +323,7 @@ fn bench_causal_graph(args: &Args) -> Result<BenchmarkResult> {
         storage_path: db_path.to_str().unwrap().to_string(),
         hnsw_config: Some(HnswConfig::default()),
         quantization: Some(QuantizationConfig::Scalar),
+        ..DbOptions::default()
     };
 
     let mem_profiler = MemoryProfiler::new();
@@ -422,6 +425,7 @@ fn bench_learning_session(args: &Args) -> Result<BenchmarkResult> {
         storage_path: db_path.to_str().unwrap().to_string(),
         hnsw_config: Some(HnswConfig::default()),
         quantization: Some(QuantizationConfig::Scalar),
+        ..DbOptions::default()
     };
 
     let mem_profiler = MemoryProfiler::new();
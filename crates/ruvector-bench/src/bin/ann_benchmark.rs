@@ -275,8 +275,10 @@ fn run_benchmark(
             ef_construction: args.ef_construction,
             ef_search,
             max_elements: vectors.len() * 2,
+            ..HnswConfig::default()
         }),
         quantization: Some(quantization),
+        ..DbOptions::default()
     };
 
     // Measure build time and memory
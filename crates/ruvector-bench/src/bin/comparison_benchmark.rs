@@ -313,6 +313,7 @@ fn setup_ruvector(
         storage_path: db_path.to_str().unwrap().to_string(),
         hnsw_config: Some(HnswConfig::default()),
         quantization: Some(quantization),
+        ..DbOptions::default()
     };
 
     let db = VectorDB::new(options)?;
@@ -343,6 +343,7 @@ fn setup_database(
         storage_path: db_path.to_str().unwrap().to_string(),
         hnsw_config: Some(HnswConfig::default()),
         quantization: Some(quantization),
+        ..DbOptions::default()
     };
 
     let db = VectorDB::new(options)?;
@@ -100,6 +100,7 @@ fn bench_memory_scale(args: &Args, num_vectors: usize) -> Result<BenchmarkResult
         storage_path: db_path.to_str().unwrap().to_string(),
         hnsw_config: Some(HnswConfig::default()),
         quantization: Some(QuantizationConfig::Scalar),
+        ..DbOptions::default()
     };
 
     let mem_profiler = MemoryProfiler::new();
@@ -215,6 +216,7 @@ fn bench_quantization_memory(args: &Args) -> Result<Vec<BenchmarkResult>> {
             storage_path: db_path.to_str().unwrap().to_string(),
             hnsw_config: Some(HnswConfig::default()),
             quantization: Some(quant_config),
+            ..DbOptions::default()
         };
 
         let mem_profiler = MemoryProfiler::new();
@@ -307,8 +309,10 @@ fn bench_index_overhead(args: &Args) -> Result<BenchmarkResult> {
             ef_construction: 200,
             ef_search: 100,
             max_elements: num_vectors * 2,
+            ..HnswConfig::default()
         }),
         quantization: Some(QuantizationConfig::None), // No quantization for overhead analysis
+        ..DbOptions::default()
     };
 
     let mem_profiler = MemoryProfiler::new();
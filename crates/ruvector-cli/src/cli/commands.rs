@@ -8,9 +8,10 @@ use crate::config::Config;
 use anyhow::{Context, Result};
 use colored::*;
 use ruvector_core::{
-    types::{DbOptions, SearchQuery, VectorEntry},
-    VectorDB,
+    types::{DbOptions, RangeSearchQuery, SearchByIdQuery, SearchQuery, VectorEntry},
+    RuvectorError, ScrollRequest, VectorDB,
 };
+use std::io::Write;
 use std::path::{Path, PathBuf};
 use std::time::Instant;
 
@@ -101,11 +102,25 @@ pub fn insert_vectors(
     Ok(())
 }
 
+/// What [`search_vectors`] searches for
+pub enum SearchMode {
+    /// The k nearest neighbors of a query vector
+    TopK { vector: Vec<f32>, k: usize },
+    /// Every point within `radius` of a query vector, nearest first
+    Range {
+        vector: Vec<f32>,
+        radius: f32,
+        max_results: usize,
+    },
+    /// The k nearest neighbors of a stored point
+    ById { id: String, k: usize },
+}
+
 /// Search for similar vectors
 pub fn search_vectors(
     db_path: &str,
-    query_vector: Vec<f32>,
-    k: usize,
+    mode: SearchMode,
+    ef_search: Option<usize>,
     config: &Config,
     show_vectors: bool,
 ) -> Result<()> {
@@ -115,14 +130,36 @@ pub fn search_vectors(
     let db = VectorDB::new(db_options).context("Failed to open database")?;
 
     let start = Instant::now();
-    let results = db
-        .search(SearchQuery {
-            vector: query_vector,
+    let results = match mode {
+        SearchMode::TopK { vector, k } => db.search(SearchQuery {
+            vector,
             k,
             filter: None,
-            ef_search: None,
-        })
-        .context("Failed to search")?;
+            ef_search,
+        }),
+        SearchMode::Range {
+            vector,
+            radius,
+            max_results,
+        } => db.search_range(RangeSearchQuery {
+            space: None,
+            vector,
+            radius,
+            max_results,
+            filter: None,
+            ef_search,
+        }),
+        SearchMode::ById { id, k } => db
+            .search_by_id(SearchByIdQuery {
+                id: id.clone(),
+                space: None,
+                k,
+                filter: None,
+                ef_search,
+            })
+            .and_then(|results| results.ok_or(RuvectorError::VectorNotFound(id))),
+    }
+    .context("Failed to search")?;
 
     let elapsed = start.elapsed();
 
@@ -236,17 +273,94 @@ pub fn export_database(
         format_success(&format!("Exporting database to: {}", output_file))
     );
 
-    // Export is currently limited - would need to add all_ids() method to VectorDB
-    // For now, return an error with a helpful message
-    return Err(anyhow::anyhow!(
-        "Export functionality requires VectorDB::all_ids() method. This will be implemented in a future update."
-    ));
-
-    // TODO: Implement when VectorDB exposes all_ids()
-    // let ids = db.all_ids()?;
-    // let tracker = ProgressTracker::new();
-    // let pb = tracker.create_bar(ids.len() as u64, "Exporting vectors...");
-    // ...
+    let start = Instant::now();
+    let total = db.len().context("Failed to count vectors")?;
+    let tracker = ProgressTracker::new();
+    let pb = tracker.create_bar(total as u64, "Exporting vectors...");
+
+    let file = std::fs::File::create(output_file).context("Failed to create output file")?;
+    let mut writer = std::io::BufWriter::new(file);
+    let batch_size = config.cli.batch_size;
+    let mut exported = 0;
+
+    match format {
+        "json" => {
+            writer.write_all(b"[")?;
+            scroll_entries(&db, batch_size, |entry| {
+                if exported > 0 {
+                    writer.write_all(b",")?;
+                }
+                writer.write_all(b"\n  ")?;
+                serde_json::to_writer(&mut writer, &entry)?;
+                exported += 1;
+                pb.inc(1);
+                Ok(())
+            })?;
+            writer.write_all(b"\n]\n")?;
+        }
+        "csv" => {
+            let mut wtr = csv::Writer::from_writer(&mut writer);
+            wtr.write_record(["id", "vector", "metadata"])?;
+            scroll_entries(&db, batch_size, |entry| {
+                let metadata = match &entry.metadata {
+                    Some(metadata) => serde_json::to_string(metadata)?,
+                    None => String::new(),
+                };
+                wtr.write_record([
+                    entry.id.as_deref().unwrap_or(""),
+                    &serde_json::to_string(&entry.vector)?,
+                    &metadata,
+                ])?;
+                exported += 1;
+                pb.inc(1);
+                Ok(())
+            })?;
+            wtr.flush()?;
+        }
+        _ => return Err(anyhow::anyhow!("Unsupported format: {}", format)),
+    }
+    writer.flush().context("Failed to write output file")?;
+    pb.finish_with_message("Export complete!");
+
+    println!(
+        "{}",
+        format_success(&format!(
+            "Exported {} vectors in {:.2}s",
+            exported,
+            start.elapsed().as_secs_f64()
+        ))
+    );
+
+    Ok(())
+}
+
+/// Visit every stored entry in id order, one scroll page at a time
+fn scroll_entries(
+    db: &VectorDB,
+    batch_size: usize,
+    mut visit: impl FnMut(VectorEntry) -> Result<()>,
+) -> Result<()> {
+    let mut request = ScrollRequest {
+        limit: batch_size.max(1),
+        ..Default::default()
+    };
+
+    loop {
+        let page = db
+            .scroll(request.clone())
+            .context("Failed to read vectors")?;
+        for point in page.points {
+            visit(VectorEntry {
+                id: Some(point.id),
+                vector: point.vector.unwrap_or_default(),
+                metadata: point.metadata,
+            })?;
+        }
+        match page.next_offset {
+            Some(offset) => request.offset = Some(offset),
+            None => return Ok(()),
+        }
+    }
 }
 
 /// Import from other vector databases
@@ -230,6 +230,7 @@ impl Config {
             storage_path: self.database.storage_path.clone(),
             hnsw_config: self.database.hnsw.clone(),
             quantization: self.database.quantization.clone(),
+            ..DbOptions::default()
         }
     }
 
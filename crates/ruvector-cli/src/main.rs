@@ -71,13 +71,29 @@ enum Commands {
         db: String,
 
         /// Query vector (comma-separated floats or JSON array)
-        #[arg(short, long)]
-        query: String,
+        #[arg(short, long, required_unless_present = "id")]
+        query: Option<String>,
+
+        /// Search for the neighbors of this stored point instead of a query vector
+        #[arg(long, conflicts_with_all = ["query", "radius"])]
+        id: Option<String>,
 
         /// Number of results
         #[arg(short = 'k', long, default_value = "10")]
         top_k: usize,
 
+        /// Return every point within this distance of the query instead of the top k
+        #[arg(long)]
+        radius: Option<f32>,
+
+        /// Maximum number of points returned by a radius search
+        #[arg(long, default_value = "1000", requires = "radius")]
+        max_results: usize,
+
+        /// HNSW ef_search for this query (defaults to the configured value)
+        #[arg(long)]
+        ef_search: Option<usize>,
+
         /// Show full vectors in results
         #[arg(long)]
         show_vectors: bool,
@@ -169,11 +185,29 @@ async fn main() -> Result<()> {
         Commands::Search {
             db,
             query,
+            id,
             top_k,
+            radius,
+            max_results,
+            ef_search,
             show_vectors,
         } => {
-            let query_vec = parse_query_vector(&query)?;
-            search_vectors(&db, query_vec, top_k, &config, show_vectors)
+            let mode = match (id, query) {
+                (Some(id), _) => SearchMode::ById { id, k: top_k },
+                (None, Some(query)) => {
+                    let vector = parse_query_vector(&query)?;
+                    match radius {
+                        Some(radius) => SearchMode::Range {
+                            vector,
+                            radius,
+                            max_results,
+                        },
+                        None => SearchMode::TopK { vector, k: top_k },
+                    }
+                }
+                (None, None) => unreachable!("clap requires --query or --id"),
+            };
+            search_vectors(&db, mode, ef_search, &config, show_vectors)
         }
         Commands::Info { db } => show_info(&db, &config),
         Commands::Benchmark { db, queries } => run_benchmark(&db, &config, queries),
@@ -1,8 +1,11 @@
 //! Collection types and operations
 
+use ruvector_core::embeddings::EmbeddingConfig;
 use ruvector_core::types::{DistanceMetric, HnswConfig, QuantizationConfig};
 use ruvector_core::vector_db::VectorDB;
 use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::sync::Arc;
 
 use crate::error::{CollectionError, Result};
 
@@ -23,6 +26,10 @@ pub struct CollectionConfig {
 
     /// Whether to store payload data on disk
     pub on_disk_payload: bool,
+
+    /// Provider embedding text for points and queries
+    #[serde(default)]
+    pub embedding: Option<EmbeddingConfig>,
 }
 
 impl CollectionConfig {
@@ -40,6 +47,18 @@ impl CollectionConfig {
             });
         }
 
+        if let Some(ref embedding) = self.embedding {
+            if embedding.dimensions() != self.dimensions {
+                return Err(CollectionError::InvalidConfiguration {
+                    message: format!(
+                        "Embedding dimensions {} do not match collection dimensions {}",
+                        embedding.dimensions(),
+                        self.dimensions
+                    ),
+                });
+            }
+        }
+
         // Validate HNSW config if present
         if let Some(ref hnsw_config) = self.hnsw_config {
             if hnsw_config.m == 0 {
@@ -72,6 +91,7 @@ impl CollectionConfig {
             hnsw_config: Some(HnswConfig::default()),
             quantization: Some(QuantizationConfig::Scalar),
             on_disk_payload: true,
+            embedding: None,
         }
     }
 }
@@ -85,7 +105,7 @@ pub struct Collection {
     pub config: CollectionConfig,
 
     /// Underlying vector database
-    pub db: VectorDB,
+    pub db: Arc<VectorDB>,
 
     /// When the collection was created (Unix timestamp in seconds)
     pub created_at: i64,
@@ -119,9 +139,13 @@ impl Collection {
             storage_path,
             hnsw_config: config.hnsw_config.clone(),
             quantization: config.quantization.clone(),
+            ..ruvector_core::types::DbOptions::default()
         };
 
         let db = VectorDB::new(db_options)?;
+        if config.embedding.is_some() {
+            db.set_embedding(config.embedding.clone())?;
+        }
 
         let now = std::time::SystemTime::now()
             .duration_since(std::time::UNIX_EPOCH)
@@ -131,7 +155,7 @@ impl Collection {
         Ok(Self {
             name,
             config,
-            db,
+            db: Arc::new(db),
             created_at: now,
             updated_at: now,
         })
@@ -143,9 +167,9 @@ impl Collection {
 
         Ok(CollectionStats {
             vectors_count,
-            segments_count: 1,  // Single segment for now
-            disk_size_bytes: 0, // TODO: Implement disk size calculation
-            ram_size_bytes: 0,  // TODO: Implement RAM size calculation
+            segments_count: 1, // Single segment for now
+            disk_size_bytes: disk_size(&self.db.options().storage_path),
+            ram_size_bytes: 0, // TODO: Implement RAM size calculation
         })
     }
 
@@ -191,6 +215,36 @@ impl CollectionStats {
     }
 }
 
+/// Size of the database file at `storage_path` and the files stored next to
+/// it under the same name, such as the index checkpoint
+fn disk_size(storage_path: &str) -> u64 {
+    let path = Path::new(storage_path);
+    let (Some(dir), Some(name)) = (path.parent(), path.file_name()) else {
+        return 0;
+    };
+    let dir = if dir.as_os_str().is_empty() {
+        Path::new(".")
+    } else {
+        dir
+    };
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return 0;
+    };
+
+    entries
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| {
+            entry
+                .file_name()
+                .to_string_lossy()
+                .starts_with(&*name.to_string_lossy())
+        })
+        .filter_map(|entry| entry.metadata().ok())
+        .filter(|metadata| metadata.is_file())
+        .map(|metadata| metadata.len())
+        .sum()
+}
+
 /// Format bytes into human-readable size
 fn format_bytes(bytes: u64) -> String {
     const UNITS: &[&str] = &["B", "KB", "MB", "GB", "TB"];
@@ -227,6 +281,7 @@ mod tests {
             hnsw_config: None,
             quantization: None,
             on_disk_payload: true,
+            embedding: None,
         };
         assert!(config.validate().is_err());
 
@@ -237,6 +292,7 @@ mod tests {
             hnsw_config: None,
             quantization: None,
             on_disk_payload: true,
+            embedding: None,
         };
         assert!(config.validate().is_err());
     }
@@ -28,6 +28,7 @@
 //!     hnsw_config: Some(HnswConfig::default()),
 //!     quantization: None,
 //!     on_disk_payload: true,
+//!     embedding: None,
 //! };
 //!
 //! manager.create_collection("documents", config)?;
@@ -50,4 +51,4 @@ pub mod manager;
 
 pub use collection::{Collection, CollectionConfig, CollectionStats};
 pub use error::{CollectionError, Result};
-pub use manager::CollectionManager;
+pub use manager::{ChangeListenerFactory, CollectionManager};
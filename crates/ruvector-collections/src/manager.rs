@@ -2,6 +2,7 @@
 
 use dashmap::DashMap;
 use parking_lot::RwLock;
+use ruvector_core::BoxedChangeListener;
 use std::collections::HashMap;
 use std::path::PathBuf;
 use std::sync::Arc;
@@ -18,8 +19,11 @@ struct CollectionMetadata {
     updated_at: i64,
 }
 
+/// Builds the change listener registered on a collection's database,
+/// given the collection's name
+pub type ChangeListenerFactory = Arc<dyn Fn(&str) -> BoxedChangeListener + Send + Sync>;
+
 /// Manages multiple vector collections with alias support
-#[derive(Debug)]
 pub struct CollectionManager {
     /// Active collections
     collections: DashMap<String, Arc<RwLock<Collection>>>,
@@ -29,6 +33,20 @@ pub struct CollectionManager {
 
     /// Base path for storing collections
     base_path: PathBuf,
+
+    /// Builds the change listener of every collection, once set
+    change_listener: RwLock<Option<ChangeListenerFactory>>,
+}
+
+impl std::fmt::Debug for CollectionManager {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("CollectionManager")
+            .field("collections", &self.collections)
+            .field("aliases", &self.aliases)
+            .field("base_path", &self.base_path)
+            .field("change_listener", &self.change_listener.read().is_some())
+            .finish()
+    }
 }
 
 impl CollectionManager {
@@ -54,6 +72,7 @@ impl CollectionManager {
             collections: DashMap::new(),
             aliases: DashMap::new(),
             base_path,
+            change_listener: RwLock::new(None),
         };
 
         // Load existing collections
@@ -106,6 +125,13 @@ impl CollectionManager {
         // Save metadata
         self.save_collection_metadata(&collection)?;
 
+        // Held until the collection is listed, so a listener set meanwhile
+        // isn't missed
+        let change_listener = self.change_listener.read();
+        if let Some(factory) = change_listener.as_ref() {
+            collection.db.add_change_listener(factory(name))?;
+        }
+
         // Add to collections map
         self.collections
             .insert(name.to_string(), Arc::new(RwLock::new(collection)));
@@ -113,6 +139,26 @@ impl CollectionManager {
         Ok(())
     }
 
+    /// Register a change listener built by `factory` on the database of
+    /// every collection, including those created later
+    ///
+    /// Replaces the factory for collections created later; listeners
+    /// already registered stay until the manager is reopened.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a listener fails to catch up with the changes
+    /// its collection logged since it left off
+    pub fn set_change_listener(&self, factory: ChangeListenerFactory) -> Result<()> {
+        let mut change_listener = self.change_listener.write();
+        for entry in self.collections.iter() {
+            let db = entry.value().read().db.clone();
+            db.add_change_listener(factory(entry.key()))?;
+        }
+        *change_listener = Some(factory);
+        Ok(())
+    }
+
     /// Delete a collection
     ///
     /// # Arguments
@@ -180,6 +226,11 @@ impl CollectionManager {
             .collect()
     }
 
+    /// Number of collections, not counting aliases
+    pub fn collection_count(&self) -> usize {
+        self.collections.len()
+    }
+
     /// Check if a collection exists
     ///
     /// # Arguments
@@ -519,4 +570,88 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn test_collections_and_aliases_survive_reopen() -> Result<()> {
+        let temp_dir = std::env::temp_dir().join("ruvector_test_collections_reopen");
+        let _ = std::fs::remove_dir_all(&temp_dir);
+
+        let manager = CollectionManager::new(temp_dir.clone())?;
+        manager.create_collection("docs", CollectionConfig::with_dimensions(3))?;
+        manager.create_alias("current", "docs")?;
+        let db = manager.get_collection("current").unwrap().read().db.clone();
+        db.insert(ruvector_core::types::VectorEntry {
+            id: Some("a".to_string()),
+            vector: vec![1.0, 0.0, 0.0],
+            metadata: None,
+        })?;
+        drop(db);
+        drop(manager);
+
+        let manager = CollectionManager::new(temp_dir.clone())?;
+        assert_eq!(manager.list_collections(), vec!["docs".to_string()]);
+        assert_eq!(manager.collection_count(), 1);
+        assert_eq!(manager.resolve_alias("current"), Some("docs".to_string()));
+        let stats = manager.collection_stats("current")?;
+        assert_eq!(stats.vectors_count, 1);
+        assert!(stats.disk_size_bytes > 0);
+
+        // A collection created again under a deleted name starts empty
+        manager.delete_alias("current")?;
+        manager.delete_collection("docs")?;
+        manager.create_collection("docs", CollectionConfig::with_dimensions(3))?;
+        assert!(manager.collection_stats("docs")?.is_empty());
+
+        let _ = std::fs::remove_dir_all(&temp_dir);
+        Ok(())
+    }
+
+    #[test]
+    fn test_change_listener_covers_every_collection() -> Result<()> {
+        use ruvector_core::{ChangeListener, PointChange};
+
+        struct Recorder {
+            collection: String,
+            seen: Arc<RwLock<Vec<String>>>,
+        }
+
+        impl ChangeListener for Recorder {
+            fn on_change(&self, changes: &[PointChange]) -> ruvector_core::Result<()> {
+                for change in changes {
+                    let seen = format!("{}/{}", self.collection, change.id);
+                    self.seen.write().push(seen);
+                }
+                Ok(())
+            }
+        }
+
+        let temp_dir = std::env::temp_dir().join("ruvector_test_collections_changes");
+        let _ = std::fs::remove_dir_all(&temp_dir);
+
+        let manager = CollectionManager::new(temp_dir.clone())?;
+        manager.create_collection("before", CollectionConfig::with_dimensions(2))?;
+
+        let seen = Arc::new(RwLock::new(Vec::new()));
+        let recorded = seen.clone();
+        manager.set_change_listener(Arc::new(move |collection: &str| {
+            Arc::new(Recorder {
+                collection: collection.to_string(),
+                seen: recorded.clone(),
+            }) as BoxedChangeListener
+        }))?;
+        manager.create_collection("after", CollectionConfig::with_dimensions(2))?;
+
+        for name in ["before", "after"] {
+            let db = manager.get_collection(name).unwrap().read().db.clone();
+            db.insert(ruvector_core::types::VectorEntry {
+                id: Some("a".to_string()),
+                vector: vec![1.0, 0.0],
+                metadata: None,
+            })?;
+        }
+        assert_eq!(*seen.read(), vec!["before/a", "after/a"]);
+
+        let _ = std::fs::remove_dir_all(&temp_dir);
+        Ok(())
+    }
 }
@@ -46,6 +46,7 @@ fn bench_insert_single(c: &mut Criterion) {
                     distance_metric: DistanceMetric::Cosine,
                     hnsw_config: Some(HnswConfig::default()),
                     quantization: None,
+                    ..DbOptions::default()
                 };
                 let db = VectorDB::new(options).unwrap();
                 let mut idx = 0;
@@ -85,6 +86,7 @@ fn bench_insert_batch(c: &mut Criterion) {
                         distance_metric: DistanceMetric::Cosine,
                         hnsw_config: Some(HnswConfig::default()),
                         quantization: None,
+                        ..DbOptions::default()
                     };
                     let db = VectorDB::new(options).unwrap();
 
@@ -120,8 +122,10 @@ fn bench_search(c: &mut Criterion) {
             ef_construction: 100,
             ef_search: 50,
             max_elements: 100000,
+            ..HnswConfig::default()
         }),
         quantization: None,
+        ..DbOptions::default()
     };
     let db = VectorDB::new(options).unwrap();
 
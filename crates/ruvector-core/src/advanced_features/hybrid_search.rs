@@ -320,7 +320,7 @@ fn tokenize(text: &str) -> Vec<String> {
         .collect()
 }
 
-fn normalize_scores(scores: &mut [f32], strategy: NormalizationStrategy) {
+pub(crate) fn normalize_scores(scores: &mut [f32], strategy: NormalizationStrategy) {
     if scores.is_empty() {
         return;
     }
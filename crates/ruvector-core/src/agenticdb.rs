@@ -325,7 +325,7 @@ impl AgenticDB {
             filter: Some({
                 let mut filter = HashMap::new();
                 filter.insert("type".to_string(), serde_json::json!("reflexion"));
-                filter
+                filter.into()
             }),
             ef_search: None,
         })?;
@@ -415,7 +415,7 @@ impl AgenticDB {
             filter: Some({
                 let mut filter = HashMap::new();
                 filter.insert("type".to_string(), serde_json::json!("skill"));
-                filter
+                filter.into()
             }),
             ef_search: None,
         })?;
@@ -537,7 +537,7 @@ impl AgenticDB {
             filter: Some({
                 let mut filter = HashMap::new();
                 filter.insert("type".to_string(), serde_json::json!("causal"));
-                filter
+                filter.into()
             }),
             ef_search: None,
         })?;
@@ -0,0 +1,269 @@
+//! Change notifications for stored points
+//!
+//! A [`ChangeListener`] registered with
+//! [`VectorDB::add_change_listener`](crate::VectorDB::add_change_listener)
+//! is told about every committed insert, update and delete of a point, with
+//! the point's vector and metadata before and after the change. Listeners
+//! are called once per write, after its operations reached the indexes but
+//! while it still holds the database's write mutex, which serializes
+//! writes, so they see changes in commit order; they are flushed once the
+//! write released the mutex. A listener that fails fails the write,
+//! although the write itself stays committed.
+//!
+//! With file storage every change carries the sequence of the logged
+//! operation that made it. A listener reporting the last sequence it
+//! recorded through [`ChangeListener::resume_after`] is replayed what it
+//! missed, such as the changes of a write that crashed before the listener
+//! flushed, from the operation log when it is added; the database keeps
+//! those operations in the log until then.
+//!
+//! Changes to named and sparse vectors alone, which leave a point's default
+//! vector and metadata as they were, are not reported.
+
+use crate::error::Result;
+use crate::oplog::{LogEntry, Operation};
+use crate::types::{VectorEntry, VectorId};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Arc;
+
+/// What happened to a point
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum PointChangeKind {
+    /// The point didn't exist before
+    Insert,
+    /// The point's vector or metadata was replaced
+    Update,
+    /// The point was deleted
+    Delete,
+}
+
+/// A committed change to a point
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PointChange {
+    /// What happened to the point
+    pub kind: PointChangeKind,
+    /// Point ID
+    pub id: VectorId,
+    /// Sequence of the logged operation that made the change, 0 without
+    /// file storage
+    pub sequence: u64,
+    /// The point before the change, `None` for inserts and for replayed
+    /// changes, whose prior state is unknown
+    pub before: Option<VectorEntry>,
+    /// The point after the change, `None` for deletes
+    pub after: Option<VectorEntry>,
+}
+
+/// Receives the changes committed to a database
+///
+/// Listeners run on the writing thread and delay the write until they
+/// return; they must not write to the database themselves.
+pub trait ChangeListener: Send + Sync {
+    /// Called after each write with the points it changed, in order
+    ///
+    /// Runs while the write holds the database's write mutex, which keeps
+    /// other writes, but not reads, waiting, so it should only stage the
+    /// changes; an error fails the write.
+    fn on_change(&self, changes: &[PointChange]) -> Result<()>;
+
+    /// Called after [`ChangeListener::on_change`] once the write released
+    /// the write mutex, to make the staged changes durable; an error fails
+    /// the write
+    fn flush(&self) -> Result<()> {
+        Ok(())
+    }
+
+    /// Sequence of the last logged operation whose changes the listener
+    /// recorded durably, `None` if it doesn't track one
+    fn resume_after(&self) -> Option<u64> {
+        None
+    }
+}
+
+/// Type-erased change listener for dynamic dispatch
+pub type BoxedChangeListener = Arc<dyn ChangeListener>;
+
+/// The changes made by the logged `written` operations, given the points
+/// they touch as they were before; points missing from `before` didn't exist
+///
+/// Without `before`, as when replaying operations whose changes were never
+/// recorded, the prior state of a point is unknown until an operation sets
+/// it: such changes have no before image, upserts are reported as updates
+/// and payload updates take the point from `stored`.
+pub(crate) fn point_changes(
+    before: Option<HashMap<VectorId, VectorEntry>>,
+    written: &[LogEntry],
+    mut stored: impl FnMut(&str) -> Option<VectorEntry>,
+) -> Vec<PointChange> {
+    let known = before.is_some();
+    // Tracks each point through the write, which may touch it repeatedly
+    let mut current: HashMap<VectorId, Option<VectorEntry>> = before
+        .unwrap_or_default()
+        .into_iter()
+        .map(|(id, entry)| (id, Some(entry)))
+        .collect();
+
+    let mut changes = Vec::new();
+    for LogEntry {
+        sequence,
+        operation,
+    } in written
+    {
+        let (id, after) = match operation {
+            Operation::Upsert {
+                id,
+                vector,
+                metadata,
+            } => (
+                id,
+                Some(VectorEntry {
+                    id: Some(id.clone()),
+                    vector: vector.clone(),
+                    metadata: metadata.clone(),
+                }),
+            ),
+            Operation::UpdatePayload { id, metadata } => {
+                let point = match current.get(id) {
+                    Some(point) => point.clone(),
+                    None if !known => stored(id),
+                    None => None,
+                };
+                let Some(point) = point else {
+                    continue;
+                };
+                let after = VectorEntry {
+                    metadata: metadata.clone(),
+                    ..point
+                };
+                (id, Some(after))
+            }
+            Operation::Delete { id } => (id, None),
+            _ => continue,
+        };
+
+        let previous = current.insert(id.clone(), after.clone());
+        let existed = previous.as_ref().map_or(!known, Option::is_some);
+        let kind = match (existed, &after) {
+            (false, Some(_)) => PointChangeKind::Insert,
+            (true, Some(_)) => PointChangeKind::Update,
+            (true, None) => PointChangeKind::Delete,
+            (false, None) => continue,
+        };
+        changes.push(PointChange {
+            kind,
+            id: id.clone(),
+            sequence: *sequence,
+            before: previous.flatten(),
+            after,
+        });
+    }
+    changes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(id: &str, x: f32) -> VectorEntry {
+        VectorEntry {
+            id: Some(id.to_string()),
+            vector: vec![x, 0.0],
+            metadata: None,
+        }
+    }
+
+    fn upsert(id: &str, x: f32) -> Operation {
+        Operation::Upsert {
+            id: id.to_string(),
+            vector: vec![x, 0.0],
+            metadata: None,
+        }
+    }
+
+    fn logged(operations: Vec<Operation>) -> Vec<LogEntry> {
+        (1..)
+            .zip(operations)
+            .map(|(sequence, operation)| LogEntry {
+                sequence,
+                operation,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_point_changes_follow_each_point() {
+        let before = HashMap::from([("a".to_string(), entry("a", 1.0))]);
+        let metadata = HashMap::from([("tag".to_string(), serde_json::json!("x"))]);
+        let written = vec![
+            upsert("a", 2.0),
+            upsert("b", 3.0),
+            upsert("b", 4.0),
+            Operation::UpdatePayload {
+                id: "b".to_string(),
+                metadata: Some(metadata.clone()),
+            },
+            Operation::Delete {
+                id: "a".to_string(),
+            },
+            Operation::DropVectorSpace {
+                space: "text".to_string(),
+            },
+        ];
+
+        let changes = point_changes(Some(before), &logged(written), |_| None);
+        let kinds: Vec<_> = changes.iter().map(|change| change.kind).collect();
+        assert_eq!(
+            kinds,
+            vec![
+                PointChangeKind::Update,
+                PointChangeKind::Insert,
+                PointChangeKind::Update,
+                PointChangeKind::Update,
+                PointChangeKind::Delete,
+            ]
+        );
+        assert_eq!(changes[0].before.as_ref().unwrap().vector, vec![1.0, 0.0]);
+        assert_eq!(changes[2].before.as_ref().unwrap().vector, vec![3.0, 0.0]);
+        let tagged = changes[3].after.as_ref().unwrap();
+        assert_eq!(tagged.vector, vec![4.0, 0.0]);
+        assert_eq!(tagged.metadata, Some(metadata));
+        assert_eq!(changes[4].before.as_ref().unwrap().vector, vec![2.0, 0.0]);
+        assert!(changes[4].after.is_none());
+        let sequences: Vec<_> = changes.iter().map(|change| change.sequence).collect();
+        assert_eq!(sequences, vec![1, 2, 3, 4, 5]);
+    }
+
+    #[test]
+    fn test_replayed_changes_have_unknown_prior_state() {
+        let metadata = HashMap::from([("tag".to_string(), serde_json::json!("x"))]);
+        let written = vec![
+            Operation::UpdatePayload {
+                id: "a".to_string(),
+                metadata: Some(metadata.clone()),
+            },
+            Operation::Delete {
+                id: "b".to_string(),
+            },
+            upsert("b", 2.0),
+            upsert("c", 3.0),
+        ];
+
+        let stored = |id: &str| (id == "a").then(|| entry("a", 1.0));
+        let changes = point_changes(None, &logged(written), stored);
+        let kinds: Vec<_> = changes.iter().map(|change| change.kind).collect();
+        assert_eq!(
+            kinds,
+            vec![
+                PointChangeKind::Update,
+                PointChangeKind::Delete,
+                PointChangeKind::Insert,
+                PointChangeKind::Update,
+            ]
+        );
+        assert!(changes.iter().all(|change| change.before.is_none()));
+        let tagged = changes[0].after.as_ref().unwrap();
+        assert_eq!(tagged.vector, vec![1.0, 0.0]);
+        assert_eq!(tagged.metadata, Some(metadata));
+    }
+}
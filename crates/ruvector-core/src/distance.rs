@@ -85,6 +85,60 @@ pub fn manhattan_distance(a: &[f32], b: &[f32]) -> f32 {
     a.iter().zip(b.iter()).map(|(x, y)| (x - y).abs()).sum()
 }
 
+/// ColBERT MaxSim score of two bags of `dimensions`-sized vectors stored back
+/// to back: the sum over query vectors of the largest dot product with any
+/// document vector (higher is more similar)
+pub fn max_sim(query: &[f32], document: &[f32], dimensions: usize) -> Result<f32> {
+    Ok(-max_sim_distance(
+        query,
+        document,
+        dimensions,
+        DistanceMetric::DotProduct,
+    )?)
+}
+
+/// Late-interaction distance of two bags of `dimensions`-sized vectors stored
+/// back to back: the sum over query vectors of the distance to the closest
+/// document vector (lower is better)
+///
+/// With [`DistanceMetric::DotProduct`] this is the negated MaxSim score, with
+/// [`DistanceMetric::Cosine`] the number of query vectors minus MaxSim over
+/// normalized vectors.
+pub fn max_sim_distance(
+    query: &[f32],
+    document: &[f32],
+    dimensions: usize,
+    metric: DistanceMetric,
+) -> Result<f32> {
+    for bag in [query, document] {
+        if dimensions == 0 || bag.is_empty() || bag.len() % dimensions != 0 {
+            return Err(RuvectorError::InvalidDimension(format!(
+                "Multi-vector of length {} is not a non-empty bag of {}-dimensional vectors",
+                bag.len(),
+                dimensions
+            )));
+        }
+    }
+
+    // Resolve the kernel once instead of per vector pair
+    let kernel: fn(&[f32], &[f32]) -> f32 = match metric {
+        DistanceMetric::Euclidean => euclidean_distance,
+        DistanceMetric::Cosine => cosine_distance,
+        DistanceMetric::DotProduct => dot_product_distance,
+        DistanceMetric::Manhattan => manhattan_distance,
+    };
+
+    Ok(query
+        .chunks_exact(dimensions)
+        .map(|q| {
+            document
+                .chunks_exact(dimensions)
+                .map(|d| kernel(q, d))
+                .fold(f32::INFINITY, f32::min)
+        })
+        .sum())
+}
+
 /// Batch distance calculation optimized with Rayon (native) or sequential (WASM)
 pub fn batch_distances(
     query: &[f32],
@@ -160,6 +214,22 @@ mod tests {
         assert!((dist - 9.0).abs() < 0.01); // |1-4| + |2-5| + |3-6| = 9
     }
 
+    #[test]
+    fn test_max_sim() -> Result<()> {
+        // Two query tokens against three document tokens
+        let query = [1.0, 0.0, 0.0, 1.0];
+        let document = [0.5, 0.0, 0.0, 2.0, 1.0, 1.0];
+        // max(0.5, 0, 1) + max(0, 2, 1)
+        assert!((max_sim(&query, &document, 2)? - 3.0).abs() < 1e-6);
+        // min(0.5, 2.236, 1) + min(1.118, 1, 1)
+        let dist = max_sim_distance(&query, &document, 2, DistanceMetric::Euclidean)?;
+        assert!((dist - 1.5).abs() < 1e-4);
+
+        assert!(max_sim(&query, &document[..5], 2).is_err());
+        assert!(max_sim(&[], &document, 2).is_err());
+        Ok(())
+    }
+
     #[test]
     fn test_dimension_mismatch() {
         let a = vec![1.0, 2.0];
@@ -25,6 +25,7 @@
 //! ```
 
 use crate::error::{Result, RuvectorError};
+use serde::{Deserialize, Serialize};
 use std::sync::Arc;
 
 /// Trait for text embedding providers
@@ -32,6 +33,14 @@ pub trait EmbeddingProvider: Send + Sync {
     /// Generate embedding vector for the given text
     fn embed(&self, text: &str) -> Result<Vec<f32>>;
 
+    /// Generate embedding vectors for several texts, in order
+    ///
+    /// Providers backed by a remote model should override this to embed
+    /// the whole batch in one call.
+    fn embed_batch(&self, texts: &[&str]) -> Result<Vec<Vec<f32>>> {
+        texts.iter().map(|text| self.embed(text)).collect()
+    }
+
     /// Get the dimensionality of embeddings produced by this provider
     fn dimensions(&self) -> usize;
 
@@ -275,8 +284,20 @@ impl ApiEmbedding {
 #[cfg(feature = "api-embeddings")]
 impl EmbeddingProvider for ApiEmbedding {
     fn embed(&self, text: &str) -> Result<Vec<f32>> {
+        self.embed_batch(&[text])?
+            .pop()
+            .ok_or_else(|| RuvectorError::ModelInferenceError(
+                "API returned no embedding".to_string()
+            ))
+    }
+
+    fn embed_batch(&self, texts: &[&str]) -> Result<Vec<Vec<f32>>> {
+        if texts.is_empty() {
+            return Ok(Vec::new());
+        }
+
         let request_body = serde_json::json!({
-            "input": text,
+            "input": texts,
             "model": self.model,
         });
 
@@ -300,40 +321,59 @@ impl EmbeddingProvider for ApiEmbedding {
             .map_err(|e| RuvectorError::ModelInferenceError(format!("Failed to parse response: {}", e)))?;
 
         // Handle different API response formats
-        let embedding = if let Some(data) = response_json.get("data") {
-            // OpenAI format: {"data": [{"embedding": [...]}]}
-            data.as_array()
-                .and_then(|arr| arr.first())
-                .and_then(|obj| obj.get("embedding"))
-                .and_then(|emb| emb.as_array())
+        let embeddings: Vec<&serde_json::Value> = if let Some(data) = response_json.get("data") {
+            // OpenAI format: {"data": [{"index": 0, "embedding": [...]}, ...]}
+            let mut items: Vec<&serde_json::Value> = data.as_array()
                 .ok_or_else(|| RuvectorError::ModelInferenceError(
                     "Invalid OpenAI response format".to_string()
                 ))?
+                .iter()
+                .collect();
+            items.sort_by_key(|item| item.get("index").and_then(|i| i.as_u64()));
+            items.into_iter()
+                .map(|item| item.get("embedding").ok_or_else(|| RuvectorError::ModelInferenceError(
+                    "Invalid OpenAI response format".to_string()
+                )))
+                .collect::<Result<_>>()?
         } else if let Some(embeddings) = response_json.get("embeddings") {
-            // Cohere format: {"embeddings": [[...]]}
+            // Cohere format: {"embeddings": [[...], ...]}
             embeddings.as_array()
-                .and_then(|arr| arr.first())
-                .and_then(|emb| emb.as_array())
                 .ok_or_else(|| RuvectorError::ModelInferenceError(
                     "Invalid Cohere response format".to_string()
                 ))?
+                .iter()
+                .collect()
         } else {
             return Err(RuvectorError::ModelInferenceError(
                 "Unknown API response format".to_string()
             ));
         };
 
-        let embedding_vec: Result<Vec<f32>> = embedding
-            .iter()
-            .map(|v| v.as_f64()
-                .map(|f| f as f32)
-                .ok_or_else(|| RuvectorError::ModelInferenceError(
-                    "Invalid embedding value".to_string()
-                ))
-            )
-            .collect();
+        if embeddings.len() != texts.len() {
+            return Err(RuvectorError::ModelInferenceError(format!(
+                "API returned {} embeddings for {} texts",
+                embeddings.len(),
+                texts.len()
+            )));
+        }
 
-        embedding_vec
+        embeddings
+            .into_iter()
+            .map(|embedding| {
+                embedding.as_array()
+                    .ok_or_else(|| RuvectorError::ModelInferenceError(
+                        "Invalid embedding format".to_string()
+                    ))?
+                    .iter()
+                    .map(|v| v.as_f64()
+                        .map(|f| f as f32)
+                        .ok_or_else(|| RuvectorError::ModelInferenceError(
+                            "Invalid embedding value".to_string()
+                        ))
+                    )
+                    .collect()
+            })
+            .collect()
     }
 
     fn dimensions(&self) -> usize {
@@ -348,6 +388,73 @@ impl EmbeddingProvider for ApiEmbedding {
 /// Type-erased embedding provider for dynamic dispatch
 pub type BoxedEmbeddingProvider = Arc<dyn EmbeddingProvider>;
 
+/// Embedding provider choice that can be persisted with a database
+///
+/// API keys are never stored: API providers read theirs from an environment
+/// variable whenever the provider is built.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "provider", rename_all = "snake_case")]
+pub enum EmbeddingConfig {
+    /// Deterministic [`HashEmbedding`], for tests and prototyping
+    Hash {
+        /// Embedding dimensions
+        dimensions: usize,
+    },
+    /// `ApiEmbedding` against an OpenAI-compatible embeddings endpoint
+    Api {
+        /// Endpoint URL
+        endpoint: String,
+        /// Model identifier
+        model: String,
+        /// Dimensions of the model's embeddings
+        dimensions: usize,
+        /// Environment variable holding the API key
+        api_key_env: String,
+    },
+}
+
+impl EmbeddingConfig {
+    /// Dimensions of the configured provider's embeddings
+    pub fn dimensions(&self) -> usize {
+        match self {
+            EmbeddingConfig::Hash { dimensions } | EmbeddingConfig::Api { dimensions, .. } => {
+                *dimensions
+            }
+        }
+    }
+
+    /// Instantiate the configured provider
+    pub fn build(&self) -> Result<BoxedEmbeddingProvider> {
+        match self {
+            EmbeddingConfig::Hash { dimensions } => Ok(Arc::new(HashEmbedding::new(*dimensions))),
+            #[cfg(feature = "api-embeddings")]
+            EmbeddingConfig::Api {
+                endpoint,
+                model,
+                dimensions,
+                api_key_env,
+            } => {
+                let api_key = std::env::var(api_key_env).map_err(|_| {
+                    RuvectorError::ModelLoadError(format!(
+                        "Environment variable {} with the embedding API key is not set",
+                        api_key_env
+                    ))
+                })?;
+                Ok(Arc::new(ApiEmbedding::new(
+                    api_key,
+                    endpoint.clone(),
+                    model.clone(),
+                    *dimensions,
+                )))
+            }
+            #[cfg(not(feature = "api-embeddings"))]
+            EmbeddingConfig::Api { .. } => Err(RuvectorError::ModelLoadError(
+                "API embeddings require the api-embeddings feature".to_string(),
+            )),
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -377,6 +484,29 @@ mod tests {
         assert_ne!(emb1, emb2, "Different text should produce different embeddings");
     }
 
+    #[test]
+    fn test_embedding_config_round_trip_and_batch() {
+        let config = EmbeddingConfig::Hash { dimensions: 32 };
+        let json = serde_json::to_string(&config).unwrap();
+        assert_eq!(json, r#"{"provider":"hash","dimensions":32}"#);
+        let parsed: EmbeddingConfig = serde_json::from_str(&json).unwrap();
+        assert_eq!(parsed, config);
+
+        let provider = config.build().unwrap();
+        assert_eq!(provider.dimensions(), 32);
+        let batch = provider.embed_batch(&["hello", "world"]).unwrap();
+        assert_eq!(batch[0], provider.embed("hello").unwrap());
+        assert_eq!(batch[1], provider.embed("world").unwrap());
+
+        let missing_key = EmbeddingConfig::Api {
+            endpoint: "https://example.invalid/v1/embeddings".to_string(),
+            model: "model".to_string(),
+            dimensions: 8,
+            api_key_env: "RUVECTOR_TEST_UNSET_EMBEDDING_KEY".to_string(),
+        };
+        assert!(missing_key.build().is_err());
+    }
+
     #[cfg(feature = "real-embeddings")]
     #[test]
     #[ignore] // Requires model download
@@ -3,9 +3,148 @@
 pub mod flat;
 #[cfg(feature = "hnsw")]
 pub mod hnsw;
+pub mod multivector;
+pub mod sparse;
 
 use crate::error::Result;
 use crate::types::{DistanceMetric, SearchResult, VectorId};
+use std::collections::HashSet;
+
+/// Allowed fraction of indexed vectors below which a filtered search scans
+/// the allowed vectors exhaustively
+pub const BRUTE_FORCE_SELECTIVITY: f32 = 0.01;
+
+/// Number of allowed vectors up to which exhaustive scanning is always used
+pub const BRUTE_FORCE_MAX_CANDIDATES: usize = 1_000;
+
+/// Allowed fraction of indexed vectors from which an oversampled unfiltered
+/// search is tried before falling back to filtered traversal
+pub const POST_FILTER_SELECTIVITY: f32 = 0.5;
+
+/// Extra headroom applied on top of `k / selectivity` when post-filtering
+pub const POST_FILTER_OVERSAMPLING: f32 = 1.5;
+
+/// Number of neighbors fetched by the first round of a range search
+pub const RANGE_SEARCH_INITIAL_K: usize = 32;
+
+/// Execution strategy for a search restricted to a set of allowed ids
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FilteredSearchPlan {
+    /// Compute exact distances to every allowed vector
+    BruteForce,
+    /// Walk the graph normally but only admit allowed vectors into the results
+    FilteredTraversal,
+    /// Search unfiltered for an oversampled k, then drop disallowed hits
+    PostFilter,
+}
+
+impl FilteredSearchPlan {
+    /// Pick a plan from the number of allowed vectors and the index size
+    pub fn choose(allowed: usize, total: usize) -> Self {
+        if total == 0 || allowed <= BRUTE_FORCE_MAX_CANDIDATES {
+            return FilteredSearchPlan::BruteForce;
+        }
+
+        let selectivity = allowed as f32 / total as f32;
+        if selectivity < BRUTE_FORCE_SELECTIVITY {
+            FilteredSearchPlan::BruteForce
+        } else if selectivity >= POST_FILTER_SELECTIVITY {
+            FilteredSearchPlan::PostFilter
+        } else {
+            FilteredSearchPlan::FilteredTraversal
+        }
+    }
+}
+
+/// Passes each allowed id to the visitor it is called with
+pub type VisitIds<'a> = dyn Fn(&mut dyn FnMut(&str)) -> Result<()> + Sync + 'a;
+
+/// The ids a filtered search is restricted to
+///
+/// Ids produced by a visitor, such as the matches of a payload filter, are
+/// only read once the index is locked, so indexes can resolve them to their
+/// internal slots without copying them.
+#[derive(Clone, Copy)]
+pub enum AllowedIds<'a> {
+    /// An explicit set of ids
+    Set(&'a HashSet<VectorId>),
+    /// Ids passed on by a visitor, which may be called once per search
+    Visit(&'a VisitIds<'a>),
+}
+
+impl AllowedIds<'_> {
+    /// Call `visit` with every allowed id
+    pub fn for_each(&self, mut visit: impl FnMut(&str)) -> Result<()> {
+        match self {
+            AllowedIds::Set(ids) => {
+                ids.iter().for_each(|id| visit(id));
+                Ok(())
+            }
+            AllowedIds::Visit(ids) => ids(&mut visit),
+        }
+    }
+
+    /// Copy the allowed ids into a set
+    pub fn collect(&self) -> Result<HashSet<VectorId>> {
+        let mut ids = HashSet::new();
+        self.for_each(|id| {
+            ids.insert(id.to_string());
+        })?;
+        Ok(ids)
+    }
+}
+
+impl<'a> From<&'a HashSet<VectorId>> for AllowedIds<'a> {
+    fn from(ids: &'a HashSet<VectorId>) -> Self {
+        AllowedIds::Set(ids)
+    }
+}
+
+/// Per-query search parameters
+///
+/// Unset fields fall back to the index's configured defaults.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SearchParams {
+    /// Beam width for HNSW search; larger values trade speed for recall
+    pub ef_search: Option<usize>,
+}
+
+impl SearchParams {
+    /// Parameters overriding the HNSW beam width
+    pub fn with_ef_search(ef_search: usize) -> Self {
+        Self {
+            ef_search: Some(ef_search),
+        }
+    }
+}
+
+/// Range search on top of a top-k search
+///
+/// `search` is called with a growing k, doubling from
+/// [`RANGE_SEARCH_INITIAL_K`], until its farthest result lies outside
+/// `radius`, it returns fewer than k results, or k reaches `max_results`.
+/// Results within `radius` are returned nearest first, at most
+/// `max_results` of them.
+pub fn expanding_range_search<F>(
+    radius: f32,
+    max_results: usize,
+    mut search: F,
+) -> Result<Vec<SearchResult>>
+where
+    F: FnMut(usize) -> Result<Vec<SearchResult>>,
+{
+    let mut k = RANGE_SEARCH_INITIAL_K.min(max_results);
+    loop {
+        let mut results = search(k)?;
+        let exhausted = results.len() < k || k >= max_results;
+        if exhausted || results.last().is_some_and(|last| last.score > radius) {
+            results.retain(|result| result.score <= radius);
+            results.truncate(max_results);
+            return Ok(results);
+        }
+        k = k.saturating_mul(2).min(max_results);
+    }
+}
 
 /// Trait for vector index implementations
 pub trait VectorIndex: Send + Sync {
@@ -23,6 +162,129 @@ pub trait VectorIndex: Send + Sync {
     /// Search for k nearest neighbors
     fn search(&self, query: &[f32], k: usize) -> Result<Vec<SearchResult>>;
 
+    /// Search for k nearest neighbors with per-query parameters
+    ///
+    /// The default ignores `params` and calls [`VectorIndex::search`].
+    fn search_with_params(
+        &self,
+        query: &[f32],
+        k: usize,
+        _params: &SearchParams,
+    ) -> Result<Vec<SearchResult>> {
+        self.search(query, k)
+    }
+
+    /// Search among the ids in `allowed` with the index's default parameters
+    fn search_filtered(
+        &self,
+        query: &[f32],
+        k: usize,
+        allowed: &HashSet<VectorId>,
+    ) -> Result<Vec<SearchResult>> {
+        self.search_filtered_with_params(query, k, allowed, &SearchParams::default())
+    }
+
+    /// Search for the k nearest neighbors among the ids in `allowed`
+    ///
+    /// The allow-set is typically produced by evaluating a payload filter
+    /// before the index is searched. Implementations return `k` results
+    /// whenever at least `k` allowed vectors are indexed.
+    ///
+    /// The default searches the whole index and drops disallowed results.
+    fn search_filtered_with_params(
+        &self,
+        query: &[f32],
+        k: usize,
+        allowed: &HashSet<VectorId>,
+        params: &SearchParams,
+    ) -> Result<Vec<SearchResult>> {
+        if allowed.is_empty() {
+            return Ok(Vec::new());
+        }
+        let mut results = self.search_with_params(query, self.len(), params)?;
+        results.retain(|result| allowed.contains(&result.id));
+        results.truncate(k);
+        Ok(results)
+    }
+
+    /// Search for the k nearest neighbors among `allowed`
+    ///
+    /// Indexes that number their vectors resolve the ids to their internal
+    /// slots under the same lock as the search. The default copies them
+    /// into a set for [`VectorIndex::search_filtered_with_params`].
+    fn search_allowed(
+        &self,
+        query: &[f32],
+        k: usize,
+        allowed: AllowedIds<'_>,
+        params: &SearchParams,
+    ) -> Result<Vec<SearchResult>> {
+        match allowed {
+            AllowedIds::Set(ids) => self.search_filtered_with_params(query, k, ids, params),
+            AllowedIds::Visit(_) => {
+                self.search_filtered_with_params(query, k, &allowed.collect()?, params)
+            }
+        }
+    }
+
+    /// Search for every vector within distance `radius` of `query`, nearest
+    /// first, returning at most `max_results` of them
+    ///
+    /// With `allowed` set only those ids are considered. The default grows a
+    /// top-k search with [`expanding_range_search`].
+    fn search_range(
+        &self,
+        query: &[f32],
+        radius: f32,
+        max_results: usize,
+        allowed: Option<AllowedIds<'_>>,
+        params: &SearchParams,
+    ) -> Result<Vec<SearchResult>> {
+        // Visit the ids once rather than in every round
+        let collected = match allowed {
+            Some(ids @ AllowedIds::Visit(_)) => Some(ids.collect()?),
+            _ => None,
+        };
+        let allowed = collected.as_ref().map(AllowedIds::Set).or(allowed);
+        expanding_range_search(radius, max_results, |k| match allowed {
+            Some(allowed) => self.search_allowed(query, k, allowed, params),
+            None => self.search_with_params(query, k, params),
+        })
+    }
+
+    /// Vector indexed under `id`, or `None` if it is not indexed or the
+    /// index doesn't keep its vectors
+    ///
+    /// Quantized indexes return the decoded, approximate vector.
+    fn get_vector(&self, _id: &str) -> Option<Vec<f32>> {
+        None
+    }
+
+    /// Search for the k nearest neighbors of the vector indexed under `id`,
+    /// excluding `id` itself, among `allowed` if set
+    ///
+    /// Returns `None` if [`VectorIndex::get_vector`] has no vector for `id`.
+    fn search_by_id(
+        &self,
+        id: &str,
+        k: usize,
+        allowed: Option<AllowedIds<'_>>,
+        params: &SearchParams,
+    ) -> Result<Option<Vec<SearchResult>>> {
+        let Some(vector) = self.get_vector(id) else {
+            return Ok(None);
+        };
+
+        let candidates = k.saturating_add(1);
+        let mut results = match allowed {
+            Some(allowed) => self.search_allowed(&vector, candidates, allowed, params)?,
+            None => self.search_with_params(&vector, candidates, params)?,
+        };
+        results.retain(|result| result.id != id);
+        results.truncate(k);
+        Ok(Some(results))
+    }
+
     /// Remove a vector from the index
     fn remove(&mut self, id: &VectorId) -> Result<bool>;
 
@@ -33,4 +295,80 @@ pub trait VectorIndex: Send + Sync {
     fn is_empty(&self) -> bool {
         self.len() == 0
     }
+
+    /// Serialize the index so it can be restored without rebuilding, or
+    /// `None` for indexes that are cheap to rebuild from storage
+    fn snapshot(&self) -> Option<Result<Vec<u8>>> {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// An index implementing only the required methods
+    struct ListIndex(Vec<(VectorId, f32)>);
+
+    impl VectorIndex for ListIndex {
+        fn add(&mut self, id: VectorId, vector: Vec<f32>) -> Result<()> {
+            self.0.push((id, vector[0]));
+            Ok(())
+        }
+
+        fn search(&self, query: &[f32], k: usize) -> Result<Vec<SearchResult>> {
+            let mut results: Vec<_> = self
+                .0
+                .iter()
+                .map(|(id, value)| SearchResult {
+                    id: id.clone(),
+                    score: (value - query[0]).abs(),
+                    vector: None,
+                    metadata: None,
+                })
+                .collect();
+            results.sort_by(|a, b| a.score.total_cmp(&b.score));
+            results.truncate(k);
+            Ok(results)
+        }
+
+        fn remove(&mut self, id: &VectorId) -> Result<bool> {
+            let len = self.0.len();
+            self.0.retain(|(other, _)| other != id);
+            Ok(self.0.len() < len)
+        }
+
+        fn len(&self) -> usize {
+            self.0.len()
+        }
+    }
+
+    #[test]
+    fn test_default_methods_delegate_to_search() {
+        let mut index = ListIndex(Vec::new());
+        for i in 0..10 {
+            index.add(format!("v{}", i), vec![i as f32]).unwrap();
+        }
+
+        let params = SearchParams::with_ef_search(4);
+        let ids = |results: Vec<SearchResult>| -> Vec<String> {
+            results.into_iter().map(|result| result.id).collect()
+        };
+        assert_eq!(
+            ids(index.search_with_params(&[0.0], 2, &params).unwrap()),
+            vec!["v0", "v1"]
+        );
+
+        let allowed: HashSet<VectorId> = ["v9", "v5", "v7"].map(String::from).into();
+        assert_eq!(
+            ids(index.search_filtered(&[0.0], 2, &allowed).unwrap()),
+            vec!["v5", "v7"]
+        );
+        assert_eq!(
+            ids(index
+                .search_range(&[0.0], 7.5, 10, Some((&allowed).into()), &params)
+                .unwrap()),
+            vec!["v5", "v7"]
+        );
+    }
 }
@@ -2,9 +2,10 @@
 
 use crate::distance::distance;
 use crate::error::Result;
-use crate::index::VectorIndex;
+use crate::index::{AllowedIds, SearchParams, VectorIndex};
 use crate::types::{DistanceMetric, SearchResult, VectorId};
 use dashmap::DashMap;
+use std::collections::HashSet;
 
 #[cfg(all(feature = "parallel", not(target_arch = "wasm32")))]
 use rayon::prelude::*;
@@ -25,6 +26,31 @@ impl FlatIndex {
             dimensions,
         }
     }
+
+    /// Distances from `query` to the allowed vectors that are indexed
+    fn allowed_distances(
+        &self,
+        query: &[f32],
+        allowed: AllowedIds<'_>,
+    ) -> Result<Vec<(VectorId, f32)>> {
+        let mut results = Vec::new();
+        let mut error = None;
+        allowed.for_each(|id| {
+            if error.is_some() {
+                return;
+            }
+            if let Some(vector) = self.vectors.get(id) {
+                match distance(query, &vector, self.metric) {
+                    Ok(dist) => results.push((id.to_string(), dist)),
+                    Err(e) => error = Some(e),
+                }
+            }
+        })?;
+        match error {
+            Some(e) => Err(e),
+            None => Ok(results),
+        }
+    }
 }
 
 impl VectorIndex for FlatIndex {
@@ -75,6 +101,83 @@ impl VectorIndex for FlatIndex {
             .collect())
     }
 
+    fn search_filtered_with_params(
+        &self,
+        query: &[f32],
+        k: usize,
+        allowed: &HashSet<VectorId>,
+        params: &SearchParams,
+    ) -> Result<Vec<SearchResult>> {
+        self.search_allowed(query, k, AllowedIds::Set(allowed), params)
+    }
+
+    fn search_allowed(
+        &self,
+        query: &[f32],
+        k: usize,
+        allowed: AllowedIds<'_>,
+        _params: &SearchParams,
+    ) -> Result<Vec<SearchResult>> {
+        let mut results = self.allowed_distances(query, allowed)?;
+
+        results.sort_by(|a, b| a.1.partial_cmp(&b.1).unwrap());
+        results.truncate(k);
+
+        Ok(results
+            .into_iter()
+            .map(|(id, score)| SearchResult {
+                id,
+                score,
+                vector: None,
+                metadata: None,
+            })
+            .collect())
+    }
+
+    fn search_range(
+        &self,
+        query: &[f32],
+        radius: f32,
+        max_results: usize,
+        allowed: Option<AllowedIds<'_>>,
+        _params: &SearchParams,
+    ) -> Result<Vec<SearchResult>> {
+        let mut results = match allowed {
+            Some(allowed) => {
+                let mut results = self.allowed_distances(query, allowed)?;
+                results.retain(|(_, dist)| *dist <= radius);
+                results
+            }
+            None => {
+                let mut results = Vec::new();
+                for entry in self.vectors.iter() {
+                    let dist = distance(query, entry.value(), self.metric)?;
+                    if dist <= radius {
+                        results.push((entry.key().clone(), dist));
+                    }
+                }
+                results
+            }
+        };
+
+        results.sort_by(|a, b| a.1.total_cmp(&b.1));
+        results.truncate(max_results);
+
+        Ok(results
+            .into_iter()
+            .map(|(id, score)| SearchResult {
+                id,
+                score,
+                vector: None,
+                metadata: None,
+            })
+            .collect())
+    }
+
+    fn get_vector(&self, id: &str) -> Option<Vec<f32>> {
+        self.vectors.get(id).map(|vector| vector.clone())
+    }
+
     fn remove(&mut self, id: &VectorId) -> Result<bool> {
         Ok(self.vectors.remove(id).is_some())
     }
@@ -105,4 +208,56 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn test_flat_index_filtered() -> Result<()> {
+        let mut index = FlatIndex::new(3, DistanceMetric::Euclidean);
+
+        index.add("v1".to_string(), vec![1.0, 0.0, 0.0])?;
+        index.add("v2".to_string(), vec![0.9, 0.1, 0.0])?;
+        index.add("v3".to_string(), vec![0.0, 0.0, 1.0])?;
+
+        let allowed: HashSet<VectorId> = ["v2".to_string(), "v3".to_string()].into();
+        let results = index.search_filtered(&[1.0, 0.0, 0.0], 2, &allowed)?;
+
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0].id, "v2");
+        assert_eq!(results[1].id, "v3");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_flat_index_range_and_by_id() -> Result<()> {
+        let mut index = FlatIndex::new(2, DistanceMetric::Euclidean);
+        for i in 0..10 {
+            index.add(format!("v{}", i), vec![i as f32, 0.0])?;
+        }
+
+        let params = SearchParams::default();
+        let results = index.search_range(&[0.0, 0.0], 2.5, 10, None, &params)?;
+        let ids: Vec<&str> = results.iter().map(|r| r.id.as_str()).collect();
+        assert_eq!(ids, ["v0", "v1", "v2"]);
+
+        let results = index.search_range(&[0.0, 0.0], 100.0, 4, None, &params)?;
+        assert_eq!(results.len(), 4);
+
+        let allowed: HashSet<VectorId> = ["v2".to_string(), "v7".to_string()].into();
+        let results = index.search_range(&[0.0, 0.0], 2.5, 10, Some((&allowed).into()), &params)?;
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].id, "v2");
+
+        let results = index.search_by_id("v5", 2, None, &params)?.unwrap();
+        assert!(results.iter().all(|r| r.id != "v5"));
+        assert!(results.iter().all(|r| (r.score - 1.0).abs() < 1e-6));
+        assert!(index.search_by_id("missing", 2, None, &params)?.is_none());
+
+        let results = index
+            .search_by_id("v2", 2, Some((&allowed).into()), &params)?
+            .unwrap();
+        let ids: Vec<&str> = results.iter().map(|r| r.id.as_str()).collect();
+        assert_eq!(ids, ["v7"]);
+
+        Ok(())
+    }
 }
@@ -1,31 +1,32 @@
 //! HNSW (Hierarchical Navigable Small World) index implementation
+//!
+//! Deletes tombstone the node and repair the neighbor lists that pointed at
+//! it, so removed vectors never take result slots. Tombstoned slots are
+//! reclaimed by rebuilding the graph once their share crosses the configured
+//! compaction threshold; the rebuild runs on a background thread while the
+//! index keeps serving reads and writes.
+//!
+//! With [`HnswIndex::with_quantization`] vectors are held in compressed form
+//! and the graph is built and searched with asymmetric distances between the
+//! f32 query and the decoded codes.
+
+mod graph;
+mod store;
 
-use crate::distance::distance;
 use crate::error::{Result, RuvectorError};
-use crate::index::VectorIndex;
-use crate::types::{DistanceMetric, HnswConfig, SearchResult, VectorId};
+use crate::index::{
+    expanding_range_search, AllowedIds, FilteredSearchPlan, SearchParams, VectorIndex,
+    POST_FILTER_OVERSAMPLING,
+};
+use crate::types::{DistanceMetric, HnswConfig, QuantizationConfig, SearchResult, VectorId};
 use bincode::{Decode, Encode};
 use dashmap::DashMap;
-use hnsw_rs::prelude::*;
-use parking_lot::RwLock;
+use graph::{Candidate, GraphState, HnswGraph, NodeSet};
+use parking_lot::{Mutex, RwLock, RwLockUpgradableReadGuard};
+use std::collections::HashSet;
 use std::sync::Arc;
-
-/// Distance function wrapper for hnsw_rs
-struct DistanceFn {
-    metric: DistanceMetric,
-}
-
-impl DistanceFn {
-    fn new(metric: DistanceMetric) -> Self {
-        Self { metric }
-    }
-}
-
-impl Distance<f32> for DistanceFn {
-    fn eval(&self, a: &[f32], b: &[f32]) -> f32 {
-        distance(a, b, self.metric).unwrap_or(f32::MAX)
-    }
-}
+use std::thread::JoinHandle;
+use store::{Scorer, VectorStore};
 
 /// HNSW index wrapper
 pub struct HnswIndex {
@@ -33,23 +34,132 @@ pub struct HnswIndex {
     config: HnswConfig,
     metric: DistanceMetric,
     dimensions: usize,
+    compaction: Mutex<Option<JoinHandle<()>>>,
 }
 
 struct HnswInner {
-    hnsw: Hnsw<'static, f32, DistanceFn>,
-    vectors: DashMap<VectorId, Vec<f32>>,
+    graph: HnswGraph,
+    /// Vectors by internal idx; tombstoned slots keep theirs until the next
+    /// compaction, though no live node links to them
+    vectors: VectorStore,
     id_to_idx: DashMap<VectorId, usize>,
     idx_to_id: DashMap<usize, VectorId>,
-    next_idx: usize,
+    /// Writes recorded while a background compaction is rebuilding the
+    /// graph; a mutex so the compaction can start recording under the read
+    /// lock it copies the live entries under
+    pending: Mutex<Option<Vec<PendingOp>>>,
+}
+
+/// A write replayed onto a freshly compacted graph
+enum PendingOp {
+    Add(VectorId, Vec<f32>),
+    Remove(VectorId),
+}
+
+impl HnswInner {
+    fn new(config: &HnswConfig, vectors: VectorStore) -> Self {
+        Self {
+            graph: HnswGraph::new(config.m, config.ef_construction, config.seed),
+            vectors,
+            id_to_idx: DashMap::new(),
+            idx_to_id: DashMap::new(),
+            pending: Mutex::new(None),
+        }
+    }
+
+    /// Build a compact graph over `vectors`, whose idx `i` belongs to `ids[i]`
+    fn build(
+        config: &HnswConfig,
+        metric: DistanceMetric,
+        ids: Vec<VectorId>,
+        vectors: VectorStore,
+    ) -> Self {
+        let mut inner = Self::new(config, vectors);
+        {
+            let scorer = Scorer::new(&inner.vectors, metric);
+            for idx in 0..ids.len() {
+                inner.graph.insert(idx, |a, b| scorer.between(a, b));
+            }
+        }
+        for (idx, id) in ids.into_iter().enumerate() {
+            inner.id_to_idx.insert(id.clone(), idx);
+            inner.idx_to_id.insert(idx, id);
+        }
+        inner
+    }
+
+    /// Add `vector` under `id`, retiring the node of an earlier vector with
+    /// the same id; returns whether one was replaced
+    fn insert(&mut self, id: VectorId, vector: Vec<f32>, metric: DistanceMetric) -> bool {
+        let replaced = self.remove(&id, metric);
+
+        let idx = self.vectors.len();
+        self.vectors.push(&vector);
+        let scorer = Scorer::new(&self.vectors, metric);
+        self.graph.insert(idx, |a, b| scorer.between(a, b));
+
+        if let Some(ops) = self.pending.get_mut() {
+            ops.push(PendingOp::Add(id.clone(), vector));
+        }
+        self.id_to_idx.insert(id.clone(), idx);
+        self.idx_to_id.insert(idx, id);
+        replaced
+    }
+
+    fn remove(&mut self, id: &VectorId, metric: DistanceMetric) -> bool {
+        let Some((_, idx)) = self.id_to_idx.remove(id) else {
+            return false;
+        };
+        if let Some(ops) = self.pending.get_mut() {
+            ops.push(PendingOp::Remove(id.clone()));
+        }
+
+        self.idx_to_id.remove(&idx);
+        let scorer = Scorer::new(&self.vectors, metric);
+        self.graph.mark_deleted(idx, |a, b| scorer.between(a, b));
+        true
+    }
+
+    fn apply(&mut self, op: PendingOp, metric: DistanceMetric) {
+        match op {
+            PendingOp::Add(id, vector) => {
+                self.insert(id, vector, metric);
+            }
+            PendingOp::Remove(id) => {
+                self.remove(&id, metric);
+            }
+        }
+    }
+
+    /// Ids and encoded vectors of the live entries, ordered by internal idx
+    fn live_entries(&self) -> (Vec<VectorId>, VectorStore) {
+        let mut ids = Vec::with_capacity(self.id_to_idx.len());
+        let mut vectors = self.vectors.empty_like();
+        for idx in 0..self.vectors.len() {
+            if let Some(id) = self.idx_to_id.get(&idx) {
+                ids.push(id.clone());
+                vectors.push_from(&self.vectors, idx);
+            }
+        }
+        (ids, vectors)
+    }
+
+    fn tombstone_ratio(&self) -> f32 {
+        let slots = self.graph.slots();
+        if slots == 0 {
+            0.0
+        } else {
+            self.graph.num_deleted() as f32 / slots as f32
+        }
+    }
 }
 
 /// Serializable HNSW index state
 #[derive(Encode, Decode, Clone)]
 pub struct HnswState {
-    vectors: Vec<(String, Vec<f32>)>,
-    id_to_idx: Vec<(String, usize)>,
+    vectors: VectorStore,
     idx_to_id: Vec<(usize, String)>,
-    next_idx: usize,
+    graph: GraphState,
     config: SerializableHnswConfig,
     dimensions: usize,
     metric: SerializableDistanceMetric,
@@ -61,6 +171,8 @@ struct SerializableHnswConfig {
     ef_construction: usize,
     ef_search: usize,
     max_elements: usize,
+    compaction_threshold: f32,
+    seed: Option<u64>,
 }
 
 #[derive(Encode, Decode, Clone, Copy)]
@@ -96,40 +208,138 @@ impl From<SerializableDistanceMetric> for DistanceMetric {
 impl HnswIndex {
     /// Create a new HNSW index
     pub fn new(dimensions: usize, metric: DistanceMetric, config: HnswConfig) -> Result<Self> {
-        let distance_fn = DistanceFn::new(metric);
-
-        // Create HNSW with configured parameters
-        let hnsw = Hnsw::<f32, DistanceFn>::new(
-            config.m,
-            config.max_elements,
-            dimensions,
-            config.ef_construction,
-            distance_fn,
-        );
-
         Ok(Self {
-            inner: Arc::new(RwLock::new(HnswInner {
-                hnsw,
-                vectors: DashMap::new(),
-                id_to_idx: DashMap::new(),
-                idx_to_id: DashMap::new(),
-                next_idx: 0,
-            })),
+            inner: Arc::new(RwLock::new(HnswInner::new(
+                &config,
+                VectorStore::new(dimensions, None)?,
+            ))),
             config,
             metric,
             dimensions,
+            compaction: Mutex::new(None),
         })
     }
 
+    /// Set the share of tombstoned slots (0.0-1.0) that triggers a background
+    /// compaction, overriding [`HnswConfig::compaction_threshold`]. A
+    /// threshold of 1.0 or more disables automatic compaction.
+    pub fn with_compaction_threshold(mut self, threshold: f32) -> Self {
+        self.config.compaction_threshold = threshold;
+        self
+    }
+
+    /// Keep vectors compressed with `quantization` instead of as full f32
+    ///
+    /// Must be applied before any vector is added. Product quantization
+    /// holds vectors at full precision until enough have been added to train
+    /// its codebooks.
+    pub fn with_quantization(self, quantization: &QuantizationConfig) -> Result<Self> {
+        {
+            let mut inner = self.inner.write();
+            if inner.vectors.len() > 0 {
+                return Err(RuvectorError::InvalidParameter(
+                    "Quantization must be configured before vectors are added".to_string(),
+                ));
+            }
+            inner.vectors = VectorStore::new(self.dimensions, Some(quantization))?;
+        }
+        Ok(self)
+    }
+
+    /// Whether search distances are computed against quantized vectors
+    pub fn is_quantized(&self) -> bool {
+        self.inner.read().vectors.is_quantized()
+    }
+
+    /// Bytes held by the in-memory vector representation
+    pub fn vector_memory_bytes(&self) -> usize {
+        self.inner.read().vectors.memory_bytes()
+    }
+
     /// Get configuration
     pub fn config(&self) -> &HnswConfig {
         &self.config
     }
 
-    /// Set efSearch parameter for query-time accuracy tuning
-    pub fn set_ef_search(&mut self, _ef_search: usize) {
-        // Note: hnsw_rs controls ef_search via the search method's knbn parameter
-        // We store it in config and use it in search_with_ef
+    /// Set the default efSearch used by queries that don't override it
+    pub fn set_ef_search(&mut self, ef_search: usize) {
+        self.config.ef_search = ef_search;
+    }
+
+    fn ef_search(&self, params: &SearchParams) -> usize {
+        params.ef_search.unwrap_or(self.config.ef_search)
+    }
+
+    /// Number of tombstoned slots waiting to be reclaimed
+    pub fn deleted_count(&self) -> usize {
+        self.inner.read().graph.num_deleted()
+    }
+
+    /// Share of graph slots that are tombstoned
+    pub fn tombstone_ratio(&self) -> f32 {
+        self.inner.read().tombstone_ratio()
+    }
+
+    /// Rebuild the graph without tombstoned slots, blocking until done
+    ///
+    /// Searches keep running against the old graph during the rebuild.
+    /// Returns the number of slots reclaimed.
+    pub fn compact(&self) -> Result<usize> {
+        self.wait_for_compaction();
+
+        let inner = self.inner.upgradable_read();
+        let reclaimed = inner.graph.num_deleted();
+        if reclaimed > 0 {
+            let (ids, vectors) = inner.live_entries();
+            let rebuilt = HnswInner::build(&self.config, self.metric, ids, vectors);
+            *RwLockUpgradableReadGuard::upgrade(inner) = rebuilt;
+        }
+        Ok(reclaimed)
+    }
+
+    /// Block until a running background compaction has finished
+    pub fn wait_for_compaction(&self) {
+        if let Some(handle) = self.compaction.lock().take() {
+            if handle.join().is_err() {
+                tracing::error!("HNSW background compaction panicked");
+            }
+        }
+    }
+
+    /// Start a background compaction if the tombstone ratio crossed the threshold
+    fn maybe_compact(&self) {
+        let mut slot = self.compaction.lock();
+        if slot.as_ref().is_some_and(|handle| !handle.is_finished()) {
+            return;
+        }
+
+        if self.inner.read().tombstone_ratio() < self.config.compaction_threshold {
+            return;
+        }
+        if let Some(finished) = slot.take() {
+            let _ = finished.join();
+        }
+
+        let shared = Arc::clone(&self.inner);
+        let config = self.config.clone();
+        let metric = self.metric;
+        *slot = Some(std::thread::spawn(move || {
+            // Copied under a read lock so searches keep running; writes made
+            // after the copy are recorded and replayed onto the new graph
+            let (ids, vectors) = {
+                let inner = shared.read();
+                *inner.pending.lock() = Some(Vec::new());
+                inner.live_entries()
+            };
+            tracing::debug!("Compacting HNSW graph with {} live vectors", ids.len());
+            let mut rebuilt = HnswInner::build(&config, metric, ids, vectors);
+
+            let mut inner = shared.write();
+            for op in inner.pending.get_mut().take().unwrap_or_default() {
+                rebuilt.apply(op, metric);
+            }
+            *inner = rebuilt;
+        }));
     }
 
     /// Serialize the index to bytes using bincode
@@ -137,27 +347,20 @@ impl HnswIndex {
         let inner = self.inner.read();
 
         let state = HnswState {
-            vectors: inner
-                .vectors
-                .iter()
-                .map(|entry| (entry.key().clone(), entry.value().clone()))
-                .collect(),
-            id_to_idx: inner
-                .id_to_idx
-                .iter()
-                .map(|entry| (entry.key().clone(), *entry.value()))
-                .collect(),
+            vectors: inner.vectors.clone(),
             idx_to_id: inner
                 .idx_to_id
                 .iter()
                 .map(|entry| (*entry.key(), entry.value().clone()))
                 .collect(),
-            next_idx: inner.next_idx,
+            graph: inner.graph.to_state(),
             config: SerializableHnswConfig {
                 m: self.config.m,
                 ef_construction: self.config.ef_construction,
                 ef_search: self.config.ef_search,
                 max_elements: self.config.max_elements,
+                compaction_threshold: self.config.compaction_threshold,
+                seed: self.config.seed,
             },
             dimensions: self.dimensions,
             metric: self.metric.into(),
@@ -169,6 +372,8 @@ impl HnswIndex {
     }
 
     /// Deserialize the index from bytes using bincode
+    ///
+    /// The graph is restored as stored, without re-inserting vectors.
     pub fn deserialize(bytes: &[u8]) -> Result<Self> {
         let (state, _): (HnswState, usize) =
             bincode::decode_from_slice(bytes, bincode::config::standard()).map_err(|e| {
@@ -183,47 +388,29 @@ impl HnswIndex {
             ef_construction: state.config.ef_construction,
             ef_search: state.config.ef_search,
             max_elements: state.config.max_elements,
+            compaction_threshold: state.config.compaction_threshold,
+            seed: state.config.seed,
         };
 
-        let dimensions = state.dimensions;
-        let metric: DistanceMetric = state.metric.into();
-
-        let distance_fn = DistanceFn::new(metric);
-        let mut hnsw = Hnsw::<'static, f32, DistanceFn>::new(
-            config.m,
-            config.max_elements,
-            dimensions,
-            config.ef_construction,
-            distance_fn,
-        );
-
-        // Rebuild the index by inserting all vectors
-        let id_to_idx: DashMap<VectorId, usize> = state.id_to_idx.into_iter().collect();
+        let id_to_idx: DashMap<VectorId, usize> = state
+            .idx_to_id
+            .iter()
+            .map(|(idx, id)| (id.clone(), *idx))
+            .collect();
         let idx_to_id: DashMap<usize, VectorId> = state.idx_to_id.into_iter().collect();
 
-        // Insert vectors into HNSW in order
-        for entry in idx_to_id.iter() {
-            let idx = *entry.key();
-            let id = entry.value();
-            if let Some(vector) = state.vectors.iter().find(|(vid, _)| vid == id) {
-                // Use insert_data method with slice and idx
-                hnsw.insert_data(&vector.1, idx);
-            }
-        }
-
-        let vectors_map: DashMap<VectorId, Vec<f32>> = state.vectors.into_iter().collect();
-
         Ok(Self {
             inner: Arc::new(RwLock::new(HnswInner {
-                hnsw,
-                vectors: vectors_map,
+                graph: HnswGraph::from_state(state.graph, config.seed),
+                vectors: state.vectors,
                 id_to_idx,
                 idx_to_id,
-                next_idx: state.next_idx,
+                pending: Mutex::new(None),
             })),
             config,
-            metric,
-            dimensions,
+            metric: state.metric.into(),
+            dimensions: state.dimensions,
+            compaction: Mutex::new(None),
         })
     }
 
@@ -234,29 +421,158 @@ impl HnswIndex {
         k: usize,
         ef_search: usize,
     ) -> Result<Vec<SearchResult>> {
+        self.check_query(query)?;
+        Ok(self.search_locked(&self.inner.read(), query, k, ef_search))
+    }
+
+    /// Search among the ids in `allowed` with a custom efSearch parameter
+    ///
+    /// The execution plan is chosen from the share of indexed vectors the
+    /// allow-set admits, see [`FilteredSearchPlan::choose`].
+    pub fn search_filtered_with_ef(
+        &self,
+        query: &[f32],
+        k: usize,
+        ef_search: usize,
+        allowed: &HashSet<VectorId>,
+    ) -> Result<Vec<SearchResult>> {
+        self.search_allowed_with_ef(query, k, ef_search, AllowedIds::Set(allowed))
+    }
+
+    /// Search among `allowed` with a custom efSearch parameter, resolving
+    /// the ids to graph slots under the same read lock as the search
+    pub fn search_allowed_with_ef(
+        &self,
+        query: &[f32],
+        k: usize,
+        ef_search: usize,
+        allowed: AllowedIds<'_>,
+    ) -> Result<Vec<SearchResult>> {
+        self.check_query(query)?;
+        let inner = self.inner.read();
+        let (allowed_idx, allowed_count) = Self::resolve_allowed(&inner, allowed)?;
+        Ok(self.search_filtered_locked(&inner, query, k, ef_search, &allowed_idx, allowed_count))
+    }
+
+    fn search_locked(
+        &self,
+        inner: &HnswInner,
+        query: &[f32],
+        k: usize,
+        ef_search: usize,
+    ) -> Vec<SearchResult> {
+        let scorer = Scorer::new(&inner.vectors, self.metric);
+        let neighbors = inner
+            .graph
+            .search(&|idx| scorer.query(query, idx), k, ef_search, &|_| true);
+
+        Self::to_results(inner, neighbors)
+    }
+
+    /// Slots of the indexed vectors among `allowed`, and their number
+    ///
+    /// Only valid while `inner` stays locked, since compaction renumbers
+    /// the slots.
+    fn resolve_allowed(inner: &HnswInner, allowed: AllowedIds<'_>) -> Result<(NodeSet, usize)> {
+        let mut allowed_idx = NodeSet::new(inner.graph.slots());
+        let mut allowed_count = 0;
+        allowed.for_each(|id| {
+            if let Some(idx) = inner.id_to_idx.get(id) {
+                if allowed_idx.insert(*idx) {
+                    allowed_count += 1;
+                }
+            }
+        })?;
+        Ok((allowed_idx, allowed_count))
+    }
+
+    fn search_filtered_locked(
+        &self,
+        inner: &HnswInner,
+        query: &[f32],
+        k: usize,
+        ef_search: usize,
+        allowed_idx: &NodeSet,
+        allowed_count: usize,
+    ) -> Vec<SearchResult> {
+        if allowed_count == 0 || k == 0 {
+            return Vec::new();
+        }
+
+        let scorer = Scorer::new(&inner.vectors, self.metric);
+        let query_distance = |idx: usize| scorer.query(query, idx);
+        let total = inner.id_to_idx.len();
+
+        let mut plan = FilteredSearchPlan::choose(allowed_count, total);
+        tracing::trace!(
+            "Filtered search over {}/{} vectors using {:?}",
+            allowed_count,
+            total,
+            plan
+        );
+
+        if plan == FilteredSearchPlan::PostFilter {
+            let selectivity = allowed_count as f32 / total as f32;
+            let oversampled = ((k as f32 / selectivity) * POST_FILTER_OVERSAMPLING).ceil() as usize;
+            let mut neighbors = inner.graph.search(
+                &query_distance,
+                oversampled,
+                ef_search.max(oversampled),
+                &|_| true,
+            );
+            neighbors.retain(|n| allowed_idx.contains(n.idx));
+
+            if neighbors.len() >= k.min(allowed_count) {
+                neighbors.truncate(k);
+                return Self::to_results(inner, neighbors);
+            }
+            // Too few allowed hits survived; walk the graph with the filter instead
+            plan = FilteredSearchPlan::FilteredTraversal;
+        }
+
+        let neighbors = match plan {
+            FilteredSearchPlan::BruteForce => {
+                let mut scored: Vec<Candidate> = allowed_idx
+                    .iter()
+                    .map(|idx| Candidate {
+                        dist: query_distance(idx),
+                        idx,
+                    })
+                    .collect();
+                scored.sort_unstable();
+                scored.truncate(k);
+                scored
+            }
+            _ => inner.graph.search(&query_distance, k, ef_search, &|idx| {
+                allowed_idx.contains(idx)
+            }),
+        };
+
+        Self::to_results(inner, neighbors)
+    }
+
+    fn check_query(&self, query: &[f32]) -> Result<()> {
         if query.len() != self.dimensions {
             return Err(RuvectorError::DimensionMismatch {
                 expected: self.dimensions,
                 actual: query.len(),
             });
         }
+        Ok(())
+    }
 
-        let inner = self.inner.read();
-
-        // Use HNSW search with custom ef parameter (knbn)
-        let neighbors = inner.hnsw.search(query, k, ef_search);
-
-        Ok(neighbors
+    fn to_results(inner: &HnswInner, neighbors: Vec<Candidate>) -> Vec<SearchResult> {
+        neighbors
             .into_iter()
             .filter_map(|neighbor| {
-                inner.idx_to_id.get(&neighbor.d_id).map(|id| SearchResult {
+                inner.idx_to_id.get(&neighbor.idx).map(|id| SearchResult {
                     id: id.clone(),
-                    score: neighbor.distance,
+                    score: neighbor.dist,
                     vector: None,
                     metadata: None,
                 })
             })
-            .collect())
+            .collect()
     }
 }
 
@@ -269,18 +585,10 @@ impl VectorIndex for HnswIndex {
             });
         }
 
-        let mut inner = self.inner.write();
-        let idx = inner.next_idx;
-        inner.next_idx += 1;
-
-        // Insert into HNSW graph using insert_data
-        inner.hnsw.insert_data(&vector, idx);
-
-        // Store mappings
-        inner.vectors.insert(id.clone(), vector);
-        inner.id_to_idx.insert(id.clone(), idx);
-        inner.idx_to_id.insert(idx, id);
-
+        let replaced = self.inner.write().insert(id, vector, self.metric);
+        if replaced {
+            self.maybe_compact();
+        }
         Ok(())
     }
 
@@ -295,65 +603,105 @@ impl VectorIndex for HnswIndex {
             }
         }
 
-        let mut inner = self.inner.write();
-
-        // Prepare batch data for parallel insertion
-        use rayon::prelude::*;
-
-        // First, assign indices and collect vector data
-        let data_with_ids: Vec<_> = entries
-            .iter()
-            .enumerate()
-            .map(|(i, (id, vector))| {
-                let idx = inner.next_idx + i;
-                (id.clone(), idx, vector.clone())
-            })
-            .collect();
-
-        // Update next_idx
-        inner.next_idx += entries.len();
-
-        // Insert into HNSW sequentially
-        // Note: Using sequential insertion to avoid Send requirements with RwLock guard
-        // For large batches, consider restructuring to use hnsw_rs parallel_insert
-        for (_id, idx, vector) in &data_with_ids {
-            inner.hnsw.insert_data(vector, *idx);
+        let mut replaced = false;
+        {
+            let mut inner = self.inner.write();
+            for (id, vector) in entries {
+                replaced |= inner.insert(id, vector, self.metric);
+            }
         }
-
-        // Store mappings
-        for (id, idx, vector) in data_with_ids {
-            inner.vectors.insert(id.clone(), vector);
-            inner.id_to_idx.insert(id.clone(), idx);
-            inner.idx_to_id.insert(idx, id);
+        if replaced {
+            self.maybe_compact();
         }
 
         Ok(())
     }
 
     fn search(&self, query: &[f32], k: usize) -> Result<Vec<SearchResult>> {
-        // Use configured ef_search
-        self.search_with_ef(query, k, self.config.ef_search)
+        self.search_with_params(query, k, &SearchParams::default())
     }
 
-    fn remove(&mut self, id: &VectorId) -> Result<bool> {
-        let mut inner = self.inner.write();
+    fn search_with_params(
+        &self,
+        query: &[f32],
+        k: usize,
+        params: &SearchParams,
+    ) -> Result<Vec<SearchResult>> {
+        self.search_with_ef(query, k, self.ef_search(params))
+    }
 
-        // Note: hnsw_rs doesn't support direct deletion
-        // We remove from our mappings but the graph structure remains
-        // This is a known limitation of HNSW
-        let removed = inner.vectors.remove(id).is_some();
+    fn search_filtered_with_params(
+        &self,
+        query: &[f32],
+        k: usize,
+        allowed: &HashSet<VectorId>,
+        params: &SearchParams,
+    ) -> Result<Vec<SearchResult>> {
+        self.search_filtered_with_ef(query, k, self.ef_search(params), allowed)
+    }
 
+    fn search_allowed(
+        &self,
+        query: &[f32],
+        k: usize,
+        allowed: AllowedIds<'_>,
+        params: &SearchParams,
+    ) -> Result<Vec<SearchResult>> {
+        self.search_allowed_with_ef(query, k, self.ef_search(params), allowed)
+    }
+
+    // Every round runs under one read lock, so concurrent writes can't make
+    // the growing result sets disagree
+    fn search_range(
+        &self,
+        query: &[f32],
+        radius: f32,
+        max_results: usize,
+        allowed: Option<AllowedIds<'_>>,
+        params: &SearchParams,
+    ) -> Result<Vec<SearchResult>> {
+        self.check_query(query)?;
+
+        let inner = self.inner.read();
+        let ef_search = self.ef_search(params);
+        let allowed = allowed
+            .map(|allowed| Self::resolve_allowed(&inner, allowed))
+            .transpose()?;
+        expanding_range_search(radius, max_results, |k| {
+            Ok(match &allowed {
+                Some((allowed_idx, allowed_count)) => self.search_filtered_locked(
+                    &inner,
+                    query,
+                    k,
+                    ef_search,
+                    allowed_idx,
+                    *allowed_count,
+                ),
+                None => self.search_locked(&inner, query, k, ef_search),
+            })
+        })
+    }
+
+    fn get_vector(&self, id: &str) -> Option<Vec<f32>> {
+        let inner = self.inner.read();
+        let idx = *inner.id_to_idx.get(id)?;
+        Some(inner.vectors.get(idx))
+    }
+
+    fn remove(&mut self, id: &VectorId) -> Result<bool> {
+        let removed = self.inner.write().remove(id, self.metric);
         if removed {
-            if let Some((_, idx)) = inner.id_to_idx.remove(id) {
-                inner.idx_to_id.remove(&idx);
-            }
+            self.maybe_compact();
         }
-
         Ok(removed)
     }
 
     fn len(&self) -> usize {
-        self.inner.read().vectors.len()
+        self.inner.read().id_to_idx.len()
+    }
+
+    fn snapshot(&self) -> Option<Result<Vec<u8>>> {
+        Some(self.serialize())
     }
 }
 
@@ -394,6 +742,7 @@ mod tests {
             ef_construction: 100,
             ef_search: 50,
             max_elements: 1000,
+            ..HnswConfig::default()
         };
 
         let mut index = HnswIndex::new(128, DistanceMetric::Cosine, config)?;
@@ -442,6 +791,8 @@ mod tests {
             ef_construction: 100,
             ef_search: 50,
             max_elements: 1000,
+            compaction_threshold: 0.5,
+            seed: Some(5),
         };
 
         let mut index = HnswIndex::new(128, DistanceMetric::Cosine, config)?;
@@ -460,6 +811,8 @@ mod tests {
         let restored_index = HnswIndex::deserialize(&bytes)?;
 
         assert_eq!(restored_index.len(), 50);
+        assert_eq!(restored_index.config.compaction_threshold, 0.5);
+        assert_eq!(restored_index.config.seed, Some(5));
 
         // Test search on restored index
         let query = normalize_vector(&vectors[0]);
@@ -480,4 +833,280 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn test_remove_keeps_k_results() -> Result<()> {
+        let config = HnswConfig {
+            m: 16,
+            ef_construction: 100,
+            ef_search: 50,
+            max_elements: 1000,
+            ..HnswConfig::default()
+        };
+        let mut index =
+            HnswIndex::new(32, DistanceMetric::Euclidean, config)?.with_compaction_threshold(1.0);
+
+        let vectors = generate_random_vectors(200, 32);
+        for (i, vector) in vectors.iter().enumerate() {
+            index.add(format!("vec_{}", i), vector.clone())?;
+        }
+
+        for i in 0..100 {
+            assert!(index.remove(&format!("vec_{}", i))?);
+        }
+        assert!(!index.remove(&"vec_0".to_string())?);
+        assert_eq!(index.len(), 100);
+        assert_eq!(index.deleted_count(), 100);
+
+        let results = index.search(&vectors[0], 10)?;
+        assert_eq!(results.len(), 10);
+        for result in &results {
+            let n: usize = result.id["vec_".len()..].parse().unwrap();
+            assert!(n >= 100, "deleted vector {} returned", result.id);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_readding_id_replaces_its_node() -> Result<()> {
+        let mut index = HnswIndex::new(16, DistanceMetric::Euclidean, HnswConfig::default())?
+            .with_compaction_threshold(1.0);
+
+        let vectors = generate_random_vectors(51, 16);
+        for (i, vector) in vectors.iter().take(50).enumerate() {
+            index.add(format!("vec_{}", i), vector.clone())?;
+        }
+        index.add("vec_0".to_string(), vectors[50].clone())?;
+        index.add_batch(vec![("vec_1".to_string(), vectors[50].clone())])?;
+
+        assert_eq!(index.len(), 50);
+        assert_eq!(index.deleted_count(), 2);
+
+        let results = index.search(&vectors[0], 50)?;
+        assert_eq!(results.len(), 50);
+        let ids: HashSet<_> = results.iter().map(|r| r.id.as_str()).collect();
+        assert_eq!(ids.len(), 50);
+
+        let results = index.search(&vectors[50], 2)?;
+        let mut ids: Vec<_> = results.iter().map(|r| r.id.as_str()).collect();
+        ids.sort();
+        assert_eq!(ids, ["vec_0", "vec_1"]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_compaction_reclaims_slots() -> Result<()> {
+        let mut index = HnswIndex::new(16, DistanceMetric::Euclidean, HnswConfig::default())?
+            .with_compaction_threshold(1.0);
+
+        let vectors = generate_random_vectors(100, 16);
+        for (i, vector) in vectors.iter().enumerate() {
+            index.add(format!("vec_{}", i), vector.clone())?;
+        }
+        for i in 0..40 {
+            index.remove(&format!("vec_{}", i))?;
+        }
+        assert!((index.tombstone_ratio() - 0.4).abs() < 1e-6);
+
+        assert_eq!(index.compact()?, 40);
+        assert_eq!(index.deleted_count(), 0);
+        assert_eq!(index.len(), 60);
+
+        let results = index.search(&vectors[50], 1)?;
+        assert_eq!(results[0].id, "vec_50");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_background_compaction_replays_concurrent_writes() -> Result<()> {
+        let config = HnswConfig {
+            compaction_threshold: 0.1,
+            ..HnswConfig::default()
+        };
+        let mut index = HnswIndex::new(16, DistanceMetric::Euclidean, config)?;
+
+        let vectors = generate_random_vectors(120, 16);
+        for (i, vector) in vectors.iter().take(100).enumerate() {
+            index.add(format!("vec_{}", i), vector.clone())?;
+        }
+        for i in 0..20 {
+            index.remove(&format!("vec_{}", i))?;
+        }
+        // Writes issued while the rebuild may still be running
+        for (i, vector) in vectors.iter().enumerate().skip(100) {
+            index.add(format!("vec_{}", i), vector.clone())?;
+        }
+        index.remove(&"vec_50".to_string())?;
+        index.wait_for_compaction();
+
+        assert_eq!(index.len(), 99);
+        assert!(index.tombstone_ratio() < 0.1);
+        let results = index.search(&vectors[110], 1)?;
+        assert_eq!(results[0].id, "vec_110");
+        let results = index.search(&vectors[50], 5)?;
+        assert!(results.iter().all(|r| r.id != "vec_50"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_quantized_index_saves_memory_and_finds_neighbors() -> Result<()> {
+        let config = HnswConfig {
+            m: 8,
+            ef_construction: 64,
+            ef_search: 64,
+            max_elements: 2000,
+            ..HnswConfig::default()
+        };
+        let vectors: Vec<Vec<f32>> = generate_random_vectors(1100, 64)
+            .iter()
+            .map(|v| normalize_vector(v))
+            .collect();
+
+        let configs = [
+            (QuantizationConfig::Scalar, 3),
+            (
+                QuantizationConfig::Product {
+                    subspaces: 8,
+                    k: 16,
+                },
+                8,
+            ),
+        ];
+        for (quantization, min_ratio) in configs {
+            let mut index = HnswIndex::new(64, DistanceMetric::Cosine, config.clone())?
+                .with_quantization(&quantization)?;
+            for (i, vector) in vectors.iter().enumerate() {
+                index.add(format!("vec_{}", i), vector.clone())?;
+            }
+
+            assert!(index.is_quantized());
+            let full_bytes = vectors.len() * 64 * std::mem::size_of::<f32>();
+            assert!(
+                index.vector_memory_bytes() * min_ratio < full_bytes,
+                "{:?} uses {} bytes",
+                quantization,
+                index.vector_memory_bytes()
+            );
+
+            // The exact vector must rank among the closest approximate hits
+            for i in [0, 700, 1099] {
+                let results = index.search(&vectors[i], 10)?;
+                let target = format!("vec_{}", i);
+                assert!(
+                    results.iter().any(|r| r.id == target),
+                    "{:?} lost {}",
+                    quantization,
+                    target
+                );
+            }
+        }
+
+        let index = HnswIndex::new(64, DistanceMetric::Cosine, HnswConfig::default())?;
+        assert!(!index.is_quantized());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_filtered_search_returns_k_for_every_plan() -> Result<()> {
+        let config = HnswConfig {
+            m: 8,
+            ef_construction: 64,
+            ef_search: 32,
+            max_elements: 4000,
+            ..HnswConfig::default()
+        };
+        let mut index = HnswIndex::new(16, DistanceMetric::Euclidean, config)?;
+
+        let vectors = generate_random_vectors(3000, 16);
+        for (i, vector) in vectors.iter().enumerate() {
+            index.add(format!("vec_{}", i), vector.clone())?;
+        }
+
+        let cases = [
+            (10, FilteredSearchPlan::BruteForce),
+            (1200, FilteredSearchPlan::FilteredTraversal),
+            (2000, FilteredSearchPlan::PostFilter),
+        ];
+        for (count, expected_plan) in cases {
+            assert_eq!(FilteredSearchPlan::choose(count, 3000), expected_plan);
+
+            // Allow every third vector so allowed points are spread over the graph
+            let allowed: HashSet<VectorId> = (0..3000)
+                .rev()
+                .step_by(3000 / count)
+                .take(count)
+                .map(|i| format!("vec_{}", i))
+                .collect();
+
+            let results = index.search_filtered(&vectors[1], 10, &allowed)?;
+            assert_eq!(
+                results.len(),
+                10,
+                "{:?} returned too few results",
+                expected_plan
+            );
+            assert!(results.iter().all(|r| allowed.contains(&r.id)));
+
+            // Visited ids, duplicates included, resolve to the same slots
+            let visit = |visit: &mut dyn FnMut(&str)| {
+                allowed.iter().chain(&allowed).for_each(|id| visit(id));
+                Ok(())
+            };
+            let visited = index.search_allowed(
+                &vectors[1],
+                10,
+                AllowedIds::Visit(&visit),
+                &SearchParams::default(),
+            )?;
+            assert_eq!(
+                visited.iter().map(|r| &r.id).collect::<Vec<_>>(),
+                results.iter().map(|r| &r.id).collect::<Vec<_>>()
+            );
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_range_search_grows_past_initial_k() -> Result<()> {
+        let mut index = HnswIndex::new(2, DistanceMetric::Euclidean, HnswConfig::default())?;
+
+        // 100 points on a line, 1.0 apart
+        for i in 0..100 {
+            index.add(format!("vec_{}", i), vec![i as f32, 0.0])?;
+        }
+
+        let params = SearchParams::default();
+        let results = index.search_range(&[0.0, 0.0], 79.5, 1000, None, &params)?;
+        assert_eq!(results.len(), 80);
+        assert!(results.windows(2).all(|w| w[0].score <= w[1].score));
+        assert!(results.iter().all(|r| r.score <= 79.5));
+
+        let results = index.search_range(&[0.0, 0.0], 79.5, 50, None, &params)?;
+        assert_eq!(results.len(), 50);
+        assert_eq!(results[49].id, "vec_49");
+
+        let allowed: HashSet<VectorId> =
+            (0..100).step_by(10).map(|i| format!("vec_{}", i)).collect();
+        let results =
+            index.search_range(&[0.0, 0.0], 35.0, 1000, Some((&allowed).into()), &params)?;
+        let ids: Vec<&str> = results.iter().map(|r| r.id.as_str()).collect();
+        assert_eq!(ids, ["vec_0", "vec_10", "vec_20", "vec_30"]);
+
+        assert_eq!(index.get_vector("vec_7"), Some(vec![7.0, 0.0]));
+        let results = index.search_by_id("vec_7", 2, None, &params)?.unwrap();
+        let mut ids: Vec<&str> = results.iter().map(|r| r.id.as_str()).collect();
+        ids.sort();
+        assert_eq!(ids, ["vec_6", "vec_8"]);
+
+        index.remove(&"vec_7".to_string())?;
+        assert!(index.search_by_id("vec_7", 2, None, &params)?.is_none());
+
+        Ok(())
+    }
 }
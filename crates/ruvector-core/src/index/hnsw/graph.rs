@@ -0,0 +1,655 @@
+//! Layered proximity graph backing [`HnswIndex`](super::HnswIndex)
+//!
+//! The graph only owns adjacency lists and tombstones. Vectors stay with the
+//! owning index and are reached through distance closures, which keeps the
+//! traversal independent of how vectors are represented in memory.
+
+use bincode::{Decode, Encode};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::cell::RefCell;
+use std::cmp::{Ordering, Reverse};
+use std::collections::{BinaryHeap, HashSet};
+
+/// Upper bound on the number of layers a node can be assigned to
+const MAX_LEVEL: usize = 16;
+
+/// A node reached during traversal together with its distance to the query
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct Candidate {
+    pub dist: f32,
+    pub idx: usize,
+}
+
+impl Eq for Candidate {}
+
+impl PartialOrd for Candidate {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Candidate {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.dist
+            .total_cmp(&other.dist)
+            .then_with(|| self.idx.cmp(&other.idx))
+    }
+}
+
+#[derive(Debug, Clone, Encode, Decode)]
+struct Node {
+    level: usize,
+    /// Neighbor lists, one per layer from 0 to `level`
+    neighbors: Vec<Vec<u32>>,
+}
+
+/// Serializable graph state
+#[derive(Debug, Clone, Encode, Decode)]
+pub(crate) struct GraphState {
+    m: usize,
+    ef_construction: usize,
+    nodes: Vec<Node>,
+    deleted: Vec<bool>,
+    entry_point: Option<usize>,
+}
+
+/// Bitset over internal node indices
+pub(crate) struct NodeSet {
+    words: Vec<u64>,
+}
+
+impl NodeSet {
+    /// Create an empty set able to hold indices below `len`
+    pub fn new(len: usize) -> Self {
+        Self {
+            words: vec![0; len.div_ceil(64)],
+        }
+    }
+
+    /// Add `idx`, returning `false` if it was already present
+    pub fn insert(&mut self, idx: usize) -> bool {
+        let (word, bit) = (idx / 64, 1u64 << (idx % 64));
+        let fresh = self.words[word] & bit == 0;
+        self.words[word] |= bit;
+        fresh
+    }
+
+    /// Whether `idx` is in the set
+    pub fn contains(&self, idx: usize) -> bool {
+        self.words
+            .get(idx / 64)
+            .is_some_and(|word| word & (1u64 << (idx % 64)) != 0)
+    }
+
+    /// Indices in the set, in ascending order
+    pub fn iter(&self) -> impl Iterator<Item = usize> + '_ {
+        self.words.iter().enumerate().flat_map(|(i, &word)| {
+            let mut rest = word;
+            std::iter::from_fn(move || {
+                if rest == 0 {
+                    return None;
+                }
+                let bit = rest.trailing_zeros() as usize;
+                rest &= rest - 1;
+                Some(i * 64 + bit)
+            })
+        })
+    }
+}
+
+/// Visited set of a layer search, reset in constant time by starting a new
+/// epoch instead of clearing the marks
+#[derive(Default)]
+struct VisitedMarks {
+    marks: Vec<u32>,
+    epoch: u32,
+}
+
+impl VisitedMarks {
+    /// Forget every visit and make room for indices below `len`
+    fn reset(&mut self, len: usize) {
+        if self.marks.len() < len {
+            self.marks.resize(len, 0);
+        }
+        self.epoch = self.epoch.wrapping_add(1);
+        if self.epoch == 0 {
+            self.marks.fill(0);
+            self.epoch = 1;
+        }
+    }
+
+    /// Mark `idx` as visited, returning `false` if it already was
+    fn insert(&mut self, idx: usize) -> bool {
+        let fresh = self.marks[idx] != self.epoch;
+        self.marks[idx] = self.epoch;
+        fresh
+    }
+}
+
+thread_local! {
+    /// Visited marks shared by the layer searches of one thread, so batches
+    /// of queries don't allocate a visited set per query
+    static VISITED: RefCell<VisitedMarks> = RefCell::new(VisitedMarks::default());
+}
+
+/// HNSW graph over dense internal indices
+///
+/// Deleted nodes are tombstoned and unlinked: every live node that linked to
+/// one has its list rebuilt from its remaining neighbors and the deleted
+/// node's, so searches never reach a tombstone. Their slots are reclaimed
+/// when the owning index compacts the graph.
+pub(crate) struct HnswGraph {
+    m: usize,
+    ef_construction: usize,
+    level_mult: f64,
+    nodes: Vec<Node>,
+    /// Nodes linking to each node, per layer, so a delete can repair every
+    /// list pointing at the deleted node
+    linked_from: Vec<Vec<Vec<u32>>>,
+    deleted: Vec<bool>,
+    num_deleted: usize,
+    entry_point: Option<usize>,
+    rng: StdRng,
+}
+
+impl HnswGraph {
+    /// Create an empty graph with `m` links per node on upper layers,
+    /// assigning node levels from `seed`, or from entropy if `None`
+    pub fn new(m: usize, ef_construction: usize, seed: Option<u64>) -> Self {
+        let m = m.max(2);
+        Self {
+            m,
+            ef_construction: ef_construction.max(1),
+            level_mult: 1.0 / (m as f64).ln(),
+            nodes: Vec::new(),
+            linked_from: Vec::new(),
+            deleted: Vec::new(),
+            num_deleted: 0,
+            entry_point: None,
+            rng: seed.map_or_else(StdRng::from_entropy, StdRng::seed_from_u64),
+        }
+    }
+
+    /// Number of slots, including tombstoned ones
+    pub fn slots(&self) -> usize {
+        self.nodes.len()
+    }
+
+    /// Number of tombstoned slots
+    pub fn num_deleted(&self) -> usize {
+        self.num_deleted
+    }
+
+    /// Whether the node at `idx` has been tombstoned
+    pub fn is_deleted(&self, idx: usize) -> bool {
+        self.deleted.get(idx).copied().unwrap_or(true)
+    }
+
+    fn max_connections(&self, layer: usize) -> usize {
+        if layer == 0 {
+            2 * self.m
+        } else {
+            self.m
+        }
+    }
+
+    fn random_level(&mut self) -> usize {
+        let r: f64 = self.rng.gen_range(f64::MIN_POSITIVE..1.0);
+        ((-r.ln() * self.level_mult) as usize).min(MAX_LEVEL)
+    }
+
+    /// Insert the node `idx`, which must be the next free slot
+    ///
+    /// `dist(a, b)` returns the distance between two stored nodes.
+    pub fn insert<D>(&mut self, idx: usize, dist: D)
+    where
+        D: Fn(usize, usize) -> f32,
+    {
+        debug_assert_eq!(idx, self.nodes.len(), "graph slots must be dense");
+
+        let level = self.random_level();
+        self.nodes.push(Node {
+            level,
+            neighbors: vec![Vec::new(); level + 1],
+        });
+        self.linked_from.push(vec![Vec::new(); level + 1]);
+        self.deleted.push(false);
+
+        let Some(entry) = self.entry_point else {
+            self.entry_point = Some(idx);
+            return;
+        };
+
+        let query = |other: usize| dist(idx, other);
+        let top = self.nodes[entry].level;
+        let mut current = Candidate {
+            dist: query(entry),
+            idx: entry,
+        };
+        for layer in (level + 1..=top).rev() {
+            current = self.greedy_closest(&query, current, layer);
+        }
+
+        let mut entry_points = vec![current];
+        for layer in (0..=level.min(top)).rev() {
+            let deleted = &self.deleted;
+            let found = self.search_layer(
+                &query,
+                &entry_points,
+                self.ef_construction,
+                layer,
+                &|i: usize| !deleted[i],
+            );
+            let selected = self.select_neighbors(&found, self.max_connections(layer), &dist);
+            for &neighbor in &selected {
+                self.link(neighbor as usize, idx, layer, &dist);
+            }
+            self.set_neighbors(idx, layer, selected);
+            if !found.is_empty() {
+                entry_points = found;
+            }
+        }
+
+        if level > top {
+            self.entry_point = Some(idx);
+        }
+    }
+
+    /// Search for the `k` closest accepted nodes using a beam of width `ef`
+    ///
+    /// `query(idx)` returns the distance from the query to a stored node and
+    /// `accept(idx)` decides whether a node may appear in the results.
+    /// Deleted nodes are unlinked when they are tombstoned, so the traversal
+    /// never reaches them.
+    pub fn search<Q, F>(&self, query: &Q, k: usize, ef: usize, accept: &F) -> Vec<Candidate>
+    where
+        Q: Fn(usize) -> f32,
+        F: Fn(usize) -> bool,
+    {
+        let Some(entry) = self.entry_point else {
+            return Vec::new();
+        };
+        if k == 0 {
+            return Vec::new();
+        }
+
+        let mut current = Candidate {
+            dist: query(entry),
+            idx: entry,
+        };
+        for layer in (1..=self.nodes[entry].level).rev() {
+            current = self.greedy_closest(query, current, layer);
+        }
+
+        let accept_live = |i: usize| !self.is_deleted(i) && accept(i);
+        let mut found = self.search_layer(query, &[current], ef.max(k), 0, &accept_live);
+        found.truncate(k);
+        found
+    }
+
+    fn greedy_closest<Q>(&self, query: &Q, mut current: Candidate, layer: usize) -> Candidate
+    where
+        Q: Fn(usize) -> f32,
+    {
+        loop {
+            let mut improved = false;
+            for &neighbor in self.neighbors(current.idx, layer) {
+                let d = query(neighbor as usize);
+                if d < current.dist {
+                    current = Candidate {
+                        dist: d,
+                        idx: neighbor as usize,
+                    };
+                    improved = true;
+                }
+            }
+            if !improved {
+                return current;
+            }
+        }
+    }
+
+    fn neighbors(&self, idx: usize, layer: usize) -> &[u32] {
+        self.nodes[idx]
+            .neighbors
+            .get(layer)
+            .map(Vec::as_slice)
+            .unwrap_or(&[])
+    }
+
+    /// Beam search on a single layer, returning accepted nodes sorted by distance
+    fn search_layer<Q, F>(
+        &self,
+        query: &Q,
+        entry_points: &[Candidate],
+        ef: usize,
+        layer: usize,
+        accept: &F,
+    ) -> Vec<Candidate>
+    where
+        Q: Fn(usize) -> f32,
+        F: Fn(usize) -> bool,
+    {
+        VISITED.with(|visited| {
+            let mut visited = visited.borrow_mut();
+            visited.reset(self.nodes.len());
+            self.search_layer_with(query, entry_points, ef, layer, accept, &mut visited)
+        })
+    }
+
+    fn search_layer_with<Q, F>(
+        &self,
+        query: &Q,
+        entry_points: &[Candidate],
+        ef: usize,
+        layer: usize,
+        accept: &F,
+        visited: &mut VisitedMarks,
+    ) -> Vec<Candidate>
+    where
+        Q: Fn(usize) -> f32,
+        F: Fn(usize) -> bool,
+    {
+        let mut candidates = BinaryHeap::new();
+        let mut results: BinaryHeap<Candidate> = BinaryHeap::with_capacity(ef + 1);
+
+        for &ep in entry_points {
+            if visited.insert(ep.idx) {
+                candidates.push(Reverse(ep));
+                if accept(ep.idx) {
+                    results.push(ep);
+                }
+            }
+        }
+        while results.len() > ef {
+            results.pop();
+        }
+
+        while let Some(Reverse(closest)) = candidates.pop() {
+            if results.len() >= ef {
+                if let Some(worst) = results.peek() {
+                    if closest.dist > worst.dist {
+                        break;
+                    }
+                }
+            }
+
+            for &neighbor in self.neighbors(closest.idx, layer) {
+                let neighbor = neighbor as usize;
+                if !visited.insert(neighbor) {
+                    continue;
+                }
+                let d = query(neighbor);
+                let worth_visiting =
+                    results.len() < ef || results.peek().map_or(true, |worst| d < worst.dist);
+                if worth_visiting {
+                    let candidate = Candidate {
+                        dist: d,
+                        idx: neighbor,
+                    };
+                    candidates.push(Reverse(candidate));
+                    if accept(neighbor) {
+                        results.push(candidate);
+                        if results.len() > ef {
+                            results.pop();
+                        }
+                    }
+                }
+            }
+        }
+
+        results.into_sorted_vec()
+    }
+
+    /// Neighbor selection heuristic from the HNSW paper, back-filled with the
+    /// closest pruned candidates so small graphs stay well connected
+    fn select_neighbors<D>(&self, sorted: &[Candidate], max: usize, dist: &D) -> Vec<u32>
+    where
+        D: Fn(usize, usize) -> f32,
+    {
+        let mut selected: Vec<Candidate> = Vec::with_capacity(max);
+        let mut pruned = Vec::new();
+
+        for &candidate in sorted {
+            if selected.len() >= max {
+                break;
+            }
+            let diverse = selected
+                .iter()
+                .all(|s| dist(candidate.idx, s.idx) > candidate.dist);
+            if diverse {
+                selected.push(candidate);
+            } else {
+                pruned.push(candidate);
+            }
+        }
+        for candidate in pruned {
+            if selected.len() >= max {
+                break;
+            }
+            selected.push(candidate);
+        }
+
+        selected.into_iter().map(|c| c.idx as u32).collect()
+    }
+
+    /// Add a directed edge `from -> to`, shrinking `from`'s list if it overflows
+    fn link<D>(&mut self, from: usize, to: usize, layer: usize, dist: &D)
+    where
+        D: Fn(usize, usize) -> f32,
+    {
+        let max = self.max_connections(layer);
+        let list = &self.nodes[from].neighbors[layer];
+        if list.contains(&(to as u32)) {
+            return;
+        }
+        if list.len() < max {
+            self.nodes[from].neighbors[layer].push(to as u32);
+            self.linked_from[to][layer].push(from as u32);
+            return;
+        }
+
+        let mut candidates: Vec<Candidate> = list
+            .iter()
+            .chain(std::iter::once(&(to as u32)))
+            .map(|&n| Candidate {
+                dist: dist(from, n as usize),
+                idx: n as usize,
+            })
+            .collect();
+        candidates.sort_unstable();
+        let shrunk = self.select_neighbors(&candidates, max, dist);
+        self.set_neighbors(from, layer, shrunk);
+    }
+
+    /// Replace `idx`'s neighbor list on `layer`, keeping `linked_from` in step
+    fn set_neighbors(&mut self, idx: usize, layer: usize, list: Vec<u32>) {
+        let old = std::mem::replace(&mut self.nodes[idx].neighbors[layer], list);
+        let new = &self.nodes[idx].neighbors[layer];
+        for &n in &old {
+            if !new.contains(&n) {
+                self.linked_from[n as usize][layer].retain(|&from| from as usize != idx);
+            }
+        }
+        for &n in new {
+            if !old.contains(&n) {
+                self.linked_from[n as usize][layer].push(idx as u32);
+            }
+        }
+    }
+
+    /// Tombstone `idx` and repair the neighbor lists that pointed at it
+    ///
+    /// Every live node that linked to the deleted node gets its list rebuilt
+    /// from its remaining neighbors plus the deleted node's neighbors, so the
+    /// region stays connected and live nodes never keep an edge to a
+    /// tombstone.
+    /// Returns `false` if the node was already deleted.
+    pub fn mark_deleted<D>(&mut self, idx: usize, dist: D) -> bool
+    where
+        D: Fn(usize, usize) -> f32,
+    {
+        if idx >= self.nodes.len() || self.deleted[idx] {
+            return false;
+        }
+        self.deleted[idx] = true;
+        self.num_deleted += 1;
+
+        for layer in 0..=self.nodes[idx].level {
+            let orphaned = self.nodes[idx].neighbors[layer].clone();
+            let linking = self.linked_from[idx][layer].clone();
+            for &neighbor in &linking {
+                let neighbor = neighbor as usize;
+                if self.deleted[neighbor] {
+                    continue;
+                }
+
+                let mut pool: HashSet<usize> = self
+                    .neighbors(neighbor, layer)
+                    .iter()
+                    .chain(orphaned.iter())
+                    .map(|&n| n as usize)
+                    .collect();
+                pool.remove(&neighbor);
+                pool.remove(&idx);
+
+                let mut candidates: Vec<Candidate> = pool
+                    .into_iter()
+                    .filter(|&n| !self.deleted[n])
+                    .map(|n| Candidate {
+                        dist: dist(neighbor, n),
+                        idx: n,
+                    })
+                    .collect();
+                candidates.sort_unstable();
+                let repaired =
+                    self.select_neighbors(&candidates, self.max_connections(layer), &dist);
+                self.set_neighbors(neighbor, layer, repaired);
+            }
+        }
+
+        if self.entry_point == Some(idx) {
+            self.entry_point = (0..self.nodes.len())
+                .filter(|&i| !self.deleted[i])
+                .max_by_key(|&i| self.nodes[i].level);
+        }
+
+        true
+    }
+
+    /// Snapshot the graph for serialization
+    pub fn to_state(&self) -> GraphState {
+        GraphState {
+            m: self.m,
+            ef_construction: self.ef_construction,
+            nodes: self.nodes.clone(),
+            deleted: self.deleted.clone(),
+            entry_point: self.entry_point,
+        }
+    }
+
+    /// Restore a graph from serialized state without re-inserting nodes,
+    /// assigning the levels of later nodes from `seed`
+    pub fn from_state(state: GraphState, seed: Option<u64>) -> Self {
+        let mut graph = Self::new(state.m, state.ef_construction, seed);
+        graph.num_deleted = state.deleted.iter().filter(|d| **d).count();
+        graph.linked_from = state
+            .nodes
+            .iter()
+            .map(|node| vec![Vec::new(); node.level + 1])
+            .collect();
+        for (idx, node) in state.nodes.iter().enumerate() {
+            for (layer, list) in node.neighbors.iter().enumerate() {
+                for &n in list {
+                    if let Some(from) = graph.linked_from[n as usize].get_mut(layer) {
+                        from.push(idx as u32);
+                    }
+                }
+            }
+        }
+        graph.nodes = state.nodes;
+        graph.deleted = state.deleted;
+        graph.entry_point = state.entry_point;
+        graph
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn line_points(n: usize) -> Vec<f32> {
+        (0..n).map(|i| i as f32).collect()
+    }
+
+    #[test]
+    fn test_search_returns_closest() {
+        let points = line_points(200);
+        let dist = |a: usize, b: usize| (points[a] - points[b]).abs();
+        let mut graph = HnswGraph::new(8, 64, Some(7));
+        for i in 0..points.len() {
+            graph.insert(i, dist);
+        }
+
+        let query = |i: usize| (points[i] - 42.2).abs();
+        let found = graph.search(&query, 3, 32, &|_| true);
+        let ids: Vec<usize> = found.iter().map(|c| c.idx).collect();
+        assert_eq!(ids, vec![42, 43, 41]);
+    }
+
+    #[test]
+    fn test_deleted_nodes_are_skipped_and_repaired() {
+        let points = line_points(100);
+        let dist = |a: usize, b: usize| (points[a] - points[b]).abs();
+        let mut graph = HnswGraph::new(4, 32, Some(7));
+        for i in 0..points.len() {
+            graph.insert(i, dist);
+        }
+
+        for i in 40..60 {
+            assert!(graph.mark_deleted(i, dist));
+        }
+        assert!(!graph.mark_deleted(45, dist));
+        assert_eq!(graph.num_deleted(), 20);
+
+        // No live node keeps an edge to a deleted node, including nodes the
+        // deleted node didn't link back to
+        for (idx, node) in graph.nodes.iter().enumerate() {
+            if graph.is_deleted(idx) {
+                continue;
+            }
+            for (layer, list) in node.neighbors.iter().enumerate() {
+                for &n in list {
+                    assert!(!graph.is_deleted(n as usize));
+                    assert!(graph.linked_from[n as usize][layer].contains(&(idx as u32)));
+                }
+            }
+        }
+
+        let query = |i: usize| (points[i] - 50.0).abs();
+        let found = graph.search(&query, 10, 32, &|_| true);
+        assert_eq!(found.len(), 10);
+        assert!(found.iter().all(|c| !(40..60).contains(&c.idx)));
+    }
+
+    #[test]
+    fn test_seed_reproduces_the_graph() {
+        let points = line_points(300);
+        let dist = |a: usize, b: usize| (points[a] - points[b]).abs();
+        let build = || {
+            let mut graph = HnswGraph::new(4, 32, Some(3));
+            for i in 0..points.len() {
+                graph.insert(i, dist);
+            }
+            graph
+        };
+
+        let (a, b) = (build(), build());
+        assert_eq!(a.entry_point, b.entry_point);
+        for (x, y) in a.nodes.iter().zip(&b.nodes) {
+            assert_eq!(x.level, y.level);
+            assert_eq!(x.neighbors, y.neighbors);
+        }
+    }
+}
@@ -0,0 +1,666 @@
+//! In-memory vector representation for [`HnswIndex`](super::HnswIndex)
+//!
+//! Vectors live in flat per-encoding buffers, either at full precision or
+//! compressed according to the configured [`QuantizationConfig`]. Distances
+//! are asymmetric: the query stays f32 and is compared against the decoded
+//! stored vector, so only the indexed side carries quantization error.
+//! Scalar codes are compared directly in the compressed domain while the
+//! graph is built, since both sides are stored vectors there.
+
+use crate::distance::distance;
+use crate::error::{Result, RuvectorError};
+use crate::quantization::{BinaryQuantized, ProductQuantized, QuantizedVector, ScalarQuantized};
+use crate::types::{DistanceMetric, QuantizationConfig};
+use bincode::{Decode, Encode};
+use std::cell::RefCell;
+
+/// Number of vectors kept at full precision before product quantization
+/// codebooks are trained on them
+pub(crate) const PRODUCT_TRAINING_SIZE: usize = 1024;
+
+/// k-means iterations used when training product quantization codebooks
+const PRODUCT_TRAINING_ITERATIONS: usize = 10;
+
+/// Vectors by internal idx in the configured encoding
+#[derive(Debug, Clone, Encode, Decode)]
+pub(crate) struct VectorStore {
+    dimensions: usize,
+    len: usize,
+    encoding: Encoding,
+}
+
+#[derive(Debug, Clone, Encode, Decode)]
+enum Encoding {
+    Full {
+        data: Vec<f32>,
+    },
+    Scalar {
+        /// Codes centred on zero so they can be compared as signed integers
+        codes: Vec<i8>,
+        rows: Vec<ScalarRow>,
+    },
+    Binary {
+        bits: Vec<u8>,
+    },
+    Product {
+        subspaces: usize,
+        centroids: usize,
+        /// Centroids laid out as `[subspace][centroid][subspace dimension]`,
+        /// empty until trained
+        codebooks: Vec<f32>,
+        codes: Vec<u8>,
+        /// Full-precision vectors collected while the codebooks are untrained
+        untrained: Vec<f32>,
+    },
+}
+
+/// Dequantization parameters of one scalar-quantized vector, which decodes
+/// to `offset + scale * code` per dimension
+#[derive(Debug, Clone, Copy, Encode, Decode)]
+struct ScalarRow {
+    offset: f32,
+    scale: f32,
+    /// Sum of the codes
+    sum: i32,
+    /// Sum of the squared codes, which fits in an i32 below 131072 dimensions
+    sum_sq: i32,
+}
+
+impl ScalarRow {
+    fn new(min: f32, scale: f32, codes: &[i8]) -> Self {
+        Self {
+            offset: min + 128.0 * scale,
+            scale,
+            sum: codes.iter().map(|&c| c as i32).sum(),
+            sum_sq: codes.iter().map(|&c| c as i32 * c as i32).sum(),
+        }
+    }
+
+    /// Squared norm of the decoded vector
+    fn norm_sq(&self, dimensions: f64) -> f64 {
+        let (offset, scale) = (self.offset as f64, self.scale as f64);
+        dimensions * offset * offset
+            + 2.0 * offset * scale * self.sum as f64
+            + scale * scale * self.sum_sq as f64
+    }
+}
+
+impl VectorStore {
+    /// Create an empty store using `quantization`, or full precision for `None`
+    pub fn new(dimensions: usize, quantization: Option<&QuantizationConfig>) -> Result<Self> {
+        let encoding = match quantization {
+            None | Some(QuantizationConfig::None) => Encoding::Full { data: Vec::new() },
+            Some(QuantizationConfig::Scalar) => Encoding::Scalar {
+                codes: Vec::new(),
+                rows: Vec::new(),
+            },
+            Some(QuantizationConfig::Binary) => Encoding::Binary { bits: Vec::new() },
+            Some(QuantizationConfig::Product { subspaces, k }) => {
+                if *subspaces == 0 || dimensions % subspaces != 0 {
+                    return Err(RuvectorError::InvalidParameter(format!(
+                        "Product quantization needs a subspace count dividing {} dimensions, got {}",
+                        dimensions, subspaces
+                    )));
+                }
+                if *k == 0 || *k > 256 {
+                    return Err(RuvectorError::InvalidParameter(format!(
+                        "Product quantization codebook size must be within 1..=256, got {}",
+                        k
+                    )));
+                }
+                Encoding::Product {
+                    subspaces: *subspaces,
+                    centroids: *k,
+                    codebooks: Vec::new(),
+                    codes: Vec::new(),
+                    untrained: Vec::new(),
+                }
+            }
+        };
+
+        Ok(Self {
+            dimensions,
+            len: 0,
+            encoding,
+        })
+    }
+
+    /// Empty store with the same encoding and trained codebooks
+    pub fn empty_like(&self) -> Self {
+        let encoding = match &self.encoding {
+            Encoding::Full { .. } => Encoding::Full { data: Vec::new() },
+            Encoding::Scalar { .. } => Encoding::Scalar {
+                codes: Vec::new(),
+                rows: Vec::new(),
+            },
+            Encoding::Binary { .. } => Encoding::Binary { bits: Vec::new() },
+            Encoding::Product {
+                subspaces,
+                centroids,
+                codebooks,
+                ..
+            } => Encoding::Product {
+                subspaces: *subspaces,
+                centroids: *centroids,
+                codebooks: codebooks.clone(),
+                codes: Vec::new(),
+                untrained: Vec::new(),
+            },
+        };
+
+        Self {
+            dimensions: self.dimensions,
+            len: 0,
+            encoding,
+        }
+    }
+
+    /// Number of stored vectors
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Whether stored vectors lose precision
+    pub fn is_quantized(&self) -> bool {
+        !matches!(self.encoding, Encoding::Full { .. })
+    }
+
+    /// Bytes held by encoded vectors and codebooks
+    pub fn memory_bytes(&self) -> usize {
+        let f32_size = std::mem::size_of::<f32>();
+        match &self.encoding {
+            Encoding::Full { data } => data.len() * f32_size,
+            Encoding::Scalar { codes, rows } => {
+                codes.len() + rows.len() * std::mem::size_of::<ScalarRow>()
+            }
+            Encoding::Binary { bits } => bits.len(),
+            Encoding::Product {
+                codebooks,
+                codes,
+                untrained,
+                ..
+            } => codes.len() + (codebooks.len() + untrained.len()) * f32_size,
+        }
+    }
+
+    /// Append a vector, encoding it as configured
+    pub fn push(&mut self, vector: &[f32]) {
+        let dimensions = self.dimensions;
+        match &mut self.encoding {
+            Encoding::Full { data } => data.extend_from_slice(vector),
+            Encoding::Scalar { codes, rows } => {
+                let quantized = ScalarQuantized::quantize(vector);
+                let start = codes.len();
+                codes.extend(quantized.data.iter().map(|&c| (c as i16 - 128) as i8));
+                rows.push(ScalarRow::new(
+                    quantized.min,
+                    quantized.scale,
+                    &codes[start..],
+                ));
+            }
+            Encoding::Binary { bits } => {
+                bits.extend_from_slice(&BinaryQuantized::quantize(vector).bits);
+            }
+            Encoding::Product {
+                subspaces,
+                centroids,
+                codebooks,
+                codes,
+                untrained,
+            } => {
+                if codebooks.is_empty() {
+                    untrained.extend_from_slice(vector);
+                    if untrained.len() / dimensions >= PRODUCT_TRAINING_SIZE.max(*centroids) {
+                        Self::train_product(
+                            dimensions, *subspaces, *centroids, codebooks, codes, untrained,
+                        );
+                    }
+                } else {
+                    encode_product(dimensions, *subspaces, *centroids, codebooks, vector, codes);
+                }
+            }
+        }
+        self.len += 1;
+    }
+
+    /// Append the vector stored at `idx` in `other`, which must share this
+    /// store's encoding, without re-quantizing it
+    pub fn push_from(&mut self, other: &Self, idx: usize) {
+        if let Some(vector) = other.full(idx) {
+            self.push(vector);
+            return;
+        }
+
+        let dimensions = self.dimensions;
+        match (&mut self.encoding, &other.encoding) {
+            (
+                Encoding::Scalar { codes, rows },
+                Encoding::Scalar {
+                    codes: src_codes,
+                    rows: src_rows,
+                },
+            ) => {
+                codes.extend_from_slice(&src_codes[idx * dimensions..(idx + 1) * dimensions]);
+                rows.push(src_rows[idx]);
+            }
+            (Encoding::Binary { bits }, Encoding::Binary { bits: src_bits }) => {
+                let stride = dimensions.div_ceil(8);
+                bits.extend_from_slice(&src_bits[idx * stride..(idx + 1) * stride]);
+            }
+            (
+                Encoding::Product {
+                    subspaces, codes, ..
+                },
+                Encoding::Product {
+                    codes: src_codes, ..
+                },
+            ) => {
+                let stride = *subspaces;
+                codes.extend_from_slice(&src_codes[idx * stride..(idx + 1) * stride]);
+            }
+            _ => {
+                let mut vector = Vec::with_capacity(dimensions);
+                other.decode(idx, &mut vector);
+                self.push(&vector);
+                return;
+            }
+        }
+        self.len += 1;
+    }
+
+    /// The vector at `idx`, decoded if it is quantized
+    pub fn get(&self, idx: usize) -> Vec<f32> {
+        let mut vector = Vec::with_capacity(self.dimensions);
+        self.decode(idx, &mut vector);
+        vector
+    }
+
+    /// The vector at `idx` if it is held at full precision
+    fn full(&self, idx: usize) -> Option<&[f32]> {
+        let d = self.dimensions;
+        match &self.encoding {
+            Encoding::Full { data } => Some(&data[idx * d..(idx + 1) * d]),
+            Encoding::Product {
+                codebooks,
+                untrained,
+                ..
+            } if codebooks.is_empty() => Some(&untrained[idx * d..(idx + 1) * d]),
+            _ => None,
+        }
+    }
+
+    /// Write the approximate vector at `idx` into `out`
+    fn decode(&self, idx: usize, out: &mut Vec<f32>) {
+        out.clear();
+        let d = self.dimensions;
+        match &self.encoding {
+            Encoding::Full { data } => out.extend_from_slice(&data[idx * d..(idx + 1) * d]),
+            Encoding::Scalar { codes, rows } => {
+                let ScalarRow { offset, scale, .. } = rows[idx];
+                out.extend(
+                    codes[idx * d..(idx + 1) * d]
+                        .iter()
+                        .map(|&code| offset + code as f32 * scale),
+                );
+            }
+            Encoding::Binary { bits } => {
+                let row = &bits[idx * d.div_ceil(8)..];
+                out.extend((0..d).map(|i| {
+                    if (row[i / 8] >> (i % 8)) & 1 == 1 {
+                        1.0
+                    } else {
+                        -1.0
+                    }
+                }));
+            }
+            Encoding::Product {
+                subspaces,
+                centroids,
+                codebooks,
+                codes,
+                untrained,
+            } => {
+                if codebooks.is_empty() {
+                    out.extend_from_slice(&untrained[idx * d..(idx + 1) * d]);
+                    return;
+                }
+                let sub_dim = d / subspaces;
+                for (s, &code) in codes[idx * subspaces..(idx + 1) * subspaces]
+                    .iter()
+                    .enumerate()
+                {
+                    let start = (s * centroids + code as usize) * sub_dim;
+                    out.extend_from_slice(&codebooks[start..start + sub_dim]);
+                }
+            }
+        }
+    }
+
+    /// Distance between two stored scalar-quantized vectors computed from
+    /// their codes, or `None` if the encoding or metric has no such shortcut
+    fn scalar_distance(&self, a: usize, b: usize, metric: DistanceMetric) -> Option<f32> {
+        let Encoding::Scalar { codes, rows } = &self.encoding else {
+            return None;
+        };
+        if metric == DistanceMetric::Manhattan {
+            return None;
+        }
+
+        let d = self.dimensions;
+        let (row_a, row_b) = (&rows[a], &rows[b]);
+        let l2sq = codes_l2sq(&codes[a * d..(a + 1) * d], &codes[b * d..(b + 1) * d]);
+        let code_dot = (row_a.sum_sq as f64 + row_b.sum_sq as f64 - l2sq) / 2.0;
+
+        let dims = d as f64;
+        let (offset_a, scale_a) = (row_a.offset as f64, row_a.scale as f64);
+        let (offset_b, scale_b) = (row_b.offset as f64, row_b.scale as f64);
+        let dot = dims * offset_a * offset_b
+            + offset_a * scale_b * row_b.sum as f64
+            + offset_b * scale_a * row_a.sum as f64
+            + scale_a * scale_b * code_dot;
+
+        let distance = match metric {
+            DistanceMetric::Euclidean => (row_a.norm_sq(dims) + row_b.norm_sq(dims) - 2.0 * dot)
+                .max(0.0)
+                .sqrt(),
+            DistanceMetric::Cosine => {
+                let norms = (row_a.norm_sq(dims) * row_b.norm_sq(dims)).sqrt();
+                if norms > 1e-16 {
+                    1.0 - dot / norms
+                } else {
+                    1.0
+                }
+            }
+            DistanceMetric::DotProduct => -dot,
+            DistanceMetric::Manhattan => return None,
+        };
+        Some(distance as f32)
+    }
+
+    /// Train codebooks on the buffered vectors and encode all of them
+    fn train_product(
+        dimensions: usize,
+        subspaces: usize,
+        centroids: usize,
+        codebooks: &mut Vec<f32>,
+        codes: &mut Vec<u8>,
+        untrained: &mut Vec<f32>,
+    ) {
+        let vectors: Vec<Vec<f32>> = untrained.chunks(dimensions).map(<[f32]>::to_vec).collect();
+        let trained = match ProductQuantized::train(
+            &vectors,
+            subspaces,
+            centroids,
+            PRODUCT_TRAINING_ITERATIONS,
+        ) {
+            Ok(trained) => trained,
+            Err(e) => {
+                tracing::warn!("Product quantization training failed: {}", e);
+                return;
+            }
+        };
+
+        // k-means yields fewer centroids than requested only for tiny inputs,
+        // which the training threshold rules out
+        if trained.codebooks.iter().any(|book| book.len() != centroids) {
+            tracing::warn!("Product quantization produced incomplete codebooks");
+            return;
+        }
+
+        *codebooks = trained.codebooks.into_iter().flatten().flatten().collect();
+        for vector in &vectors {
+            encode_product(dimensions, subspaces, centroids, codebooks, vector, codes);
+        }
+        *untrained = Vec::new();
+        tracing::debug!(
+            "Trained product quantization codebooks on {} vectors",
+            vectors.len()
+        );
+    }
+}
+
+/// Squared Euclidean distance between two code rows
+fn codes_l2sq(a: &[i8], b: &[i8]) -> f64 {
+    #[cfg(all(feature = "simd", not(target_arch = "wasm32")))]
+    {
+        simsimd::SpatialSimilarity::l2sq(a, b).expect("SimSIMD l2sq failed")
+    }
+    #[cfg(any(not(feature = "simd"), target_arch = "wasm32"))]
+    {
+        a.iter()
+            .zip(b)
+            .map(|(&x, &y)| {
+                let diff = x as i32 - y as i32;
+                (diff * diff) as f64
+            })
+            .sum()
+    }
+}
+
+/// Append the product quantization codes of `vector` to `codes`
+fn encode_product(
+    dimensions: usize,
+    subspaces: usize,
+    centroids: usize,
+    codebooks: &[f32],
+    vector: &[f32],
+    codes: &mut Vec<u8>,
+) {
+    let sub_dim = dimensions / subspaces;
+    for s in 0..subspaces {
+        let sub = &vector[s * sub_dim..(s + 1) * sub_dim];
+        let book = &codebooks[s * centroids * sub_dim..(s + 1) * centroids * sub_dim];
+        let nearest = book
+            .chunks(sub_dim)
+            .map(|centroid| {
+                centroid
+                    .iter()
+                    .zip(sub)
+                    .map(|(c, x)| (c - x) * (c - x))
+                    .sum::<f32>()
+            })
+            .enumerate()
+            .min_by(|(_, a), (_, b)| a.total_cmp(b))
+            .map(|(code, _)| code as u8)
+            .unwrap_or(0);
+        codes.push(nearest);
+    }
+}
+
+/// Computes distances against a [`VectorStore`], decoding quantized vectors
+/// into reusable scratch buffers
+pub(crate) struct Scorer<'a> {
+    store: &'a VectorStore,
+    metric: DistanceMetric,
+    /// Last decoded left-hand vector and its idx; graph construction measures
+    /// many pairs against the same node in a row
+    lhs: RefCell<(usize, Vec<f32>)>,
+    rhs: RefCell<Vec<f32>>,
+}
+
+thread_local! {
+    /// Scratch buffers of dropped scorers, handed to the next scorer on the
+    /// same thread
+    static SCRATCH: RefCell<Vec<Vec<f32>>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Scratch buffers kept per thread; a scorer holds two
+const MAX_POOLED_SCRATCH: usize = 4;
+
+fn take_scratch() -> Vec<f32> {
+    SCRATCH
+        .with(|pool| pool.borrow_mut().pop())
+        .unwrap_or_default()
+}
+
+impl<'a> Scorer<'a> {
+    pub fn new(store: &'a VectorStore, metric: DistanceMetric) -> Self {
+        Self {
+            store,
+            metric,
+            lhs: RefCell::new((usize::MAX, take_scratch())),
+            rhs: RefCell::new(take_scratch()),
+        }
+    }
+
+    /// Distance from a full-precision query to the stored vector at `idx`
+    pub fn query(&self, query: &[f32], idx: usize) -> f32 {
+        match self.store.full(idx) {
+            Some(vector) => self.pair(query, vector),
+            None => {
+                let mut buf = self.rhs.borrow_mut();
+                self.store.decode(idx, &mut buf);
+                self.pair(query, &buf)
+            }
+        }
+    }
+
+    /// Distance between the stored vectors at `a` and `b`
+    pub fn between(&self, a: usize, b: usize) -> f32 {
+        if let Some(vector) = self.store.full(a) {
+            return self.query(vector, b);
+        }
+        if let Some(distance) = self.store.scalar_distance(a, b, self.metric) {
+            return distance;
+        }
+
+        let mut lhs = self.lhs.borrow_mut();
+        // Every metric is symmetric, so reuse whichever side is cached
+        let (a, b) = if lhs.0 == b { (b, a) } else { (a, b) };
+        if lhs.0 != a {
+            self.store.decode(a, &mut lhs.1);
+            lhs.0 = a;
+        }
+        self.query(&lhs.1, b)
+    }
+
+    fn pair(&self, a: &[f32], b: &[f32]) -> f32 {
+        distance(a, b, self.metric).unwrap_or(f32::MAX)
+    }
+}
+
+impl Drop for Scorer<'_> {
+    fn drop(&mut self) {
+        let buffers = [
+            std::mem::take(&mut self.lhs.get_mut().1),
+            std::mem::take(self.rhs.get_mut()),
+        ];
+        SCRATCH.with(|pool| {
+            let mut pool = pool.borrow_mut();
+            for buffer in buffers {
+                if pool.len() < MAX_POOLED_SCRATCH && buffer.capacity() > 0 {
+                    pool.push(buffer);
+                }
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample(i: usize, dimensions: usize) -> Vec<f32> {
+        (0..dimensions)
+            .map(|d| ((i * 31 + d * 17) % 101) as f32 / 50.0 - 1.0)
+            .collect()
+    }
+
+    #[test]
+    fn test_quantized_distances_track_exact() -> Result<()> {
+        let configs = [
+            QuantizationConfig::Scalar,
+            QuantizationConfig::Binary,
+            QuantizationConfig::Product {
+                subspaces: 4,
+                k: 16,
+            },
+        ];
+        for config in configs {
+            let mut store = VectorStore::new(32, Some(&config))?;
+            let vectors: Vec<Vec<f32>> = (0..PRODUCT_TRAINING_SIZE + 10)
+                .map(|i| sample(i, 32))
+                .collect();
+            for vector in &vectors {
+                store.push(vector);
+            }
+            assert!(store.is_quantized());
+            assert!(store.memory_bytes() < vectors.len() * 32 * 4 / 2);
+
+            let scorer = Scorer::new(&store, DistanceMetric::Cosine);
+            let query = sample(5000, 32);
+            for idx in [0, 500, vectors.len() - 1] {
+                let exact = distance(&query, &vectors[idx], DistanceMetric::Cosine)?;
+                let approx = scorer.query(&query, idx);
+                let tolerance = if matches!(config, QuantizationConfig::Scalar) {
+                    0.05
+                } else {
+                    1.0
+                };
+                assert!(
+                    (exact - approx).abs() < tolerance,
+                    "{:?}: exact {} approx {}",
+                    config,
+                    exact,
+                    approx
+                );
+            }
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_scalar_codes_match_decoded_distances() -> Result<()> {
+        let mut store = VectorStore::new(48, Some(&QuantizationConfig::Scalar))?;
+        for i in 0..6 {
+            store.push(&sample(i, 48));
+        }
+
+        let (mut a, mut b) = (Vec::new(), Vec::new());
+        store.decode(1, &mut a);
+        store.decode(4, &mut b);
+        for metric in [
+            DistanceMetric::Euclidean,
+            DistanceMetric::Cosine,
+            DistanceMetric::DotProduct,
+        ] {
+            let decoded = distance(&a, &b, metric)?;
+            let coded = store.scalar_distance(1, 4, metric).unwrap();
+            assert!(
+                (decoded - coded).abs() < 1e-3,
+                "{:?}: decoded {} coded {}",
+                metric,
+                decoded,
+                coded
+            );
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_push_from_copies_codes() -> Result<()> {
+        let mut store = VectorStore::new(8, Some(&QuantizationConfig::Scalar))?;
+        for i in 0..4 {
+            store.push(&sample(i, 8));
+        }
+
+        let mut copy = store.empty_like();
+        copy.push_from(&store, 2);
+        assert_eq!(copy.len(), 1);
+
+        let (mut original, mut copied) = (Vec::new(), Vec::new());
+        store.decode(2, &mut original);
+        copy.decode(0, &mut copied);
+        assert_eq!(original, copied);
+        Ok(())
+    }
+
+    #[test]
+    fn test_invalid_product_config() {
+        let config = QuantizationConfig::Product {
+            subspaces: 5,
+            k: 256,
+        };
+        assert!(VectorStore::new(32, Some(&config)).is_err());
+    }
+}
@@ -0,0 +1,223 @@
+//! Late-interaction index over bags of vectors
+//!
+//! Every id holds a bag of equally sized vectors stored back to back (e.g.
+//! one embedding per token). Each vector of the bag is added to a token-level
+//! index; a query bag gathers candidates from the nearest tokens of each of
+//! its vectors, and candidates are ranked by the exact late-interaction
+//! distance over the full bags kept here.
+
+use crate::distance::max_sim_distance;
+use crate::error::{Result, RuvectorError};
+use crate::index::{SearchParams, VectorIndex, BRUTE_FORCE_MAX_CANDIDATES};
+use crate::types::{DistanceMetric, SearchResult, VectorId};
+use std::collections::{HashMap, HashSet};
+
+/// Multi-vector index on top of a token-level index
+pub struct MultiVectorIndex {
+    tokens: Box<dyn VectorIndex>,
+    bags: HashMap<VectorId, Vec<f32>>,
+    dimensions: usize,
+    metric: DistanceMetric,
+    candidates_per_token: usize,
+}
+
+impl MultiVectorIndex {
+    /// Create an index whose bags hold `dimensions`-sized vectors, indexed
+    /// individually in `tokens`
+    pub fn new(
+        tokens: Box<dyn VectorIndex>,
+        dimensions: usize,
+        metric: DistanceMetric,
+        candidates_per_token: usize,
+    ) -> Self {
+        Self {
+            tokens,
+            bags: HashMap::new(),
+            dimensions,
+            metric,
+            candidates_per_token: candidates_per_token.max(1),
+        }
+    }
+
+    fn check_bag(&self, bag: &[f32]) -> Result<()> {
+        if bag.is_empty() || bag.len() % self.dimensions != 0 {
+            return Err(RuvectorError::InvalidDimension(format!(
+                "Multi-vector of length {} is not a non-empty bag of {}-dimensional vectors",
+                bag.len(),
+                self.dimensions
+            )));
+        }
+        Ok(())
+    }
+
+    fn token_id(id: &str, position: usize) -> VectorId {
+        // The position never contains '#', so the first one ends it
+        format!("{}#{}", position, id)
+    }
+
+    fn point_id(token_id: &str) -> &str {
+        token_id.split_once('#').map_or(token_id, |(_, id)| id)
+    }
+
+    fn remove_tokens(&mut self, id: &str) -> Result<bool> {
+        let Some(bag) = self.bags.remove(id) else {
+            return Ok(false);
+        };
+        for position in 0..bag.len() / self.dimensions {
+            self.tokens.remove(&Self::token_id(id, position))?;
+        }
+        Ok(true)
+    }
+
+    /// Candidate ids from the nearest tokens of each query vector
+    fn candidates(
+        &self,
+        query: &[f32],
+        k: usize,
+        allowed_tokens: Option<&HashSet<VectorId>>,
+        params: &SearchParams,
+    ) -> Result<HashSet<VectorId>> {
+        let per_token = self.candidates_per_token.max(k);
+        let mut candidates = HashSet::new();
+        for token in query.chunks_exact(self.dimensions) {
+            let hits = match allowed_tokens {
+                Some(allowed) => self
+                    .tokens
+                    .search_filtered_with_params(token, per_token, allowed, params)?,
+                None => self.tokens.search_with_params(token, per_token, params)?,
+            };
+            candidates.extend(
+                hits.into_iter()
+                    .map(|hit| Self::point_id(&hit.id).to_string()),
+            );
+        }
+        Ok(candidates)
+    }
+
+    /// Rank `ids` by their exact distance to `query`
+    fn rank<'a>(
+        &self,
+        query: &[f32],
+        k: usize,
+        ids: impl IntoIterator<Item = &'a VectorId>,
+    ) -> Result<Vec<SearchResult>> {
+        let mut results = Vec::new();
+        for id in ids {
+            if let Some(bag) = self.bags.get(id) {
+                let score = max_sim_distance(query, bag, self.dimensions, self.metric)?;
+                results.push(SearchResult {
+                    id: id.clone(),
+                    score,
+                    vector: None,
+                    metadata: None,
+                });
+            }
+        }
+
+        results.sort_by(|a, b| a.score.total_cmp(&b.score));
+        results.truncate(k);
+        Ok(results)
+    }
+}
+
+impl VectorIndex for MultiVectorIndex {
+    fn add(&mut self, id: VectorId, vector: Vec<f32>) -> Result<()> {
+        self.check_bag(&vector)?;
+        self.remove_tokens(&id)?;
+
+        let tokens = vector
+            .chunks_exact(self.dimensions)
+            .enumerate()
+            .map(|(position, token)| (Self::token_id(&id, position), token.to_vec()))
+            .collect();
+        self.tokens.add_batch(tokens)?;
+        self.bags.insert(id, vector);
+        Ok(())
+    }
+
+    fn search(&self, query: &[f32], k: usize) -> Result<Vec<SearchResult>> {
+        self.search_with_params(query, k, &SearchParams::default())
+    }
+
+    fn search_with_params(
+        &self,
+        query: &[f32],
+        k: usize,
+        params: &SearchParams,
+    ) -> Result<Vec<SearchResult>> {
+        self.check_bag(query)?;
+        let candidates = self.candidates(query, k, None, params)?;
+        self.rank(query, k, &candidates)
+    }
+
+    fn search_filtered_with_params(
+        &self,
+        query: &[f32],
+        k: usize,
+        allowed: &HashSet<VectorId>,
+        params: &SearchParams,
+    ) -> Result<Vec<SearchResult>> {
+        self.check_bag(query)?;
+        if allowed.len() <= BRUTE_FORCE_MAX_CANDIDATES {
+            return self.rank(query, k, allowed);
+        }
+
+        let allowed_tokens = allowed
+            .iter()
+            .filter_map(|id| Some((id, self.bags.get(id)?.len() / self.dimensions)))
+            .flat_map(|(id, count)| (0..count).map(move |position| Self::token_id(id, position)))
+            .collect();
+        let candidates = self.candidates(query, k, Some(&allowed_tokens), params)?;
+        self.rank(query, k, &candidates)
+    }
+
+    fn remove(&mut self, id: &VectorId) -> Result<bool> {
+        self.remove_tokens(id)
+    }
+
+    fn len(&self) -> usize {
+        self.bags.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::index::flat::FlatIndex;
+
+    #[test]
+    fn test_max_sim_ranking_and_replacement() -> Result<()> {
+        let tokens = Box::new(FlatIndex::new(2, DistanceMetric::DotProduct));
+        let mut index = MultiVectorIndex::new(tokens, 2, DistanceMetric::DotProduct, 1);
+
+        // "a#1" must not be confused with token ids of other points
+        index.add("a#1".to_string(), vec![1.0, 0.0, 0.0, 1.0])?;
+        index.add("b".to_string(), vec![2.0, 0.0])?;
+        index.add("c".to_string(), vec![0.0, 0.5, 0.5, 0.0, 0.1, 0.1])?;
+        assert_eq!(index.len(), 3);
+
+        // Query tokens (1, 0) and (0, 1): a scores 1 + 1, b 2 + 0, c 0.5 + 0.5
+        let results = index.search(&[1.0, 0.0, 0.0, 1.0], 3)?;
+        let ids: Vec<&str> = results.iter().map(|r| r.id.as_str()).collect();
+        assert_eq!(ids.len(), 3);
+        assert!((results[0].score + 2.0).abs() < 1e-6);
+        assert!((results[2].score + 1.0).abs() < 1e-6);
+        assert_eq!(ids[2], "c");
+
+        let allowed: HashSet<VectorId> = ["c".to_string()].into();
+        let results = index.search_filtered(&[1.0, 0.0], 5, &allowed)?;
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].id, "c");
+
+        // Replacing a bag drops its old tokens
+        index.add("a#1".to_string(), vec![-1.0, 0.0])?;
+        let results = index.search(&[1.0, 0.0], 1)?;
+        assert_eq!(results[0].id, "b");
+        assert!(index.remove(&"a#1".to_string())?);
+        assert_eq!(index.len(), 2);
+
+        assert!(index.add("d".to_string(), vec![1.0, 2.0, 3.0]).is_err());
+        assert!(index.search(&[1.0], 1).is_err());
+        Ok(())
+    }
+}
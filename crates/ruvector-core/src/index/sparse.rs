@@ -0,0 +1,304 @@
+//! Inverted index over sparse vectors
+//!
+//! Every term keeps a posting list ordered by an internal document number,
+//! together with bounds on its weights. Top-k retrieval by dot product uses
+//! WAND: cursors over the query terms are kept sorted by their current
+//! document, and a document is only scored once the summed upper bounds of the
+//! cursors up to it can beat the current k-th best score; all other documents
+//! are skipped by seeking the lagging cursors forward.
+
+use crate::types::{SparseVector, VectorId};
+use std::cmp::{Ordering, Reverse};
+use std::collections::{BTreeMap, BinaryHeap, HashMap, HashSet};
+
+#[derive(Debug, Default)]
+struct PostingList {
+    weights: BTreeMap<u32, f32>,
+    max_weight: f32,
+    min_weight: f32,
+}
+
+impl PostingList {
+    fn refresh_bounds(&mut self) {
+        self.max_weight = self
+            .weights
+            .values()
+            .copied()
+            .fold(f32::NEG_INFINITY, f32::max);
+        self.min_weight = self.weights.values().copied().fold(f32::INFINITY, f32::min);
+    }
+}
+
+/// Cursor of one query term over its posting list
+struct Cursor<'a> {
+    postings: &'a BTreeMap<u32, f32>,
+    query_weight: f32,
+    upper_bound: f32,
+    current: Option<(u32, f32)>,
+}
+
+impl Cursor<'_> {
+    fn seek(&mut self, doc: u32) {
+        self.current = self
+            .postings
+            .range(doc..)
+            .next()
+            .map(|(&doc, &weight)| (doc, weight));
+    }
+
+    fn doc(&self) -> u32 {
+        self.current.map_or(u32::MAX, |(doc, _)| doc)
+    }
+}
+
+#[derive(PartialEq)]
+struct Scored(f32, u32);
+
+impl Eq for Scored {}
+
+impl PartialOrd for Scored {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Scored {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.0.total_cmp(&other.0).then(other.1.cmp(&self.1))
+    }
+}
+
+/// Inverted index answering top-k dot product queries over sparse vectors
+#[derive(Debug, Default)]
+pub struct SparseIndex {
+    postings: HashMap<u32, PostingList>,
+    vectors: HashMap<VectorId, (u32, SparseVector)>,
+    ids: HashMap<u32, VectorId>,
+    next_doc: u32,
+}
+
+impl SparseIndex {
+    /// Create an empty index
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add or replace the vector stored under `id`
+    pub fn insert(&mut self, id: VectorId, vector: SparseVector) {
+        self.remove(&id);
+
+        let doc = self.next_doc;
+        self.next_doc += 1;
+        for (&term, &weight) in vector.indices.iter().zip(&vector.values) {
+            let list = self.postings.entry(term).or_insert_with(|| PostingList {
+                max_weight: weight,
+                min_weight: weight,
+                ..Default::default()
+            });
+            list.weights.insert(doc, weight);
+            list.max_weight = list.max_weight.max(weight);
+            list.min_weight = list.min_weight.min(weight);
+        }
+        self.ids.insert(doc, id.clone());
+        self.vectors.insert(id, (doc, vector));
+    }
+
+    /// Remove the vector stored under `id`, returning whether it existed
+    pub fn remove(&mut self, id: &str) -> bool {
+        let Some((doc, vector)) = self.vectors.remove(id) else {
+            return false;
+        };
+        self.ids.remove(&doc);
+        for term in &vector.indices {
+            if let Some(list) = self.postings.get_mut(term) {
+                if let Some(weight) = list.weights.remove(&doc) {
+                    if list.weights.is_empty() {
+                        self.postings.remove(term);
+                    } else if weight == list.max_weight || weight == list.min_weight {
+                        list.refresh_bounds();
+                    }
+                }
+            }
+        }
+        true
+    }
+
+    /// Vector stored under `id`
+    pub fn get(&self, id: &str) -> Option<&SparseVector> {
+        self.vectors.get(id).map(|(_, vector)| vector)
+    }
+
+    /// Number of indexed vectors
+    pub fn len(&self) -> usize {
+        self.vectors.len()
+    }
+
+    /// Whether the index holds no vectors
+    pub fn is_empty(&self) -> bool {
+        self.vectors.is_empty()
+    }
+
+    /// Top `k` ids by dot product with `query`, best first, optionally
+    /// restricted to `allowed`
+    ///
+    /// Only vectors sharing at least one term with the query are returned.
+    pub fn search(
+        &self,
+        query: &SparseVector,
+        k: usize,
+        allowed: Option<&HashSet<VectorId>>,
+    ) -> Vec<(VectorId, f32)> {
+        if k == 0 {
+            return Vec::new();
+        }
+
+        let mut cursors: Vec<Cursor> = query
+            .indices
+            .iter()
+            .zip(&query.values)
+            .filter_map(|(term, &query_weight)| {
+                let list = self.postings.get(term)?;
+                let bound = (query_weight * list.max_weight).max(query_weight * list.min_weight);
+                let mut cursor = Cursor {
+                    postings: &list.weights,
+                    query_weight,
+                    // Clamped so partial sums of bounds never decrease
+                    upper_bound: bound.max(0.0),
+                    current: None,
+                };
+                cursor.seek(0);
+                Some(cursor)
+            })
+            .collect();
+
+        let mut heap: BinaryHeap<Reverse<Scored>> = BinaryHeap::with_capacity(k + 1);
+        loop {
+            cursors.sort_by_key(Cursor::doc);
+            let threshold = if heap.len() < k {
+                f32::NEG_INFINITY
+            } else {
+                heap.peek()
+                    .map_or(f32::NEG_INFINITY, |Reverse(worst)| worst.0)
+            };
+
+            // First cursor at which the accumulated bounds can beat the k-th score
+            let mut bound = 0.0;
+            let Some(pivot) = cursors.iter().position(|cursor| {
+                bound += cursor.upper_bound;
+                bound > threshold
+            }) else {
+                break;
+            };
+            let pivot_doc = cursors[pivot].doc();
+            if pivot_doc == u32::MAX {
+                break;
+            }
+
+            if cursors[0].doc() == pivot_doc {
+                let mut score = 0.0;
+                for cursor in cursors.iter_mut().take_while(|c| c.doc() == pivot_doc) {
+                    if let Some((_, weight)) = cursor.current {
+                        score += cursor.query_weight * weight;
+                    }
+                    cursor.seek(pivot_doc + 1);
+                }
+
+                let admitted = match allowed {
+                    Some(allowed) => self
+                        .ids
+                        .get(&pivot_doc)
+                        .is_some_and(|id| allowed.contains(id)),
+                    None => true,
+                };
+                if admitted && score > threshold {
+                    heap.push(Reverse(Scored(score, pivot_doc)));
+                    if heap.len() > k {
+                        heap.pop();
+                    }
+                }
+            } else {
+                for cursor in cursors.iter_mut().take(pivot) {
+                    cursor.seek(pivot_doc);
+                }
+            }
+        }
+
+        let mut results: Vec<Scored> = heap.into_iter().map(|Reverse(scored)| scored).collect();
+        results.sort_by(|a, b| b.cmp(a));
+        results
+            .into_iter()
+            .filter_map(|Scored(score, doc)| Some((self.ids.get(&doc)?.clone(), score)))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sparse(entries: &[(u32, f32)]) -> SparseVector {
+        SparseVector::new(entries.to_vec()).unwrap()
+    }
+
+    #[test]
+    fn test_wand_matches_exhaustive_scoring() {
+        let mut index = SparseIndex::new();
+        let mut vectors = Vec::new();
+        for i in 0..200u32 {
+            let entries: Vec<(u32, f32)> = (0..6)
+                .map(|j| {
+                    (
+                        (i * 7 + j * 13) % 50,
+                        ((i * 31 + j * 17) % 11) as f32 / 10.0,
+                    )
+                })
+                .collect::<HashMap<_, _>>()
+                .into_iter()
+                .collect();
+            let vector = sparse(&entries);
+            index.insert(format!("doc{}", i), vector.clone());
+            vectors.push((format!("doc{}", i), vector));
+        }
+
+        let query = sparse(&[(3, 1.0), (10, 0.5), (21, 2.0), (42, 0.25)]);
+        let mut expected: Vec<(String, f32)> = vectors
+            .iter()
+            .map(|(id, vector)| (id.clone(), query.dot(vector)))
+            .filter(|(_, score)| *score > 0.0)
+            .collect();
+        expected.sort_by(|a, b| b.1.total_cmp(&a.1));
+
+        let results = index.search(&query, 10, None);
+        assert_eq!(results.len(), 10);
+        for (result, expected) in results.iter().zip(&expected) {
+            assert!((result.1 - expected.1).abs() < 1e-5);
+        }
+
+        let allowed: HashSet<VectorId> =
+            expected.iter().skip(20).map(|(id, _)| id.clone()).collect();
+        let results = index.search(&query, 3, Some(&allowed));
+        assert!(results.iter().all(|(id, _)| allowed.contains(id)));
+        assert!((results[0].1 - expected[20].1).abs() < 1e-5);
+    }
+
+    #[test]
+    fn test_replace_and_remove() {
+        let mut index = SparseIndex::new();
+        index.insert("a".to_string(), sparse(&[(1, 1.0), (2, 3.0)]));
+        index.insert("b".to_string(), sparse(&[(2, 1.0)]));
+
+        let results = index.search(&sparse(&[(2, 1.0)]), 5, None);
+        assert_eq!(results[0].0, "a");
+
+        // Replacing "a" lowers the bound of term 2 without stale postings
+        index.insert("a".to_string(), sparse(&[(2, 0.5)]));
+        let results = index.search(&sparse(&[(2, 1.0)]), 5, None);
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0].0, "b");
+
+        assert!(index.remove("b"));
+        assert!(!index.remove("b"));
+        assert_eq!(index.len(), 1);
+        assert!(index.search(&sparse(&[(1, 1.0)]), 5, None).is_empty());
+    }
+}
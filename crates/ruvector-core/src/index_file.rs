@@ -0,0 +1,166 @@
+//! Index files persisted next to the redb storage file
+//!
+//! A serialized index is written as a fixed header followed by the payload:
+//!
+//! | bytes  | field                                      |
+//! |--------|--------------------------------------------|
+//! | 0..8   | magic `RVINDEX\0`                          |
+//! | 8..12  | format version, little endian              |
+//! | 12..16 | CRC-32 of the payload, little endian       |
+//! | 16..24 | last applied log sequence, little endian   |
+//! | 24..32 | payload length in bytes, little endian     |
+//!
+//! Files are replaced atomically by writing a temporary file and renaming
+//! it over the old one, so a crash mid-write leaves the previous file intact.
+
+use crate::error::{Result, RuvectorError};
+use memmap2::Mmap;
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+/// Current index file format version
+pub const INDEX_FILE_VERSION: u32 = 2;
+
+const MAGIC: &[u8; 8] = b"RVINDEX\0";
+const HEADER_LEN: usize = 32;
+
+/// Location of the index file belonging to the storage file at `storage_path`
+pub fn index_file_path(storage_path: &str) -> PathBuf {
+    PathBuf::from(format!("{}.hnsw", storage_path))
+}
+
+/// Write `payload` as the index file at `path`, tagged with the sequence of
+/// the last logged operation it reflects
+pub fn write_index_file(path: &Path, sequence: u64, payload: &[u8]) -> Result<()> {
+    let mut header = [0u8; HEADER_LEN];
+    header[0..8].copy_from_slice(MAGIC);
+    header[8..12].copy_from_slice(&INDEX_FILE_VERSION.to_le_bytes());
+    header[12..16].copy_from_slice(&crc32fast::hash(payload).to_le_bytes());
+    header[16..24].copy_from_slice(&sequence.to_le_bytes());
+    header[24..32].copy_from_slice(&(payload.len() as u64).to_le_bytes());
+
+    // Unique per writer, since several handles may share one storage file
+    let tmp_path = PathBuf::from(format!("{}.{}.tmp", path.display(), uuid::Uuid::new_v4()));
+    let written = (|| {
+        let mut file = File::create(&tmp_path)?;
+        file.write_all(&header)?;
+        file.write_all(payload)?;
+        file.sync_all()?;
+        fs::rename(&tmp_path, path)
+    })();
+    if let Err(e) = written {
+        let _ = fs::remove_file(&tmp_path);
+        return Err(e.into());
+    }
+
+    Ok(())
+}
+
+/// A validated, memory-mapped index file
+pub struct IndexFile {
+    mmap: Mmap,
+    sequence: u64,
+}
+
+impl IndexFile {
+    /// Map and validate the index file at `path`, or `None` if there is none
+    ///
+    /// Fails if the file is truncated, was written by another format version
+    /// or does not match its checksum.
+    pub fn open(path: &Path) -> Result<Option<Self>> {
+        let file = match File::open(path) {
+            Ok(file) => file,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+        // SAFETY: index files are only ever replaced by rename, never
+        // modified in place, so the mapped contents cannot change under us
+        let mmap = unsafe { Mmap::map(&file)? };
+
+        if mmap.len() < HEADER_LEN || &mmap[0..8] != MAGIC {
+            return Err(corrupted(path, "missing header"));
+        }
+
+        let version = u32::from_le_bytes(mmap[8..12].try_into().unwrap());
+        if version != INDEX_FILE_VERSION {
+            return Err(RuvectorError::SerializationError(format!(
+                "Index file {} has format version {}, expected {}",
+                path.display(),
+                version,
+                INDEX_FILE_VERSION
+            )));
+        }
+
+        let checksum = u32::from_le_bytes(mmap[12..16].try_into().unwrap());
+        let sequence = u64::from_le_bytes(mmap[16..24].try_into().unwrap());
+        let len = u64::from_le_bytes(mmap[24..32].try_into().unwrap());
+        if (mmap.len() - HEADER_LEN) as u64 != len {
+            return Err(corrupted(path, "length mismatch"));
+        }
+        if crc32fast::hash(&mmap[HEADER_LEN..]) != checksum {
+            return Err(corrupted(path, "checksum mismatch"));
+        }
+
+        Ok(Some(Self { mmap, sequence }))
+    }
+
+    /// Sequence of the last logged operation the index reflects
+    pub fn sequence(&self) -> u64 {
+        self.sequence
+    }
+
+    /// Serialized index
+    pub fn payload(&self) -> &[u8] {
+        &self.mmap[HEADER_LEN..]
+    }
+}
+
+fn corrupted(path: &Path, reason: &str) -> RuvectorError {
+    RuvectorError::SerializationError(format!(
+        "Corrupted index file {}: {}",
+        path.display(),
+        reason
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_round_trip() -> Result<()> {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("vectors.db.hnsw");
+        assert!(IndexFile::open(&path)?.is_none());
+
+        write_index_file(&path, 7, b"graph bytes")?;
+        let file = IndexFile::open(&path)?.unwrap();
+        assert_eq!(file.sequence(), 7);
+        assert_eq!(file.payload(), b"graph bytes");
+        Ok(())
+    }
+
+    #[test]
+    fn test_rejects_corruption_and_other_versions() -> Result<()> {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("vectors.db.hnsw");
+        write_index_file(&path, 1, b"graph bytes")?;
+        let original = fs::read(&path)?;
+
+        let mut flipped = original.clone();
+        *flipped.last_mut().unwrap() ^= 0xff;
+        fs::write(&path, &flipped)?;
+        assert!(IndexFile::open(&path).is_err());
+
+        let mut newer = original.clone();
+        newer[8..12].copy_from_slice(&(INDEX_FILE_VERSION + 1).to_le_bytes());
+        fs::write(&path, &newer)?;
+        assert!(IndexFile::open(&path).is_err());
+
+        fs::write(&path, &original[..original.len() - 1])?;
+        assert!(IndexFile::open(&path).is_err());
+        Ok(())
+    }
+}
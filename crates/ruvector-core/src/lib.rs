@@ -34,16 +34,21 @@ pub mod advanced_features;
 #[cfg(feature = "storage")]
 pub mod agenticdb;
 
+pub mod changes;
 pub mod distance;
 pub mod embeddings;
 pub mod error;
 pub mod index;
+pub mod oplog;
 pub mod quantization;
 
 // Storage backends - conditional compilation based on features
 #[cfg(feature = "storage")]
 pub mod storage;
 
+#[cfg(feature = "storage")]
+pub mod index_file;
+
 #[cfg(not(feature = "storage"))]
 pub mod storage_memory;
 
@@ -73,7 +78,9 @@ pub use advanced_features::{
 #[cfg(feature = "storage")]
 pub use agenticdb::AgenticDB;
 
-pub use embeddings::{EmbeddingProvider, HashEmbedding, BoxedEmbeddingProvider};
+pub use changes::{BoxedChangeListener, ChangeListener, PointChange, PointChangeKind};
+
+pub use embeddings::{EmbeddingProvider, HashEmbedding, BoxedEmbeddingProvider, EmbeddingConfig};
 #[cfg(feature = "api-embeddings")]
 pub use embeddings::ApiEmbedding;
 
@@ -93,9 +100,17 @@ const _: () = {
 };
 
 pub use error::{Result, RuvectorError};
-pub use types::{DistanceMetric, SearchQuery, SearchResult, VectorEntry, VectorId};
+pub use types::{
+    DistanceMetric, FusedSearchQuery, GroupedSearchQuery, HybridFusion, HybridSearchQuery,
+    MultiVectorConfig, PayloadUpdate, PointGroup, PointRecord, RangeSearchQuery, ScrollPage,
+    ScrollRequest, SearchByIdQuery, SearchFilter, SearchQuery, SearchResult, SparseVector,
+    TextEntry, VectorEntry, VectorId, VectorSpaceConfig, WeightedVector,
+};
 pub use vector_db::VectorDB;
 
+/// Payload filter expressions and indexes used by `SearchQuery::filter`
+pub use ruvector_filter as filter;
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -0,0 +1,319 @@
+//! Operation log entries
+//!
+//! Every committed change to the stored vectors and their metadata is
+//! recorded as an [`Operation`] under a sequence number that increases by one
+//! per operation. The log is written in the same storage transaction as the
+//! change itself, so replaying it from any sequence reproduces the stored
+//! state. Indexes are checkpointed together with the last sequence they
+//! reflect and only replay the tail of the log when the database is opened.
+
+#[cfg(feature = "storage")]
+use crate::error::{Result, RuvectorError};
+use crate::types::{SparseVector, VectorId};
+#[cfg(feature = "storage")]
+use bincode::{config, Decode, Encode};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+
+/// A committed change to a stored vector
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum Operation {
+    /// A vector was inserted, or replaced together with its metadata
+    Upsert {
+        /// Vector ID
+        id: VectorId,
+        /// Vector data
+        vector: Vec<f32>,
+        /// Metadata stored with the vector
+        metadata: Option<HashMap<String, Value>>,
+    },
+    /// A vector was deleted
+    Delete {
+        /// Vector ID
+        id: VectorId,
+    },
+    /// The metadata of a vector changed while its vector did not
+    UpdatePayload {
+        /// Vector ID
+        id: VectorId,
+        /// Metadata after the change, `None` if none is left
+        metadata: Option<HashMap<String, Value>>,
+    },
+    /// A vector of a named vector space was set for a stored point
+    UpsertNamed {
+        /// Vector ID
+        id: VectorId,
+        /// Vector space name
+        space: String,
+        /// Vector data
+        vector: Vec<f32>,
+    },
+    /// A vector of a named vector space was removed from a point
+    DeleteNamed {
+        /// Vector ID
+        id: VectorId,
+        /// Vector space name
+        space: String,
+    },
+    /// A named vector space was dropped together with all its vectors
+    DropVectorSpace {
+        /// Vector space name
+        space: String,
+    },
+    /// A sparse vector was set for a stored point
+    UpsertSparse {
+        /// Vector ID
+        id: VectorId,
+        /// Sparse vector space name
+        space: String,
+        /// Sparse vector data
+        vector: SparseVector,
+    },
+    /// A sparse vector was removed from a point
+    DeleteSparse {
+        /// Vector ID
+        id: VectorId,
+        /// Sparse vector space name
+        space: String,
+    },
+    /// A sparse vector space was dropped together with all its vectors
+    DropSparseSpace {
+        /// Sparse vector space name
+        space: String,
+    },
+}
+
+impl Operation {
+    /// ID of the vector the operation applies to, `None` for operations on
+    /// a whole vector space
+    pub fn id(&self) -> Option<&str> {
+        match self {
+            Operation::Upsert { id, .. }
+            | Operation::Delete { id }
+            | Operation::UpdatePayload { id, .. }
+            | Operation::UpsertNamed { id, .. }
+            | Operation::DeleteNamed { id, .. }
+            | Operation::UpsertSparse { id, .. }
+            | Operation::DeleteSparse { id, .. } => Some(id),
+            Operation::DropVectorSpace { .. } | Operation::DropSparseSpace { .. } => None,
+        }
+    }
+}
+
+/// An operation together with its position in the log
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LogEntry {
+    /// Sequence number, starting at 1 for the first operation
+    pub sequence: u64,
+    /// The change that was committed
+    pub operation: Operation,
+}
+
+/// Compact on-disk form; metadata is kept as JSON since `Value` has no
+/// bincode encoding
+#[cfg(feature = "storage")]
+#[derive(Encode, Decode)]
+enum EncodedOperation {
+    Upsert {
+        id: String,
+        vector: Vec<f32>,
+        metadata: Option<String>,
+    },
+    Delete {
+        id: String,
+    },
+    UpdatePayload {
+        id: String,
+        metadata: Option<String>,
+    },
+    UpsertNamed {
+        id: String,
+        space: String,
+        vector: Vec<f32>,
+    },
+    DeleteNamed {
+        id: String,
+        space: String,
+    },
+    DropVectorSpace {
+        space: String,
+    },
+    UpsertSparse {
+        id: String,
+        space: String,
+        indices: Vec<u32>,
+        values: Vec<f32>,
+    },
+    DeleteSparse {
+        id: String,
+        space: String,
+    },
+    DropSparseSpace {
+        space: String,
+    },
+}
+
+/// Serialize an operation for the log table
+#[cfg(feature = "storage")]
+pub(crate) fn encode(operation: &Operation) -> Result<Vec<u8>> {
+    let encoded = match operation {
+        Operation::Upsert {
+            id,
+            vector,
+            metadata,
+        } => EncodedOperation::Upsert {
+            id: id.clone(),
+            vector: vector.clone(),
+            metadata: metadata.as_ref().map(encode_metadata).transpose()?,
+        },
+        Operation::Delete { id } => EncodedOperation::Delete { id: id.clone() },
+        Operation::UpdatePayload { id, metadata } => EncodedOperation::UpdatePayload {
+            id: id.clone(),
+            metadata: metadata.as_ref().map(encode_metadata).transpose()?,
+        },
+        Operation::UpsertNamed { id, space, vector } => EncodedOperation::UpsertNamed {
+            id: id.clone(),
+            space: space.clone(),
+            vector: vector.clone(),
+        },
+        Operation::DeleteNamed { id, space } => EncodedOperation::DeleteNamed {
+            id: id.clone(),
+            space: space.clone(),
+        },
+        Operation::DropVectorSpace { space } => EncodedOperation::DropVectorSpace {
+            space: space.clone(),
+        },
+        Operation::UpsertSparse { id, space, vector } => EncodedOperation::UpsertSparse {
+            id: id.clone(),
+            space: space.clone(),
+            indices: vector.indices.clone(),
+            values: vector.values.clone(),
+        },
+        Operation::DeleteSparse { id, space } => EncodedOperation::DeleteSparse {
+            id: id.clone(),
+            space: space.clone(),
+        },
+        Operation::DropSparseSpace { space } => EncodedOperation::DropSparseSpace {
+            space: space.clone(),
+        },
+    };
+
+    bincode::encode_to_vec(&encoded, config::standard())
+        .map_err(|e| RuvectorError::SerializationError(e.to_string()))
+}
+
+/// Deserialize an operation read from the log table
+#[cfg(feature = "storage")]
+pub(crate) fn decode(bytes: &[u8]) -> Result<Operation> {
+    let (encoded, _): (EncodedOperation, usize) =
+        bincode::decode_from_slice(bytes, config::standard())
+            .map_err(|e| RuvectorError::SerializationError(e.to_string()))?;
+
+    Ok(match encoded {
+        EncodedOperation::Upsert {
+            id,
+            vector,
+            metadata,
+        } => Operation::Upsert {
+            id,
+            vector,
+            metadata: metadata.as_deref().map(decode_metadata).transpose()?,
+        },
+        EncodedOperation::Delete { id } => Operation::Delete { id },
+        EncodedOperation::UpdatePayload { id, metadata } => Operation::UpdatePayload {
+            id,
+            metadata: metadata.as_deref().map(decode_metadata).transpose()?,
+        },
+        EncodedOperation::UpsertNamed { id, space, vector } => {
+            Operation::UpsertNamed { id, space, vector }
+        }
+        EncodedOperation::DeleteNamed { id, space } => Operation::DeleteNamed { id, space },
+        EncodedOperation::DropVectorSpace { space } => Operation::DropVectorSpace { space },
+        EncodedOperation::UpsertSparse {
+            id,
+            space,
+            indices,
+            values,
+        } => Operation::UpsertSparse {
+            id,
+            space,
+            vector: SparseVector { indices, values },
+        },
+        EncodedOperation::DeleteSparse { id, space } => Operation::DeleteSparse { id, space },
+        EncodedOperation::DropSparseSpace { space } => Operation::DropSparseSpace { space },
+    })
+}
+
+#[cfg(feature = "storage")]
+fn encode_metadata(metadata: &HashMap<String, Value>) -> Result<String> {
+    serde_json::to_string(metadata).map_err(|e| RuvectorError::SerializationError(e.to_string()))
+}
+
+#[cfg(feature = "storage")]
+fn decode_metadata(json: &str) -> Result<HashMap<String, Value>> {
+    serde_json::from_str(json).map_err(|e| RuvectorError::SerializationError(e.to_string()))
+}
+
+#[cfg(all(test, feature = "storage"))]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_round_trip() -> Result<()> {
+        let mut metadata = HashMap::new();
+        metadata.insert("tag".to_string(), json!(["a", 1]));
+
+        let operations = vec![
+            Operation::Upsert {
+                id: "v1".to_string(),
+                vector: vec![1.0, -2.5],
+                metadata: Some(metadata.clone()),
+            },
+            Operation::Delete {
+                id: "v2".to_string(),
+            },
+            Operation::UpdatePayload {
+                id: "v3".to_string(),
+                metadata: None,
+            },
+            Operation::UpsertNamed {
+                id: "v4".to_string(),
+                space: "title".to_string(),
+                vector: vec![0.5],
+            },
+            Operation::DeleteNamed {
+                id: "v4".to_string(),
+                space: "title".to_string(),
+            },
+            Operation::DropVectorSpace {
+                space: "title".to_string(),
+            },
+            Operation::UpsertSparse {
+                id: "v5".to_string(),
+                space: "terms".to_string(),
+                vector: SparseVector {
+                    indices: vec![3, 17],
+                    values: vec![0.25, 1.5],
+                },
+            },
+            Operation::DeleteSparse {
+                id: "v5".to_string(),
+                space: "terms".to_string(),
+            },
+            Operation::DropSparseSpace {
+                space: "terms".to_string(),
+            },
+        ];
+
+        for operation in operations {
+            let decoded = decode(&encode(&operation)?)?;
+            assert_eq!(
+                serde_json::to_value(&decoded).unwrap(),
+                serde_json::to_value(&operation).unwrap()
+            );
+        }
+        Ok(())
+    }
+}
@@ -3,10 +3,16 @@
 //! This module is only available when the "storage" feature is enabled.
 //! For WASM builds, use the in-memory storage backend instead.
 
+#[cfg(feature = "storage")]
+use crate::embeddings::EmbeddingConfig;
 #[cfg(feature = "storage")]
 use crate::error::{Result, RuvectorError};
 #[cfg(feature = "storage")]
-use crate::types::{DbOptions, VectorEntry, VectorId};
+use crate::oplog::{self, LogEntry, Operation};
+#[cfg(feature = "storage")]
+use crate::types::{
+    DbOptions, PayloadUpdate, SparseVector, VectorEntry, VectorId, VectorSpaceConfig,
+};
 #[cfg(feature = "storage")]
 use bincode::config;
 #[cfg(feature = "storage")]
@@ -16,32 +22,78 @@ use parking_lot::Mutex;
 #[cfg(feature = "storage")]
 use redb::{Database, ReadableTable, ReadableTableMetadata, TableDefinition};
 #[cfg(feature = "storage")]
+use ruvector_filter::IndexType;
+#[cfg(feature = "storage")]
 use serde_json;
 #[cfg(feature = "storage")]
-use std::collections::HashMap;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
 #[cfg(feature = "storage")]
 use std::path::{Path, PathBuf};
 #[cfg(feature = "storage")]
-use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+#[cfg(feature = "storage")]
+use std::sync::{Arc, Weak};
 
 #[cfg(feature = "storage")]
 
 const VECTORS_TABLE: TableDefinition<&str, &[u8]> = TableDefinition::new("vectors");
 const METADATA_TABLE: TableDefinition<&str, &str> = TableDefinition::new("metadata");
 const CONFIG_TABLE: TableDefinition<&str, &str> = TableDefinition::new("config");
+const LOG_TABLE: TableDefinition<u64, &[u8]> = TableDefinition::new("oplog");
+/// Vectors of named vector spaces, keyed by (vector id, space name)
+const NAMED_VECTORS_TABLE: TableDefinition<(&str, &str), &[u8]> =
+    TableDefinition::new("named_vectors");
+/// Vectors of sparse vector spaces, keyed by (vector id, space name)
+const SPARSE_VECTORS_TABLE: TableDefinition<(&str, &str), &[u8]> =
+    TableDefinition::new("sparse_vectors");
 
 /// Key used to store database configuration in CONFIG_TABLE
 const DB_CONFIG_KEY: &str = "__ruvector_db_config__";
 
+/// Key used to store payload index definitions in CONFIG_TABLE
+///
+/// Kept apart from the database configuration like the vector space
+/// definitions below; both are only changed by the calls that declare them.
+const PAYLOAD_INDEXES_KEY: &str = "__ruvector_payload_indexes__";
+
+/// Key used to store named vector space definitions in CONFIG_TABLE
+const VECTOR_SPACES_KEY: &str = "__ruvector_vector_spaces__";
+
+/// Key used to store sparse vector space names in CONFIG_TABLE
+const SPARSE_SPACES_KEY: &str = "__ruvector_sparse_spaces__";
+
+/// Key used to store the embedding provider configuration in CONFIG_TABLE
+const EMBEDDING_CONFIG_KEY: &str = "__ruvector_embedding_config__";
+
+/// Key used to store the last operation log sequence in CONFIG_TABLE
+const SEQUENCE_KEY: &str = "__ruvector_sequence__";
+
+/// Stored vectors by id, together with the log sequence they reflect
+pub type VectorSnapshot = (u64, Vec<(VectorId, Vec<f32>)>);
+
+/// Stored named vectors by space name, together with the log sequence they
+/// reflect
+pub type NamedVectorSnapshot = (u64, HashMap<String, Vec<(VectorId, Vec<f32>)>>);
+
+/// Stored sparse vectors by space name, together with the log sequence they
+/// reflect
+pub type SparseVectorSnapshot = (u64, HashMap<String, Vec<(VectorId, SparseVector)>>);
+
 // Global database connection pool to allow multiple VectorDB instances
-// to share the same underlying database file
-static DB_POOL: Lazy<Mutex<HashMap<PathBuf, Arc<Database>>>> =
+// to share the same underlying database file. Entries are weak so a file is
+// closed once its last storage is dropped, and a file deleted and created
+// again at the same path is not served from a stale connection.
+static DB_POOL: Lazy<Mutex<HashMap<PathBuf, Weak<Database>>>> =
     Lazy::new(|| Mutex::new(HashMap::new()));
 
 /// Storage backend for vector database
 pub struct VectorStorage {
     db: Arc<Database>,
     dimensions: usize,
+    /// First and last sequence the last write through this handle logged
+    appended: Mutex<Option<(u64, u64)>>,
+    /// Bytes of operations logged through this handle
+    logged_bytes: AtomicU64,
 }
 
 impl VectorStorage {
@@ -88,7 +140,7 @@ impl VectorStorage {
                             std::path::Component::ParentDir => {
                                 if !normalized.pop() || !normalized.starts_with(&cwd) {
                                     return Err(RuvectorError::InvalidPath(
-                                        "Path traversal attempt detected".to_string()
+                                        "Path traversal attempt detected".to_string(),
                                     ));
                                 }
                             }
@@ -104,9 +156,9 @@ impl VectorStorage {
         let db = {
             let mut pool = DB_POOL.lock();
 
-            if let Some(existing_db) = pool.get(&path_buf) {
+            if let Some(existing_db) = pool.get(&path_buf).and_then(Weak::upgrade) {
                 // Reuse existing database connection
-                Arc::clone(existing_db)
+                existing_db
             } else {
                 // Create new database and add to pool
                 let new_db = Arc::new(Database::create(&path_buf)?);
@@ -117,19 +169,78 @@ impl VectorStorage {
                     let _ = write_txn.open_table(VECTORS_TABLE)?;
                     let _ = write_txn.open_table(METADATA_TABLE)?;
                     let _ = write_txn.open_table(CONFIG_TABLE)?;
+                    let _ = write_txn.open_table(LOG_TABLE)?;
+                    let _ = write_txn.open_table(NAMED_VECTORS_TABLE)?;
+                    let _ = write_txn.open_table(SPARSE_VECTORS_TABLE)?;
                 }
                 write_txn.commit()?;
 
-                pool.insert(path_buf, Arc::clone(&new_db));
+                pool.retain(|_, db| db.strong_count() > 0);
+                pool.insert(path_buf, Arc::downgrade(&new_db));
                 new_db
             }
         };
 
-        Ok(Self { db, dimensions })
+        Ok(Self {
+            db,
+            dimensions,
+            appended: Mutex::new(None),
+            logged_bytes: AtomicU64::new(0),
+        })
     }
 
-    /// Insert a vector entry
+    /// Insert a vector entry, replacing the vector and metadata of an
+    /// existing entry with the same id
     pub fn insert(&self, entry: &VectorEntry) -> Result<VectorId> {
+        let write_txn = self.db.begin_write()?;
+        let mut operations = Vec::with_capacity(1);
+        let id = self.insert_in(&write_txn, entry, &mut operations)?;
+        self.append_log(&write_txn, &operations)?;
+        write_txn.commit()?;
+
+        Ok(id)
+    }
+
+    /// Insert a vector entry and replace all of its named vectors in one
+    /// transaction
+    ///
+    /// Named vectors the point had in spaces missing from `vectors` are
+    /// removed. The caller validates the named vectors against their spaces.
+    pub fn insert_with_vectors(
+        &self,
+        entry: &VectorEntry,
+        vectors: &HashMap<String, Vec<f32>>,
+    ) -> Result<VectorId> {
+        let write_txn = self.db.begin_write()?;
+        let mut operations = Vec::with_capacity(1 + vectors.len());
+        let id = self.insert_in(&write_txn, entry, &mut operations)?;
+
+        let mut updates: Vec<(String, Option<Vec<f32>>)> =
+            Self::spaces_in(&write_txn, NAMED_VECTORS_TABLE, &id)?
+                .into_iter()
+                .filter(|space| !vectors.contains_key(space))
+                .map(|space| (space, None))
+                .collect();
+        updates.extend(
+            vectors
+                .iter()
+                .map(|(space, vector)| (space.clone(), Some(vector.clone()))),
+        );
+        Self::write_named_in(&write_txn, &id, &updates, &mut operations)?;
+
+        self.append_log(&write_txn, &operations)?;
+        write_txn.commit()?;
+
+        Ok(id)
+    }
+
+    /// Store `entry` within `write_txn`, recording the operation to log
+    fn insert_in(
+        &self,
+        write_txn: &redb::WriteTransaction,
+        entry: &VectorEntry,
+        operations: &mut Vec<Operation>,
+    ) -> Result<VectorId> {
         if entry.vector.len() != self.dimensions {
             return Err(RuvectorError::DimensionMismatch {
                 expected: self.dimensions,
@@ -142,7 +253,6 @@ impl VectorStorage {
             .clone()
             .unwrap_or_else(|| uuid::Uuid::new_v4().to_string());
 
-        let write_txn = self.db.begin_write()?;
         {
             let mut table = write_txn.open_table(VECTORS_TABLE)?;
 
@@ -152,23 +262,30 @@ impl VectorStorage {
 
             table.insert(id.as_str(), vector_data.as_slice())?;
 
-            // Store metadata if present
+            // Store metadata if present, dropping any left by an earlier entry
+            let mut meta_table = write_txn.open_table(METADATA_TABLE)?;
             if let Some(metadata) = &entry.metadata {
-                let mut meta_table = write_txn.open_table(METADATA_TABLE)?;
                 let metadata_json = serde_json::to_string(metadata)
                     .map_err(|e| RuvectorError::SerializationError(e.to_string()))?;
                 meta_table.insert(id.as_str(), metadata_json.as_str())?;
+            } else {
+                meta_table.remove(id.as_str())?;
             }
         }
-        write_txn.commit()?;
+        operations.push(Operation::Upsert {
+            id: id.clone(),
+            vector: entry.vector.clone(),
+            metadata: entry.metadata.clone(),
+        });
 
         Ok(id)
     }
 
-    /// Insert multiple vectors in a batch
+    /// Insert multiple vectors in a batch, replacing existing entries
     pub fn insert_batch(&self, entries: &[VectorEntry]) -> Result<Vec<VectorId>> {
         let write_txn = self.db.begin_write()?;
         let mut ids = Vec::with_capacity(entries.len());
+        let mut operations = Vec::with_capacity(entries.len());
 
         {
             let mut table = write_txn.open_table(VECTORS_TABLE)?;
@@ -197,12 +314,20 @@ impl VectorStorage {
                     let metadata_json = serde_json::to_string(metadata)
                         .map_err(|e| RuvectorError::SerializationError(e.to_string()))?;
                     meta_table.insert(id.as_str(), metadata_json.as_str())?;
+                } else {
+                    meta_table.remove(id.as_str())?;
                 }
 
+                operations.push(Operation::Upsert {
+                    id: id.clone(),
+                    vector: entry.vector.clone(),
+                    metadata: entry.metadata.clone(),
+                });
                 ids.push(id);
             }
         }
 
+        self.append_log(&write_txn, &operations)?;
         write_txn.commit()?;
         Ok(ids)
     }
@@ -211,7 +336,26 @@ impl VectorStorage {
     pub fn get(&self, id: &str) -> Result<Option<VectorEntry>> {
         let read_txn = self.db.begin_read()?;
         let table = read_txn.open_table(VECTORS_TABLE)?;
+        let meta_table = read_txn.open_table(METADATA_TABLE)?;
+        Self::read_entry(&table, &meta_table, id)
+    }
 
+    /// Get several vectors by ID from one read transaction, in the order of
+    /// `ids`
+    pub fn get_batch(&self, ids: &[&str]) -> Result<Vec<Option<VectorEntry>>> {
+        let read_txn = self.db.begin_read()?;
+        let table = read_txn.open_table(VECTORS_TABLE)?;
+        let meta_table = read_txn.open_table(METADATA_TABLE)?;
+        ids.iter()
+            .map(|id| Self::read_entry(&table, &meta_table, id))
+            .collect()
+    }
+
+    fn read_entry(
+        table: &impl ReadableTable<&'static str, &'static [u8]>,
+        meta_table: &impl ReadableTable<&'static str, &'static str>,
+        id: &str,
+    ) -> Result<Option<VectorEntry>> {
         let Some(vector_data) = table.get(id)? else {
             return Ok(None);
         };
@@ -221,7 +365,6 @@ impl VectorStorage {
                 .map_err(|e| RuvectorError::SerializationError(e.to_string()))?;
 
         // Try to get metadata
-        let meta_table = read_txn.open_table(METADATA_TABLE)?;
         let metadata = if let Some(meta_data) = meta_table.get(id)? {
             let meta_str = meta_data.value();
             Some(
@@ -241,21 +384,413 @@ impl VectorStorage {
 
     /// Delete a vector by ID
     pub fn delete(&self, id: &str) -> Result<bool> {
+        Ok(self.delete_batch(&[id])?[0])
+    }
+
+    /// Delete vectors in a single transaction, returning whether each id
+    /// was stored
+    pub fn delete_batch(&self, ids: &[&str]) -> Result<Vec<bool>> {
         let write_txn = self.db.begin_write()?;
-        let mut deleted = false;
+        let mut deleted = Vec::with_capacity(ids.len());
+        let mut operations = Vec::new();
 
-        {
-            let mut table = write_txn.open_table(VECTORS_TABLE)?;
-            deleted = table.remove(id)?.is_some();
+        for &id in ids {
+            let removed = {
+                let mut table = write_txn.open_table(VECTORS_TABLE)?;
+                let removed = table.remove(id)?.is_some();
 
-            let mut meta_table = write_txn.open_table(METADATA_TABLE)?;
-            let _ = meta_table.remove(id)?;
+                let mut meta_table = write_txn.open_table(METADATA_TABLE)?;
+                let _ = meta_table.remove(id)?;
+                removed
+            };
+
+            if removed {
+                // Named and sparse vectors go with the point and aren't logged
+                // separately
+                for table_definition in [NAMED_VECTORS_TABLE, SPARSE_VECTORS_TABLE] {
+                    let spaces = Self::spaces_in(&write_txn, table_definition, id)?;
+                    let mut table = write_txn.open_table(table_definition)?;
+                    for space in spaces {
+                        table.remove((id, space.as_str()))?;
+                    }
+                }
+                operations.push(Operation::Delete { id: id.to_string() });
+            }
+            deleted.push(removed);
         }
 
+        self.append_log(&write_txn, &operations)?;
         write_txn.commit()?;
         Ok(deleted)
     }
 
+    /// Apply metadata updates in a single transaction
+    ///
+    /// Returns the resulting metadata for each update, or `None` where the id
+    /// is not stored. Metadata left empty by an update is removed.
+    pub fn update_metadata(
+        &self,
+        updates: &[(VectorId, PayloadUpdate)],
+    ) -> Result<Vec<Option<HashMap<String, serde_json::Value>>>> {
+        let write_txn = self.db.begin_write()?;
+        let mut results = Vec::with_capacity(updates.len());
+        let mut operations = Vec::new();
+
+        {
+            let table = write_txn.open_table(VECTORS_TABLE)?;
+            let mut meta_table = write_txn.open_table(METADATA_TABLE)?;
+
+            for (id, update) in updates {
+                if table.get(id.as_str())?.is_none() {
+                    results.push(None);
+                    continue;
+                }
+
+                let mut metadata: HashMap<String, serde_json::Value> =
+                    match meta_table.get(id.as_str())? {
+                        Some(data) => serde_json::from_str(data.value())
+                            .map_err(|e| RuvectorError::SerializationError(e.to_string()))?,
+                        None => HashMap::new(),
+                    };
+                update.apply(&mut metadata);
+
+                if metadata.is_empty() {
+                    meta_table.remove(id.as_str())?;
+                } else {
+                    let metadata_json = serde_json::to_string(&metadata)
+                        .map_err(|e| RuvectorError::SerializationError(e.to_string()))?;
+                    meta_table.insert(id.as_str(), metadata_json.as_str())?;
+                }
+                operations.push(Operation::UpdatePayload {
+                    id: id.clone(),
+                    metadata: (!metadata.is_empty()).then(|| metadata.clone()),
+                });
+                results.push(Some(metadata));
+            }
+        }
+
+        self.append_log(&write_txn, &operations)?;
+        write_txn.commit()?;
+        Ok(results)
+    }
+
+    /// Set (`Some`) or remove (`None`) named vectors of a stored point in a
+    /// single transaction, returning whether `id` exists
+    ///
+    /// The caller validates the vectors against their spaces.
+    pub fn update_vectors(&self, id: &str, updates: &[(String, Option<Vec<f32>>)]) -> Result<bool> {
+        let write_txn = self.db.begin_write()?;
+        {
+            let table = write_txn.open_table(VECTORS_TABLE)?;
+            if table.get(id)?.is_none() {
+                return Ok(false);
+            }
+        }
+
+        let mut operations = Vec::with_capacity(updates.len());
+        Self::write_named_in(&write_txn, id, updates, &mut operations)?;
+        self.append_log(&write_txn, &operations)?;
+        write_txn.commit()?;
+        Ok(true)
+    }
+
+    /// Named vectors of a point by space name, empty if it has none
+    pub fn get_vectors(&self, id: &str) -> Result<HashMap<String, Vec<f32>>> {
+        let read_txn = self.db.begin_read()?;
+        let table = read_txn.open_table(NAMED_VECTORS_TABLE)?;
+
+        let mut vectors = HashMap::new();
+        for item in table.range((id, "")..)? {
+            let (key, vector_data) = item?;
+            let (key_id, space) = key.value();
+            if key_id != id {
+                break;
+            }
+            vectors.insert(space.to_string(), Self::decode_vector(vector_data.value())?);
+        }
+
+        Ok(vectors)
+    }
+
+    /// The vector of a point in a named space
+    pub fn get_named(&self, space: &str, id: &str) -> Result<Option<Vec<f32>>> {
+        let read_txn = self.db.begin_read()?;
+        let table = read_txn.open_table(NAMED_VECTORS_TABLE)?;
+
+        table
+            .get((id, space))?
+            .map(|vector_data| Self::decode_vector(vector_data.value()))
+            .transpose()
+    }
+
+    /// Remove every vector of a named space and save the remaining space
+    /// definitions in one transaction, returning how many vectors were removed
+    pub fn drop_vector_space(
+        &self,
+        space: &str,
+        spaces: &BTreeMap<String, VectorSpaceConfig>,
+    ) -> Result<usize> {
+        let spaces_json = serde_json::to_string(spaces)
+            .map_err(|e| RuvectorError::SerializationError(e.to_string()))?;
+
+        let write_txn = self.db.begin_write()?;
+        let removed = {
+            let mut table = write_txn.open_table(NAMED_VECTORS_TABLE)?;
+            let before = table.len()?;
+            table.retain(|(_, key_space), _| key_space != space)?;
+            (before - table.len()?) as usize
+        };
+        {
+            let mut config_table = write_txn.open_table(CONFIG_TABLE)?;
+            config_table.insert(VECTOR_SPACES_KEY, spaces_json.as_str())?;
+        }
+        self.append_log(
+            &write_txn,
+            &[Operation::DropVectorSpace {
+                space: space.to_string(),
+            }],
+        )?;
+        write_txn.commit()?;
+
+        Ok(removed)
+    }
+
+    /// Every stored named vector grouped by space, together with the
+    /// sequence they reflect, read from a single consistent snapshot
+    pub fn snapshot_named_vectors(&self) -> Result<NamedVectorSnapshot> {
+        let read_txn = self.db.begin_read()?;
+        let sequence = Self::read_sequence(&read_txn)?;
+        let table = read_txn.open_table(NAMED_VECTORS_TABLE)?;
+
+        let mut spaces: HashMap<String, Vec<(VectorId, Vec<f32>)>> = HashMap::new();
+        for item in table.iter()? {
+            let (key, vector_data) = item?;
+            let (id, space) = key.value();
+            spaces
+                .entry(space.to_string())
+                .or_default()
+                .push((id.to_string(), Self::decode_vector(vector_data.value())?));
+        }
+
+        Ok((sequence, spaces))
+    }
+
+    /// Set (`Some`) or remove (`None`) sparse vectors of a stored point in a
+    /// single transaction, returning whether `id` exists
+    ///
+    /// The caller validates the vectors and their spaces.
+    pub fn update_sparse(
+        &self,
+        id: &str,
+        updates: &[(String, Option<SparseVector>)],
+    ) -> Result<bool> {
+        let write_txn = self.db.begin_write()?;
+        {
+            let table = write_txn.open_table(VECTORS_TABLE)?;
+            if table.get(id)?.is_none() {
+                return Ok(false);
+            }
+        }
+
+        let mut operations = Vec::with_capacity(updates.len());
+        {
+            let mut table = write_txn.open_table(SPARSE_VECTORS_TABLE)?;
+            for (space, vector) in updates {
+                match vector {
+                    Some(vector) => {
+                        let vector_data = bincode::encode_to_vec(
+                            (&vector.indices, &vector.values),
+                            config::standard(),
+                        )
+                        .map_err(|e| RuvectorError::SerializationError(e.to_string()))?;
+                        table.insert((id, space.as_str()), vector_data.as_slice())?;
+                        operations.push(Operation::UpsertSparse {
+                            id: id.to_string(),
+                            space: space.clone(),
+                            vector: vector.clone(),
+                        });
+                    }
+                    None => {
+                        if table.remove((id, space.as_str()))?.is_some() {
+                            operations.push(Operation::DeleteSparse {
+                                id: id.to_string(),
+                                space: space.clone(),
+                            });
+                        }
+                    }
+                }
+            }
+        }
+
+        self.append_log(&write_txn, &operations)?;
+        write_txn.commit()?;
+        Ok(true)
+    }
+
+    /// Sparse vectors of a point by space name, empty if it has none
+    pub fn get_sparse(&self, id: &str) -> Result<HashMap<String, SparseVector>> {
+        let read_txn = self.db.begin_read()?;
+        let table = read_txn.open_table(SPARSE_VECTORS_TABLE)?;
+
+        let mut vectors = HashMap::new();
+        for item in table.range((id, "")..)? {
+            let (key, vector_data) = item?;
+            let (key_id, space) = key.value();
+            if key_id != id {
+                break;
+            }
+            vectors.insert(space.to_string(), Self::decode_sparse(vector_data.value())?);
+        }
+
+        Ok(vectors)
+    }
+
+    /// Remove every vector of a sparse space and save the remaining space
+    /// names in one transaction, returning how many vectors were removed
+    pub fn drop_sparse_space(&self, space: &str, spaces: &BTreeSet<String>) -> Result<usize> {
+        let spaces_json = serde_json::to_string(spaces)
+            .map_err(|e| RuvectorError::SerializationError(e.to_string()))?;
+
+        let write_txn = self.db.begin_write()?;
+        let removed = {
+            let mut table = write_txn.open_table(SPARSE_VECTORS_TABLE)?;
+            let before = table.len()?;
+            table.retain(|(_, key_space), _| key_space != space)?;
+            (before - table.len()?) as usize
+        };
+        {
+            let mut config_table = write_txn.open_table(CONFIG_TABLE)?;
+            config_table.insert(SPARSE_SPACES_KEY, spaces_json.as_str())?;
+        }
+        self.append_log(
+            &write_txn,
+            &[Operation::DropSparseSpace {
+                space: space.to_string(),
+            }],
+        )?;
+        write_txn.commit()?;
+
+        Ok(removed)
+    }
+
+    /// Every stored sparse vector grouped by space, together with the
+    /// sequence they reflect, read from a single consistent snapshot
+    pub fn snapshot_sparse_vectors(&self) -> Result<SparseVectorSnapshot> {
+        let read_txn = self.db.begin_read()?;
+        let sequence = Self::read_sequence(&read_txn)?;
+        let table = read_txn.open_table(SPARSE_VECTORS_TABLE)?;
+
+        let mut spaces: HashMap<String, Vec<(VectorId, SparseVector)>> = HashMap::new();
+        for item in table.iter()? {
+            let (key, vector_data) = item?;
+            let (id, space) = key.value();
+            spaces
+                .entry(space.to_string())
+                .or_default()
+                .push((id.to_string(), Self::decode_sparse(vector_data.value())?));
+        }
+
+        Ok((sequence, spaces))
+    }
+
+    /// Spaces `id` has a vector in within a table keyed by (id, space)
+    fn spaces_in(
+        write_txn: &redb::WriteTransaction,
+        table_definition: TableDefinition<(&str, &str), &[u8]>,
+        id: &str,
+    ) -> Result<Vec<String>> {
+        let table = write_txn.open_table(table_definition)?;
+
+        let mut spaces = Vec::new();
+        for item in table.range((id, "")..)? {
+            let (key, _) = item?;
+            let (key_id, space) = key.value();
+            if key_id != id {
+                break;
+            }
+            spaces.push(space.to_string());
+        }
+
+        Ok(spaces)
+    }
+
+    /// Set or remove named vectors of `id` within `write_txn`, recording the
+    /// operations to log
+    fn write_named_in(
+        write_txn: &redb::WriteTransaction,
+        id: &str,
+        updates: &[(String, Option<Vec<f32>>)],
+        operations: &mut Vec<Operation>,
+    ) -> Result<()> {
+        let mut table = write_txn.open_table(NAMED_VECTORS_TABLE)?;
+
+        for (space, vector) in updates {
+            match vector {
+                Some(vector) => {
+                    let vector_data = bincode::encode_to_vec(vector, config::standard())
+                        .map_err(|e| RuvectorError::SerializationError(e.to_string()))?;
+                    table.insert((id, space.as_str()), vector_data.as_slice())?;
+                    operations.push(Operation::UpsertNamed {
+                        id: id.to_string(),
+                        space: space.clone(),
+                        vector: vector.clone(),
+                    });
+                }
+                None => {
+                    if table.remove((id, space.as_str()))?.is_some() {
+                        operations.push(Operation::DeleteNamed {
+                            id: id.to_string(),
+                            space: space.clone(),
+                        });
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn decode_vector(vector_data: &[u8]) -> Result<Vec<f32>> {
+        let (vector, _): (Vec<f32>, usize) =
+            bincode::decode_from_slice(vector_data, config::standard())
+                .map_err(|e| RuvectorError::SerializationError(e.to_string()))?;
+        Ok(vector)
+    }
+
+    fn decode_sparse(vector_data: &[u8]) -> Result<SparseVector> {
+        let ((indices, values), _): ((Vec<u32>, Vec<f32>), usize) =
+            bincode::decode_from_slice(vector_data, config::standard())
+                .map_err(|e| RuvectorError::SerializationError(e.to_string()))?;
+        Ok(SparseVector { indices, values })
+    }
+
+    /// Visit stored points in id order, starting at `start` (inclusive),
+    /// from a single consistent snapshot until `visit` returns `false`
+    pub fn scan_points<F>(&self, start: &str, mut visit: F) -> Result<()>
+    where
+        F: FnMut(&str, Vec<f32>, Option<serde_json::Value>) -> bool,
+    {
+        let read_txn = self.db.begin_read()?;
+        let table = read_txn.open_table(VECTORS_TABLE)?;
+        let meta_table = read_txn.open_table(METADATA_TABLE)?;
+
+        for item in table.range(start..)? {
+            let (key, vector_data) = item?;
+            let id = key.value();
+            let vector = Self::decode_vector(vector_data.value())?;
+            let metadata = meta_table
+                .get(id)?
+                .map(|data| serde_json::from_str(data.value()))
+                .transpose()
+                .map_err(|e| RuvectorError::SerializationError(e.to_string()))?;
+            if !visit(id, vector, metadata) {
+                break;
+            }
+        }
+
+        Ok(())
+    }
+
     /// Get the number of vectors stored
     pub fn len(&self) -> Result<usize> {
         let read_txn = self.db.begin_read()?;
@@ -283,6 +818,76 @@ impl VectorStorage {
         Ok(ids)
     }
 
+    /// Visit every stored point's id with its metadata, `None` for points
+    /// without any, in one pass that decodes no vectors
+    pub fn scan_payloads<F>(&self, mut visit: F) -> Result<()>
+    where
+        F: FnMut(&str, Option<&serde_json::Value>),
+    {
+        let read_txn = self.db.begin_read()?;
+        let table = read_txn.open_table(VECTORS_TABLE)?;
+        let meta_table = read_txn.open_table(METADATA_TABLE)?;
+
+        // Both tables are ordered by id, so they are merged as they are read
+        let mut metadata = meta_table.iter()?;
+        let mut next_metadata = metadata.next().transpose()?;
+        for item in table.iter()? {
+            let (key, _) = item?;
+            let id = key.value();
+            while next_metadata
+                .as_ref()
+                .is_some_and(|(meta_id, _)| meta_id.value() < id)
+            {
+                next_metadata = metadata.next().transpose()?;
+            }
+            let payload: Option<serde_json::Value> = match &next_metadata {
+                Some((meta_id, data)) if meta_id.value() == id => Some(
+                    serde_json::from_str(data.value())
+                        .map_err(|e| RuvectorError::SerializationError(e.to_string()))?,
+                ),
+                _ => None,
+            };
+            visit(id, payload.as_ref());
+        }
+
+        Ok(())
+    }
+
+    /// Visit the metadata of every vector that has any
+    pub fn scan_metadata<F>(&self, mut visit: F) -> Result<()>
+    where
+        F: FnMut(&str, &serde_json::Value),
+    {
+        let read_txn = self.db.begin_read()?;
+        let table = read_txn.open_table(METADATA_TABLE)?;
+
+        for item in table.iter()? {
+            let (key, value) = item?;
+            let metadata: serde_json::Value = serde_json::from_str(value.value())
+                .map_err(|e| RuvectorError::SerializationError(e.to_string()))?;
+            visit(key.value(), &metadata);
+        }
+
+        Ok(())
+    }
+
+    /// Collect the ids whose metadata satisfies `predicate`
+    ///
+    /// Vectors without metadata never match.
+    pub fn filter_ids<F>(&self, predicate: F) -> Result<HashSet<VectorId>>
+    where
+        F: Fn(&serde_json::Value) -> bool,
+    {
+        let mut ids = HashSet::new();
+        self.scan_metadata(|id, metadata| {
+            if predicate(metadata) {
+                ids.insert(id.to_string());
+            }
+        })?;
+
+        Ok(ids)
+    }
+
     /// Save database configuration to persistent storage
     pub fn save_config(&self, options: &DbOptions) -> Result<()> {
         let config_json = serde_json::to_string(options)
@@ -318,6 +923,297 @@ impl VectorStorage {
         Ok(Some(config))
     }
 
+    /// Save payload index definitions alongside the database configuration
+    pub fn save_payload_indexes(&self, indexes: &BTreeMap<String, IndexType>) -> Result<()> {
+        let indexes_json = serde_json::to_string(indexes)
+            .map_err(|e| RuvectorError::SerializationError(e.to_string()))?;
+
+        let write_txn = self.db.begin_write()?;
+        {
+            let mut table = write_txn.open_table(CONFIG_TABLE)?;
+            table.insert(PAYLOAD_INDEXES_KEY, indexes_json.as_str())?;
+        }
+        write_txn.commit()?;
+
+        Ok(())
+    }
+
+    /// Load payload index definitions, empty for databases that declare none
+    pub fn load_payload_indexes(&self) -> Result<BTreeMap<String, IndexType>> {
+        let read_txn = self.db.begin_read()?;
+
+        let table = match read_txn.open_table(CONFIG_TABLE) {
+            Ok(t) => t,
+            Err(_) => return Ok(BTreeMap::new()),
+        };
+
+        let Some(indexes_data) = table.get(PAYLOAD_INDEXES_KEY)? else {
+            return Ok(BTreeMap::new());
+        };
+
+        serde_json::from_str(indexes_data.value())
+            .map_err(|e| RuvectorError::SerializationError(e.to_string()))
+    }
+
+    /// Save named vector space definitions alongside the database
+    /// configuration
+    pub fn save_vector_spaces(&self, spaces: &BTreeMap<String, VectorSpaceConfig>) -> Result<()> {
+        let spaces_json = serde_json::to_string(spaces)
+            .map_err(|e| RuvectorError::SerializationError(e.to_string()))?;
+
+        let write_txn = self.db.begin_write()?;
+        {
+            let mut table = write_txn.open_table(CONFIG_TABLE)?;
+            table.insert(VECTOR_SPACES_KEY, spaces_json.as_str())?;
+        }
+        write_txn.commit()?;
+
+        Ok(())
+    }
+
+    /// Load named vector space definitions, empty for databases that
+    /// declare none
+    pub fn load_vector_spaces(&self) -> Result<BTreeMap<String, VectorSpaceConfig>> {
+        let read_txn = self.db.begin_read()?;
+
+        let table = match read_txn.open_table(CONFIG_TABLE) {
+            Ok(t) => t,
+            Err(_) => return Ok(BTreeMap::new()),
+        };
+
+        let Some(spaces_data) = table.get(VECTOR_SPACES_KEY)? else {
+            return Ok(BTreeMap::new());
+        };
+
+        serde_json::from_str(spaces_data.value())
+            .map_err(|e| RuvectorError::SerializationError(e.to_string()))
+    }
+
+    /// Save the embedding provider configuration alongside the database
+    /// configuration, removing it for `None`
+    pub fn save_embedding_config(&self, embedding: Option<&EmbeddingConfig>) -> Result<()> {
+        let write_txn = self.db.begin_write()?;
+        {
+            let mut table = write_txn.open_table(CONFIG_TABLE)?;
+            match embedding {
+                Some(embedding) => {
+                    let embedding_json = serde_json::to_string(embedding)
+                        .map_err(|e| RuvectorError::SerializationError(e.to_string()))?;
+                    table.insert(EMBEDDING_CONFIG_KEY, embedding_json.as_str())?;
+                }
+                None => {
+                    table.remove(EMBEDDING_CONFIG_KEY)?;
+                }
+            }
+        }
+        write_txn.commit()?;
+
+        Ok(())
+    }
+
+    /// Load the embedding provider configuration, if one was saved
+    pub fn load_embedding_config(&self) -> Result<Option<EmbeddingConfig>> {
+        let read_txn = self.db.begin_read()?;
+
+        let table = match read_txn.open_table(CONFIG_TABLE) {
+            Ok(t) => t,
+            Err(_) => return Ok(None),
+        };
+
+        let Some(embedding_data) = table.get(EMBEDDING_CONFIG_KEY)? else {
+            return Ok(None);
+        };
+
+        serde_json::from_str(embedding_data.value())
+            .map(Some)
+            .map_err(|e| RuvectorError::SerializationError(e.to_string()))
+    }
+
+    /// Save sparse vector space names alongside the database configuration
+    pub fn save_sparse_spaces(&self, spaces: &BTreeSet<String>) -> Result<()> {
+        let spaces_json = serde_json::to_string(spaces)
+            .map_err(|e| RuvectorError::SerializationError(e.to_string()))?;
+
+        let write_txn = self.db.begin_write()?;
+        {
+            let mut table = write_txn.open_table(CONFIG_TABLE)?;
+            table.insert(SPARSE_SPACES_KEY, spaces_json.as_str())?;
+        }
+        write_txn.commit()?;
+
+        Ok(())
+    }
+
+    /// Load sparse vector space names, empty for databases that declare none
+    pub fn load_sparse_spaces(&self) -> Result<BTreeSet<String>> {
+        let read_txn = self.db.begin_read()?;
+
+        let table = match read_txn.open_table(CONFIG_TABLE) {
+            Ok(t) => t,
+            Err(_) => return Ok(BTreeSet::new()),
+        };
+
+        let Some(spaces_data) = table.get(SPARSE_SPACES_KEY)? else {
+            return Ok(BTreeSet::new());
+        };
+
+        serde_json::from_str(spaces_data.value())
+            .map_err(|e| RuvectorError::SerializationError(e.to_string()))
+    }
+
+    /// Sequence number of the last committed operation, 0 if none
+    pub fn last_sequence(&self) -> Result<u64> {
+        let read_txn = self.db.begin_read()?;
+        Self::read_sequence(&read_txn)
+    }
+
+    /// Log entries with a sequence above `after`, at most `limit` of them
+    ///
+    /// Returns `None` if some of those entries were already truncated, in
+    /// which case the stored vectors have to be read in full instead.
+    pub fn read_log(&self, after: u64, limit: usize) -> Result<Option<Vec<LogEntry>>> {
+        let read_txn = self.db.begin_read()?;
+        let last = Self::read_sequence(&read_txn)?;
+        if after >= last {
+            return Ok(Some(Vec::new()));
+        }
+
+        let table = read_txn.open_table(LOG_TABLE)?;
+        let mut entries = Vec::new();
+        for item in table.range(after + 1..)?.take(limit) {
+            let (sequence, operation) = item?;
+            let sequence = sequence.value();
+            if entries.is_empty() && sequence != after + 1 {
+                return Ok(None);
+            }
+            entries.push(LogEntry {
+                sequence,
+                operation: oplog::decode(operation.value())?,
+            });
+        }
+        if entries.is_empty() {
+            return Ok(None);
+        }
+
+        Ok(Some(entries))
+    }
+
+    /// The log entries of the last write through this handle, if it logged
+    /// any
+    ///
+    /// Only meaningful while the caller keeps other writes through this
+    /// handle out, as [`VectorDB`](crate::VectorDB) does.
+    pub fn last_written(&self) -> Result<Vec<LogEntry>> {
+        let Some((first, last)) = self.last_appended() else {
+            return Ok(Vec::new());
+        };
+        let count = (last - first + 1) as usize;
+        self.read_log(first - 1, count)?.ok_or_else(|| {
+            RuvectorError::StorageError(format!(
+                "Operation log was truncated past sequence {}",
+                first
+            ))
+        })
+    }
+
+    /// First and last sequence the last write through this handle logged,
+    /// `None` if it logged nothing
+    ///
+    /// Only meaningful under the same conditions as
+    /// [`last_written`](Self::last_written).
+    pub fn last_appended(&self) -> Option<(u64, u64)> {
+        *self.appended.lock()
+    }
+
+    /// Bytes of operations logged through this handle since it was opened
+    pub fn logged_bytes(&self) -> u64 {
+        self.logged_bytes.load(Ordering::Relaxed)
+    }
+
+    /// Drop log entries up to and including `through`, returning how many
+    /// were removed
+    pub fn truncate_log(&self, through: u64) -> Result<usize> {
+        let write_txn = self.db.begin_write()?;
+        let removed = {
+            let mut table = write_txn.open_table(LOG_TABLE)?;
+            let before = table.len()?;
+            table.retain_in(..=through, |_, _| false)?;
+            (before - table.len()?) as usize
+        };
+        write_txn.commit()?;
+        Ok(removed)
+    }
+
+    /// Every stored vector together with the sequence they reflect, read
+    /// from a single consistent snapshot
+    pub fn snapshot_vectors(&self) -> Result<VectorSnapshot> {
+        let read_txn = self.db.begin_read()?;
+        let sequence = Self::read_sequence(&read_txn)?;
+        let table = read_txn.open_table(VECTORS_TABLE)?;
+
+        let mut vectors = Vec::with_capacity(table.len()? as usize);
+        for item in table.iter()? {
+            let (id, vector_data) = item?;
+            let (vector, _): (Vec<f32>, usize) =
+                bincode::decode_from_slice(vector_data.value(), config::standard())
+                    .map_err(|e| RuvectorError::SerializationError(e.to_string()))?;
+            vectors.push((id.value().to_string(), vector));
+        }
+
+        Ok((sequence, vectors))
+    }
+
+    fn read_sequence(read_txn: &redb::ReadTransaction) -> Result<u64> {
+        let table = match read_txn.open_table(CONFIG_TABLE) {
+            Ok(t) => t,
+            Err(_) => return Ok(0),
+        };
+
+        match table.get(SEQUENCE_KEY)? {
+            Some(value) => Self::parse_sequence(value.value()),
+            None => Ok(0),
+        }
+    }
+
+    /// Record `operations` in the log within `write_txn`, numbering them
+    /// after the last committed operation
+    fn append_log(
+        &self,
+        write_txn: &redb::WriteTransaction,
+        operations: &[Operation],
+    ) -> Result<()> {
+        *self.appended.lock() = None;
+        if operations.is_empty() {
+            return Ok(());
+        }
+
+        let mut config_table = write_txn.open_table(CONFIG_TABLE)?;
+        let mut sequence = match config_table.get(SEQUENCE_KEY)? {
+            Some(value) => Self::parse_sequence(value.value())?,
+            None => 0,
+        };
+
+        let mut table = write_txn.open_table(LOG_TABLE)?;
+        let mut bytes = 0;
+        for operation in operations {
+            sequence += 1;
+            let encoded = oplog::encode(operation)?;
+            bytes += encoded.len() as u64;
+            table.insert(sequence, encoded.as_slice())?;
+        }
+        self.logged_bytes.fetch_add(bytes, Ordering::Relaxed);
+        config_table.insert(SEQUENCE_KEY, sequence.to_string().as_str())?;
+        let first = sequence + 1 - operations.len() as u64;
+        *self.appended.lock() = Some((first, sequence));
+        Ok(())
+    }
+
+    fn parse_sequence(value: &str) -> Result<u64> {
+        value
+            .parse()
+            .map_err(|_| RuvectorError::StorageError(format!("Corrupted log sequence: {}", value)))
+    }
+
     /// Get the stored dimensions
     pub fn dimensions(&self) -> usize {
         self.dimensions
@@ -400,6 +1296,40 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_scan_payloads_visits_points_with_and_without_metadata() -> Result<()> {
+        let dir = tempdir().unwrap();
+        let storage = VectorStorage::new(dir.path().join("test.db"), 1)?;
+
+        let entries: Vec<VectorEntry> = ["a", "b", "c", "d"]
+            .iter()
+            .enumerate()
+            .map(|(i, id)| VectorEntry {
+                id: Some(id.to_string()),
+                vector: vec![i as f32],
+                metadata: (i % 2 == 1)
+                    .then(|| HashMap::from([("i".to_string(), serde_json::json!(i))])),
+            })
+            .collect();
+        storage.insert_batch(&entries)?;
+
+        let mut visited = Vec::new();
+        storage.scan_payloads(|id, metadata| {
+            visited.push((id.to_string(), metadata.map(|m| m["i"].clone())));
+        })?;
+        assert_eq!(
+            visited,
+            vec![
+                ("a".to_string(), None),
+                ("b".to_string(), Some(serde_json::json!(1))),
+                ("c".to_string(), None),
+                ("d".to_string(), Some(serde_json::json!(3))),
+            ]
+        );
+
+        Ok(())
+    }
+
     #[test]
     fn test_multiple_instances_same_path() -> Result<()> {
         // This test verifies the fix for the database locking bug
@@ -444,4 +1374,61 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn test_operation_log_sequences_and_truncation() -> Result<()> {
+        let dir = tempdir().unwrap();
+        let db_path = dir.path().join("oplog.db");
+        let entry = |id: &str| VectorEntry {
+            id: Some(id.to_string()),
+            vector: vec![1.0, 2.0, 3.0],
+            metadata: None,
+        };
+
+        let storage1 = VectorStorage::new(&db_path, 3)?;
+        assert_eq!(storage1.last_sequence()?, 0);
+        assert!(storage1.read_log(0, 10)?.unwrap().is_empty());
+
+        storage1.insert(&entry("a"))?;
+        storage1.insert_batch(&[entry("b"), entry("c")])?;
+        assert!(!storage1.delete("missing")?);
+        storage1.update_metadata(&[
+            ("a".to_string(), PayloadUpdate::DeleteKeys(vec![])),
+            ("missing".to_string(), PayloadUpdate::DeleteKeys(vec![])),
+        ])?;
+
+        // Writes through another handle continue the same sequence
+        let storage2 = VectorStorage::new(&db_path, 3)?;
+        storage2.delete("a")?;
+        assert_eq!(storage1.last_sequence()?, 5);
+
+        let sequences_and_ids = |entries: Vec<LogEntry>| -> Vec<(u64, String)> {
+            entries
+                .into_iter()
+                .map(|entry| (entry.sequence, entry.operation.id().unwrap().to_string()))
+                .collect()
+        };
+        let tail = storage1.read_log(2, 2)?.unwrap();
+        assert!(matches!(tail[0].operation, Operation::Upsert { .. }));
+        assert_eq!(
+            sequences_and_ids(tail),
+            vec![(3, "c".to_string()), (4, "a".to_string())]
+        );
+        let tail = storage1.read_log(4, 10)?.unwrap();
+        assert!(matches!(tail[0].operation, Operation::Delete { .. }));
+
+        assert_eq!(storage1.truncate_log(3)?, 3);
+        assert!(storage1.read_log(2, 10)?.is_none());
+        assert_eq!(
+            sequences_and_ids(storage1.read_log(3, 10)?.unwrap()),
+            vec![(4, "a".to_string()), (5, "a".to_string())]
+        );
+        assert!(storage1.read_log(5, 10)?.unwrap().is_empty());
+
+        let (sequence, vectors) = storage1.snapshot_vectors()?;
+        assert_eq!(sequence, 5);
+        assert_eq!(vectors.len(), 2);
+
+        Ok(())
+    }
 }
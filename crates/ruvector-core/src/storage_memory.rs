@@ -4,15 +4,20 @@
 //! making it suitable for WebAssembly environments.
 
 use crate::error::{Result, RuvectorError};
-use crate::types::{VectorEntry, VectorId};
+use crate::types::{PayloadUpdate, SparseVector, VectorEntry, VectorId};
 use dashmap::DashMap;
 use serde_json::Value as JsonValue;
+use std::collections::{HashMap, HashSet};
 use std::sync::atomic::{AtomicU64, Ordering};
 
 /// In-memory storage backend using DashMap for thread-safe concurrent access
 pub struct MemoryStorage {
     vectors: DashMap<String, Vec<f32>>,
     metadata: DashMap<String, JsonValue>,
+    /// Named vectors of each point by space name
+    named: DashMap<String, HashMap<String, Vec<f32>>>,
+    /// Sparse vectors of each point by space name
+    sparse: DashMap<String, HashMap<String, SparseVector>>,
     dimensions: usize,
     counter: AtomicU64,
 }
@@ -23,6 +28,8 @@ impl MemoryStorage {
         Ok(Self {
             vectors: DashMap::new(),
             metadata: DashMap::new(),
+            named: DashMap::new(),
+            sparse: DashMap::new(),
             dimensions,
             counter: AtomicU64::new(0),
         })
@@ -34,7 +41,8 @@ impl MemoryStorage {
         format!("vec_{}", id)
     }
 
-    /// Insert a vector entry
+    /// Insert a vector entry, replacing the vector and metadata of an
+    /// existing entry with the same id
     pub fn insert(&self, entry: &VectorEntry) -> Result<VectorId> {
         if entry.vector.len() != self.dimensions {
             return Err(RuvectorError::DimensionMismatch {
@@ -59,12 +67,31 @@ impl MemoryStorage {
                         .collect(),
                 ),
             );
+        } else {
+            self.metadata.remove(&id);
         }
 
         Ok(id)
     }
 
-    /// Insert multiple vectors in a batch
+    /// Insert a vector entry and replace all of its named vectors
+    ///
+    /// The caller validates the named vectors against their spaces.
+    pub fn insert_with_vectors(
+        &self,
+        entry: &VectorEntry,
+        vectors: &HashMap<String, Vec<f32>>,
+    ) -> Result<VectorId> {
+        let id = self.insert(entry)?;
+        if vectors.is_empty() {
+            self.named.remove(&id);
+        } else {
+            self.named.insert(id.clone(), vectors.clone());
+        }
+        Ok(id)
+    }
+
+    /// Insert multiple vectors in a batch, replacing existing entries
     pub fn insert_batch(&self, entries: &[VectorEntry]) -> Result<Vec<VectorId>> {
         let mut ids = Vec::with_capacity(entries.len());
 
@@ -90,6 +117,8 @@ impl MemoryStorage {
                             .collect(),
                     ),
                 );
+            } else {
+                self.metadata.remove(&id);
             }
 
             ids.push(id);
@@ -120,13 +149,195 @@ impl MemoryStorage {
         }
     }
 
+    /// Get several vectors by ID, in the order of `ids`
+    pub fn get_batch(&self, ids: &[&str]) -> Result<Vec<Option<VectorEntry>>> {
+        ids.iter().map(|id| self.get(id)).collect()
+    }
+
     /// Delete a vector by ID
     pub fn delete(&self, id: &str) -> Result<bool> {
         let vector_removed = self.vectors.remove(id).is_some();
         self.metadata.remove(id);
+        self.named.remove(id);
+        self.sparse.remove(id);
         Ok(vector_removed)
     }
 
+    /// Delete vectors, returning whether each id was stored
+    pub fn delete_batch(&self, ids: &[&str]) -> Result<Vec<bool>> {
+        ids.iter().map(|id| self.delete(id)).collect()
+    }
+
+    /// Apply metadata updates
+    ///
+    /// Returns the resulting metadata for each update, or `None` where the id
+    /// is not stored. Metadata left empty by an update is removed.
+    pub fn update_metadata(
+        &self,
+        updates: &[(VectorId, PayloadUpdate)],
+    ) -> Result<Vec<Option<HashMap<String, JsonValue>>>> {
+        let mut results = Vec::with_capacity(updates.len());
+
+        for (id, update) in updates {
+            if !self.vectors.contains_key(id) {
+                results.push(None);
+                continue;
+            }
+
+            let mut metadata: HashMap<String, JsonValue> = match self.metadata.get(id) {
+                Some(value) => match value.value() {
+                    JsonValue::Object(map) => {
+                        map.iter().map(|(k, v)| (k.clone(), v.clone())).collect()
+                    }
+                    _ => HashMap::new(),
+                },
+                None => HashMap::new(),
+            };
+            update.apply(&mut metadata);
+
+            if metadata.is_empty() {
+                self.metadata.remove(id);
+            } else {
+                self.metadata.insert(
+                    id.clone(),
+                    JsonValue::Object(
+                        metadata
+                            .iter()
+                            .map(|(k, v)| (k.clone(), v.clone()))
+                            .collect(),
+                    ),
+                );
+            }
+            results.push(Some(metadata));
+        }
+
+        Ok(results)
+    }
+
+    /// Set (`Some`) or remove (`None`) named vectors of a stored point,
+    /// returning whether `id` exists
+    pub fn update_vectors(&self, id: &str, updates: &[(String, Option<Vec<f32>>)]) -> Result<bool> {
+        if !self.vectors.contains_key(id) {
+            return Ok(false);
+        }
+
+        let mut named = self.named.entry(id.to_string()).or_default();
+        for (space, vector) in updates {
+            match vector {
+                Some(vector) => named.insert(space.clone(), vector.clone()),
+                None => named.remove(space),
+            };
+        }
+        let empty = named.is_empty();
+        drop(named);
+        if empty {
+            self.named.remove(id);
+        }
+
+        Ok(true)
+    }
+
+    /// Named vectors of a point by space name, empty if it has none
+    pub fn get_vectors(&self, id: &str) -> Result<HashMap<String, Vec<f32>>> {
+        Ok(self
+            .named
+            .get(id)
+            .map(|named| named.value().clone())
+            .unwrap_or_default())
+    }
+
+    /// The vector of a point in a named space
+    pub fn get_named(&self, space: &str, id: &str) -> Result<Option<Vec<f32>>> {
+        Ok(self
+            .named
+            .get(id)
+            .and_then(|named| named.get(space).cloned()))
+    }
+
+    /// Remove every vector of a named space, returning how many were removed
+    pub fn drop_vector_space(&self, space: &str) -> Result<usize> {
+        let mut removed = 0;
+        self.named.retain(|_, named| {
+            removed += usize::from(named.remove(space).is_some());
+            !named.is_empty()
+        });
+        Ok(removed)
+    }
+
+    /// Set (`Some`) or remove (`None`) sparse vectors of a stored point,
+    /// returning whether `id` exists
+    pub fn update_sparse(
+        &self,
+        id: &str,
+        updates: &[(String, Option<SparseVector>)],
+    ) -> Result<bool> {
+        if !self.vectors.contains_key(id) {
+            return Ok(false);
+        }
+
+        let mut sparse = self.sparse.entry(id.to_string()).or_default();
+        for (space, vector) in updates {
+            match vector {
+                Some(vector) => sparse.insert(space.clone(), vector.clone()),
+                None => sparse.remove(space),
+            };
+        }
+        let empty = sparse.is_empty();
+        drop(sparse);
+        if empty {
+            self.sparse.remove(id);
+        }
+
+        Ok(true)
+    }
+
+    /// Sparse vectors of a point by space name, empty if it has none
+    pub fn get_sparse(&self, id: &str) -> Result<HashMap<String, SparseVector>> {
+        Ok(self
+            .sparse
+            .get(id)
+            .map(|sparse| sparse.value().clone())
+            .unwrap_or_default())
+    }
+
+    /// Remove every vector of a sparse space, returning how many were removed
+    pub fn drop_sparse_space(&self, space: &str) -> Result<usize> {
+        let mut removed = 0;
+        self.sparse.retain(|_, sparse| {
+            removed += usize::from(sparse.remove(space).is_some());
+            !sparse.is_empty()
+        });
+        Ok(removed)
+    }
+
+    /// Visit stored points in id order, starting at `start` (inclusive),
+    /// until `visit` returns `false`
+    pub fn scan_points<F>(&self, start: &str, mut visit: F) -> Result<()>
+    where
+        F: FnMut(&str, Vec<f32>, Option<JsonValue>) -> bool,
+    {
+        let mut ids: Vec<String> = self
+            .vectors
+            .iter()
+            .filter(|entry| entry.key().as_str() >= start)
+            .map(|entry| entry.key().clone())
+            .collect();
+        ids.sort_unstable();
+
+        for id in ids {
+            // Skip points deleted since the ids were collected
+            let Some(vector) = self.vectors.get(&id).map(|v| v.value().clone()) else {
+                continue;
+            };
+            let metadata = self.metadata.get(&id).map(|m| m.value().clone());
+            if !visit(&id, vector, metadata) {
+                break;
+            }
+        }
+
+        Ok(())
+    }
+
     /// Get the number of vectors stored
     pub fn len(&self) -> Result<usize> {
         Ok(self.vectors.len())
@@ -150,10 +361,51 @@ impl MemoryStorage {
         Ok(self.keys())
     }
 
+    /// Visit every stored point's id with its metadata, `None` for points
+    /// without any
+    pub fn scan_payloads<F>(&self, mut visit: F) -> Result<()>
+    where
+        F: FnMut(&str, Option<&JsonValue>),
+    {
+        for entry in self.vectors.iter() {
+            let metadata = self.metadata.get(entry.key());
+            visit(entry.key(), metadata.as_deref());
+        }
+        Ok(())
+    }
+
+    /// Visit the metadata of every vector that has any
+    pub fn scan_metadata<F>(&self, mut visit: F) -> Result<()>
+    where
+        F: FnMut(&str, &JsonValue),
+    {
+        for entry in self.metadata.iter() {
+            visit(entry.key(), entry.value());
+        }
+        Ok(())
+    }
+
+    /// Collect the ids whose metadata satisfies `predicate`
+    ///
+    /// Vectors without metadata never match.
+    pub fn filter_ids<F>(&self, predicate: F) -> Result<HashSet<VectorId>>
+    where
+        F: Fn(&JsonValue) -> bool,
+    {
+        Ok(self
+            .metadata
+            .iter()
+            .filter(|entry| predicate(entry.value()))
+            .map(|entry| entry.key().clone())
+            .collect())
+    }
+
     /// Clear all data
     pub fn clear(&self) -> Result<()> {
         self.vectors.clear();
         self.metadata.clear();
+        self.named.clear();
+        self.sparse.clear();
         Ok(())
     }
 }
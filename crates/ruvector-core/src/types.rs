@@ -1,5 +1,8 @@
 //! Core types and data structures
 
+use crate::advanced_features::NormalizationStrategy;
+use crate::error::{Result, RuvectorError};
+use ruvector_filter::FilterExpression;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 
@@ -30,6 +33,118 @@ pub struct VectorEntry {
     pub metadata: Option<HashMap<String, serde_json::Value>>,
 }
 
+/// Entry whose vector is embedded from text by the database's embedding
+/// provider
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TextEntry {
+    /// Optional ID (auto-generated if not provided)
+    pub id: Option<VectorId>,
+    /// Text to embed
+    pub text: String,
+    /// Optional metadata
+    pub metadata: Option<HashMap<String, serde_json::Value>>,
+}
+
+/// Sparse vector of term weights (e.g. SPLADE or BM25), indices strictly
+/// increasing
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct SparseVector {
+    /// Dimension (term) indices
+    pub indices: Vec<u32>,
+    /// Weight of each index
+    pub values: Vec<f32>,
+}
+
+impl SparseVector {
+    /// Create a sparse vector from unordered `(index, weight)` pairs
+    pub fn new(mut entries: Vec<(u32, f32)>) -> Result<Self> {
+        entries.sort_by_key(|&(index, _)| index);
+        let (indices, values) = entries.into_iter().unzip();
+        let vector = Self { indices, values };
+        vector.validate()?;
+        Ok(vector)
+    }
+
+    /// Check that indices and values pair up, indices strictly increase and
+    /// weights are finite
+    pub fn validate(&self) -> Result<()> {
+        if self.indices.len() != self.values.len() {
+            return Err(RuvectorError::InvalidInput(format!(
+                "Sparse vector has {} indices but {} values",
+                self.indices.len(),
+                self.values.len()
+            )));
+        }
+        if self.indices.windows(2).any(|pair| pair[0] >= pair[1]) {
+            return Err(RuvectorError::InvalidInput(
+                "Sparse vector indices must be strictly increasing".to_string(),
+            ));
+        }
+        if self.values.iter().any(|value| !value.is_finite()) {
+            return Err(RuvectorError::InvalidInput(
+                "Sparse vector weights must be finite".to_string(),
+            ));
+        }
+        Ok(())
+    }
+
+    /// Dot product with another sparse vector
+    pub fn dot(&self, other: &SparseVector) -> f32 {
+        let (mut i, mut j) = (0, 0);
+        let mut dot = 0.0;
+        while i < self.indices.len() && j < other.indices.len() {
+            match self.indices[i].cmp(&other.indices[j]) {
+                std::cmp::Ordering::Less => i += 1,
+                std::cmp::Ordering::Greater => j += 1,
+                std::cmp::Ordering::Equal => {
+                    dot += self.values[i] * other.values[j];
+                    i += 1;
+                    j += 1;
+                }
+            }
+        }
+        dot
+    }
+
+    /// Number of stored weights
+    pub fn len(&self) -> usize {
+        self.indices.len()
+    }
+
+    /// Whether the vector has no weights
+    pub fn is_empty(&self) -> bool {
+        self.indices.is_empty()
+    }
+}
+
+/// Change to the metadata of a stored vector that leaves its vector untouched
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum PayloadUpdate {
+    /// Replace the whole metadata
+    Set(HashMap<String, serde_json::Value>),
+    /// Insert or overwrite the given keys, keeping the others
+    Merge(HashMap<String, serde_json::Value>),
+    /// Remove the given keys
+    DeleteKeys(Vec<String>),
+}
+
+impl PayloadUpdate {
+    /// Apply the update to `metadata` in place
+    pub fn apply(&self, metadata: &mut HashMap<String, serde_json::Value>) {
+        match self {
+            PayloadUpdate::Set(fields) => *metadata = fields.clone(),
+            PayloadUpdate::Merge(fields) => {
+                metadata.extend(fields.iter().map(|(k, v)| (k.clone(), v.clone())))
+            }
+            PayloadUpdate::DeleteKeys(keys) => {
+                for key in keys {
+                    metadata.remove(key);
+                }
+            }
+        }
+    }
+}
+
 /// Search query parameters
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SearchQuery {
@@ -37,12 +152,53 @@ pub struct SearchQuery {
     pub vector: Vec<f32>,
     /// Number of results to return (top-k)
     pub k: usize,
-    /// Optional metadata filters
-    pub filter: Option<HashMap<String, serde_json::Value>>,
-    /// Optional ef_search parameter for HNSW (overrides default)
+    /// Optional metadata filter
+    pub filter: Option<SearchFilter>,
+    /// Optional ef_search parameter for HNSW (overrides the database default,
+    /// see [`VectorDB::set_ef_search`](crate::VectorDB::set_ef_search))
     pub ef_search: Option<usize>,
 }
 
+/// Metadata filter applied to a search
+///
+/// Deserializes from either a `ruvector_filter::FilterExpression` (an object
+/// with a `type` tag) or a plain map of field/value equality constraints.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum SearchFilter {
+    /// Full filter expression (range, in, geo, text match, logical operators)
+    Expression(FilterExpression),
+    /// Every field must equal the given value
+    Equals(HashMap<String, serde_json::Value>),
+}
+
+impl SearchFilter {
+    /// Convert into an equivalent filter expression
+    pub fn to_expression(&self) -> FilterExpression {
+        match self {
+            SearchFilter::Expression(expr) => expr.clone(),
+            SearchFilter::Equals(fields) => FilterExpression::and(
+                fields
+                    .iter()
+                    .map(|(field, value)| FilterExpression::eq(field.clone(), value.clone()))
+                    .collect(),
+            ),
+        }
+    }
+}
+
+impl From<FilterExpression> for SearchFilter {
+    fn from(expr: FilterExpression) -> Self {
+        SearchFilter::Expression(expr)
+    }
+}
+
+impl From<HashMap<String, serde_json::Value>> for SearchFilter {
+    fn from(fields: HashMap<String, serde_json::Value>) -> Self {
+        SearchFilter::Equals(fields)
+    }
+}
+
 /// Search result with similarity score
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SearchResult {
@@ -56,6 +212,203 @@ pub struct SearchResult {
     pub metadata: Option<HashMap<String, serde_json::Value>>,
 }
 
+/// Query combining several vector spaces into one ranking
+///
+/// Candidates are gathered from each component's index, then every candidate
+/// is scored as the weighted sum of its exact distances to the component
+/// vectors. Points missing a vector in any queried space are not returned.
+/// Distances of different metrics have different scales, which the weights
+/// have to account for.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FusedSearchQuery {
+    /// Query vectors with their weights
+    pub vectors: Vec<WeightedVector>,
+    /// Number of results to return (top-k)
+    pub k: usize,
+    /// Optional metadata filter
+    pub filter: Option<SearchFilter>,
+    /// Optional ef_search parameter for HNSW, applied to every space
+    pub ef_search: Option<usize>,
+}
+
+/// Query vector for one vector space of a [`FusedSearchQuery`]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WeightedVector {
+    /// Named vector space, `None` for the database's default vector
+    pub space: Option<String>,
+    /// Query vector
+    pub vector: Vec<f32>,
+    /// Weight of this space's distance in the fused score
+    pub weight: f32,
+}
+
+/// Query for every point within a distance of a query vector
+///
+/// Distances are compared as returned by search, so with
+/// [`DistanceMetric::DotProduct`] the radius bounds the negated dot product.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RangeSearchQuery {
+    /// Named vector space, `None` for the database's default vector
+    pub space: Option<String>,
+    /// Query vector
+    pub vector: Vec<f32>,
+    /// Largest distance of a returned point
+    pub radius: f32,
+    /// Maximum number of points to return, nearest first
+    pub max_results: usize,
+    /// Optional metadata filter
+    pub filter: Option<SearchFilter>,
+    /// Optional ef_search parameter for HNSW
+    pub ef_search: Option<usize>,
+}
+
+/// Query for the nearest neighbors of a stored point, using its own vector
+/// as the query
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SearchByIdQuery {
+    /// Stored point whose neighbors are searched; never part of the results
+    pub id: VectorId,
+    /// Named vector space, `None` for the database's default vector
+    pub space: Option<String>,
+    /// Number of results to return (top-k)
+    pub k: usize,
+    /// Optional metadata filter
+    pub filter: Option<SearchFilter>,
+    /// Optional ef_search parameter for HNSW
+    pub ef_search: Option<usize>,
+}
+
+/// Page request for iterating stored points in id order
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScrollRequest {
+    /// Opaque token from a previous page's `next_offset`, `None` to start at
+    /// the first point
+    pub offset: Option<String>,
+    /// Maximum number of points in the page
+    pub limit: usize,
+    /// Optional metadata filter; points that don't match are skipped
+    pub filter: Option<SearchFilter>,
+    /// Whether to include each point's vector
+    pub with_vector: bool,
+    /// Whether to include each point's metadata
+    pub with_payload: bool,
+}
+
+impl Default for ScrollRequest {
+    fn default() -> Self {
+        Self {
+            offset: None,
+            limit: 100,
+            filter: None,
+            with_vector: true,
+            with_payload: true,
+        }
+    }
+}
+
+/// A stored point with the parts a scroll or batch get asked for
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PointRecord {
+    /// Vector ID
+    pub id: VectorId,
+    /// Vector data, if requested
+    pub vector: Option<Vec<f32>>,
+    /// Metadata, if requested and present
+    pub metadata: Option<HashMap<String, serde_json::Value>>,
+}
+
+/// Search returning the best groups of points sharing a metadata value, e.g.
+/// the chunks of one document
+///
+/// Points are grouped by the value of their top-level `group_by` metadata
+/// field; points without it, or where it is null, an array or an object,
+/// are skipped.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GroupedSearchQuery {
+    /// Named vector space, `None` for the database's default vector
+    pub space: Option<String>,
+    /// Query vector
+    pub vector: Vec<f32>,
+    /// Metadata field whose value groups the hits
+    pub group_by: String,
+    /// Number of groups to return
+    pub groups: usize,
+    /// Maximum number of hits per group
+    pub group_size: usize,
+    /// Optional metadata filter
+    pub filter: Option<SearchFilter>,
+    /// Optional ef_search parameter for HNSW
+    pub ef_search: Option<usize>,
+}
+
+/// Hits sharing one value of [`GroupedSearchQuery::group_by`], best first
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PointGroup {
+    /// Value of the grouping field
+    pub key: serde_json::Value,
+    /// Best hits of the group
+    pub hits: Vec<SearchResult>,
+}
+
+/// One page of a scroll
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScrollPage {
+    /// Points in id order
+    pub points: Vec<PointRecord>,
+    /// Token resuming after this page, `None` once every point was visited
+    pub next_offset: Option<String>,
+}
+
+/// Query combining a dense and a sparse retriever into one ranking
+///
+/// Results are ordered by the fused score, higher is better.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HybridSearchQuery {
+    /// Named dense vector space, `None` for the database's default vector
+    pub dense_space: Option<String>,
+    /// Dense query vector
+    pub dense: Vec<f32>,
+    /// Sparse vector space
+    pub sparse_space: String,
+    /// Sparse query vector
+    pub sparse: SparseVector,
+    /// Number of results to return (top-k)
+    pub k: usize,
+    /// Optional metadata filter applied to both retrievers
+    pub filter: Option<SearchFilter>,
+    /// How the two rankings are combined
+    pub fusion: HybridFusion,
+    /// Optional ef_search parameter for the dense HNSW search
+    pub ef_search: Option<usize>,
+}
+
+/// Combination of dense and sparse rankings in a hybrid search
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum HybridFusion {
+    /// Reciprocal rank fusion: each ranking adds `1 / (k + rank)`, with ranks
+    /// starting at 1
+    Rrf {
+        /// Rank offset, 60 in the original formulation
+        k: f32,
+    },
+    /// Weighted sum of normalized similarities; dense distances are negated
+    /// first, and a point missing from one ranking gets 0 for it
+    Weighted {
+        /// Weight of the dense similarity
+        dense_weight: f32,
+        /// Weight of the sparse dot product
+        sparse_weight: f32,
+        /// Normalization applied to each ranking's scores
+        normalization: NormalizationStrategy,
+    },
+}
+
+impl Default for HybridFusion {
+    fn default() -> Self {
+        HybridFusion::Rrf { k: 60.0 }
+    }
+}
+
 /// Database configuration options
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct DbOptions {
@@ -67,8 +420,76 @@ pub struct DbOptions {
     pub storage_path: String,
     /// HNSW configuration
     pub hnsw_config: Option<HnswConfig>,
-    /// Quantization configuration
+    /// Quantization of the vectors held in memory by the HNSW index
+    ///
+    /// Full-precision vectors stay in storage and are used to rescore the
+    /// top candidates, see `VectorDB::set_rescore_oversampling`.
     pub quantization: Option<QuantizationConfig>,
+    /// When the database checkpoints on its own
+    #[serde(default)]
+    pub checkpoint: CheckpointConfig,
+}
+
+/// When a database checkpoints on its own, writing the index file and
+/// truncating the operation log on a background thread
+///
+/// A checkpoint starts once either limit is reached by the writes since the
+/// last one; see `VectorDB::checkpoint`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CheckpointConfig {
+    /// Operations logged, `None` to not count them
+    pub max_operations: Option<u64>,
+    /// Bytes of operations logged, `None` to not count them
+    pub max_log_bytes: Option<u64>,
+}
+
+impl Default for CheckpointConfig {
+    fn default() -> Self {
+        Self {
+            max_operations: Some(100_000),
+            max_log_bytes: Some(256 * 1024 * 1024),
+        }
+    }
+}
+
+/// Configuration of a named vector space
+///
+/// Besides the default vector every point may hold one vector per named
+/// space, each with its own dimensions, metric and index.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VectorSpaceConfig {
+    /// Vector dimensions
+    pub dimensions: usize,
+    /// Distance metric
+    pub distance_metric: DistanceMetric,
+    /// HNSW configuration, `None` for a flat index
+    pub hnsw_config: Option<HnswConfig>,
+    /// Quantization of the vectors held in memory by the HNSW index
+    pub quantization: Option<QuantizationConfig>,
+    /// Store a bag of vectors per point instead of a single one
+    #[serde(default)]
+    pub multivector: Option<MultiVectorConfig>,
+}
+
+/// Late-interaction (ColBERT-style) configuration of a vector space
+///
+/// Each point holds any number of `dimensions`-sized vectors, e.g. one per
+/// token, stored back to back. A query is itself a bag of vectors: every
+/// query vector fetches candidates from a token-level index, and candidates
+/// are ranked exactly by
+/// [`max_sim_distance`](crate::distance::max_sim_distance).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MultiVectorConfig {
+    /// Nearest stored vectors fetched per query vector to gather candidates
+    pub candidates_per_token: usize,
+}
+
+impl Default for MultiVectorConfig {
+    fn default() -> Self {
+        Self {
+            candidates_per_token: 32,
+        }
+    }
 }
 
 /// HNSW index configuration
@@ -82,6 +503,22 @@ pub struct HnswConfig {
     pub ef_search: usize,
     /// Maximum number of elements
     pub max_elements: usize,
+    /// Share of tombstoned slots (0.0-1.0) that triggers a background
+    /// compaction; 1.0 or more disables automatic compaction
+    #[serde(default = "default_compaction_threshold")]
+    pub compaction_threshold: f32,
+    /// Seed for the random layer each inserted node is assigned, so the
+    /// same inserts build the same graph; drawn from entropy if `None`
+    #[serde(default)]
+    pub seed: Option<u64>,
+}
+
+/// Default share of tombstoned HNSW slots that triggers a background
+/// compaction
+pub const DEFAULT_COMPACTION_THRESHOLD: f32 = 0.25;
+
+fn default_compaction_threshold() -> f32 {
+    DEFAULT_COMPACTION_THRESHOLD
 }
 
 impl Default for HnswConfig {
@@ -91,6 +528,8 @@ impl Default for HnswConfig {
             ef_construction: 200,
             ef_search: 100,
             max_elements: 10_000_000,
+            compaction_threshold: default_compaction_threshold(),
+            seed: None,
         }
     }
 }
@@ -121,6 +560,7 @@ impl Default for DbOptions {
             storage_path: "./ruvector.db".to_string(),
             hnsw_config: Some(HnswConfig::default()),
             quantization: Some(QuantizationConfig::Scalar),
+            checkpoint: CheckpointConfig::default(),
         }
     }
 }
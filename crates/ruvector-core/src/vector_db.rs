@@ -1,37 +1,154 @@
 //! Main VectorDB interface
 
-use crate::error::Result;
+use crate::advanced_features::hybrid_search::normalize_scores;
+use crate::changes::{point_changes, BoxedChangeListener};
+use crate::distance::{distance, max_sim_distance};
+use crate::embeddings::{BoxedEmbeddingProvider, EmbeddingConfig};
+use crate::error::{Result, RuvectorError};
 use crate::index::flat::FlatIndex;
 
 #[cfg(feature = "hnsw")]
 use crate::index::hnsw::HnswIndex;
 
-use crate::index::VectorIndex;
+use crate::index::multivector::MultiVectorIndex;
+use crate::index::sparse::SparseIndex;
+use crate::index::{expanding_range_search, AllowedIds, SearchParams, VectorIndex};
+use crate::oplog::Operation;
 use crate::types::*;
-use parking_lot::RwLock;
+use parking_lot::{Mutex, MutexGuard, RwLock, RwLockWriteGuard};
+use ruvector_filter::{
+    FilterError, FilterEvaluator, FilterExpression, IndexType, PayloadIndexManager,
+};
+use serde_json::Value;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::Arc;
 
 // Import appropriate storage backend based on features
 #[cfg(feature = "storage")]
+use crate::changes::ChangeListener;
+#[cfg(feature = "storage")]
+use crate::index_file::{index_file_path, write_index_file, IndexFile};
+#[cfg(feature = "storage")]
 use crate::storage::VectorStorage;
+#[cfg(feature = "storage")]
+use std::path::PathBuf;
+#[cfg(feature = "storage")]
+use std::sync::atomic::AtomicU64;
+#[cfg(feature = "storage")]
+use std::thread::JoinHandle;
 
+#[cfg(not(feature = "storage"))]
+use crate::oplog::LogEntry;
 #[cfg(not(feature = "storage"))]
 use crate::storage_memory::MemoryStorage as VectorStorage;
 
+/// Candidates fetched per requested result when a quantized index is
+/// rescored against the full-precision vectors in storage
+pub const DEFAULT_RESCORE_OVERSAMPLING: usize = 4;
+
+/// Candidates gathered per requested result from each vector space of a
+/// fused search
+pub const FUSED_CANDIDATES_PER_RESULT: usize = 4;
+
+/// Candidates up to which a grouped search keeps growing while it has found
+/// too few groups
+pub const GROUPED_SEARCH_MAX_CANDIDATES: usize = 10_000;
+
+/// Texts embedded per call to the embedding provider
+pub const EMBEDDING_BATCH_SIZE: usize = 64;
+
+/// Log entries read at a time while replaying the operation log
+#[cfg(feature = "storage")]
+const LOG_REPLAY_BATCH: usize = 1024;
+
+/// Index over the vectors of one named vector space
+struct VectorSpace {
+    config: VectorSpaceConfig,
+    index: Box<dyn VectorIndex>,
+}
+
+/// Write access to the indexes a write changes, locked in field order by all
+/// writers
+struct IndexGuards<'a> {
+    vectors: Option<RwLockWriteGuard<'a, Box<dyn VectorIndex>>>,
+    payload: Option<RwLockWriteGuard<'a, PayloadIndexManager>>,
+    spaces: Option<RwLockWriteGuard<'a, HashMap<String, VectorSpace>>>,
+    sparse: Option<RwLockWriteGuard<'a, HashMap<String, SparseIndex>>>,
+}
+
+impl IndexGuards<'_> {
+    fn vectors(&mut self) -> &mut Box<dyn VectorIndex> {
+        self.vectors.as_mut().expect("vector index is locked")
+    }
+
+    fn payload(&mut self) -> &mut PayloadIndexManager {
+        self.payload.as_mut().expect("payload indexes are locked")
+    }
+
+    fn spaces(&mut self) -> &mut HashMap<String, VectorSpace> {
+        self.spaces.as_mut().expect("vector spaces are locked")
+    }
+
+    fn sparse(&mut self) -> &mut HashMap<String, SparseIndex> {
+        self.sparse.as_mut().expect("sparse spaces are locked")
+    }
+}
+
 /// Main vector database
 pub struct VectorDB {
     storage: Arc<VectorStorage>,
     index: Arc<RwLock<Box<dyn VectorIndex>>>,
+    payload_indexes: RwLock<PayloadIndexManager>,
+    /// Named vector spaces by name
+    spaces: RwLock<HashMap<String, VectorSpace>>,
+    /// Sparse vector spaces by name
+    sparse: RwLock<HashMap<String, SparseIndex>>,
+    /// Provider embedding text for the default vector, with its persisted
+    /// configuration unless it was set at runtime
+    embedding: RwLock<Option<(Option<EmbeddingConfig>, BoxedEmbeddingProvider)>>,
+    /// Candidates per result to rescore exactly, 0 to trust index distances
+    rescore_oversampling: AtomicUsize,
+    /// HNSW beam width for queries that don't set one, 0 for the index default
+    ef_search: AtomicUsize,
+    /// Listeners told about every committed change to a point
+    change_listeners: RwLock<Vec<BoxedChangeListener>>,
+    /// Held by every write through this handle from before its storage
+    /// commit until the indexes reflect it, so writes apply in log order
+    writes: Mutex<()>,
+    /// Sequence of the last logged operation reflected by the indexes
+    #[cfg(feature = "storage")]
+    applied_sequence: Arc<AtomicU64>,
+    /// Sequence the index file on disk was written at, `u64::MAX` if none
+    #[cfg(feature = "storage")]
+    persisted_sequence: Arc<AtomicU64>,
+    /// Checkpoints started by writes, see [`CheckpointConfig`]
+    #[cfg(feature = "storage")]
+    auto_checkpoint: Mutex<AutoCheckpoint>,
     options: DbOptions,
 }
 
+/// Points a search is restricted to
+enum Allowed<'a> {
+    /// Points whose metadata matches a filter
+    Matching(FilterExpression),
+    /// Listed points
+    Ids(&'a HashSet<VectorId>),
+}
+
 impl VectorDB {
     /// Create a new vector database with the given options
     ///
-    /// If a storage path is provided and contains persisted vectors,
-    /// the HNSW index will be automatically rebuilt from storage.
+    /// If a storage path is provided and contains persisted vectors, the
+    /// HNSW graph is loaded from the index file next to it and brought up to
+    /// date by replaying the operations logged since it was written. Without
+    /// a usable index file it is rebuilt from the stored vectors.
     /// If opening an existing database, the stored configuration (dimensions,
     /// distance metric, etc.) will be used instead of the provided options.
+    /// Payload indexes and named and sparse vector spaces are stored with the
+    /// database too and restored as they were declared, whatever options it
+    /// is opened with; they only change through calls such as
+    /// [`VectorDB::create_payload_index`].
     pub fn new(mut options: DbOptions) -> Result<Self> {
         #[cfg(feature = "storage")]
         let storage = {
@@ -58,6 +175,8 @@ impl VectorDB {
                     distance_metric: config.distance_metric,
                     hnsw_config: config.hnsw_config,
                     quantization: config.quantization,
+                    // How often to checkpoint isn't a property of the data
+                    checkpoint: options.checkpoint.clone(),
                 };
                 // Recreate storage with correct dimensions
                 Arc::new(VectorStorage::new(
@@ -78,224 +197,3963 @@ impl VectorDB {
         #[cfg(not(feature = "storage"))]
         let storage = Arc::new(VectorStorage::new(options.dimensions)?);
 
+        let mut index = Self::create_index(&Self::default_space(&options))?;
+
+        #[cfg(feature = "storage")]
+        let restored = Self::load_index_file(&options, &storage);
+        #[cfg(feature = "storage")]
+        let (applied_sequence, persisted_sequence) = match restored {
+            Some((restored, sequence)) => {
+                index = restored;
+                (sequence, sequence)
+            }
+            None => {
+                // Rebuild index from persisted vectors if storage is not empty
+                // This fixes the bug where search() returns empty results after restart
+                let (sequence, vectors) = storage.snapshot_vectors()?;
+                if !vectors.is_empty() {
+                    tracing::info!("Rebuilding index from {} persisted vectors", vectors.len());
+
+                    // Add all vectors to index in batch for better performance
+                    index.add_batch(vectors)?;
+
+                    tracing::info!("Index rebuilt successfully");
+                }
+                (sequence, u64::MAX)
+            }
+        };
+
+        #[cfg(feature = "storage")]
+        let payload_indexes =
+            Self::build_payload_indexes(&storage, &storage.load_payload_indexes()?)?;
+        #[cfg(not(feature = "storage"))]
+        let payload_indexes = PayloadIndexManager::new();
+
+        // Read after the vector snapshot, so it is at least as recent
+        #[cfg(feature = "storage")]
+        let spaces = Self::build_vector_spaces(&storage, storage.load_vector_spaces()?)?;
+        #[cfg(not(feature = "storage"))]
+        let spaces = HashMap::new();
+        #[cfg(feature = "storage")]
+        let sparse = Self::build_sparse_spaces(&storage, storage.load_sparse_spaces()?)?;
+        #[cfg(not(feature = "storage"))]
+        let sparse = HashMap::new();
+
+        // A provider that can't be built, e.g. for a missing API key, only
+        // fails text operations
+        #[cfg(feature = "storage")]
+        let embedding = storage
+            .load_embedding_config()?
+            .and_then(|config| match config.build() {
+                Ok(provider) => Some((Some(config), provider)),
+                Err(e) => {
+                    tracing::warn!("Embedding provider unavailable: {}", e);
+                    None
+                }
+            });
+        #[cfg(not(feature = "storage"))]
+        let embedding = None;
+
+        let rescore_oversampling = if Self::is_quantized(&Self::default_space(&options)) {
+            DEFAULT_RESCORE_OVERSAMPLING
+        } else {
+            0
+        };
+
+        let db = Self {
+            storage,
+            index: Arc::new(RwLock::new(index)),
+            payload_indexes: RwLock::new(payload_indexes),
+            spaces: RwLock::new(spaces),
+            sparse: RwLock::new(sparse),
+            embedding: RwLock::new(embedding),
+            rescore_oversampling: AtomicUsize::new(rescore_oversampling),
+            ef_search: AtomicUsize::new(
+                options
+                    .hnsw_config
+                    .as_ref()
+                    .map_or(0, |config| config.ef_search),
+            ),
+            change_listeners: RwLock::new(Vec::new()),
+            writes: Mutex::new(()),
+            #[cfg(feature = "storage")]
+            applied_sequence: Arc::new(AtomicU64::new(applied_sequence)),
+            #[cfg(feature = "storage")]
+            persisted_sequence: Arc::new(AtomicU64::new(persisted_sequence)),
+            #[cfg(feature = "storage")]
+            auto_checkpoint: Mutex::new(AutoCheckpoint::new(applied_sequence)),
+            options,
+        };
+
+        // Replay whatever was logged after the index file was written
+        #[cfg(feature = "storage")]
+        {
+            let mut indexes = db.write_indexes();
+            db.catch_up(&mut indexes)?;
+        }
+
+        Ok(db)
+    }
+
+    /// Index configuration of the default vector
+    fn default_space(options: &DbOptions) -> VectorSpaceConfig {
+        VectorSpaceConfig {
+            dimensions: options.dimensions,
+            distance_metric: options.distance_metric,
+            hnsw_config: options.hnsw_config.clone(),
+            quantization: options.quantization.clone(),
+            multivector: None,
+        }
+    }
+
+    /// Whether the index for `config` returns approximate distances from
+    /// quantized vectors
+    fn is_quantized(config: &VectorSpaceConfig) -> bool {
+        // Only the HNSW index keeps quantized vectors; flat search is exact,
+        // and multi-vector indexes rank by their full-precision bags
+        cfg!(feature = "hnsw")
+            && config.hnsw_config.is_some()
+            && config.multivector.is_none()
+            && !matches!(config.quantization, None | Some(QuantizationConfig::None))
+    }
+
+    /// Distance between a query and a stored vector of a space
+    fn space_distance(config: &VectorSpaceConfig, query: &[f32], vector: &[f32]) -> Result<f32> {
+        match config.multivector {
+            Some(_) => max_sim_distance(query, vector, config.dimensions, config.distance_metric),
+            None => distance(query, vector, config.distance_metric),
+        }
+    }
+
+    /// Create an empty index for `config`
+    fn create_index(config: &VectorSpaceConfig) -> Result<Box<dyn VectorIndex>> {
+        if let Some(multivector) = &config.multivector {
+            let tokens = Self::create_index(&VectorSpaceConfig {
+                multivector: None,
+                ..config.clone()
+            })?;
+            return Ok(Box::new(MultiVectorIndex::new(
+                tokens,
+                config.dimensions,
+                config.distance_metric,
+                multivector.candidates_per_token,
+            )));
+        }
+
         // Choose index based on configuration and available features
-        let mut index: Box<dyn VectorIndex> = if let Some(hnsw_config) = &options.hnsw_config {
+        let index: Box<dyn VectorIndex> = if let Some(hnsw_config) = &config.hnsw_config {
             #[cfg(feature = "hnsw")]
             {
-                Box::new(HnswIndex::new(
-                    options.dimensions,
-                    options.distance_metric,
+                let mut hnsw = HnswIndex::new(
+                    config.dimensions,
+                    config.distance_metric,
                     hnsw_config.clone(),
-                )?)
+                )?;
+                if let Some(quantization) = &config.quantization {
+                    hnsw = hnsw.with_quantization(quantization)?;
+                }
+                Box::new(hnsw)
             }
             #[cfg(not(feature = "hnsw"))]
             {
                 // Fall back to flat index if HNSW is not available
                 tracing::warn!("HNSW requested but not available (WASM build), using flat index");
-                Box::new(FlatIndex::new(options.dimensions, options.distance_metric))
+                Box::new(FlatIndex::new(config.dimensions, config.distance_metric))
+            }
+        } else {
+            Box::new(FlatIndex::new(config.dimensions, config.distance_metric))
+        };
+
+        Ok(index)
+    }
+
+    /// Create the declared vector spaces and fill them from storage
+    #[cfg(feature = "storage")]
+    fn build_vector_spaces(
+        storage: &VectorStorage,
+        definitions: BTreeMap<String, VectorSpaceConfig>,
+    ) -> Result<HashMap<String, VectorSpace>> {
+        let mut spaces = HashMap::with_capacity(definitions.len());
+        if definitions.is_empty() {
+            return Ok(spaces);
+        }
+
+        for (name, config) in definitions {
+            let index = Self::create_index(&config)?;
+            spaces.insert(name, VectorSpace { config, index });
+        }
+        let (_, mut vectors) = storage.snapshot_named_vectors()?;
+        for (name, space) in &mut spaces {
+            if let Some(vectors) = vectors.remove(name) {
+                space.index.add_batch(vectors)?;
+            }
+        }
+        tracing::info!("Rebuilt {} vector spaces", spaces.len());
+
+        Ok(spaces)
+    }
+
+    /// Create the declared sparse vector spaces and fill them from storage
+    #[cfg(feature = "storage")]
+    fn build_sparse_spaces(
+        storage: &VectorStorage,
+        names: BTreeSet<String>,
+    ) -> Result<HashMap<String, SparseIndex>> {
+        let mut spaces: HashMap<String, SparseIndex> = names
+            .into_iter()
+            .map(|name| (name, SparseIndex::new()))
+            .collect();
+        if spaces.is_empty() {
+            return Ok(spaces);
+        }
+
+        let (_, mut vectors) = storage.snapshot_sparse_vectors()?;
+        for (name, index) in &mut spaces {
+            for (id, vector) in vectors.remove(name).unwrap_or_default() {
+                index.insert(id, vector);
+            }
+        }
+        tracing::info!("Rebuilt {} sparse vector spaces", spaces.len());
+
+        Ok(spaces)
+    }
+
+    fn space_definitions(
+        spaces: &HashMap<String, VectorSpace>,
+    ) -> BTreeMap<String, VectorSpaceConfig> {
+        spaces
+            .iter()
+            .map(|(name, space)| (name.clone(), space.config.clone()))
+            .collect()
+    }
+
+    /// Keep other writes through this handle out until the guard is
+    /// dropped, which [`VectorDB::apply_written`] expects
+    fn begin_write(&self) -> MutexGuard<'_, ()> {
+        self.writes.lock()
+    }
+
+    /// Lock every index for writing
+    fn write_indexes(&self) -> IndexGuards<'_> {
+        IndexGuards {
+            vectors: Some(self.index.write()),
+            payload: Some(self.payload_indexes.write()),
+            spaces: Some(self.spaces.write()),
+            sparse: Some(self.sparse.write()),
+        }
+    }
+
+    /// Lock the indexes `operations` change for writing, in the same order
+    /// as [`VectorDB::write_indexes`]
+    fn lock_indexes(&self, operations: &[Operation]) -> IndexGuards<'_> {
+        let (mut vectors, mut payload, mut spaces, mut sparse) = (false, false, false, false);
+        for operation in operations {
+            match operation {
+                Operation::Upsert { .. } => {
+                    vectors = true;
+                    payload = true;
+                }
+                Operation::Delete { .. } => return self.write_indexes(),
+                Operation::UpdatePayload { .. } => payload = true,
+                Operation::UpsertNamed { .. }
+                | Operation::DeleteNamed { .. }
+                | Operation::DropVectorSpace { .. } => spaces = true,
+                Operation::UpsertSparse { .. }
+                | Operation::DeleteSparse { .. }
+                | Operation::DropSparseSpace { .. } => sparse = true,
+            }
+        }
+        IndexGuards {
+            vectors: vectors.then(|| self.index.write()),
+            payload: payload.then(|| self.payload_indexes.write()),
+            spaces: spaces.then(|| self.spaces.write()),
+            sparse: sparse.then(|| self.sparse.write()),
+        }
+    }
+
+    /// Create the declared payload indexes and fill them from storage
+    #[cfg(feature = "storage")]
+    fn build_payload_indexes(
+        storage: &VectorStorage,
+        definitions: &BTreeMap<String, IndexType>,
+    ) -> Result<PayloadIndexManager> {
+        let mut payload_indexes = PayloadIndexManager::new();
+        if definitions.is_empty() {
+            return Ok(payload_indexes);
+        }
+
+        for (field, index_type) in definitions {
+            payload_indexes.create_index(field, *index_type)?;
+        }
+        let mut result = Ok(());
+        storage.scan_metadata(|id, metadata| {
+            if result.is_ok() {
+                result = payload_indexes.index_payload(id, metadata);
+            }
+        })?;
+        result?;
+        tracing::info!("Rebuilt {} payload indexes", definitions.len());
+
+        Ok(payload_indexes)
+    }
+
+    /// Restore the index from its file if the operations logged since it
+    /// was written are still available; anything unusable is logged and
+    /// skipped
+    #[cfg(feature = "storage")]
+    fn load_index_file(
+        options: &DbOptions,
+        storage: &VectorStorage,
+    ) -> Option<(Box<dyn VectorIndex>, u64)> {
+        #[cfg(feature = "hnsw")]
+        if options.hnsw_config.is_some() {
+            let path = index_file_path(&options.storage_path);
+            let restored = (|| -> Result<Option<(HnswIndex, u64)>> {
+                let Some(file) = IndexFile::open(&path)? else {
+                    return Ok(None);
+                };
+                let sequence = file.sequence();
+                let last = storage.last_sequence()?;
+                if sequence > last || storage.read_log(sequence, 1)?.is_none() {
+                    tracing::info!(
+                        "Index file {} is stale (sequence {}, storage at {})",
+                        path.display(),
+                        sequence,
+                        last
+                    );
+                    return Ok(None);
+                }
+
+                let index = HnswIndex::deserialize(file.payload())?;
+                Ok(Some((index, sequence)))
+            })();
+
+            match restored {
+                Ok(Some((index, sequence))) => {
+                    tracing::info!(
+                        "Loaded {} indexed vectors from {} at sequence {}",
+                        index.len(),
+                        path.display(),
+                        sequence
+                    );
+                    return Some((Box::new(index), sequence));
+                }
+                Ok(None) => {}
+                Err(e) => tracing::warn!("Ignoring index file {}: {}", path.display(), e),
+            }
+        }
+
+        None
+    }
+
+    /// Write the index graph next to the storage file so the next open
+    /// loads it instead of rebuilding the index from the stored vectors
+    ///
+    /// Returns `false` without writing if the index has no serialized form
+    /// (the flat index). See [`VectorDB::checkpoint`] to also release the
+    /// operation log the file makes redundant.
+    #[cfg(feature = "storage")]
+    pub fn persist_index(&self) -> Result<bool> {
+        let mut auto = self.auto_checkpoint.lock();
+        auto.join();
+        Ok(self.checkpointer().write_index_file()?.is_some())
+    }
+
+    /// Persist the index and truncate the operation log up to the sequence
+    /// it reflects, returning that sequence
+    ///
+    /// This happens automatically when the database is dropped after any
+    /// change, and on a background thread as writes reach the limits of
+    /// [`DbOptions::checkpoint`].
+    #[cfg(feature = "storage")]
+    pub fn checkpoint(&self) -> Result<u64> {
+        let mut auto = self.auto_checkpoint.lock();
+        auto.join();
+        auto.mark(self.applied_sequence(), self.storage.logged_bytes());
+        self.checkpointer().checkpoint()
+    }
+
+    /// Block until a checkpoint started by writes has finished
+    #[cfg(feature = "storage")]
+    pub fn wait_for_checkpoint(&self) {
+        self.auto_checkpoint.lock().join();
+    }
+
+    /// Start a checkpoint on a background thread if the writes since the
+    /// last one reached a limit of [`DbOptions::checkpoint`]
+    #[cfg(feature = "storage")]
+    fn maybe_checkpoint(&self) {
+        // Skipped while a checkpoint is being taken in the foreground
+        let Some(mut auto) = self.auto_checkpoint.try_lock() else {
+            return;
+        };
+        if auto
+            .thread
+            .as_ref()
+            .is_some_and(|thread| !thread.is_finished())
+        {
+            return;
+        }
+
+        let config = &self.options.checkpoint;
+        let sequence = self.applied_sequence();
+        let bytes = self.storage.logged_bytes();
+        let due = config
+            .max_operations
+            .is_some_and(|max| sequence.saturating_sub(auto.sequence) >= max)
+            || config
+                .max_log_bytes
+                .is_some_and(|max| bytes.saturating_sub(auto.bytes) >= max);
+        if !due {
+            return;
+        }
+
+        auto.join();
+        auto.mark(sequence, bytes);
+        let checkpointer = self.checkpointer();
+        auto.thread = Some(std::thread::spawn(move || {
+            if let Err(e) = checkpointer.checkpoint() {
+                tracing::warn!("Background checkpoint failed: {}", e);
+            }
+        }));
+    }
+
+    #[cfg(feature = "storage")]
+    fn checkpointer(&self) -> Checkpointer {
+        Checkpointer {
+            storage: self.storage.clone(),
+            index: self.index.clone(),
+            applied_sequence: self.applied_sequence.clone(),
+            persisted_sequence: self.persisted_sequence.clone(),
+            path: index_file_path(&self.options.storage_path),
+            listeners: self.change_listeners.read().clone(),
+        }
+    }
+
+    /// Sequence of the last logged operation reflected by the indexes
+    ///
+    /// Writes made through other handles on the same storage file are
+    /// applied on this handle's next write.
+    #[cfg(feature = "storage")]
+    pub fn applied_sequence(&self) -> u64 {
+        self.applied_sequence.load(Ordering::SeqCst)
+    }
+
+    /// Replay logged operations the indexes have not applied yet
+    ///
+    /// If the log no longer reaches back far enough, the indexes are rebuilt
+    /// from the stored vectors first.
+    #[cfg(feature = "storage")]
+    fn catch_up(&self, indexes: &mut IndexGuards<'_>) -> Result<()> {
+        loop {
+            let applied = self.applied_sequence.load(Ordering::SeqCst);
+            let Some(entries) = self.storage.read_log(applied, LOG_REPLAY_BATCH)? else {
+                tracing::warn!(
+                    "Operation log was truncated past sequence {}, rebuilding indexes",
+                    applied
+                );
+                let (sequence, vectors) = self.storage.snapshot_vectors()?;
+                let mut rebuilt = Self::create_index(&Self::default_space(&self.options))?;
+                rebuilt.add_batch(vectors)?;
+                *indexes.vectors() = rebuilt;
+                *indexes.payload() = Self::build_payload_indexes(
+                    &self.storage,
+                    &Self::index_definitions(indexes.payload()),
+                )?;
+                *indexes.spaces() = Self::build_vector_spaces(
+                    &self.storage,
+                    Self::space_definitions(indexes.spaces()),
+                )?;
+                *indexes.sparse() = Self::build_sparse_spaces(
+                    &self.storage,
+                    indexes.sparse().keys().cloned().collect(),
+                )?;
+                self.applied_sequence.store(sequence, Ordering::SeqCst);
+                continue;
+            };
+
+            let Some(last) = entries.last().map(|entry| entry.sequence) else {
+                return Ok(());
+            };
+            Self::apply_operations(indexes, entries.into_iter().map(|entry| entry.operation))?;
+            self.applied_sequence.store(last, Ordering::SeqCst);
+        }
+    }
+
+    /// Bring the indexes up to date after the caller committed `written`,
+    /// holding the guard from [`VectorDB::begin_write`] since before the
+    /// commit
+    ///
+    /// Only the indexes the operations change are locked, after the commit.
+    /// With file storage, operations committed through other handles in
+    /// between, or left unapplied by a failed write, are replayed from the
+    /// log instead, so a failure part way leaves nothing that the next write
+    /// or open won't reconcile.
+    fn apply_written(&self, written: Vec<Operation>) -> Result<()> {
+        #[cfg(feature = "storage")]
+        {
+            let Some((first, last)) = self.storage.last_appended() else {
+                return Ok(());
+            };
+            if self.applied_sequence.load(Ordering::SeqCst) + 1 == first {
+                // Held until the sequence is advanced, so a checkpoint never
+                // sees the operations in the graph under an older sequence
+                let mut indexes = self.lock_indexes(&written);
+                Self::apply_operations(&mut indexes, written)?;
+                self.applied_sequence.store(last, Ordering::SeqCst);
+                drop(indexes);
+            } else {
+                self.catch_up(&mut self.write_indexes())?;
+            }
+            self.maybe_checkpoint();
+            Ok(())
+        }
+        #[cfg(not(feature = "storage"))]
+        Self::apply_operations(&mut self.lock_indexes(&written), written)
+    }
+
+    /// [`VectorDB::apply_written`], then tell the change listeners about
+    /// the points `written` changed, given them as they were before the
+    /// commit, and flush the listeners once `writing` is released
+    ///
+    /// `before` is `None` if there were no listeners to capture it for.
+    fn apply_and_notify(
+        &self,
+        writing: MutexGuard<'_, ()>,
+        written: Vec<Operation>,
+        before: Option<HashMap<VectorId, VectorEntry>>,
+    ) -> Result<()> {
+        let Some(before) = before else {
+            return self.apply_written(written);
+        };
+        // The logged operations carry the sequences the changes report
+        #[cfg(feature = "storage")]
+        let logged = self.storage.last_written();
+        #[cfg(not(feature = "storage"))]
+        let logged = Ok(written
+            .iter()
+            .map(|operation| LogEntry {
+                sequence: 0,
+                operation: operation.clone(),
+            })
+            .collect::<Vec<_>>());
+        let mut result = self.apply_written(written);
+
+        // Committed either way, so listeners hear of it even if an index lags
+        let listeners = self.change_listeners.read().clone();
+        match logged {
+            Ok(logged) => {
+                let changes = point_changes(Some(before), &logged, |_| None);
+                if !changes.is_empty() {
+                    for listener in &listeners {
+                        result = result.and(listener.on_change(&changes));
+                    }
+                }
+            }
+            Err(e) => result = result.and(Err(e)),
+        }
+
+        drop(writing);
+        for listener in &listeners {
+            result = result.and(listener.flush());
+        }
+        result
+    }
+
+    /// The stored points among `ids`, for [`VectorDB::apply_and_notify`];
+    /// `None` without reading them if there are no change listeners
+    fn points_before<'a>(
+        &self,
+        ids: impl IntoIterator<Item = &'a str>,
+    ) -> Result<Option<HashMap<VectorId, VectorEntry>>> {
+        if self.change_listeners.read().is_empty() {
+            return Ok(None);
+        }
+
+        let ids: Vec<&str> = ids.into_iter().collect();
+        let points = self
+            .storage
+            .get_batch(&ids)?
+            .into_iter()
+            .zip(&ids)
+            .filter_map(|(entry, id)| Some((id.to_string(), entry?)))
+            .collect();
+        Ok(Some(points))
+    }
+
+    /// Apply operations to the vector, payload, vector space and sparse
+    /// indexes in order
+    ///
+    /// Operations on vector spaces this handle doesn't know are skipped.
+    fn apply_operations(
+        indexes: &mut IndexGuards<'_>,
+        operations: impl IntoIterator<Item = Operation>,
+    ) -> Result<()> {
+        // Consecutive upserts are added as one batch
+        let mut upserts = Vec::new();
+        for operation in operations {
+            match operation {
+                Operation::Upsert {
+                    id,
+                    vector,
+                    metadata,
+                } => {
+                    Self::index_payload(indexes.payload(), &id, metadata.as_ref())?;
+                    upserts.push((id, vector));
+                }
+                Operation::Delete { id } => {
+                    if !upserts.is_empty() {
+                        indexes.vectors().add_batch(std::mem::take(&mut upserts))?;
+                    }
+                    indexes.payload().clear_vector(&id);
+                    indexes.vectors().remove(&id)?;
+                    for space in indexes.spaces().values_mut() {
+                        space.index.remove(&id)?;
+                    }
+                    for index in indexes.sparse().values_mut() {
+                        index.remove(&id);
+                    }
+                }
+                Operation::UpdatePayload { id, metadata } => {
+                    Self::index_payload(indexes.payload(), &id, metadata.as_ref())?;
+                }
+                Operation::UpsertNamed { id, space, vector } => {
+                    if let Some(space) = indexes.spaces().get_mut(&space) {
+                        space.index.add(id, vector)?;
+                    }
+                }
+                Operation::DeleteNamed { id, space } => {
+                    if let Some(space) = indexes.spaces().get_mut(&space) {
+                        space.index.remove(&id)?;
+                    }
+                }
+                Operation::DropVectorSpace { space } => {
+                    if let Some(space) = indexes.spaces().get_mut(&space) {
+                        space.index = Self::create_index(&space.config)?;
+                    }
+                }
+                Operation::UpsertSparse { id, space, vector } => {
+                    if let Some(index) = indexes.sparse().get_mut(&space) {
+                        index.insert(id, vector);
+                    }
+                }
+                Operation::DeleteSparse { id, space } => {
+                    if let Some(index) = indexes.sparse().get_mut(&space) {
+                        index.remove(&id);
+                    }
+                }
+                Operation::DropSparseSpace { space } => {
+                    if let Some(index) = indexes.sparse().get_mut(&space) {
+                        *index = SparseIndex::new();
+                    }
+                }
+            }
+        }
+        if !upserts.is_empty() {
+            indexes.vectors().add_batch(upserts)?;
+        }
+
+        Ok(())
+    }
+
+    /// Create with default options
+    pub fn with_dimensions(dimensions: usize) -> Result<Self> {
+        let mut options = DbOptions::default();
+        options.dimensions = dimensions;
+        Self::new(options)
+    }
+
+    /// Configure the provider embedding text for the default vector,
+    /// persisting the choice with the database, or remove it with `None`
+    pub fn set_embedding(&self, config: Option<EmbeddingConfig>) -> Result<()> {
+        let embedding = match config {
+            Some(config) => {
+                self.check_embedding_dimensions(config.dimensions())?;
+                let provider = config.build()?;
+                Some((Some(config), provider))
+            }
+            None => None,
+        };
+
+        #[cfg(feature = "storage")]
+        self.storage
+            .save_embedding_config(embedding.as_ref().and_then(|(config, _)| config.as_ref()))?;
+        *self.embedding.write() = embedding;
+        Ok(())
+    }
+
+    /// Use `provider` to embed text until the database is reopened
+    ///
+    /// Unlike [`VectorDB::set_embedding`] this accepts any provider, but
+    /// nothing is persisted.
+    pub fn set_embedding_provider(&self, provider: BoxedEmbeddingProvider) -> Result<()> {
+        self.check_embedding_dimensions(provider.dimensions())?;
+        *self.embedding.write() = Some((None, provider));
+        Ok(())
+    }
+
+    /// Tell `listener` about every change to a point committed from now on,
+    /// until the database is reopened
+    ///
+    /// With file storage, a listener reporting where it left off through
+    /// [`ChangeListener::resume_after`](crate::ChangeListener::resume_after)
+    /// is first told about the changes logged since. See [`crate::changes`]
+    /// for what is reported.
+    pub fn add_change_listener(&self, listener: BoxedChangeListener) -> Result<()> {
+        // Keeps writers out, so no change falls between the replay and the
+        // registration
+        let writing = self.begin_write();
+        #[cfg(feature = "storage")]
+        if let Some(after) = listener.resume_after() {
+            self.replay_changes(listener.as_ref(), after)?;
+        }
+        self.change_listeners.write().push(listener.clone());
+        drop(writing);
+        listener.flush()
+    }
+
+    /// Tell `listener` about the changes to points logged after sequence
+    /// `after`, whose prior state is unknown
+    #[cfg(feature = "storage")]
+    fn replay_changes(&self, listener: &dyn ChangeListener, mut after: u64) -> Result<()> {
+        loop {
+            let Some(entries) = self.storage.read_log(after, LOG_REPLAY_BATCH)? else {
+                tracing::warn!(
+                    "Operation log was truncated past sequence {}, so changes since can't be replayed",
+                    after
+                );
+                return Ok(());
+            };
+            let Some(last) = entries.last().map(|entry| entry.sequence) else {
+                return Ok(());
+            };
+
+            let changes = point_changes(None, &entries, |id| self.storage.get(id).ok().flatten());
+            if !changes.is_empty() {
+                listener.on_change(&changes)?;
+            }
+            after = last;
+        }
+    }
+
+    /// Persisted embedding provider configuration, `None` if there is none
+    /// or the provider was set at runtime
+    pub fn embedding_config(&self) -> Option<EmbeddingConfig> {
+        self.embedding
+            .read()
+            .as_ref()
+            .and_then(|(config, _)| config.clone())
+    }
+
+    fn check_embedding_dimensions(&self, dimensions: usize) -> Result<()> {
+        if dimensions != self.options.dimensions {
+            return Err(RuvectorError::DimensionMismatch {
+                expected: self.options.dimensions,
+                actual: dimensions,
+            });
+        }
+        Ok(())
+    }
+
+    /// Embed `texts` with the configured provider, in batches of
+    /// [`EMBEDDING_BATCH_SIZE`]
+    pub fn embed(&self, texts: &[&str]) -> Result<Vec<Vec<f32>>> {
+        let provider = match &*self.embedding.read() {
+            Some((_, provider)) => provider.clone(),
+            None => {
+                return Err(RuvectorError::InvalidParameter(
+                    "No embedding provider is configured".to_string(),
+                ))
+            }
+        };
+
+        let mut vectors = Vec::with_capacity(texts.len());
+        for batch in texts.chunks(EMBEDDING_BATCH_SIZE) {
+            let embedded = provider.embed_batch(batch)?;
+            if embedded.len() != batch.len() {
+                return Err(RuvectorError::ModelInferenceError(format!(
+                    "{} returned {} embeddings for {} texts",
+                    provider.name(),
+                    embedded.len(),
+                    batch.len()
+                )));
+            }
+            vectors.extend(embedded);
+        }
+        Ok(vectors)
+    }
+
+    /// Embed the text of each entry and upsert the results as in
+    /// [`VectorDB::upsert_batch`]
+    pub fn upsert_texts(&self, entries: Vec<TextEntry>) -> Result<Vec<VectorId>> {
+        let texts: Vec<&str> = entries.iter().map(|entry| entry.text.as_str()).collect();
+        let vectors = self.embed(&texts)?;
+        let entries = entries
+            .into_iter()
+            .zip(vectors)
+            .map(|(entry, vector)| VectorEntry {
+                id: entry.id,
+                vector,
+                metadata: entry.metadata,
+            })
+            .collect();
+        self.upsert_batch(entries)
+    }
+
+    /// Search the default vector with the embedding of `text`
+    pub fn search_text(
+        &self,
+        text: &str,
+        k: usize,
+        filter: Option<SearchFilter>,
+    ) -> Result<Vec<SearchResult>> {
+        let vector = self.embed(&[text])?.pop().unwrap_or_default();
+        self.search(SearchQuery {
+            vector,
+            k,
+            filter,
+            ef_search: None,
+        })
+    }
+
+    /// Insert a vector entry
+    ///
+    /// An entry whose id is already stored replaces it, see [`VectorDB::upsert`].
+    pub fn insert(&self, entry: VectorEntry) -> Result<VectorId> {
+        self.upsert(entry)
+    }
+
+    /// Insert multiple vectors in a batch, replacing entries whose id is
+    /// already stored
+    pub fn insert_batch(&self, entries: Vec<VectorEntry>) -> Result<Vec<VectorId>> {
+        self.upsert_batch(entries)
+    }
+
+    /// Insert a vector entry or replace the stored entry with the same id
+    ///
+    /// The vector and metadata are replaced together in one storage
+    /// transaction, so an entry upserted without metadata loses any it had.
+    /// The index retires the previous vector instead of keeping both.
+    pub fn upsert(&self, entry: VectorEntry) -> Result<VectorId> {
+        let writing = self.begin_write();
+        let before = self.points_before(entry.id.as_deref())?;
+        let id = self.storage.insert(&entry)?;
+
+        let written = vec![Operation::Upsert {
+            id: id.clone(),
+            vector: entry.vector,
+            metadata: entry.metadata,
+        }];
+        self.apply_and_notify(writing, written, before)?;
+
+        Ok(id)
+    }
+
+    /// Upsert multiple vectors in one storage transaction
+    ///
+    /// When an id appears more than once the last entry wins.
+    pub fn upsert_batch(&self, entries: Vec<VectorEntry>) -> Result<Vec<VectorId>> {
+        let writing = self.begin_write();
+        let before = self.points_before(entries.iter().filter_map(|entry| entry.id.as_deref()))?;
+        let ids = self.storage.insert_batch(&entries)?;
+
+        let written = ids
+            .iter()
+            .zip(entries)
+            .map(|(id, entry)| Operation::Upsert {
+                id: id.clone(),
+                vector: entry.vector,
+                metadata: entry.metadata,
+            })
+            .collect();
+        self.apply_and_notify(writing, written, before)?;
+
+        Ok(ids)
+    }
+
+    /// Upsert a vector entry together with its named vectors
+    ///
+    /// Replaces the whole point in one storage transaction: the default
+    /// vector, the metadata and the named vectors, so named vectors in
+    /// spaces missing from `vectors` are removed. [`VectorDB::upsert`] leaves
+    /// named vectors untouched.
+    pub fn upsert_with_vectors(
+        &self,
+        entry: VectorEntry,
+        vectors: HashMap<String, Vec<f32>>,
+    ) -> Result<VectorId> {
+        // Spaces are only declared and dropped by writers, so they stay as
+        // validated until the write is applied
+        let writing = self.begin_write();
+        let spaces: Vec<String> = {
+            let spaces = self.spaces.read();
+            Self::validate_vectors(&spaces, &vectors)?;
+            spaces.keys().cloned().collect()
+        };
+        let before = self.points_before(entry.id.as_deref())?;
+        let id = self.storage.insert_with_vectors(&entry, &vectors)?;
+
+        let mut written = vec![Operation::Upsert {
+            id: id.clone(),
+            vector: entry.vector,
+            metadata: entry.metadata,
+        }];
+        written.extend(
+            spaces
+                .into_iter()
+                .filter(|space| !vectors.contains_key(space))
+                .map(|space| Operation::DeleteNamed {
+                    id: id.clone(),
+                    space,
+                }),
+        );
+        written.extend(
+            vectors
+                .into_iter()
+                .map(|(space, vector)| Operation::UpsertNamed {
+                    id: id.clone(),
+                    space,
+                    vector,
+                }),
+        );
+        self.apply_and_notify(writing, written, before)?;
+
+        Ok(id)
+    }
+
+    /// Set named vectors of a stored point, keeping its other vectors;
+    /// returns whether `id` exists
+    pub fn update_vectors(&self, id: &str, vectors: HashMap<String, Vec<f32>>) -> Result<bool> {
+        let _writing = self.begin_write();
+        Self::validate_vectors(&self.spaces.read(), &vectors)?;
+        let updates: Vec<(String, Option<Vec<f32>>)> = vectors
+            .into_iter()
+            .map(|(space, vector)| (space, Some(vector)))
+            .collect();
+        if !self.storage.update_vectors(id, &updates)? {
+            return Ok(false);
+        }
+
+        let written = updates
+            .into_iter()
+            .filter_map(|(space, vector)| {
+                Some(Operation::UpsertNamed {
+                    id: id.to_string(),
+                    space,
+                    vector: vector?,
+                })
+            })
+            .collect();
+        self.apply_written(written)?;
+
+        Ok(true)
+    }
+
+    /// Remove named vectors of a stored point, returning whether `id` exists
+    pub fn delete_vectors(&self, id: &str, spaces: &[String]) -> Result<bool> {
+        let _writing = self.begin_write();
+        {
+            let declared = self.spaces.read();
+            for space in spaces {
+                Self::space(&declared, space)?;
+            }
+        }
+        let updates: Vec<(String, Option<Vec<f32>>)> =
+            spaces.iter().map(|space| (space.clone(), None)).collect();
+        if !self.storage.update_vectors(id, &updates)? {
+            return Ok(false);
+        }
+
+        let written = spaces
+            .iter()
+            .map(|space| Operation::DeleteNamed {
+                id: id.to_string(),
+                space: space.clone(),
+            })
+            .collect();
+        self.apply_written(written)?;
+
+        Ok(true)
+    }
+
+    /// Named vectors of a stored point by space name, `None` if `id` is not
+    /// stored
+    pub fn get_vectors(&self, id: &str) -> Result<Option<HashMap<String, Vec<f32>>>> {
+        if self.storage.get(id)?.is_none() {
+            return Ok(None);
+        }
+        self.storage.get_vectors(id).map(Some)
+    }
+
+    /// Set the bag of vectors of a stored point in a multi-vector space,
+    /// returning whether `id` exists
+    pub fn update_multivector(&self, id: &str, space: &str, vectors: &[Vec<f32>]) -> Result<bool> {
+        let bag = Self::flatten_bag(vectors)?;
+        self.update_vectors(id, HashMap::from([(space.to_string(), bag)]))
+    }
+
+    /// Late-interaction search of a multi-vector space with a bag of query
+    /// vectors
+    ///
+    /// Scores are [`max_sim_distance`]s, so lower is better; with the dot
+    /// product metric a score is the negated ColBERT MaxSim.
+    pub fn search_multivector(
+        &self,
+        space: &str,
+        vectors: &[Vec<f32>],
+        k: usize,
+        filter: Option<SearchFilter>,
+    ) -> Result<Vec<SearchResult>> {
+        let query = SearchQuery {
+            vector: Self::flatten_bag(vectors)?,
+            k,
+            filter,
+            ef_search: None,
+        };
+        self.search_named(space, query)
+    }
+
+    /// Concatenate equally sized vectors into the stored bag layout
+    fn flatten_bag(vectors: &[Vec<f32>]) -> Result<Vec<f32>> {
+        let Some(first) = vectors.first() else {
+            return Err(RuvectorError::InvalidInput(
+                "Multi-vector needs at least one vector".to_string(),
+            ));
+        };
+        if let Some(vector) = vectors.iter().find(|v| v.len() != first.len()) {
+            return Err(RuvectorError::DimensionMismatch {
+                expected: first.len(),
+                actual: vector.len(),
+            });
+        }
+        Ok(vectors.concat())
+    }
+
+    /// Search for similar vectors
+    ///
+    /// Metadata filters are resolved to the set of matching ids up front and
+    /// applied during the index walk, so a selective filter still returns `k`
+    /// results when at least `k` vectors match. Filters over fields with a
+    /// payload index are answered from the index; anything else falls back
+    /// to scanning stored metadata.
+    pub fn search(&self, query: SearchQuery) -> Result<Vec<SearchResult>> {
+        let allowed = query
+            .filter
+            .as_ref()
+            .map(|filter| Allowed::Matching(filter.to_expression()));
+        self.search_index(None, &query, allowed.as_ref())
+    }
+
+    /// Run several searches at once
+    ///
+    /// Queries are answered in parallel under a single index read lock, and
+    /// all hits are enriched from storage in one read. Results are returned
+    /// in the order of `queries` and match what [`VectorDB::search`] returns
+    /// for each query.
+    pub fn search_batch(&self, queries: Vec<SearchQuery>) -> Result<Vec<Vec<SearchResult>>> {
+        let filters: Vec<Option<FilterExpression>> = queries
+            .iter()
+            .map(|query| query.filter.as_ref().map(SearchFilter::to_expression))
+            .collect();
+
+        let oversampling = self.rescore_oversampling();
+        let default_ef = Some(self.ef_search()).filter(|&ef| ef > 0);
+        let mut batches = {
+            let index = self.index.read();
+            let index: &dyn VectorIndex = &**index;
+            let payload = self.payload_indexes.read();
+            let search = |(query, filter): (&SearchQuery, &Option<FilterExpression>)| {
+                let candidates = query.k.saturating_mul(oversampling.max(1));
+                let params = SearchParams {
+                    ef_search: query.ef_search.or(default_ef),
+                };
+                match filter {
+                    Some(filter) => {
+                        let visit = |visit: &mut dyn FnMut(&str)| {
+                            self.visit_matching(&payload, filter, visit)
+                        };
+                        index.search_allowed(
+                            &query.vector,
+                            candidates,
+                            AllowedIds::Visit(&visit),
+                            &params,
+                        )
+                    }
+                    None => index.search_with_params(&query.vector, candidates, &params),
+                }
+            };
+
+            #[cfg(all(feature = "parallel", not(target_arch = "wasm32")))]
+            {
+                use rayon::prelude::*;
+                queries
+                    .par_iter()
+                    .zip(&filters)
+                    .map(search)
+                    .collect::<Result<Vec<_>>>()?
+            }
+            #[cfg(any(not(feature = "parallel"), target_arch = "wasm32"))]
+            {
+                queries
+                    .iter()
+                    .zip(&filters)
+                    .map(search)
+                    .collect::<Result<Vec<_>>>()?
+            }
+        };
+
+        let ids: Vec<VectorId> = batches
+            .iter()
+            .flatten()
+            .map(|result| result.id.clone())
+            .collect::<HashSet<_>>()
+            .into_iter()
+            .collect();
+        let stored = self
+            .storage
+            .get_batch(&ids.iter().map(String::as_str).collect::<Vec<_>>())?;
+        let entries: HashMap<VectorId, VectorEntry> = ids
+            .into_iter()
+            .zip(stored)
+            .filter_map(|(id, entry)| Some((id, entry?)))
+            .collect();
+
+        let config = Self::default_space(&self.options);
+        for (query, results) in queries.iter().zip(&mut batches) {
+            for result in results.iter_mut() {
+                let Some(entry) = entries.get(&result.id) else {
+                    continue;
+                };
+                if oversampling > 0 {
+                    result.score = Self::space_distance(&config, &query.vector, &entry.vector)?;
+                }
+                result.vector = Some(entry.vector.clone());
+                result.metadata = entry.metadata.clone();
+            }
+            if oversampling > 0 {
+                results.sort_by(|a, b| a.score.total_cmp(&b.score));
+                results.truncate(query.k);
+            }
+        }
+
+        Ok(batches)
+    }
+
+    /// Search for similar vectors among an explicit set of allowed ids
+    ///
+    /// Any metadata filter on the query is applied on top of the allow-set.
+    pub fn search_filtered(
+        &self,
+        query: SearchQuery,
+        allowed: &HashSet<VectorId>,
+    ) -> Result<Vec<SearchResult>> {
+        match &query.filter {
+            Some(filter) => {
+                let matching = self.matching_ids(&filter.to_expression())?;
+                let allowed: HashSet<VectorId> = allowed.intersection(&matching).cloned().collect();
+                self.search_index(None, &query, Some(&Allowed::Ids(&allowed)))
+            }
+            None => self.search_index(None, &query, Some(&Allowed::Ids(allowed))),
+        }
+    }
+
+    /// Search the vectors of a named vector space
+    ///
+    /// Results carry the point's vector in that space and its metadata.
+    /// Filters work as in [`VectorDB::search`].
+    pub fn search_named(&self, space: &str, query: SearchQuery) -> Result<Vec<SearchResult>> {
+        let allowed = query
+            .filter
+            .as_ref()
+            .map(|filter| Allowed::Matching(filter.to_expression()));
+        self.search_index(Some(space), &query, allowed.as_ref())
+    }
+
+    /// Search for every point within [`RangeSearchQuery::radius`] of a
+    /// query vector, nearest first
+    ///
+    /// At most `max_results` points are returned. Quantized indexes only
+    /// know approximate distances, so there the range is found by growing
+    /// rescored top-k searches until a result falls outside the radius.
+    /// Results carry the vector of the searched space and metadata.
+    pub fn search_range(&self, query: RangeSearchQuery) -> Result<Vec<SearchResult>> {
+        if query.radius.is_nan() {
+            return Err(RuvectorError::InvalidParameter(
+                "Range search radius must be a number".to_string(),
+            ));
+        }
+        if query.max_results == 0 {
+            return Err(RuvectorError::InvalidParameter(
+                "max_results must be greater than 0".to_string(),
+            ));
+        }
+
+        let allowed = query
+            .filter
+            .as_ref()
+            .map(|filter| Allowed::Matching(filter.to_expression()));
+        let space = query.space.as_deref();
+
+        let (mut results, _, oversampling) = self.with_space_index(
+            space,
+            query.ef_search,
+            allowed.as_ref(),
+            |index, config, oversampling, params, allowed| {
+                if oversampling == 0 {
+                    return index.search_range(
+                        &query.vector,
+                        query.radius,
+                        query.max_results,
+                        allowed,
+                        params,
+                    );
+                }
+
+                expanding_range_search(query.radius, query.max_results, |k| {
+                    let candidates = k.saturating_mul(oversampling);
+                    let mut results = match allowed {
+                        Some(allowed) => {
+                            index.search_allowed(&query.vector, candidates, allowed, params)?
+                        }
+                        None => index.search_with_params(&query.vector, candidates, params)?,
+                    };
+                    self.enrich_results(space, &mut results, Some((config, &query.vector)))?;
+                    results.sort_by(|a, b| a.score.total_cmp(&b.score));
+                    results.truncate(k);
+                    Ok(results)
+                })
+            },
+        )?;
+
+        if oversampling == 0 {
+            self.enrich_results(space, &mut results, None)?;
+        }
+        Ok(results)
+    }
+
+    /// Search for the nearest neighbors of a stored point, using its vector
+    /// in [`SearchByIdQuery::space`] as the query
+    ///
+    /// The point itself is never returned. Returns `None` if the point is
+    /// not stored or has no vector in the space. Filters work as in
+    /// [`VectorDB::search`].
+    pub fn search_by_id(&self, query: SearchByIdQuery) -> Result<Option<Vec<SearchResult>>> {
+        let vector = match &query.space {
+            None => self.storage.get(&query.id)?.map(|entry| entry.vector),
+            Some(name) => {
+                Self::space(&self.spaces.read(), name)?;
+                self.storage.get_named(name, &query.id)?
+            }
+        };
+        let Some(vector) = vector else {
+            return Ok(None);
+        };
+
+        let allowed = query
+            .filter
+            .as_ref()
+            .map(|filter| Allowed::Matching(filter.to_expression()));
+        let search = SearchQuery {
+            vector,
+            k: query.k.saturating_add(1),
+            filter: None,
+            ef_search: query.ef_search,
+        };
+        let mut results = self.search_index(query.space.as_deref(), &search, allowed.as_ref())?;
+        results.retain(|result| result.id != query.id);
+        results.truncate(query.k);
+        Ok(Some(results))
+    }
+
+    /// Search for the best groups of points sharing a value of
+    /// [`GroupedSearchQuery::group_by`], with up to `group_size` hits each
+    ///
+    /// The search starts with `groups * group_size` candidates and doubles
+    /// them, up to [`GROUPED_SEARCH_MAX_CANDIDATES`], until enough distinct
+    /// groups were found. Groups are filled from the same candidates, so a
+    /// group may hold fewer than `group_size` hits even if more points share
+    /// its value. Groups are ordered by their best hit.
+    pub fn search_groups(&self, query: GroupedSearchQuery) -> Result<Vec<PointGroup>> {
+        if query.groups == 0 || query.group_size == 0 {
+            return Err(RuvectorError::InvalidParameter(
+                "groups and group_size must be greater than 0".to_string(),
+            ));
+        }
+
+        let allowed = query
+            .filter
+            .as_ref()
+            .map(|filter| Allowed::Matching(filter.to_expression()));
+
+        let mut candidates = query.groups.saturating_mul(query.group_size);
+        let max_candidates = candidates.max(GROUPED_SEARCH_MAX_CANDIDATES);
+        loop {
+            let search = SearchQuery {
+                vector: query.vector.clone(),
+                k: candidates,
+                filter: None,
+                ef_search: query.ef_search,
+            };
+            let results = self.search_index(query.space.as_deref(), &search, allowed.as_ref())?;
+            let exhausted = results.len() < candidates || candidates >= max_candidates;
+
+            let mut groups = Self::group_results(results, &query.group_by, query.group_size);
+            if exhausted || groups.len() >= query.groups {
+                groups.truncate(query.groups);
+                return Ok(groups);
+            }
+            candidates = candidates.saturating_mul(2).min(max_candidates);
+        }
+    }
+
+    /// Group ordered results by the value of their `field` metadata, keeping
+    /// the first `group_size` hits of each group
+    fn group_results(
+        results: Vec<SearchResult>,
+        field: &str,
+        group_size: usize,
+    ) -> Vec<PointGroup> {
+        let mut groups: Vec<PointGroup> = Vec::new();
+        let mut positions: HashMap<String, usize> = HashMap::new();
+        for result in results {
+            let key = match result.metadata.as_ref().and_then(|m| m.get(field)) {
+                Some(key @ (Value::String(_) | Value::Number(_) | Value::Bool(_))) => key.clone(),
+                _ => continue,
+            };
+
+            // Keyed by the JSON form so "1" and 1 are different groups
+            let position = *positions.entry(key.to_string()).or_insert_with(|| {
+                groups.push(PointGroup {
+                    key,
+                    hits: Vec::new(),
+                });
+                groups.len() - 1
+            });
+            let group = &mut groups[position];
+            if group.hits.len() < group_size {
+                group.hits.push(result);
+            }
+        }
+        groups
+    }
+
+    /// Search several vector spaces at once, ranking points by the weighted
+    /// sum of their distances in each space
+    ///
+    /// Each space contributes its [`FUSED_CANDIDATES_PER_RESULT`] * `k`
+    /// nearest points as candidates, which are then scored exactly in every
+    /// space. Results carry the default vector and metadata.
+    pub fn search_fused(&self, query: FusedSearchQuery) -> Result<Vec<SearchResult>> {
+        if query.vectors.is_empty() {
+            return Err(RuvectorError::InvalidParameter(
+                "Fused search needs at least one query vector".to_string(),
+            ));
+        }
+        if let Some(component) = query.vectors.iter().find(|c| !c.weight.is_finite()) {
+            return Err(RuvectorError::InvalidParameter(format!(
+                "Invalid weight {} in fused search",
+                component.weight
+            )));
+        }
+
+        let allowed = query
+            .filter
+            .as_ref()
+            .map(|filter| Allowed::Matching(filter.to_expression()));
+
+        let mut candidates = HashSet::new();
+        let mut configs = Vec::with_capacity(query.vectors.len());
+        for component in &query.vectors {
+            let component_query = SearchQuery {
+                vector: component.vector.clone(),
+                k: query.k.saturating_mul(FUSED_CANDIDATES_PER_RESULT),
+                filter: None,
+                ef_search: query.ef_search,
+            };
+            let (results, config, _) = self.query_space(
+                component.space.as_deref(),
+                &component_query,
+                allowed.as_ref(),
+            )?;
+            candidates.extend(results.into_iter().map(|result| result.id));
+            configs.push(config);
+        }
+
+        let mut results = Vec::with_capacity(candidates.len());
+        'candidates: for id in candidates {
+            let Some(entry) = self.storage.get(&id)? else {
+                continue;
+            };
+            let named = self.storage.get_vectors(&id)?;
+
+            let mut score = 0.0;
+            for (component, config) in query.vectors.iter().zip(&configs) {
+                let vector = match &component.space {
+                    None => &entry.vector,
+                    Some(name) => match named.get(name) {
+                        Some(vector) => vector,
+                        None => continue 'candidates,
+                    },
+                };
+                score +=
+                    component.weight * Self::space_distance(config, &component.vector, vector)?;
+            }
+
+            results.push(SearchResult {
+                id,
+                score,
+                vector: Some(entry.vector),
+                metadata: entry.metadata,
+            });
+        }
+
+        results.sort_by(|a, b| a.score.total_cmp(&b.score));
+        results.truncate(query.k);
+        Ok(results)
+    }
+
+    /// Search a sparse vector space by dot product
+    ///
+    /// Scores are negated dot products, so lower is better as with
+    /// [`DistanceMetric::DotProduct`]; only points sharing a term with the
+    /// query are returned. Results carry the default vector and metadata.
+    pub fn search_sparse(
+        &self,
+        space: &str,
+        vector: &SparseVector,
+        k: usize,
+        filter: Option<SearchFilter>,
+    ) -> Result<Vec<SearchResult>> {
+        vector.validate()?;
+        let allowed = match &filter {
+            Some(filter) => Some(self.matching_ids(&filter.to_expression())?),
+            None => None,
+        };
+
+        let hits = {
+            let spaces = self.sparse.read();
+            Self::sparse_space(&spaces, space)?.search(vector, k, allowed.as_ref())
+        };
+        self.point_results(hits.into_iter().map(|(id, dot)| (id, -dot)))
+    }
+
+    /// Search a dense and a sparse vector space at once and fuse both
+    /// rankings
+    ///
+    /// Each side contributes its [`FUSED_CANDIDATES_PER_RESULT`] * `k` best
+    /// points, combined as chosen by [`HybridSearchQuery::fusion`]. Results
+    /// are ordered by the fused score, higher is better, and carry the
+    /// default vector and metadata.
+    pub fn search_hybrid(&self, query: HybridSearchQuery) -> Result<Vec<SearchResult>> {
+        query.sparse.validate()?;
+        let weights_valid = match query.fusion {
+            HybridFusion::Rrf { k } => k.is_finite() && k >= 0.0,
+            HybridFusion::Weighted {
+                dense_weight,
+                sparse_weight,
+                ..
+            } => dense_weight.is_finite() && sparse_weight.is_finite(),
+        };
+        if !weights_valid {
+            return Err(RuvectorError::InvalidParameter(format!(
+                "Invalid hybrid fusion: {:?}",
+                query.fusion
+            )));
+        }
+
+        let allowed = match &query.filter {
+            Some(filter) => Some(self.matching_ids(&filter.to_expression())?),
+            None => None,
+        };
+        let candidates = query.k.saturating_mul(FUSED_CANDIDATES_PER_RESULT);
+
+        let dense_query = SearchQuery {
+            vector: query.dense,
+            k: candidates,
+            filter: None,
+            ef_search: query.ef_search,
+        };
+        let dense: Vec<(VectorId, f32)> = self
+            .search_index(
+                query.dense_space.as_deref(),
+                &dense_query,
+                allowed.as_ref().map(Allowed::Ids).as_ref(),
+            )?
+            .into_iter()
+            // Distances become similarities, higher is better
+            .map(|result| (result.id, -result.score))
+            .collect();
+        let sparse = {
+            let spaces = self.sparse.read();
+            Self::sparse_space(&spaces, &query.sparse_space)?.search(
+                &query.sparse,
+                candidates,
+                allowed.as_ref(),
+            )
+        };
+
+        let mut fused: HashMap<VectorId, f32> = HashMap::new();
+        match query.fusion {
+            HybridFusion::Rrf { k } => {
+                for ranking in [dense, sparse] {
+                    for (rank, (id, _)) in ranking.into_iter().enumerate() {
+                        *fused.entry(id).or_default() += 1.0 / (k + rank as f32 + 1.0);
+                    }
+                }
+            }
+            HybridFusion::Weighted {
+                dense_weight,
+                sparse_weight,
+                normalization,
+            } => {
+                for (ranking, weight) in [(dense, dense_weight), (sparse, sparse_weight)] {
+                    let (ids, mut scores): (Vec<VectorId>, Vec<f32>) = ranking.into_iter().unzip();
+                    normalize_scores(&mut scores, normalization);
+                    for (id, score) in ids.into_iter().zip(scores) {
+                        *fused.entry(id).or_default() += weight * score;
+                    }
+                }
+            }
+        }
+
+        let mut ranked: Vec<(VectorId, f32)> = fused.into_iter().collect();
+        ranked.sort_by(|a, b| b.1.total_cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        ranked.truncate(query.k);
+        self.point_results(ranked)
+    }
+
+    /// Results for scored ids, in order, with the default vector and metadata
+    /// of each point still stored
+    fn point_results(
+        &self,
+        scored: impl IntoIterator<Item = (VectorId, f32)>,
+    ) -> Result<Vec<SearchResult>> {
+        let mut results = Vec::new();
+        for (id, score) in scored {
+            if let Some(entry) = self.storage.get(&id)? {
+                results.push(SearchResult {
+                    id,
+                    score,
+                    vector: Some(entry.vector),
+                    metadata: entry.metadata,
+                });
+            }
+        }
+        Ok(results)
+    }
+
+    /// Ids of the stored vectors whose metadata satisfies `filter`
+    pub fn filter_ids(&self, filter: &FilterExpression) -> Result<HashSet<VectorId>> {
+        self.matching_ids(filter)
+    }
+
+    /// Declare a payload index on a metadata field
+    ///
+    /// Existing vectors are indexed immediately and the definition is
+    /// persisted alongside the database configuration, so the index is
+    /// rebuilt when the database is reopened, with any options.
+    pub fn create_payload_index(&self, field: &str, index_type: IndexType) -> Result<()> {
+        // Writes committed during the scan are indexed once it finishes
+        let _writing = self.begin_write();
+        let mut indexes = self.payload_indexes.write();
+        indexes.create_index(field, index_type)?;
+
+        let mut result: Result<()> = Ok(());
+        let scanned = self.storage.scan_metadata(|id, metadata| {
+            if let (Ok(()), Some(value)) = (&result, metadata.get(field)) {
+                if let Some(index) = indexes.get_index_mut(field) {
+                    result = index.add(id, value).map_err(Into::into);
+                }
+            }
+        });
+        if let Err(e) = scanned.and(result) {
+            let _ = indexes.drop_index(field);
+            return Err(e);
+        }
+
+        #[cfg(feature = "storage")]
+        self.storage
+            .save_payload_indexes(&Self::index_definitions(&indexes))?;
+
+        Ok(())
+    }
+
+    /// Drop a payload index, returning whether it existed
+    pub fn drop_payload_index(&self, field: &str) -> Result<bool> {
+        let _writing = self.begin_write();
+        let mut indexes = self.payload_indexes.write();
+        if indexes.drop_index(field).is_err() {
+            return Ok(false);
+        }
+
+        #[cfg(feature = "storage")]
+        self.storage
+            .save_payload_indexes(&Self::index_definitions(&indexes))?;
+
+        Ok(true)
+    }
+
+    /// Declared payload indexes by field name
+    pub fn payload_indexes(&self) -> BTreeMap<String, IndexType> {
+        Self::index_definitions(&self.payload_indexes.read())
+    }
+
+    fn index_definitions(indexes: &PayloadIndexManager) -> BTreeMap<String, IndexType> {
+        indexes
+            .indexed_fields()
+            .into_iter()
+            .filter_map(|field| {
+                let index_type = indexes.get_index(&field)?.index_type();
+                Some((field, index_type))
+            })
+            .collect()
+    }
+
+    /// Declare a named vector space
+    ///
+    /// Points hold at most one vector per space besides their default
+    /// vector, see [`VectorDB::upsert_with_vectors`]. The definition is
+    /// persisted alongside the database configuration; the space's index is
+    /// rebuilt from the stored vectors when the database is reopened.
+    pub fn create_vector_space(&self, name: &str, config: VectorSpaceConfig) -> Result<()> {
+        if name.is_empty() {
+            return Err(RuvectorError::InvalidParameter(
+                "Vector space name must not be empty".to_string(),
+            ));
+        }
+        if config.dimensions == 0 {
+            return Err(RuvectorError::InvalidDimension(
+                "Vector space dimensions must be greater than 0".to_string(),
+            ));
+        }
+
+        let _writing = self.begin_write();
+        let mut spaces = self.spaces.write();
+        if spaces.contains_key(name) || self.sparse.read().contains_key(name) {
+            return Err(RuvectorError::InvalidParameter(format!(
+                "Vector space already exists: {}",
+                name
+            )));
+        }
+
+        let index = Self::create_index(&config)?;
+        spaces.insert(name.to_string(), VectorSpace { config, index });
+
+        #[cfg(feature = "storage")]
+        if let Err(e) = self
+            .storage
+            .save_vector_spaces(&Self::space_definitions(&spaces))
+        {
+            spaces.remove(name);
+            return Err(e);
+        }
+
+        Ok(())
+    }
+
+    /// Drop a named vector space and every vector stored in it, returning
+    /// whether it existed
+    pub fn drop_vector_space(&self, name: &str) -> Result<bool> {
+        let _writing = self.begin_write();
+        if !self.spaces.read().contains_key(name) {
+            return Ok(false);
+        }
+
+        #[cfg(feature = "storage")]
+        {
+            let mut remaining = Self::space_definitions(&self.spaces.read());
+            remaining.remove(name);
+            self.storage.drop_vector_space(name, &remaining)?;
+        }
+        #[cfg(not(feature = "storage"))]
+        self.storage.drop_vector_space(name)?;
+
+        let written = vec![Operation::DropVectorSpace {
+            space: name.to_string(),
+        }];
+        self.apply_written(written)?;
+        self.spaces.write().remove(name);
+
+        Ok(true)
+    }
+
+    /// Declared named vector spaces by name
+    pub fn vector_spaces(&self) -> BTreeMap<String, VectorSpaceConfig> {
+        Self::space_definitions(&self.spaces.read())
+    }
+
+    /// Declare a sparse vector space
+    ///
+    /// Points hold at most one sparse vector per space, set with
+    /// [`VectorDB::update_sparse_vectors`]. Sparse and named vector spaces
+    /// share one namespace. The space is persisted alongside the database
+    /// configuration and its inverted index rebuilt when the database is
+    /// reopened.
+    pub fn create_sparse_space(&self, name: &str) -> Result<()> {
+        if name.is_empty() {
+            return Err(RuvectorError::InvalidParameter(
+                "Vector space name must not be empty".to_string(),
+            ));
+        }
+
+        let _writing = self.begin_write();
+        let spaces = self.spaces.read();
+        let mut sparse = self.sparse.write();
+        if spaces.contains_key(name) || sparse.contains_key(name) {
+            return Err(RuvectorError::InvalidParameter(format!(
+                "Vector space already exists: {}",
+                name
+            )));
+        }
+        sparse.insert(name.to_string(), SparseIndex::new());
+
+        #[cfg(feature = "storage")]
+        if let Err(e) = self
+            .storage
+            .save_sparse_spaces(&sparse.keys().cloned().collect())
+        {
+            sparse.remove(name);
+            return Err(e);
+        }
+
+        Ok(())
+    }
+
+    /// Drop a sparse vector space and every vector stored in it, returning
+    /// whether it existed
+    pub fn drop_sparse_space(&self, name: &str) -> Result<bool> {
+        let _writing = self.begin_write();
+        if !self.sparse.read().contains_key(name) {
+            return Ok(false);
+        }
+
+        #[cfg(feature = "storage")]
+        {
+            let mut remaining: BTreeSet<String> = self.sparse.read().keys().cloned().collect();
+            remaining.remove(name);
+            self.storage.drop_sparse_space(name, &remaining)?;
+        }
+        #[cfg(not(feature = "storage"))]
+        self.storage.drop_sparse_space(name)?;
+
+        let written = vec![Operation::DropSparseSpace {
+            space: name.to_string(),
+        }];
+        self.apply_written(written)?;
+        self.sparse.write().remove(name);
+
+        Ok(true)
+    }
+
+    /// Declared sparse vector spaces
+    pub fn sparse_spaces(&self) -> BTreeSet<String> {
+        self.sparse.read().keys().cloned().collect()
+    }
+
+    /// Set sparse vectors of a stored point, keeping its other vectors;
+    /// returns whether `id` exists
+    pub fn update_sparse_vectors(
+        &self,
+        id: &str,
+        vectors: HashMap<String, SparseVector>,
+    ) -> Result<bool> {
+        let _writing = self.begin_write();
+        {
+            let declared = self.sparse.read();
+            for (space, vector) in &vectors {
+                Self::sparse_space(&declared, space)?;
+                vector.validate()?;
+            }
+        }
+        let updates: Vec<(String, Option<SparseVector>)> = vectors
+            .into_iter()
+            .map(|(space, vector)| (space, Some(vector)))
+            .collect();
+        if !self.storage.update_sparse(id, &updates)? {
+            return Ok(false);
+        }
+
+        let written = updates
+            .into_iter()
+            .filter_map(|(space, vector)| {
+                Some(Operation::UpsertSparse {
+                    id: id.to_string(),
+                    space,
+                    vector: vector?,
+                })
+            })
+            .collect();
+        self.apply_written(written)?;
+
+        Ok(true)
+    }
+
+    /// Remove sparse vectors of a stored point, returning whether `id` exists
+    pub fn delete_sparse_vectors(&self, id: &str, spaces: &[String]) -> Result<bool> {
+        let _writing = self.begin_write();
+        {
+            let declared = self.sparse.read();
+            for space in spaces {
+                Self::sparse_space(&declared, space)?;
+            }
+        }
+        let updates: Vec<(String, Option<SparseVector>)> =
+            spaces.iter().map(|space| (space.clone(), None)).collect();
+        if !self.storage.update_sparse(id, &updates)? {
+            return Ok(false);
+        }
+
+        let written = spaces
+            .iter()
+            .map(|space| Operation::DeleteSparse {
+                id: id.to_string(),
+                space: space.clone(),
+            })
+            .collect();
+        self.apply_written(written)?;
+
+        Ok(true)
+    }
+
+    /// Sparse vectors of a stored point by space name, `None` if `id` is not
+    /// stored
+    pub fn get_sparse_vectors(&self, id: &str) -> Result<Option<HashMap<String, SparseVector>>> {
+        if self.storage.get(id)?.is_none() {
+            return Ok(None);
+        }
+        self.storage.get_sparse(id).map(Some)
+    }
+
+    /// Replace the metadata of a stored vector, returning whether `id` exists
+    ///
+    /// Payload updates never change the vector index.
+    pub fn update_payload(&self, id: &str, metadata: HashMap<String, Value>) -> Result<bool> {
+        self.update_payload_one(id, PayloadUpdate::Set(metadata))
+    }
+
+    /// Insert or overwrite metadata keys of a stored vector, keeping its other
+    /// keys; returns whether `id` exists
+    pub fn merge_payload(&self, id: &str, metadata: HashMap<String, Value>) -> Result<bool> {
+        self.update_payload_one(id, PayloadUpdate::Merge(metadata))
+    }
+
+    /// Remove metadata keys of a stored vector, returning whether `id` exists
+    pub fn delete_payload_keys(&self, id: &str, keys: &[String]) -> Result<bool> {
+        self.update_payload_one(id, PayloadUpdate::DeleteKeys(keys.to_vec()))
+    }
+
+    /// Apply payload updates in one storage transaction
+    ///
+    /// Returns whether each update's id exists; updates of missing ids are
+    /// skipped. Updates of the same id apply in order.
+    pub fn update_payload_batch(
+        &self,
+        updates: Vec<(VectorId, PayloadUpdate)>,
+    ) -> Result<Vec<bool>> {
+        let writing = self.begin_write();
+        let before = self.points_before(updates.iter().map(|(id, _)| id.as_str()))?;
+        let results = self.storage.update_metadata(&updates)?;
+        let updated = results.iter().map(Option::is_some).collect();
+
+        let written = updates
+            .into_iter()
+            .zip(results)
+            .filter_map(|((id, _), metadata)| {
+                let metadata = metadata?;
+                Some(Operation::UpdatePayload {
+                    id,
+                    metadata: (!metadata.is_empty()).then_some(metadata),
+                })
+            })
+            .collect();
+        self.apply_and_notify(writing, written, before)?;
+
+        Ok(updated)
+    }
+
+    fn update_payload_one(&self, id: &str, update: PayloadUpdate) -> Result<bool> {
+        let updated = self.update_payload_batch(vec![(id.to_string(), update)])?;
+        Ok(updated[0])
+    }
+
+    /// Replace the indexed payload of `id` with `metadata`
+    fn index_payload(
+        indexes: &mut PayloadIndexManager,
+        id: &str,
+        metadata: Option<&HashMap<String, Value>>,
+    ) -> Result<()> {
+        if indexes.index_count() == 0 {
+            return Ok(());
+        }
+
+        indexes.clear_vector(id);
+        if let Some(metadata) = metadata {
+            let payload = Value::Object(
+                metadata
+                    .iter()
+                    .map(|(key, value)| (key.clone(), value.clone()))
+                    .collect(),
+            );
+            indexes.index_payload(id, &payload)?;
+        }
+        Ok(())
+    }
+
+    /// Run `search` with `allowed` as the allow-set of an index search
+    ///
+    /// Filter matches are only visited once the index is searched, so the
+    /// index can resolve them without the ids being copied. Payload indexes
+    /// are locked for the duration; callers searching a vector space must
+    /// lock the spaces inside `search` to keep the order writers lock in.
+    fn with_allowed<T>(
+        &self,
+        allowed: Option<&Allowed<'_>>,
+        search: impl FnOnce(Option<AllowedIds<'_>>) -> Result<T>,
+    ) -> Result<T> {
+        match allowed {
+            None => search(None),
+            Some(Allowed::Ids(ids)) => search(Some(AllowedIds::Set(ids))),
+            Some(Allowed::Matching(filter)) => {
+                let indexes = self.payload_indexes.read();
+                let visit =
+                    |visit: &mut dyn FnMut(&str)| self.visit_matching(&indexes, filter, visit);
+                search(Some(AllowedIds::Visit(&visit)))
+            }
+        }
+    }
+
+    /// Ids whose metadata satisfies `filter`
+    fn matching_ids(&self, filter: &FilterExpression) -> Result<HashSet<VectorId>> {
+        let mut ids = HashSet::new();
+        self.visit_matching(&self.payload_indexes.read(), filter, &mut |id| {
+            ids.insert(id.to_string());
+        })?;
+        Ok(ids)
+    }
+
+    /// Call `visit` with the id of every stored vector whose metadata
+    /// satisfies `filter`
+    fn visit_matching(
+        &self,
+        indexes: &PayloadIndexManager,
+        filter: &FilterExpression,
+        visit: &mut dyn FnMut(&str),
+    ) -> Result<()> {
+        let evaluator = FilterEvaluator::new(indexes);
+
+        if Self::is_index_answerable(filter) {
+            match evaluator.evaluate_ids(filter) {
+                Ok(ids) => {
+                    ids.into_iter().for_each(visit);
+                    return Ok(());
+                }
+                Err(FilterError::IndexNotFound(_)) | Err(FilterError::InvalidIndexType(_)) => {}
+                Err(e) => return Err(e.into()),
+            }
+        }
+
+        // Unindexed filters cost one pass over the stored payloads; vectors
+        // stored without metadata match filters such as `not` that accept
+        // an empty payload
+        let empty = Value::Object(Default::default());
+        let empty_matches = evaluator.matches(&empty, filter);
+        self.storage.scan_payloads(|id, metadata| {
+            let matches = match metadata {
+                Some(metadata) => evaluator.matches(metadata, filter),
+                None => empty_matches,
+            };
+            if matches {
+                visit(id);
+            }
+        })
+    }
+
+    /// Whether index evaluation of `filter` agrees with matching payloads
+    ///
+    /// Index-based negation and null checks only see vectors present in the
+    /// referenced indexes, and an empty `and` yields nothing instead of
+    /// everything, so those expressions are always evaluated by scanning.
+    fn is_index_answerable(filter: &FilterExpression) -> bool {
+        match filter {
+            FilterExpression::Ne { .. }
+            | FilterExpression::Not(_)
+            | FilterExpression::IsNull { .. } => false,
+            FilterExpression::And(filters) | FilterExpression::Or(filters) => {
+                !filters.is_empty() && filters.iter().all(Self::is_index_answerable)
+            }
+            _ => true,
+        }
+    }
+
+    /// Number of candidates per result rescored with exact distances
+    pub fn rescore_oversampling(&self) -> usize {
+        self.rescore_oversampling.load(Ordering::Relaxed)
+    }
+
+    /// Set how many candidates per requested result are fetched from a
+    /// quantized index and rescored against the stored f32 vectors
+    ///
+    /// Defaults to [`DEFAULT_RESCORE_OVERSAMPLING`] when quantization is
+    /// enabled; 0 returns the index's approximate distances as-is.
+    pub fn set_rescore_oversampling(&self, oversampling: usize) {
+        self.rescore_oversampling
+            .store(oversampling, Ordering::Relaxed);
+    }
+
+    /// Default HNSW beam width for queries that don't set
+    /// [`SearchQuery::ef_search`], 0 when the database has no HNSW index
+    pub fn ef_search(&self) -> usize {
+        self.ef_search.load(Ordering::Relaxed)
+    }
+
+    /// Change the default HNSW beam width at runtime
+    ///
+    /// Starts out as the configured `HnswConfig::ef_search` and applies
+    /// until the database is reopened. Per-query values still take
+    /// precedence.
+    pub fn set_ef_search(&self, ef_search: usize) -> Result<()> {
+        if ef_search == 0 {
+            return Err(RuvectorError::InvalidParameter(
+                "ef_search must be greater than 0".to_string(),
+            ));
+        }
+        self.ef_search.store(ef_search, Ordering::Relaxed);
+        Ok(())
+    }
+
+    fn search_index(
+        &self,
+        space: Option<&str>,
+        query: &SearchQuery,
+        allowed: Option<&Allowed<'_>>,
+    ) -> Result<Vec<SearchResult>> {
+        let (mut results, config, oversampling) = self.query_space(space, query, allowed)?;
+
+        // Replace approximate distances with exact ones when rescoring
+        let rescore = (oversampling > 0).then_some((&config, query.vector.as_slice()));
+        self.enrich_results(space, &mut results, rescore)?;
+
+        if oversampling > 0 {
+            results.sort_by(|a, b| a.score.total_cmp(&b.score));
+            results.truncate(query.k);
+        }
+
+        Ok(results)
+    }
+
+    /// Fill in the vector in `space` and the metadata of index results,
+    /// recomputing each score against the query of `rescore` if set
+    fn enrich_results(
+        &self,
+        space: Option<&str>,
+        results: &mut [SearchResult],
+        rescore: Option<(&VectorSpaceConfig, &[f32])>,
+    ) -> Result<()> {
+        for result in results {
+            let Ok(Some(entry)) = self.storage.get(&result.id) else {
+                continue;
+            };
+            let vector = match space {
+                None => Some(entry.vector),
+                Some(name) => self.storage.get_named(name, &result.id).ok().flatten(),
+            };
+            if let Some(vector) = vector {
+                if let Some((config, query)) = rescore {
+                    result.score = Self::space_distance(config, query, &vector)?;
+                }
+                result.vector = Some(vector);
+            }
+            result.metadata = entry.metadata;
+        }
+        Ok(())
+    }
+
+    /// Raw index results for `query` in `space` (the default vector if
+    /// `None`), with the space's configuration and rescoring oversampling
+    fn query_space(
+        &self,
+        space: Option<&str>,
+        query: &SearchQuery,
+        allowed: Option<&Allowed<'_>>,
+    ) -> Result<(Vec<SearchResult>, VectorSpaceConfig, usize)> {
+        self.with_space_index(
+            space,
+            query.ef_search,
+            allowed,
+            |index, _, oversampling, params, allowed| {
+                let candidates = query.k.saturating_mul(oversampling.max(1));
+                match allowed {
+                    Some(allowed) => {
+                        index.search_allowed(&query.vector, candidates, allowed, params)
+                    }
+                    None => index.search_with_params(&query.vector, candidates, params),
+                }
+            },
+        )
+    }
+
+    /// Run `search` on the index of `space` (the default vector if `None`)
+    /// with the space's configuration, rescoring oversampling, search
+    /// parameters and the allow-set of `allowed`
+    ///
+    /// Quantized named spaces are rescored with
+    /// [`DEFAULT_RESCORE_OVERSAMPLING`] and use their own `ef_search`
+    /// unless the query sets one.
+    fn with_space_index<F>(
+        &self,
+        space: Option<&str>,
+        ef_search: Option<usize>,
+        allowed: Option<&Allowed<'_>>,
+        search: F,
+    ) -> Result<(Vec<SearchResult>, VectorSpaceConfig, usize)>
+    where
+        F: FnOnce(
+            &dyn VectorIndex,
+            &VectorSpaceConfig,
+            usize,
+            &SearchParams,
+            Option<AllowedIds<'_>>,
+        ) -> Result<Vec<SearchResult>>,
+    {
+        match space {
+            None => {
+                let oversampling = self.rescore_oversampling();
+                let params = SearchParams {
+                    ef_search: ef_search.or_else(|| Some(self.ef_search()).filter(|&ef| ef > 0)),
+                };
+                let config = Self::default_space(&self.options);
+                let index = self.index.read();
+                let results = self.with_allowed(allowed, |allowed| {
+                    search(&**index, &config, oversampling, &params, allowed)
+                })?;
+                Ok((results, config, oversampling))
+            }
+            Some(name) => self.with_allowed(allowed, |allowed| {
+                let spaces = self.spaces.read();
+                let space = Self::space(&spaces, name)?;
+                let oversampling = if Self::is_quantized(&space.config) {
+                    DEFAULT_RESCORE_OVERSAMPLING
+                } else {
+                    0
+                };
+                let params = SearchParams { ef_search };
+                let results = search(&*space.index, &space.config, oversampling, &params, allowed)?;
+                Ok((results, space.config.clone(), oversampling))
+            }),
+        }
+    }
+
+    fn space<'a>(spaces: &'a HashMap<String, VectorSpace>, name: &str) -> Result<&'a VectorSpace> {
+        spaces.get(name).ok_or_else(|| {
+            RuvectorError::InvalidParameter(format!("Unknown vector space: {}", name))
+        })
+    }
+
+    fn sparse_space<'a>(
+        spaces: &'a HashMap<String, SparseIndex>,
+        name: &str,
+    ) -> Result<&'a SparseIndex> {
+        spaces.get(name).ok_or_else(|| {
+            RuvectorError::InvalidParameter(format!("Unknown sparse vector space: {}", name))
+        })
+    }
+
+    /// Check that every named vector targets a known space with matching
+    /// dimensions
+    fn validate_vectors<'a>(
+        spaces: &HashMap<String, VectorSpace>,
+        vectors: impl IntoIterator<Item = (&'a String, &'a Vec<f32>)>,
+    ) -> Result<()> {
+        for (name, vector) in vectors {
+            let config = &Self::space(spaces, name)?.config;
+            if config.multivector.is_some() {
+                if vector.is_empty() || vector.len() % config.dimensions != 0 {
+                    return Err(RuvectorError::InvalidDimension(format!(
+                        "Multi-vector of length {} is not a non-empty bag of {}-dimensional vectors",
+                        vector.len(),
+                        config.dimensions
+                    )));
+                }
+            } else if vector.len() != config.dimensions {
+                return Err(RuvectorError::DimensionMismatch {
+                    expected: config.dimensions,
+                    actual: vector.len(),
+                });
+            }
+        }
+        Ok(())
+    }
+
+    /// Delete a vector by ID
+    pub fn delete(&self, id: &str) -> Result<bool> {
+        Ok(self.delete_batch(&[id])?[0])
+    }
+
+    /// Delete vectors in one storage transaction, returning whether each id
+    /// was stored
+    pub fn delete_batch(&self, ids: &[&str]) -> Result<Vec<bool>> {
+        let writing = self.begin_write();
+        let before = self.points_before(ids.iter().copied())?;
+        let deleted = self.storage.delete_batch(ids)?;
+
+        let written = ids
+            .iter()
+            .zip(&deleted)
+            .filter(|(_, &deleted)| deleted)
+            .map(|(id, _)| Operation::Delete { id: id.to_string() })
+            .collect::<Vec<_>>();
+        if !written.is_empty() {
+            self.apply_and_notify(writing, written, before)?;
+        }
+
+        Ok(deleted)
+    }
+
+    /// Delete every vector whose metadata satisfies `filter`, returning how
+    /// many were deleted
+    ///
+    /// Matches are resolved before deleting, so vectors written concurrently
+    /// may survive even if they match.
+    pub fn delete_by_filter(&self, filter: &FilterExpression) -> Result<usize> {
+        let ids = self.matching_ids(filter)?;
+        let ids: Vec<&str> = ids.iter().map(String::as_str).collect();
+        let deleted = self.delete_batch(&ids)?;
+        Ok(deleted.into_iter().filter(|&deleted| deleted).count())
+    }
+
+    /// Get a vector by ID
+    pub fn get(&self, id: &str) -> Result<Option<VectorEntry>> {
+        self.storage.get(id)
+    }
+
+    /// Get several vectors by ID from one consistent snapshot, `None` where
+    /// an id is not stored
+    pub fn get_batch(&self, ids: &[&str]) -> Result<Vec<Option<VectorEntry>>> {
+        self.storage.get_batch(ids)
+    }
+
+    /// Get the number of vectors
+    pub fn len(&self) -> Result<usize> {
+        self.storage.len()
+    }
+
+    /// Number of vectors whose metadata satisfies `filter`, or of all
+    /// vectors without one
+    pub fn count(&self, filter: Option<&FilterExpression>) -> Result<usize> {
+        match filter {
+            Some(filter) => Ok(self.matching_ids(filter)?.len()),
+            None => self.storage.len(),
+        }
+    }
+
+    /// Check if database is empty
+    pub fn is_empty(&self) -> Result<bool> {
+        self.storage.is_empty()
+    }
+
+    /// Get database options
+    pub fn options(&self) -> &DbOptions {
+        &self.options
+    }
+
+    /// Get all vector IDs (for iteration/serialization)
+    pub fn keys(&self) -> Result<Vec<String>> {
+        self.storage.all_ids()
+    }
+
+    /// Read one page of stored points in id order
+    ///
+    /// Pass the returned `next_offset` back as `offset` to continue; points
+    /// written meanwhile are seen if their id sorts after the offset. Each
+    /// page is read from one consistent snapshot and only holds `limit`
+    /// points in memory, however many points are skipped by the filter.
+    pub fn scroll(&self, request: ScrollRequest) -> Result<ScrollPage> {
+        if request.limit == 0 {
+            return Err(RuvectorError::InvalidParameter(
+                "Scroll limit must be greater than 0".to_string(),
+            ));
+        }
+        let start = match &request.offset {
+            Some(offset) => Self::decode_scroll_offset(offset)?,
+            None => String::new(),
+        };
+        let filter = request.filter.as_ref().map(SearchFilter::to_expression);
+
+        let indexes = self.payload_indexes.read();
+        let evaluator = FilterEvaluator::new(&indexes);
+        let empty = Value::Object(Default::default());
+
+        let mut points = Vec::with_capacity(request.limit);
+        let mut next_offset = None;
+        self.storage.scan_points(&start, |id, vector, metadata| {
+            if let Some(filter) = &filter {
+                if !evaluator.matches(metadata.as_ref().unwrap_or(&empty), filter) {
+                    return true;
+                }
+            }
+            if points.len() == request.limit {
+                next_offset = Some(Self::encode_scroll_offset(id));
+                return false;
+            }
+
+            points.push(PointRecord {
+                id: id.to_string(),
+                vector: request.with_vector.then_some(vector),
+                metadata: match metadata {
+                    Some(Value::Object(map)) if request.with_payload => {
+                        Some(map.into_iter().collect())
+                    }
+                    _ => None,
+                },
+            });
+            true
+        })?;
+
+        Ok(ScrollPage {
+            points,
+            next_offset,
+        })
+    }
+
+    /// Scroll offsets hex-encode the id the next page starts at, so they are
+    /// safe in URLs whatever the id contains
+    fn encode_scroll_offset(id: &str) -> String {
+        id.bytes().map(|byte| format!("{:02x}", byte)).collect()
+    }
+
+    fn decode_scroll_offset(offset: &str) -> Result<String> {
+        let invalid =
+            || RuvectorError::InvalidParameter(format!("Invalid scroll offset: {}", offset));
+        if offset.len() % 2 != 0 {
+            return Err(invalid());
+        }
+        let bytes = (0..offset.len())
+            .step_by(2)
+            .map(|i| {
+                offset
+                    .get(i..i + 2)
+                    .and_then(|pair| u8::from_str_radix(pair, 16).ok())
+            })
+            .collect::<Option<Vec<u8>>>()
+            .ok_or_else(invalid)?;
+        String::from_utf8(bytes).map_err(|_| invalid())
+    }
+}
+
+#[cfg(feature = "storage")]
+impl Drop for VectorDB {
+    fn drop(&mut self) {
+        self.auto_checkpoint.get_mut().join();
+        let applied = self.applied_sequence.load(Ordering::SeqCst);
+        if applied == self.persisted_sequence.load(Ordering::SeqCst) {
+            return;
+        }
+        if let Err(e) = self.checkpoint() {
+            tracing::warn!("Failed to checkpoint index on close: {}", e);
+        }
+    }
+}
+
+/// Checkpoints started by writes
+#[cfg(feature = "storage")]
+struct AutoCheckpoint {
+    thread: Option<JoinHandle<()>>,
+    /// Applied sequence and bytes logged through this handle when the last
+    /// checkpoint started
+    sequence: u64,
+    bytes: u64,
+}
+
+#[cfg(feature = "storage")]
+impl AutoCheckpoint {
+    fn new(sequence: u64) -> Self {
+        Self {
+            thread: None,
+            sequence,
+            bytes: 0,
+        }
+    }
+
+    /// Wait for a running checkpoint to finish
+    fn join(&mut self) {
+        if let Some(thread) = self.thread.take() {
+            if thread.join().is_err() {
+                tracing::error!("Background checkpoint panicked");
+            }
+        }
+    }
+
+    fn mark(&mut self, sequence: u64, bytes: u64) {
+        self.sequence = sequence;
+        self.bytes = bytes;
+    }
+}
+
+/// What a checkpoint reads and writes, moved to the background thread of a
+/// checkpoint started by writes
+#[cfg(feature = "storage")]
+struct Checkpointer {
+    storage: Arc<VectorStorage>,
+    index: Arc<RwLock<Box<dyn VectorIndex>>>,
+    applied_sequence: Arc<AtomicU64>,
+    persisted_sequence: Arc<AtomicU64>,
+    path: PathBuf,
+    listeners: Vec<BoxedChangeListener>,
+}
+
+#[cfg(feature = "storage")]
+impl Checkpointer {
+    fn checkpoint(&self) -> Result<u64> {
+        let sequence = match self.write_index_file()? {
+            Some(sequence) => sequence,
+            // Indexes without a serialized form are rebuilt from the stored
+            // vectors on open and never replay the log
+            None => self.applied_sequence.load(Ordering::SeqCst),
+        };
+        // Keeps what change listeners haven't recorded, to replay it to them
+        let keep_after = self
+            .listeners
+            .iter()
+            .filter_map(|listener| listener.resume_after())
+            .fold(sequence, u64::min);
+        self.storage.truncate_log(keep_after)?;
+        Ok(sequence)
+    }
+
+    /// Write the index file, returning the sequence it was written at
+    fn write_index_file(&self) -> Result<Option<u64>> {
+        // Writes that change the graph hold its lock while advancing the
+        // applied sequence, so the graph reflects every operation up to the
+        // sequence read under it
+        let (payload, sequence) = {
+            let index = self.index.read();
+            let Some(payload) = index.snapshot() else {
+                return Ok(None);
+            };
+            (payload?, self.applied_sequence.load(Ordering::SeqCst))
+        };
+
+        write_index_file(&self.path, sequence, &payload)?;
+        self.persisted_sequence.store(sequence, Ordering::SeqCst);
+        Ok(Some(sequence))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_vector_db_creation() -> Result<()> {
+        let dir = tempdir().unwrap();
+        let mut options = DbOptions::default();
+        options.storage_path = dir.path().join("test.db").to_string_lossy().to_string();
+        options.dimensions = 3;
+
+        let db = VectorDB::new(options)?;
+        assert!(db.is_empty()?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_insert_and_search() -> Result<()> {
+        let dir = tempdir().unwrap();
+        let mut options = DbOptions::default();
+        options.storage_path = dir.path().join("test.db").to_string_lossy().to_string();
+        options.dimensions = 3;
+        options.distance_metric = DistanceMetric::Euclidean; // Use Euclidean for clearer test
+        options.hnsw_config = None; // Use flat index for testing
+
+        let db = VectorDB::new(options)?;
+
+        // Insert vectors
+        db.insert(VectorEntry {
+            id: Some("v1".to_string()),
+            vector: vec![1.0, 0.0, 0.0],
+            metadata: None,
+        })?;
+
+        db.insert(VectorEntry {
+            id: Some("v2".to_string()),
+            vector: vec![0.0, 1.0, 0.0],
+            metadata: None,
+        })?;
+
+        db.insert(VectorEntry {
+            id: Some("v3".to_string()),
+            vector: vec![0.0, 0.0, 1.0],
+            metadata: None,
+        })?;
+
+        // Search for exact match
+        let results = db.search(SearchQuery {
+            vector: vec![1.0, 0.0, 0.0],
+            k: 2,
+            filter: None,
+            ef_search: None,
+        })?;
+
+        assert!(results.len() >= 1);
+        assert_eq!(results[0].id, "v1", "First result should be exact match");
+        assert!(
+            results[0].score < 0.01,
+            "Exact match should have ~0 distance"
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_selective_filter_returns_k() -> Result<()> {
+        let dir = tempdir().unwrap();
+        let mut options = DbOptions::default();
+        options.storage_path = dir.path().join("test.db").to_string_lossy().to_string();
+        options.dimensions = 8;
+        options.distance_metric = DistanceMetric::Euclidean;
+
+        let db = VectorDB::new(options)?;
+
+        let entries: Vec<VectorEntry> = (0..500)
+            .map(|i| {
+                let mut metadata = HashMap::new();
+                let tag = if i % 40 == 0 { "rare" } else { "common" };
+                metadata.insert("tag".to_string(), serde_json::json!(tag));
+                VectorEntry {
+                    id: Some(format!("v{}", i)),
+                    vector: (0..8).map(|d| ((i * 7 + d * 13) % 97) as f32).collect(),
+                    metadata: Some(metadata),
+                }
+            })
+            .collect();
+        db.insert_batch(entries)?;
+
+        let mut filter = HashMap::new();
+        filter.insert("tag".to_string(), serde_json::json!("rare"));
+        let results = db.search(SearchQuery {
+            vector: vec![1.0; 8],
+            k: 10,
+            filter: Some(filter.into()),
+            ef_search: None,
+        })?;
+
+        assert_eq!(
+            results.len(),
+            10,
+            "13 vectors match, so k=10 must be filled"
+        );
+        for result in &results {
+            let tag = &result.metadata.as_ref().unwrap()["tag"];
+            assert_eq!(tag, "rare");
+        }
+
+        let allowed: HashSet<VectorId> = ["v1".to_string(), "v2".to_string()].into();
+        let results = db.search_filtered(
+            SearchQuery {
+                vector: vec![1.0; 8],
+                k: 10,
+                filter: None,
+                ef_search: None,
+            },
+            &allowed,
+        )?;
+        assert_eq!(results.len(), 2);
+
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(feature = "storage")]
+    fn test_expression_filters_with_payload_indexes() -> Result<()> {
+        use serde_json::json;
+
+        let dir = tempdir().unwrap();
+        let db_path = dir.path().join("filters.db").to_string_lossy().to_string();
+        let mut options = DbOptions::default();
+        options.storage_path = db_path.clone();
+        options.dimensions = 4;
+        options.distance_metric = DistanceMetric::Euclidean;
+
+        let query = |filter: FilterExpression| SearchQuery {
+            vector: vec![0.0; 4],
+            k: 100,
+            filter: Some(filter.into()),
+            ef_search: None,
+        };
+        let ids = |results: Vec<SearchResult>| {
+            let mut ids: Vec<String> = results.into_iter().map(|r| r.id).collect();
+            ids.sort();
+            ids
+        };
+
+        let priced = FilterExpression::and(vec![
+            FilterExpression::range("price", Some(json!(20)), Some(json!(40))),
+            FilterExpression::in_values("category", vec![json!("books"), json!("games")]),
+        ]);
+        let not_books = FilterExpression::not(FilterExpression::eq("category", json!("books")));
+
+        let (expected_priced, expected_not_books) = {
+            let db = VectorDB::new(options.clone())?;
+            db.create_payload_index("price", IndexType::Integer)?;
+
+            for i in 0..60 {
+                let category = ["books", "games", "music"][i % 3];
+                let mut metadata = HashMap::new();
+                metadata.insert("price".to_string(), json!(i));
+                metadata.insert("category".to_string(), json!(category));
+                db.insert(VectorEntry {
+                    id: Some(format!("v{:02}", i)),
+                    vector: vec![i as f32; 4],
+                    metadata: Some(metadata),
+                })?;
+            }
+            db.insert(VectorEntry {
+                id: Some("bare".to_string()),
+                vector: vec![1.0; 4],
+                metadata: None,
+            })?;
+            db.create_payload_index("category", IndexType::Keyword)?;
+            assert!(db.delete("v21")?);
+
+            let expected_priced: Vec<String> = (20..=40)
+                .filter(|i| i % 3 != 2 && *i != 21)
+                .map(|i| format!("v{:02}", i))
+                .collect();
+            assert_eq!(ids(db.search(query(priced.clone()))?), expected_priced);
+
+            // Negation is answered by scanning and includes vectors without metadata
+            let not_books = ids(db.search(query(not_books.clone()))?);
+            assert_eq!(not_books.len(), 40 + 1);
+            assert!(not_books.contains(&"bare".to_string()));
+
+            (expected_priced, not_books)
+        };
+
+        let db = VectorDB::new(options)?;
+        let indexes = db.payload_indexes();
+        assert_eq!(indexes.get("price"), Some(&IndexType::Integer));
+        assert_eq!(indexes.get("category"), Some(&IndexType::Keyword));
+        assert_eq!(ids(db.search(query(priced))?), expected_priced);
+        assert_eq!(ids(db.search(query(not_books))?), expected_not_books);
+
+        assert!(db.drop_payload_index("price")?);
+        assert!(!db.drop_payload_index("price")?);
+        let filter = FilterExpression::gte("price", json!(55));
+        assert_eq!(db.filter_ids(&filter)?.len(), 5);
+
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(feature = "storage")]
+    fn test_payload_indexes_outlast_the_options_a_database_is_reopened_with() -> Result<()> {
+        use serde_json::json;
+
+        let dir = tempdir().unwrap();
+        let db_path = dir.path().join("reopen.db").to_string_lossy().to_string();
+        let mut options = DbOptions::default();
+        options.storage_path = db_path.clone();
+        options.dimensions = 2;
+        options.distance_metric = DistanceMetric::Euclidean;
+
+        {
+            let db = VectorDB::new(options)?;
+            db.create_payload_index("tag", IndexType::Keyword)?;
+            db.insert(VectorEntry {
+                id: Some("a".to_string()),
+                vector: vec![1.0, 0.0],
+                metadata: Some(HashMap::from([("tag".to_string(), json!("x"))])),
+            })?;
+        }
+
+        let mut other = DbOptions::default();
+        other.storage_path = db_path;
+        other.dimensions = 8;
+        other.hnsw_config = None;
+        let db = VectorDB::new(other)?;
+        assert_eq!(db.options().dimensions, 2);
+        assert_eq!(db.options().distance_metric, DistanceMetric::Euclidean);
+        assert_eq!(
+            db.payload_indexes(),
+            BTreeMap::from([("tag".to_string(), IndexType::Keyword)])
+        );
+        let filter = FilterExpression::eq("tag", json!("x"));
+        assert_eq!(db.filter_ids(&filter)?, HashSet::from(["a".to_string()]));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_upsert_replaces_vector_and_payload() -> Result<()> {
+        use serde_json::json;
+
+        let dir = tempdir().unwrap();
+        let mut options = DbOptions::default();
+        options.storage_path = dir.path().join("upsert.db").to_string_lossy().to_string();
+        options.dimensions = 4;
+        options.distance_metric = DistanceMetric::Euclidean;
+
+        let db = VectorDB::new(options)?;
+        db.create_payload_index("tag", IndexType::Keyword)?;
+        for i in 0..20 {
+            let mut metadata = HashMap::new();
+            metadata.insert("tag".to_string(), json!("old"));
+            db.insert(VectorEntry {
+                id: Some(format!("v{}", i)),
+                vector: vec![i as f32; 4],
+                metadata: Some(metadata),
+            })?;
+        }
+
+        db.upsert(VectorEntry {
+            id: Some("v0".to_string()),
+            vector: vec![100.0; 4],
+            metadata: None,
+        })?;
+        let mut metadata = HashMap::new();
+        metadata.insert("tag".to_string(), json!("new"));
+        db.upsert_batch(vec![
+            VectorEntry {
+                id: Some("v1".to_string()),
+                vector: vec![50.0; 4],
+                metadata: None,
+            },
+            VectorEntry {
+                id: Some("v1".to_string()),
+                vector: vec![200.0; 4],
+                metadata: Some(metadata),
+            },
+        ])?;
+
+        assert_eq!(db.len()?, 20);
+        let v0 = db.get("v0")?.unwrap();
+        assert_eq!(v0.vector, vec![100.0; 4]);
+        assert!(v0.metadata.is_none());
+
+        let query = |vector: Vec<f32>, k: usize| SearchQuery {
+            vector,
+            k,
+            filter: None,
+            ef_search: None,
+        };
+        let results = db.search(query(vec![100.0; 4], 1))?;
+        assert_eq!(results[0].id, "v0");
+        assert_eq!(results[0].score, 0.0);
+        assert_eq!(db.search(query(vec![200.0; 4], 1))?[0].id, "v1");
+
+        let ids: HashSet<_> = db
+            .search(query(vec![0.0; 4], 100))?
+            .into_iter()
+            .map(|r| r.id)
+            .collect();
+        assert_eq!(ids.len(), 20);
+
+        let old = db.filter_ids(&FilterExpression::eq("tag", json!("old")))?;
+        assert_eq!(old.len(), 18);
+        assert!(!old.contains("v0") && !old.contains("v1"));
+        let new = db.filter_ids(&FilterExpression::eq("tag", json!("new")))?;
+        assert_eq!(new, HashSet::from(["v1".to_string()]));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_batch_delete_delete_by_filter_and_count() -> Result<()> {
+        use serde_json::json;
+
+        let dir = tempdir().unwrap();
+        let mut options = DbOptions::default();
+        options.storage_path = dir.path().join("delete.db").to_string_lossy().to_string();
+        options.dimensions = 2;
+
+        let parity = |value: &str| FilterExpression::eq("parity", json!(value));
+        {
+            let db = VectorDB::new(options.clone())?;
+            db.create_payload_index("parity", IndexType::Keyword)?;
+            db.insert_batch(
+                (0..10)
+                    .map(|i| {
+                        let mut metadata = HashMap::new();
+                        let parity = if i % 2 == 0 { "even" } else { "odd" };
+                        metadata.insert("parity".to_string(), json!(parity));
+                        VectorEntry {
+                            id: Some(format!("p{}", i)),
+                            vector: vec![i as f32, 1.0],
+                            metadata: Some(metadata),
+                        }
+                    })
+                    .collect(),
+            )?;
+            assert_eq!(db.count(None)?, 10);
+            assert_eq!(db.count(Some(&parity("odd")))?, 5);
+
+            let deleted = db.delete_batch(&["p1", "missing", "p3"])?;
+            assert_eq!(deleted, [true, false, true]);
+            assert_eq!(db.delete_by_filter(&parity("even"))?, 5);
+            assert_eq!(db.delete_by_filter(&parity("even"))?, 0);
+
+            let entries = db.get_batch(&["p5", "p0"])?;
+            assert_eq!(entries[0].as_ref().unwrap().vector, vec![5.0, 1.0]);
+            assert!(entries[1].is_none());
+        }
+
+        // Deletions are replayed into the index and payload index on reopen
+        let db = VectorDB::new(options)?;
+        assert_eq!(db.count(None)?, 3);
+        assert_eq!(db.index.read().len(), 3);
+        assert_eq!(db.count(Some(&parity("odd")))?, 3);
+        let ids: HashSet<VectorId> = db
+            .search(SearchQuery {
+                vector: vec![0.0, 1.0],
+                k: 10,
+                filter: None,
+                ef_search: None,
+            })?
+            .into_iter()
+            .map(|result| result.id)
+            .collect();
+        assert_eq!(ids, HashSet::from(["p5", "p7", "p9"].map(String::from)));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_payload_updates_keep_vectors_and_filters_consistent() -> Result<()> {
+        use serde_json::json;
+
+        let dir = tempdir().unwrap();
+        let mut options = DbOptions::default();
+        options.storage_path = dir.path().join("payload.db").to_string_lossy().to_string();
+        options.dimensions = 2;
+
+        let fields = |pairs: &[(&str, Value)]| -> HashMap<String, Value> {
+            pairs
+                .iter()
+                .map(|(key, value)| (key.to_string(), value.clone()))
+                .collect()
+        };
+        let color = |value: &str| FilterExpression::eq("color", json!(value));
+
+        {
+            let db = VectorDB::new(options.clone())?;
+            db.create_payload_index("color", IndexType::Keyword)?;
+            db.insert(VectorEntry {
+                id: Some("a".to_string()),
+                vector: vec![1.0, 0.0],
+                metadata: Some(fields(&[("color", json!("red")), ("size", json!(3))])),
+            })?;
+            db.insert(VectorEntry {
+                id: Some("b".to_string()),
+                vector: vec![0.0, 1.0],
+                metadata: None,
+            })?;
+
+            assert!(db.merge_payload("a", fields(&[("color", json!("blue"))]))?);
+            assert!(db.delete_payload_keys("a", &["size".to_string()])?);
+            assert!(!db.merge_payload("missing", fields(&[("color", json!("red"))]))?);
+            assert_eq!(
+                db.update_payload_batch(vec![
+                    (
+                        "b".to_string(),
+                        PayloadUpdate::Set(fields(&[("color", json!("red"))]))
+                    ),
+                    ("missing".to_string(), PayloadUpdate::DeleteKeys(vec![])),
+                    (
+                        "b".to_string(),
+                        PayloadUpdate::Merge(fields(&[("size", json!(1))]))
+                    ),
+                ])?,
+                vec![true, false, true]
+            );
+
+            // The vectors are untouched
+            assert_eq!(db.index.read().len(), 2);
+            assert_eq!(db.get("a")?.unwrap().vector, vec![1.0, 0.0]);
+            assert_eq!(
+                db.get("a")?.unwrap().metadata,
+                Some(fields(&[("color", json!("blue"))]))
+            );
+            assert_eq!(
+                db.filter_ids(&color("red"))?,
+                HashSet::from(["b".to_string()])
+            );
+            assert_eq!(
+                db.filter_ids(&color("blue"))?,
+                HashSet::from(["a".to_string()])
+            );
+
+            assert!(db.update_payload("a", HashMap::new())?);
+            assert!(db.get("a")?.unwrap().metadata.is_none());
+            assert!(db.filter_ids(&color("blue"))?.is_empty());
+        }
+
+        let db = VectorDB::new(options)?;
+        assert_eq!(
+            db.filter_ids(&color("red"))?,
+            HashSet::from(["b".to_string()])
+        );
+        assert_eq!(
+            db.get("b")?.unwrap().metadata,
+            Some(fields(&[("color", json!("red")), ("size", json!(1))]))
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_index_file_is_loaded_and_log_tail_replayed() -> Result<()> {
+        let dir = tempdir().unwrap();
+        let db_path = dir.path().join("graph.db").to_string_lossy().to_string();
+        let options = || {
+            let mut options = DbOptions::default();
+            options.storage_path = db_path.clone();
+            options.dimensions = 4;
+            options.distance_metric = DistanceMetric::Euclidean;
+            options
+        };
+        let entry = |i: usize| VectorEntry {
+            id: Some(format!("v{}", i)),
+            vector: vec![i as f32, 1.0, 0.0, 0.0],
+            metadata: None,
+        };
+        let nearest = |db: &VectorDB, x: f32| -> Result<String> {
+            let results = db.search(SearchQuery {
+                vector: vec![x, 1.0, 0.0, 0.0],
+                k: 1,
+                filter: None,
+                ef_search: None,
+            })?;
+            Ok(results[0].id.clone())
+        };
+
+        {
+            let db = VectorDB::new(options())?;
+            db.insert_batch((0..50).map(entry).collect())?;
+            db.delete("v7")?;
+            assert_eq!(db.applied_sequence(), 51);
+        }
+        let sequence = IndexFile::open(&index_file_path(&db_path))?
+            .unwrap()
+            .sequence();
+        assert_eq!(sequence, 51);
+
+        {
+            let db = VectorDB::new(options())?;
+            assert_eq!(db.persisted_sequence.load(Ordering::SeqCst), sequence);
+            assert!(db.storage.read_log(0, 10)?.is_none());
+            assert_eq!(db.len()?, 49);
+            assert_eq!(nearest(&db, 7.2)?, "v8");
+
+            // Crash after some writes, leaving the old index file behind
+            db.insert(entry(100))?;
+            db.delete("v8")?;
+            std::mem::forget(db);
+        }
+
+        // The file is still loaded and only the two logged operations replayed
+        let db = VectorDB::new(options())?;
+        assert_eq!(db.persisted_sequence.load(Ordering::SeqCst), sequence);
+        assert_eq!(db.applied_sequence(), sequence + 2);
+        assert_eq!(nearest(&db, 99.0)?, "v100");
+        assert_eq!(nearest(&db, 8.0)?, "v9");
+        drop(db);
+
+        // Without a usable index file everything is rebuilt from storage
+        std::fs::write(index_file_path(&db_path), b"garbage")?;
+        let db = VectorDB::new(options())?;
+        assert_eq!(db.persisted_sequence.load(Ordering::SeqCst), u64::MAX);
+        assert_eq!(db.applied_sequence(), sequence + 2);
+        assert_eq!(nearest(&db, 99.0)?, "v100");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_writes_through_other_handles_are_replayed() -> Result<()> {
+        use serde_json::json;
+
+        let dir = tempdir().unwrap();
+        let mut options = DbOptions::default();
+        options.storage_path = dir.path().join("shared.db").to_string_lossy().to_string();
+        options.dimensions = 2;
+        options.distance_metric = DistanceMetric::Euclidean;
+
+        let entry = |id: &str, x: f32| VectorEntry {
+            id: Some(id.to_string()),
+            vector: vec![x, 0.0],
+            metadata: Some(HashMap::from([("id".to_string(), json!(id))])),
+        };
+        let ids = |db: &VectorDB| -> Result<Vec<String>> {
+            let mut ids: Vec<String> = db
+                .search(SearchQuery {
+                    vector: vec![0.0, 0.0],
+                    k: 10,
+                    filter: None,
+                    ef_search: None,
+                })?
+                .into_iter()
+                .map(|r| r.id)
+                .collect();
+            ids.sort();
+            Ok(ids)
+        };
+
+        let db1 = VectorDB::new(options.clone())?;
+        db1.create_payload_index("id", IndexType::Keyword)?;
+        db1.insert(entry("a", 1.0))?;
+
+        let db2 = VectorDB::new(options)?;
+        db2.insert(entry("b", 2.0))?;
+        db2.delete("a")?;
+        assert_eq!(ids(&db1)?, ["a"]);
+
+        // The next write through the first handle applies the missed ones
+        db1.insert(entry("c", 3.0))?;
+        assert_eq!(ids(&db1)?, ["b", "c"]);
+        assert_eq!(db1.applied_sequence(), 4);
+
+        // Once the log is truncated past what a handle applied, it rebuilds
+        db2.checkpoint()?;
+        db2.insert(entry("d", 4.0))?;
+        db1.update_payload("b", HashMap::from([("id".to_string(), json!("x"))]))?;
+        assert_eq!(ids(&db1)?, ["b", "c", "d"]);
+        assert_eq!(db1.applied_sequence(), 6);
+        let filter = FilterExpression::eq("id", json!("x"));
+        assert_eq!(db1.filter_ids(&filter)?, HashSet::from(["b".to_string()]));
+
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(feature = "storage")]
+    fn test_named_vector_spaces() -> Result<()> {
+        let dir = tempdir().unwrap();
+        let mut options = DbOptions::default();
+        options.storage_path = dir.path().join("named.db").to_string_lossy().to_string();
+        options.dimensions = 2;
+        options.distance_metric = DistanceMetric::Euclidean;
+
+        let entry = |id: &str, x: f32| VectorEntry {
+            id: Some(id.to_string()),
+            vector: vec![x, 0.0],
+            metadata: None,
+        };
+        let query = |vector: Vec<f32>, k: usize| SearchQuery {
+            vector,
+            k,
+            filter: None,
+            ef_search: None,
+        };
+        let ids = |results: Vec<SearchResult>| -> Vec<String> {
+            results.into_iter().map(|r| r.id).collect()
+        };
+        let space = |name: &str, vector: Vec<f32>| (name.to_string(), vector);
+
+        let db = VectorDB::new(options.clone())?;
+        db.create_vector_space(
+            "title",
+            VectorSpaceConfig {
+                dimensions: 3,
+                distance_metric: DistanceMetric::Cosine,
+                hnsw_config: Some(HnswConfig::default()),
+                quantization: None,
+                multivector: None,
+            },
+        )?;
+        db.create_vector_space(
+            "image",
+            VectorSpaceConfig {
+                dimensions: 2,
+                distance_metric: DistanceMetric::Euclidean,
+                hnsw_config: None,
+                quantization: None,
+                multivector: None,
+            },
+        )?;
+        assert!(db
+            .create_vector_space("title", db.vector_spaces()["title"].clone())
+            .is_err());
+
+        db.upsert_with_vectors(
+            entry("p1", 0.0),
+            HashMap::from([
+                space("title", vec![1.0, 0.0, 0.0]),
+                space("image", vec![0.0, 0.0]),
+            ]),
+        )?;
+        db.upsert_with_vectors(
+            entry("p2", 1.0),
+            HashMap::from([
+                space("title", vec![0.0, 1.0, 0.0]),
+                space("image", vec![5.0, 5.0]),
+            ]),
+        )?;
+        db.upsert_with_vectors(
+            entry("p3", 2.0),
+            HashMap::from([space("title", vec![0.9, 0.1, 0.0])]),
+        )?;
+        assert!(db
+            .upsert_with_vectors(entry("p4", 0.0), HashMap::from([space("title", vec![1.0])]))
+            .is_err());
+        assert!(db
+            .upsert_with_vectors(entry("p4", 0.0), HashMap::from([space("audio", vec![1.0])]))
+            .is_err());
+
+        let results = db.search_named("title", query(vec![1.0, 0.0, 0.0], 2))?;
+        assert_eq!(results[0].vector, Some(vec![1.0, 0.0, 0.0]));
+        assert_eq!(ids(results), ["p1", "p3"]);
+        assert_eq!(
+            ids(db.search_named("image", query(vec![5.0, 5.0], 1))?),
+            ["p2"]
+        );
+        assert!(db.search_named("audio", query(vec![1.0], 1)).is_err());
+
+        // p3 has no image vector, so it can't be scored in the image space
+        let fused = |vectors: Vec<(Option<&str>, Vec<f32>, f32)>| {
+            db.search_fused(FusedSearchQuery {
+                vectors: vectors
+                    .into_iter()
+                    .map(|(space, vector, weight)| WeightedVector {
+                        space: space.map(str::to_string),
+                        vector,
+                        weight,
+                    })
+                    .collect(),
+                k: 10,
+                filter: None,
+                ef_search: None,
+            })
+        };
+        let results = fused(vec![
+            (Some("title"), vec![1.0, 0.0, 0.0], 1.0),
+            (Some("image"), vec![5.0, 5.0], 0.01),
+        ])?;
+        assert!((results[0].score - 0.01 * 50f32.sqrt()).abs() < 1e-4);
+        assert_eq!(ids(results), ["p1", "p2"]);
+        let results = fused(vec![
+            (None, vec![2.0, 0.0], 1.0),
+            (Some("title"), vec![0.0, 1.0, 0.0], 1.0),
+        ])?;
+        assert_eq!(ids(results), ["p3", "p2", "p1"]);
+        assert!(fused(vec![]).is_err());
+
+        // Upserts without named vectors keep them; deletes drop them
+        assert!(db.update_vectors("p3", HashMap::from([space("image", vec![4.0, 4.0])]))?);
+        assert!(!db.update_vectors("p9", HashMap::from([space("image", vec![4.0, 4.0])]))?);
+        assert!(db.delete_vectors("p2", &["image".to_string()])?);
+        assert_eq!(
+            ids(db.search_named("image", query(vec![5.0, 5.0], 10))?),
+            ["p3", "p1"]
+        );
+        db.upsert(entry("p1", 3.0))?;
+        assert_eq!(db.get_vectors("p1")?.unwrap().len(), 2);
+        db.delete("p1")?;
+        assert_eq!(db.get_vectors("p1")?, None);
+        assert_eq!(
+            db.get_vectors("p2")?,
+            Some(HashMap::from([space("title", vec![0.0, 1.0, 0.0])]))
+        );
+        drop(db);
+
+        // Spaces are rebuilt from storage on open
+        let db = VectorDB::new(options)?;
+        assert_eq!(
+            db.vector_spaces().keys().collect::<Vec<_>>(),
+            ["image", "title"]
+        );
+        assert_eq!(
+            ids(db.search_named("title", query(vec![1.0, 0.0, 0.0], 10))?),
+            ["p3", "p2"]
+        );
+
+        assert!(db.drop_vector_space("image")?);
+        assert!(!db.drop_vector_space("image")?);
+        assert!(db.search_named("image", query(vec![5.0, 5.0], 1)).is_err());
+        assert_eq!(db.get_vectors("p3")?.unwrap().len(), 1);
+        db.create_vector_space(
+            "image",
+            VectorSpaceConfig {
+                dimensions: 4,
+                distance_metric: DistanceMetric::Euclidean,
+                hnsw_config: None,
+                quantization: None,
+                multivector: None,
+            },
+        )?;
+        assert!(db
+            .search_named("image", query(vec![0.0; 4], 10))?
+            .is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_multivector_space_late_interaction() -> Result<()> {
+        let dir = tempdir().unwrap();
+        let mut options = DbOptions::default();
+        options.storage_path = dir.path().join("colbert.db").to_string_lossy().to_string();
+        options.dimensions = 2;
+
+        let db = VectorDB::new(options)?;
+        db.create_vector_space(
+            "tokens",
+            VectorSpaceConfig {
+                dimensions: 2,
+                distance_metric: DistanceMetric::DotProduct,
+                hnsw_config: Some(HnswConfig::default()),
+                quantization: None,
+                multivector: Some(MultiVectorConfig::default()),
+            },
+        )?;
+
+        let passages: [(&str, Vec<Vec<f32>>); 3] = [
+            ("both", vec![vec![1.0, 0.0], vec![0.0, 1.0]]),
+            (
+                "first",
+                vec![vec![1.5, 0.0], vec![0.1, 0.0], vec![0.2, 0.0]],
+            ),
+            ("neither", vec![vec![-1.0, -1.0]]),
+        ];
+        for (id, tokens) in &passages {
+            db.insert(VectorEntry {
+                id: Some(id.to_string()),
+                vector: vec![1.0, 1.0],
+                metadata: None,
+            })?;
+            assert!(db.update_multivector(id, "tokens", tokens)?);
+        }
+        assert!(db
+            .update_multivector("both", "tokens", &[vec![1.0, 0.0], vec![1.0]])
+            .is_err());
+        assert!(db
+            .update_vectors(
+                "both",
+                HashMap::from([("tokens".to_string(), vec![1.0; 3])])
+            )
+            .is_err());
+
+        // MaxSim: both = 1 + 1, first = 1.5 + 0, neither = -1 - 1
+        let query = [vec![1.0, 0.0], vec![0.0, 1.0]];
+        let results = db.search_multivector("tokens", &query, 3, None)?;
+        let ids: Vec<&str> = results.iter().map(|r| r.id.as_str()).collect();
+        assert_eq!(ids, ["both", "first", "neither"]);
+        assert!((results[0].score + 2.0).abs() < 1e-5);
+        assert!((results[2].score - 2.0).abs() < 1e-5);
+        assert_eq!(results[0].vector, Some(vec![1.0, 0.0, 0.0, 1.0]));
+
+        // One query token that only "first" matches strongly
+        let results = db.search_multivector("tokens", &[vec![3.0, 0.0]], 1, None)?;
+        assert_eq!(results[0].id, "first");
+
+        // Fused scoring uses MaxSim for multi-vector spaces
+        let results = db.search_fused(FusedSearchQuery {
+            vectors: vec![WeightedVector {
+                space: Some("tokens".to_string()),
+                vector: query.concat(),
+                weight: 1.0,
+            }],
+            k: 1,
+            filter: None,
+            ef_search: None,
+        })?;
+        assert!((results[0].score + 2.0).abs() < 1e-5);
+
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(feature = "storage")]
+    fn test_sparse_vectors_and_hybrid_search() -> Result<()> {
+        use crate::advanced_features::NormalizationStrategy;
+        use serde_json::json;
+
+        let dir = tempdir().unwrap();
+        let mut options = DbOptions::default();
+        options.storage_path = dir.path().join("hybrid.db").to_string_lossy().to_string();
+        options.dimensions = 2;
+        options.distance_metric = DistanceMetric::Euclidean;
+
+        let sparse = |entries: &[(u32, f32)]| SparseVector::new(entries.to_vec()).unwrap();
+        let terms = |vector: SparseVector| HashMap::from([("terms".to_string(), vector)]);
+        let ids = |results: Vec<SearchResult>| -> Vec<String> {
+            results.into_iter().map(|r| r.id).collect()
+        };
+        let hybrid = |sparse: SparseVector, fusion: HybridFusion| HybridSearchQuery {
+            dense_space: None,
+            dense: vec![0.0, 0.0],
+            sparse_space: "terms".to_string(),
+            sparse,
+            k: 3,
+            filter: None,
+            fusion,
+            ef_search: None,
+        };
+
+        {
+            let db = VectorDB::new(options.clone())?;
+            db.create_sparse_space("terms")?;
+            assert!(db.create_sparse_space("terms").is_err());
+            assert!(db
+                .create_vector_space(
+                    "terms",
+                    VectorSpaceConfig {
+                        dimensions: 2,
+                        distance_metric: DistanceMetric::Euclidean,
+                        hnsw_config: None,
+                        quantization: None,
+                        multivector: None,
+                    },
+                )
+                .is_err());
+
+            for (id, x, lang) in [("p1", 0.0, "en"), ("p2", 1.0, "de"), ("p3", 5.0, "en")] {
+                db.insert(VectorEntry {
+                    id: Some(id.to_string()),
+                    vector: vec![x, 0.0],
+                    metadata: Some(HashMap::from([("lang".to_string(), json!(lang))])),
+                })?;
+            }
+            assert!(db.update_sparse_vectors("p1", terms(sparse(&[(1, 1.0)])))?);
+            assert!(db.update_sparse_vectors("p2", terms(sparse(&[(2, 2.0), (1, 0.5)])))?);
+            assert!(db.update_sparse_vectors("p3", terms(sparse(&[(2, 1.0), (7, 3.0)])))?);
+            assert!(!db.update_sparse_vectors("p4", terms(sparse(&[(1, 1.0)])))?);
+            let unsorted = SparseVector {
+                indices: vec![2, 1],
+                values: vec![1.0, 1.0],
+            };
+            assert!(db.update_sparse_vectors("p1", terms(unsorted)).is_err());
+            assert!(db
+                .update_sparse_vectors(
+                    "p1",
+                    HashMap::from([("body".to_string(), sparse(&[(1, 1.0)]))])
+                )
+                .is_err());
+
+            // Dense order is p1, p2, p3 while only p3 matches term 7
+            let results = db.search_hybrid(hybrid(sparse(&[(7, 1.0)]), HybridFusion::default()))?;
+            assert_eq!(results[0].vector, Some(vec![5.0, 0.0]));
+            assert!((results[0].score - (1.0 / 61.0 + 1.0 / 63.0)).abs() < 1e-6);
+            assert_eq!(ids(results), ["p3", "p1", "p2"]);
+
+            let weighted = HybridFusion::Weighted {
+                dense_weight: 0.2,
+                sparse_weight: 1.0,
+                normalization: NormalizationStrategy::MinMax,
+            };
+            assert_eq!(
+                ids(db.search_hybrid(hybrid(sparse(&[(1, 1.0), (2, 1.0)]), weighted))?),
+                ["p2", "p1", "p3"]
+            );
+        }
+
+        // Sparse spaces and vectors survive a restart
+        let db = VectorDB::new(options)?;
+        assert_eq!(
+            db.sparse_spaces().into_iter().collect::<Vec<_>>(),
+            ["terms"]
+        );
+        assert_eq!(
+            db.get_sparse_vectors("p3")?.unwrap()["terms"],
+            sparse(&[(2, 1.0), (7, 3.0)])
+        );
+
+        let results = db.search_sparse("terms", &sparse(&[(2, 1.0)]), 5, None)?;
+        assert!((results[0].score + 2.0).abs() < 1e-6);
+        assert_eq!(ids(results), ["p2", "p3"]);
+        let english = SearchFilter::Equals(HashMap::from([("lang".to_string(), json!("en"))]));
+        assert_eq!(
+            ids(db.search_sparse("terms", &sparse(&[(2, 1.0)]), 5, Some(english))?),
+            ["p3"]
+        );
+
+        assert!(db.delete_sparse_vectors("p2", &["terms".to_string()])?);
+        db.delete("p3")?;
+        assert!(db
+            .search_sparse("terms", &sparse(&[(2, 1.0)]), 5, None)?
+            .is_empty());
+        assert_eq!(db.get_sparse_vectors("p2")?, Some(HashMap::new()));
+
+        assert!(db.drop_sparse_space("terms")?);
+        assert!(!db.drop_sparse_space("terms")?);
+        assert!(db
+            .search_sparse("terms", &sparse(&[(1, 1.0)]), 5, None)
+            .is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_scroll_pages_through_points_in_id_order() -> Result<()> {
+        let dir = tempdir().unwrap();
+        let mut options = DbOptions::default();
+        options.storage_path = dir.path().join("scroll.db").to_string_lossy().to_string();
+        options.dimensions = 2;
+        options.hnsw_config = None;
+
+        let db = VectorDB::new(options)?;
+        for i in (0..25).rev() {
+            let mut metadata = HashMap::new();
+            metadata.insert("even".to_string(), serde_json::json!(i % 2 == 0));
+            db.insert(VectorEntry {
+                id: Some(format!("p{:02}", i)),
+                vector: vec![i as f32, 0.0],
+                metadata: Some(metadata),
+            })?;
+        }
+
+        let mut request = ScrollRequest {
+            limit: 10,
+            ..Default::default()
+        };
+        let mut ids = Vec::new();
+        let mut pages = 0;
+        loop {
+            let page = db.scroll(request.clone())?;
+            pages += 1;
+            assert!(page
+                .points
+                .iter()
+                .all(|p| p.vector.is_some() && p.metadata.is_some()));
+            ids.extend(page.points.into_iter().map(|p| p.id));
+            match page.next_offset {
+                Some(offset) => request.offset = Some(offset),
+                None => break,
+            }
+        }
+        assert_eq!(pages, 3);
+        let expected: Vec<String> = (0..25).map(|i| format!("p{:02}", i)).collect();
+        assert_eq!(ids, expected);
+
+        // Filtered scroll with projection; the filter doesn't shrink pages
+        let mut filter = HashMap::new();
+        filter.insert("even".to_string(), serde_json::json!(true));
+        let page = db.scroll(ScrollRequest {
+            offset: None,
+            limit: 5,
+            filter: Some(SearchFilter::Equals(filter)),
+            with_vector: false,
+            with_payload: false,
+        })?;
+        let ids: Vec<&str> = page.points.iter().map(|p| p.id.as_str()).collect();
+        assert_eq!(ids, ["p00", "p02", "p04", "p06", "p08"]);
+        assert!(page
+            .points
+            .iter()
+            .all(|p| p.vector.is_none() && p.metadata.is_none()));
+        assert!(page.next_offset.is_some());
+
+        assert!(db
+            .scroll(ScrollRequest {
+                offset: Some("not hex".to_string()),
+                ..Default::default()
+            })
+            .is_err());
+        assert!(db
+            .scroll(ScrollRequest {
+                limit: 0,
+                ..Default::default()
+            })
+            .is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_ef_search_per_query_and_runtime_default() -> Result<()> {
+        let dir = tempdir().unwrap();
+        let mut options = DbOptions::default();
+        options.storage_path = dir.path().join("ef.db").to_string_lossy().to_string();
+        options.dimensions = 16;
+        options.distance_metric = DistanceMetric::Euclidean;
+        options.quantization = None;
+        options.hnsw_config = Some(HnswConfig {
+            m: 4,
+            ef_construction: 32,
+            ef_search: 1,
+            max_elements: 10_000,
+            seed: Some(7),
+            ..HnswConfig::default()
+        });
+        let db = VectorDB::new(options)?;
+        assert_eq!(db.ef_search(), 1);
+        assert!(db.set_ef_search(0).is_err());
+
+        use rand::{Rng, SeedableRng};
+        let mut rng = rand::rngs::StdRng::seed_from_u64(11);
+        let vectors: Vec<Vec<f32>> = (0..2000)
+            .map(|_| (0..16).map(|_| rng.gen_range(-1.0..1.0)).collect())
+            .collect();
+        db.insert_batch(
+            vectors
+                .iter()
+                .enumerate()
+                .map(|(i, v)| VectorEntry {
+                    id: Some(i.to_string()),
+                    vector: v.clone(),
+                    metadata: None,
+                })
+                .collect(),
+        )?;
+
+        let queries: Vec<Vec<f32>> = (0..30)
+            .map(|_| (0..16).map(|_| rng.gen_range(-1.0..1.0)).collect())
+            .collect();
+        let recall = |ef_search: Option<usize>| -> Result<f32> {
+            let mut hits = 0;
+            for query in &queries {
+                let mut exact: Vec<(usize, f32)> = vectors
+                    .iter()
+                    .enumerate()
+                    .map(|(i, v)| (i, distance(query, v, DistanceMetric::Euclidean).unwrap()))
+                    .collect();
+                exact.sort_by(|a, b| a.1.total_cmp(&b.1));
+                let expected: HashSet<String> =
+                    exact.iter().take(10).map(|(i, _)| i.to_string()).collect();
+
+                let results = db.search(SearchQuery {
+                    vector: query.clone(),
+                    k: 10,
+                    filter: None,
+                    ef_search,
+                })?;
+                hits += results.iter().filter(|r| expected.contains(&r.id)).count();
             }
-        } else {
-            Box::new(FlatIndex::new(options.dimensions, options.distance_metric))
+            Ok(hits as f32 / (queries.len() * 10) as f32)
         };
 
-        // Rebuild index from persisted vectors if storage is not empty
-        // This fixes the bug where search() returns empty results after restart
-        #[cfg(feature = "storage")]
-        {
-            let stored_ids = storage.all_ids()?;
-            if !stored_ids.is_empty() {
-                tracing::info!(
-                    "Rebuilding index from {} persisted vectors",
-                    stored_ids.len()
-                );
+        let narrow = recall(None)?;
+        let wide = recall(Some(2000))?;
+        assert!(wide > 0.99, "recall with ef_search=2000 was {}", wide);
+        assert!(narrow < wide, "narrow {} wide {}", narrow, wide);
 
-                // Batch load all vectors for efficient index rebuilding
-                let mut entries = Vec::with_capacity(stored_ids.len());
-                for id in stored_ids {
-                    if let Some(entry) = storage.get(&id)? {
-                        entries.push((id, entry.vector));
-                    }
-                }
+        db.set_ef_search(2000)?;
+        assert_eq!(recall(None)?, wide);
+        assert_eq!(recall(Some(1))?, narrow);
 
-                // Add all vectors to index in batch for better performance
-                index.add_batch(entries)?;
+        Ok(())
+    }
 
-                tracing::info!("Index rebuilt successfully");
-            }
+    #[test]
+    fn test_range_search_and_search_by_id() -> Result<()> {
+        let dir = tempdir().unwrap();
+        let mut options = DbOptions::default();
+        options.storage_path = dir.path().join("range.db").to_string_lossy().to_string();
+        options.dimensions = 2;
+        options.distance_metric = DistanceMetric::Euclidean;
+
+        let db = VectorDB::new(options)?;
+        for i in 0..60 {
+            let mut metadata = HashMap::new();
+            metadata.insert("even".to_string(), serde_json::json!(i % 2 == 0));
+            db.insert(VectorEntry {
+                id: Some(format!("p{:02}", i)),
+                vector: vec![i as f32, 0.0],
+                metadata: Some(metadata),
+            })?;
         }
 
-        Ok(Self {
-            storage,
-            index: Arc::new(RwLock::new(index)),
-            options,
-        })
-    }
+        let range = RangeSearchQuery {
+            space: None,
+            vector: vec![0.0, 0.0],
+            radius: 40.5,
+            max_results: 100,
+            filter: None,
+            ef_search: None,
+        };
+        let results = db.search_range(range.clone())?;
+        assert_eq!(results.len(), 41);
+        assert_eq!(results[40].id, "p40");
+        assert!(results
+            .iter()
+            .all(|r| r.vector.is_some() && r.metadata.is_some()));
 
-    /// Create with default options
-    pub fn with_dimensions(dimensions: usize) -> Result<Self> {
-        let mut options = DbOptions::default();
-        options.dimensions = dimensions;
-        Self::new(options)
-    }
+        let mut filter = HashMap::new();
+        filter.insert("even".to_string(), serde_json::json!(true));
+        let results = db.search_range(RangeSearchQuery {
+            filter: Some(SearchFilter::Equals(filter.clone())),
+            ..range.clone()
+        })?;
+        assert_eq!(results.len(), 21);
 
-    /// Insert a vector entry
-    pub fn insert(&self, entry: VectorEntry) -> Result<VectorId> {
-        let id = self.storage.insert(&entry)?;
+        let results = db.search_range(RangeSearchQuery {
+            max_results: 5,
+            ..range.clone()
+        })?;
+        assert_eq!(results.len(), 5);
+        assert!(db
+            .search_range(RangeSearchQuery {
+                max_results: 0,
+                ..range.clone()
+            })
+            .is_err());
 
-        // Add to index
-        let mut index = self.index.write();
-        index.add(id.clone(), entry.vector)?;
+        let by_id = SearchByIdQuery {
+            id: "p10".to_string(),
+            space: None,
+            k: 2,
+            filter: Some(SearchFilter::Equals(filter)),
+            ef_search: None,
+        };
+        let results = db.search_by_id(by_id.clone())?.unwrap();
+        let mut ids: Vec<&str> = results.iter().map(|r| r.id.as_str()).collect();
+        ids.sort();
+        assert_eq!(ids, ["p08", "p12"]);
+        assert!(db
+            .search_by_id(SearchByIdQuery {
+                id: "missing".to_string(),
+                ..by_id.clone()
+            })?
+            .is_none());
+        assert!(db
+            .search_by_id(SearchByIdQuery {
+                space: Some("unknown".to_string()),
+                ..by_id
+            })
+            .is_err());
 
-        Ok(id)
+        Ok(())
     }
 
-    /// Insert multiple vectors in a batch
-    pub fn insert_batch(&self, entries: Vec<VectorEntry>) -> Result<Vec<VectorId>> {
-        let ids = self.storage.insert_batch(&entries)?;
+    #[test]
+    fn test_quantized_range_search_uses_exact_distances() -> Result<()> {
+        let dir = tempdir().unwrap();
+        let mut options = DbOptions::default();
+        options.storage_path = dir
+            .path()
+            .join("quantized_range.db")
+            .to_string_lossy()
+            .to_string();
+        options.dimensions = 16;
+        options.distance_metric = DistanceMetric::Euclidean;
+        options.quantization = Some(QuantizationConfig::Binary);
 
-        // Add to index
-        let mut index = self.index.write();
-        let index_entries: Vec<_> = ids
-            .iter()
-            .zip(entries.iter())
-            .map(|(id, entry)| (id.clone(), entry.vector.clone()))
+        let db = VectorDB::new(options)?;
+        use rand::{Rng, SeedableRng};
+        let mut rng = rand::rngs::StdRng::seed_from_u64(11);
+        let vectors: Vec<Vec<f32>> = (0..300)
+            .map(|_| (0..16).map(|_| rng.gen_range(-1.0..1.0)).collect())
             .collect();
+        db.insert_batch(
+            vectors
+                .iter()
+                .enumerate()
+                .map(|(i, v)| VectorEntry {
+                    id: Some(format!("v{}", i)),
+                    vector: v.clone(),
+                    metadata: None,
+                })
+                .collect(),
+        )?;
+
+        let mut exact: Vec<f32> = vectors
+            .iter()
+            .map(|v| distance(&vectors[0], v, DistanceMetric::Euclidean))
+            .collect::<Result<_>>()?;
+        exact.sort_by(f32::total_cmp);
+        let radius = exact[40];
 
-        index.add_batch(index_entries)?;
+        let results = db.search_range(RangeSearchQuery {
+            space: None,
+            vector: vectors[0].clone(),
+            radius,
+            max_results: 1000,
+            filter: None,
+            ef_search: Some(200),
+        })?;
+        assert_eq!(results[0].id, "v0");
+        assert!(results.len() > 35 && results.len() <= 41);
+        for result in &results {
+            let vector = &vectors[result.id[1..].parse::<usize>().unwrap()];
+            let expected = distance(&vectors[0], vector, DistanceMetric::Euclidean)?;
+            assert!((result.score - expected).abs() < 1e-5);
+            assert!(result.score <= radius);
+        }
 
-        Ok(ids)
+        Ok(())
     }
 
-    /// Search for similar vectors
-    pub fn search(&self, query: SearchQuery) -> Result<Vec<SearchResult>> {
-        let index = self.index.read();
-        let mut results = index.search(&query.vector, query.k)?;
+    #[test]
+    fn test_grouped_search_expands_until_enough_groups() -> Result<()> {
+        let dir = tempdir().unwrap();
+        let mut options = DbOptions::default();
+        options.storage_path = dir.path().join("groups.db").to_string_lossy().to_string();
+        options.dimensions = 2;
+        options.distance_metric = DistanceMetric::Euclidean;
 
-        // Enrich results with full data if needed
-        for result in &mut results {
-            if let Ok(Some(entry)) = self.storage.get(&result.id) {
-                result.vector = Some(entry.vector);
-                result.metadata = entry.metadata;
+        let db = VectorDB::new(options)?;
+        // 20 chunks per document, each document's chunks closer together
+        // than to any other document
+        for doc in 0..10 {
+            for chunk in 0..20 {
+                let mut metadata = HashMap::new();
+                metadata.insert("doc_id".to_string(), serde_json::json!(doc));
+                db.insert(VectorEntry {
+                    id: Some(format!("d{}c{}", doc, chunk)),
+                    vector: vec![doc as f32 * 10.0 + chunk as f32 * 0.01, 0.0],
+                    metadata: Some(metadata),
+                })?;
             }
         }
+        db.insert(VectorEntry {
+            id: Some("ungrouped".to_string()),
+            vector: vec![0.0, 0.0],
+            metadata: None,
+        })?;
 
-        // Apply metadata filters if specified
-        if let Some(filter) = &query.filter {
-            results.retain(|r| {
-                if let Some(metadata) = &r.metadata {
-                    filter
-                        .iter()
-                        .all(|(key, value)| metadata.get(key).map_or(false, |v| v == value))
-                } else {
-                    false
-                }
-            });
-        }
+        let query = GroupedSearchQuery {
+            space: None,
+            vector: vec![0.0, 0.0],
+            group_by: "doc_id".to_string(),
+            groups: 3,
+            group_size: 2,
+            filter: None,
+            ef_search: None,
+        };
+        let groups = db.search_groups(query.clone())?;
+        let keys: Vec<String> = groups.iter().map(|g| g.key.to_string()).collect();
+        assert_eq!(keys, ["0", "1", "2"]);
+        assert!(groups.iter().all(|g| g.hits.len() == 2));
+        assert_eq!(groups[0].hits[0].id, "d0c0");
+        assert_eq!(groups[1].hits[0].id, "d1c0");
 
-        Ok(results)
+        // Fewer groups than requested once every candidate was seen
+        let groups = db.search_groups(GroupedSearchQuery {
+            groups: 20,
+            ..query.clone()
+        })?;
+        assert_eq!(groups.len(), 10);
+
+        assert!(db
+            .search_groups(GroupedSearchQuery {
+                group_size: 0,
+                ..query
+            })
+            .is_err());
+
+        Ok(())
     }
 
-    /// Delete a vector by ID
-    pub fn delete(&self, id: &str) -> Result<bool> {
-        let deleted_storage = self.storage.delete(id)?;
+    #[test]
+    fn test_search_batch_matches_single_searches() -> Result<()> {
+        let dir = tempdir().unwrap();
+        let mut options = DbOptions::default();
+        options.storage_path = dir.path().join("batch.db").to_string_lossy().to_string();
+        options.dimensions = 8;
+        options.quantization = Some(QuantizationConfig::Scalar);
 
-        if deleted_storage {
-            let mut index = self.index.write();
-            let _ = index.remove(&id.to_string())?;
+        let db = VectorDB::new(options)?;
+        use rand::{Rng, SeedableRng};
+        let mut rng = rand::rngs::StdRng::seed_from_u64(3);
+        let vectors: Vec<Vec<f32>> = (0..500)
+            .map(|_| (0..8).map(|_| rng.gen_range(-1.0..1.0)).collect())
+            .collect();
+        db.insert_batch(
+            vectors
+                .iter()
+                .enumerate()
+                .map(|(i, v)| {
+                    let mut metadata = HashMap::new();
+                    metadata.insert("bucket".to_string(), serde_json::json!(i % 5));
+                    VectorEntry {
+                        id: Some(format!("v{}", i)),
+                        vector: v.clone(),
+                        metadata: Some(metadata),
+                    }
+                })
+                .collect(),
+        )?;
+
+        let queries: Vec<SearchQuery> = (0..40)
+            .map(|i| {
+                let filter = (i % 2 == 0).then(|| {
+                    let mut filter = HashMap::new();
+                    filter.insert("bucket".to_string(), serde_json::json!(i % 5));
+                    SearchFilter::Equals(filter)
+                });
+                SearchQuery {
+                    vector: vectors[i * 7].clone(),
+                    k: 1 + i % 10,
+                    filter,
+                    ef_search: None,
+                }
+            })
+            .collect();
+
+        let batches = db.search_batch(queries.clone())?;
+        assert_eq!(batches.len(), queries.len());
+        for (query, batch) in queries.into_iter().zip(batches) {
+            let single = db.search(query)?;
+            assert_eq!(batch.len(), single.len());
+            for (a, b) in batch.iter().zip(&single) {
+                assert_eq!(a.id, b.id);
+                assert_eq!(a.score, b.score);
+                assert_eq!(a.vector, b.vector);
+                assert_eq!(a.metadata, b.metadata);
+            }
         }
 
-        Ok(deleted_storage)
+        assert!(db.search_batch(Vec::new())?.is_empty());
+        Ok(())
     }
 
-    /// Get a vector by ID
-    pub fn get(&self, id: &str) -> Result<Option<VectorEntry>> {
-        self.storage.get(id)
-    }
+    #[test]
+    fn test_quantized_search_is_rescored_exactly() -> Result<()> {
+        let dir = tempdir().unwrap();
+        let mut options = DbOptions::default();
+        options.storage_path = dir
+            .path()
+            .join("quantized.db")
+            .to_string_lossy()
+            .to_string();
+        options.dimensions = 16;
+        options.distance_metric = DistanceMetric::Euclidean;
+        options.quantization = Some(QuantizationConfig::Binary);
 
-    /// Get the number of vectors
-    pub fn len(&self) -> Result<usize> {
-        self.storage.len()
-    }
+        let db = VectorDB::new(options)?;
+        assert_eq!(db.rescore_oversampling(), DEFAULT_RESCORE_OVERSAMPLING);
 
-    /// Check if database is empty
-    pub fn is_empty(&self) -> Result<bool> {
-        self.storage.is_empty()
-    }
+        use rand::{Rng, SeedableRng};
+        let mut rng = rand::rngs::StdRng::seed_from_u64(7);
+        let vectors: Vec<Vec<f32>> = (0..300)
+            .map(|_| (0..16).map(|_| rng.gen_range(-1.0..1.0)).collect())
+            .collect();
+        db.insert_batch(
+            vectors
+                .iter()
+                .enumerate()
+                .map(|(i, v)| VectorEntry {
+                    id: Some(format!("v{}", i)),
+                    vector: v.clone(),
+                    metadata: None,
+                })
+                .collect(),
+        )?;
 
-    /// Get database options
-    pub fn options(&self) -> &DbOptions {
-        &self.options
-    }
+        let query = vectors[42].iter().map(|x| x + 0.01).collect::<Vec<f32>>();
+        let results = db.search(SearchQuery {
+            vector: query.clone(),
+            k: 5,
+            filter: None,
+            ef_search: None,
+        })?;
 
-    /// Get all vector IDs (for iteration/serialization)
-    pub fn keys(&self) -> Result<Vec<String>> {
-        self.storage.all_ids()
-    }
-}
+        assert_eq!(results.len(), 5);
+        assert_eq!(results[0].id, "v42");
+        for result in &results {
+            let exact = distance(
+                &query,
+                result.vector.as_ref().unwrap(),
+                DistanceMetric::Euclidean,
+            )?;
+            assert!((result.score - exact).abs() < 1e-4);
+        }
+        assert!(results.windows(2).all(|w| w[0].score <= w[1].score));
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use std::path::Path;
-    use tempfile::tempdir;
+        db.set_rescore_oversampling(0);
+        let results = db.search(SearchQuery {
+            vector: query,
+            k: 5,
+            filter: None,
+            ef_search: None,
+        })?;
+        assert_eq!(results.len(), 5);
+
+        Ok(())
+    }
 
     #[test]
-    fn test_vector_db_creation() -> Result<()> {
+    fn test_checkpoint_taken_in_background() -> Result<()> {
         let dir = tempdir().unwrap();
+        let db_path = dir.path().join("auto.db").to_string_lossy().to_string();
         let mut options = DbOptions::default();
-        options.storage_path = dir.path().join("test.db").to_string_lossy().to_string();
-        options.dimensions = 3;
+        options.storage_path = db_path.clone();
+        options.dimensions = 2;
+        options.checkpoint = CheckpointConfig {
+            max_operations: Some(10),
+            max_log_bytes: None,
+        };
 
         let db = VectorDB::new(options)?;
-        assert!(db.is_empty()?);
+        for i in 0..25 {
+            db.insert(VectorEntry {
+                id: Some(format!("v{}", i)),
+                vector: vec![i as f32, 1.0],
+                metadata: None,
+            })?;
+        }
+        db.wait_for_checkpoint();
+
+        let sequence = IndexFile::open(&index_file_path(&db_path))?
+            .unwrap()
+            .sequence();
+        assert!(sequence >= 10);
+        assert_eq!(db.persisted_sequence.load(Ordering::SeqCst), sequence);
+        assert!(db.storage.read_log(0, 1)?.is_none());
+        assert_eq!(db.len()?, 25);
 
         Ok(())
     }
 
     #[test]
-    fn test_insert_and_search() -> Result<()> {
+    fn test_checkpoint_during_writes_tags_the_graph_it_wrote() -> Result<()> {
         let dir = tempdir().unwrap();
+        let db_path = dir.path().join("inflight.db").to_string_lossy().to_string();
         let mut options = DbOptions::default();
-        options.storage_path = dir.path().join("test.db").to_string_lossy().to_string();
-        options.dimensions = 3;
-        options.distance_metric = DistanceMetric::Euclidean; // Use Euclidean for clearer test
-        options.hnsw_config = None; // Use flat index for testing
+        options.storage_path = db_path.clone();
+        options.dimensions = 2;
+        options.checkpoint = CheckpointConfig {
+            max_operations: None,
+            max_log_bytes: None,
+        };
 
-        let db = VectorDB::new(options)?;
+        let db = Arc::new(VectorDB::new(options)?);
+        let writer = {
+            let db = db.clone();
+            std::thread::spawn(move || -> Result<()> {
+                for i in 0..300 {
+                    db.insert(VectorEntry {
+                        id: Some(format!("v{}", i)),
+                        vector: vec![i as f32, 1.0],
+                        metadata: None,
+                    })?;
+                }
+                Ok(())
+            })
+        };
 
-        // Insert vectors
-        db.insert(VectorEntry {
-            id: Some("v1".to_string()),
-            vector: vec![1.0, 0.0, 0.0],
-            metadata: None,
-        })?;
+        // Each insert logs one operation, so the graph written at sequence
+        // `n` holds exactly `n` vectors
+        while !writer.is_finished() {
+            assert!(db.persist_index()?);
+            let file = IndexFile::open(&index_file_path(&db_path))?.unwrap();
+            let index = HnswIndex::deserialize(file.payload())?;
+            assert_eq!(index.len() as u64, file.sequence());
+        }
+        writer.join().unwrap()?;
 
-        db.insert(VectorEntry {
-            id: Some("v2".to_string()),
-            vector: vec![0.0, 1.0, 0.0],
-            metadata: None,
-        })?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_payload_updates_leave_the_vector_index_unlocked() -> Result<()> {
+        let dir = tempdir().unwrap();
+        let mut options = DbOptions::default();
+        options.storage_path = dir.path().join("payload.db").to_string_lossy().to_string();
+        options.dimensions = 2;
 
+        let db = VectorDB::new(options)?;
         db.insert(VectorEntry {
-            id: Some("v3".to_string()),
-            vector: vec![0.0, 0.0, 1.0],
+            id: Some("a".to_string()),
+            vector: vec![1.0, 0.0],
             metadata: None,
         })?;
 
-        // Search for exact match
-        let results = db.search(SearchQuery {
-            vector: vec![1.0, 0.0, 0.0],
-            k: 2,
-            filter: None,
-            ef_search: None,
-        })?;
-
-        assert!(results.len() >= 1);
-        assert_eq!(results[0].id, "v1", "First result should be exact match");
-        assert!(
-            results[0].score < 0.01,
-            "Exact match should have ~0 distance"
-        );
+        let _index = db.index.write();
+        let metadata = HashMap::from([("tag".to_string(), serde_json::json!("x"))]);
+        assert!(db.update_payload("a", metadata)?);
+        assert_eq!(db.applied_sequence(), 2);
 
         Ok(())
     }
@@ -387,4 +4245,193 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn test_text_upsert_and_search_with_persisted_embedding() -> Result<()> {
+        let dir = tempdir().unwrap();
+        let mut options = DbOptions::default();
+        options.storage_path = dir.path().join("text.db").to_string_lossy().to_string();
+        options.dimensions = 16;
+
+        let db = VectorDB::new(options.clone())?;
+        assert!(db.search_text("hello", 1, None).is_err());
+        assert!(db
+            .set_embedding(Some(EmbeddingConfig::Hash { dimensions: 8 }))
+            .is_err());
+
+        db.set_embedding(Some(EmbeddingConfig::Hash { dimensions: 16 }))?;
+        let texts: Vec<TextEntry> = (0..100)
+            .map(|i| TextEntry {
+                id: Some(format!("t{}", i)),
+                text: format!("document number {}", i),
+                metadata: None,
+            })
+            .collect();
+        assert_eq!(db.upsert_texts(texts)?.len(), 100);
+        assert_eq!(db.len()?, 100);
+        drop(db);
+
+        // The provider configuration survives reopening
+        let db = VectorDB::new(options)?;
+        assert_eq!(
+            db.embedding_config(),
+            Some(EmbeddingConfig::Hash { dimensions: 16 })
+        );
+        let results = db.search_text("document number 42", 1, None)?;
+        assert_eq!(results[0].id, "t42");
+        assert!(results[0].score.abs() < 1e-4);
+
+        db.set_embedding(None)?;
+        assert!(db.embed(&["hello"]).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_change_listeners_see_committed_writes() -> Result<()> {
+        use crate::changes::{ChangeListener, PointChange, PointChangeKind};
+        use parking_lot::Mutex;
+
+        #[derive(Default)]
+        struct Recorder(Mutex<Vec<PointChange>>);
+
+        impl ChangeListener for Recorder {
+            fn on_change(&self, changes: &[PointChange]) -> Result<()> {
+                self.0.lock().extend_from_slice(changes);
+                Ok(())
+            }
+        }
+
+        let dir = tempdir().unwrap();
+        let mut options = DbOptions::default();
+        options.storage_path = dir.path().join("changes.db").to_string_lossy().to_string();
+        options.dimensions = 2;
+        let db = VectorDB::new(options)?;
+        let recorder = Arc::new(Recorder::default());
+        db.add_change_listener(recorder.clone())?;
+
+        let entry = |id: &str, x: f32| VectorEntry {
+            id: Some(id.to_string()),
+            vector: vec![x, 0.0],
+            metadata: None,
+        };
+        db.upsert(entry("a", 1.0))?;
+        db.upsert_batch(vec![entry("a", 2.0), entry("b", 3.0)])?;
+        let metadata = HashMap::from([("tag".to_string(), serde_json::json!("x"))]);
+        assert!(db.update_payload("b", metadata.clone())?);
+        assert!(!db.update_payload("missing", metadata.clone())?);
+        assert!(db.delete("a")?);
+        assert!(!db.delete("a")?);
+        // Rejected writes aren't reported
+        assert!(db
+            .upsert(VectorEntry {
+                vector: vec![1.0],
+                ..entry("c", 0.0)
+            })
+            .is_err());
+
+        let changes = recorder.0.lock();
+        let summary: Vec<_> = changes
+            .iter()
+            .map(|change| (change.kind, change.id.as_str()))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                (PointChangeKind::Insert, "a"),
+                (PointChangeKind::Update, "a"),
+                (PointChangeKind::Insert, "b"),
+                (PointChangeKind::Update, "b"),
+                (PointChangeKind::Delete, "a"),
+            ]
+        );
+        assert_eq!(changes[1].before.as_ref().unwrap().vector, vec![1.0, 0.0]);
+        assert_eq!(changes[1].after.as_ref().unwrap().vector, vec![2.0, 0.0]);
+        assert_eq!(changes[3].after.as_ref().unwrap().metadata, Some(metadata));
+        assert_eq!(changes[4].before.as_ref().unwrap().vector, vec![2.0, 0.0]);
+        assert!(changes[4].after.is_none());
+        let sequences: Vec<_> = changes.iter().map(|change| change.sequence).collect();
+        assert_eq!(sequences, vec![1, 2, 3, 4, 5]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_failed_change_listeners_fail_writes_and_catch_up() -> Result<()> {
+        use crate::changes::{ChangeListener, PointChange, PointChangeKind};
+        use parking_lot::Mutex;
+        use std::sync::atomic::AtomicBool;
+
+        /// Records changes durably on flush, unless told to fail
+        #[derive(Default)]
+        struct Recorder {
+            staged: Mutex<Vec<PointChange>>,
+            flushed: Mutex<Vec<PointChange>>,
+            failing: AtomicBool,
+        }
+
+        impl ChangeListener for Recorder {
+            fn on_change(&self, changes: &[PointChange]) -> Result<()> {
+                self.staged.lock().extend_from_slice(changes);
+                Ok(())
+            }
+
+            fn flush(&self) -> Result<()> {
+                let staged = std::mem::take(&mut *self.staged.lock());
+                if self.failing.load(Ordering::SeqCst) {
+                    return Err(RuvectorError::Internal("flush failed".to_string()));
+                }
+                self.flushed.lock().extend(staged);
+                Ok(())
+            }
+
+            fn resume_after(&self) -> Option<u64> {
+                let flushed = self.flushed.lock();
+                Some(flushed.last().map_or(0, |change| change.sequence))
+            }
+        }
+
+        let dir = tempdir().unwrap();
+        let mut options = DbOptions::default();
+        options.storage_path = dir.path().join("changes.db").to_string_lossy().to_string();
+        options.dimensions = 2;
+        let entry = |id: &str, x: f32| VectorEntry {
+            id: Some(id.to_string()),
+            vector: vec![x, 0.0],
+            metadata: None,
+        };
+
+        let recorder = Arc::new(Recorder::default());
+        {
+            let db = VectorDB::new(options.clone())?;
+            db.add_change_listener(recorder.clone())?;
+            db.upsert(entry("a", 1.0))?;
+
+            // The write is committed but reported as failed
+            recorder.failing.store(true, Ordering::SeqCst);
+            assert!(db.upsert(entry("b", 2.0)).is_err());
+            assert!(db.delete("a").is_err());
+            assert!(db.get("b")?.is_some());
+            // Dropping checkpoints, but keeps the changes not recorded
+        }
+        assert_eq!(recorder.flushed.lock().len(), 1);
+
+        let db = VectorDB::new(options)?;
+        recorder.failing.store(false, Ordering::SeqCst);
+        db.add_change_listener(recorder.clone())?;
+        let flushed = recorder.flushed.lock();
+        let summary: Vec<_> = flushed
+            .iter()
+            .map(|change| (change.kind, change.id.as_str(), change.sequence))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                (PointChangeKind::Insert, "a", 1),
+                (PointChangeKind::Update, "b", 2),
+                (PointChangeKind::Delete, "a", 3),
+            ]
+        );
+        assert!(flushed[1].before.is_none());
+        assert_eq!(flushed[1].after.as_ref().unwrap().vector, vec![2.0, 0.0]);
+        Ok(())
+    }
 }
@@ -64,6 +64,8 @@ fn test_hnsw_100_vectors() -> Result<()> {
         ef_construction: 100,
         ef_search: 200,
         max_elements: 1000,
+        seed: Some(42),
+        ..HnswConfig::default()
     };
 
     let mut index = HnswIndex::new(dimensions, DistanceMetric::Cosine, config)?;
@@ -130,6 +132,8 @@ fn test_hnsw_1k_vectors() -> Result<()> {
         ef_construction: 200,
         ef_search: 200,
         max_elements: 10000,
+        seed: Some(42),
+        ..HnswConfig::default()
     };
 
     let mut index = HnswIndex::new(dimensions, DistanceMetric::Cosine, config)?;
@@ -197,6 +201,8 @@ fn test_hnsw_10k_vectors() -> Result<()> {
         ef_construction: 200,
         ef_search: 200,
         max_elements: 100000,
+        seed: Some(42),
+        ..HnswConfig::default()
     };
 
     let mut index = HnswIndex::new(dimensions, DistanceMetric::Cosine, config)?;
@@ -275,6 +281,8 @@ fn test_hnsw_ef_search_tuning() -> Result<()> {
         ef_construction: 200,
         ef_search: 50, // Start with lower ef_search
         max_elements: 10000,
+        seed: Some(42),
+        ..HnswConfig::default()
     };
 
     let mut index = HnswIndex::new(dimensions, DistanceMetric::Cosine, config)?;
@@ -366,6 +374,8 @@ fn test_hnsw_serialization_large() -> Result<()> {
         ef_construction: 200,
         ef_search: 100,
         max_elements: 10000,
+        seed: Some(42),
+        ..HnswConfig::default()
     };
 
     let mut index = HnswIndex::new(dimensions, DistanceMetric::Cosine, config)?;
@@ -431,6 +441,8 @@ fn test_hnsw_different_metrics() -> Result<()> {
             ef_construction: 100,
             ef_search: 100,
             max_elements: 1000,
+            seed: Some(42),
+            ..HnswConfig::default()
         };
 
         let mut index = HnswIndex::new(dimensions, metric, config)?;
@@ -463,6 +475,8 @@ fn test_hnsw_parallel_batch_insert() -> Result<()> {
         ef_construction: 200,
         ef_search: 100,
         max_elements: 10000,
+        seed: Some(42),
+        ..HnswConfig::default()
     };
 
     let mut index = HnswIndex::new(dimensions, DistanceMetric::Cosine, config)?;
@@ -496,3 +510,64 @@ fn test_hnsw_parallel_batch_insert() -> Result<()> {
 
     Ok(())
 }
+
+#[test]
+fn test_hnsw_recall_after_delete_insert_churn() -> Result<()> {
+    let dimensions = 64;
+    let k = 10;
+
+    let config = HnswConfig {
+        m: 16,
+        ef_construction: 100,
+        ef_search: 100,
+        max_elements: 10000,
+        seed: Some(42),
+        ..HnswConfig::default()
+    };
+
+    let mut index = HnswIndex::new(dimensions, DistanceMetric::Euclidean, config)?;
+    let mut live: Vec<(String, Vec<f32>)> = Vec::new();
+    let mut next_id = 0;
+
+    let initial = generate_random_vectors(1000, dimensions, 7);
+    for vector in initial {
+        let id = format!("vec_{}", next_id);
+        next_id += 1;
+        index.add(id.clone(), vector.clone())?;
+        live.push((id, vector));
+    }
+
+    // Each cycle deletes 30% of the live set and inserts the same number back
+    for cycle in 0..5u64 {
+        let to_delete = live.len() * 3 / 10;
+        for (id, _) in live.drain(..to_delete) {
+            assert!(index.remove(&id)?);
+        }
+
+        for vector in generate_random_vectors(to_delete, dimensions, 100 + cycle) {
+            let id = format!("vec_{}", next_id);
+            next_id += 1;
+            index.add(id.clone(), vector.clone())?;
+            live.push((id, vector));
+        }
+    }
+    index.wait_for_compaction();
+    assert_eq!(index.len(), live.len());
+
+    let queries = generate_random_vectors(20, dimensions, 4242);
+    let mut total_recall = 0.0;
+    for query in &queries {
+        let results = index.search(query, k)?;
+        assert_eq!(results.len(), k, "search must return k results after churn");
+
+        let result_ids: Vec<_> = results.iter().map(|r| r.id.clone()).collect();
+        let ground_truth = brute_force_search(query, &live, k, DistanceMetric::Euclidean);
+        total_recall += calculate_recall(&ground_truth, &result_ids);
+    }
+
+    let avg_recall = total_recall / queries.len() as f32;
+    println!("Recall@{} after churn: {:.2}%", k, avg_recall * 100.0);
+    assert!(avg_recall >= 0.90, "Recall after churn was {:.2}", avg_recall);
+
+    Ok(())
+}
@@ -23,6 +23,7 @@ fn test_complete_insert_search_workflow() {
         ef_construction: 100,
         ef_search: 50,
         max_elements: 100_000,
+        ..HnswConfig::default()
     });
 
     let db = VectorDB::new(options).unwrap();
@@ -278,18 +279,21 @@ fn test_hnsw_different_configurations() {
             ef_construction: 50,
             ef_search: 50,
             max_elements: 1000,
+            ..HnswConfig::default()
         },
         HnswConfig {
             m: 16,
             ef_construction: 100,
             ef_search: 100,
             max_elements: 1000,
+            ..HnswConfig::default()
         },
         HnswConfig {
             m: 32,
             ef_construction: 200,
             ef_search: 200,
             max_elements: 1000,
+            ..HnswConfig::default()
         },
     ];
 
@@ -365,7 +369,7 @@ fn test_complex_metadata_filtering() {
         .search(SearchQuery {
             vector: query.clone(),
             k: 100,
-            filter: Some(filter1),
+            filter: Some(filter1.into()),
             ef_search: None,
         })
         .unwrap();
@@ -384,7 +388,7 @@ fn test_complex_metadata_filtering() {
         .search(SearchQuery {
             vector: query,
             k: 100,
-            filter: Some(filter2),
+            filter: Some(filter2.into()),
             ef_search: None,
         })
         .unwrap();
@@ -24,6 +24,7 @@ fn test_million_vector_insertion() {
         ef_construction: 100,
         ef_search: 50,
         max_elements: 2_000_000,
+        ..HnswConfig::default()
     });
 
     let db = VectorDB::new(options).unwrap();
@@ -293,6 +294,7 @@ fn test_memory_pressure_large_vectors() {
         ef_construction: 50,
         ef_search: 50,
         max_elements: 100_000,
+        ..HnswConfig::default()
     });
 
     let db = VectorDB::new(options).unwrap();
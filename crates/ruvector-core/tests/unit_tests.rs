@@ -509,7 +509,7 @@ mod vector_db_tests {
         let results = db.search(SearchQuery {
             vector: vec![1.0, 0.0, 0.0],
             k: 10,
-            filter: Some(filter),
+            filter: Some(filter.into()),
             ef_search: None,
         })?;
 
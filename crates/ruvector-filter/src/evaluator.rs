@@ -18,6 +18,16 @@ impl<'a> FilterEvaluator<'a> {
 
     /// Evaluate a filter expression and return matching vector IDs
     pub fn evaluate(&self, filter: &FilterExpression) -> Result<HashSet<String>> {
+        Ok(self
+            .evaluate_ids(filter)?
+            .into_iter()
+            .map(str::to_string)
+            .collect())
+    }
+
+    /// Evaluate a filter expression and return the matching vector IDs as
+    /// stored in the indices, without copying them
+    pub fn evaluate_ids(&self, filter: &FilterExpression) -> Result<HashSet<&'a str>> {
         match filter {
             FilterExpression::Eq { field, value } => self.evaluate_eq(field, value),
             FilterExpression::Ne { field, value } => self.evaluate_ne(field, value),
@@ -107,6 +117,23 @@ impl<'a> FilterEvaluator<'a> {
             FilterExpression::Match { field, text } => Self::get_field_value(payload, field)
                 .and_then(|v| v.as_str())
                 .map_or(false, |s| s.to_lowercase().contains(&text.to_lowercase())),
+            FilterExpression::GeoRadius {
+                field,
+                lat,
+                lon,
+                radius_m,
+            } => Self::get_geo_point(payload, field).is_some_and(|(point_lat, point_lon)| {
+                haversine_distance(*lat, *lon, point_lat, point_lon) <= *radius_m
+            }),
+            FilterExpression::GeoBoundingBox {
+                field,
+                top_left,
+                bottom_right,
+            } => Self::get_geo_point(payload, field).is_some_and(|(lat, lon)| {
+                let (north, west) = *top_left;
+                let (south, east) = *bottom_right;
+                lat <= north && lat >= south && lon >= west && lon <= east
+            }),
             FilterExpression::And(filters) => filters.iter().all(|f| self.matches(payload, f)),
             FilterExpression::Or(filters) => filters.iter().any(|f| self.matches(payload, f)),
             FilterExpression::Not(filter) => !self.matches(payload, filter),
@@ -114,11 +141,10 @@ impl<'a> FilterEvaluator<'a> {
             FilterExpression::IsNull { field } => {
                 Self::get_field_value(payload, field).map_or(true, |v| v.is_null())
             }
-            _ => false, // Geo operations not supported in direct matching
         }
     }
 
-    fn evaluate_eq(&self, field: &str, value: &Value) -> Result<HashSet<String>> {
+    fn evaluate_eq(&self, field: &str, value: &Value) -> Result<HashSet<&'a str>> {
         let index = self
             .indices
             .get_index(field)
@@ -127,28 +153,31 @@ impl<'a> FilterEvaluator<'a> {
         match index {
             PayloadIndex::Integer(map) => {
                 if let Some(num) = value.as_i64() {
-                    Ok(map.get(&num).cloned().unwrap_or_default())
+                    Ok(map.get(&num).map(Self::borrow_ids).unwrap_or_default())
                 } else {
                     Ok(HashSet::new())
                 }
             }
             PayloadIndex::Float(map) => {
                 if let Some(num) = value.as_f64() {
-                    Ok(map.get(&OrderedFloat(num)).cloned().unwrap_or_default())
+                    Ok(map
+                        .get(&OrderedFloat(num))
+                        .map(Self::borrow_ids)
+                        .unwrap_or_default())
                 } else {
                     Ok(HashSet::new())
                 }
             }
             PayloadIndex::Keyword(map) => {
                 if let Some(s) = value.as_str() {
-                    Ok(map.get(s).cloned().unwrap_or_default())
+                    Ok(map.get(s).map(Self::borrow_ids).unwrap_or_default())
                 } else {
                     Ok(HashSet::new())
                 }
             }
             PayloadIndex::Bool(map) => {
                 if let Some(b) = value.as_bool() {
-                    Ok(map.get(&b).cloned().unwrap_or_default())
+                    Ok(map.get(&b).map(Self::borrow_ids).unwrap_or_default())
                 } else {
                     Ok(HashSet::new())
                 }
@@ -157,13 +186,13 @@ impl<'a> FilterEvaluator<'a> {
         }
     }
 
-    fn evaluate_ne(&self, field: &str, value: &Value) -> Result<HashSet<String>> {
+    fn evaluate_ne(&self, field: &str, value: &Value) -> Result<HashSet<&'a str>> {
         let eq_results = self.evaluate_eq(field, value)?;
         let all_ids = self.get_all_ids_for_field(field)?;
         Ok(all_ids.difference(&eq_results).cloned().collect())
     }
 
-    fn evaluate_gt(&self, field: &str, value: &Value) -> Result<HashSet<String>> {
+    fn evaluate_gt(&self, field: &str, value: &Value) -> Result<HashSet<&'a str>> {
         let index = self
             .indices
             .get_index(field)
@@ -175,7 +204,7 @@ impl<'a> FilterEvaluator<'a> {
                     Ok(map
                         .range((num + 1)..)
                         .flat_map(|(_, ids)| ids)
-                        .cloned()
+                        .map(String::as_str)
                         .collect())
                 } else {
                     Ok(HashSet::new())
@@ -188,7 +217,7 @@ impl<'a> FilterEvaluator<'a> {
                         .range(threshold..)
                         .filter(|(k, _)| **k > threshold)
                         .flat_map(|(_, ids)| ids)
-                        .cloned()
+                        .map(String::as_str)
                         .collect())
                 } else {
                     Ok(HashSet::new())
@@ -198,7 +227,7 @@ impl<'a> FilterEvaluator<'a> {
         }
     }
 
-    fn evaluate_gte(&self, field: &str, value: &Value) -> Result<HashSet<String>> {
+    fn evaluate_gte(&self, field: &str, value: &Value) -> Result<HashSet<&'a str>> {
         let index = self
             .indices
             .get_index(field)
@@ -207,7 +236,11 @@ impl<'a> FilterEvaluator<'a> {
         match index {
             PayloadIndex::Integer(map) => {
                 if let Some(num) = value.as_i64() {
-                    Ok(map.range(num..).flat_map(|(_, ids)| ids).cloned().collect())
+                    Ok(map
+                        .range(num..)
+                        .flat_map(|(_, ids)| ids)
+                        .map(String::as_str)
+                        .collect())
                 } else {
                     Ok(HashSet::new())
                 }
@@ -217,7 +250,7 @@ impl<'a> FilterEvaluator<'a> {
                     Ok(map
                         .range(OrderedFloat(num)..)
                         .flat_map(|(_, ids)| ids)
-                        .cloned()
+                        .map(String::as_str)
                         .collect())
                 } else {
                     Ok(HashSet::new())
@@ -227,7 +260,7 @@ impl<'a> FilterEvaluator<'a> {
         }
     }
 
-    fn evaluate_lt(&self, field: &str, value: &Value) -> Result<HashSet<String>> {
+    fn evaluate_lt(&self, field: &str, value: &Value) -> Result<HashSet<&'a str>> {
         let index = self
             .indices
             .get_index(field)
@@ -236,7 +269,11 @@ impl<'a> FilterEvaluator<'a> {
         match index {
             PayloadIndex::Integer(map) => {
                 if let Some(num) = value.as_i64() {
-                    Ok(map.range(..num).flat_map(|(_, ids)| ids).cloned().collect())
+                    Ok(map
+                        .range(..num)
+                        .flat_map(|(_, ids)| ids)
+                        .map(String::as_str)
+                        .collect())
                 } else {
                     Ok(HashSet::new())
                 }
@@ -246,7 +283,7 @@ impl<'a> FilterEvaluator<'a> {
                     Ok(map
                         .range(..OrderedFloat(num))
                         .flat_map(|(_, ids)| ids)
-                        .cloned()
+                        .map(String::as_str)
                         .collect())
                 } else {
                     Ok(HashSet::new())
@@ -256,7 +293,7 @@ impl<'a> FilterEvaluator<'a> {
         }
     }
 
-    fn evaluate_lte(&self, field: &str, value: &Value) -> Result<HashSet<String>> {
+    fn evaluate_lte(&self, field: &str, value: &Value) -> Result<HashSet<&'a str>> {
         let index = self
             .indices
             .get_index(field)
@@ -268,7 +305,7 @@ impl<'a> FilterEvaluator<'a> {
                     Ok(map
                         .range(..=num)
                         .flat_map(|(_, ids)| ids)
-                        .cloned()
+                        .map(String::as_str)
                         .collect())
                 } else {
                     Ok(HashSet::new())
@@ -279,7 +316,7 @@ impl<'a> FilterEvaluator<'a> {
                     Ok(map
                         .range(..=OrderedFloat(num))
                         .flat_map(|(_, ids)| ids)
-                        .cloned()
+                        .map(String::as_str)
                         .collect())
                 } else {
                     Ok(HashSet::new())
@@ -294,7 +331,7 @@ impl<'a> FilterEvaluator<'a> {
         field: &str,
         gte: Option<&Value>,
         lte: Option<&Value>,
-    ) -> Result<HashSet<String>> {
+    ) -> Result<HashSet<&'a str>> {
         let mut result = self.get_all_ids_for_field(field)?;
 
         if let Some(gte_val) = gte {
@@ -310,7 +347,7 @@ impl<'a> FilterEvaluator<'a> {
         Ok(result)
     }
 
-    fn evaluate_in(&self, field: &str, values: &[Value]) -> Result<HashSet<String>> {
+    fn evaluate_in(&self, field: &str, values: &[Value]) -> Result<HashSet<&'a str>> {
         let mut result = HashSet::new();
         for value in values {
             let ids = self.evaluate_eq(field, value)?;
@@ -319,7 +356,7 @@ impl<'a> FilterEvaluator<'a> {
         Ok(result)
     }
 
-    fn evaluate_match(&self, field: &str, text: &str) -> Result<HashSet<String>> {
+    fn evaluate_match(&self, field: &str, text: &str) -> Result<HashSet<&'a str>> {
         let index = self
             .indices
             .get_index(field)
@@ -331,7 +368,7 @@ impl<'a> FilterEvaluator<'a> {
                 let mut result = HashSet::new();
                 for word in words {
                     if let Some(ids) = map.get(&word) {
-                        result.extend(ids.iter().cloned());
+                        result.extend(ids.iter().map(String::as_str));
                     }
                 }
                 Ok(result)
@@ -346,7 +383,7 @@ impl<'a> FilterEvaluator<'a> {
         lat: f64,
         lon: f64,
         radius_m: f64,
-    ) -> Result<HashSet<String>> {
+    ) -> Result<HashSet<&'a str>> {
         let index = self
             .indices
             .get_index(field)
@@ -358,7 +395,7 @@ impl<'a> FilterEvaluator<'a> {
                 for (id, point_lat, point_lon) in points {
                     let distance = haversine_distance(lat, lon, *point_lat, *point_lon);
                     if distance <= radius_m {
-                        result.insert(id.clone());
+                        result.insert(id.as_str());
                     }
                 }
                 Ok(result)
@@ -372,7 +409,7 @@ impl<'a> FilterEvaluator<'a> {
         field: &str,
         top_left: (f64, f64),
         bottom_right: (f64, f64),
-    ) -> Result<HashSet<String>> {
+    ) -> Result<HashSet<&'a str>> {
         let index = self
             .indices
             .get_index(field)
@@ -386,7 +423,7 @@ impl<'a> FilterEvaluator<'a> {
 
                 for (id, lat, lon) in points {
                     if *lat <= north && *lat >= south && *lon >= west && *lon <= east {
-                        result.insert(id.clone());
+                        result.insert(id.as_str());
                     }
                 }
                 Ok(result)
@@ -395,14 +432,14 @@ impl<'a> FilterEvaluator<'a> {
         }
     }
 
-    fn evaluate_and(&self, filters: &[FilterExpression]) -> Result<HashSet<String>> {
+    fn evaluate_and(&self, filters: &[FilterExpression]) -> Result<HashSet<&'a str>> {
         if filters.is_empty() {
             return Ok(HashSet::new());
         }
 
-        let mut result = self.evaluate(&filters[0])?;
+        let mut result = self.evaluate_ids(&filters[0])?;
         for filter in &filters[1..] {
-            let next = self.evaluate(filter)?;
+            let next = self.evaluate_ids(filter)?;
             result = result.intersection(&next).cloned().collect();
             if result.is_empty() {
                 break;
@@ -411,17 +448,17 @@ impl<'a> FilterEvaluator<'a> {
         Ok(result)
     }
 
-    fn evaluate_or(&self, filters: &[FilterExpression]) -> Result<HashSet<String>> {
+    fn evaluate_or(&self, filters: &[FilterExpression]) -> Result<HashSet<&'a str>> {
         let mut result = HashSet::new();
         for filter in filters {
-            let next = self.evaluate(filter)?;
+            let next = self.evaluate_ids(filter)?;
             result.extend(next);
         }
         Ok(result)
     }
 
-    fn evaluate_not(&self, filter: &FilterExpression) -> Result<HashSet<String>> {
-        let filter_results = self.evaluate(filter)?;
+    fn evaluate_not(&self, filter: &FilterExpression) -> Result<HashSet<&'a str>> {
+        let filter_results = self.evaluate_ids(filter)?;
         let fields = filter.get_fields();
         let mut all_ids = HashSet::new();
 
@@ -432,38 +469,47 @@ impl<'a> FilterEvaluator<'a> {
         Ok(all_ids.difference(&filter_results).cloned().collect())
     }
 
-    fn evaluate_exists(&self, field: &str) -> Result<HashSet<String>> {
+    fn evaluate_exists(&self, field: &str) -> Result<HashSet<&'a str>> {
         self.get_all_ids_for_field(field)
     }
 
-    fn evaluate_is_null(&self, _field: &str) -> Result<HashSet<String>> {
+    fn evaluate_is_null(&self, _field: &str) -> Result<HashSet<&'a str>> {
         // This would require tracking null values separately
         // For now, return empty set
         Ok(HashSet::new())
     }
 
-    fn get_all_ids_for_field(&self, field: &str) -> Result<HashSet<String>> {
+    fn get_all_ids_for_field(&self, field: &str) -> Result<HashSet<&'a str>> {
         let index = self
             .indices
             .get_index(field)
             .ok_or_else(|| FilterError::IndexNotFound(field.to_string()))?;
 
         let ids = match index {
-            PayloadIndex::Integer(map) => map.values().flatten().cloned().collect(),
-            PayloadIndex::Float(map) => map.values().flatten().cloned().collect(),
-            PayloadIndex::Keyword(map) => map.values().flatten().cloned().collect(),
-            PayloadIndex::Bool(map) => map.values().flatten().cloned().collect(),
-            PayloadIndex::Geo(points) => points.iter().map(|(id, _, _)| id.clone()).collect(),
-            PayloadIndex::Text(map) => map.values().flatten().cloned().collect(),
+            PayloadIndex::Integer(map) => map.values().flatten().map(String::as_str).collect(),
+            PayloadIndex::Float(map) => map.values().flatten().map(String::as_str).collect(),
+            PayloadIndex::Keyword(map) => map.values().flatten().map(String::as_str).collect(),
+            PayloadIndex::Bool(map) => map.values().flatten().map(String::as_str).collect(),
+            PayloadIndex::Geo(points) => points.iter().map(|(id, _, _)| id.as_str()).collect(),
+            PayloadIndex::Text(map) => map.values().flatten().map(String::as_str).collect(),
         };
 
         Ok(ids)
     }
 
+    fn borrow_ids(ids: &'a HashSet<String>) -> HashSet<&'a str> {
+        ids.iter().map(String::as_str).collect()
+    }
+
     fn get_field_value<'b>(payload: &'b Value, field: &str) -> Option<&'b Value> {
         payload.as_object()?.get(field)
     }
 
+    fn get_geo_point(payload: &Value, field: &str) -> Option<(f64, f64)> {
+        let point = Self::get_field_value(payload, field)?.as_object()?;
+        Some((point.get("lat")?.as_f64()?, point.get("lon")?.as_f64()?))
+    }
+
     fn compare_values(a: &Value, b: &Value) -> Option<std::cmp::Ordering> {
         match (a, b) {
             (Value::Number(a), Value::Number(b)) => {
@@ -584,6 +630,22 @@ mod tests {
         assert!(!evaluator.matches(&payload, &FilterExpression::eq("age", json!(30))));
     }
 
+    #[test]
+    fn test_matches_geo_payload() {
+        let manager = PayloadIndexManager::new();
+        let evaluator = FilterEvaluator::new(&manager);
+
+        let payload = json!({"location": {"lat": 40.7128, "lon": -74.0060}});
+
+        let near = FilterExpression::geo_radius("location", 40.7306, -73.9352, 10_000.0);
+        let far = FilterExpression::geo_radius("location", 34.0522, -118.2437, 10_000.0);
+        assert!(evaluator.matches(&payload, &near));
+        assert!(!evaluator.matches(&payload, &far));
+
+        let bbox = FilterExpression::geo_bounding_box("location", (41.0, -75.0), (40.0, -73.0));
+        assert!(evaluator.matches(&payload, &bbox));
+    }
+
     #[test]
     fn test_haversine_distance() {
         // New York to Los Angeles (approx 3935 km)
@@ -64,9 +64,9 @@ pub enum FilterExpression {
     },
 
     // Logical operators
-    And(Vec<FilterExpression>),
-    Or(Vec<FilterExpression>),
-    Not(Box<FilterExpression>),
+    And(#[serde(with = "operands")] Vec<FilterExpression>),
+    Or(#[serde(with = "operands")] Vec<FilterExpression>),
+    Not(#[serde(with = "operand")] Box<FilterExpression>),
 
     // Existence check
     Exists {
@@ -241,6 +241,66 @@ impl FilterExpression {
     }
 }
 
+/// Operands of `And`/`Or`, held in a `filters` field of the tagged object
+/// since an internally tagged variant can't hold a bare sequence
+mod operands {
+    use super::FilterExpression;
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    #[derive(Serialize)]
+    struct Borrowed<'a> {
+        filters: &'a [FilterExpression],
+    }
+
+    #[derive(Deserialize)]
+    struct Owned {
+        filters: Vec<FilterExpression>,
+    }
+
+    pub fn serialize<S: Serializer>(
+        filters: &[FilterExpression],
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        Borrowed { filters }.serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Vec<FilterExpression>, D::Error> {
+        Owned::deserialize(deserializer).map(|owned| owned.filters)
+    }
+}
+
+/// Operand of `Not`, held in a `filter` field so its own `type` tag doesn't
+/// collide with the `not` tag
+mod operand {
+    use super::FilterExpression;
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    #[derive(Serialize)]
+    struct Borrowed<'a> {
+        filter: &'a FilterExpression,
+    }
+
+    #[derive(Deserialize)]
+    struct Owned {
+        filter: Box<FilterExpression>,
+    }
+
+    pub fn serialize<S: Serializer>(
+        filter: &FilterExpression,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        Borrowed { filter }.serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Box<FilterExpression>, D::Error> {
+        Owned::deserialize(deserializer).map(|owned| owned.filter)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -279,4 +339,39 @@ mod tests {
         let deserialized: FilterExpression = serde_json::from_str(&json).unwrap();
         assert!(matches!(deserialized, FilterExpression::Eq { .. }));
     }
+
+    #[test]
+    fn test_logical_serialization() {
+        let filter = FilterExpression::and(vec![
+            FilterExpression::gte("age", json!(18)),
+            FilterExpression::not(FilterExpression::eq("status", json!("banned"))),
+        ]);
+        let json = serde_json::to_value(&filter).unwrap();
+        assert_eq!(json["type"], "and");
+        assert_eq!(json["filters"][1]["filter"]["type"], "eq");
+
+        let deserialized: FilterExpression = serde_json::from_value(json).unwrap();
+        assert_eq!(deserialized.get_fields(), vec!["age", "status"]);
+    }
+
+    #[test]
+    fn test_stored_filters_deserialize() {
+        // Filters as clients and payload index definitions already store them
+        let stored = json!({
+            "type": "or",
+            "filters": [
+                {"type": "eq", "field": "status", "value": "active"},
+                {"type": "range", "field": "age", "gte": 18, "lte": null},
+                {"type": "not", "filter": {"type": "exists", "field": "deleted"}}
+            ]
+        });
+        let filter: FilterExpression = serde_json::from_value(stored.clone()).unwrap();
+        let FilterExpression::Or(filters) = &filter else {
+            panic!("expected an OR filter, got {:?}", filter);
+        };
+        assert!(matches!(filters[0], FilterExpression::Eq { .. }));
+        assert!(matches!(&filters[2], FilterExpression::Not(inner)
+            if matches!(**inner, FilterExpression::Exists { .. })));
+        assert_eq!(serde_json::to_value(&filter).unwrap(), stored);
+    }
 }
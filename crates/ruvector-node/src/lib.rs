@@ -9,8 +9,9 @@
 use napi::bindgen_prelude::*;
 use napi_derive::napi;
 use ruvector_core::{
-    types::{DbOptions, HnswConfig, QuantizationConfig},
-    DistanceMetric, SearchQuery, SearchResult, VectorDB as CoreVectorDB, VectorEntry,
+    types::{DbOptions, HnswConfig, QuantizationConfig, DEFAULT_COMPACTION_THRESHOLD},
+    DistanceMetric, PointRecord, ScrollPage, ScrollRequest, SearchFilter, SearchQuery,
+    SearchResult, VectorDB as CoreVectorDB, VectorEntry,
 };
 use std::sync::Arc;
 use std::sync::RwLock;
@@ -86,16 +87,34 @@ pub struct JsHnswConfig {
     pub ef_search: Option<u32>,
     /// Maximum number of elements
     pub max_elements: Option<u32>,
+    /// Share of deleted slots (0.0-1.0) that triggers a background
+    /// compaction
+    pub compaction_threshold: Option<f64>,
 }
 
-impl From<JsHnswConfig> for HnswConfig {
-    fn from(config: JsHnswConfig) -> Self {
-        HnswConfig {
+impl TryFrom<JsHnswConfig> for HnswConfig {
+    type Error = napi::Error;
+
+    fn try_from(config: JsHnswConfig) -> Result<Self> {
+        let compaction_threshold = match config.compaction_threshold {
+            None => DEFAULT_COMPACTION_THRESHOLD,
+            Some(threshold) if (0.0..=f64::from(f32::MAX)).contains(&threshold) => threshold as f32,
+            Some(threshold) => {
+                return Err(Error::from_reason(format!(
+                    "Invalid compactionThreshold: {}, expected a non-negative number",
+                    threshold
+                )))
+            }
+        };
+
+        Ok(HnswConfig {
             m: config.m.unwrap_or(32) as usize,
             ef_construction: config.ef_construction.unwrap_or(200) as usize,
             ef_search: config.ef_search.unwrap_or(100) as usize,
             max_elements: config.max_elements.unwrap_or(10_000_000) as usize,
-        }
+            compaction_threshold,
+            seed: None,
+        })
     }
 }
 
@@ -115,9 +134,11 @@ pub struct JsDbOptions {
     pub quantization: Option<JsQuantizationConfig>,
 }
 
-impl From<JsDbOptions> for DbOptions {
-    fn from(options: JsDbOptions) -> Self {
-        DbOptions {
+impl TryFrom<JsDbOptions> for DbOptions {
+    type Error = napi::Error;
+
+    fn try_from(options: JsDbOptions) -> Result<Self> {
+        Ok(DbOptions {
             dimensions: options.dimensions as usize,
             distance_metric: options
                 .distance_metric
@@ -126,9 +147,10 @@ impl From<JsDbOptions> for DbOptions {
             storage_path: options
                 .storage_path
                 .unwrap_or_else(|| "./ruvector.db".to_string()),
-            hnsw_config: options.hnsw_config.map(Into::into),
+            hnsw_config: options.hnsw_config.map(TryInto::try_into).transpose()?,
             quantization: options.quantization.map(Into::into),
-        }
+            ..DbOptions::default()
+        })
     }
 }
 
@@ -168,15 +190,20 @@ pub struct JsSearchQuery {
     /// Optional ef_search parameter for HNSW
     pub ef_search: Option<u32>,
     /// Optional metadata filter as JSON string (use JSON.stringify on objects)
+    ///
+    /// Either a field/value equality object or a filter expression such as
+    /// `{"type": "range", "field": "price", "gte": 10, "lte": 20}`
     pub filter: Option<String>,
 }
 
 impl JsSearchQuery {
     fn to_core(&self) -> Result<SearchQuery> {
-        // Parse JSON string to HashMap<String, serde_json::Value>
-        let filter = self.filter.as_ref().and_then(|s| {
-            serde_json::from_str::<std::collections::HashMap<String, serde_json::Value>>(s).ok()
-        });
+        let filter = self
+            .filter
+            .as_ref()
+            .map(|s| serde_json::from_str::<SearchFilter>(s))
+            .transpose()
+            .map_err(|e| Error::from_reason(format!("Invalid filter: {}", e)))?;
 
         Ok(SearchQuery {
             vector: self.vector.to_vec(),
@@ -220,6 +247,80 @@ impl From<SearchResult> for JsSearchResult {
     }
 }
 
+/// Scroll parameters for paging through stored vectors
+#[napi(object)]
+pub struct JsScrollRequest {
+    /// `nextOffset` of the previous page, omitted for the first page
+    pub offset: Option<String>,
+    /// Maximum number of vectors in the page (default 100)
+    pub limit: Option<u32>,
+    /// Optional metadata filter as JSON string, same format as in search
+    pub filter: Option<String>,
+    /// Whether to include vectors (default true)
+    pub with_vector: Option<bool>,
+    /// Whether to include metadata (default true)
+    pub with_payload: Option<bool>,
+}
+
+impl JsScrollRequest {
+    fn to_core(&self) -> Result<ScrollRequest> {
+        let filter = self
+            .filter
+            .as_ref()
+            .map(|s| serde_json::from_str::<SearchFilter>(s))
+            .transpose()
+            .map_err(|e| Error::from_reason(format!("Invalid filter: {}", e)))?;
+
+        let defaults = ScrollRequest::default();
+        Ok(ScrollRequest {
+            offset: self.offset.clone(),
+            limit: self.limit.map_or(defaults.limit, |v| v as usize),
+            filter,
+            with_vector: self.with_vector.unwrap_or(defaults.with_vector),
+            with_payload: self.with_payload.unwrap_or(defaults.with_payload),
+        })
+    }
+}
+
+/// Stored vector returned by a scroll
+#[napi(object)]
+pub struct JsPointRecord {
+    /// Vector ID
+    pub id: String,
+    /// Vector data (if requested)
+    pub vector: Option<Float32Array>,
+    /// Metadata as JSON string (if requested and present)
+    pub metadata: Option<String>,
+}
+
+impl From<PointRecord> for JsPointRecord {
+    fn from(record: PointRecord) -> Self {
+        JsPointRecord {
+            id: record.id,
+            vector: record.vector.map(Float32Array::new),
+            metadata: record.metadata.and_then(|m| serde_json::to_string(&m).ok()),
+        }
+    }
+}
+
+/// One page of a scroll
+#[napi(object)]
+pub struct JsScrollPage {
+    /// Vectors in ID order
+    pub points: Vec<JsPointRecord>,
+    /// Offset for the next page, null once every vector was visited
+    pub next_offset: Option<String>,
+}
+
+impl From<ScrollPage> for JsScrollPage {
+    fn from(page: ScrollPage) -> Self {
+        JsScrollPage {
+            points: page.points.into_iter().map(Into::into).collect(),
+            next_offset: page.next_offset,
+        }
+    }
+}
+
 /// High-performance vector database with HNSW indexing
 #[napi]
 pub struct VectorDB {
@@ -245,7 +346,7 @@ impl VectorDB {
     /// ```
     #[napi(constructor)]
     pub fn new(options: JsDbOptions) -> Result<Self> {
-        let core_options: DbOptions = options.into();
+        let core_options = DbOptions::try_from(options)?;
         let db = CoreVectorDB::new(core_options)
             .map_err(|e| Error::from_reason(format!("Failed to create database: {}", e)))?;
 
@@ -348,6 +449,44 @@ impl VectorDB {
         .map(|results| results.into_iter().map(Into::into).collect())
     }
 
+    /// Run several searches at once
+    ///
+    /// Queries run in parallel and return one result array per query, in
+    /// the order given
+    ///
+    /// # Example
+    /// ```javascript
+    /// const [first, second] = await db.searchBatch([
+    ///   { vector: new Float32Array([1, 2, 3]), k: 10 },
+    ///   { vector: new Float32Array([3, 2, 1]), k: 5 }
+    /// ]);
+    /// ```
+    #[napi]
+    pub async fn search_batch(
+        &self,
+        queries: Vec<JsSearchQuery>,
+    ) -> Result<Vec<Vec<JsSearchResult>>> {
+        let core_queries = queries
+            .iter()
+            .map(JsSearchQuery::to_core)
+            .collect::<Result<Vec<_>>>()?;
+        let db = self.inner.clone();
+
+        tokio::task::spawn_blocking(move || {
+            let db = db.read().expect("RwLock poisoned");
+            db.search_batch(core_queries)
+        })
+        .await
+        .map_err(|e| Error::from_reason(format!("Task failed: {}", e)))?
+        .map_err(|e| Error::from_reason(format!("Search failed: {}", e)))
+        .map(|batches| {
+            batches
+                .into_iter()
+                .map(|results| results.into_iter().map(Into::into).collect())
+                .collect()
+        })
+    }
+
     /// Delete a vector by ID
     ///
     /// Returns true if the vector was deleted, false if not found
@@ -447,6 +586,64 @@ impl VectorDB {
         .map_err(|e| Error::from_reason(format!("Task failed: {}", e)))?
         .map_err(|e| Error::from_reason(format!("IsEmpty failed: {}", e)))
     }
+
+    /// Read one page of stored vectors in ID order
+    ///
+    /// Pass `nextOffset` back as `offset` to continue, so whole databases can
+    /// be exported without loading them into memory.
+    ///
+    /// # Example
+    /// ```javascript
+    /// let offset;
+    /// do {
+    ///   const page = await db.scroll({ offset, limit: 1000 });
+    ///   page.points.forEach(exportPoint);
+    ///   offset = page.nextOffset;
+    /// } while (offset);
+    /// ```
+    #[napi]
+    pub async fn scroll(&self, request: JsScrollRequest) -> Result<JsScrollPage> {
+        let core_request = request.to_core()?;
+        let db = self.inner.clone();
+
+        tokio::task::spawn_blocking(move || {
+            let db = db.read().expect("RwLock poisoned");
+            db.scroll(core_request)
+        })
+        .await
+        .map_err(|e| Error::from_reason(format!("Task failed: {}", e)))?
+        .map_err(|e| Error::from_reason(format!("Scroll failed: {}", e)))
+        .map(Into::into)
+    }
+
+    /// Get the HNSW ef_search used by queries that don't set one
+    ///
+    /// # Example
+    /// ```javascript
+    /// console.log(`Default efSearch: ${db.getEfSearch()}`);
+    /// ```
+    #[napi]
+    pub fn get_ef_search(&self) -> Result<u32> {
+        let db = self.inner.read().expect("RwLock poisoned");
+        let ef_search = db.ef_search();
+        u32::try_from(ef_search)
+            .map_err(|_| Error::from_reason(format!("efSearch {} is out of range", ef_search)))
+    }
+
+    /// Set the HNSW ef_search used by queries that don't set one
+    ///
+    /// Larger values improve recall at the cost of latency.
+    ///
+    /// # Example
+    /// ```javascript
+    /// db.setEfSearch(200);
+    /// ```
+    #[napi]
+    pub fn set_ef_search(&self, ef_search: u32) -> Result<()> {
+        let db = self.inner.read().expect("RwLock poisoned");
+        db.set_ef_search(ef_search as usize)
+            .map_err(|e| Error::from_reason(format!("SetEfSearch failed: {}", e)))
+    }
 }
 
 /// Get the version of the Ruvector library
@@ -508,18 +705,21 @@ pub struct JsCollectionConfig {
     pub quantization: Option<JsQuantizationConfig>,
 }
 
-impl From<JsCollectionConfig> for ruvector_collections::CollectionConfig {
-    fn from(config: JsCollectionConfig) -> Self {
-        ruvector_collections::CollectionConfig {
+impl TryFrom<JsCollectionConfig> for ruvector_collections::CollectionConfig {
+    type Error = napi::Error;
+
+    fn try_from(config: JsCollectionConfig) -> Result<Self> {
+        Ok(ruvector_collections::CollectionConfig {
             dimensions: config.dimensions as usize,
             distance_metric: config
                 .distance_metric
                 .map(Into::into)
                 .unwrap_or(DistanceMetric::Cosine),
-            hnsw_config: config.hnsw_config.map(Into::into),
+            hnsw_config: config.hnsw_config.map(TryInto::try_into).transpose()?,
             quantization: config.quantization.map(Into::into),
             on_disk_payload: true,
-        }
+            embedding: None,
+        })
     }
 }
 
@@ -601,7 +801,7 @@ impl CollectionManager {
     /// ```
     #[napi]
     pub async fn create_collection(&self, name: String, config: JsCollectionConfig) -> Result<()> {
-        let core_config: ruvector_collections::CollectionConfig = config.into();
+        let core_config = ruvector_collections::CollectionConfig::try_from(config)?;
         let manager = self.inner.clone();
 
         tokio::task::spawn_blocking(move || {
@@ -319,6 +319,23 @@ test('VectorDB - HNSW index configuration', async (t) => {
   t.truthy(results.length >= 1);
 });
 
+test('VectorDB - invalid compaction threshold', (t) => {
+  const tempDir = createTempDir();
+  t.teardown(() => cleanupTempDir(tempDir));
+
+  for (const compactionThreshold of [-0.5, NaN, 1e300]) {
+    t.throws(
+      () =>
+        new VectorDB({
+          dimensions: 3,
+          storagePath: join(tempDir, 'test.db'),
+          hnswConfig: { compactionThreshold },
+        }),
+      { message: /compactionThreshold/ }
+    );
+  }
+});
+
 test('VectorDB - memory stress test', async (t) => {
   const tempDir = createTempDir();
   t.teardown(() => cleanupTempDir(tempDir));
@@ -5,6 +5,7 @@
 //! - Vote request handling
 //! - Term management
 //! - Split vote prevention
+//! - Pre-votes, so partitioned nodes don't disrupt the cluster
 
 use crate::{NodeId, Term};
 use rand::Rng;
@@ -136,6 +137,12 @@ pub struct ElectionState {
 
     /// Current term being contested
     pub current_term: Term,
+
+    /// Term a pre-vote is being held for, if one is in progress
+    pub pre_vote_term: Option<Term>,
+
+    /// Last time a leader was heard from
+    last_leader_contact: Option<Instant>,
 }
 
 impl ElectionState {
@@ -145,12 +152,23 @@ impl ElectionState {
             timer: ElectionTimer::new(min_timeout_ms, max_timeout_ms),
             votes: VoteTracker::new(cluster_size),
             current_term: 0,
+            pre_vote_term: None,
+            last_leader_contact: None,
         }
     }
 
+    /// Start a pre-vote for the given term, without entering it
+    pub fn start_pre_vote(&mut self, term: Term, self_id: &NodeId) {
+        self.pre_vote_term = Some(term);
+        self.votes.reset();
+        self.votes.record_vote(self_id.clone());
+        self.timer.reset();
+    }
+
     /// Start a new election for the given term
     pub fn start_election(&mut self, term: Term, self_id: &NodeId) {
         self.current_term = term;
+        self.pre_vote_term = None;
         self.votes.reset();
         self.votes.record_vote(self_id.clone());
         self.timer.reset();
@@ -161,6 +179,20 @@ impl ElectionState {
         self.timer.reset();
     }
 
+    /// Record a message from the current leader, resetting the timer and
+    /// abandoning any pre-vote
+    pub fn record_leader_contact(&mut self) {
+        self.last_leader_contact = Some(Instant::now());
+        self.pre_vote_term = None;
+        self.timer.reset();
+    }
+
+    /// Check if a leader was heard from within `window`
+    pub fn leader_contacted_within(&self, window: Duration) -> bool {
+        self.last_leader_contact
+            .is_some_and(|contact| contact.elapsed() < window)
+    }
+
     /// Check if election timeout has occurred
     pub fn should_start_election(&self) -> bool {
         self.timer.is_elapsed()
@@ -292,6 +324,23 @@ mod tests {
         assert!(won);
     }
 
+    #[test]
+    fn test_pre_vote_state() {
+        let mut state = ElectionState::new(3, 50, 100);
+        let self_id = "node1".to_string();
+
+        state.start_pre_vote(2, &self_id);
+        assert_eq!(state.pre_vote_term, Some(2));
+        assert_eq!(state.current_term, 0);
+        assert!(state.record_vote("node2".to_string()));
+
+        // Hearing from a leader abandons the pre-vote
+        assert!(!state.leader_contacted_within(Duration::from_millis(50)));
+        state.record_leader_contact();
+        assert_eq!(state.pre_vote_term, None);
+        assert!(state.leader_contacted_within(Duration::from_millis(50)));
+    }
+
     #[test]
     fn test_vote_validation() {
         // Should grant vote when candidate is up-to-date
@@ -6,16 +6,27 @@
 
 pub mod election;
 pub mod log;
+pub mod membership;
 pub mod node;
+pub mod replicated;
 pub mod rpc;
 pub mod state;
+pub mod state_machine;
+pub mod storage;
+pub mod transport;
 
+pub use log::EntryKind;
+pub use membership::{ClusterConfig, MembershipChange};
 pub use node::{RaftNode, RaftNodeConfig};
+pub use replicated::{ReadConsistency, ReplicatedVectorDB, VectorStateMachine};
 pub use rpc::{
     AppendEntriesRequest, AppendEntriesResponse, InstallSnapshotRequest, InstallSnapshotResponse,
-    RequestVoteRequest, RequestVoteResponse,
+    RequestVoteRequest, RequestVoteResponse, TimeoutNowRequest,
 };
 pub use state::{LeaderState, PersistentState, RaftState, VolatileState};
+pub use state_machine::StateMachine;
+pub use storage::{FileStorage, MemoryStorage, RaftStorage};
+pub use transport::{LocalTransport, RaftTransport, TcpTransport};
 
 use thiserror::Error;
 
@@ -52,9 +63,18 @@ pub enum RaftError {
     #[error("Log inconsistency detected")]
     LogInconsistency,
 
+    #[error("Corrupted storage: {0}")]
+    CorruptedStorage(String),
+
     #[error("Snapshot installation failed: {0}")]
     SnapshotFailed(String),
 
+    #[error("Timed out waiting for the cluster")]
+    Timeout,
+
+    #[error("Vector database error: {0}")]
+    Database(#[from] ruvector_core::RuvectorError),
+
     #[error("Configuration error: {0}")]
     ConfigError(String),
 
@@ -10,6 +10,17 @@ use crate::{LogIndex, RaftError, RaftResult, Term};
 use serde::{Deserialize, Serialize};
 use std::collections::VecDeque;
 
+/// What a log entry's command holds
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub enum EntryKind {
+    /// A command for the state machine (empty for a leader's no-op)
+    #[default]
+    Command,
+
+    /// A serialized [`ClusterConfig`](crate::membership::ClusterConfig)
+    Configuration,
+}
+
 /// A single entry in the Raft log
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
 pub struct LogEntry {
@@ -19,8 +30,11 @@ pub struct LogEntry {
     /// Index position in the log
     pub index: LogIndex,
 
-    /// State machine command
+    /// State machine command, or configuration
     pub command: Vec<u8>,
+
+    /// What `command` holds
+    pub kind: EntryKind,
 }
 
 impl LogEntry {
@@ -30,6 +44,18 @@ impl LogEntry {
             term,
             index,
             command,
+            kind: EntryKind::Command,
+        }
+    }
+
+    /// Create an entry changing the cluster's members to the serialized
+    /// `configuration`
+    pub fn configuration(term: Term, index: LogIndex, configuration: Vec<u8>) -> Self {
+        Self {
+            term,
+            index,
+            command: configuration,
+            kind: EntryKind::Configuration,
         }
     }
 }
@@ -48,6 +74,9 @@ pub struct Snapshot {
 
     /// Configuration at the time of snapshot
     pub configuration: Vec<String>,
+
+    /// Non-voting members at the time of snapshot
+    pub learners: Vec<String>,
 }
 
 /// The Raft replicated log
@@ -204,6 +233,7 @@ impl RaftLog {
         up_to_index: LogIndex,
         data: Vec<u8>,
         configuration: Vec<String>,
+        learners: Vec<String>,
     ) -> RaftResult<Snapshot> {
         if up_to_index <= self.base_index {
             return Err(RaftError::InvalidLogIndex(up_to_index));
@@ -218,6 +248,7 @@ impl RaftLog {
             last_included_term: term,
             data,
             configuration,
+            learners,
         };
 
         // Compact the log by removing entries before the snapshot
@@ -326,7 +357,7 @@ mod tests {
         log.append(2, b"cmd3".to_vec());
 
         let snapshot = log
-            .create_snapshot(2, b"state".to_vec(), vec!["node1".to_string()])
+            .create_snapshot(2, b"state".to_vec(), vec!["node1".to_string()], Vec::new())
             .unwrap();
 
         assert_eq!(snapshot.last_included_index, 2);
@@ -0,0 +1,256 @@
+//! Cluster membership
+//!
+//! The members of a cluster are recorded in the log as configuration
+//! entries and change one server at a time, so any majority of the old
+//! voters overlaps any majority of the new ones. Every node uses the latest
+//! configuration in its log, committed or not.
+//!
+//! New members join as learners: they receive the log but don't vote or
+//! count towards quorums until they are promoted, so a node that is still
+//! catching up can't stall commits.
+
+use crate::{
+    log::{EntryKind, RaftLog, Snapshot},
+    LogIndex, NodeId, RaftError, RaftResult,
+};
+use serde::{Deserialize, Serialize};
+use tracing::error;
+
+/// Members of a cluster
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ClusterConfig {
+    /// Members that vote and count towards quorums
+    pub voters: Vec<NodeId>,
+
+    /// Members that receive the log but don't vote
+    pub learners: Vec<NodeId>,
+}
+
+/// A change to the members of a cluster
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum MembershipChange {
+    /// Start replicating to a new, non-voting member
+    AddLearner(NodeId),
+
+    /// Make a learner a voter
+    PromoteLearner(NodeId),
+
+    /// Remove a voter or a learner
+    RemoveMember(NodeId),
+}
+
+impl ClusterConfig {
+    /// Create a configuration of voters only
+    pub fn new(voters: Vec<NodeId>) -> Self {
+        Self {
+            voters,
+            learners: Vec::new(),
+        }
+    }
+
+    /// Read the configuration stored in a snapshot
+    pub fn from_snapshot(snapshot: &Snapshot) -> Self {
+        Self {
+            voters: snapshot.configuration.clone(),
+            learners: snapshot.learners.clone(),
+        }
+    }
+
+    /// Check if `node_id` votes
+    pub fn is_voter(&self, node_id: &str) -> bool {
+        self.voters.iter().any(|voter| voter == node_id)
+    }
+
+    /// Check if `node_id` is a learner
+    pub fn is_learner(&self, node_id: &str) -> bool {
+        self.learners.iter().any(|learner| learner == node_id)
+    }
+
+    /// Check if `node_id` is a voter or a learner
+    pub fn contains(&self, node_id: &str) -> bool {
+        self.is_voter(node_id) || self.is_learner(node_id)
+    }
+
+    /// All members, voters first
+    pub fn members(&self) -> impl Iterator<Item = &NodeId> {
+        self.voters.iter().chain(self.learners.iter())
+    }
+
+    /// Number of voters that make a majority
+    pub fn quorum(&self) -> usize {
+        self.voters.len() / 2 + 1
+    }
+
+    /// The configuration after `change`
+    ///
+    /// # Errors
+    ///
+    /// Returns `ConfigError` if the change doesn't apply to this
+    /// configuration, or would leave the cluster without voters
+    pub fn apply(&self, change: &MembershipChange) -> RaftResult<Self> {
+        let mut config = self.clone();
+        match change {
+            MembershipChange::AddLearner(node_id) => {
+                if self.contains(node_id) {
+                    return Err(RaftError::ConfigError(format!(
+                        "{} is already a member",
+                        node_id
+                    )));
+                }
+                config.learners.push(node_id.clone());
+            }
+            MembershipChange::PromoteLearner(node_id) => {
+                if !self.is_learner(node_id) {
+                    return Err(RaftError::ConfigError(format!(
+                        "{} is not a learner",
+                        node_id
+                    )));
+                }
+                config.learners.retain(|learner| learner != node_id);
+                config.voters.push(node_id.clone());
+            }
+            MembershipChange::RemoveMember(node_id) => {
+                if !self.contains(node_id) {
+                    return Err(RaftError::ConfigError(format!(
+                        "{} is not a member",
+                        node_id
+                    )));
+                }
+                if self.voters == [node_id.clone()] {
+                    return Err(RaftError::ConfigError(
+                        "Can't remove the last voter".to_string(),
+                    ));
+                }
+                config.voters.retain(|voter| voter != node_id);
+                config.learners.retain(|learner| learner != node_id);
+            }
+        }
+        Ok(config)
+    }
+
+    /// Serialize to bytes
+    pub fn to_bytes(&self) -> Result<Vec<u8>, bincode::error::EncodeError> {
+        use bincode::config;
+        bincode::encode_to_vec(bincode::serde::Compat(self), config::standard())
+    }
+
+    /// Deserialize from bytes
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, bincode::error::DecodeError> {
+        use bincode::config;
+        let (compat, _): (bincode::serde::Compat<Self>, _) =
+            bincode::decode_from_slice(bytes, config::standard())?;
+        Ok(compat.0)
+    }
+}
+
+/// The configuration in effect at `index`, with the index of the entry
+/// that set it
+///
+/// That is the latest configuration entry up to `index`, else the
+/// snapshot's configuration, else `initial`.
+pub(crate) fn configuration_at(
+    log: &RaftLog,
+    index: LogIndex,
+    initial: &ClusterConfig,
+) -> (LogIndex, ClusterConfig) {
+    let last = index.min(log.last_index());
+    for i in (log.base_index() + 1..=last).rev() {
+        let Some(entry) = log.get(i) else {
+            continue;
+        };
+        if entry.kind != EntryKind::Configuration {
+            continue;
+        }
+        match ClusterConfig::from_bytes(&entry.command) {
+            Ok(config) => return (i, config),
+            Err(e) => error!("Skipping unreadable configuration at {}: {}", i, e),
+        }
+    }
+
+    match log.snapshot() {
+        Some(snapshot) => (
+            snapshot.last_included_index,
+            ClusterConfig::from_snapshot(snapshot),
+        ),
+        None => (0, initial.clone()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::log::LogEntry;
+
+    fn config(voters: &[&str], learners: &[&str]) -> ClusterConfig {
+        ClusterConfig {
+            voters: voters.iter().map(|id| id.to_string()).collect(),
+            learners: learners.iter().map(|id| id.to_string()).collect(),
+        }
+    }
+
+    #[test]
+    fn test_membership_changes() {
+        let initial = config(&["node1", "node2", "node3"], &[]);
+        assert_eq!(initial.quorum(), 2);
+
+        let added = initial
+            .apply(&MembershipChange::AddLearner("node4".to_string()))
+            .unwrap();
+        assert!(added.is_learner("node4"));
+        assert_eq!(added.quorum(), 2);
+        assert!(added
+            .apply(&MembershipChange::AddLearner("node1".to_string()))
+            .is_err());
+        assert!(added
+            .apply(&MembershipChange::PromoteLearner("node1".to_string()))
+            .is_err());
+
+        let promoted = added
+            .apply(&MembershipChange::PromoteLearner("node4".to_string()))
+            .unwrap();
+        assert_eq!(promoted, config(&["node1", "node2", "node3", "node4"], &[]));
+        assert_eq!(promoted.quorum(), 3);
+
+        let removed = promoted
+            .apply(&MembershipChange::RemoveMember("node1".to_string()))
+            .unwrap();
+        assert_eq!(removed, config(&["node2", "node3", "node4"], &[]));
+
+        let single = config(&["node1"], &["node2"]);
+        assert!(single
+            .apply(&MembershipChange::RemoveMember("node1".to_string()))
+            .is_err());
+        assert!(single
+            .apply(&MembershipChange::RemoveMember("node5".to_string()))
+            .is_err());
+    }
+
+    #[test]
+    fn test_configuration_at() {
+        let initial = config(&["node1"], &[]);
+        let joined = config(&["node1"], &["node2"]);
+
+        let mut log = RaftLog::new();
+        log.append(1, b"cmd1".to_vec());
+        log.append_entries(vec![LogEntry::configuration(
+            1,
+            2,
+            joined.to_bytes().unwrap(),
+        )])
+        .unwrap();
+        log.append(1, b"cmd3".to_vec());
+
+        assert_eq!(configuration_at(&log, 1, &initial), (0, initial.clone()));
+        assert_eq!(configuration_at(&log, 3, &initial), (2, joined.clone()));
+
+        // Compacted away, the configuration is read from the snapshot
+        log.create_snapshot(
+            3,
+            Vec::new(),
+            joined.voters.clone(),
+            joined.learners.clone(),
+        )
+        .unwrap();
+        assert_eq!(configuration_at(&log, 3, &initial), (3, joined));
+    }
+}
@@ -6,21 +6,29 @@
 //! - Log replication
 //! - Leader election
 //! - Client request processing
+//! - Membership changes and leadership transfer
 
 use crate::{
     election::{ElectionState, VoteValidator},
+    log::{EntryKind, LogEntry, Snapshot},
+    membership::{configuration_at, ClusterConfig, MembershipChange},
     rpc::{
         AppendEntriesRequest, AppendEntriesResponse, InstallSnapshotRequest,
         InstallSnapshotResponse, RaftMessage, RequestVoteRequest, RequestVoteResponse,
+        TimeoutNowRequest,
     },
     state::{LeaderState, PersistentState, RaftState, VolatileState},
+    state_machine::StateMachine,
+    storage::{MemoryStorage, RaftStorage},
+    transport::RaftTransport,
     LogIndex, NodeId, RaftError, RaftResult, Term,
 };
-use parking_lot::RwLock;
+use parking_lot::{Mutex, RwLock};
+use std::collections::HashMap;
 use std::sync::Arc;
 use std::time::Duration;
-use tokio::sync::mpsc;
-use tokio::time::{interval, sleep};
+use tokio::sync::{mpsc, oneshot, Notify};
+use tokio::time::{interval, timeout, timeout_at, Instant};
 use tracing::{debug, error, info, warn};
 
 /// Configuration for a Raft node
@@ -29,7 +37,10 @@ pub struct RaftNodeConfig {
     /// This node's ID
     pub node_id: NodeId,
 
-    /// IDs of all cluster members (including self)
+    /// IDs of the voting members the cluster starts with (including self)
+    ///
+    /// A node joining an existing cluster starts with none and learns the
+    /// members from the leader's log.
     pub cluster_members: Vec<NodeId>,
 
     /// Minimum election timeout (milliseconds)
@@ -46,6 +57,14 @@ pub struct RaftNodeConfig {
 
     /// Snapshot chunk size (bytes)
     pub snapshot_chunk_size: usize,
+
+    /// Applied entries after which the state machine is snapshotted and the
+    /// log compacted (0 disables automatic snapshots)
+    pub snapshot_threshold: u64,
+
+    /// How long a client request waits to be applied or for a read to be
+    /// confirmed (milliseconds)
+    pub request_timeout: u64,
 }
 
 impl RaftNodeConfig {
@@ -59,6 +78,8 @@ impl RaftNodeConfig {
             heartbeat_interval: 50,
             max_entries_per_message: 100,
             snapshot_chunk_size: 64 * 1024, // 64KB
+            snapshot_threshold: 10_000,
+            request_timeout: 5_000,
         }
     }
 }
@@ -76,6 +97,9 @@ pub struct CommandResult {
     pub term: Term,
 }
 
+/// Sender for the state machine's output once a command is applied
+type AppliedSender = oneshot::Sender<RaftResult<Vec<u8>>>;
+
 /// Internal messages for the Raft node
 #[derive(Debug)]
 enum InternalMessage {
@@ -85,6 +109,13 @@ enum InternalMessage {
     ClientCommand {
         command: Command,
         response_tx: mpsc::Sender<RaftResult<CommandResult>>,
+        applied_tx: Option<AppliedSender>,
+    },
+    /// Membership change to replicate
+    ChangeMembership {
+        change: MembershipChange,
+        response_tx: mpsc::Sender<RaftResult<CommandResult>>,
+        applied_tx: AppliedSender,
     },
     /// Election timeout fired
     ElectionTimeout,
@@ -97,9 +128,12 @@ pub struct RaftNode {
     /// Configuration
     config: RaftNodeConfig,
 
-    /// Persistent state
+    /// Persistent state, written through to `storage` before it changes
     persistent: Arc<RwLock<PersistentState>>,
 
+    /// Stable storage for the persistent state
+    storage: Arc<dyn RaftStorage>,
+
     /// Volatile state
     volatile: Arc<RwLock<VolatileState>>,
 
@@ -115,20 +149,83 @@ pub struct RaftNode {
     /// Current leader ID (if known)
     current_leader: Arc<RwLock<Option<NodeId>>>,
 
+    /// Latest configuration in the log, with the index of its entry
+    membership: RwLock<(LogIndex, ClusterConfig)>,
+
+    /// Follower leadership is being handed to; the leader appends no new
+    /// entries meanwhile
+    transfer_target: RwLock<Option<NodeId>>,
+
+    /// Delivers messages to the other nodes
+    transport: Option<Arc<dyn RaftTransport>>,
+
+    /// State committed entries are applied to
+    state_machine: Option<Arc<dyn StateMachine>>,
+
+    /// Held while entries are applied, so snapshots see a consistent
+    /// state; also held while snapshots are stored and installed
+    apply_lock: Mutex<()>,
+
+    /// Wakes the task applying committed entries
+    apply_ready: Notify,
+
+    /// Clients waiting for the entry at an index, proposed in a term, to
+    /// be applied
+    waiters: Mutex<HashMap<LogIndex, (Term, AppliedSender)>>,
+
+    /// Chunks of the snapshot being received from the leader
+    snapshot_buffer: Mutex<Vec<u8>>,
+
+    /// Notified whenever the node has processed a message
+    progress: Notify,
+
     /// Channel for internal messages
     internal_tx: mpsc::UnboundedSender<InternalMessage>,
-    internal_rx: Arc<RwLock<mpsc::UnboundedReceiver<InternalMessage>>>,
+    internal_rx: Arc<tokio::sync::Mutex<mpsc::UnboundedReceiver<InternalMessage>>>,
 }
 
 impl RaftNode {
-    /// Create a new Raft node
+    /// Create a new Raft node whose state is kept in memory only
     pub fn new(config: RaftNodeConfig) -> Self {
+        Self::with_state(config, Arc::new(MemoryStorage), PersistentState::new())
+    }
+
+    /// Create a Raft node backed by `storage`, recovering the term, vote,
+    /// log and snapshot stored there
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the stored state can't be read
+    pub fn with_storage(config: RaftNodeConfig, storage: Arc<dyn RaftStorage>) -> RaftResult<Self> {
+        let persistent = storage.load()?;
+        info!(
+            "Recovered Raft node {} at term {} with log up to {}",
+            config.node_id,
+            persistent.current_term,
+            persistent.log.last_index()
+        );
+        Ok(Self::with_state(config, storage, persistent))
+    }
+
+    fn with_state(
+        config: RaftNodeConfig,
+        storage: Arc<dyn RaftStorage>,
+        persistent: PersistentState,
+    ) -> Self {
         let (internal_tx, internal_rx) = mpsc::unbounded_channel();
-        let cluster_size = config.cluster_members.len();
+        let initial = ClusterConfig::new(config.cluster_members.clone());
+        let membership = configuration_at(&persistent.log, persistent.log.last_index(), &initial);
+        let cluster_size = membership.1.voters.len();
+
+        // Entries covered by the snapshot were committed and applied
+        let mut volatile = VolatileState::new();
+        volatile.update_commit_index(persistent.log.base_index());
+        volatile.apply_entries(persistent.log.base_index());
 
         Self {
-            persistent: Arc::new(RwLock::new(PersistentState::new())),
-            volatile: Arc::new(RwLock::new(VolatileState::new())),
+            persistent: Arc::new(RwLock::new(persistent)),
+            storage,
+            volatile: Arc::new(RwLock::new(volatile)),
             state: Arc::new(RwLock::new(RaftState::Follower)),
             leader_state: Arc::new(RwLock::new(None)),
             election_state: Arc::new(RwLock::new(ElectionState::new(
@@ -137,12 +234,41 @@ impl RaftNode {
                 config.election_timeout_max,
             ))),
             current_leader: Arc::new(RwLock::new(None)),
+            membership: RwLock::new(membership),
+            transfer_target: RwLock::new(None),
+            transport: None,
+            state_machine: None,
+            apply_lock: Mutex::new(()),
+            apply_ready: Notify::new(),
+            waiters: Mutex::new(HashMap::new()),
+            snapshot_buffer: Mutex::new(Vec::new()),
+            progress: Notify::new(),
             config,
             internal_tx,
-            internal_rx: Arc::new(RwLock::new(internal_rx)),
+            internal_rx: Arc::new(tokio::sync::Mutex::new(internal_rx)),
         }
     }
 
+    /// Send messages to the other nodes through `transport`
+    pub fn with_transport(mut self, transport: Arc<dyn RaftTransport>) -> Self {
+        self.transport = Some(transport);
+        self
+    }
+
+    /// Apply committed entries to `state_machine`, first restoring it from
+    /// the stored snapshot if there is one
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the state machine can't restore the snapshot
+    pub fn with_state_machine(mut self, state_machine: Arc<dyn StateMachine>) -> RaftResult<Self> {
+        if let Some(snapshot) = self.persistent.read().log.snapshot() {
+            state_machine.restore(&snapshot.data)?;
+        }
+        self.state_machine = Some(state_machine);
+        Ok(self)
+    }
+
     /// Start the Raft node
     pub async fn start(self: Arc<Self>) {
         info!("Starting Raft node: {}", self.config.node_id);
@@ -153,140 +279,243 @@ impl RaftNode {
         // Spawn heartbeat timer task (for leaders)
         self.clone().spawn_heartbeat_timer();
 
+        // Spawn the task applying committed entries
+        self.clone().spawn_applier();
+
         // Main message processing loop
         self.run().await;
     }
 
     /// Main message processing loop
     async fn run(self: Arc<Self>) {
-        loop {
-            let message = {
-                let mut rx = self.internal_rx.write();
-                rx.recv().await
-            };
-
+        let mut rx = self.internal_rx.lock().await;
+        while let Some(message) = rx.recv().await {
             match message {
-                Some(InternalMessage::Rpc { from, message }) => {
+                InternalMessage::Rpc { from, message } => {
                     self.handle_rpc_message(from, message).await;
                 }
-                Some(InternalMessage::ClientCommand {
+                InternalMessage::ClientCommand {
                     command,
                     response_tx,
-                }) => {
-                    self.handle_client_command(command, response_tx).await;
+                    applied_tx,
+                } => {
+                    self.handle_client_command(command, response_tx, applied_tx)
+                        .await;
+                }
+                InternalMessage::ChangeMembership {
+                    change,
+                    response_tx,
+                    applied_tx,
+                } => {
+                    self.handle_membership_change(change, response_tx, applied_tx)
+                        .await;
                 }
-                Some(InternalMessage::ElectionTimeout) => {
+                InternalMessage::ElectionTimeout => {
                     self.handle_election_timeout().await;
                 }
-                Some(InternalMessage::HeartbeatTimeout) => {
+                InternalMessage::HeartbeatTimeout => {
                     self.handle_heartbeat_timeout().await;
                 }
-                None => {
-                    warn!("Internal channel closed, stopping node");
-                    break;
-                }
             }
+
+            self.apply_ready.notify_one();
+            self.step_down_if_removed();
+            self.progress.notify_waiters();
+        }
+        warn!("Internal channel closed, stopping node");
+    }
+
+    /// Queue a message from another node for processing
+    ///
+    /// Called by the transport when a message for this node arrives.
+    pub fn receive(&self, from: NodeId, message: RaftMessage) {
+        let _ = self
+            .internal_tx
+            .send(InternalMessage::Rpc { from, message });
+    }
+
+    /// Send a message to another node
+    fn send(&self, to: &NodeId, message: RaftMessage) {
+        match &self.transport {
+            Some(transport) => transport.send(&self.config.node_id, to, message),
+            None => debug!("No transport, dropping message to {}", to),
         }
     }
 
     /// Handle RPC message from another node
-    async fn handle_rpc_message(&self, from: NodeId, message: RaftMessage) {
-        // Update term if necessary
+    async fn handle_rpc_message(self: &Arc<Self>, from: NodeId, message: RaftMessage) {
+        // Update term if necessary; pre-votes carry a term the candidate
+        // hasn't entered yet
         let message_term = message.term();
         let current_term = self.persistent.read().current_term;
 
-        if message_term > current_term {
-            self.step_down(message_term).await;
+        if message_term > current_term && !message.is_pre_vote() {
+            // A message from a term that can't be made durable is dropped,
+            // like a vote that can't be persisted
+            if let Err(e) = self.step_down(message_term).await {
+                error!(
+                    "Failed to persist term {}, dropping message from {}: {}",
+                    message_term, from, e
+                );
+                return;
+            }
         }
 
         match message {
             RaftMessage::AppendEntriesRequest(req) => {
-                let response = self.handle_append_entries(req).await;
-                // TODO: Send response back to sender
-                debug!("AppendEntries response to {}: {:?}", from, response);
+                let round = req.round;
+                let mut response = self.handle_append_entries(req).await;
+                response.round = round;
+                self.send(&from, RaftMessage::AppendEntriesResponse(response));
             }
             RaftMessage::AppendEntriesResponse(resp) => {
                 self.handle_append_entries_response(from, resp).await;
             }
             RaftMessage::RequestVoteRequest(req) => {
                 let response = self.handle_request_vote(req).await;
-                // TODO: Send response back to sender
-                debug!("RequestVote response to {}: {:?}", from, response);
+                self.send(&from, RaftMessage::RequestVoteResponse(response));
             }
             RaftMessage::RequestVoteResponse(resp) => {
                 self.handle_request_vote_response(from, resp).await;
             }
             RaftMessage::InstallSnapshotRequest(req) => {
                 let response = self.handle_install_snapshot(req).await;
-                // TODO: Send response back to sender
-                debug!("InstallSnapshot response to {}: {:?}", from, response);
+                self.send(&from, RaftMessage::InstallSnapshotResponse(response));
             }
             RaftMessage::InstallSnapshotResponse(resp) => {
                 self.handle_install_snapshot_response(from, resp).await;
             }
+            RaftMessage::TimeoutNow(req) => {
+                self.handle_timeout_now(from, req).await;
+            }
         }
     }
 
     /// Handle AppendEntries RPC
     async fn handle_append_entries(&self, req: AppendEntriesRequest) -> AppendEntriesResponse {
-        let mut persistent = self.persistent.write();
-        let mut volatile = self.volatile.write();
+        let (current_term, truncate_from, new_entries) = {
+            let persistent = self.persistent.read();
 
-        // Reply false if term < currentTerm
-        if req.term < persistent.current_term {
-            return AppendEntriesResponse::failure(persistent.current_term, None, None);
-        }
+            // Reply false if term < currentTerm
+            if req.term < persistent.current_term {
+                return AppendEntriesResponse::failure(persistent.current_term, None, None);
+            }
 
-        // Reset election timer
-        self.election_state.write().reset_timer();
-        *self.current_leader.write() = Some(req.leader_id.clone());
+            // A candidate that hears from the leader of its term lost the
+            // election
+            if self.state.read().is_candidate() {
+                *self.state.write() = RaftState::Follower;
+            }
 
-        // Reply false if log doesn't contain an entry at prevLogIndex with prevLogTerm
-        if !persistent
-            .log
-            .matches(req.prev_log_index, req.prev_log_term)
-        {
-            let conflict_index = req.prev_log_index;
-            let conflict_term = persistent.log.term_at(conflict_index);
-            return AppendEntriesResponse::failure(
-                persistent.current_term,
-                Some(conflict_index),
-                conflict_term,
-            );
-        }
+            // Reset election timer
+            self.election_state.write().record_leader_contact();
+            *self.current_leader.write() = Some(req.leader_id.clone());
+
+            // Reply false if log doesn't contain an entry at prevLogIndex
+            // with prevLogTerm, pointing the leader at where to resume
+            if !persistent
+                .log
+                .matches(req.prev_log_index, req.prev_log_term)
+            {
+                let last_index = persistent.log.last_index();
+                let base_index = persistent.log.base_index();
+                let conflict_index = if req.prev_log_index > last_index {
+                    last_index + 1
+                } else if req.prev_log_index < base_index {
+                    base_index + 1
+                } else {
+                    req.prev_log_index
+                };
+                let conflict_term = persistent.log.term_at(req.prev_log_index);
+                return AppendEntriesResponse::failure(
+                    persistent.current_term,
+                    Some(conflict_index),
+                    conflict_term,
+                );
+            }
 
-        // Append new entries
-        if !req.entries.is_empty() {
-            // Delete conflicting entries and append new ones
-            let mut index = req.prev_log_index + 1;
-            for entry in &req.entries {
-                if let Some(existing_term) = persistent.log.term_at(index) {
-                    if existing_term != entry.term {
-                        // Conflict found, truncate from here
-                        let _ = persistent.log.truncate_from(index);
+            // Skip entries already in the log; a conflicting entry and all
+            // that follow it are deleted, and the rest appended
+            let mut first_new = req.entries.len();
+            let mut truncate_from = None;
+            for (i, entry) in req.entries.iter().enumerate() {
+                if entry.index <= persistent.log.base_index() {
+                    continue;
+                }
+                match persistent.log.term_at(entry.index) {
+                    Some(existing_term) if existing_term == entry.term => {}
+                    Some(_) => {
+                        truncate_from = Some(entry.index);
+                        first_new = i;
+                        break;
+                    }
+                    None => {
+                        first_new = i;
+                        break;
                     }
                 }
-                index += 1;
             }
-
-            // Append entries
-            if let Err(e) = persistent.log.append_entries(req.entries.clone()) {
-                error!("Failed to append entries: {}", e);
-                return AppendEntriesResponse::failure(persistent.current_term, None, None);
+            (
+                persistent.current_term,
+                truncate_from,
+                req.entries[first_new..].to_vec(),
+            )
+        };
+
+        // Only the message loop changes the log, so it can be stored
+        // without holding it
+        let new_entries = if truncate_from.is_some() || !new_entries.is_empty() {
+            let stored = self
+                .persist(move |storage| {
+                    if let Some(index) = truncate_from {
+                        storage.truncate_from(index)?;
+                    }
+                    if !new_entries.is_empty() {
+                        storage.append_entries(&new_entries)?;
+                    }
+                    Ok(new_entries)
+                })
+                .await;
+            match stored {
+                Ok(new_entries) => new_entries,
+                Err(e) => {
+                    error!("Failed to store entries: {}", e);
+                    return AppendEntriesResponse::failure(current_term, None, None);
+                }
             }
+        } else {
+            new_entries
+        };
+
+        let mut persistent = self.persistent.write();
+        // A truncated configuration is rolled back, an appended one takes
+        // effect right away
+        let appended_configuration = new_entries
+            .iter()
+            .any(|entry| entry.kind == EntryKind::Configuration);
+        let updated = match truncate_from {
+            Some(index) => persistent.log.truncate_from(index),
+            None => Ok(()),
+        }
+        .and_then(|_| persistent.log.append_entries(new_entries));
+        if let Err(e) = updated {
+            error!("Failed to append entries: {}", e);
+            return AppendEntriesResponse::failure(current_term, None, None);
         }
+        if truncate_from.is_some() || appended_configuration {
+            self.refresh_membership(&persistent);
+        }
+
+        // Only the entries the leader sent are known to match its log
+        let last_new_entry = req.prev_log_index + req.entries.len() as LogIndex;
 
         // Update commit index
+        let mut volatile = self.volatile.write();
         if req.leader_commit > volatile.commit_index {
-            let last_new_entry = if req.entries.is_empty() {
-                req.prev_log_index
-            } else {
-                req.entries.last().unwrap().index
-            };
             volatile.update_commit_index(std::cmp::min(req.leader_commit, last_new_entry));
         }
 
-        AppendEntriesResponse::success(persistent.current_term, persistent.log.last_index())
+        AppendEntriesResponse::success(current_term, last_new_entry)
     }
 
     /// Handle AppendEntries response
@@ -295,74 +524,177 @@ impl RaftNode {
             return;
         }
 
-        let persistent = self.persistent.write();
-        let mut leader_state_guard = self.leader_state.write();
+        {
+            let persistent = self.persistent.read();
+            if resp.term != persistent.current_term {
+                return;
+            }
+
+            let mut leader_state_guard = self.leader_state.write();
+            let Some(leader_state) = leader_state_guard.as_mut() else {
+                return;
+            };
+
+            // Removed members are no longer replicated to
+            if leader_state.get_next_index(&from).is_none() {
+                return;
+            }
+
+            // Any response in our term shows the follower still follows us
+            leader_state.acknowledge(&from, resp.round);
 
-        if let Some(leader_state) = leader_state_guard.as_mut() {
             if resp.success {
                 // Update next_index and match_index
                 if let Some(match_index) = resp.match_index {
-                    leader_state.update_replication(&from, match_index);
-
-                    // Update commit index
-                    let new_commit = leader_state.calculate_commit_index();
-                    let mut volatile = self.volatile.write();
-                    if new_commit > volatile.commit_index {
-                        // Verify the entry is from current term
-                        if let Some(term) = persistent.log.term_at(new_commit) {
-                            if term == persistent.current_term {
-                                volatile.update_commit_index(new_commit);
-                                info!("Updated commit index to {}", new_commit);
-                            }
-                        }
+                    if match_index >= leader_state.get_match_index(&from).unwrap_or(0) {
+                        leader_state.update_replication(&from, match_index);
                     }
                 }
+            } else if let Some(conflict_index) = resp.conflict_index {
+                leader_state
+                    .next_index
+                    .insert(from.clone(), conflict_index.max(1));
+                debug!(
+                    "Replication to {} failed, resuming from {}",
+                    from, conflict_index
+                );
             } else {
                 // Decrement next_index and retry
                 leader_state.decrement_next_index(&from);
                 debug!("Replication failed for {}, decrementing next_index", from);
             }
         }
+
+        self.advance_commit_index();
+    }
+
+    /// Commit the entries a majority of the voters has stored
+    fn advance_commit_index(&self) {
+        let persistent = self.persistent.read();
+        let voters = self.membership.read().1.voters.clone();
+        let leader_state_guard = self.leader_state.read();
+        let Some(leader_state) = leader_state_guard.as_ref() else {
+            return;
+        };
+
+        let new_commit = leader_state.quorum_match_index(
+            &voters,
+            &self.config.node_id,
+            persistent.log.last_index(),
+        );
+
+        let mut volatile = self.volatile.write();
+        if new_commit > volatile.commit_index {
+            // Only entries from the current term are committed by counting
+            // replicas, earlier ones are committed along with them
+            if persistent.log.term_at(new_commit) == Some(persistent.current_term) {
+                volatile.update_commit_index(new_commit);
+                debug!("Updated commit index to {}", new_commit);
+            }
+        }
     }
 
     /// Handle RequestVote RPC
     async fn handle_request_vote(&self, req: RequestVoteRequest) -> RequestVoteResponse {
-        let mut persistent = self.persistent.write();
-
-        // Reply false if term < currentTerm
-        if req.term < persistent.current_term {
-            return RequestVoteResponse::denied(persistent.current_term);
+        if req.pre_vote {
+            return self.handle_pre_vote(req);
         }
 
-        let last_log_index = persistent.log.last_index();
-        let last_log_term = persistent.log.last_term();
+        let (current_term, should_grant) = {
+            let persistent = self.persistent.read();
 
-        // Check if we should grant vote
-        let should_grant = VoteValidator::should_grant_vote(
-            persistent.current_term,
-            &persistent.voted_for,
-            last_log_index,
-            last_log_term,
-            &req.candidate_id,
-            req.term,
-            req.last_log_index,
-            req.last_log_term,
-        );
+            // Reply false if term < currentTerm
+            if req.term < persistent.current_term {
+                return RequestVoteResponse::denied(persistent.current_term);
+            }
+
+            // Check if we should grant vote
+            let should_grant = VoteValidator::should_grant_vote(
+                persistent.current_term,
+                &persistent.voted_for,
+                persistent.log.last_index(),
+                persistent.log.last_term(),
+                &req.candidate_id,
+                req.term,
+                req.last_log_index,
+                req.last_log_term,
+            );
+            (persistent.current_term, should_grant)
+        };
 
         if should_grant {
-            persistent.vote_for(req.candidate_id.clone());
+            // The vote must be durable before it is granted
+            let candidate_id = req.candidate_id.clone();
+            let stored = self
+                .persist(move |storage| storage.save_hard_state(current_term, Some(&candidate_id)))
+                .await;
+            if let Err(e) = stored {
+                error!("Failed to persist vote for {}: {}", req.candidate_id, e);
+                return RequestVoteResponse::denied(current_term);
+            }
+            self.persistent.write().vote_for(req.candidate_id.clone());
             self.election_state.write().reset_timer();
             info!("Granted vote to {} for term {}", req.candidate_id, req.term);
-            RequestVoteResponse::granted(persistent.current_term)
+            RequestVoteResponse::granted(current_term)
         } else {
             debug!("Denied vote to {} for term {}", req.candidate_id, req.term);
-            RequestVoteResponse::denied(persistent.current_term)
+            RequestVoteResponse::denied(current_term)
+        }
+    }
+
+    /// Handle a pre-vote, neither changing the term nor voting
+    ///
+    /// Granted if the candidate's log is up to date and no leader was heard
+    /// from within the minimum election timeout, so a node rejoining after
+    /// a partition can't force an election on a cluster with a live leader.
+    fn handle_pre_vote(&self, req: RequestVoteRequest) -> RequestVoteResponse {
+        let persistent = self.persistent.read();
+        let window = Duration::from_millis(self.config.election_timeout_min);
+        let has_leader = self.state.read().is_leader()
+            || self.election_state.read().leader_contacted_within(window);
+
+        let should_grant = !has_leader
+            && VoteValidator::should_grant_vote(
+                persistent.current_term,
+                &None,
+                persistent.log.last_index(),
+                persistent.log.last_term(),
+                &req.candidate_id,
+                req.term,
+                req.last_log_index,
+                req.last_log_term,
+            );
+
+        if should_grant {
+            debug!(
+                "Granted pre-vote to {} for term {}",
+                req.candidate_id, req.term
+            );
+            RequestVoteResponse::pre_vote(req.term, true)
+        } else {
+            debug!(
+                "Denied pre-vote to {} for term {}",
+                req.candidate_id, req.term
+            );
+            RequestVoteResponse::pre_vote(persistent.current_term, false)
         }
     }
 
     /// Handle RequestVote response
     async fn handle_request_vote_response(&self, from: NodeId, resp: RequestVoteResponse) {
-        if !self.state.read().is_candidate() {
+        // Only voters' votes count
+        let is_voter = self.membership.read().1.is_voter(&from);
+        if !is_voter {
+            return;
+        }
+
+        if resp.pre_vote {
+            self.handle_pre_vote_response(from, resp).await;
+            return;
+        }
+
+        let is_candidate = self.state.read().is_candidate();
+        if !is_candidate {
             return;
         }
 
@@ -380,29 +712,148 @@ impl RaftNode {
         }
     }
 
+    /// Handle a pre-vote response, starting the election once a majority
+    /// would vote in it
+    async fn handle_pre_vote_response(&self, from: NodeId, resp: RequestVoteResponse) {
+        let is_follower = self.state.read().is_follower();
+        let pre_vote_term = self.election_state.read().pre_vote_term;
+        if !is_follower || !resp.vote_granted || pre_vote_term != Some(resp.term) {
+            return;
+        }
+
+        let won_pre_vote = self.election_state.write().record_vote(from);
+        if won_pre_vote {
+            info!("Won pre-vote for term {}", resp.term);
+            self.start_election().await;
+        }
+    }
+
+    /// Handle TimeoutNow RPC, starting an election right away as the leader
+    /// hands over leadership
+    async fn handle_timeout_now(&self, from: NodeId, req: TimeoutNowRequest) {
+        let current_term = self.persistent.read().current_term;
+        let is_voter = self.membership.read().1.is_voter(&self.config.node_id);
+        if req.term != current_term || !is_voter {
+            return;
+        }
+
+        info!("{} is handing over leadership, starting election", from);
+        self.start_election().await;
+    }
+
     /// Handle InstallSnapshot RPC
+    ///
+    /// Chunks are buffered until the last one arrives, then the snapshot
+    /// replaces the state machine and the log it covers.
     async fn handle_install_snapshot(
-        &self,
+        self: &Arc<Self>,
         req: InstallSnapshotRequest,
     ) -> InstallSnapshotResponse {
-        let persistent = self.persistent.write();
+        let current_term = self.persistent.read().current_term;
+        if req.term < current_term {
+            return InstallSnapshotResponse::failure(current_term);
+        }
+
+        self.election_state.write().record_leader_contact();
+        *self.current_leader.write() = Some(req.leader_id.clone());
 
-        if req.term < persistent.current_term {
-            return InstallSnapshotResponse::failure(persistent.current_term);
+        let data = {
+            let mut buffer = self.snapshot_buffer.lock();
+            if req.offset == 0 {
+                buffer.clear();
+            }
+            if req.offset != buffer.len() as u64 {
+                debug!(
+                    "Snapshot chunk at {} doesn't follow the {} bytes received",
+                    req.offset,
+                    buffer.len()
+                );
+                return InstallSnapshotResponse::failure(current_term);
+            }
+            buffer.extend_from_slice(&req.data);
+            if !req.done {
+                return InstallSnapshotResponse::success(current_term, Some(buffer.len() as u64));
+            }
+            std::mem::take(&mut *buffer)
+        };
+
+        if req.last_included_index <= self.volatile.read().last_applied {
+            return InstallSnapshotResponse::success(current_term, None);
         }
 
-        // TODO: Implement snapshot installation
-        // For now, just acknowledge
-        InstallSnapshotResponse::success(persistent.current_term, None)
+        let snapshot = Snapshot {
+            last_included_index: req.last_included_index,
+            last_included_term: req.last_included_term,
+            data,
+            configuration: req.configuration,
+            learners: req.learners,
+        };
+        // Restoring the state machine blocks, so it runs off the message
+        // loop, which waits for it before handling later entries
+        let node = self.clone();
+        let installed = tokio::task::spawn_blocking(move || node.install_snapshot(snapshot))
+            .await
+            .unwrap_or_else(|e| Err(RaftError::Internal(format!("Install panicked: {}", e))));
+        match installed {
+            Ok(()) => InstallSnapshotResponse::success(current_term, None),
+            Err(e) => {
+                error!("Failed to install snapshot: {}", e);
+                InstallSnapshotResponse::failure(current_term)
+            }
+        }
+    }
+
+    /// Replace the state machine and the log up to the snapshot's last entry
+    fn install_snapshot(&self, snapshot: Snapshot) -> RaftResult<()> {
+        let _applying = self.apply_lock.lock();
+        let index = snapshot.last_included_index;
+        info!(
+            "Installing snapshot up to {} from term {}",
+            index, snapshot.last_included_term
+        );
+
+        if let Some(state_machine) = &self.state_machine {
+            state_machine.restore(&snapshot.data)?;
+        }
+
+        // Entries after the snapshot are kept only if the log agrees with
+        // it at its last entry
+        let truncate = {
+            let persistent = self.persistent.read();
+            persistent.log.term_at(index) != Some(snapshot.last_included_term)
+                && persistent.log.last_index() > index
+        };
+        self.storage.save_snapshot(&snapshot)?;
+        if truncate {
+            self.storage.truncate_from(index + 1)?;
+        }
+
+        {
+            let mut persistent = self.persistent.write();
+            persistent.log.install_snapshot(snapshot)?;
+            if truncate {
+                persistent.log.truncate_from(index + 1)?;
+            }
+            self.refresh_membership(&persistent);
+        }
+
+        let mut volatile = self.volatile.write();
+        volatile.update_commit_index(index);
+        volatile.apply_entries(index);
+        drop(volatile);
+
+        self.fail_waiters(|waiting| waiting <= index);
+        Ok(())
     }
 
     /// Handle InstallSnapshot response
-    async fn handle_install_snapshot_response(
-        &self,
-        _from: NodeId,
-        _resp: InstallSnapshotResponse,
-    ) {
-        // TODO: Implement snapshot response handling
+    ///
+    /// The leader already resumes replication after the snapshot, and the
+    /// AppendEntries responses that follow report the follower's progress.
+    async fn handle_install_snapshot_response(&self, from: NodeId, resp: InstallSnapshotResponse) {
+        if !resp.success {
+            debug!("Snapshot installation on {} failed", from);
+        }
     }
 
     /// Handle client command
@@ -410,75 +861,293 @@ impl RaftNode {
         &self,
         command: Command,
         response_tx: mpsc::Sender<RaftResult<CommandResult>>,
+        applied_tx: Option<AppliedSender>,
     ) {
-        // Only leader can handle client commands
-        if !self.state.read().is_leader() {
-            let _ = response_tx.send(Err(RaftError::NotLeader)).await;
-            return;
+        let result = self
+            .append_entry(EntryKind::Command, command.data, applied_tx)
+            .await;
+        let _ = response_tx.send(result).await;
+    }
+
+    /// Handle a membership change, appending the new configuration
+    async fn handle_membership_change(
+        &self,
+        change: MembershipChange,
+        response_tx: mpsc::Sender<RaftResult<CommandResult>>,
+        applied_tx: AppliedSender,
+    ) {
+        let result = self.append_membership_change(&change, applied_tx).await;
+        let _ = response_tx.send(result).await;
+    }
+
+    async fn append_membership_change(
+        &self,
+        change: &MembershipChange,
+        applied_tx: AppliedSender,
+    ) -> RaftResult<CommandResult> {
+        self.check_leader()?;
+
+        // One server at a time: the previous change must be committed first
+        let (index, config) = self.membership.read().clone();
+        if index > self.volatile.read().commit_index {
+            return Err(RaftError::ConfigError(
+                "Another membership change is in progress".to_string(),
+            ));
         }
 
-        let mut persistent = self.persistent.write();
-        let term = persistent.current_term;
-        let index = persistent.log.append(term, command.data);
+        let config = config.apply(change)?;
+        info!("Changing cluster membership: {:?}", change);
+        self.append_entry(
+            EntryKind::Configuration,
+            config.to_bytes()?,
+            Some(applied_tx),
+        )
+        .await
+    }
 
-        let result = CommandResult { index, term };
-        let _ = response_tx.send(Ok(result)).await;
+    /// Append an entry to the leader's log and start replicating it
+    async fn append_entry(
+        &self,
+        kind: EntryKind,
+        data: Vec<u8>,
+        applied_tx: Option<AppliedSender>,
+    ) -> RaftResult<CommandResult> {
+        // Only leader can handle client commands, and not while it hands
+        // leadership over
+        if !self.state.read().is_leader() || self.transfer_target.read().is_some() {
+            return Err(RaftError::NotLeader);
+        }
+
+        let (term, index) = {
+            let persistent = self.persistent.read();
+            (persistent.current_term, persistent.log.last_index() + 1)
+        };
+        let entry = match kind {
+            EntryKind::Command => LogEntry::new(term, index, data),
+            EntryKind::Configuration => LogEntry::configuration(term, index, data),
+        };
+        let entry = self
+            .persist(move |storage| {
+                storage.append_entries(std::slice::from_ref(&entry))?;
+                Ok(entry)
+            })
+            .await?;
+
+        let result = {
+            let mut persistent = self.persistent.write();
+            persistent.log.append_entries(vec![entry])?;
+
+            // A configuration takes effect as soon as it is in the log
+            if kind == EntryKind::Configuration {
+                self.refresh_membership(&persistent);
+            }
+            if let Some(applied_tx) = applied_tx {
+                self.waiters.lock().insert(index, (term, applied_tx));
+            }
+            CommandResult { index, term }
+        };
+
+        // A single node commits as soon as the entry is stored
+        self.advance_commit_index();
 
         // Trigger immediate replication
-        drop(persistent);
         let _ = self.internal_tx.send(InternalMessage::HeartbeatTimeout);
+        Ok(result)
+    }
+
+    /// Switch to the latest configuration in the log
+    fn refresh_membership(&self, persistent: &PersistentState) {
+        let initial = ClusterConfig::new(self.config.cluster_members.clone());
+        let latest = configuration_at(&persistent.log, persistent.log.last_index(), &initial);
+
+        let mut membership = self.membership.write();
+        if *membership == latest {
+            return;
+        }
+        let config = &latest.1;
+        info!(
+            "Cluster voters are now {:?}, learners {:?}",
+            config.voters, config.learners
+        );
+
+        self.election_state
+            .write()
+            .update_cluster_size(config.voters.len());
+        if let Some(leader_state) = self.leader_state.write().as_mut() {
+            let last_index = persistent.log.last_index();
+            for member in config.members() {
+                if member != &self.config.node_id {
+                    leader_state.add_member(member, last_index);
+                }
+            }
+            let removed: Vec<NodeId> = leader_state
+                .next_index
+                .keys()
+                .filter(|member| !config.contains(member))
+                .cloned()
+                .collect();
+            for member in &removed {
+                leader_state.remove_member(member);
+            }
+        }
+        *membership = latest;
+    }
+
+    /// Step down once a configuration that removed this leader is
+    /// committed, handing over to the most up-to-date voter
+    ///
+    /// It stays a follower, and doesn't campaign, until it is added back.
+    fn step_down_if_removed(&self) {
+        if !self.state.read().is_leader() {
+            return;
+        }
+        let (index, config) = self.membership.read().clone();
+        if config.is_voter(&self.config.node_id) || index > self.volatile.read().commit_index {
+            return;
+        }
+
+        info!("Removed from the cluster, stepping down");
+        let successor = self.leader_state.read().as_ref().and_then(|leader_state| {
+            config
+                .voters
+                .iter()
+                .max_by_key(|voter| leader_state.get_match_index(voter).unwrap_or(0))
+                .cloned()
+        });
+        *self.state.write() = RaftState::Follower;
+        *self.leader_state.write() = None;
+        *self.current_leader.write() = None;
+
+        if let Some(successor) = successor {
+            let term = self.persistent.read().current_term;
+            let request = TimeoutNowRequest::new(term, self.config.node_id.clone());
+            self.send(&successor, RaftMessage::TimeoutNow(request));
+        }
     }
 
     /// Handle election timeout
+    ///
+    /// A follower first holds a pre-vote; a candidate whose election timed
+    /// out, having already won one, runs again directly.
     async fn handle_election_timeout(&self) {
-        if self.state.read().is_leader() {
+        let is_leader = self.state.read().is_leader();
+        let timed_out = self.election_state.read().should_start_election();
+        if is_leader || !timed_out {
             return;
         }
 
-        if !self.election_state.read().should_start_election() {
+        // Learners and removed members never campaign
+        let is_voter = self.membership.read().1.is_voter(&self.config.node_id);
+        if !is_voter {
+            self.election_state.write().reset_timer();
             return;
         }
 
-        info!("Election timeout, starting election");
-        self.start_election().await;
+        let is_candidate = self.state.read().is_candidate();
+        if is_candidate {
+            info!("Election timeout, starting election");
+            self.start_election().await;
+        } else {
+            info!("Election timeout, starting pre-vote");
+            self.start_pre_vote().await;
+        }
+    }
+
+    /// Ask the voters whether this node could win an election in the next
+    /// term, before disrupting the cluster by entering it
+    async fn start_pre_vote(&self) {
+        let (term, last_log_index, last_log_term) = {
+            let persistent = self.persistent.read();
+            (
+                persistent.current_term + 1,
+                persistent.log.last_index(),
+                persistent.log.last_term(),
+            )
+        };
+
+        self.election_state
+            .write()
+            .start_pre_vote(term, &self.config.node_id);
+
+        // A single voter needs no one's agreement
+        let won_pre_vote = self.election_state.read().votes.has_quorum();
+        if won_pre_vote {
+            self.start_election().await;
+            return;
+        }
+
+        debug!("Starting pre-vote for term {}", term);
+        let voters = self.membership.read().1.voters.clone();
+        for voter in voters.iter().filter(|voter| *voter != &self.config.node_id) {
+            let request = RequestVoteRequest::pre_vote(
+                term,
+                self.config.node_id.clone(),
+                last_log_index,
+                last_log_term,
+            );
+            self.send(voter, RaftMessage::RequestVoteRequest(request));
+        }
     }
 
     /// Start a new election
     async fn start_election(&self) {
-        // Transition to candidate
-        *self.state.write() = RaftState::Candidate;
+        // Increment term and vote for self, durably, before asking for votes
+        let term = self.persistent.read().current_term + 1;
+        let node_id = self.config.node_id.clone();
+        let stored = self
+            .persist(move |storage| storage.save_hard_state(term, Some(&node_id)))
+            .await;
+        if let Err(e) = stored {
+            error!(
+                "Failed to persist term {}, not starting election: {}",
+                term, e
+            );
+            return;
+        }
 
-        // Increment term and vote for self
-        let mut persistent = self.persistent.write();
-        persistent.increment_term();
-        persistent.vote_for(self.config.node_id.clone());
-        let term = persistent.current_term;
+        let (term, last_log_index, last_log_term) = {
+            let mut persistent = self.persistent.write();
+
+            // Transition to candidate
+            *self.state.write() = RaftState::Candidate;
+            *self.current_leader.write() = None;
+            persistent.increment_term();
+            persistent.vote_for(self.config.node_id.clone());
+
+            (
+                persistent.current_term,
+                persistent.log.last_index(),
+                persistent.log.last_term(),
+            )
+        };
 
         // Initialize election state
         self.election_state
             .write()
             .start_election(term, &self.config.node_id);
 
-        let last_log_index = persistent.log.last_index();
-        let last_log_term = persistent.log.last_term();
-
         info!(
             "Starting election for term {} as {}",
             term, self.config.node_id
         );
 
-        // Send RequestVote RPCs to all other nodes
-        for member in &self.config.cluster_members {
-            if member != &self.config.node_id {
-                let _request = RequestVoteRequest::new(
-                    term,
-                    self.config.node_id.clone(),
-                    last_log_index,
-                    last_log_term,
-                );
-                // TODO: Send request to member
-                debug!("Would send RequestVote to {}", member);
-            }
+        // A single node elects itself
+        let elected = self.election_state.read().votes.has_quorum();
+        if elected {
+            self.become_leader().await;
+            return;
+        }
+
+        // Send RequestVote RPCs to all other voters
+        let voters = self.membership.read().1.voters.clone();
+        for voter in voters.iter().filter(|voter| *voter != &self.config.node_id) {
+            let request = RequestVoteRequest::new(
+                term,
+                self.config.node_id.clone(),
+                last_log_index,
+                last_log_term,
+            );
+            self.send(voter, RaftMessage::RequestVoteRequest(request));
         }
     }
 
@@ -493,30 +1162,46 @@ impl RaftNode {
         *self.current_leader.write() = Some(self.config.node_id.clone());
 
         let last_log_index = self.persistent.read().log.last_index();
-        let other_members: Vec<_> = self
-            .config
-            .cluster_members
-            .iter()
-            .filter(|m| *m != &self.config.node_id)
-            .cloned()
-            .collect();
+        let other_members = self.other_members();
 
         *self.leader_state.write() = Some(LeaderState::new(&other_members, last_log_index));
 
-        // Send initial heartbeats
-        let _ = self.internal_tx.send(InternalMessage::HeartbeatTimeout);
+        // Commit an entry of the new term right away, which commits the
+        // entries of earlier terms and lets reads confirm the commit index
+        if let Err(e) = self
+            .append_entry(EntryKind::Command, Vec::new(), None)
+            .await
+        {
+            error!("Failed to append no-op entry: {}", e);
+        }
     }
 
     /// Step down to follower (when discovering higher term)
-    async fn step_down(&self, term: Term) {
+    ///
+    /// The new term is persisted first; if that fails the node is left
+    /// unchanged, so it never acts on a term and cleared vote that a restart
+    /// would forget.
+    async fn step_down(&self, term: Term) -> RaftResult<()> {
+        if term > self.persistent.read().current_term {
+            self.persist(move |storage| storage.save_hard_state(term, None))
+                .await?;
+            self.persistent.write().update_term(term);
+        }
+
         info!("Stepping down to follower for term {}", term);
 
+        // A former leader or candidate waits a full timeout before running
+        let was_follower = self.state.read().is_follower();
         *self.state.write() = RaftState::Follower;
         *self.leader_state.write() = None;
         *self.current_leader.write() = None;
-
-        let mut persistent = self.persistent.write();
-        persistent.update_term(term);
+        *self.transfer_target.write() = None;
+        let mut election_state = self.election_state.write();
+        election_state.pre_vote_term = None;
+        if !was_follower {
+            election_state.reset_timer();
+        }
+        Ok(())
     }
 
     /// Handle heartbeat timeout (for leaders)
@@ -525,28 +1210,202 @@ impl RaftNode {
             return;
         }
 
-        self.send_heartbeats().await;
+        self.replicate();
     }
 
-    /// Send heartbeats to all followers
-    async fn send_heartbeats(&self) {
+    /// Send every follower the entries it is missing, or the snapshot if
+    /// they were compacted away; with nothing to send this is a heartbeat
+    fn replicate(&self) {
         let persistent = self.persistent.read();
         let term = persistent.current_term;
         let commit_index = self.volatile.read().commit_index;
+        let members = self.other_members();
+
+        let mut leader_state_guard = self.leader_state.write();
+        let Some(leader_state) = leader_state_guard.as_mut() else {
+            return;
+        };
+        leader_state.round += 1;
+        let round = leader_state.round;
+
+        for member in &members {
+            let next_index = leader_state
+                .get_next_index(member)
+                .unwrap_or(persistent.log.last_index() + 1);
+            if let Some(snapshot) = persistent.log.snapshot() {
+                if next_index <= snapshot.last_included_index {
+                    self.send_snapshot(member, term, snapshot);
+                    leader_state
+                        .next_index
+                        .insert(member.clone(), snapshot.last_included_index + 1);
+                    continue;
+                }
+            }
 
-        for member in &self.config.cluster_members {
-            if member != &self.config.node_id {
-                let request = AppendEntriesRequest::heartbeat(
-                    term,
-                    self.config.node_id.clone(),
-                    commit_index,
+            let prev_log_index = next_index - 1;
+            let prev_log_term = persistent.log.term_at(prev_log_index).unwrap_or(0);
+            let entries = persistent
+                .log
+                .entries_from(next_index)
+                .into_iter()
+                .take(self.config.max_entries_per_message)
+                .collect();
+
+            let mut request = AppendEntriesRequest::new(
+                term,
+                self.config.node_id.clone(),
+                prev_log_index,
+                prev_log_term,
+                entries,
+                commit_index,
+            );
+            request.round = round;
+            self.send(member, RaftMessage::AppendEntriesRequest(request));
+        }
+    }
+
+    /// Send a snapshot to a follower in chunks
+    fn send_snapshot(&self, to: &NodeId, term: Term, snapshot: &Snapshot) {
+        debug!(
+            "Sending snapshot up to {} to {}",
+            snapshot.last_included_index, to
+        );
+
+        let chunk_size = self.config.snapshot_chunk_size.max(1);
+        let mut offset = 0;
+        loop {
+            let end = std::cmp::min(offset + chunk_size, snapshot.data.len());
+            let request = InstallSnapshotRequest {
+                term,
+                leader_id: self.config.node_id.clone(),
+                last_included_index: snapshot.last_included_index,
+                last_included_term: snapshot.last_included_term,
+                offset: offset as u64,
+                data: snapshot.data[offset..end].to_vec(),
+                done: end == snapshot.data.len(),
+                configuration: snapshot.configuration.clone(),
+                learners: snapshot.learners.clone(),
+            };
+            self.send(to, RaftMessage::InstallSnapshotRequest(request));
+
+            if end == snapshot.data.len() {
+                break;
+            }
+            offset = end;
+        }
+    }
+
+    /// Apply the committed entries the state machine hasn't seen, in order,
+    /// and hand each result to the client waiting for it
+    fn apply_committed(&self) {
+        let _applying = self.apply_lock.lock();
+
+        let (commit_index, last_applied) = {
+            let volatile = self.volatile.read();
+            (volatile.commit_index, volatile.last_applied)
+        };
+        if commit_index <= last_applied {
+            return;
+        }
+
+        let entries: Vec<LogEntry> = {
+            let persistent = self.persistent.read();
+            (last_applied + 1..=commit_index)
+                .map_while(|index| persistent.log.get(index).cloned())
+                .collect()
+        };
+
+        for entry in entries {
+            // Leaders append empty no-op entries, and configurations took
+            // effect when they were appended
+            let is_command = entry.kind == EntryKind::Command && !entry.command.is_empty();
+            let result = match &self.state_machine {
+                Some(state_machine) if is_command => {
+                    state_machine.apply(entry.index, &entry.command)
+                }
+                _ => Ok(Vec::new()),
+            };
+            if let Err(e) = &result {
+                debug!(
+                    "Entry {} was rejected by the state machine: {}",
+                    entry.index, e
                 );
-                // TODO: Send heartbeat to member
-                debug!("Would send heartbeat to {}", member);
+            }
+            self.volatile.write().apply_entries(entry.index);
+
+            let waiter = self.waiters.lock().remove(&entry.index);
+            if let Some((term, applied_tx)) = waiter {
+                // Another leader's entry replaced the one proposed here
+                let result = if term == entry.term {
+                    result
+                } else {
+                    Err(RaftError::NotLeader)
+                };
+                let _ = applied_tx.send(result);
+            }
+        }
+
+        let threshold = self.config.snapshot_threshold;
+        if self.state_machine.is_some() && threshold > 0 {
+            let last_applied = self.volatile.read().last_applied;
+            let base_index = self.persistent.read().log.base_index();
+            if last_applied - base_index >= threshold {
+                if let Err(e) = self.snapshot_applied() {
+                    error!("Failed to snapshot the state machine: {}", e);
+                }
             }
         }
     }
 
+    /// Fail the clients waiting for entries at the indexes `matches` selects
+    fn fail_waiters(&self, matches: impl Fn(LogIndex) -> bool) {
+        let mut waiters = self.waiters.lock();
+        let failed: Vec<LogIndex> = waiters.keys().copied().filter(|i| matches(*i)).collect();
+        for index in failed {
+            if let Some((_, applied_tx)) = waiters.remove(&index) {
+                let _ = applied_tx.send(Err(RaftError::NotLeader));
+            }
+        }
+    }
+
+    /// Snapshot the state machine at the last applied entry, with the
+    /// apply lock held
+    fn snapshot_applied(&self) -> RaftResult<Snapshot> {
+        let data = match &self.state_machine {
+            Some(state_machine) => state_machine.snapshot()?,
+            None => Vec::new(),
+        };
+        let last_applied = self.volatile.read().last_applied;
+        self.compact_applied(last_applied, data)
+    }
+
+    /// Run a write to stable storage on a blocking thread, off the message
+    /// loop
+    async fn persist<T: Send + 'static>(
+        &self,
+        write: impl FnOnce(&dyn RaftStorage) -> RaftResult<T> + Send + 'static,
+    ) -> RaftResult<T> {
+        let storage = self.storage.clone();
+        tokio::task::spawn_blocking(move || write(storage.as_ref()))
+            .await
+            .map_err(|e| RaftError::Internal(format!("Storage write panicked: {}", e)))?
+    }
+
+    /// Spawn the task applying committed entries whenever the message loop
+    /// advanced, on a blocking thread so the state machine doesn't stall it
+    fn spawn_applier(self: Arc<Self>) {
+        tokio::spawn(async move {
+            loop {
+                self.apply_ready.notified().await;
+                let node = self.clone();
+                if let Err(e) = tokio::task::spawn_blocking(move || node.apply_committed()).await {
+                    error!("Applying committed entries panicked: {}", e);
+                }
+                self.progress.notify_waiters();
+            }
+        });
+    }
+
     /// Spawn election timer task
     fn spawn_election_timer(self: Arc<Self>) {
         let node = self.clone();
@@ -577,7 +1436,38 @@ impl RaftNode {
     }
 
     /// Submit a command to the Raft cluster
+    ///
+    /// Returns once the command is in the leader's log, before it is
+    /// committed; see [`RaftNode::propose`] to wait for it to be applied.
     pub async fn submit_command(&self, data: Vec<u8>) -> RaftResult<CommandResult> {
+        self.submit(data, None).await
+    }
+
+    /// Replicate a command and wait until it is committed and applied,
+    /// returning the state machine's output
+    ///
+    /// # Errors
+    ///
+    /// Returns `NotLeader` if this node doesn't lead or lost leadership
+    /// before the command committed, `Timeout` if it wasn't applied within
+    /// the request timeout, and the state machine's error if it rejected
+    /// the command
+    pub async fn propose(&self, data: Vec<u8>) -> RaftResult<Vec<u8>> {
+        let (applied_tx, applied_rx) = oneshot::channel();
+        self.submit(data, Some(applied_tx)).await?;
+
+        match timeout(self.request_timeout(), applied_rx).await {
+            Ok(Ok(result)) => result,
+            Ok(Err(_)) => Err(RaftError::Internal("Node stopped".to_string())),
+            Err(_) => Err(RaftError::Timeout),
+        }
+    }
+
+    async fn submit(
+        &self,
+        data: Vec<u8>,
+        applied_tx: Option<AppliedSender>,
+    ) -> RaftResult<CommandResult> {
         let (tx, mut rx) = mpsc::channel(1);
         let command = Command { data };
 
@@ -585,6 +1475,7 @@ impl RaftNode {
             .send(InternalMessage::ClientCommand {
                 command,
                 response_tx: tx,
+                applied_tx,
             })
             .map_err(|_| RaftError::Internal("Node stopped".to_string()))?;
 
@@ -593,6 +1484,276 @@ impl RaftNode {
             .ok_or_else(|| RaftError::Internal("Response channel closed".to_string()))?
     }
 
+    /// Confirm this node still leads the cluster and wait until everything
+    /// committed so far is applied, returning the commit index read at
+    /// the start
+    ///
+    /// Reading the state machine after this returns is linearizable (the
+    /// ReadIndex protocol): the read sees every write acknowledged before
+    /// the call, without appending to the log.
+    ///
+    /// # Errors
+    ///
+    /// Returns `NotLeader` if this node doesn't lead, and `Timeout` if a
+    /// majority didn't confirm its leadership within the request timeout
+    pub async fn read_index(&self) -> RaftResult<LogIndex> {
+        let deadline = Instant::now() + self.request_timeout();
+
+        // The commit index is only known to be up to date once the leader
+        // committed an entry of its own term
+        self.wait_for_term_commit(deadline).await?;
+        let read_index = self.volatile.read().commit_index;
+
+        // A majority acknowledging a round started after the read began
+        // proves no other leader was elected meanwhile
+        let round = match self.leader_state.read().as_ref() {
+            Some(leader_state) => leader_state.round + 1,
+            None => return Err(RaftError::NotLeader),
+        };
+        let _ = self.internal_tx.send(InternalMessage::HeartbeatTimeout);
+        let membership = self.membership();
+        let quorum = membership.quorum();
+        let own_vote = usize::from(membership.is_voter(&self.config.node_id));
+        let followers: Vec<NodeId> = membership
+            .voters
+            .into_iter()
+            .filter(|voter| voter != &self.config.node_id)
+            .collect();
+        self.wait_until(deadline, || {
+            self.check_leader()?;
+            let acknowledged = self.leader_state.read().as_ref().map_or(0, |leader_state| {
+                leader_state.acknowledged(round, &followers)
+            });
+            Ok(acknowledged + own_vote >= quorum)
+        })
+        .await?;
+
+        self.wait_until(deadline, || {
+            Ok(self.volatile.read().last_applied >= read_index)
+        })
+        .await?;
+        Ok(read_index)
+    }
+
+    /// Wait until this leader committed an entry of its own term, before
+    /// which its commit index may lag the cluster's
+    async fn wait_for_term_commit(&self, deadline: Instant) -> RaftResult<()> {
+        self.wait_until(deadline, || {
+            self.check_leader()?;
+            let persistent = self.persistent.read();
+            let commit_index = self.volatile.read().commit_index;
+            Ok(persistent.log.term_at(commit_index) == Some(persistent.current_term))
+        })
+        .await
+    }
+
+    /// Add `node_id` to the cluster as a learner, which receives the log
+    /// but doesn't vote, returning once the change is applied
+    ///
+    /// The new node is started with no cluster members and learns them
+    /// from the log.
+    ///
+    /// # Errors
+    ///
+    /// Returns `NotLeader` if this node doesn't lead, `ConfigError` if
+    /// `node_id` is a member or another change is in progress, and
+    /// `Timeout` if the change wasn't applied within the request timeout
+    pub async fn add_learner(&self, node_id: &str) -> RaftResult<()> {
+        self.change_membership(MembershipChange::AddLearner(node_id.to_string()))
+            .await
+    }
+
+    /// Make the learner `node_id` a voter, once it has caught up with the
+    /// entries committed when this is called
+    ///
+    /// A voter that is far behind would hold back commits until it caught
+    /// up.
+    ///
+    /// # Errors
+    ///
+    /// Returns `NotLeader` if this node doesn't lead, `ConfigError` if
+    /// `node_id` isn't a learner or another change is in progress, and
+    /// `Timeout` if it didn't catch up, or the change wasn't applied,
+    /// within the request timeout
+    pub async fn promote_learner(&self, node_id: &str) -> RaftResult<()> {
+        self.check_leader()?;
+        if !self.membership.read().1.is_learner(node_id) {
+            return Err(RaftError::ConfigError(format!(
+                "{} is not a learner",
+                node_id
+            )));
+        }
+
+        let deadline = Instant::now() + self.request_timeout();
+        let caught_up_to = self.volatile.read().commit_index;
+        let node_id = node_id.to_string();
+        self.wait_until(deadline, || {
+            self.check_leader()?;
+            let matched = self
+                .leader_state
+                .read()
+                .as_ref()
+                .and_then(|leader_state| leader_state.get_match_index(&node_id))
+                .unwrap_or(0);
+            Ok(matched >= caught_up_to)
+        })
+        .await?;
+
+        self.change_membership(MembershipChange::PromoteLearner(node_id))
+            .await
+    }
+
+    /// Remove the voter or learner `node_id` from the cluster, returning
+    /// once the change is applied
+    ///
+    /// A leader that removes itself keeps leading until the change commits,
+    /// then steps down.
+    ///
+    /// # Errors
+    ///
+    /// Returns `NotLeader` if this node doesn't lead, `ConfigError` if
+    /// `node_id` isn't a member, is the last voter, or another change is in
+    /// progress, and `Timeout` if the change wasn't applied within the
+    /// request timeout
+    pub async fn remove_member(&self, node_id: &str) -> RaftResult<()> {
+        self.change_membership(MembershipChange::RemoveMember(node_id.to_string()))
+            .await
+    }
+
+    async fn change_membership(&self, change: MembershipChange) -> RaftResult<()> {
+        let deadline = Instant::now() + self.request_timeout();
+
+        // Until the leader committed an entry of its term, a configuration
+        // from an earlier leader may still be replaced
+        self.wait_for_term_commit(deadline).await?;
+
+        let (tx, mut rx) = mpsc::channel(1);
+        let (applied_tx, applied_rx) = oneshot::channel();
+        self.internal_tx
+            .send(InternalMessage::ChangeMembership {
+                change,
+                response_tx: tx,
+                applied_tx,
+            })
+            .map_err(|_| RaftError::Internal("Node stopped".to_string()))?;
+        rx.recv()
+            .await
+            .ok_or_else(|| RaftError::Internal("Response channel closed".to_string()))??;
+
+        match timeout_at(deadline, applied_rx).await {
+            Ok(Ok(result)) => result.map(|_| ()),
+            Ok(Err(_)) => Err(RaftError::Internal("Node stopped".to_string())),
+            Err(_) => Err(RaftError::Timeout),
+        }
+    }
+
+    /// Hand leadership over to the voter `target`
+    ///
+    /// The leader stops accepting writes, waits until `target` stored its
+    /// whole log, then tells it to start an election, which it wins
+    /// without waiting for an election timeout.
+    ///
+    /// # Errors
+    ///
+    /// Returns `NotLeader` if this node doesn't lead, `ConfigError` if
+    /// `target` isn't a voter, and `Timeout` if leadership didn't move
+    /// within the request timeout
+    pub async fn transfer_leadership(&self, target: &str) -> RaftResult<()> {
+        self.check_leader()?;
+        if target == self.config.node_id {
+            return Ok(());
+        }
+        if !self.membership.read().1.is_voter(target) {
+            return Err(RaftError::ConfigError(format!("{} is not a voter", target)));
+        }
+
+        let target = target.to_string();
+        *self.transfer_target.write() = Some(target.clone());
+        let result = self.hand_over(&target).await;
+        *self.transfer_target.write() = None;
+        result
+    }
+
+    async fn hand_over(&self, target: &NodeId) -> RaftResult<()> {
+        let deadline = Instant::now() + self.request_timeout();
+
+        let _ = self.internal_tx.send(InternalMessage::HeartbeatTimeout);
+        self.wait_until(deadline, || {
+            self.check_leader()?;
+            let last_index = self.persistent.read().log.last_index();
+            let matched = self
+                .leader_state
+                .read()
+                .as_ref()
+                .and_then(|leader_state| leader_state.get_match_index(target))
+                .unwrap_or(0);
+            Ok(matched >= last_index)
+        })
+        .await?;
+
+        let term = self.persistent.read().current_term;
+        info!("Transferring leadership to {}", target);
+        let request = TimeoutNowRequest::new(term, self.config.node_id.clone());
+        self.send(target, RaftMessage::TimeoutNow(request));
+
+        // The target's election in a later term makes this node step down
+        self.wait_until(deadline, || Ok(!self.state.read().is_leader()))
+            .await
+    }
+
+    fn check_leader(&self) -> RaftResult<()> {
+        if self.state.read().is_leader() {
+            Ok(())
+        } else {
+            Err(RaftError::NotLeader)
+        }
+    }
+
+    /// Wait until `done` holds, checking again whenever the node made
+    /// progress
+    async fn wait_until(
+        &self,
+        deadline: Instant,
+        mut done: impl FnMut() -> RaftResult<bool>,
+    ) -> RaftResult<()> {
+        loop {
+            let mut notified = std::pin::pin!(self.progress.notified());
+            notified.as_mut().enable();
+            if done()? {
+                return Ok(());
+            }
+            if timeout_at(deadline, notified).await.is_err() {
+                return Err(RaftError::Timeout);
+            }
+        }
+    }
+
+    fn request_timeout(&self) -> Duration {
+        Duration::from_millis(self.config.request_timeout)
+    }
+
+    /// Members other than this node
+    fn other_members(&self) -> Vec<NodeId> {
+        self.membership
+            .read()
+            .1
+            .members()
+            .filter(|member| *member != &self.config.node_id)
+            .cloned()
+            .collect()
+    }
+
+    /// Get this node's ID
+    pub fn node_id(&self) -> &NodeId {
+        &self.config.node_id
+    }
+
+    /// Get the cluster's members, as of the latest configuration in the
+    /// log, committed or not
+    pub fn membership(&self) -> ClusterConfig {
+        self.membership.read().1.clone()
+    }
+
     /// Get current state
     pub fn current_state(&self) -> RaftState {
         *self.state.read()
@@ -607,6 +1768,84 @@ impl RaftNode {
     pub fn current_leader(&self) -> Option<NodeId> {
         self.current_leader.read().clone()
     }
+
+    /// Get the index of the last log entry
+    pub fn last_log_index(&self) -> LogIndex {
+        self.persistent.read().log.last_index()
+    }
+
+    /// Get the index of the last committed entry
+    pub fn commit_index(&self) -> LogIndex {
+        self.volatile.read().commit_index
+    }
+
+    /// Get the index of the last entry applied to the state machine
+    pub fn last_applied(&self) -> LogIndex {
+        self.volatile.read().last_applied
+    }
+
+    /// Snapshot the state machine at the last applied entry and compact
+    /// the log behind it
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if nothing was applied since the last snapshot, or
+    /// the snapshot can't be taken or stored
+    pub fn snapshot(&self) -> RaftResult<Snapshot> {
+        let _applying = self.apply_lock.lock();
+        self.snapshot_applied()
+    }
+
+    /// Snapshot the state machine up to the applied entry `up_to_index`
+    /// and compact the log behind it
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the entry isn't applied or is already
+    /// compacted, or the snapshot can't be stored
+    pub fn compact(&self, up_to_index: LogIndex, data: Vec<u8>) -> RaftResult<Snapshot> {
+        let _applying = self.apply_lock.lock();
+        self.compact_applied(up_to_index, data)
+    }
+
+    /// Compact the log up to `up_to_index`, with the apply lock held
+    ///
+    /// The lock keeps snapshots from being stored out of order; the log
+    /// itself is only locked to read it and to drop the compacted entries,
+    /// so the message loop isn't held up while the snapshot is stored.
+    fn compact_applied(&self, up_to_index: LogIndex, data: Vec<u8>) -> RaftResult<Snapshot> {
+        if up_to_index > self.volatile.read().last_applied {
+            return Err(RaftError::InvalidLogIndex(up_to_index));
+        }
+
+        let snapshot = {
+            let persistent = self.persistent.read();
+            if up_to_index <= persistent.log.base_index() {
+                return Err(RaftError::InvalidLogIndex(up_to_index));
+            }
+            let term = persistent
+                .log
+                .term_at(up_to_index)
+                .ok_or(RaftError::InvalidLogIndex(up_to_index))?;
+
+            let initial = ClusterConfig::new(self.config.cluster_members.clone());
+            let (_, config) = configuration_at(&persistent.log, up_to_index, &initial);
+
+            Snapshot {
+                last_included_index: up_to_index,
+                last_included_term: term,
+                data,
+                configuration: config.voters,
+                learners: config.learners,
+            }
+        };
+        self.storage.save_snapshot(&snapshot)?;
+        self.persistent
+            .write()
+            .log
+            .install_snapshot(snapshot.clone())?;
+        Ok(snapshot)
+    }
 }
 
 #[cfg(test)]
@@ -628,4 +1867,306 @@ mod tests {
         assert_eq!(node.current_state(), RaftState::Follower);
         assert_eq!(node.current_term(), 0);
     }
+
+    fn open_node(dir: &std::path::Path) -> Arc<RaftNode> {
+        let config = RaftNodeConfig::new(
+            "node1".to_string(),
+            vec![
+                "node1".to_string(),
+                "node2".to_string(),
+                "node3".to_string(),
+            ],
+        );
+        let storage = Arc::new(crate::FileStorage::open(dir).unwrap());
+        Arc::new(RaftNode::with_storage(config, storage).unwrap())
+    }
+
+    fn entries(term: Term, indexes: std::ops::RangeInclusive<LogIndex>) -> Vec<LogEntry> {
+        indexes
+            .map(|index| LogEntry::new(term, index, vec![index as u8]))
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_vote_survives_restart() {
+        let dir = tempfile::tempdir().unwrap();
+        {
+            let node = open_node(dir.path());
+            let request = RequestVoteRequest::new(5, "node2".to_string(), 0, 0);
+            node.handle_rpc_message(
+                "node2".to_string(),
+                RaftMessage::RequestVoteRequest(request),
+            )
+            .await;
+            assert_eq!(node.current_term(), 5);
+        }
+
+        // After a crash the node must not vote twice in the same term
+        let node = open_node(dir.path());
+        assert_eq!(node.current_term(), 5);
+        let response = node
+            .handle_request_vote(RequestVoteRequest::new(5, "node3".to_string(), 0, 0))
+            .await;
+        assert!(!response.vote_granted);
+        let response = node
+            .handle_request_vote(RequestVoteRequest::new(5, "node2".to_string(), 0, 0))
+            .await;
+        assert!(response.vote_granted);
+    }
+
+    #[tokio::test]
+    async fn test_log_survives_restart() {
+        let dir = tempfile::tempdir().unwrap();
+        {
+            let node = open_node(dir.path());
+            let request =
+                AppendEntriesRequest::new(1, "node2".to_string(), 0, 0, entries(1, 1..=3), 0);
+            assert!(node.handle_append_entries(request).await.success);
+
+            // Resending entries the follower already has is a no-op
+            let request =
+                AppendEntriesRequest::new(1, "node2".to_string(), 0, 0, entries(1, 1..=2), 0);
+            assert!(node.handle_append_entries(request).await.success);
+            assert_eq!(node.last_log_index(), 3);
+
+            // A new leader overwrites the conflicting suffix
+            let request =
+                AppendEntriesRequest::new(2, "node3".to_string(), 1, 1, entries(2, 2..=2), 2);
+            node.handle_rpc_message(
+                "node3".to_string(),
+                RaftMessage::AppendEntriesRequest(request),
+            )
+            .await;
+        }
+
+        let node = open_node(dir.path());
+        assert_eq!(node.current_term(), 2);
+        assert_eq!(node.last_log_index(), 2);
+        assert_eq!(node.persistent.read().log.term_at(2), Some(2));
+
+        // Only applied entries can be compacted
+        let request = AppendEntriesRequest::new(2, "node3".to_string(), 2, 2, entries(2, 3..=4), 2);
+        assert!(node.handle_append_entries(request).await.success);
+        node.apply_committed();
+        assert_eq!(node.last_applied(), 2);
+        assert!(node.compact(3, b"state".to_vec()).is_err());
+        let snapshot = node.compact(2, b"state".to_vec()).unwrap();
+        assert_eq!(snapshot.last_included_term, 2);
+        drop(node);
+
+        let node = open_node(dir.path());
+        let persistent = node.persistent.read();
+        assert_eq!(persistent.log.base_index(), 2);
+        assert_eq!(persistent.log.last_index(), 4);
+        assert_eq!(persistent.log.snapshot().unwrap().data, b"state".to_vec());
+        assert_eq!(node.volatile.read().last_applied, 2);
+    }
+
+    /// Storage whose hard state writes fail while `failing` is set
+    #[derive(Default)]
+    struct FlakyStorage {
+        failing: std::sync::atomic::AtomicBool,
+    }
+
+    impl RaftStorage for FlakyStorage {
+        fn load(&self) -> RaftResult<PersistentState> {
+            Ok(PersistentState::new())
+        }
+
+        fn save_hard_state(&self, _term: Term, _voted_for: Option<&NodeId>) -> RaftResult<()> {
+            if self.failing.load(std::sync::atomic::Ordering::SeqCst) {
+                return Err(std::io::Error::other("disk full").into());
+            }
+            Ok(())
+        }
+
+        fn append_entries(&self, _entries: &[LogEntry]) -> RaftResult<()> {
+            Ok(())
+        }
+
+        fn truncate_from(&self, _index: LogIndex) -> RaftResult<()> {
+            Ok(())
+        }
+
+        fn save_snapshot(&self, _snapshot: &Snapshot) -> RaftResult<()> {
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_higher_term_is_ignored_until_durable() {
+        let storage = Arc::new(FlakyStorage::default());
+        let config = RaftNodeConfig::new(
+            "node1".to_string(),
+            vec![
+                "node1".to_string(),
+                "node2".to_string(),
+                "node3".to_string(),
+            ],
+        );
+        let node = Arc::new(RaftNode::with_storage(config, storage.clone()).unwrap());
+        node.handle_rpc_message(
+            "node2".to_string(),
+            RaftMessage::RequestVoteRequest(RequestVoteRequest::new(2, "node2".to_string(), 0, 0)),
+        )
+        .await;
+        assert_eq!(node.current_term(), 2);
+        assert_eq!(node.persistent.read().voted_for.as_deref(), Some("node2"));
+
+        // Neither the new term nor the cleared vote may be acted on
+        storage
+            .failing
+            .store(true, std::sync::atomic::Ordering::SeqCst);
+        node.handle_rpc_message(
+            "node3".to_string(),
+            RaftMessage::RequestVoteRequest(RequestVoteRequest::new(3, "node3".to_string(), 0, 0)),
+        )
+        .await;
+        assert_eq!(node.current_term(), 2);
+        assert_eq!(node.persistent.read().voted_for.as_deref(), Some("node2"));
+
+        storage
+            .failing
+            .store(false, std::sync::atomic::Ordering::SeqCst);
+        node.handle_rpc_message(
+            "node3".to_string(),
+            RaftMessage::RequestVoteRequest(RequestVoteRequest::new(3, "node3".to_string(), 0, 0)),
+        )
+        .await;
+        assert_eq!(node.current_term(), 3);
+        assert_eq!(node.persistent.read().voted_for.as_deref(), Some("node3"));
+    }
+
+    #[tokio::test]
+    async fn test_pre_vote_leaves_term_alone() {
+        let node = Arc::new(RaftNode::new(RaftNodeConfig::new(
+            "node1".to_string(),
+            vec![
+                "node1".to_string(),
+                "node2".to_string(),
+                "node3".to_string(),
+            ],
+        )));
+
+        // Granted while no leader is known, without entering the term
+        let request = RequestVoteRequest::pre_vote(3, "node2".to_string(), 0, 0);
+        node.handle_rpc_message(
+            "node2".to_string(),
+            RaftMessage::RequestVoteRequest(request.clone()),
+        )
+        .await;
+        assert_eq!(node.current_term(), 0);
+        assert!(node.persistent.read().voted_for.is_none());
+        assert!(node.handle_request_vote(request.clone()).await.vote_granted);
+
+        // Denied once the node follows a live leader
+        let heartbeat = AppendEntriesRequest::new(1, "node3".to_string(), 0, 0, Vec::new(), 0);
+        node.handle_rpc_message(
+            "node3".to_string(),
+            RaftMessage::AppendEntriesRequest(heartbeat),
+        )
+        .await;
+        let response = node.handle_request_vote(request).await;
+        assert!(!response.vote_granted);
+        assert_eq!(response.term, 1);
+    }
+
+    #[tokio::test]
+    async fn test_configuration_entries_take_effect_when_appended() {
+        let node = Arc::new(RaftNode::new(RaftNodeConfig::new(
+            "node4".to_string(),
+            Vec::new(),
+        )));
+        assert!(node.membership().voters.is_empty());
+
+        let joined = ClusterConfig {
+            voters: vec!["node1".to_string()],
+            learners: vec!["node4".to_string()],
+        };
+        let entries = vec![
+            LogEntry::new(1, 1, Vec::new()),
+            LogEntry::configuration(1, 2, joined.to_bytes().unwrap()),
+        ];
+        let request = AppendEntriesRequest::new(1, "node1".to_string(), 0, 0, entries, 1);
+        assert!(node.handle_append_entries(request).await.success);
+        assert_eq!(node.membership(), joined);
+
+        // A new leader's log without the configuration rolls it back
+        let entries = vec![LogEntry::new(2, 2, b"cmd".to_vec())];
+        let request = AppendEntriesRequest::new(2, "node1".to_string(), 1, 1, entries, 1);
+        node.handle_rpc_message(
+            "node1".to_string(),
+            RaftMessage::AppendEntriesRequest(request),
+        )
+        .await;
+        assert_eq!(node.last_log_index(), 2);
+        assert!(node.membership().voters.is_empty());
+    }
+
+    /// State machine whose applies block until `released` is set
+    #[derive(Default)]
+    struct BlockingStateMachine {
+        released: std::sync::atomic::AtomicBool,
+    }
+
+    impl StateMachine for BlockingStateMachine {
+        fn apply(&self, _index: LogIndex, command: &[u8]) -> RaftResult<Vec<u8>> {
+            while !self.released.load(std::sync::atomic::Ordering::SeqCst) {
+                std::thread::sleep(Duration::from_millis(5));
+            }
+            Ok(command.to_vec())
+        }
+
+        fn snapshot(&self) -> RaftResult<Vec<u8>> {
+            Ok(Vec::new())
+        }
+
+        fn restore(&self, _data: &[u8]) -> RaftResult<()> {
+            Ok(())
+        }
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_slow_applies_do_not_stall_the_message_loop() {
+        let state_machine = Arc::new(BlockingStateMachine::default());
+        let node = Arc::new(
+            RaftNode::new(RaftNodeConfig::new(
+                "node1".to_string(),
+                vec!["node1".to_string()],
+            ))
+            .with_state_machine(state_machine.clone())
+            .unwrap(),
+        );
+        tokio::spawn(node.clone().start());
+        while !node.current_state().is_leader() {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+
+        let proposal = tokio::spawn({
+            let node = node.clone();
+            async move { node.propose(b"first".to_vec()).await }
+        });
+
+        // The first command follows the leader's no-op
+        while node.commit_index() < 2 {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+
+        // While the first command is being applied, more are accepted
+        let result = timeout(
+            Duration::from_secs(1),
+            node.submit_command(b"second".to_vec()),
+        )
+        .await
+        .unwrap()
+        .unwrap();
+        assert_eq!(result.index, 3);
+        assert_eq!(node.commit_index(), 3);
+        assert_eq!(node.last_applied(), 1);
+
+        state_machine
+            .released
+            .store(true, std::sync::atomic::Ordering::SeqCst);
+        assert_eq!(proposal.await.unwrap().unwrap(), b"first".to_vec());
+    }
 }
@@ -0,0 +1,336 @@
+//! Vector database replicated through Raft
+//!
+//! Writes to a [`ReplicatedVectorDB`] are proposed to the Raft log and
+//! applied in log order to a local [`VectorDB`] on every node, so all
+//! replicas converge on the same points. Reads are served by the local
+//! database, either after the leader confirms it is up to date
+//! ([`ReadConsistency::Linearizable`]) or directly on any node
+//! ([`ReadConsistency::Stale`]).
+
+use crate::{
+    log::Snapshot, node::RaftNode, state_machine::StateMachine, LogIndex, RaftError, RaftResult,
+};
+use ruvector_core::{
+    RuvectorError, ScrollRequest, SearchQuery, SearchResult, VectorDB, VectorEntry, VectorId,
+};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::collections::HashSet;
+use std::sync::Arc;
+use tokio::task::JoinHandle;
+
+/// How up to date a read from a [`ReplicatedVectorDB`] must be
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ReadConsistency {
+    /// See every write acknowledged before the read; only the leader can
+    /// serve it, after a majority confirms it still leads
+    #[default]
+    Linearizable,
+
+    /// Read the local replica as is, on any node; it may lag the leader
+    Stale,
+}
+
+/// A write, as stored in the log
+#[derive(Debug, Serialize, Deserialize)]
+enum VectorCommand {
+    /// Insert or replace points, whose ids were assigned before proposing
+    /// so every replica stores the same ones
+    Upsert(Vec<VectorEntry>),
+
+    /// Delete points by id
+    Delete(Vec<VectorId>),
+}
+
+/// Points read from the database, and restored, at a time
+const SNAPSHOT_PAGE_POINTS: usize = 256;
+
+/// A point in a snapshot
+///
+/// Vectors are kept binary; metadata is JSON, since bincode can't decode
+/// self-describing values.
+#[derive(Serialize, Deserialize)]
+struct SnapshotPoint {
+    id: VectorId,
+    vector: Vec<f32>,
+    metadata: Option<Vec<u8>>,
+}
+
+/// State machine applying replicated writes to a local database
+///
+/// A snapshot is a series of pages of points in id order, each bincode
+/// encoded and prefixed with its length as a big-endian `u32`, so neither
+/// taking nor restoring one decodes more than a page at a time.
+pub struct VectorStateMachine {
+    db: Arc<VectorDB>,
+}
+
+impl VectorStateMachine {
+    /// Apply writes to `db`
+    pub fn new(db: Arc<VectorDB>) -> Self {
+        Self { db }
+    }
+
+    /// Delete the points whose ids `keep` doesn't hold
+    fn delete_except(&self, keep: &HashSet<VectorId>) -> RaftResult<()> {
+        let mut stale = Vec::new();
+        let mut offset = None;
+        loop {
+            let page = self.db.scroll(ScrollRequest {
+                offset,
+                limit: SNAPSHOT_PAGE_POINTS,
+                filter: None,
+                with_vector: false,
+                with_payload: false,
+            })?;
+            stale.extend(
+                page.points
+                    .into_iter()
+                    .map(|record| record.id)
+                    .filter(|id| !keep.contains(id)),
+            );
+            offset = page.next_offset;
+            if offset.is_none() {
+                break;
+            }
+        }
+
+        for ids in stale.chunks(SNAPSHOT_PAGE_POINTS) {
+            let ids: Vec<&str> = ids.iter().map(String::as_str).collect();
+            self.db.delete_batch(&ids)?;
+        }
+        Ok(())
+    }
+}
+
+impl StateMachine for VectorStateMachine {
+    fn apply(&self, _index: LogIndex, command: &[u8]) -> RaftResult<Vec<u8>> {
+        match decode(command)? {
+            VectorCommand::Upsert(entries) => encode(&self.db.upsert_batch(entries)?),
+            VectorCommand::Delete(ids) => {
+                let ids: Vec<&str> = ids.iter().map(String::as_str).collect();
+                encode(&self.db.delete_batch(&ids)?)
+            }
+        }
+    }
+
+    fn snapshot(&self) -> RaftResult<Vec<u8>> {
+        let mut data = Vec::new();
+        let mut offset = None;
+        loop {
+            let page = self.db.scroll(ScrollRequest {
+                offset,
+                limit: SNAPSHOT_PAGE_POINTS,
+                filter: None,
+                with_vector: true,
+                with_payload: true,
+            })?;
+            offset = page.next_offset;
+
+            let points = page
+                .points
+                .into_iter()
+                .map(|record| {
+                    Ok(SnapshotPoint {
+                        id: record.id,
+                        vector: record.vector.unwrap_or_default(),
+                        metadata: record.metadata.as_ref().map(encode).transpose()?,
+                    })
+                })
+                .collect::<RaftResult<Vec<_>>>()?;
+            let page = bincode::serde::encode_to_vec(&points, bincode::config::standard())?;
+            let len = u32::try_from(page.len()).map_err(|_| {
+                RaftError::SnapshotFailed(format!("Page of {} bytes is too large", page.len()))
+            })?;
+            data.extend_from_slice(&len.to_be_bytes());
+            data.extend_from_slice(&page);
+
+            if offset.is_none() {
+                break;
+            }
+        }
+        Ok(data)
+    }
+
+    /// Upsert the snapshot's points a page at a time, then delete the
+    /// points it doesn't hold
+    ///
+    /// Interrupted, the database holds a mix of old and restored points,
+    /// which restoring the same snapshot again replaces.
+    fn restore(&self, data: &[u8]) -> RaftResult<()> {
+        let mut restored = HashSet::new();
+        let mut rest = data;
+        while !rest.is_empty() {
+            let truncated = || RaftError::SnapshotFailed("Snapshot is truncated".to_string());
+            let (len, tail) = rest.split_first_chunk::<4>().ok_or_else(truncated)?;
+            let len = u32::from_be_bytes(*len) as usize;
+            if tail.len() < len {
+                return Err(truncated());
+            }
+            let (page, tail) = tail.split_at(len);
+            rest = tail;
+
+            let (points, _): (Vec<SnapshotPoint>, _) =
+                bincode::serde::decode_from_slice(page, bincode::config::standard())?;
+            let entries = points
+                .into_iter()
+                .map(|point| {
+                    restored.insert(point.id.clone());
+                    Ok(VectorEntry {
+                        id: Some(point.id),
+                        vector: point.vector,
+                        metadata: point.metadata.as_deref().map(decode).transpose()?,
+                    })
+                })
+                .collect::<RaftResult<Vec<_>>>()?;
+            if !entries.is_empty() {
+                self.db.upsert_batch(entries)?;
+            }
+        }
+
+        self.delete_except(&restored)
+    }
+}
+
+/// A [`VectorDB`] kept in sync with its replicas on the other nodes of a
+/// Raft cluster
+///
+/// Writes must go to the leader and return once a majority stored them
+/// and the leader applied them; on other nodes they fail with
+/// [`RaftError::NotLeader`].
+pub struct ReplicatedVectorDB {
+    node: Arc<RaftNode>,
+    db: Arc<VectorDB>,
+}
+
+impl ReplicatedVectorDB {
+    /// Replicate `db` through `node`, which applies committed writes to it
+    ///
+    /// If the node recovered a snapshot from its storage, `db` is replaced
+    /// with the snapshot's points first.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `db` can't be restored from the snapshot
+    pub fn new(node: RaftNode, db: VectorDB) -> RaftResult<Self> {
+        let db = Arc::new(db);
+        let node = node.with_state_machine(Arc::new(VectorStateMachine::new(db.clone())))?;
+        Ok(Self {
+            node: Arc::new(node),
+            db,
+        })
+    }
+
+    /// The Raft node replicating this database
+    pub fn node(&self) -> &Arc<RaftNode> {
+        &self.node
+    }
+
+    /// Start the node's message loop and timers on the current runtime
+    pub fn start(&self) -> JoinHandle<()> {
+        tokio::spawn(self.node.clone().start())
+    }
+
+    /// Insert a point, replacing the point with the same id
+    pub async fn insert(&self, entry: VectorEntry) -> RaftResult<VectorId> {
+        let mut ids = self.upsert_batch(vec![entry]).await?;
+        Ok(ids.remove(0))
+    }
+
+    /// Insert or replace points, assigning ids to those without one
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a vector has the wrong dimensions, this node
+    /// isn't the leader, or the write wasn't applied in time
+    pub async fn upsert_batch(&self, mut entries: Vec<VectorEntry>) -> RaftResult<Vec<VectorId>> {
+        if entries.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        // Rejected here rather than by every replica after it was logged
+        let dimensions = self.db.options().dimensions;
+        for entry in &mut entries {
+            if entry.vector.len() != dimensions {
+                return Err(RuvectorError::DimensionMismatch {
+                    expected: dimensions,
+                    actual: entry.vector.len(),
+                }
+                .into());
+            }
+            entry
+                .id
+                .get_or_insert_with(|| uuid::Uuid::new_v4().to_string());
+        }
+
+        self.propose(&VectorCommand::Upsert(entries)).await
+    }
+
+    /// Delete a point, returning whether it existed
+    pub async fn delete(&self, id: &str) -> RaftResult<bool> {
+        let mut deleted = self.delete_batch(&[id]).await?;
+        Ok(deleted.remove(0))
+    }
+
+    /// Delete points, returning whether each existed
+    pub async fn delete_batch(&self, ids: &[&str]) -> RaftResult<Vec<bool>> {
+        if ids.is_empty() {
+            return Ok(Vec::new());
+        }
+        let ids = ids.iter().map(|id| id.to_string()).collect();
+        self.propose(&VectorCommand::Delete(ids)).await
+    }
+
+    /// Search the local replica
+    pub async fn search(
+        &self,
+        query: SearchQuery,
+        consistency: ReadConsistency,
+    ) -> RaftResult<Vec<SearchResult>> {
+        self.before_read(consistency).await?;
+        Ok(self.db.search(query)?)
+    }
+
+    /// Get a point from the local replica
+    pub async fn get(
+        &self,
+        id: &str,
+        consistency: ReadConsistency,
+    ) -> RaftResult<Option<VectorEntry>> {
+        self.before_read(consistency).await?;
+        Ok(self.db.get(id)?)
+    }
+
+    /// Count the points in the local replica
+    pub async fn count(&self, consistency: ReadConsistency) -> RaftResult<usize> {
+        self.before_read(consistency).await?;
+        Ok(self.db.len()?)
+    }
+
+    /// Snapshot the database at the last applied write and compact the log
+    /// behind it; replicas too far behind receive the snapshot
+    pub fn snapshot(&self) -> RaftResult<Snapshot> {
+        self.node.snapshot()
+    }
+
+    async fn before_read(&self, consistency: ReadConsistency) -> RaftResult<()> {
+        if consistency == ReadConsistency::Linearizable {
+            self.node.read_index().await?;
+        }
+        Ok(())
+    }
+
+    async fn propose<T: DeserializeOwned>(&self, command: &VectorCommand) -> RaftResult<T> {
+        let output = self.node.propose(encode(command)?).await?;
+        decode(&output)
+    }
+}
+
+// JSON, since metadata values are self-describing
+fn encode<T: Serialize>(value: &T) -> RaftResult<Vec<u8>> {
+    serde_json::to_vec(value).map_err(|e| RaftError::Internal(format!("Encoding failed: {}", e)))
+}
+
+fn decode<T: DeserializeOwned>(bytes: &[u8]) -> RaftResult<T> {
+    serde_json::from_slice(bytes)
+        .map_err(|e| RaftError::Internal(format!("Decoding failed: {}", e)))
+}
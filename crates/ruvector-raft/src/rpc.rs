@@ -4,6 +4,7 @@
 //! - AppendEntries (log replication and heartbeat)
 //! - RequestVote (leader election)
 //! - InstallSnapshot (snapshot transfer)
+//! - TimeoutNow (leadership transfer)
 
 use crate::{log::LogEntry, log::Snapshot, LogIndex, NodeId, Term};
 use serde::{Deserialize, Serialize};
@@ -30,6 +31,10 @@ pub struct AppendEntriesRequest {
 
     /// Leader's commitIndex
     pub leader_commit: LogIndex,
+
+    /// Leader's broadcast round, echoed in the response so the leader can
+    /// confirm it still leads before serving a read
+    pub round: u64,
 }
 
 impl AppendEntriesRequest {
@@ -49,6 +54,7 @@ impl AppendEntriesRequest {
             prev_log_term,
             entries,
             leader_commit,
+            round: 0,
         }
     }
 
@@ -61,6 +67,7 @@ impl AppendEntriesRequest {
             prev_log_term: 0,
             entries: Vec::new(),
             leader_commit,
+            round: 0,
         }
     }
 
@@ -99,6 +106,9 @@ pub struct AppendEntriesResponse {
     /// Conflict information for faster log backtracking
     pub conflict_index: Option<LogIndex>,
     pub conflict_term: Option<Term>,
+
+    /// Round of the request this responds to
+    pub round: u64,
 }
 
 impl AppendEntriesResponse {
@@ -110,6 +120,7 @@ impl AppendEntriesResponse {
             match_index: Some(match_index),
             conflict_index: None,
             conflict_term: None,
+            round: 0,
         }
     }
 
@@ -125,6 +136,7 @@ impl AppendEntriesResponse {
             match_index: None,
             conflict_index,
             conflict_term,
+            round: 0,
         }
     }
 
@@ -159,6 +171,10 @@ pub struct RequestVoteRequest {
 
     /// Term of candidate's last log entry
     pub last_log_term: Term,
+
+    /// Whether this is a pre-vote, asking if the candidate could win an
+    /// election in `term` without disrupting the cluster by starting it
+    pub pre_vote: bool,
 }
 
 impl RequestVoteRequest {
@@ -174,6 +190,20 @@ impl RequestVoteRequest {
             candidate_id,
             last_log_index,
             last_log_term,
+            pre_vote: false,
+        }
+    }
+
+    /// Create a pre-vote request for the term the candidate would run in
+    pub fn pre_vote(
+        term: Term,
+        candidate_id: NodeId,
+        last_log_index: LogIndex,
+        last_log_term: Term,
+    ) -> Self {
+        Self {
+            pre_vote: true,
+            ..Self::new(term, candidate_id, last_log_index, last_log_term)
         }
     }
 
@@ -200,6 +230,10 @@ pub struct RequestVoteResponse {
 
     /// True means candidate received vote
     pub vote_granted: bool,
+
+    /// Whether this answers a pre-vote; a granted pre-vote carries the
+    /// term it was requested for
+    pub pre_vote: bool,
 }
 
 impl RequestVoteResponse {
@@ -208,6 +242,7 @@ impl RequestVoteResponse {
         Self {
             term,
             vote_granted: true,
+            pre_vote: false,
         }
     }
 
@@ -216,6 +251,16 @@ impl RequestVoteResponse {
         Self {
             term,
             vote_granted: false,
+            pre_vote: false,
+        }
+    }
+
+    /// Create a pre-vote response
+    pub fn pre_vote(term: Term, vote_granted: bool) -> Self {
+        Self {
+            term,
+            vote_granted,
+            pre_vote: true,
         }
     }
 
@@ -259,6 +304,12 @@ pub struct InstallSnapshotRequest {
 
     /// True if this is the last chunk
     pub done: bool,
+
+    /// Voting members in the snapshot's configuration
+    pub configuration: Vec<NodeId>,
+
+    /// Non-voting members in the snapshot's configuration
+    pub learners: Vec<NodeId>,
 }
 
 impl InstallSnapshotRequest {
@@ -283,6 +334,8 @@ impl InstallSnapshotRequest {
             offset,
             data: chunk,
             done,
+            configuration: snapshot.configuration,
+            learners: snapshot.learners,
         }
     }
 
@@ -348,6 +401,40 @@ impl InstallSnapshotResponse {
     }
 }
 
+/// TimeoutNow RPC request
+///
+/// Sent by a leader handing leadership over to an up-to-date follower,
+/// which starts an election right away
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TimeoutNowRequest {
+    /// Leader's term
+    pub term: Term,
+
+    /// Leader's ID
+    pub leader_id: NodeId,
+}
+
+impl TimeoutNowRequest {
+    /// Create a new TimeoutNow request
+    pub fn new(term: Term, leader_id: NodeId) -> Self {
+        Self { term, leader_id }
+    }
+
+    /// Serialize to bytes
+    pub fn to_bytes(&self) -> Result<Vec<u8>, bincode::error::EncodeError> {
+        use bincode::config;
+        bincode::encode_to_vec(bincode::serde::Compat(self), config::standard())
+    }
+
+    /// Deserialize from bytes
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, bincode::error::DecodeError> {
+        use bincode::config;
+        let (compat, _): (bincode::serde::Compat<Self>, _) =
+            bincode::decode_from_slice(bytes, config::standard())?;
+        Ok(compat.0)
+    }
+}
+
 /// RPC message envelope
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum RaftMessage {
@@ -357,6 +444,7 @@ pub enum RaftMessage {
     RequestVoteResponse(RequestVoteResponse),
     InstallSnapshotRequest(InstallSnapshotRequest),
     InstallSnapshotResponse(InstallSnapshotResponse),
+    TimeoutNow(TimeoutNowRequest),
 }
 
 impl RaftMessage {
@@ -369,6 +457,17 @@ impl RaftMessage {
             RaftMessage::RequestVoteResponse(resp) => resp.term,
             RaftMessage::InstallSnapshotRequest(req) => req.term,
             RaftMessage::InstallSnapshotResponse(resp) => resp.term,
+            RaftMessage::TimeoutNow(req) => req.term,
+        }
+    }
+
+    /// Check if the message is a pre-vote request or a granted pre-vote,
+    /// whose term is one the sender hasn't entered yet
+    pub fn is_pre_vote(&self) -> bool {
+        match self {
+            RaftMessage::RequestVoteRequest(req) => req.pre_vote,
+            RaftMessage::RequestVoteResponse(resp) => resp.pre_vote && resp.vote_granted,
+            _ => false,
         }
     }
 
@@ -439,4 +538,19 @@ mod tests {
         let denied = RequestVoteResponse::denied(1);
         assert!(!denied.vote_granted);
     }
+
+    #[test]
+    fn test_pre_vote_messages() {
+        let req = RequestVoteRequest::pre_vote(3, "candidate".to_string(), 15, 2);
+        let decoded = RequestVoteRequest::from_bytes(&req.to_bytes().unwrap()).unwrap();
+        assert!(decoded.pre_vote);
+        assert!(RaftMessage::RequestVoteRequest(decoded).is_pre_vote());
+
+        // A denied pre-vote carries the voter's real term
+        let denied = RaftMessage::RequestVoteResponse(RequestVoteResponse::pre_vote(4, false));
+        assert!(!denied.is_pre_vote());
+        let granted = RaftMessage::RequestVoteResponse(RequestVoteResponse::pre_vote(3, true));
+        assert!(granted.is_pre_vote());
+        assert!(!RaftMessage::RequestVoteResponse(RequestVoteResponse::granted(3)).is_pre_vote());
+    }
 }
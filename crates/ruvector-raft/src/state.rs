@@ -174,6 +174,12 @@ pub struct LeaderState {
     /// For each server, index of highest log entry known to be replicated
     /// (initialized to 0, increases monotonically)
     pub match_index: HashMap<NodeId, LogIndex>,
+
+    /// Round of the last AppendEntries broadcast to the followers
+    pub round: u64,
+
+    /// For each server, highest broadcast round it acknowledged
+    pub acked_round: HashMap<NodeId, u64>,
 }
 
 impl LeaderState {
@@ -192,6 +198,8 @@ impl LeaderState {
         Self {
             next_index,
             match_index,
+            round: 0,
+            acked_round: HashMap::new(),
         }
     }
 
@@ -224,6 +232,47 @@ impl LeaderState {
         indices.get(mid).copied().unwrap_or(0)
     }
 
+    /// Highest index stored by a majority of `voters`, counting the
+    /// leader's own log as `leader_last_index` when it is one of them
+    pub fn quorum_match_index(
+        &self,
+        voters: &[NodeId],
+        leader_id: &NodeId,
+        leader_last_index: LogIndex,
+    ) -> LogIndex {
+        if voters.is_empty() {
+            return 0;
+        }
+
+        let mut indices: Vec<LogIndex> = voters
+            .iter()
+            .map(|voter| {
+                if voter == leader_id {
+                    leader_last_index
+                } else {
+                    self.get_match_index(voter).unwrap_or(0)
+                }
+            })
+            .collect();
+        indices.sort_unstable_by(|a, b| b.cmp(a));
+        indices[voters.len() / 2]
+    }
+
+    /// Start replicating to a new member from the end of the leader's log
+    pub fn add_member(&mut self, node_id: &NodeId, last_log_index: LogIndex) {
+        self.next_index
+            .entry(node_id.clone())
+            .or_insert(last_log_index + 1);
+        self.match_index.entry(node_id.clone()).or_insert(0);
+    }
+
+    /// Stop replicating to a removed member
+    pub fn remove_member(&mut self, node_id: &NodeId) {
+        self.next_index.remove(node_id);
+        self.match_index.remove(node_id);
+        self.acked_round.remove(node_id);
+    }
+
     /// Get next_index for a specific follower
     pub fn get_next_index(&self, node_id: &NodeId) -> Option<LogIndex> {
         self.next_index.get(node_id).copied()
@@ -233,6 +282,24 @@ impl LeaderState {
     pub fn get_match_index(&self, node_id: &NodeId) -> Option<LogIndex> {
         self.match_index.get(node_id).copied()
     }
+
+    /// Record that a follower acknowledged the broadcast `round`
+    pub fn acknowledge(&mut self, node_id: &NodeId, round: u64) {
+        let acked = self.acked_round.entry(node_id.clone()).or_insert(0);
+        *acked = (*acked).max(round);
+    }
+
+    /// Number of `followers` that acknowledged `round` or a later one
+    pub fn acknowledged(&self, round: u64, followers: &[NodeId]) -> usize {
+        followers
+            .iter()
+            .filter(|follower| {
+                self.acked_round
+                    .get(*follower)
+                    .is_some_and(|acked| *acked >= round)
+            })
+            .count()
+    }
 }
 
 #[cfg(test)]
@@ -314,4 +381,31 @@ mod tests {
         let commit = leader_state.calculate_commit_index();
         assert_eq!(commit, 5); // Median of [3, 5, 8]
     }
+
+    #[test]
+    fn test_quorum_match_index() {
+        let followers = vec![
+            "node2".to_string(),
+            "node3".to_string(),
+            "node4".to_string(),
+        ];
+        let leader = "node1".to_string();
+        let mut leader_state = LeaderState::new(&followers, 10);
+        leader_state.update_replication(&followers[0], 8);
+        leader_state.update_replication(&followers[1], 3);
+        leader_state.update_replication(&followers[2], 10);
+
+        // The learner node4 doesn't count
+        let voters = vec![leader.clone(), followers[0].clone(), followers[1].clone()];
+        assert_eq!(leader_state.quorum_match_index(&voters, &leader, 10), 8);
+
+        // A leader being removed counts only its followers
+        let voters = vec![followers[0].clone(), followers[1].clone()];
+        assert_eq!(leader_state.quorum_match_index(&voters, &leader, 10), 3);
+
+        leader_state.remove_member(&followers[2]);
+        assert_eq!(leader_state.get_match_index(&followers[2]), None);
+        leader_state.add_member(&followers[2], 10);
+        assert_eq!(leader_state.get_next_index(&followers[2]), Some(11));
+    }
 }
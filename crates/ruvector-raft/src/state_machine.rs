@@ -0,0 +1,23 @@
+//! Replicated state machine interface
+//!
+//! Every node applies committed log entries to its [`StateMachine`] in log
+//! order, so nodes that applied the same prefix of the log hold the same
+//! state.
+
+use crate::{LogIndex, RaftResult};
+
+/// State that committed log entries are applied to
+pub trait StateMachine: Send + Sync {
+    /// Apply the command of the committed entry at `index`
+    ///
+    /// Must be deterministic: every node applies the same commands and has
+    /// to end up in the same state, rejected commands included. The returned
+    /// bytes are handed to the client that proposed the command.
+    fn apply(&self, index: LogIndex, command: &[u8]) -> RaftResult<Vec<u8>>;
+
+    /// Serialize the current state for a snapshot
+    fn snapshot(&self) -> RaftResult<Vec<u8>>;
+
+    /// Replace the current state with the one serialized in `data`
+    fn restore(&self, data: &[u8]) -> RaftResult<()>;
+}
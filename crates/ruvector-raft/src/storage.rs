@@ -0,0 +1,503 @@
+//! Stable storage for Raft persistent state
+//!
+//! Raft requires `currentTerm`, `votedFor` and the log to survive restarts,
+//! and to be on stable storage before a node answers an RPC. [`RaftStorage`]
+//! abstracts that storage:
+//! - [`MemoryStorage`] keeps nothing, for tests and single-process use
+//! - [`FileStorage`] keeps everything in a directory and fsyncs every write
+//!
+//! The [`RaftNode`](crate::RaftNode) writes through its storage before
+//! changing its in-memory [`PersistentState`], and recovers that state from
+//! storage on startup.
+
+use crate::{
+    log::{LogEntry, Snapshot},
+    state::PersistentState,
+    LogIndex, NodeId, RaftError, RaftResult, Term,
+};
+use parking_lot::Mutex;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::fs::{self, File, OpenOptions};
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+
+/// Stable storage for a node's term, vote, log and snapshot
+///
+/// Every method must have made its change durable when it returns.
+pub trait RaftStorage: Send + Sync {
+    /// Recover the persistent state, empty if nothing was stored
+    fn load(&self) -> RaftResult<PersistentState>;
+
+    /// Store the current term and vote
+    fn save_hard_state(&self, current_term: Term, voted_for: Option<&NodeId>) -> RaftResult<()>;
+
+    /// Append entries following the last stored entry
+    fn append_entries(&self, entries: &[LogEntry]) -> RaftResult<()>;
+
+    /// Delete stored entries with an index >= `index`
+    fn truncate_from(&self, index: LogIndex) -> RaftResult<()>;
+
+    /// Store a snapshot, compacting away the entries it covers
+    fn save_snapshot(&self, snapshot: &Snapshot) -> RaftResult<()>;
+}
+
+/// Storage that keeps nothing, so a node starts empty on every restart
+#[derive(Debug, Default)]
+pub struct MemoryStorage;
+
+impl RaftStorage for MemoryStorage {
+    fn load(&self) -> RaftResult<PersistentState> {
+        Ok(PersistentState::new())
+    }
+
+    fn save_hard_state(&self, _current_term: Term, _voted_for: Option<&NodeId>) -> RaftResult<()> {
+        Ok(())
+    }
+
+    fn append_entries(&self, _entries: &[LogEntry]) -> RaftResult<()> {
+        Ok(())
+    }
+
+    fn truncate_from(&self, _index: LogIndex) -> RaftResult<()> {
+        Ok(())
+    }
+
+    fn save_snapshot(&self, _snapshot: &Snapshot) -> RaftResult<()> {
+        Ok(())
+    }
+}
+
+const HARD_STATE_FILE: &str = "hard_state";
+const SNAPSHOT_FILE: &str = "snapshot";
+const LOG_FILE: &str = "log";
+
+const MAGIC: &[u8; 8] = b"RVRAFT\0\0";
+const HEADER_LEN: usize = 20;
+const RECORD_HEADER_LEN: usize = 8;
+
+#[derive(Serialize, Deserialize)]
+struct HardState {
+    current_term: Term,
+    voted_for: Option<NodeId>,
+}
+
+/// Storage in a directory of files
+///
+/// - `hard_state` and `snapshot` are replaced atomically by writing a
+///   temporary file and renaming it over the old one
+/// - `log` is append-only; each entry is a record of its length, its CRC-32
+///   and the encoded entry
+///
+/// A crash mid-append leaves a torn record at the end of the log, which is
+/// dropped when the directory is reopened. Entries that were acknowledged
+/// were synced first, so only unacknowledged ones can be lost.
+pub struct FileStorage {
+    dir: PathBuf,
+    log: Mutex<LogFile>,
+}
+
+/// The open log file and where each of its records starts
+struct LogFile {
+    file: File,
+    /// Index of the first entry in the file
+    first_index: LogIndex,
+    /// Byte offset of each record, in index order
+    offsets: Vec<u64>,
+    /// Length of the valid prefix of the file
+    end: u64,
+}
+
+impl FileStorage {
+    /// Open the storage in `dir`, creating the directory if needed
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the directory or log can't be opened, or the log
+    /// is corrupted before its last record
+    pub fn open(dir: impl Into<PathBuf>) -> RaftResult<Self> {
+        let dir = dir.into();
+        fs::create_dir_all(&dir)?;
+
+        let path = dir.join(LOG_FILE);
+        let mut file = OpenOptions::new()
+            .read(true)
+            .append(true)
+            .create(true)
+            .open(&path)?;
+        let mut bytes = Vec::new();
+        file.read_to_end(&mut bytes)?;
+
+        let records = scan_log(&bytes)?;
+        let end = records.last().map_or(0, |(_, offset, len)| offset + len);
+        if end < bytes.len() as u64 {
+            tracing::warn!(
+                "Dropping {} bytes of torn log record in {}",
+                bytes.len() as u64 - end,
+                path.display()
+            );
+            file.set_len(end)?;
+            file.sync_all()?;
+        }
+
+        let log = LogFile {
+            file,
+            first_index: records.first().map_or(0, |(entry, _, _)| entry.index),
+            offsets: records.iter().map(|(_, offset, _)| *offset).collect(),
+            end,
+        };
+        Ok(Self {
+            dir,
+            log: Mutex::new(log),
+        })
+    }
+
+    /// Directory holding the files
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    fn read_file<T: DeserializeOwned>(&self, name: &str) -> RaftResult<Option<T>> {
+        let path = self.dir.join(name);
+        let bytes = match fs::read(&path) {
+            Ok(bytes) => bytes,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+
+        if bytes.len() < HEADER_LEN || &bytes[0..8] != MAGIC {
+            return Err(corrupted(&path, "missing header"));
+        }
+        let checksum = u32::from_le_bytes(bytes[8..12].try_into().unwrap());
+        let len = u64::from_le_bytes(bytes[12..20].try_into().unwrap());
+        let payload = &bytes[HEADER_LEN..];
+        if payload.len() as u64 != len {
+            return Err(corrupted(&path, "length mismatch"));
+        }
+        if crc32fast::hash(payload) != checksum {
+            return Err(corrupted(&path, "checksum mismatch"));
+        }
+        Ok(Some(decode(payload)?))
+    }
+
+    fn write_file<T: Serialize>(&self, name: &str, value: &T) -> RaftResult<()> {
+        let payload = encode(value)?;
+        let mut bytes = Vec::with_capacity(HEADER_LEN + payload.len());
+        bytes.extend_from_slice(MAGIC);
+        bytes.extend_from_slice(&crc32fast::hash(&payload).to_le_bytes());
+        bytes.extend_from_slice(&(payload.len() as u64).to_le_bytes());
+        bytes.extend_from_slice(&payload);
+
+        self.replace_file(name, &bytes)
+    }
+
+    /// Atomically replace the file `name` with `bytes`
+    fn replace_file(&self, name: &str, bytes: &[u8]) -> RaftResult<()> {
+        let path = self.dir.join(name);
+        let tmp_path = self.dir.join(format!("{}.tmp", name));
+        let written = (|| {
+            let mut file = File::create(&tmp_path)?;
+            file.write_all(bytes)?;
+            file.sync_all()?;
+            fs::rename(&tmp_path, &path)?;
+            sync_dir(&self.dir)
+        })();
+        if let Err(e) = written {
+            let _ = fs::remove_file(&tmp_path);
+            return Err(e.into());
+        }
+        Ok(())
+    }
+}
+
+impl RaftStorage for FileStorage {
+    fn load(&self) -> RaftResult<PersistentState> {
+        let mut state = PersistentState::new();
+        if let Some(hard_state) = self.read_file::<HardState>(HARD_STATE_FILE)? {
+            state.current_term = hard_state.current_term;
+            state.voted_for = hard_state.voted_for;
+        }
+        if let Some(snapshot) = self.read_file::<Snapshot>(SNAPSHOT_FILE)? {
+            state.log.install_snapshot(snapshot)?;
+        }
+
+        // The log may still hold entries the snapshot covers if a crash
+        // interrupted compaction
+        let mut bytes = Vec::new();
+        {
+            let log = self.log.lock();
+            let mut file = log.file.try_clone()?;
+            std::io::Seek::rewind(&mut file)?;
+            file.take(log.end).read_to_end(&mut bytes)?;
+        }
+        let base_index = state.log.base_index();
+        let entries = scan_log(&bytes)?
+            .into_iter()
+            .map(|(entry, _, _)| entry)
+            .filter(|entry| entry.index > base_index)
+            .collect();
+        state.log.append_entries(entries)?;
+
+        Ok(state)
+    }
+
+    fn save_hard_state(&self, current_term: Term, voted_for: Option<&NodeId>) -> RaftResult<()> {
+        self.write_file(
+            HARD_STATE_FILE,
+            &HardState {
+                current_term,
+                voted_for: voted_for.cloned(),
+            },
+        )
+    }
+
+    fn append_entries(&self, entries: &[LogEntry]) -> RaftResult<()> {
+        let Some(first) = entries.first() else {
+            return Ok(());
+        };
+
+        let mut log = self.log.lock();
+        let next_index = log.first_index + log.offsets.len() as LogIndex;
+        if log.offsets.is_empty() {
+            log.first_index = first.index;
+        } else if first.index != next_index {
+            return Err(RaftError::InvalidLogIndex(first.index));
+        }
+
+        let mut bytes = Vec::new();
+        let mut offsets = Vec::with_capacity(entries.len());
+        for (i, entry) in entries.iter().enumerate() {
+            if entry.index != first.index + i as LogIndex {
+                return Err(RaftError::LogInconsistency);
+            }
+            offsets.push(log.end + bytes.len() as u64);
+            let payload = encode(entry)?;
+            bytes.extend_from_slice(&(payload.len() as u32).to_le_bytes());
+            bytes.extend_from_slice(&crc32fast::hash(&payload).to_le_bytes());
+            bytes.extend_from_slice(&payload);
+        }
+
+        let written = log
+            .file
+            .write_all(&bytes)
+            .and_then(|()| log.file.sync_data());
+        if let Err(e) = written {
+            // The file is appended to, so bytes left past `end` would sit
+            // between the recorded entries and the next append's
+            let _ = log.file.set_len(log.end);
+            return Err(e.into());
+        }
+        log.end += bytes.len() as u64;
+        log.offsets.extend(offsets);
+        Ok(())
+    }
+
+    fn truncate_from(&self, index: LogIndex) -> RaftResult<()> {
+        let mut log = self.log.lock();
+        let keep = index.saturating_sub(log.first_index) as usize;
+        if keep >= log.offsets.len() {
+            return Ok(());
+        }
+
+        let end = log.offsets[keep];
+        log.file.set_len(end)?;
+        log.file.sync_data()?;
+        log.offsets.truncate(keep);
+        log.end = end;
+        Ok(())
+    }
+
+    fn save_snapshot(&self, snapshot: &Snapshot) -> RaftResult<()> {
+        self.write_file(SNAPSHOT_FILE, snapshot)?;
+
+        // Rewrite the log without the entries the snapshot covers
+        let mut log = self.log.lock();
+        let covered = (snapshot.last_included_index + 1).saturating_sub(log.first_index) as usize;
+        if covered == 0 {
+            return Ok(());
+        }
+        let start = log.offsets.get(covered).copied().unwrap_or(log.end);
+
+        let mut kept = Vec::new();
+        {
+            let mut file = log.file.try_clone()?;
+            std::io::Seek::seek(&mut file, std::io::SeekFrom::Start(start))?;
+            file.take(log.end - start).read_to_end(&mut kept)?;
+        }
+        self.replace_file(LOG_FILE, &kept)?;
+
+        log.file = OpenOptions::new()
+            .read(true)
+            .append(true)
+            .open(self.dir.join(LOG_FILE))?;
+        log.first_index = snapshot.last_included_index + 1;
+        log.offsets = log
+            .offsets
+            .iter()
+            .skip(covered)
+            .map(|offset| offset - start)
+            .collect();
+        log.end -= start;
+        Ok(())
+    }
+}
+
+/// Parse the valid records of a log file into entries with their offset and
+/// length
+///
+/// Parsing stops at a torn or corrupted final record. A corrupted record
+/// followed by more data means the log was damaged, not torn, and fails.
+fn scan_log(bytes: &[u8]) -> RaftResult<Vec<(LogEntry, u64, u64)>> {
+    let mut records = Vec::new();
+    let mut offset = 0usize;
+    while offset < bytes.len() {
+        let Some(header) = bytes.get(offset..offset + RECORD_HEADER_LEN) else {
+            break;
+        };
+        let len = u32::from_le_bytes(header[0..4].try_into().unwrap()) as usize;
+        let checksum = u32::from_le_bytes(header[4..8].try_into().unwrap());
+        let start = offset + RECORD_HEADER_LEN;
+        let Some(payload) = bytes.get(start..start + len) else {
+            break;
+        };
+        if crc32fast::hash(payload) != checksum {
+            if start + len < bytes.len() {
+                return Err(RaftError::CorruptedStorage(format!(
+                    "Log record at offset {} fails its checksum",
+                    offset
+                )));
+            }
+            break;
+        }
+
+        let entry: LogEntry = decode(payload)?;
+        records.push((entry, offset as u64, (RECORD_HEADER_LEN + len) as u64));
+        offset = start + len;
+    }
+    Ok(records)
+}
+
+fn encode<T: Serialize>(value: &T) -> RaftResult<Vec<u8>> {
+    Ok(bincode::encode_to_vec(
+        bincode::serde::Compat(value),
+        bincode::config::standard(),
+    )?)
+}
+
+fn decode<T: DeserializeOwned>(bytes: &[u8]) -> RaftResult<T> {
+    let (compat, _): (bincode::serde::Compat<T>, _) =
+        bincode::decode_from_slice(bytes, bincode::config::standard())?;
+    Ok(compat.0)
+}
+
+/// Make a rename in `dir` durable
+fn sync_dir(dir: &Path) -> std::io::Result<()> {
+    #[cfg(unix)]
+    File::open(dir)?.sync_all()?;
+    #[cfg(not(unix))]
+    let _ = dir;
+    Ok(())
+}
+
+fn corrupted(path: &Path, reason: &str) -> RaftError {
+    RaftError::CorruptedStorage(format!("{}: {}", path.display(), reason))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    fn entries(term: Term, indexes: std::ops::RangeInclusive<LogIndex>) -> Vec<LogEntry> {
+        indexes
+            .map(|index| LogEntry::new(term, index, format!("cmd{}", index).into_bytes()))
+            .collect()
+    }
+
+    #[test]
+    fn test_hard_state_and_log_survive_reopen() -> RaftResult<()> {
+        let dir = tempdir()?;
+        {
+            let storage = FileStorage::open(dir.path())?;
+            storage.save_hard_state(3, Some(&"node2".to_string()))?;
+            storage.append_entries(&entries(1, 1..=3))?;
+            storage.append_entries(&entries(2, 4..=5))?;
+            storage.truncate_from(4)?;
+            storage.append_entries(&entries(3, 4..=4))?;
+        }
+
+        let state = FileStorage::open(dir.path())?.load()?;
+        assert_eq!(state.current_term, 3);
+        assert_eq!(state.voted_for.as_deref(), Some("node2"));
+        assert_eq!(state.log.last_index(), 4);
+        assert_eq!(state.log.last_term(), 3);
+        assert_eq!(state.log.get(2).unwrap().command, b"cmd2");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_snapshot_compacts_log() -> RaftResult<()> {
+        let dir = tempdir()?;
+        {
+            let storage = FileStorage::open(dir.path())?;
+            storage.append_entries(&entries(1, 1..=5))?;
+            storage.save_snapshot(&Snapshot {
+                last_included_index: 3,
+                last_included_term: 1,
+                data: b"state".to_vec(),
+                configuration: vec!["node1".to_string()],
+                learners: Vec::new(),
+            })?;
+            storage.append_entries(&entries(2, 6..=6))?;
+            storage.truncate_from(6)?;
+        }
+
+        let state = FileStorage::open(dir.path())?.load()?;
+        assert_eq!(state.log.base_index(), 3);
+        assert_eq!(state.log.snapshot().unwrap().data, b"state");
+        assert_eq!(state.log.len(), 2);
+        assert_eq!(state.log.last_index(), 5);
+        assert_eq!(
+            fs::metadata(dir.path().join(LOG_FILE))?.len(),
+            encoded_len(&entries(1, 4..=5))
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_torn_record_is_dropped_on_open() -> RaftResult<()> {
+        let dir = tempdir()?;
+        {
+            let storage = FileStorage::open(dir.path())?;
+            storage.append_entries(&entries(1, 1..=3))?;
+        }
+
+        // Simulate a crash partway through appending entry 4
+        let mut record = Vec::new();
+        let payload = encode(&entries(1, 4..=4)[0])?;
+        record.extend_from_slice(&(payload.len() as u32).to_le_bytes());
+        record.extend_from_slice(&crc32fast::hash(&payload).to_le_bytes());
+        record.extend_from_slice(&payload[..payload.len() / 2]);
+        OpenOptions::new()
+            .append(true)
+            .open(dir.path().join(LOG_FILE))?
+            .write_all(&record)?;
+
+        let storage = FileStorage::open(dir.path())?;
+        assert_eq!(storage.load()?.log.last_index(), 3);
+        storage.append_entries(&entries(2, 4..=4))?;
+        let state = FileStorage::open(dir.path())?.load()?;
+        assert_eq!(state.log.last_index(), 4);
+        assert_eq!(state.log.last_term(), 2);
+
+        Ok(())
+    }
+
+    fn encoded_len(entries: &[LogEntry]) -> u64 {
+        entries
+            .iter()
+            .map(|entry| (RECORD_HEADER_LEN + encode(entry).unwrap().len()) as u64)
+            .sum()
+    }
+}
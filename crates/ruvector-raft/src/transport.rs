@@ -0,0 +1,409 @@
+//! Delivery of Raft messages between nodes
+//!
+//! A [`RaftNode`] hands every outgoing request and response to its
+//! [`RaftTransport`], and the transport feeds them to the receiving node's
+//! [`RaftNode::receive`]. Delivery is best effort: Raft retries on its own,
+//! so a transport may drop messages to unreachable nodes.
+//!
+//! Over TCP, a connection opens with a frame holding the connecting node's
+//! id, followed by messages encoded with [`RaftMessage::to_bytes`], each
+//! frame prefixed with its length as a big-endian `u32`. Messages flow both
+//! ways over a connection, so a node answers a request over the connection
+//! it arrived on, even if it doesn't know the requester's address.
+
+use crate::{node::RaftNode, rpc::RaftMessage, NodeId, RaftResult};
+use dashmap::{mapref::entry::Entry, DashMap, DashSet};
+use parking_lot::RwLock;
+use std::io;
+use std::net::SocketAddr;
+use std::sync::{Arc, Weak};
+use std::time::Duration;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::net::tcp::{OwnedReadHalf, OwnedWriteHalf};
+use tokio::net::{TcpListener, TcpStream, ToSocketAddrs};
+use tokio::sync::{mpsc, watch};
+use tokio::task::JoinHandle;
+use tracing::{debug, trace, warn};
+
+/// Largest frame accepted over TCP
+pub const MAX_FRAME_LEN: usize = 64 * 1024 * 1024;
+
+/// Messages queued for a peer before further ones are dropped
+const QUEUE_LEN: usize = 1024;
+
+/// How long connecting to a peer may take
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Sends Raft messages to other nodes
+pub trait RaftTransport: Send + Sync {
+    /// Deliver `message` from node `from` to node `to`
+    fn send(&self, from: &NodeId, to: &NodeId, message: RaftMessage);
+}
+
+/// Transport between nodes in the same process
+///
+/// Nodes are registered by id; messages to a disconnected node, or from
+/// one, are dropped, which simulates a network partition.
+#[derive(Clone, Default)]
+pub struct LocalTransport {
+    nodes: Arc<DashMap<NodeId, Weak<RaftNode>>>,
+    disconnected: Arc<DashSet<NodeId>>,
+}
+
+impl LocalTransport {
+    /// Create a transport with no nodes
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Route messages addressed to `node`'s id to it
+    pub fn register(&self, node: &Arc<RaftNode>) {
+        self.nodes
+            .insert(node.node_id().clone(), Arc::downgrade(node));
+    }
+
+    /// Drop all messages to and from `node_id`
+    pub fn disconnect(&self, node_id: &str) {
+        self.disconnected.insert(node_id.to_string());
+    }
+
+    /// Deliver messages to and from `node_id` again
+    pub fn reconnect(&self, node_id: &str) {
+        self.disconnected.remove(node_id);
+    }
+}
+
+impl RaftTransport for LocalTransport {
+    fn send(&self, from: &NodeId, to: &NodeId, message: RaftMessage) {
+        if self.disconnected.contains(from) || self.disconnected.contains(to) {
+            trace!(
+                "Dropping message from {} to {} across a partition",
+                from,
+                to
+            );
+            return;
+        }
+
+        let node = self.nodes.get(to).and_then(|node| node.upgrade());
+        match node {
+            Some(node) => node.receive(from.clone(), message),
+            None => trace!("Dropping message to unknown node {}", to),
+        }
+    }
+}
+
+/// Transport between nodes over TCP
+///
+/// Each node binds its own transport, registers itself with it and adds
+/// the addresses of its peers. One connection is kept per peer, opened on
+/// the first message to it and reopened after a failure; messages are
+/// queued per peer and dropped while it can't be reached.
+pub struct TcpTransport {
+    shared: Arc<TcpShared>,
+    local_addr: SocketAddr,
+    accept: JoinHandle<()>,
+}
+
+/// State shared with the transport's connection tasks
+struct TcpShared {
+    node: RwLock<Weak<RaftNode>>,
+    peers: DashMap<NodeId, String>,
+    /// Queues of the open connections, by the peer at the other end
+    routes: DashMap<NodeId, mpsc::Sender<Vec<u8>>>,
+    disconnected: DashSet<NodeId>,
+    shutdown: watch::Sender<bool>,
+}
+
+impl TcpTransport {
+    /// Listen on `addr` for connections from the other nodes
+    ///
+    /// Must be called within a Tokio runtime, which runs the connections.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `addr` can't be bound
+    pub async fn bind(addr: impl ToSocketAddrs) -> RaftResult<Self> {
+        let listener = TcpListener::bind(addr).await?;
+        let local_addr = listener.local_addr()?;
+        let shared = Arc::new(TcpShared {
+            node: RwLock::new(Weak::new()),
+            peers: DashMap::new(),
+            routes: DashMap::new(),
+            disconnected: DashSet::new(),
+            shutdown: watch::Sender::new(false),
+        });
+        let accept = tokio::spawn(accept(shared.clone(), listener));
+        Ok(Self {
+            shared,
+            local_addr,
+            accept,
+        })
+    }
+
+    /// The address the transport listens on
+    pub fn local_addr(&self) -> SocketAddr {
+        self.local_addr
+    }
+
+    /// Deliver the messages that arrive to `node`
+    pub fn register(&self, node: &Arc<RaftNode>) {
+        *self.shared.node.write() = Arc::downgrade(node);
+    }
+
+    /// Connect to `node_id` at `address` when there is a message for it
+    pub fn add_peer(&self, node_id: &str, address: impl Into<String>) {
+        self.shared
+            .peers
+            .insert(node_id.to_string(), address.into());
+    }
+
+    /// Close the connection to `node_id` and drop all messages to and
+    /// from it
+    pub fn disconnect(&self, node_id: &str) {
+        self.shared.disconnected.insert(node_id.to_string());
+        self.shared.routes.remove(node_id);
+    }
+
+    /// Deliver messages to and from `node_id` again
+    pub fn reconnect(&self, node_id: &str) {
+        self.shared.disconnected.remove(node_id);
+    }
+
+    /// The queue of the connection to `to`, connecting from `from` if
+    /// there is none
+    fn route(&self, from: &NodeId, to: &NodeId) -> Option<mpsc::Sender<Vec<u8>>> {
+        match self.shared.routes.entry(to.clone()) {
+            Entry::Occupied(route) if !route.get().is_closed() => Some(route.get().clone()),
+            entry => {
+                let Some(address) = self.shared.peers.get(to).map(|address| address.clone()) else {
+                    trace!("Dropping message to {} with no known address", to);
+                    return None;
+                };
+                let (tx, rx) = mpsc::channel(QUEUE_LEN);
+                entry.insert(tx.clone());
+                tokio::spawn(connect(
+                    self.shared.clone(),
+                    from.clone(),
+                    to.clone(),
+                    address,
+                    rx,
+                ));
+                Some(tx)
+            }
+        }
+    }
+}
+
+impl Drop for TcpTransport {
+    fn drop(&mut self) {
+        self.accept.abort();
+        self.shared.shutdown.send_replace(true);
+    }
+}
+
+impl RaftTransport for TcpTransport {
+    fn send(&self, from: &NodeId, to: &NodeId, message: RaftMessage) {
+        if self.shared.disconnected.contains(to) {
+            trace!("Dropping message to disconnected node {}", to);
+            return;
+        }
+
+        let frame = match message.to_bytes() {
+            Ok(frame) if frame.len() <= MAX_FRAME_LEN => frame,
+            Ok(frame) => {
+                warn!("Dropping message of {} bytes to {}", frame.len(), to);
+                return;
+            }
+            Err(e) => {
+                warn!("Failed to encode message to {}: {}", to, e);
+                return;
+            }
+        };
+
+        if let Some(route) = self.route(from, to) {
+            if route.try_send(frame).is_err() {
+                trace!("Dropping message to {}, its queue is full", to);
+            }
+        }
+    }
+}
+
+/// Accept connections from other nodes until the transport is dropped
+async fn accept(shared: Arc<TcpShared>, listener: TcpListener) {
+    loop {
+        let (stream, peer_addr) = match listener.accept().await {
+            Ok(accepted) => accepted,
+            Err(e) => {
+                warn!("Failed to accept Raft connection: {}", e);
+                tokio::time::sleep(Duration::from_millis(100)).await;
+                continue;
+            }
+        };
+        let shared = shared.clone();
+        tokio::spawn(async move {
+            if let Err(e) = serve(&shared, stream).await {
+                debug!("Raft connection from {} closed: {}", peer_addr, e);
+            }
+        });
+    }
+}
+
+/// Serve a connection another node opened
+async fn serve(shared: &TcpShared, stream: TcpStream) -> io::Result<()> {
+    stream.set_nodelay(true)?;
+    let (mut reader, writer) = stream.into_split();
+    let hello = read_frame(&mut reader).await?;
+    let peer =
+        String::from_utf8(hello).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    if shared.disconnected.contains(&peer) {
+        return Ok(());
+    }
+
+    // Messages to the peer, responses included, go over this connection
+    // unless one to it is already open
+    let outgoing = match shared.routes.entry(peer.clone()) {
+        Entry::Occupied(route) if !route.get().is_closed() => None,
+        entry => {
+            let (tx, rx) = mpsc::channel(QUEUE_LEN);
+            entry.insert(tx);
+            Some(rx)
+        }
+    };
+    run_connection(shared, &peer, reader, writer, outgoing).await
+}
+
+/// Open a connection to `to` at `address` and send it the messages queued
+/// on `outgoing`
+async fn connect(
+    shared: Arc<TcpShared>,
+    from: NodeId,
+    to: NodeId,
+    address: String,
+    outgoing: mpsc::Receiver<Vec<u8>>,
+) {
+    let result = async {
+        let stream = tokio::time::timeout(CONNECT_TIMEOUT, TcpStream::connect(&address))
+            .await
+            .map_err(|_| io::Error::new(io::ErrorKind::TimedOut, "connection timed out"))??;
+        stream.set_nodelay(true)?;
+        let (reader, mut writer) = stream.into_split();
+        write_frame(&mut writer, from.as_bytes()).await?;
+        run_connection(&shared, &to, reader, writer, Some(outgoing)).await
+    }
+    .await;
+
+    if let Err(e) = result {
+        debug!("Raft connection to {} at {} closed: {}", to, address, e);
+        shared.routes.remove_if(&to, |_, route| route.is_closed());
+    }
+}
+
+/// Deliver the messages `peer` sends over a connection, and send it those
+/// queued on `outgoing`, until either side closes it
+async fn run_connection(
+    shared: &TcpShared,
+    peer: &NodeId,
+    mut reader: OwnedReadHalf,
+    mut writer: OwnedWriteHalf,
+    outgoing: Option<mpsc::Receiver<Vec<u8>>>,
+) -> io::Result<()> {
+    let mut shutdown = shared.shutdown.subscribe();
+
+    let read = async {
+        loop {
+            let frame = read_frame(&mut reader).await?;
+            if shared.disconnected.contains(peer) {
+                return Ok(());
+            }
+            let message = RaftMessage::from_bytes(&frame)
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+            match shared.node.read().upgrade() {
+                Some(node) => node.receive(peer.clone(), message),
+                None => trace!("Dropping message from {} with no node registered", peer),
+            }
+        }
+    };
+    let write = async {
+        let Some(mut outgoing) = outgoing else {
+            // Messages to the peer go over another connection
+            return std::future::pending().await;
+        };
+        while let Some(frame) = outgoing.recv().await {
+            write_frame(&mut writer, &frame).await?;
+        }
+        Ok(())
+    };
+
+    let result = tokio::select! {
+        result = read => result,
+        result = write => result,
+        _ = shutdown.wait_for(|stopped| *stopped) => Ok(()),
+    };
+    // The queue was dropped with the writer, so a new connection replaces it
+    shared.routes.remove_if(peer, |_, route| route.is_closed());
+    result
+}
+
+async fn write_frame<W: AsyncWrite + Unpin>(writer: &mut W, frame: &[u8]) -> io::Result<()> {
+    let len = u32::try_from(frame.len())
+        .ok()
+        .filter(|len| *len as usize <= MAX_FRAME_LEN)
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "frame is too large"))?;
+    writer.write_u32(len).await?;
+    writer.write_all(frame).await?;
+    writer.flush().await
+}
+
+async fn read_frame<R: AsyncRead + Unpin>(reader: &mut R) -> io::Result<Vec<u8>> {
+    let len = reader.read_u32().await? as usize;
+    if len > MAX_FRAME_LEN {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("frame of {} bytes is too large", len),
+        ));
+    }
+    let mut frame = vec![0; len];
+    reader.read_exact(&mut frame).await?;
+    Ok(frame)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rpc::AppendEntriesRequest;
+
+    #[tokio::test]
+    async fn test_frames_round_trip() {
+        let message = RaftMessage::AppendEntriesRequest(AppendEntriesRequest::heartbeat(
+            3,
+            "node1".to_string(),
+            7,
+        ));
+
+        let mut buffer = Vec::new();
+        write_frame(&mut buffer, &message.to_bytes().unwrap())
+            .await
+            .unwrap();
+        assert_eq!(
+            u32::from_be_bytes(buffer[..4].try_into().unwrap()) as usize,
+            buffer.len() - 4
+        );
+
+        let frame = read_frame(&mut buffer.as_slice()).await.unwrap();
+        match RaftMessage::from_bytes(&frame).unwrap() {
+            RaftMessage::AppendEntriesRequest(req) => {
+                assert_eq!(req.term, 3);
+                assert_eq!(req.leader_commit, 7);
+            }
+            other => panic!("Unexpected message: {:?}", other),
+        }
+
+        // A truncated frame is an error, not a short read
+        buffer.truncate(buffer.len() - 1);
+        assert!(read_frame(&mut buffer.as_slice()).await.is_err());
+
+        // Oversized frames are refused before their body is read
+        let oversized = (MAX_FRAME_LEN as u32 + 1).to_be_bytes();
+        assert!(read_frame(&mut oversized.as_slice()).await.is_err());
+    }
+}
@@ -0,0 +1,135 @@
+//! Cluster harness shared by the integration tests
+//!
+//! Runs replicas of a 2-dimensional vector database in one process, kept
+//! in sync through Raft over the in-process transport.
+
+#![allow(dead_code)]
+
+use parking_lot::Mutex;
+use ruvector_core::types::DbOptions;
+use ruvector_core::{DistanceMetric, SearchQuery, VectorDB, VectorEntry};
+use ruvector_raft::{LocalTransport, RaftNode, RaftNodeConfig, ReplicatedVectorDB};
+use std::sync::Arc;
+use std::time::Duration;
+use tempfile::TempDir;
+use tokio::time::sleep;
+
+pub const NODES: [&str; 3] = ["node1", "node2", "node3"];
+
+pub struct Cluster {
+    pub transport: LocalTransport,
+    dir: TempDir,
+    replicas: Mutex<Vec<Arc<ReplicatedVectorDB>>>,
+}
+
+impl Cluster {
+    /// Start a cluster of the three `NODES`
+    pub fn start() -> Self {
+        let cluster = Self {
+            transport: LocalTransport::new(),
+            dir: tempfile::tempdir().unwrap(),
+            replicas: Mutex::new(Vec::new()),
+        };
+        let members: Vec<String> = NODES.iter().map(|id| id.to_string()).collect();
+        for id in NODES {
+            cluster.spawn(id, members.clone());
+        }
+        cluster
+    }
+
+    /// Start a node with no members, to be added to the cluster
+    pub fn join(&self, id: &str) -> Arc<ReplicatedVectorDB> {
+        self.spawn(id, Vec::new())
+    }
+
+    fn spawn(&self, id: &str, members: Vec<String>) -> Arc<ReplicatedVectorDB> {
+        let mut config = RaftNodeConfig::new(id.to_string(), members);
+        config.snapshot_threshold = 0;
+        config.request_timeout = 1_000;
+        let node = RaftNode::new(config).with_transport(Arc::new(self.transport.clone()));
+
+        let dir = self.dir.path().join(id);
+        std::fs::create_dir_all(&dir).unwrap();
+        let db = VectorDB::new(DbOptions {
+            dimensions: 2,
+            distance_metric: DistanceMetric::Euclidean,
+            storage_path: dir.join("vectors.db").to_string_lossy().into_owned(),
+            quantization: None,
+            ..DbOptions::default()
+        })
+        .unwrap();
+
+        let replica = Arc::new(ReplicatedVectorDB::new(node, db).unwrap());
+        self.transport.register(replica.node());
+        replica.start();
+        self.replicas.lock().push(replica.clone());
+        replica
+    }
+
+    /// Every node started, members or not
+    pub fn replicas(&self) -> Vec<Arc<ReplicatedVectorDB>> {
+        self.replicas.lock().clone()
+    }
+
+    pub fn replica(&self, id: &str) -> Arc<ReplicatedVectorDB> {
+        self.replicas()
+            .into_iter()
+            .find(|replica| replica.node().node_id() == id)
+            .unwrap()
+    }
+
+    /// Wait for a leader among the replicas other than `excluded`
+    pub async fn leader(&self, excluded: Option<&str>) -> Arc<ReplicatedVectorDB> {
+        for _ in 0..100 {
+            let leader = self
+                .replicas()
+                .into_iter()
+                .filter(|replica| Some(replica.node().node_id().as_str()) != excluded)
+                .filter(|replica| replica.node().current_state().is_leader())
+                .max_by_key(|replica| replica.node().current_term());
+            if let Some(leader) = leader {
+                return leader;
+            }
+            sleep(Duration::from_millis(50)).await;
+        }
+        panic!("no leader was elected");
+    }
+
+    /// Wait until the leader's members other than `excluded` applied its
+    /// whole log
+    pub async fn converge(&self, excluded: Option<&str>) {
+        for _ in 0..100 {
+            let leader = self.leader(excluded).await;
+            let last_index = leader.node().last_log_index();
+            let membership = leader.node().membership();
+            let converged = self
+                .replicas()
+                .iter()
+                .filter(|replica| Some(replica.node().node_id().as_str()) != excluded)
+                .filter(|replica| membership.contains(replica.node().node_id()))
+                .all(|replica| replica.node().last_applied() == last_index);
+            if converged {
+                return;
+            }
+            sleep(Duration::from_millis(50)).await;
+        }
+        panic!("replicas didn't converge");
+    }
+}
+
+pub fn entry(id: &str, vector: [f32; 2]) -> VectorEntry {
+    VectorEntry {
+        id: Some(id.to_string()),
+        vector: vector.to_vec(),
+        metadata: None,
+    }
+}
+
+pub fn query(vector: [f32; 2]) -> SearchQuery {
+    SearchQuery {
+        vector: vector.to_vec(),
+        k: 1,
+        filter: None,
+        ef_search: None,
+    }
+}
@@ -0,0 +1,167 @@
+//! Membership changes, leadership transfer and pre-votes on a running
+//! cluster
+
+mod common;
+
+use common::{entry, Cluster};
+use ruvector_raft::{RaftError, ReadConsistency};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::time::sleep;
+
+/// Insert points through whichever node leads until `stop` is set,
+/// returning the ids of those acknowledged
+async fn write_load(cluster: Arc<Cluster>, stop: Arc<AtomicBool>) -> Vec<String> {
+    let mut written = Vec::new();
+    while !stop.load(Ordering::Relaxed) {
+        let id = format!("w{}", written.len());
+        let leader = cluster.leader(None).await;
+        match leader.insert(entry(&id, [written.len() as f32, 0.0])).await {
+            Ok(_) => written.push(id),
+            // Leadership is moving; retry the same point
+            Err(_) => sleep(Duration::from_millis(20)).await,
+        }
+    }
+    written
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_nodes_join_and_leave_under_write_load() {
+    let cluster = Arc::new(Cluster::start());
+    let leader = cluster.leader(None).await;
+    leader.insert(entry("a", [0.0, 0.0])).await.unwrap();
+    leader.snapshot().unwrap();
+
+    let stop = Arc::new(AtomicBool::new(false));
+    let writer = tokio::spawn(write_load(cluster.clone(), stop.clone()));
+
+    // The new node catches up as a learner from the snapshot and the log
+    let joined = cluster.join("node4");
+    let leader = cluster.leader(None).await;
+    leader.node().add_learner("node4").await.unwrap();
+    let err = leader.node().add_learner("node4").await.unwrap_err();
+    assert!(matches!(err, RaftError::ConfigError(_)));
+    assert!(leader.node().membership().is_learner("node4"));
+    assert_eq!(leader.node().membership().quorum(), 2);
+
+    leader.node().promote_learner("node4").await.unwrap();
+    assert_eq!(leader.node().membership().voters.len(), 4);
+    assert_eq!(leader.node().membership().quorum(), 3);
+
+    // The leader removes itself, and a remaining voter takes over
+    let old_id = leader.node().node_id().clone();
+    leader.node().remove_member(&old_id).await.unwrap();
+    let new_leader = cluster.leader(Some(&old_id)).await;
+    let membership = new_leader.node().membership();
+    assert_eq!(membership.voters.len(), 3);
+    assert!(!membership.contains(&old_id));
+
+    sleep(Duration::from_millis(200)).await;
+    stop.store(true, Ordering::Relaxed);
+    let written = writer.await.unwrap();
+    assert!(!written.is_empty());
+
+    // Every acknowledged write reached every remaining member
+    cluster.converge(Some(&old_id)).await;
+    let new_leader = cluster.leader(Some(&old_id)).await;
+    // A write that timed out while leadership moved may still have landed
+    let count = new_leader
+        .count(ReadConsistency::Linearizable)
+        .await
+        .unwrap();
+    assert!(count > written.len());
+    for replica in cluster.replicas() {
+        if replica.node().node_id() == &old_id {
+            continue;
+        }
+        for id in &written {
+            assert!(replica
+                .get(id, ReadConsistency::Stale)
+                .await
+                .unwrap()
+                .is_some());
+        }
+    }
+    assert!(joined.node().membership().is_voter("node4"));
+
+    // The removed node no longer campaigns
+    let term = new_leader.node().current_term();
+    sleep(Duration::from_millis(600)).await;
+    assert!(!cluster.replica(&old_id).node().current_state().is_leader());
+    assert!(new_leader.node().current_state().is_leader());
+    assert_eq!(new_leader.node().current_term(), term);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_leadership_transfer() {
+    let cluster = Cluster::start();
+    let leader = cluster.leader(None).await;
+    leader.insert(entry("a", [0.0, 0.0])).await.unwrap();
+
+    let err = leader
+        .node()
+        .transfer_leadership("node9")
+        .await
+        .unwrap_err();
+    assert!(matches!(err, RaftError::ConfigError(_)));
+
+    let target = cluster
+        .replicas()
+        .iter()
+        .find(|replica| !replica.node().current_state().is_leader())
+        .unwrap()
+        .node()
+        .node_id()
+        .clone();
+    leader.node().transfer_leadership(&target).await.unwrap();
+    assert!(!leader.node().current_state().is_leader());
+
+    let new_leader = cluster.leader(None).await;
+    assert_eq!(new_leader.node().node_id(), &target);
+    new_leader.insert(entry("b", [5.0, 5.0])).await.unwrap();
+    assert_eq!(
+        new_leader
+            .count(ReadConsistency::Linearizable)
+            .await
+            .unwrap(),
+        2
+    );
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_partitioned_follower_does_not_disrupt_leader() {
+    let cluster = Cluster::start();
+    let leader = cluster.leader(None).await;
+    leader.insert(entry("a", [0.0, 0.0])).await.unwrap();
+    let term = leader.node().current_term();
+
+    let follower = cluster
+        .replicas()
+        .iter()
+        .find(|replica| !replica.node().current_state().is_leader())
+        .unwrap()
+        .node()
+        .node_id()
+        .clone();
+
+    // Cut off, its pre-votes fail, so it never enters a new term
+    cluster.transport.disconnect(&follower);
+    sleep(Duration::from_millis(1_000)).await;
+    assert_eq!(cluster.replica(&follower).node().current_term(), term);
+
+    // Back in the cluster it follows the same leader
+    cluster.transport.reconnect(&follower);
+    leader.insert(entry("b", [5.0, 5.0])).await.unwrap();
+    cluster.converge(None).await;
+    assert!(leader.node().current_state().is_leader());
+    assert_eq!(leader.node().current_term(), term);
+    assert_eq!(
+        cluster
+            .replica(&follower)
+            .count(ReadConsistency::Stale)
+            .await
+            .unwrap(),
+        2
+    );
+}
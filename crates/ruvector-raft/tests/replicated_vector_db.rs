@@ -0,0 +1,223 @@
+//! Three replicas of a vector database kept in sync through Raft, over the
+//! in-process transport
+
+mod common;
+
+use common::{entry, query, Cluster};
+use ruvector_core::types::DbOptions;
+use ruvector_core::{DistanceMetric, VectorDB, VectorEntry};
+use ruvector_raft::{RaftError, ReadConsistency, StateMachine, VectorStateMachine};
+use std::collections::HashMap;
+use std::sync::Arc;
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_writes_are_applied_on_every_replica() {
+    let cluster = Cluster::start();
+    let leader = cluster.leader(None).await;
+
+    leader.insert(entry("a", [0.0, 0.0])).await.unwrap();
+    let ids = leader
+        .upsert_batch(vec![
+            entry("b", [5.0, 5.0]),
+            entry("c", [9.0, 9.0]),
+            VectorEntry {
+                id: None,
+                vector: vec![1.0, 1.0],
+                metadata: None,
+            },
+        ])
+        .await
+        .unwrap();
+    assert_eq!(ids.len(), 3);
+    assert!(leader.delete("c").await.unwrap());
+    assert!(!leader.delete("c").await.unwrap());
+
+    leader.insert(entry("d", [1.0, 0.0])).await.unwrap();
+
+    // Rejected before reaching the log
+    let err = leader
+        .upsert_batch(vec![VectorEntry {
+            id: None,
+            vector: vec![1.0],
+            metadata: None,
+        }])
+        .await
+        .unwrap_err();
+    assert!(matches!(err, RaftError::Database(_)));
+
+    // The leader reads its own writes
+    assert_eq!(
+        leader.count(ReadConsistency::Linearizable).await.unwrap(),
+        4
+    );
+    let results = leader
+        .search(query([4.0, 4.0]), ReadConsistency::Linearizable)
+        .await
+        .unwrap();
+    assert_eq!(results[0].id, "b");
+
+    // Followers refuse writes and linearizable reads, and serve stale ones
+    cluster.converge(None).await;
+    for follower in cluster
+        .replicas()
+        .iter()
+        .filter(|replica| !replica.node().current_state().is_leader())
+    {
+        let err = follower.insert(entry("e", [2.0, 2.0])).await.unwrap_err();
+        assert!(matches!(err, RaftError::NotLeader));
+        let err = follower
+            .count(ReadConsistency::Linearizable)
+            .await
+            .unwrap_err();
+        assert!(matches!(err, RaftError::NotLeader));
+
+        assert_eq!(follower.count(ReadConsistency::Stale).await.unwrap(), 4);
+        let auto_id = follower
+            .get(&ids[2], ReadConsistency::Stale)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(auto_id.vector, vec![1.0, 1.0]);
+        assert!(follower
+            .get("c", ReadConsistency::Stale)
+            .await
+            .unwrap()
+            .is_none());
+    }
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_partitioned_leader_is_replaced() {
+    let cluster = Cluster::start();
+    let old_leader = cluster.leader(None).await;
+    old_leader.insert(entry("a", [0.0, 0.0])).await.unwrap();
+    let old_id = old_leader.node().node_id().clone();
+
+    // Cut off from the majority, the old leader can't confirm reads
+    cluster.transport.disconnect(&old_id);
+    let err = old_leader
+        .count(ReadConsistency::Linearizable)
+        .await
+        .unwrap_err();
+    assert!(matches!(err, RaftError::Timeout | RaftError::NotLeader));
+
+    let new_leader = cluster.leader(Some(&old_id)).await;
+    assert_ne!(new_leader.node().node_id(), &old_id);
+    new_leader.insert(entry("b", [5.0, 5.0])).await.unwrap();
+    assert_eq!(
+        new_leader
+            .count(ReadConsistency::Linearizable)
+            .await
+            .unwrap(),
+        2
+    );
+
+    // Back in the cluster it catches up with the writes it missed
+    cluster.transport.reconnect(&old_id);
+    cluster.converge(None).await;
+    let old_leader = cluster.replica(&old_id);
+    assert!(old_leader
+        .get("b", ReadConsistency::Stale)
+        .await
+        .unwrap()
+        .is_some());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_lagging_replica_installs_snapshot() {
+    let cluster = Cluster::start();
+    let leader = cluster.leader(None).await;
+    let lagging = cluster
+        .replicas()
+        .iter()
+        .find(|replica| !replica.node().current_state().is_leader())
+        .unwrap()
+        .node()
+        .node_id()
+        .clone();
+
+    cluster.transport.disconnect(&lagging);
+    for i in 0..20 {
+        leader
+            .insert(entry(&format!("p{}", i), [i as f32, 0.0]))
+            .await
+            .unwrap();
+    }
+
+    // Every connected replica compacts its log, so the lagging one can only
+    // catch up from a snapshot
+    cluster.converge(Some(&lagging)).await;
+    for replica in cluster.replicas() {
+        if replica.node().node_id() != &lagging {
+            let snapshot = replica.snapshot().unwrap();
+            assert_eq!(snapshot.last_included_index, leader.node().last_applied());
+        }
+    }
+    leader.delete("p0").await.unwrap();
+
+    cluster.transport.reconnect(&lagging);
+    cluster.converge(None).await;
+    let lagging = cluster.replica(&lagging);
+    assert_eq!(lagging.count(ReadConsistency::Stale).await.unwrap(), 19);
+    let results = lagging
+        .search(query([7.2, 0.0]), ReadConsistency::Stale)
+        .await
+        .unwrap();
+    assert_eq!(results[0].id, "p7");
+}
+
+fn open_db(dir: &tempfile::TempDir, name: &str) -> Arc<VectorDB> {
+    let path = dir.path().join(name);
+    Arc::new(
+        VectorDB::new(DbOptions {
+            dimensions: 2,
+            distance_metric: DistanceMetric::Euclidean,
+            storage_path: path.to_string_lossy().into_owned(),
+            quantization: None,
+            ..DbOptions::default()
+        })
+        .unwrap(),
+    )
+}
+
+#[test]
+fn test_snapshot_restore_replaces_points_and_can_be_repeated() {
+    let dir = tempfile::tempdir().unwrap();
+    let source = open_db(&dir, "source.db");
+    // More points than fit in one page of the snapshot
+    let entries: Vec<VectorEntry> = (0..260)
+        .map(|i| VectorEntry {
+            metadata: Some(HashMap::from([("i".to_string(), serde_json::json!(i))])),
+            ..entry(&format!("p{:03}", i), [i as f32, 0.0])
+        })
+        .collect();
+    source.upsert_batch(entries).unwrap();
+    let snapshot = VectorStateMachine::new(source).snapshot().unwrap();
+
+    // The target holds points the snapshot replaces or doesn't have
+    let target = open_db(&dir, "target.db");
+    target
+        .upsert_batch(vec![
+            entry("p001", [-1.0, -1.0]),
+            entry("stale", [3.0, 3.0]),
+        ])
+        .unwrap();
+    let state_machine = VectorStateMachine::new(target.clone());
+
+    // A restore stopped short is completed by restoring again
+    state_machine.restore(&snapshot).unwrap();
+    state_machine.restore(&snapshot).unwrap();
+    assert_eq!(target.len().unwrap(), 260);
+    assert!(target.get("stale").unwrap().is_none());
+    let point = target.get("p001").unwrap().unwrap();
+    assert_eq!(point.vector, vec![1.0, 0.0]);
+    assert_eq!(point.metadata.unwrap()["i"], serde_json::json!(1));
+
+    let err = state_machine.restore(&snapshot[..2]).unwrap_err();
+    assert!(matches!(err, RaftError::SnapshotFailed(_)));
+
+    // An empty database's snapshot empties the target
+    let empty = VectorStateMachine::new(open_db(&dir, "empty.db"));
+    state_machine.restore(&empty.snapshot().unwrap()).unwrap();
+    assert!(target.is_empty().unwrap());
+}
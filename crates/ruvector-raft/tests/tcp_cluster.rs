@@ -0,0 +1,160 @@
+//! A cluster whose nodes talk to each other over TCP
+
+mod common;
+
+use common::{entry, NODES};
+use ruvector_core::types::DbOptions;
+use ruvector_core::{DistanceMetric, VectorDB};
+use ruvector_raft::{
+    RaftError, RaftNode, RaftNodeConfig, ReadConsistency, ReplicatedVectorDB, TcpTransport,
+};
+use std::sync::Arc;
+use std::time::Duration;
+use tempfile::TempDir;
+use tokio::time::sleep;
+
+struct TcpNode {
+    replica: Arc<ReplicatedVectorDB>,
+    transport: Arc<TcpTransport>,
+}
+
+/// Start the three `NODES`, each listening on its own port
+async fn start(dir: &TempDir) -> Vec<TcpNode> {
+    let members: Vec<String> = NODES.iter().map(|id| id.to_string()).collect();
+    let mut transports = Vec::new();
+    for _ in NODES {
+        transports.push(Arc::new(TcpTransport::bind("127.0.0.1:0").await.unwrap()));
+    }
+    for transport in &transports {
+        for (id, peer) in NODES.iter().zip(&transports) {
+            transport.add_peer(id, peer.local_addr().to_string());
+        }
+    }
+
+    let mut nodes = Vec::new();
+    for (id, transport) in NODES.iter().zip(transports) {
+        let mut config = RaftNodeConfig::new(id.to_string(), members.clone());
+        config.request_timeout = 1_000;
+        let node = RaftNode::new(config).with_transport(transport.clone());
+
+        let path = dir.path().join(format!("{}.db", id));
+        let db = VectorDB::new(DbOptions {
+            dimensions: 2,
+            distance_metric: DistanceMetric::Euclidean,
+            storage_path: path.to_string_lossy().into_owned(),
+            quantization: None,
+            ..DbOptions::default()
+        })
+        .unwrap();
+
+        let replica = Arc::new(ReplicatedVectorDB::new(node, db).unwrap());
+        transport.register(replica.node());
+        replica.start();
+        nodes.push(TcpNode { replica, transport });
+    }
+    nodes
+}
+
+/// Wait for a leader among `nodes`
+async fn leader(nodes: &[&TcpNode]) -> Arc<ReplicatedVectorDB> {
+    for _ in 0..100 {
+        let leader = nodes
+            .iter()
+            .map(|node| &node.replica)
+            .filter(|replica| replica.node().current_state().is_leader())
+            .max_by_key(|replica| replica.node().current_term());
+        if let Some(leader) = leader {
+            return leader.clone();
+        }
+        sleep(Duration::from_millis(50)).await;
+    }
+    panic!("no leader was elected");
+}
+
+/// Wait until every node applied the leader's whole log
+async fn converge(nodes: &[TcpNode]) {
+    for _ in 0..100 {
+        let all: Vec<&TcpNode> = nodes.iter().collect();
+        let last_index = leader(&all).await.node().last_log_index();
+        if nodes
+            .iter()
+            .all(|node| node.replica.node().last_applied() == last_index)
+        {
+            return;
+        }
+        sleep(Duration::from_millis(50)).await;
+    }
+    panic!("nodes didn't converge");
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_cluster_elects_replicates_and_heals_over_tcp() {
+    let dir = tempfile::tempdir().unwrap();
+    let nodes = start(&dir).await;
+    let all: Vec<&TcpNode> = nodes.iter().collect();
+
+    // A leader is elected and its writes reach every node
+    let old_leader = leader(&all).await;
+    old_leader.insert(entry("a", [0.0, 0.0])).await.unwrap();
+    old_leader.insert(entry("b", [1.0, 1.0])).await.unwrap();
+    converge(&nodes).await;
+    for node in &nodes {
+        let count = node.replica.count(ReadConsistency::Stale).await.unwrap();
+        assert_eq!(count, 2);
+    }
+
+    // Cut off from the others, the leader can't commit, and they elect a
+    // new one
+    let old_id = old_leader.node().node_id().clone();
+    let old_term = old_leader.node().current_term();
+    for node in &nodes {
+        let id = node.replica.node().node_id();
+        if id == &old_id {
+            for peer in NODES.iter().filter(|peer| **peer != old_id) {
+                node.transport.disconnect(peer);
+            }
+        } else {
+            node.transport.disconnect(&old_id);
+        }
+    }
+    let err = old_leader.insert(entry("lost", [9.0, 9.0])).await;
+    assert!(matches!(err, Err(RaftError::Timeout)));
+
+    let majority: Vec<&TcpNode> = nodes
+        .iter()
+        .filter(|node| node.replica.node().node_id() != &old_id)
+        .collect();
+    let new_leader = leader(&majority).await;
+    assert!(new_leader.node().current_term() > old_term);
+    new_leader.insert(entry("c", [2.0, 2.0])).await.unwrap();
+
+    // Once the partition heals over new connections, the old leader
+    // follows, drops its uncommitted write and catches up
+    for node in &nodes {
+        for peer in NODES {
+            node.transport.reconnect(peer);
+        }
+    }
+    converge(&nodes).await;
+    assert!(!old_leader.node().current_state().is_leader());
+    assert_eq!(
+        old_leader.node().current_leader().as_ref(),
+        Some(new_leader.node().node_id())
+    );
+    for node in &nodes {
+        let replica = &node.replica;
+        assert_eq!(replica.count(ReadConsistency::Stale).await.unwrap(), 3);
+        for id in ["a", "b", "c"] {
+            assert!(replica
+                .get(id, ReadConsistency::Stale)
+                .await
+                .unwrap()
+                .is_some());
+        }
+        assert!(replica
+            .get("lost", ReadConsistency::Stale)
+            .await
+            .unwrap()
+            .is_none());
+    }
+}
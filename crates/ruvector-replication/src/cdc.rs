@@ -0,0 +1,1169 @@
+//! Durable change data capture from vector databases
+//!
+//! A [`ChangeCapture`] registered as a change listener on a
+//! [`VectorDB`](ruvector_core::VectorDB) publishes every committed insert,
+//! update and delete of a point to a [`ChangeLog`] as a [`ChangeEvent`]
+//! carrying the point before and after the change. The log is an
+//! append-only JSON-lines file, so events keep their sequence numbers
+//! across restarts, and it stores the checkpoints of the consumers reading
+//! it, so a [`ChangeConsumer`] resumes after the last event it committed.
+//!
+//! Events are appended while the write holds the database's write mutex,
+//! so in commit order, and synced to disk after it released it; a write whose events can't be
+//! made durable fails, although the database keeps it. Every event records
+//! the sequence of the database operation that made it, and a capture
+//! registered again after a restart is replayed the operations its
+//! collection logged since its last durable event, so a crash between the
+//! database commit and the sync loses nothing. Replayed events carry no
+//! `before` image.
+//!
+//! The log retains the events its [`ChangeLogConfig`] allows and is
+//! compacted in the background, once [`ChangeLog::start_compaction`] was
+//! called, when enough of them can be dropped. Only the most recent events
+//! are held in memory; older ones are read back from the file.
+
+use crate::stream::{ChangeEvent, Checkpoint};
+use crate::{ReplicationError, Result};
+use chrono::{DateTime, Utc};
+use parking_lot::{Mutex, RwLock};
+use ruvector_core::{ChangeListener, PointChange, RuvectorError};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufRead, BufReader, BufWriter, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, SyncSender};
+use std::sync::{Arc, Weak};
+use std::thread::JoinHandle;
+use std::time::Duration;
+use tokio::sync::watch;
+
+/// File holding the events, one JSON object per line
+const EVENTS_FILE: &str = "changes.jsonl";
+
+/// File holding the consumer checkpoints
+const CHECKPOINTS_FILE: &str = "checkpoints.json";
+
+/// File holding what compaction dropped that the log still needs
+const COMPACTED_FILE: &str = "compacted.json";
+
+/// Events between the file offsets kept to seek to an event
+const OFFSET_STRIDE: u64 = 256;
+
+/// Longest the background compaction waits between checks, so events
+/// expire while nothing is published
+const COMPACTION_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Most passes a compaction makes over events appended while it copied,
+/// before it makes appends wait for it to copy the rest
+const COMPACTION_PASSES: usize = 8;
+
+/// Appended bytes a compaction leaves for appends to wait on
+const COMPACTION_TAIL_LEN: u64 = 1024 * 1024;
+
+/// Which events a [`ChangeLog`] retains, and how many it holds in memory
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ChangeLogConfig {
+    /// Most events retained, read or not; `None` for no limit
+    pub max_events: Option<u64>,
+    /// Longest an event is retained, read or not; `None` for no limit
+    pub max_age: Option<Duration>,
+    /// Also drop the events every consumer committed, once one has
+    pub drop_consumed: bool,
+    /// Most recent events held in memory
+    pub resident_events: usize,
+}
+
+impl Default for ChangeLogConfig {
+    fn default() -> Self {
+        Self {
+            max_events: Some(1_000_000),
+            max_age: Some(Duration::from_secs(7 * 24 * 60 * 60)),
+            drop_consumed: false,
+            resident_events: 10_000,
+        }
+    }
+}
+
+/// Events read from a [`ChangeLog`]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChangeBatch {
+    /// The events, in sequence order
+    pub events: Vec<ChangeEvent>,
+
+    /// Sequence to read after next; past the last event returned when
+    /// later events were skipped by a collection filter
+    pub next: u64,
+}
+
+/// What compaction dropped that a reopened log still needs
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct Compacted {
+    /// Sequence of the last event dropped, for sequences to continue after
+    last_sequence: u64,
+    /// Source sequence of each collection's last synced event
+    captured: HashMap<String, u64>,
+}
+
+struct EventState {
+    /// The most recent synced events, read without touching the file
+    events: VecDeque<ChangeEvent>,
+    /// Events written but not synced yet
+    pending: Vec<ChangeEvent>,
+    file: File,
+    /// Length of the complete lines in the file
+    len: u64,
+    /// Sequence of the first event in the file, the next one if it's empty
+    first: u64,
+    /// When the first event in the file was published
+    first_timestamp: Option<DateTime<Utc>>,
+    /// File offsets of every `OFFSET_STRIDE`-th event from the first
+    offsets: Vec<u64>,
+    /// Sequence of the last event appended
+    last: u64,
+    /// Sequence of the last event synced
+    synced: u64,
+    /// Source sequence of each collection's last synced event
+    captured: HashMap<String, u64>,
+    /// Whether a sync failed, after which appended events may be lost
+    failed: bool,
+}
+
+/// Copies whole event lines into a compacted file, indexing them as
+/// [`EventState::offsets`] does
+struct LineCopy<W> {
+    target: W,
+    /// Sequence of the next line copied
+    next: u64,
+    /// Sequence of the first line copied
+    first: u64,
+    len: u64,
+    offsets: Vec<u64>,
+    first_timestamp: Option<DateTime<Utc>>,
+}
+
+impl<W: Write> LineCopy<W> {
+    /// Copy the lines in the next `len` bytes of `source`
+    fn copy(&mut self, source: &mut impl BufRead, len: u64) -> Result<()> {
+        let mut line = Vec::new();
+        let mut remaining = len;
+        while remaining > 0 {
+            line.clear();
+            if source.read_until(b'\n', &mut line)? == 0 {
+                return Err(ReplicationError::InvalidState(
+                    "Change log ended mid-compaction".to_string(),
+                ));
+            }
+            if self.next == self.first {
+                self.first_timestamp = serde_json::from_slice::<ChangeEvent>(&line)
+                    .ok()
+                    .map(|event| event.timestamp);
+            }
+            if (self.next - self.first) % OFFSET_STRIDE == 0 {
+                self.offsets.push(self.len);
+            }
+            self.target.write_all(&line)?;
+            self.next += 1;
+            self.len += line.len() as u64;
+            remaining = remaining.saturating_sub(line.len() as u64);
+        }
+        Ok(())
+    }
+}
+
+/// A durable, append-only log of change events
+pub struct ChangeLog {
+    dir: PathBuf,
+    config: ChangeLogConfig,
+    state: RwLock<EventState>,
+    checkpoints: RwLock<HashMap<String, Checkpoint>>,
+    /// Held while compacting, so only one compaction copies the file
+    compaction: Mutex<()>,
+    /// Wakes the background compaction after a sync
+    compaction_tx: SyncSender<()>,
+    /// Taken by the background compaction once started
+    compaction_rx: Mutex<Option<Receiver<()>>>,
+    /// Sequence of the last event published
+    last_sequence: watch::Sender<u64>,
+}
+
+impl ChangeLog {
+    /// Open the log stored in `dir` with the default retention, creating it
+    /// if it doesn't exist
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the directory can't be read or written, or the
+    /// checkpoints are corrupt
+    pub fn open(dir: impl AsRef<Path>) -> Result<Self> {
+        Self::with_config(dir, ChangeLogConfig::default())
+    }
+
+    /// Open the log stored in `dir`, creating it if it doesn't exist
+    ///
+    /// A line left incomplete by a crash during a publish is dropped.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the directory can't be read or written, or the
+    /// checkpoints are corrupt
+    pub fn with_config(dir: impl AsRef<Path>, config: ChangeLogConfig) -> Result<Self> {
+        let dir = dir.as_ref().to_path_buf();
+        fs::create_dir_all(&dir)?;
+
+        let compacted: Compacted = read_json(&dir.join(COMPACTED_FILE))?.unwrap_or_default();
+        let mut last = compacted.last_sequence;
+        let mut captured = compacted.captured;
+
+        let path = dir.join(EVENTS_FILE);
+        let mut events = VecDeque::new();
+        let mut first = None;
+        let mut first_timestamp = None;
+        let mut offsets = Vec::new();
+        let mut valid_len = 0;
+        if path.exists() {
+            let mut reader = BufReader::new(File::open(&path)?);
+            let mut line = Vec::new();
+            while reader.read_until(b'\n', &mut line)? > 0 {
+                let event = line
+                    .strip_suffix(b"\n")
+                    .and_then(|line| serde_json::from_slice::<ChangeEvent>(line).ok());
+                let Some(event) = event else {
+                    tracing::warn!(
+                        "Dropping incomplete change event at byte {} of {}",
+                        valid_len,
+                        path.display()
+                    );
+                    break;
+                };
+
+                let first = *first.get_or_insert(event.sequence);
+                first_timestamp.get_or_insert(event.timestamp);
+                if (event.sequence - first) % OFFSET_STRIDE == 0 {
+                    offsets.push(valid_len);
+                }
+                valid_len += line.len() as u64;
+                if event.source_sequence > 0 {
+                    captured.insert(event.collection.clone(), event.source_sequence);
+                }
+                last = event.sequence;
+                events.push_back(event);
+                if events.len() > config.resident_events {
+                    events.pop_front();
+                }
+                line.clear();
+            }
+        }
+
+        let file = OpenOptions::new().create(true).append(true).open(&path)?;
+        file.set_len(valid_len)?;
+
+        let checkpoints = read_json(&dir.join(CHECKPOINTS_FILE))?.unwrap_or_default();
+        let (compaction_tx, compaction_rx) = mpsc::sync_channel(1);
+
+        Ok(Self {
+            dir,
+            config,
+            state: RwLock::new(EventState {
+                events,
+                pending: Vec::new(),
+                file,
+                len: valid_len,
+                first: first.unwrap_or(last + 1),
+                first_timestamp,
+                offsets,
+                last,
+                synced: last,
+                captured,
+                failed: false,
+            }),
+            checkpoints: RwLock::new(checkpoints),
+            compaction: Mutex::new(()),
+            compaction_tx,
+            compaction_rx: Mutex::new(Some(compaction_rx)),
+            last_sequence: watch::Sender::new(last),
+        })
+    }
+
+    /// Compact the log on a background thread after syncs, and at least
+    /// every minute, until the log is dropped
+    ///
+    /// # Errors
+    ///
+    /// Returns `InvalidState` if the compaction was already started, or an
+    /// error if the thread can't be spawned
+    pub fn start_compaction(self: &Arc<Self>) -> Result<JoinHandle<()>> {
+        let rx = self.compaction_rx.lock().take().ok_or_else(|| {
+            ReplicationError::InvalidState("Change log is already compacting".to_string())
+        })?;
+        let log = Arc::downgrade(self);
+        let handle = std::thread::Builder::new()
+            .name("change-log-compaction".to_string())
+            .spawn(move || compact_in_background(log, rx))?;
+        Ok(handle)
+    }
+
+    /// Append the changes committed to points of `collection` as events and
+    /// sync them, returning the sequence of the last event
+    pub fn publish(&self, collection: &str, changes: &[PointChange]) -> Result<u64> {
+        let sequence = self.append(collection, changes)?;
+        self.sync()?;
+        Ok(sequence)
+    }
+
+    /// Append the changes committed to points of `collection` as events,
+    /// returning the sequence of the last event
+    ///
+    /// The events are written but not synced; readers see them once
+    /// [`ChangeLog::sync`] made them durable.
+    pub fn append(&self, collection: &str, changes: &[PointChange]) -> Result<u64> {
+        let mut state = self.state.write();
+        if state.failed {
+            return Err(ReplicationError::InvalidState(
+                "Change log failed to sync and must be reopened".to_string(),
+            ));
+        }
+        let mut sequence = state.last;
+        if changes.is_empty() {
+            return Ok(sequence);
+        }
+
+        let mut events = Vec::with_capacity(changes.len());
+        let mut lines = Vec::new();
+        let mut offsets = Vec::new();
+        for change in changes {
+            sequence += 1;
+            let event = ChangeEvent::from_point_change(sequence, collection.to_string(), change);
+            if (sequence - state.first) % OFFSET_STRIDE == 0 {
+                offsets.push(state.len + lines.len() as u64);
+            }
+            serde_json::to_writer(&mut lines, &event)
+                .map_err(|e| ReplicationError::InvalidState(format!("Encoding failed: {}", e)))?;
+            lines.push(b'\n');
+            events.push(event);
+        }
+        if let Err(e) = state.file.write_all(&lines) {
+            // Don't leave half a line for the next append to extend
+            let _ = state.file.set_len(state.len);
+            return Err(e.into());
+        }
+        if state.first_timestamp.is_none() {
+            state.first_timestamp = events.first().map(|event| event.timestamp);
+        }
+        state.len += lines.len() as u64;
+        state.last = sequence;
+        state.offsets.extend(offsets);
+        state.pending.extend(events);
+        Ok(sequence)
+    }
+
+    /// Sync the appended events to disk and make them readable, then wake
+    /// the background compaction
+    ///
+    /// Appends and reads aren't blocked while the file syncs. Once a sync
+    /// failed the log refuses new events until it is reopened, since those
+    /// already appended may be lost.
+    pub fn sync(&self) -> Result<()> {
+        let (file, through) = {
+            let state = self.state.read();
+            let Some(last) = state.pending.last() else {
+                return Ok(());
+            };
+            (state.file.try_clone()?, last.sequence)
+        };
+        if let Err(e) = file.sync_data() {
+            self.state.write().failed = true;
+            return Err(e.into());
+        }
+
+        // A concurrent sync may have covered some of the events already
+        let mut state = self.state.write();
+        let synced = state
+            .pending
+            .partition_point(|event| event.sequence <= through);
+        let events: Vec<_> = state.pending.drain(..synced).collect();
+        for event in &events {
+            if event.source_sequence > 0 {
+                state
+                    .captured
+                    .insert(event.collection.clone(), event.source_sequence);
+            }
+        }
+        state.events.extend(events);
+        let evicted = state
+            .events
+            .len()
+            .saturating_sub(self.config.resident_events);
+        state.events.drain(..evicted);
+        state.synced = state.synced.max(through);
+        drop(state);
+
+        self.last_sequence.send_if_modified(|last| {
+            if through <= *last {
+                return false;
+            }
+            *last = through;
+            true
+        });
+
+        // A request already queued covers this sync too
+        let _ = self.compaction_tx.try_send(());
+        Ok(())
+    }
+
+    /// Source sequence of the last synced event of `collection`, `None` if
+    /// it has none
+    pub fn captured(&self, collection: &str) -> Option<u64> {
+        self.state.read().captured.get(collection).copied()
+    }
+
+    /// Up to `limit` events after sequence `after`, only those of
+    /// `collection` if one is given
+    ///
+    /// Reading continues from the oldest event retained if those after
+    /// `after` were dropped.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if events no longer held in memory can't be read
+    /// from the file
+    pub fn read(&self, after: u64, limit: usize, collection: Option<&str>) -> Result<ChangeBatch> {
+        let state = self.state.read();
+        let mut batch = ChangeBatch {
+            events: Vec::new(),
+            next: after,
+        };
+        let matches = |event: &ChangeEvent| {
+            collection.map_or(true, |collection| event.collection == collection)
+        };
+
+        let resident = state
+            .events
+            .front()
+            .map_or(state.synced + 1, |event| event.sequence);
+        let from = (after + 1).max(state.first);
+        if from < resident {
+            let mut reader = self.seek(&state, from)?;
+            let mut line = Vec::new();
+            for _ in from..resident {
+                if batch.events.len() >= limit {
+                    return Ok(batch);
+                }
+                line.clear();
+                reader.read_until(b'\n', &mut line)?;
+                let event: ChangeEvent = serde_json::from_slice(&line).map_err(|e| {
+                    ReplicationError::InvalidState(format!("Corrupt change event: {}", e))
+                })?;
+                batch.next = event.sequence;
+                if matches(&event) {
+                    batch.events.push(event);
+                }
+            }
+        }
+
+        let start = state
+            .events
+            .partition_point(|event| event.sequence <= batch.next);
+        for event in state.events.range(start..) {
+            if batch.events.len() >= limit {
+                break;
+            }
+            batch.next = event.sequence;
+            if matches(event) {
+                batch.events.push(event.clone());
+            }
+        }
+        Ok(batch)
+    }
+
+    /// [`ChangeLog::read`], waiting up to `timeout` for an event to be
+    /// published if there are none
+    pub async fn read_wait(
+        &self,
+        after: u64,
+        limit: usize,
+        collection: Option<&str>,
+        timeout: Duration,
+    ) -> Result<ChangeBatch> {
+        let deadline = tokio::time::Instant::now() + timeout;
+        let mut after = after;
+        loop {
+            let batch = self.read(after, limit, collection)?;
+            let remaining = deadline.saturating_duration_since(tokio::time::Instant::now());
+            // Events of other collections advance the batch but return
+            // nothing, so keep waiting for one of `collection`
+            if !batch.events.is_empty() || !self.wait_for(batch.next, remaining).await {
+                return Ok(batch);
+            }
+            after = batch.next;
+        }
+    }
+
+    /// Wait up to `timeout` for an event after sequence `after`, returning
+    /// whether there is one
+    pub async fn wait_for(&self, after: u64, timeout: Duration) -> bool {
+        let mut last_sequence = self.subscribe();
+        let published = last_sequence.wait_for(|last| *last > after);
+        let ready = matches!(tokio::time::timeout(timeout, published).await, Ok(Ok(_)));
+        ready
+    }
+
+    /// Watch the sequence of the last event published
+    pub fn subscribe(&self) -> watch::Receiver<u64> {
+        self.last_sequence.subscribe()
+    }
+
+    /// Sequence of the last event published, 0 if there is none
+    pub fn last_sequence(&self) -> u64 {
+        *self.last_sequence.borrow()
+    }
+
+    /// Sequence of the oldest event still in the log, 0 if there is none
+    pub fn first_sequence(&self) -> u64 {
+        let state = self.state.read();
+        if state.first > state.synced {
+            return 0;
+        }
+        state.first
+    }
+
+    /// Number of events in the log
+    pub fn len(&self) -> usize {
+        let state = self.state.read();
+        (state.synced + 1).saturating_sub(state.first) as usize
+    }
+
+    /// Check if the log holds no events
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Drop the events the [`ChangeLogConfig`] no longer retains, returning
+    /// how many were dropped
+    ///
+    /// Does nothing until at least a quarter of the log can be dropped, or
+    /// its oldest event is a quarter past the maximum age, so the file is
+    /// rewritten only every so often; the background compaction calls this.
+    pub fn compact(&self) -> Result<usize> {
+        let (first, synced, appended, first_timestamp) = {
+            let state = self.state.read();
+            (
+                state.first,
+                state.synced,
+                state.last + 1 - state.first,
+                state.first_timestamp,
+            )
+        };
+
+        let mut keep_from = first;
+        if let Some(max_events) = self.config.max_events {
+            keep_from = keep_from.max((synced + 1).saturating_sub(max_events));
+        }
+        if self.config.drop_consumed {
+            let consumed = self
+                .checkpoints
+                .read()
+                .values()
+                .map(|checkpoint| checkpoint.sequence)
+                .min();
+            if let Some(consumed) = consumed {
+                keep_from = keep_from.max(consumed + 1);
+            }
+        }
+
+        let age = |timestamp: DateTime<Utc>| (Utc::now() - timestamp).to_std().unwrap_or_default();
+        let expired = match (self.config.max_age, first_timestamp) {
+            (Some(max_age), Some(timestamp)) => age(timestamp) > max_age + max_age / 4,
+            _ => false,
+        };
+        let worthwhile = keep_from > first && (keep_from - first) * 4 >= appended;
+        if !worthwhile && !expired {
+            return Ok(0);
+        }
+        if let (true, Some(max_age)) = (expired, self.config.max_age) {
+            keep_from = keep_from.max(self.first_younger_than(max_age, age)?);
+        }
+        self.truncate_before(keep_from)
+    }
+
+    /// Sequence of the first synced event no older than `max_age`
+    fn first_younger_than(
+        &self,
+        max_age: Duration,
+        age: impl Fn(DateTime<Utc>) -> Duration,
+    ) -> Result<u64> {
+        let state = self.state.read();
+        let mut reader = self.seek(&state, state.first)?;
+        let mut line = Vec::new();
+        for sequence in state.first..=state.synced {
+            line.clear();
+            reader.read_until(b'\n', &mut line)?;
+            let event: ChangeEvent = serde_json::from_slice(&line).map_err(|e| {
+                ReplicationError::InvalidState(format!("Corrupt change event: {}", e))
+            })?;
+            if age(event.timestamp) <= max_age {
+                return Ok(sequence);
+            }
+        }
+        Ok(state.synced + 1)
+    }
+
+    /// Drop the events before sequence `sequence`, returning how many were
+    /// dropped
+    ///
+    /// Events not synced yet are kept. The retained events are copied to a
+    /// new file and synced without blocking appends, which only wait while
+    /// the last of those appended meanwhile are copied and the new file
+    /// replaces the old.
+    pub fn truncate_before(&self, sequence: u64) -> Result<usize> {
+        let _compaction = self.compaction.lock();
+        let path = self.dir.join(EVENTS_FILE);
+        let temp = path.with_extension("tmp");
+
+        // The file never changes below its current length
+        let (mut source, mut copy, mut copied, compacted) = {
+            let state = self.state.read();
+            let sequence = sequence.min(state.synced + 1);
+            if sequence <= state.first {
+                return Ok(0);
+            }
+            let source = self.seek(&state, sequence)?;
+            let copy = LineCopy {
+                target: BufWriter::new(File::create(&temp)?),
+                next: sequence,
+                first: sequence,
+                len: 0,
+                offsets: Vec::new(),
+                first_timestamp: None,
+            };
+            // Events after these keep their collections' sequences in the
+            // new file
+            let compacted = Compacted {
+                last_sequence: sequence - 1,
+                captured: state.captured.clone(),
+            };
+            (source, copy, state.len, compacted)
+        };
+        let start = source.stream_position()?;
+        copy.copy(&mut source, copied - start)?;
+
+        // Then what was appended meanwhile, each pass seeking back to drop
+        // anything read past `copied` before a failed append truncated it
+        for _ in 0..COMPACTION_PASSES {
+            let len = self.state.read().len;
+            if len - copied <= COMPACTION_TAIL_LEN {
+                break;
+            }
+            source.seek(SeekFrom::Start(copied))?;
+            copy.copy(&mut source, len - copied)?;
+            copied = len;
+        }
+        copy.target.flush()?;
+        copy.target.get_ref().sync_all()?;
+
+        let bytes = serde_json::to_vec_pretty(&compacted)
+            .map_err(|e| ReplicationError::InvalidState(format!("Encoding failed: {}", e)))?;
+        write_atomically(&self.dir.join(COMPACTED_FILE), &bytes)?;
+
+        source.seek(SeekFrom::Start(copied))?;
+        let mut state = self.state.write();
+        let tail = state.len - copied;
+        copy.copy(&mut source, tail)?;
+        let target = copy
+            .target
+            .into_inner()
+            .map_err(|e| ReplicationError::from(e.into_error()))?;
+        // Synced events may be among those, and must stay durable
+        if tail > 0 {
+            target.sync_data()?;
+        }
+        fs::rename(&temp, &path)?;
+
+        let dropped = copy.first - state.first;
+        state.file = OpenOptions::new().append(true).open(&path)?;
+        state.len = copy.len;
+        state.first = copy.first;
+        state.first_timestamp = copy.first_timestamp;
+        state.offsets = copy.offsets;
+        let evicted = state
+            .events
+            .partition_point(|event| event.sequence < copy.first);
+        state.events.drain(..evicted);
+        Ok(dropped as usize)
+    }
+
+    /// A reader of the file positioned at the line of event `sequence`,
+    /// which must be in the file or the next one appended
+    fn seek(&self, state: &EventState, sequence: u64) -> Result<BufReader<File>> {
+        let mut reader = BufReader::new(File::open(self.dir.join(EVENTS_FILE))?);
+        if sequence > state.last {
+            reader.seek(SeekFrom::Start(state.len))?;
+            return Ok(reader);
+        }
+        let stride = ((sequence - state.first) / OFFSET_STRIDE) as usize;
+        reader.seek(SeekFrom::Start(state.offsets[stride]))?;
+
+        let mut line = Vec::new();
+        for _ in state.first + stride as u64 * OFFSET_STRIDE..sequence {
+            line.clear();
+            reader.read_until(b'\n', &mut line)?;
+        }
+        Ok(reader)
+    }
+
+    /// The checkpoint committed under `key`
+    pub fn checkpoint(&self, key: &str) -> Option<Checkpoint> {
+        self.checkpoints.read().get(key).cloned()
+    }
+
+    /// Durably store `checkpoint` under `key`, replacing the previous one
+    pub fn commit(&self, key: &str, checkpoint: Checkpoint) -> Result<()> {
+        let mut checkpoints = self.checkpoints.write();
+        let mut updated = checkpoints.clone();
+        updated.insert(key.to_string(), checkpoint);
+
+        let bytes = serde_json::to_vec_pretty(&updated)
+            .map_err(|e| ReplicationError::InvalidState(format!("Encoding failed: {}", e)))?;
+        write_atomically(&self.dir.join(CHECKPOINTS_FILE), &bytes)?;
+        *checkpoints = updated;
+        Ok(())
+    }
+}
+
+/// Compact `log` whenever a sync requests it or the interval passes, until
+/// the log is dropped
+fn compact_in_background(log: Weak<ChangeLog>, requests: Receiver<()>) {
+    loop {
+        match requests.recv_timeout(COMPACTION_INTERVAL) {
+            Ok(()) | Err(RecvTimeoutError::Timeout) => {}
+            Err(RecvTimeoutError::Disconnected) => return,
+        }
+        let Some(log) = log.upgrade() else {
+            return;
+        };
+        if let Err(e) = log.compact() {
+            tracing::warn!("Failed to compact the change log: {}", e);
+        }
+    }
+}
+
+/// The JSON file at `path`, `None` if it doesn't exist
+fn read_json<T: serde::de::DeserializeOwned>(path: &Path) -> Result<Option<T>> {
+    if !path.exists() {
+        return Ok(None);
+    }
+    serde_json::from_slice(&fs::read(path)?)
+        .map(Some)
+        .map_err(|e| ReplicationError::InvalidState(format!("Corrupt {}: {}", path.display(), e)))
+}
+
+/// Replace the file at `path` with `bytes`, so a crash leaves either the
+/// old or the new contents
+fn write_atomically(path: &Path, bytes: &[u8]) -> Result<()> {
+    let temp = path.with_extension("tmp");
+    let mut file = File::create(&temp)?;
+    file.write_all(bytes)?;
+    file.sync_all()?;
+    fs::rename(&temp, path)?;
+    Ok(())
+}
+
+/// Publishes the changes committed to a collection's database to a
+/// [`ChangeLog`]
+pub struct ChangeCapture {
+    log: Arc<ChangeLog>,
+    collection: String,
+}
+
+impl ChangeCapture {
+    /// Publish changes as events of `collection`
+    pub fn new(log: Arc<ChangeLog>, collection: impl Into<String>) -> Self {
+        Self {
+            log,
+            collection: collection.into(),
+        }
+    }
+}
+
+impl ChangeListener for ChangeCapture {
+    fn on_change(&self, changes: &[PointChange]) -> ruvector_core::Result<()> {
+        self.log
+            .append(&self.collection, changes)
+            .map(drop)
+            .map_err(capture_failed)
+    }
+
+    fn flush(&self) -> ruvector_core::Result<()> {
+        self.log.sync().map_err(capture_failed)
+    }
+
+    fn resume_after(&self) -> Option<u64> {
+        self.log.captured(&self.collection)
+    }
+}
+
+fn capture_failed(e: ReplicationError) -> RuvectorError {
+    RuvectorError::StorageError(format!("Change capture failed: {}", e))
+}
+
+/// Reads a [`ChangeLog`] from a committed checkpoint
+///
+/// Consumers sharing a group share one checkpoint, so a consumer that
+/// replaces another in its group resumes where the group left off.
+pub struct ChangeConsumer {
+    log: Arc<ChangeLog>,
+    consumer_id: String,
+    group: Option<String>,
+    collection: Option<String>,
+    /// Sequence of the last event consumed
+    position: u64,
+}
+
+impl ChangeConsumer {
+    /// Read `log` as `consumer_id`, from its committed checkpoint or the
+    /// start of the log
+    pub fn new(log: Arc<ChangeLog>, consumer_id: impl Into<String>) -> Self {
+        let mut consumer = Self {
+            log,
+            consumer_id: consumer_id.into(),
+            group: None,
+            collection: None,
+            position: 0,
+        };
+        consumer.resume();
+        consumer
+    }
+
+    /// Read as a member of `group`, from the group's committed checkpoint
+    pub fn with_group(mut self, group: impl Into<String>) -> Self {
+        self.group = Some(group.into());
+        self.resume();
+        self
+    }
+
+    /// Only read the events of `collection`
+    pub fn with_collection(mut self, collection: impl Into<String>) -> Self {
+        self.collection = Some(collection.into());
+        self
+    }
+
+    /// The next events, up to `limit` of them
+    pub fn poll(&mut self, limit: usize) -> Result<Vec<ChangeEvent>> {
+        let batch = self
+            .log
+            .read(self.position, limit, self.collection.as_deref())?;
+        self.position = batch.next;
+        Ok(batch.events)
+    }
+
+    /// The next events, up to `limit` of them, waiting up to `timeout` for
+    /// one to be published if there are none
+    pub async fn poll_wait(&mut self, limit: usize, timeout: Duration) -> Result<Vec<ChangeEvent>> {
+        let batch = self
+            .log
+            .read_wait(self.position, limit, self.collection.as_deref(), timeout)
+            .await?;
+        self.position = batch.next;
+        Ok(batch.events)
+    }
+
+    /// Durably record that the events read so far were processed
+    pub fn commit(&self) -> Result<()> {
+        let mut checkpoint = Checkpoint::new(self.position, self.consumer_id.clone());
+        if let Some(group) = &self.group {
+            checkpoint = checkpoint.with_group(group.clone());
+        }
+        self.log.commit(self.checkpoint_key(), checkpoint)
+    }
+
+    /// Continue reading after sequence `sequence`
+    pub fn seek(&mut self, sequence: u64) {
+        self.position = sequence;
+    }
+
+    /// Sequence of the last event consumed
+    pub fn position(&self) -> u64 {
+        self.position
+    }
+
+    fn checkpoint_key(&self) -> &str {
+        self.group.as_deref().unwrap_or(&self.consumer_id)
+    }
+
+    fn resume(&mut self) {
+        self.position = self
+            .log
+            .checkpoint(self.checkpoint_key())
+            .map_or(0, |checkpoint| checkpoint.sequence);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ruvector_core::{PointChangeKind, VectorEntry};
+    use tempfile::tempdir;
+
+    fn insert(id: &str) -> PointChange {
+        PointChange {
+            kind: PointChangeKind::Insert,
+            id: id.to_string(),
+            sequence: 0,
+            before: None,
+            after: Some(VectorEntry {
+                id: Some(id.to_string()),
+                vector: vec![1.0, 0.0],
+                metadata: None,
+            }),
+        }
+    }
+
+    #[test]
+    fn test_events_survive_reopen() {
+        let dir = tempdir().unwrap();
+        {
+            let log = ChangeLog::open(dir.path()).unwrap();
+            assert_eq!(log.publish("a", &[insert("p1"), insert("p2")]).unwrap(), 2);
+            assert_eq!(log.publish("b", &[insert("p3")]).unwrap(), 3);
+        }
+
+        // A publish cut short by a crash leaves half a line
+        let mut file = OpenOptions::new()
+            .append(true)
+            .open(dir.path().join(EVENTS_FILE))
+            .unwrap();
+        file.write_all(b"{\"id\":").unwrap();
+
+        let log = ChangeLog::open(dir.path()).unwrap();
+        assert_eq!(log.last_sequence(), 3);
+        assert_eq!(log.publish("a", &[insert("p4")]).unwrap(), 4);
+
+        let batch = log.read(0, 10, Some("a")).unwrap();
+        let ids: Vec<_> = batch
+            .events
+            .iter()
+            .map(|e| e.document_id.as_str())
+            .collect();
+        assert_eq!(ids, vec!["p1", "p2", "p4"]);
+        assert_eq!(batch.next, 4);
+        let batch = log.read(1, 2, None).unwrap();
+        assert_eq!(batch.events.len(), 2);
+        assert_eq!(batch.next, 3);
+
+        assert_eq!(log.truncate_before(3).unwrap(), 2);
+        assert_eq!(log.first_sequence(), 3);
+        assert_eq!(log.read(0, 10, None).unwrap().events[0].sequence, 3);
+        assert_eq!(log.truncate_before(10).unwrap(), 2);
+        assert!(log.is_empty());
+        drop(log);
+        let log = ChangeLog::open(dir.path()).unwrap();
+        assert_eq!(log.first_sequence(), 0);
+        assert_eq!(log.publish("a", &[insert("p5")]).unwrap(), 5);
+        assert_eq!(log.first_sequence(), 5);
+    }
+
+    #[test]
+    fn test_appended_events_are_read_once_synced() {
+        let dir = tempdir().unwrap();
+        let log = ChangeLog::open(dir.path()).unwrap();
+        assert_eq!(log.append("a", &[insert("p1")]).unwrap(), 1);
+        assert!(log.read(0, 10, None).unwrap().events.is_empty());
+        assert_eq!(log.last_sequence(), 0);
+
+        log.sync().unwrap();
+        assert_eq!(log.read(0, 10, None).unwrap().events.len(), 1);
+        assert_eq!(log.last_sequence(), 1);
+    }
+
+    #[test]
+    fn test_capture_replays_uncaptured_writes() -> ruvector_core::Result<()> {
+        use ruvector_core::types::DbOptions;
+        use ruvector_core::VectorDB;
+
+        let dir = tempdir().unwrap();
+        let options = DbOptions {
+            storage_path: dir.path().join("vectors.db").to_string_lossy().to_string(),
+            dimensions: 2,
+            ..DbOptions::default()
+        };
+        let entry = |id: &str| VectorEntry {
+            id: Some(id.to_string()),
+            vector: vec![1.0, 0.0],
+            metadata: None,
+        };
+
+        {
+            let log = Arc::new(ChangeLog::open(dir.path().join("changes")).unwrap());
+            let db = VectorDB::new(options.clone())?;
+            db.add_change_listener(Arc::new(ChangeCapture::new(log.clone(), "docs")))?;
+            db.upsert(entry("p1"))?;
+            assert_eq!(log.captured("docs"), Some(1));
+
+            // Commits, then crashes before its changes are captured
+            let crashed = VectorDB::new(options.clone())?;
+            crashed.upsert(entry("p2"))?;
+            std::mem::forget(crashed);
+        }
+
+        let log = Arc::new(ChangeLog::open(dir.path().join("changes")).unwrap());
+        let db = VectorDB::new(options)?;
+        db.add_change_listener(Arc::new(ChangeCapture::new(log.clone(), "docs")))?;
+        let events = log.read(0, 10, None).unwrap().events;
+        let replayed: Vec<_> = events
+            .iter()
+            .map(|event| (event.document_id.as_str(), event.source_sequence))
+            .collect();
+        assert_eq!(replayed, vec![("p1", 1), ("p2", 2)]);
+        assert!(events[1].before.is_none());
+        assert_eq!(log.captured("docs"), Some(2));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_consumers_resume_from_checkpoints() {
+        let dir = tempdir().unwrap();
+        let log = Arc::new(ChangeLog::open(dir.path()).unwrap());
+        log.publish("a", &[insert("p1"), insert("p2")]).unwrap();
+        log.publish("b", &[insert("p3")]).unwrap();
+
+        let mut consumer = ChangeConsumer::new(log.clone(), "c1").with_group("indexers");
+        assert_eq!(consumer.poll(1).unwrap().len(), 1);
+        consumer.commit().unwrap();
+        assert_eq!(consumer.poll(10).unwrap().len(), 2);
+
+        // Another member of the group picks up after the committed event
+        let mut replacement = ChangeConsumer::new(log.clone(), "c2").with_group("indexers");
+        assert_eq!(replacement.position(), 1);
+        let events = replacement.poll(10).unwrap();
+        assert_eq!(events[0].document_id, "p2");
+
+        // Checkpoints survive a restart
+        drop(replacement);
+        drop(consumer);
+        drop(log);
+        let log = Arc::new(ChangeLog::open(dir.path()).unwrap());
+        assert_eq!(ChangeConsumer::new(log.clone(), "c3").position(), 0);
+        let mut consumer = ChangeConsumer::new(log.clone(), "c3")
+            .with_group("indexers")
+            .with_collection("a");
+        assert_eq!(consumer.position(), 1);
+
+        // Waits past events of other collections for its own
+        let publisher = {
+            let log = log.clone();
+            tokio::spawn(async move {
+                tokio::time::sleep(Duration::from_millis(50)).await;
+                log.publish("b", &[insert("p4")]).unwrap();
+                tokio::time::sleep(Duration::from_millis(50)).await;
+                log.publish("a", &[insert("p5")]).unwrap();
+            })
+        };
+        assert_eq!(consumer.poll(10).unwrap().len(), 1);
+        let events = consumer
+            .poll_wait(10, Duration::from_secs(5))
+            .await
+            .unwrap();
+        assert_eq!(events[0].document_id, "p5");
+        publisher.await.unwrap();
+
+        assert!(consumer
+            .poll_wait(10, Duration::from_millis(50))
+            .await
+            .unwrap()
+            .is_empty());
+        consumer.seek(0);
+        assert_eq!(consumer.poll(10).unwrap().len(), 3);
+    }
+
+    fn config(max_events: Option<u64>, drop_consumed: bool) -> ChangeLogConfig {
+        ChangeLogConfig {
+            max_events,
+            max_age: None,
+            drop_consumed,
+            resident_events: 4,
+        }
+    }
+
+    #[test]
+    fn test_old_events_are_read_from_disk() {
+        let dir = tempdir().unwrap();
+        let log = ChangeLog::with_config(dir.path(), config(None, false)).unwrap();
+        let count = OFFSET_STRIDE * 2 + 10;
+        for i in 1..=count {
+            let collection = if i % 2 == 0 { "a" } else { "b" };
+            log.publish(collection, &[insert(&format!("p{}", i))])
+                .unwrap();
+        }
+        assert_eq!(log.state.read().events.len(), 4);
+
+        // Reads cross from the file into the resident events
+        let batch = log.read(OFFSET_STRIDE + 3, 1000, None).unwrap();
+        let sequences: Vec<_> = batch.events.iter().map(|e| e.sequence).collect();
+        assert_eq!(sequences, (OFFSET_STRIDE + 4..=count).collect::<Vec<_>>());
+        let batch = log.read(0, 3, Some("a")).unwrap();
+        let ids: Vec<_> = batch
+            .events
+            .iter()
+            .map(|e| e.document_id.as_str())
+            .collect();
+        assert_eq!(ids, vec!["p2", "p4", "p6"]);
+        assert_eq!(batch.next, 6);
+
+        drop(log);
+        let log = ChangeLog::with_config(dir.path(), config(None, false)).unwrap();
+        assert_eq!(log.len() as u64, count);
+        let batch = log.read(count - 6, 1000, None).unwrap();
+        assert_eq!(batch.events.len(), 6);
+        assert_eq!(batch.next, count);
+    }
+
+    #[test]
+    fn test_retention_compacts_the_log() {
+        let dir = tempdir().unwrap();
+        let log = Arc::new(ChangeLog::with_config(dir.path(), config(Some(8), false)).unwrap());
+        let compaction = log.start_compaction().unwrap();
+        assert!(log.start_compaction().is_err());
+        for i in 1..=20 {
+            log.publish("a", &[insert(&format!("p{}", i))]).unwrap();
+        }
+        // Compacts once a quarter of the log can go, so it stays near the cap
+        for _ in 0..100 {
+            if log.len() <= 10 {
+                break;
+            }
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        assert!(log.len() >= 8 && log.len() <= 10);
+        let batch = log.read(0, 100, None).unwrap();
+        assert_eq!(batch.events[0].sequence, log.first_sequence());
+        assert_eq!(batch.next, 20);
+
+        // The compaction ends with the log
+        drop(log);
+        compaction.join().unwrap();
+        let log = ChangeLog::with_config(dir.path(), config(Some(8), false)).unwrap();
+        assert_eq!(log.last_sequence(), 20);
+        assert_eq!(log.publish("a", &[insert("p21")]).unwrap(), 21);
+    }
+
+    #[test]
+    fn test_consumed_events_are_dropped() {
+        let dir = tempdir().unwrap();
+        let log = Arc::new(ChangeLog::with_config(dir.path(), config(None, true)).unwrap());
+        for i in 1..=10 {
+            log.publish("a", &[insert(&format!("p{}", i))]).unwrap();
+        }
+        let mut fast = ChangeConsumer::new(log.clone(), "fast");
+        let mut slow = ChangeConsumer::new(log.clone(), "slow");
+        fast.poll(10).unwrap();
+        fast.commit().unwrap();
+        slow.poll(4).unwrap();
+        slow.commit().unwrap();
+
+        // Only what every consumer committed is dropped
+        log.compact().unwrap();
+        assert_eq!(log.first_sequence(), 5);
+        assert_eq!(slow.poll(10).unwrap().len(), 6);
+    }
+}
@@ -3,8 +3,10 @@
 //! This crate provides comprehensive replication capabilities including:
 //! - Multi-node replica management
 //! - Synchronous, asynchronous, and semi-synchronous replication modes
+//! - Shipping writes to secondaries in-process or over TCP
 //! - Conflict resolution with vector clocks and CRDTs
-//! - Change data capture and streaming
+//! - Change data capture and streaming, with a durable change log fed by
+//!   vector database writes
 //! - Automatic failover and split-brain prevention
 //!
 //! # Examples
@@ -29,17 +31,26 @@
 //! }
 //! ```
 
+pub mod cdc;
 pub mod conflict;
 pub mod failover;
 pub mod replica;
+pub mod secondary;
 pub mod stream;
 pub mod sync;
+pub mod transport;
 
+pub use cdc::{ChangeBatch, ChangeCapture, ChangeConsumer, ChangeLog, ChangeLogConfig};
 pub use conflict::{ConflictResolver, LastWriteWins, MergeFunction, VectorClock};
 pub use failover::{FailoverManager, FailoverPolicy, HealthStatus};
 pub use replica::{Replica, ReplicaRole, ReplicaSet, ReplicaStatus};
-pub use stream::{ChangeEvent, ChangeOperation, ReplicationStream};
+pub use secondary::{SecondaryReplica, VectorOperation};
+pub use stream::{ChangeEvent, ChangeOperation, Checkpoint, ReplicationStream};
 pub use sync::{LogEntry, ReplicationLog, SyncManager, SyncMode};
+pub use transport::{
+    LocalReplicaTransport, ReplicaServer, ReplicaSnapshot, ReplicaTransport, ReplicationAck,
+    ReplicationMessage, TcpReplicaTransport,
+};
 
 use thiserror::Error;
 
@@ -85,6 +96,9 @@ pub enum ReplicationError {
     #[error("Serialization decode error: {0}")]
     SerializationDecode(#[from] bincode::error::DecodeError),
 
+    #[error("Vector database error: {0}")]
+    Database(#[from] ruvector_core::RuvectorError),
+
     #[error("IO error: {0}")]
     Io(#[from] std::io::Error),
 }
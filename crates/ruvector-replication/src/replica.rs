@@ -189,6 +189,16 @@ impl ReplicaSet {
         self.replicas.get(id).map(|r| r.clone())
     }
 
+    /// Modify a replica in place, such as to record its progress
+    pub fn update_replica(&self, id: &str, update: impl FnOnce(&mut Replica)) -> Result<()> {
+        let mut replica = self
+            .replicas
+            .get_mut(id)
+            .ok_or_else(|| ReplicationError::ReplicaNotFound(id.to_string()))?;
+        update(&mut replica);
+        Ok(())
+    }
+
     /// Get the current primary replica
     pub fn get_primary(&self) -> Option<Replica> {
         let primary_id = self.primary_id.read();
@@ -217,17 +227,19 @@ impl ReplicaSet {
 
     /// Promote a secondary to primary
     pub fn promote_to_primary(&mut self, id: &str) -> Result<()> {
-        // Get the replica and verify it exists
-        let mut replica = self
+        // Get the replica and verify it exists, without holding its entry:
+        // it may share a map shard with the old primary's
+        let role = self
             .replicas
-            .get_mut(id)
+            .get(id)
+            .map(|replica| replica.role)
             .ok_or_else(|| ReplicationError::ReplicaNotFound(id.to_string()))?;
 
-        if replica.role == ReplicaRole::Primary {
+        if role == ReplicaRole::Primary {
             return Ok(());
         }
 
-        if replica.role == ReplicaRole::Witness {
+        if role == ReplicaRole::Witness {
             return Err(ReplicationError::InvalidState(
                 "Cannot promote witness to primary".to_string(),
             ));
@@ -246,7 +258,9 @@ impl ReplicaSet {
         }
 
         // Promote new primary
-        replica.role = ReplicaRole::Primary;
+        if let Some(mut replica) = self.replicas.get_mut(id) {
+            replica.role = ReplicaRole::Primary;
+        }
         let mut primary = self.primary_id.write();
         *primary = Some(id.to_string());
 
@@ -347,6 +361,18 @@ mod tests {
         assert_eq!(set.get_secondaries().len(), 1);
     }
 
+    #[test]
+    fn test_update_replica() {
+        let mut set = ReplicaSet::new("cluster-1");
+        set.add_replica("r1", "127.0.0.1:9001", ReplicaRole::Primary)
+            .unwrap();
+
+        set.update_replica("r1", |replica| replica.update_position(42))
+            .unwrap();
+        assert_eq!(set.get_replica("r1").unwrap().log_position, 42);
+        assert!(set.update_replica("r9", |_| {}).is_err());
+    }
+
     #[test]
     fn test_promotion() {
         let mut set = ReplicaSet::new("cluster-1");
@@ -0,0 +1,557 @@
+//! Applying replicated writes on secondaries
+//!
+//! The primary records each write to its [`VectorDB`] as a
+//! [`VectorOperation`] in the [`ReplicationLog`](crate::ReplicationLog) and
+//! ships the entries to its secondaries. A [`SecondaryReplica`] applies them
+//! to its own database strictly in sequence order, one message at a time,
+//! and acknowledges the last sequence it applied. Entries it has already
+//! applied are skipped, and entries after a gap are refused, so the primary
+//! resends whatever is missing.
+//!
+//! A secondary missing entries the primary no longer holds is sent a
+//! snapshot instead, in chunks. Each chunk's points are stored as it
+//! arrives, and points the snapshot doesn't hold are only deleted once the
+//! last chunk is applied, so a restore cut short never leaves the secondary
+//! with fewer points than it had.
+
+use crate::{
+    sync::LogEntry,
+    transport::{ReplicaSnapshot, ReplicationAck, ReplicationMessage},
+    ReplicationError, Result,
+};
+use parking_lot::Mutex;
+use ruvector_core::{ScrollRequest, VectorDB, VectorEntry, VectorId};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::collections::HashSet;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use tokio::sync::{mpsc, oneshot};
+use tokio::task::JoinHandle;
+
+/// A write, as stored in the data of a log entry
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum VectorOperation {
+    /// Insert or replace points, whose ids were assigned on the primary so
+    /// every replica stores the same ones
+    Upsert(Vec<VectorEntry>),
+
+    /// Delete points by id
+    Delete(Vec<VectorId>),
+}
+
+impl VectorOperation {
+    /// Apply the write to `db`
+    pub fn apply(&self, db: &VectorDB) -> Result<()> {
+        match self {
+            Self::Upsert(entries) => {
+                db.upsert_batch(entries.clone())?;
+            }
+            Self::Delete(ids) => {
+                let ids: Vec<&str> = ids.iter().map(String::as_str).collect();
+                db.delete_batch(&ids)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Serialize for a log entry
+    pub fn encode(&self) -> Result<Vec<u8>> {
+        encode(self)
+    }
+
+    /// Deserialize from a log entry
+    pub fn decode(bytes: &[u8]) -> Result<Self> {
+        decode(bytes)
+    }
+}
+
+/// Points read from the database at a time while capturing a snapshot chunk
+const SNAPSHOT_PAGE_POINTS: usize = 128;
+
+/// A point in a snapshot chunk
+///
+/// Vectors are kept binary; metadata is JSON, since bincode can't decode
+/// self-describing values.
+#[derive(Serialize, Deserialize)]
+struct SnapshotPoint {
+    id: VectorId,
+    vector: Vec<f32>,
+    metadata: Option<Vec<u8>>,
+}
+
+impl ReplicaSnapshot {
+    /// Capture the chunk of `db` starting at scroll `offset`, `None` for the
+    /// first, as part of a snapshot at log position `sequence`
+    ///
+    /// Points are read a page at a time until the chunk holds about
+    /// `max_bytes` of them, so it is larger by at most one page.
+    pub fn capture(
+        db: &VectorDB,
+        sequence: u64,
+        offset: Option<String>,
+        max_bytes: usize,
+    ) -> Result<Self> {
+        let mut points = Vec::new();
+        let mut bytes = 0;
+        let mut next_offset = offset.clone();
+        loop {
+            let page = db.scroll(ScrollRequest {
+                offset: next_offset,
+                limit: SNAPSHOT_PAGE_POINTS,
+                filter: None,
+                with_vector: true,
+                with_payload: true,
+            })?;
+            next_offset = page.next_offset;
+
+            for record in page.points {
+                let point = SnapshotPoint {
+                    id: record.id,
+                    vector: record.vector.unwrap_or_default(),
+                    metadata: record.metadata.as_ref().map(encode).transpose()?,
+                };
+                bytes += point.id.len()
+                    + point.vector.len() * std::mem::size_of::<f32>()
+                    + point.metadata.as_ref().map_or(0, Vec::len);
+                points.push(point);
+            }
+            if next_offset.is_none() || bytes >= max_bytes {
+                break;
+            }
+        }
+
+        Ok(Self {
+            sequence,
+            offset,
+            next_offset,
+            data: bincode::serde::encode_to_vec(&points, bincode::config::standard())?,
+        })
+    }
+
+    /// The chunk's points
+    pub fn entries(&self) -> Result<Vec<VectorEntry>> {
+        let (points, _): (Vec<SnapshotPoint>, _) =
+            bincode::serde::decode_from_slice(&self.data, bincode::config::standard())?;
+        points
+            .into_iter()
+            .map(|point| {
+                Ok(VectorEntry {
+                    id: Some(point.id),
+                    vector: point.vector,
+                    metadata: point.metadata.as_deref().map(decode).transpose()?,
+                })
+            })
+            .collect()
+    }
+}
+
+type ApplyRequest = (ReplicationMessage, oneshot::Sender<Result<ReplicationAck>>);
+
+/// The receiving side of replication, applying a primary's writes to a
+/// local database
+///
+/// Messages passed to [`receive`](Self::receive) are queued for the apply
+/// loop, which must be started with [`start`](Self::start).
+pub struct SecondaryReplica {
+    replica_id: String,
+    db: Arc<VectorDB>,
+    /// Sequence of the last entry applied
+    position: Arc<AtomicU64>,
+    tx: mpsc::UnboundedSender<ApplyRequest>,
+    rx: Mutex<Option<mpsc::UnboundedReceiver<ApplyRequest>>>,
+}
+
+impl SecondaryReplica {
+    /// Apply writes to `db` as replica `replica_id`, starting from an empty
+    /// log
+    pub fn new(replica_id: impl Into<String>, db: Arc<VectorDB>) -> Self {
+        let (tx, rx) = mpsc::unbounded_channel();
+        Self {
+            replica_id: replica_id.into(),
+            db,
+            position: Arc::new(AtomicU64::new(0)),
+            tx,
+            rx: Mutex::new(Some(rx)),
+        }
+    }
+
+    /// Start the apply loop on the current runtime
+    ///
+    /// The loop ends when the replica is dropped.
+    ///
+    /// # Errors
+    ///
+    /// Returns `InvalidState` if the loop was already started
+    pub fn start(&self) -> Result<JoinHandle<()>> {
+        let mut rx = self.rx.lock().take().ok_or_else(|| {
+            ReplicationError::InvalidState(format!(
+                "Replica {} is already applying",
+                self.replica_id
+            ))
+        })?;
+        let mut applier = Applier {
+            replica_id: self.replica_id.clone(),
+            db: self.db.clone(),
+            position: self.position.clone(),
+            restore: None,
+        };
+
+        Ok(tokio::spawn(async move {
+            while let Some((message, ack_tx)) = rx.recv().await {
+                let result = applier.apply(message);
+                if let Err(e) = &result {
+                    tracing::error!("Replica {} failed to apply: {}", applier.replica_id, e);
+                }
+                let _ = ack_tx.send(result);
+            }
+        }))
+    }
+
+    /// Apply a message from the primary, returning the acknowledgement once
+    /// the apply loop processed it
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if an entry is corrupt or can't be applied, or the
+    /// apply loop isn't running
+    pub async fn receive(&self, message: ReplicationMessage) -> Result<ReplicationAck> {
+        let (ack_tx, ack_rx) = oneshot::channel();
+        self.tx
+            .send((message, ack_tx))
+            .map_err(|_| self.not_applying())?;
+        ack_rx.await.map_err(|_| self.not_applying())?
+    }
+
+    /// This replica's id
+    pub fn replica_id(&self) -> &str {
+        &self.replica_id
+    }
+
+    /// Sequence of the last entry applied
+    pub fn position(&self) -> u64 {
+        self.position.load(Ordering::Acquire)
+    }
+
+    /// The database writes are applied to
+    pub fn db(&self) -> &Arc<VectorDB> {
+        &self.db
+    }
+
+    fn not_applying(&self) -> ReplicationError {
+        ReplicationError::InvalidState(format!("Replica {} is not applying", self.replica_id))
+    }
+}
+
+/// State owned by the apply loop
+struct Applier {
+    replica_id: String,
+    db: Arc<VectorDB>,
+    position: Arc<AtomicU64>,
+    /// Snapshot whose chunks are being applied
+    restore: Option<Restore>,
+}
+
+/// Progress through the chunks of a snapshot
+struct Restore {
+    sequence: u64,
+    /// Where the next chunk must start
+    next_offset: Option<String>,
+    /// Ids of the points in the chunks applied so far
+    ids: HashSet<VectorId>,
+}
+
+impl Applier {
+    fn apply(&mut self, message: ReplicationMessage) -> Result<ReplicationAck> {
+        match message {
+            ReplicationMessage::Entries(entries) => {
+                for entry in &entries {
+                    if !self.apply_entry(entry)? {
+                        break;
+                    }
+                }
+            }
+            ReplicationMessage::Snapshot(chunk) => {
+                // An older snapshot would undo entries applied since
+                if chunk.sequence > self.position.load(Ordering::Acquire) {
+                    self.apply_chunk(chunk)?;
+                }
+            }
+        }
+
+        Ok(ReplicationAck {
+            replica_id: self.replica_id.clone(),
+            position: self.position.load(Ordering::Acquire),
+        })
+    }
+
+    /// Apply `entry` if it is the next in sequence, returning false if
+    /// entries before it are missing
+    fn apply_entry(&self, entry: &LogEntry) -> Result<bool> {
+        let position = self.position.load(Ordering::Acquire);
+        if entry.sequence <= position {
+            return Ok(true);
+        }
+        if entry.sequence != position + 1 {
+            tracing::debug!(
+                "Replica {} at {} is missing entries before {}",
+                self.replica_id,
+                position,
+                entry.sequence
+            );
+            return Ok(false);
+        }
+        if !entry.verify() {
+            return Err(ReplicationError::SyncFailed(format!(
+                "Entry {} failed its checksum",
+                entry.sequence
+            )));
+        }
+
+        VectorOperation::decode(&entry.data)?.apply(&self.db)?;
+        self.position.store(entry.sequence, Ordering::Release);
+        Ok(true)
+    }
+
+    /// Store the points of a snapshot chunk, and once it is the last one,
+    /// delete the points the snapshot doesn't hold and resume after it
+    ///
+    /// A first chunk starts the restore over. A chunk that doesn't follow
+    /// the previous one abandons the restore, and the primary has to send
+    /// the snapshot again.
+    fn apply_chunk(&mut self, chunk: ReplicaSnapshot) -> Result<()> {
+        let mut restore = match (&chunk.offset, self.restore.take()) {
+            (None, _) => Restore {
+                sequence: chunk.sequence,
+                next_offset: None,
+                ids: HashSet::new(),
+            },
+            (Some(offset), Some(restore))
+                if restore.sequence == chunk.sequence
+                    && restore.next_offset.as_ref() == Some(offset) =>
+            {
+                restore
+            }
+            (Some(_), _) => {
+                return Err(ReplicationError::SyncFailed(format!(
+                    "Snapshot chunk at {} doesn't follow the chunks applied",
+                    chunk.sequence
+                )))
+            }
+        };
+
+        let entries = chunk.entries()?;
+        restore
+            .ids
+            .extend(entries.iter().filter_map(|entry| entry.id.clone()));
+        if !entries.is_empty() {
+            self.db.upsert_batch(entries)?;
+        }
+
+        if chunk.next_offset.is_some() {
+            restore.next_offset = chunk.next_offset;
+            self.restore = Some(restore);
+            return Ok(());
+        }
+
+        let stale: Vec<String> = self
+            .db
+            .keys()?
+            .into_iter()
+            .filter(|id| !restore.ids.contains(id))
+            .collect();
+        let stale: Vec<&str> = stale.iter().map(String::as_str).collect();
+        self.db.delete_batch(&stale)?;
+        self.position.store(restore.sequence, Ordering::Release);
+        tracing::info!(
+            "Replica {} restored a snapshot at {}",
+            self.replica_id,
+            restore.sequence
+        );
+        Ok(())
+    }
+}
+
+// JSON, since metadata values are self-describing
+fn encode<T: Serialize>(value: &T) -> Result<Vec<u8>> {
+    serde_json::to_vec(value)
+        .map_err(|e| ReplicationError::InvalidState(format!("Encoding failed: {}", e)))
+}
+
+fn decode<T: DeserializeOwned>(bytes: &[u8]) -> Result<T> {
+    serde_json::from_slice(bytes)
+        .map_err(|e| ReplicationError::InvalidState(format!("Decoding failed: {}", e)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ruvector_core::types::DbOptions;
+    use ruvector_core::DistanceMetric;
+    use std::path::Path;
+    use tempfile::tempdir;
+
+    fn db(dir: &Path) -> Arc<VectorDB> {
+        Arc::new(
+            VectorDB::new(DbOptions {
+                dimensions: 2,
+                distance_metric: DistanceMetric::Euclidean,
+                storage_path: dir.join("vectors.db").to_string_lossy().into_owned(),
+                quantization: None,
+                ..DbOptions::default()
+            })
+            .unwrap(),
+        )
+    }
+
+    fn upsert(sequence: u64, id: &str) -> LogEntry {
+        let operation = VectorOperation::Upsert(vec![VectorEntry {
+            id: Some(id.to_string()),
+            vector: vec![sequence as f32, 0.0],
+            metadata: None,
+        }]);
+        LogEntry::new(sequence, operation.encode().unwrap(), "r1".to_string())
+    }
+
+    #[tokio::test]
+    async fn test_entries_apply_in_sequence() {
+        let dir = tempdir().unwrap();
+        let replica = SecondaryReplica::new("r2", db(dir.path()));
+        replica.start().unwrap();
+        assert!(replica.start().is_err());
+
+        let ack = replica
+            .receive(ReplicationMessage::Entries(vec![
+                upsert(1, "a"),
+                upsert(2, "b"),
+            ]))
+            .await
+            .unwrap();
+        assert_eq!(ack.position, 2);
+
+        // A gap stops the batch; duplicates are skipped
+        let ack = replica
+            .receive(ReplicationMessage::Entries(vec![upsert(4, "d")]))
+            .await
+            .unwrap();
+        assert_eq!(ack.position, 2);
+        let ack = replica
+            .receive(ReplicationMessage::Entries(vec![
+                upsert(2, "b"),
+                upsert(3, "c"),
+                upsert(4, "d"),
+            ]))
+            .await
+            .unwrap();
+        assert_eq!(ack.position, 4);
+        assert_eq!(replica.db().len().unwrap(), 4);
+
+        let mut corrupt = upsert(5, "e");
+        corrupt.data.push(b' ');
+        assert!(replica
+            .receive(ReplicationMessage::Entries(vec![corrupt]))
+            .await
+            .is_err());
+        assert_eq!(replica.position(), 4);
+    }
+
+    #[tokio::test]
+    async fn test_snapshot_replaces_points() {
+        let primary_dir = tempdir().unwrap();
+        let primary = db(primary_dir.path());
+        VectorOperation::Upsert(vec![VectorEntry {
+            id: Some("x".to_string()),
+            vector: vec![1.0, 1.0],
+            metadata: None,
+        }])
+        .apply(&primary)
+        .unwrap();
+        let snapshot = ReplicaSnapshot::capture(&primary, 7, None, usize::MAX).unwrap();
+
+        let dir = tempdir().unwrap();
+        let replica = SecondaryReplica::new("r2", db(dir.path()));
+        replica.start().unwrap();
+        replica
+            .receive(ReplicationMessage::Entries(vec![upsert(1, "a")]))
+            .await
+            .unwrap();
+
+        let ack = replica
+            .receive(ReplicationMessage::Snapshot(snapshot.clone()))
+            .await
+            .unwrap();
+        assert_eq!(ack.position, 7);
+        assert!(replica.db().get("a").unwrap().is_none());
+        assert!(replica.db().get("x").unwrap().is_some());
+
+        // Replayed entries and stale snapshots change nothing
+        let ack = replica
+            .receive(ReplicationMessage::Entries(vec![upsert(7, "y")]))
+            .await
+            .unwrap();
+        assert_eq!(ack.position, 7);
+        let mut stale = snapshot;
+        stale.sequence = 3;
+        replica
+            .receive(ReplicationMessage::Snapshot(stale))
+            .await
+            .unwrap();
+        assert_eq!(replica.position(), 7);
+        assert_eq!(replica.db().len().unwrap(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_snapshot_chunks_keep_points_until_the_last() {
+        let primary_dir = tempdir().unwrap();
+        let primary = db(primary_dir.path());
+        let entries = (0..300)
+            .map(|i| VectorEntry {
+                id: Some(format!("p{:03}", i)),
+                vector: vec![i as f32, 0.0],
+                metadata: Some([("n".to_string(), serde_json::json!(i))].into()),
+            })
+            .collect();
+        VectorOperation::Upsert(entries).apply(&primary).unwrap();
+
+        let dir = tempdir().unwrap();
+        let replica = SecondaryReplica::new("r2", db(dir.path()));
+        replica.start().unwrap();
+        replica
+            .receive(ReplicationMessage::Entries(vec![upsert(1, "a")]))
+            .await
+            .unwrap();
+
+        // Every page fills a chunk
+        let first = ReplicaSnapshot::capture(&primary, 9, None, 1).unwrap();
+        let second = ReplicaSnapshot::capture(&primary, 9, first.next_offset.clone(), 1).unwrap();
+        let third = ReplicaSnapshot::capture(&primary, 9, second.next_offset.clone(), 1).unwrap();
+        assert!(third.next_offset.is_none());
+
+        // A chunk that doesn't follow the previous one is refused
+        assert!(replica
+            .receive(ReplicationMessage::Snapshot(second.clone()))
+            .await
+            .is_err());
+
+        for chunk in [first, second] {
+            let ack = replica
+                .receive(ReplicationMessage::Snapshot(chunk))
+                .await
+                .unwrap();
+            assert_eq!(ack.position, 1);
+        }
+        // Points the snapshot doesn't hold outlast every chunk but the last
+        assert!(replica.db().get("a").unwrap().is_some());
+        assert_eq!(replica.db().len().unwrap(), 257);
+
+        let ack = replica
+            .receive(ReplicationMessage::Snapshot(third))
+            .await
+            .unwrap();
+        assert_eq!(ack.position, 9);
+        assert!(replica.db().get("a").unwrap().is_none());
+        assert_eq!(replica.db().len().unwrap(), 300);
+        let point = replica.db().get("p042").unwrap().unwrap();
+        assert_eq!(point.vector, vec![42.0, 0.0]);
+        assert_eq!(point.metadata.unwrap()["n"], 42);
+    }
+}
@@ -6,6 +6,7 @@
 use crate::{LogEntry, ReplicationError, ReplicationLog, Result};
 use chrono::{DateTime, Utc};
 use parking_lot::RwLock;
+use ruvector_core::{PointChange, PointChangeKind, VectorEntry};
 use serde::{Deserialize, Serialize};
 use std::sync::Arc;
 use tokio::sync::mpsc;
@@ -43,6 +44,16 @@ pub struct ChangeEvent {
     pub data: Vec<u8>,
     /// Metadata for the change
     pub metadata: serde_json::Value,
+    /// The point before the change, for updates and deletes of a point
+    #[serde(default)]
+    pub before: Option<VectorEntry>,
+    /// The point after the change, for inserts and updates of a point
+    #[serde(default)]
+    pub after: Option<VectorEntry>,
+    /// Sequence of the database operation that made the change, 0 if
+    /// unknown
+    #[serde(default)]
+    pub source_sequence: u64,
 }
 
 impl ChangeEvent {
@@ -63,6 +74,30 @@ impl ChangeEvent {
             document_id,
             data,
             metadata: serde_json::Value::Null,
+            before: None,
+            after: None,
+            source_sequence: 0,
+        }
+    }
+
+    /// Convert from a change committed to a point of `collection`
+    pub fn from_point_change(sequence: u64, collection: String, change: &PointChange) -> Self {
+        let operation = match change.kind {
+            PointChangeKind::Insert => ChangeOperation::Insert,
+            PointChangeKind::Update => ChangeOperation::Update,
+            PointChangeKind::Delete => ChangeOperation::Delete,
+        };
+        Self {
+            before: change.before.clone(),
+            after: change.after.clone(),
+            source_sequence: change.sequence,
+            ..Self::new(
+                sequence,
+                operation,
+                collection,
+                change.id.clone(),
+                Vec::new(),
+            )
         }
     }
 
@@ -91,6 +126,9 @@ impl ChangeEvent {
                 "source_replica": entry.source_replica,
                 "checksum": entry.checksum,
             }),
+            before: None,
+            after: None,
+            source_sequence: 0,
         }
     }
 }
@@ -347,6 +385,30 @@ mod tests {
         assert_eq!(event.sequence, 1);
         assert_eq!(event.operation, ChangeOperation::Insert);
         assert_eq!(event.collection, "vectors");
+        assert!(event.before.is_none() && event.after.is_none());
+    }
+
+    #[test]
+    fn test_change_event_from_point_change() {
+        let after = VectorEntry {
+            id: Some("doc-1".to_string()),
+            vector: vec![1.0, 2.0],
+            metadata: None,
+        };
+        let change = PointChange {
+            kind: PointChangeKind::Insert,
+            id: "doc-1".to_string(),
+            sequence: 3,
+            before: None,
+            after: Some(after),
+        };
+        let event = ChangeEvent::from_point_change(7, "vectors".to_string(), &change);
+
+        assert_eq!(event.sequence, 7);
+        assert_eq!(event.source_sequence, 3);
+        assert_eq!(event.operation, ChangeOperation::Insert);
+        assert_eq!(event.document_id, "doc-1");
+        assert_eq!(event.after.unwrap().vector, vec![1.0, 2.0]);
     }
 
     #[test]
@@ -3,13 +3,19 @@
 //! Provides different replication modes (sync, async, semi-sync)
 //! and manages the replication log for tracking changes.
 
-use crate::{ReplicaSet, ReplicationError, Result};
+use crate::{
+    secondary::VectorOperation,
+    transport::{ReplicaSnapshot, ReplicaTransport, ReplicationAck, ReplicationMessage},
+    Replica, ReplicaSet, ReplicaStatus, ReplicationError, Result,
+};
 use chrono::{DateTime, Utc};
 use dashmap::DashMap;
-use parking_lot::RwLock;
+use parking_lot::{Mutex, RwLock};
+use ruvector_core::VectorDB;
 use serde::{Deserialize, Serialize};
 use std::sync::Arc;
 use std::time::Duration;
+use tokio::sync::mpsc;
 use tokio::time::timeout;
 use uuid::Uuid;
 
@@ -76,6 +82,8 @@ pub struct ReplicationLog {
     entries: Arc<DashMap<u64, LogEntry>>,
     /// Current sequence number
     sequence: Arc<RwLock<u64>>,
+    /// Lowest sequence not truncated
+    first_sequence: Arc<RwLock<u64>>,
     /// Replica ID
     replica_id: String,
 }
@@ -86,6 +94,7 @@ impl ReplicationLog {
         Self {
             entries: Arc::new(DashMap::new()),
             sequence: Arc::new(RwLock::new(0)),
+            first_sequence: Arc::new(RwLock::new(1)),
             replica_id: replica_id.into(),
         }
     }
@@ -128,40 +137,106 @@ impl ReplicationLog {
 
     /// Truncate log before a given sequence
     pub fn truncate_before(&self, before: u64) {
+        {
+            let current = self.sequence.read();
+            let mut first = self.first_sequence.write();
+            *first = (*first).max(before.min(*current + 1));
+        }
         self.entries.retain(|seq, _| *seq >= before);
     }
 
+    /// Get the lowest sequence still in the log; entries before it were
+    /// truncated
+    pub fn first_sequence(&self) -> u64 {
+        *self.first_sequence.read()
+    }
+
     /// Get log size
     pub fn size(&self) -> usize {
         self.entries.len()
     }
 }
 
+/// Rounds of catching up a replica before giving up on it
+const MAX_SHIPPING_ROUNDS: usize = 3;
+
+/// Default size of the points in one snapshot chunk
+const DEFAULT_SNAPSHOT_CHUNK_BYTES: usize = 16 * 1024 * 1024;
+
 /// Manages synchronization across replicas
+///
+/// Entries are shipped to every secondary through the configured
+/// [`ReplicaTransport`]. A secondary missing earlier entries receives those
+/// too, or a snapshot of the database in chunks if they were truncated from
+/// the log, and its acknowledgements update its position and lag in the
+/// replica set.
 pub struct SyncManager {
-    /// The replica set
-    replica_set: Arc<ReplicaSet>,
-    /// Replication log
-    log: Arc<ReplicationLog>,
+    /// What entries are shipped with
+    shipper: Shipper,
     /// Synchronization mode
     sync_mode: Arc<RwLock<SyncMode>>,
     /// Timeout for synchronous operations
     sync_timeout: Duration,
+    /// Held while a write is applied and logged
+    write_lock: Mutex<()>,
+}
+
+/// Everything needed to bring a secondary up to date, shared with the
+/// tasks shipping entries
+#[derive(Clone)]
+struct Shipper {
+    replica_set: Arc<ReplicaSet>,
+    log: Arc<ReplicationLog>,
+    transport: Option<Arc<dyn ReplicaTransport>>,
+    database: Option<Arc<VectorDB>>,
+    snapshot_chunk_bytes: usize,
 }
 
 impl SyncManager {
     /// Create a new sync manager
     pub fn new(replica_set: Arc<ReplicaSet>, log: Arc<ReplicationLog>) -> Self {
         Self {
-            replica_set,
-            log,
+            shipper: Shipper {
+                replica_set,
+                log,
+                transport: None,
+                database: None,
+                snapshot_chunk_bytes: DEFAULT_SNAPSHOT_CHUNK_BYTES,
+            },
             sync_mode: Arc::new(RwLock::new(SyncMode::Async)),
             sync_timeout: Duration::from_secs(5),
+            write_lock: Mutex::new(()),
         }
     }
 
+    /// Ship entries to secondaries through `transport`
+    pub fn with_transport(mut self, transport: Arc<dyn ReplicaTransport>) -> Self {
+        self.shipper.transport = Some(transport);
+        self
+    }
+
+    /// Apply writes to the primary's `database`, and snapshot it for
+    /// secondaries whose missing entries were truncated
+    pub fn with_database(mut self, database: Arc<VectorDB>) -> Self {
+        self.shipper.database = Some(database);
+        self
+    }
+
+    /// Send snapshots in chunks of about `bytes` of points, 16 MiB by
+    /// default; keep it well below the transport's frame limit
+    pub fn with_snapshot_chunk_bytes(mut self, bytes: usize) -> Self {
+        self.shipper.snapshot_chunk_bytes = bytes;
+        self
+    }
+
     /// Set the synchronization mode
     pub fn set_sync_mode(&self, mode: SyncMode) {
+        if self.shipper.transport.is_none() {
+            tracing::warn!(
+                "No replica transport configured, {:?} writes won't reach secondaries",
+                mode
+            );
+        }
         *self.sync_mode.write() = mode;
     }
 
@@ -175,107 +250,163 @@ impl SyncManager {
         self.sync_timeout = timeout;
     }
 
-    /// Replicate data to all replicas according to sync mode
-    pub async fn replicate(&self, data: Vec<u8>) -> Result<LogEntry> {
-        // Append to local log
-        let entry = self.log.append(data);
-
-        // Get sync mode
-        let mode = self.sync_mode();
-
-        match mode {
-            SyncMode::Sync => {
-                self.replicate_sync(&entry).await?;
-            }
-            SyncMode::Async => {
-                // Fire and forget
-                let entry_clone = entry.clone();
-                let replica_set = self.replica_set.clone();
-                tokio::spawn(async move {
-                    if let Err(e) = Self::send_to_replicas(&replica_set, &entry_clone).await {
-                        tracing::error!("Async replication failed: {}", e);
-                    }
-                });
-            }
-            SyncMode::SemiSync { min_replicas } => {
-                self.replicate_semi_sync(&entry, min_replicas).await?;
+    /// Apply a write to the primary's database and replicate it
+    ///
+    /// Points without an id are assigned one first, so every replica
+    /// stores the same ones.
+    ///
+    /// # Errors
+    ///
+    /// Returns `InvalidState` if there is no database, or an error if the
+    /// write can't be applied or too few secondaries acknowledged it for
+    /// the sync mode; in the latter case it is still applied and logged
+    pub async fn write(&self, mut operation: VectorOperation) -> Result<LogEntry> {
+        let database = self.shipper.database.as_ref().ok_or_else(|| {
+            ReplicationError::InvalidState("No database to apply writes to".to_string())
+        })?;
+        if let VectorOperation::Upsert(entries) = &mut operation {
+            for entry in entries {
+                entry.id.get_or_insert_with(|| Uuid::new_v4().to_string());
             }
         }
+        let data = operation.encode()?;
+
+        let entry = {
+            // Applied and logged together, so secondaries apply writes in
+            // the order the primary did
+            let _guard = self.write_lock.lock();
+            operation.apply(database)?;
+            self.shipper.log.append(data)
+        };
 
+        self.distribute(&entry).await?;
         Ok(entry)
     }
 
-    /// Synchronous replication - wait for all replicas
-    async fn replicate_sync(&self, entry: &LogEntry) -> Result<()> {
-        timeout(
-            self.sync_timeout,
-            Self::send_to_replicas(&self.replica_set, entry),
-        )
-        .await
-        .map_err(|_| ReplicationError::Timeout("Sync replication timed out".to_string()))?
+    /// Replicate data to all replicas according to sync mode
+    ///
+    /// A [`SecondaryReplica`](crate::SecondaryReplica) expects `data` to be
+    /// an encoded [`VectorOperation`].
+    pub async fn replicate(&self, data: Vec<u8>) -> Result<LogEntry> {
+        // Append to local log
+        let entry = self.shipper.log.append(data);
+        self.distribute(&entry).await?;
+        Ok(entry)
     }
 
-    /// Semi-synchronous replication - wait for minimum replicas
-    async fn replicate_semi_sync(&self, entry: &LogEntry, min_replicas: usize) -> Result<()> {
-        let secondaries = self.replica_set.get_secondaries();
-        if secondaries.len() < min_replicas {
+    /// Ship `entry` to every secondary, waiting for as many
+    /// acknowledgements as the sync mode requires
+    async fn distribute(&self, entry: &LogEntry) -> Result<()> {
+        let secondaries = self.shipper.replica_set.get_secondaries();
+        let (needed, mode) = match self.sync_mode() {
+            SyncMode::Sync => (secondaries.len(), "Sync"),
+            SyncMode::Async => (0, "Async"),
+            SyncMode::SemiSync { min_replicas } => (min_replicas, "Semi-sync"),
+        };
+        if secondaries.len() < needed {
             return Err(ReplicationError::QuorumNotMet {
-                needed: min_replicas,
+                needed,
                 available: secondaries.len(),
             });
         }
 
-        // Send to all and wait for min_replicas to respond
-        let entry_clone = entry.clone();
-        let replica_set = self.replica_set.clone();
-        let min = min_replicas;
+        // Without a transport there is nothing to ship with; that was
+        // warned about when the sync mode was set
+        if self.shipper.transport.is_none() {
+            return if needed == 0 {
+                Ok(())
+            } else {
+                Err(ReplicationError::QuorumNotMet {
+                    needed,
+                    available: 0,
+                })
+            };
+        }
+
+        let total = secondaries.len();
+        let mut results = self.ship_to_all(secondaries, entry.sequence);
+        if needed == 0 {
+            return Ok(());
+        }
 
+        // Secondaries not waited for keep catching up in the background
         timeout(self.sync_timeout, async move {
-            // Simulate sending to replicas and waiting for acknowledgments
-            // In a real implementation, this would use network calls
-            let acks = secondaries.len().min(min);
-            if acks >= min {
+            let mut acked = 0;
+            let mut failed = 0;
+            while acked < needed && total - failed >= needed {
+                match results.recv().await {
+                    Some(Ok(_)) => acked += 1,
+                    Some(Err(_)) => failed += 1,
+                    None => break,
+                }
+            }
+            if acked >= needed {
                 Ok(())
             } else {
                 Err(ReplicationError::QuorumNotMet {
-                    needed: min,
-                    available: acks,
+                    needed,
+                    available: acked,
                 })
             }
         })
         .await
-        .map_err(|_| ReplicationError::Timeout("Semi-sync replication timed out".to_string()))?
+        .map_err(|_| ReplicationError::Timeout(format!("{} replication timed out", mode)))?
     }
 
-    /// Send log entry to all replicas
-    async fn send_to_replicas(replica_set: &ReplicaSet, entry: &LogEntry) -> Result<()> {
-        let secondaries = replica_set.get_secondaries();
-
-        // In a real implementation, this would send over the network
-        // For now, we simulate successful replication
-        for replica in secondaries {
-            if replica.is_healthy() {
-                tracing::debug!("Replicating entry {} to {}", entry.sequence, replica.id);
-            }
+    /// Ship the entry at `sequence` to each of `replicas` in its own task,
+    /// returning a receiver of their results
+    fn ship_to_all(
+        &self,
+        replicas: Vec<Replica>,
+        sequence: u64,
+    ) -> mpsc::UnboundedReceiver<Result<u64>> {
+        let (tx, rx) = mpsc::unbounded_channel();
+        for replica in replicas {
+            let shipper = self.shipper.clone();
+            let tx = tx.clone();
+            tokio::spawn(async move {
+                let result = shipper.ship(&replica, sequence).await;
+                if let Err(e) = &result {
+                    tracing::warn!(
+                        "Replicating entry {} to {} failed: {}",
+                        sequence,
+                        replica.id,
+                        e
+                    );
+                }
+                let _ = tx.send(result);
+            });
         }
-
-        Ok(())
+        rx
     }
 
     /// Catch up a lagging replica
+    ///
+    /// Returns the entries after `from_sequence`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the replica is unknown, or `SyncFailed` if some
+    /// of the entries were truncated from the log; use
+    /// [`SyncManager::catchup_message`] to fall back to a snapshot
     pub async fn catchup(&self, replica_id: &str, from_sequence: u64) -> Result<Vec<LogEntry>> {
-        let replica = self
-            .replica_set
-            .get_replica(replica_id)
-            .ok_or_else(|| ReplicationError::ReplicaNotFound(replica_id.to_string()))?;
+        if self.shipper.replica_set.get_replica(replica_id).is_none() {
+            return Err(ReplicationError::ReplicaNotFound(replica_id.to_string()));
+        }
 
-        let current_sequence = self.log.current_sequence();
+        let current_sequence = self.shipper.log.current_sequence();
         if from_sequence >= current_sequence {
             return Ok(Vec::new());
         }
+        if from_sequence + 1 < self.shipper.log.first_sequence() {
+            return Err(ReplicationError::SyncFailed(format!(
+                "Entries after {} were truncated from the log",
+                from_sequence
+            )));
+        }
 
         // Get missing entries
-        let entries = self.log.get_since(from_sequence);
+        let entries = self.shipper.log.get_since(from_sequence);
 
         tracing::info!(
             "Catching up replica {} with {} entries (from {} to {})",
@@ -288,14 +419,55 @@ impl SyncManager {
         Ok(entries)
     }
 
+    /// What a replica at `from_sequence` is missing: the entries after it,
+    /// or the first chunk of a snapshot of the database if some of those
+    /// were truncated from the log
+    ///
+    /// [`SyncManager::sync_replica`] sends every chunk of the snapshot.
+    pub async fn catchup_message(
+        &self,
+        replica_id: &str,
+        from_sequence: u64,
+    ) -> Result<ReplicationMessage> {
+        if self.shipper.replica_set.get_replica(replica_id).is_none() {
+            return Err(ReplicationError::ReplicaNotFound(replica_id.to_string()));
+        }
+
+        let current_sequence = self.shipper.log.current_sequence();
+        if from_sequence >= current_sequence {
+            return Ok(ReplicationMessage::Entries(Vec::new()));
+        }
+
+        match self.shipper.entries_since(from_sequence) {
+            Some(entries) => Ok(ReplicationMessage::Entries(entries)),
+            None => Ok(ReplicationMessage::Snapshot(
+                self.shipper
+                    .snapshot_chunk(self.shipper.log.current_sequence(), None)?,
+            )),
+        }
+    }
+
+    /// Ship a secondary everything it is missing, such as after it was
+    /// unreachable, returning the position it acknowledged
+    pub async fn sync_replica(&self, replica_id: &str) -> Result<u64> {
+        let replica = self
+            .shipper
+            .replica_set
+            .get_replica(replica_id)
+            .ok_or_else(|| ReplicationError::ReplicaNotFound(replica_id.to_string()))?;
+        let target = self.shipper.log.current_sequence();
+        self.shipper.ship(&replica, target).await
+    }
+
     /// Get the current log position
     pub fn current_position(&self) -> u64 {
-        self.log.current_sequence()
+        self.shipper.log.current_sequence()
     }
 
     /// Verify log entry integrity
     pub fn verify_entry(&self, sequence: u64) -> Result<bool> {
         let entry = self
+            .shipper
             .log
             .get(sequence)
             .ok_or_else(|| ReplicationError::InvalidState("Log entry not found".to_string()))?;
@@ -303,6 +475,145 @@ impl SyncManager {
     }
 }
 
+impl Shipper {
+    /// Bring `replica` up to log position `target`, returning the position
+    /// it acknowledged
+    ///
+    /// Only the entry at `target` is sent at first; a replica missing
+    /// earlier ones is then sent everything after its position, or a
+    /// snapshot if some of that was truncated.
+    async fn ship(&self, replica: &Replica, target: u64) -> Result<u64> {
+        let transport = self.transport.as_ref().ok_or_else(|| {
+            ReplicationError::Network("No replica transport configured".to_string())
+        })?;
+
+        let mut ack = self
+            .send(
+                transport,
+                replica,
+                ReplicationMessage::Entries(self.log.get_range(target, target)),
+            )
+            .await?;
+        for round in 1..=MAX_SHIPPING_ROUNDS {
+            self.record_ack(&ack);
+            if ack.position >= target || round == MAX_SHIPPING_ROUNDS {
+                break;
+            }
+            ack = match self.entries_since(ack.position) {
+                Some(entries) => {
+                    self.send(transport, replica, ReplicationMessage::Entries(entries))
+                        .await?
+                }
+                None => self.send_snapshot(transport, replica).await?,
+            };
+        }
+
+        if ack.position >= target {
+            Ok(ack.position)
+        } else {
+            Err(ReplicationError::SyncFailed(format!(
+                "Replica {} didn't catch up to {}",
+                replica.id, target
+            )))
+        }
+    }
+
+    /// Send `message` to `replica`, marking it offline if it can't be
+    /// reached
+    async fn send(
+        &self,
+        transport: &Arc<dyn ReplicaTransport>,
+        replica: &Replica,
+        message: ReplicationMessage,
+    ) -> Result<ReplicationAck> {
+        transport.send(replica, message).await.inspect_err(|e| {
+            if matches!(
+                e,
+                ReplicationError::Network(_) | ReplicationError::Timeout(_)
+            ) {
+                let _ = self.replica_set.update_replica(&replica.id, |replica| {
+                    replica.status = ReplicaStatus::Offline;
+                });
+            }
+        })
+    }
+
+    /// Send `replica` every chunk of a snapshot of the database, returning
+    /// its acknowledgement of the last
+    async fn send_snapshot(
+        &self,
+        transport: &Arc<dyn ReplicaTransport>,
+        replica: &Replica,
+    ) -> Result<ReplicationAck> {
+        // Read before the points, which then include every write up to it.
+        // Writes after it may be included too; replaying them gives the same
+        // points, since each one replaces or deletes whole points.
+        let sequence = self.log.current_sequence();
+        tracing::info!(
+            "Catching up replica {} with a snapshot at {}",
+            replica.id,
+            sequence
+        );
+
+        let mut offset = None;
+        loop {
+            let chunk = self.snapshot_chunk(sequence, offset)?;
+            offset = chunk.next_offset.clone();
+            let ack = self
+                .send(transport, replica, ReplicationMessage::Snapshot(chunk))
+                .await?;
+            if offset.is_none() {
+                return Ok(ack);
+            }
+        }
+    }
+
+    /// The entries after `from_sequence`, or `None` if some of them were
+    /// truncated
+    fn entries_since(&self, from_sequence: u64) -> Option<Vec<LogEntry>> {
+        (from_sequence + 1 >= self.log.first_sequence()).then(|| self.log.get_since(from_sequence))
+    }
+
+    /// The chunk of a snapshot at `sequence` starting at scroll `offset`
+    fn snapshot_chunk(&self, sequence: u64, offset: Option<String>) -> Result<ReplicaSnapshot> {
+        let database = self.database.as_ref().ok_or_else(|| {
+            ReplicationError::InvalidState(format!(
+                "Entries after {} were truncated and there is no database to snapshot",
+                sequence
+            ))
+        })?;
+        ReplicaSnapshot::capture(database, sequence, offset, self.snapshot_chunk_bytes)
+    }
+
+    /// Record a replica's progress in the replica set
+    fn record_ack(&self, ack: &ReplicationAck) {
+        let caught_up = ack.position >= self.log.current_sequence();
+        // Lag is the age of the oldest entry the replica hasn't applied
+        let lag_ms = match self.log.get(ack.position + 1) {
+            Some(next) if !caught_up => Utc::now()
+                .signed_duration_since(next.timestamp)
+                .num_milliseconds()
+                .max(0) as u64,
+            _ => 0,
+        };
+
+        let updated = self.replica_set.update_replica(&ack.replica_id, |replica| {
+            replica.heartbeat();
+            // Acknowledgements of concurrent shipments arrive in any order
+            if ack.position > replica.log_position {
+                replica.update_position(ack.position);
+            }
+            replica.update_lag(lag_ms);
+            if caught_up && replica.status == ReplicaStatus::Recovering {
+                replica.status = ReplicaStatus::Healthy;
+            }
+        });
+        if let Err(e) = updated {
+            tracing::warn!("Ignoring acknowledgement: {}", e);
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -371,4 +682,34 @@ mod tests {
         let entries = manager.catchup("r2", 1).await.unwrap();
         assert_eq!(entries.len(), 2); // Entries 2 and 3
     }
+
+    #[tokio::test]
+    async fn test_catchup_message() {
+        let mut replica_set = ReplicaSet::new("cluster-1");
+        replica_set
+            .add_replica("r1", "127.0.0.1:9001", ReplicaRole::Primary)
+            .unwrap();
+        replica_set
+            .add_replica("r2", "127.0.0.1:9002", ReplicaRole::Secondary)
+            .unwrap();
+
+        let log = Arc::new(ReplicationLog::new("r1"));
+        let manager = SyncManager::new(Arc::new(replica_set), log.clone());
+        log.append(b"data1".to_vec());
+        log.append(b"data2".to_vec());
+        log.append(b"data3".to_vec());
+
+        match manager.catchup_message("r2", 1).await.unwrap() {
+            ReplicationMessage::Entries(entries) => assert_eq!(entries.len(), 2),
+            other => panic!("Unexpected catchup: {:?}", other),
+        }
+
+        // Entry 2 is gone, and there is no database to snapshot
+        log.truncate_before(3);
+        assert_eq!(log.first_sequence(), 3);
+        assert!(manager.catchup_message("r2", 2).await.is_ok());
+        assert!(manager.catchup_message("r2", 1).await.is_err());
+        assert!(manager.catchup("r2", 1).await.is_err());
+        assert!(manager.catchup_message("r9", 1).await.is_err());
+    }
 }
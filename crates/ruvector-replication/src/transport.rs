@@ -0,0 +1,445 @@
+//! Delivery of replication messages from a primary to its secondaries
+//!
+//! A [`SyncManager`](crate::SyncManager) ships log entries, or a snapshot
+//! when a secondary needs entries the log no longer holds, through a
+//! [`ReplicaTransport`], which hands them to the secondary's
+//! [`SecondaryReplica`] and returns its acknowledgement. Snapshots are sent
+//! as a series of chunks, so no message holds the whole database.
+//!
+//! Over TCP, each request and response is a bincode frame prefixed with
+//! its length as a big-endian `u32`.
+//!
+//! A [`ReplicaServer`] applies whatever a connected peer sends it, so
+//! anyone who can reach it can overwrite the secondary's database. With a
+//! shared secret set on both sides, every connection starts with the server
+//! sending a random challenge, which the primary answers with its
+//! HMAC-SHA256 under the secret; the server drops connections that answer
+//! wrongly before reading any message. Frames are neither encrypted nor
+//! signed after that, so on an untrusted network the connection must also
+//! be tunneled. Without a secret, the server only serves loopback
+//! addresses.
+
+use crate::{secondary::SecondaryReplica, sync::LogEntry, Replica, ReplicationError, Result};
+use dashmap::{DashMap, DashSet};
+use futures::future::BoxFuture;
+use hmac::{Hmac, Mac};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use sha2::Sha256;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream, ToSocketAddrs};
+use tokio::time::timeout;
+
+/// Largest frame accepted over TCP by default
+pub const MAX_FRAME_LEN: usize = 256 * 1024 * 1024;
+
+/// Bytes of the challenge a server sends to authenticate a primary
+const CHALLENGE_LEN: usize = 32;
+
+/// Largest handshake frame, read before the peer is authenticated
+const MAX_HANDSHAKE_LEN: usize = 64;
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// What a primary sends a secondary
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum ReplicationMessage {
+    /// Log entries to apply, in sequence order
+    Entries(Vec<LogEntry>),
+
+    /// One chunk of the primary's database, for a secondary missing
+    /// entries that were truncated from the log
+    Snapshot(ReplicaSnapshot),
+}
+
+/// One chunk of a snapshot of a primary's database
+///
+/// The chunks of a snapshot hold its points in id order and are sent one
+/// after another, each starting at the scroll offset the previous one ended
+/// at. The secondary only resumes from the snapshot once the last chunk is
+/// applied.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReplicaSnapshot {
+    /// Log position the snapshot was taken at; the secondary resumes with
+    /// the entry after it
+    pub sequence: u64,
+
+    /// Scroll offset the chunk starts at, `None` for the first chunk
+    pub offset: Option<String>,
+
+    /// Scroll offset the next chunk starts at, `None` for the last chunk
+    pub next_offset: Option<String>,
+
+    /// The chunk's points, bincode-encoded
+    pub data: Vec<u8>,
+}
+
+/// A secondary's answer to a [`ReplicationMessage`]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ReplicationAck {
+    /// The secondary that applied the message
+    pub replica_id: String,
+
+    /// Sequence of the last entry the secondary applied; below the entries
+    /// sent if it is missing earlier ones
+    pub position: u64,
+}
+
+/// Sends replication messages to secondaries
+pub trait ReplicaTransport: Send + Sync {
+    /// Deliver `message` to `replica`, returning its acknowledgement
+    fn send<'a>(
+        &'a self,
+        replica: &'a Replica,
+        message: ReplicationMessage,
+    ) -> BoxFuture<'a, Result<ReplicationAck>>;
+}
+
+/// Transport to secondaries in the same process
+///
+/// Secondaries are registered by replica id; messages to a disconnected
+/// one fail, which simulates a network partition.
+#[derive(Clone, Default)]
+pub struct LocalReplicaTransport {
+    replicas: Arc<DashMap<String, Arc<SecondaryReplica>>>,
+    disconnected: Arc<DashSet<String>>,
+}
+
+impl LocalReplicaTransport {
+    /// Create a transport with no secondaries
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Route messages addressed to `replica`'s id to it
+    pub fn register(&self, replica: Arc<SecondaryReplica>) {
+        self.replicas
+            .insert(replica.replica_id().to_string(), replica);
+    }
+
+    /// Fail all messages to `replica_id`
+    pub fn disconnect(&self, replica_id: &str) {
+        self.disconnected.insert(replica_id.to_string());
+    }
+
+    /// Deliver messages to `replica_id` again
+    pub fn reconnect(&self, replica_id: &str) {
+        self.disconnected.remove(replica_id);
+    }
+}
+
+impl ReplicaTransport for LocalReplicaTransport {
+    fn send<'a>(
+        &'a self,
+        replica: &'a Replica,
+        message: ReplicationMessage,
+    ) -> BoxFuture<'a, Result<ReplicationAck>> {
+        Box::pin(async move {
+            if self.disconnected.contains(&replica.id) {
+                return Err(ReplicationError::Network(format!(
+                    "Replica {} is disconnected",
+                    replica.id
+                )));
+            }
+
+            let secondary = self
+                .replicas
+                .get(&replica.id)
+                .map(|secondary| secondary.clone())
+                .ok_or_else(|| ReplicationError::ReplicaNotFound(replica.id.clone()))?;
+            secondary.receive(message).await
+        })
+    }
+}
+
+/// Transport to secondaries served by a [`ReplicaServer`] at their
+/// [`Replica::address`]
+///
+/// One connection is kept per address and reopened after a failure, so
+/// messages to a replica are delivered one at a time, in order.
+pub struct TcpReplicaTransport {
+    connections: DashMap<String, Arc<tokio::sync::Mutex<Option<TcpStream>>>>,
+    timeout: Duration,
+    max_frame_len: usize,
+    secret: Option<Arc<[u8]>>,
+}
+
+impl TcpReplicaTransport {
+    /// Create a transport whose connections and requests time out after
+    /// `timeout`
+    pub fn new(timeout: Duration) -> Self {
+        Self {
+            connections: DashMap::new(),
+            timeout,
+            max_frame_len: MAX_FRAME_LEN,
+            secret: None,
+        }
+    }
+
+    /// Refuse to send or receive frames larger than `max_frame_len` bytes,
+    /// [`MAX_FRAME_LEN`] by default
+    pub fn with_max_frame_len(mut self, max_frame_len: usize) -> Self {
+        self.max_frame_len = max_frame_len;
+        self
+    }
+
+    /// Authenticate to servers with `secret`, which they must share
+    pub fn with_secret(mut self, secret: impl Into<Vec<u8>>) -> Self {
+        self.secret = Some(secret.into().into());
+        self
+    }
+
+    async fn request(&self, address: &str, message: &ReplicationMessage) -> Result<ReplicationAck> {
+        let connection = self
+            .connections
+            .entry(address.to_string())
+            .or_default()
+            .clone();
+        let mut connection = connection.lock().await;
+
+        let result = timeout(self.timeout, async {
+            let mut stream = match connection.take() {
+                Some(stream) => stream,
+                None => {
+                    let mut stream = TcpStream::connect(address).await?;
+                    stream.set_nodelay(true)?;
+                    if let Some(secret) = &self.secret {
+                        let challenge: Vec<u8> = read_frame(&mut stream, MAX_HANDSHAKE_LEN).await?;
+                        let proof = mac(secret, &challenge).finalize().into_bytes().to_vec();
+                        write_frame(&mut stream, &proof, MAX_HANDSHAKE_LEN).await?;
+                    }
+                    stream
+                }
+            };
+            write_frame(&mut stream, message, self.max_frame_len).await?;
+            let response: std::result::Result<ReplicationAck, String> =
+                read_frame(&mut stream, self.max_frame_len).await?;
+            // A stream that failed may be mid-frame, so only this one is kept
+            *connection = Some(stream);
+            Ok::<_, ReplicationError>(response)
+        })
+        .await;
+
+        match result {
+            Ok(Ok(response)) => response.map_err(ReplicationError::SyncFailed),
+            Ok(Err(e)) => Err(ReplicationError::Network(format!(
+                "Replication to {} failed: {}",
+                address, e
+            ))),
+            Err(_) => Err(ReplicationError::Timeout(format!(
+                "Replication to {} timed out",
+                address
+            ))),
+        }
+    }
+}
+
+impl Default for TcpReplicaTransport {
+    fn default() -> Self {
+        Self::new(Duration::from_secs(5))
+    }
+}
+
+impl ReplicaTransport for TcpReplicaTransport {
+    fn send<'a>(
+        &'a self,
+        replica: &'a Replica,
+        message: ReplicationMessage,
+    ) -> BoxFuture<'a, Result<ReplicationAck>> {
+        Box::pin(async move { self.request(&replica.address, &message).await })
+    }
+}
+
+/// Accepts connections from a [`TcpReplicaTransport`] and feeds their
+/// messages to a secondary
+///
+/// Every message is applied as is, so only primaries that share the
+/// server's secret may connect; without one, it serves only loopback
+/// addresses, trusting every local process. See the [module
+/// docs](crate::transport) for what the secret does and doesn't protect.
+pub struct ReplicaServer {
+    listener: TcpListener,
+    replica: Arc<SecondaryReplica>,
+    max_frame_len: usize,
+    secret: Option<Arc<[u8]>>,
+}
+
+impl ReplicaServer {
+    /// Listen on `addr` for messages to `replica`
+    ///
+    /// A non-loopback `addr` must be given a secret with
+    /// [`with_secret`](Self::with_secret) before serving.
+    pub async fn bind(addr: impl ToSocketAddrs, replica: Arc<SecondaryReplica>) -> Result<Self> {
+        Ok(Self {
+            listener: TcpListener::bind(addr).await?,
+            replica,
+            max_frame_len: MAX_FRAME_LEN,
+            secret: None,
+        })
+    }
+
+    /// Only accept primaries that authenticate with `secret`
+    pub fn with_secret(mut self, secret: impl Into<Vec<u8>>) -> Self {
+        self.secret = Some(secret.into().into());
+        self
+    }
+
+    /// Refuse to send or receive frames larger than `max_frame_len` bytes,
+    /// [`MAX_FRAME_LEN`] by default
+    pub fn with_max_frame_len(mut self, max_frame_len: usize) -> Self {
+        self.max_frame_len = max_frame_len;
+        self
+    }
+
+    /// The address the server listens on
+    pub fn local_addr(&self) -> Result<SocketAddr> {
+        Ok(self.listener.local_addr()?)
+    }
+
+    /// Serve connections until the task is dropped
+    ///
+    /// # Errors
+    ///
+    /// Returns `InvalidState` without serving if the server listens on a
+    /// non-loopback address and has no secret
+    pub async fn serve(self) -> Result<()> {
+        let addr = self.local_addr()?;
+        if self.secret.is_none() && !addr.ip().is_loopback() {
+            return Err(ReplicationError::InvalidState(format!(
+                "Refusing to serve replication on {} without a secret",
+                addr
+            )));
+        }
+
+        loop {
+            let (stream, peer) = self.listener.accept().await?;
+            let replica = self.replica.clone();
+            let max_frame_len = self.max_frame_len;
+            let secret = self.secret.clone();
+            tokio::spawn(async move {
+                let served = serve_connection(stream, &replica, max_frame_len, secret).await;
+                if let Err(e) = served {
+                    tracing::debug!("Replication connection from {} closed: {}", peer, e);
+                }
+            });
+        }
+    }
+}
+
+async fn serve_connection(
+    mut stream: TcpStream,
+    replica: &SecondaryReplica,
+    max_frame_len: usize,
+    secret: Option<Arc<[u8]>>,
+) -> Result<()> {
+    stream.set_nodelay(true)?;
+    if let Some(secret) = secret {
+        let challenge: [u8; CHALLENGE_LEN] = rand::random();
+        write_frame(&mut stream, &challenge.to_vec(), MAX_HANDSHAKE_LEN).await?;
+        let proof: Vec<u8> = read_frame(&mut stream, MAX_HANDSHAKE_LEN).await?;
+        mac(&secret, &challenge)
+            .verify_slice(&proof)
+            .map_err(|_| ReplicationError::Network("Primary failed to authenticate".to_string()))?;
+    }
+
+    loop {
+        let message: ReplicationMessage = read_frame(&mut stream, max_frame_len).await?;
+        // Errors go back to the primary as text
+        let response = replica.receive(message).await.map_err(|e| e.to_string());
+        write_frame(&mut stream, &response, max_frame_len).await?;
+    }
+}
+
+/// The HMAC of `challenge` under `secret`
+fn mac(secret: &[u8], challenge: &[u8]) -> HmacSha256 {
+    let mut mac = HmacSha256::new_from_slice(secret).expect("HMAC accepts keys of any length");
+    mac.update(challenge);
+    mac
+}
+
+async fn write_frame<W, T>(writer: &mut W, value: &T, max_len: usize) -> Result<()>
+where
+    W: AsyncWrite + Unpin,
+    T: Serialize,
+{
+    let bytes = bincode::serde::encode_to_vec(value, bincode::config::standard())?;
+    let len = u32::try_from(bytes.len())
+        .ok()
+        .filter(|len| *len as usize <= max_len)
+        .ok_or_else(|| {
+            ReplicationError::InvalidState(format!("Frame of {} bytes is too large", bytes.len()))
+        })?;
+    writer.write_u32(len).await?;
+    writer.write_all(&bytes).await?;
+    writer.flush().await?;
+    Ok(())
+}
+
+async fn read_frame<R, T>(reader: &mut R, max_len: usize) -> Result<T>
+where
+    R: AsyncRead + Unpin,
+    T: DeserializeOwned,
+{
+    let len = reader.read_u32().await? as usize;
+    if len > max_len {
+        return Err(ReplicationError::InvalidState(format!(
+            "Frame of {} bytes is too large",
+            len
+        )));
+    }
+    let mut bytes = vec![0; len];
+    reader.read_exact(&mut bytes).await?;
+    let (value, _) = bincode::serde::decode_from_slice(&bytes, bincode::config::standard())?;
+    Ok(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_frames_round_trip() {
+        let message =
+            ReplicationMessage::Entries(vec![LogEntry::new(3, b"data".to_vec(), "r1".to_string())]);
+
+        let mut buffer = Vec::new();
+        write_frame(&mut buffer, &message, MAX_FRAME_LEN)
+            .await
+            .unwrap();
+        assert_eq!(
+            u32::from_be_bytes(buffer[..4].try_into().unwrap()) as usize,
+            buffer.len() - 4
+        );
+
+        let decoded: ReplicationMessage = read_frame(&mut buffer.as_slice(), MAX_FRAME_LEN)
+            .await
+            .unwrap();
+        match decoded {
+            ReplicationMessage::Entries(entries) => {
+                assert_eq!(entries[0].sequence, 3);
+                assert!(entries[0].verify());
+            }
+            other => panic!("Unexpected message: {:?}", other),
+        }
+
+        // Frames over the limit are refused on either side
+        assert!(write_frame(&mut Vec::new(), &message, buffer.len() - 5)
+            .await
+            .is_err());
+        assert!(
+            read_frame::<_, ReplicationMessage>(&mut buffer.as_slice(), buffer.len() - 5)
+                .await
+                .is_err()
+        );
+
+        // A truncated frame is an error, not a short read
+        buffer.truncate(buffer.len() - 1);
+        assert!(
+            read_frame::<_, ReplicationMessage>(&mut buffer.as_slice(), MAX_FRAME_LEN)
+                .await
+                .is_err()
+        );
+    }
+}
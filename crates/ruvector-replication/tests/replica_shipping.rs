@@ -0,0 +1,357 @@
+//! A primary shipping writes to secondaries that apply them to their own
+//! vector databases
+
+use ruvector_core::types::DbOptions;
+use ruvector_core::{DistanceMetric, VectorDB, VectorEntry};
+use ruvector_replication::{
+    LocalReplicaTransport, LogEntry, Replica, ReplicaRole, ReplicaServer, ReplicaSet,
+    ReplicaStatus, ReplicaTransport, ReplicationError, ReplicationLog, ReplicationMessage,
+    SecondaryReplica, SyncManager, SyncMode, TcpReplicaTransport, VectorOperation,
+};
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+use tempfile::{tempdir, TempDir};
+
+/// Open a database in `dir`, creating it
+fn db(dir: &Path) -> Arc<VectorDB> {
+    std::fs::create_dir_all(dir).unwrap();
+    Arc::new(
+        VectorDB::new(DbOptions {
+            dimensions: 2,
+            distance_metric: DistanceMetric::Euclidean,
+            storage_path: dir.join("vectors.db").to_string_lossy().into_owned(),
+            quantization: None,
+            ..DbOptions::default()
+        })
+        .unwrap(),
+    )
+}
+
+fn upsert(id: &str, vector: [f32; 2]) -> VectorOperation {
+    VectorOperation::Upsert(vec![VectorEntry {
+        id: Some(id.to_string()),
+        vector: vector.to_vec(),
+        metadata: None,
+    }])
+}
+
+fn secondary(dir: &Path, id: &str) -> Arc<SecondaryReplica> {
+    let replica = Arc::new(SecondaryReplica::new(id, db(&dir.join(id))));
+    replica.start().unwrap();
+    replica
+}
+
+/// A primary `r1` with secondaries `r2` and `r3` over the in-process
+/// transport
+struct LocalCluster {
+    manager: SyncManager,
+    replica_set: Arc<ReplicaSet>,
+    log: Arc<ReplicationLog>,
+    transport: LocalReplicaTransport,
+    secondaries: Vec<Arc<SecondaryReplica>>,
+    _dir: TempDir,
+}
+
+impl LocalCluster {
+    fn start(name: &str) -> Self {
+        let dir = tempdir().unwrap();
+        let mut replica_set = ReplicaSet::new(name);
+        replica_set
+            .add_replica("r1", "local", ReplicaRole::Primary)
+            .unwrap();
+        let transport = LocalReplicaTransport::new();
+        let mut secondaries = Vec::new();
+        for id in ["r2", "r3"] {
+            replica_set
+                .add_replica(id, "local", ReplicaRole::Secondary)
+                .unwrap();
+            let replica = secondary(dir.path(), id);
+            transport.register(replica.clone());
+            secondaries.push(replica);
+        }
+
+        let replica_set = Arc::new(replica_set);
+        let log = Arc::new(ReplicationLog::new("r1"));
+        let manager = SyncManager::new(replica_set.clone(), log.clone())
+            .with_transport(Arc::new(transport.clone()))
+            .with_database(db(&dir.path().join("r1")));
+        Self {
+            manager,
+            replica_set,
+            log,
+            transport,
+            secondaries,
+            _dir: dir,
+        }
+    }
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_sync_writes_reach_every_secondary() {
+    let cluster = LocalCluster::start("sync");
+    let manager = &cluster.manager;
+    manager.set_sync_mode(SyncMode::Sync);
+
+    manager.write(upsert("a", [0.0, 0.0])).await.unwrap();
+    manager.write(upsert("b", [5.0, 5.0])).await.unwrap();
+    let entry = manager
+        .write(VectorOperation::Upsert(vec![VectorEntry {
+            id: None,
+            vector: vec![1.0, 1.0],
+            metadata: None,
+        }]))
+        .await
+        .unwrap();
+    manager
+        .write(VectorOperation::Delete(vec!["a".to_string()]))
+        .await
+        .unwrap();
+
+    manager.write(upsert("c", [1.0, 1.0])).await.unwrap();
+
+    // Rejected by the primary before reaching the log
+    let err = manager
+        .write(VectorOperation::Upsert(vec![VectorEntry {
+            id: Some("d".to_string()),
+            vector: vec![1.0],
+            metadata: None,
+        }]))
+        .await
+        .unwrap_err();
+    assert!(matches!(err, ReplicationError::Database(_)));
+    assert_eq!(manager.current_position(), 5);
+
+    // The id assigned on the primary is the one every secondary stores
+    let VectorOperation::Upsert(entries) = VectorOperation::decode(&entry.data).unwrap() else {
+        panic!("Expected an upsert");
+    };
+    let auto_id = entries[0].id.clone().unwrap();
+
+    for replica in &cluster.secondaries {
+        assert_eq!(replica.position(), 5);
+        assert_eq!(replica.db().len().unwrap(), 3);
+        assert!(replica.db().get("a").unwrap().is_none());
+        assert!(replica.db().get(&auto_id).unwrap().is_some());
+
+        let tracked = cluster
+            .replica_set
+            .get_replica(replica.replica_id())
+            .unwrap();
+        assert_eq!(tracked.log_position, 5);
+        assert_eq!(tracked.status, ReplicaStatus::Healthy);
+    }
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_unreachable_secondary_catches_up_from_snapshot() {
+    let mut cluster = LocalCluster::start("semi_sync");
+    cluster.manager.set_sync_timeout(Duration::from_secs(1));
+    let manager = &cluster.manager;
+    manager.set_sync_mode(SyncMode::SemiSync { min_replicas: 1 });
+
+    cluster.transport.disconnect("r3");
+    for i in 0..5 {
+        manager
+            .write(upsert(&format!("p{}", i), [i as f32, 0.0]))
+            .await
+            .unwrap();
+    }
+    assert_eq!(cluster.secondaries[0].position(), 5);
+
+    // Sync mode needs both secondaries; the write still lands on the rest
+    manager.set_sync_mode(SyncMode::Sync);
+    let err = manager.write(upsert("q", [9.0, 9.0])).await.unwrap_err();
+    assert!(matches!(err, ReplicationError::QuorumNotMet { .. }));
+    assert_eq!(
+        cluster.replica_set.get_replica("r3").unwrap().status,
+        ReplicaStatus::Offline
+    );
+    assert_eq!(cluster.secondaries[1].position(), 0);
+
+    manager.set_sync_mode(SyncMode::SemiSync { min_replicas: 1 });
+    manager.write(upsert("p0", [7.0, 7.0])).await.unwrap();
+    assert!(matches!(
+        manager.catchup_message("r3", 0).await.unwrap(),
+        ReplicationMessage::Entries(entries) if entries.len() == 7
+    ));
+
+    // The entries it missed are gone, so it catches up from a snapshot
+    cluster.log.truncate_before(manager.current_position());
+    assert!(matches!(
+        manager.catchup_message("r3", 0).await.unwrap(),
+        ReplicationMessage::Snapshot(_)
+    ));
+    cluster.transport.reconnect("r3");
+    let position = manager.sync_replica("r3").await.unwrap();
+    assert_eq!(position, manager.current_position());
+
+    let lagging = &cluster.secondaries[1];
+    assert_eq!(lagging.db().len().unwrap(), 6);
+    assert_eq!(
+        lagging.db().get("p0").unwrap().unwrap().vector,
+        vec![7.0, 7.0]
+    );
+    let tracked = cluster.replica_set.get_replica("r3").unwrap();
+    assert_eq!(tracked.log_position, position);
+    assert_eq!(tracked.status, ReplicaStatus::Healthy);
+
+    // Later writes follow the snapshot as entries
+    manager.set_sync_mode(SyncMode::Sync);
+    manager.write(upsert("p9", [9.0, 0.0])).await.unwrap();
+    assert_eq!(lagging.db().len().unwrap(), 7);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_secondary_over_tcp() {
+    let dir = tempdir().unwrap();
+    let replica = secondary(dir.path(), "r2");
+    let server = ReplicaServer::bind("127.0.0.1:0", replica.clone())
+        .await
+        .unwrap();
+    let address = server.local_addr().unwrap().to_string();
+    let server = tokio::spawn(server.serve());
+
+    let mut replica_set = ReplicaSet::new("tcp");
+    replica_set
+        .add_replica("r1", "127.0.0.1:0", ReplicaRole::Primary)
+        .unwrap();
+    replica_set
+        .add_replica("r2", address, ReplicaRole::Secondary)
+        .unwrap();
+    let replica_set = Arc::new(replica_set);
+    let manager = SyncManager::new(replica_set.clone(), Arc::new(ReplicationLog::new("r1")))
+        .with_transport(Arc::new(TcpReplicaTransport::default()))
+        .with_database(db(&dir.path().join("r1")));
+    manager.set_sync_mode(SyncMode::Sync);
+
+    for i in 0..3 {
+        manager
+            .write(upsert(&format!("p{}", i), [i as f32, 0.0]))
+            .await
+            .unwrap();
+    }
+    assert_eq!(replica.position(), 3);
+    assert_eq!(replica.db().len().unwrap(), 3);
+    assert_eq!(replica_set.get_replica("r2").unwrap().log_position, 3);
+
+    // The secondary reports entries it can't apply back to the primary
+    let err = manager
+        .replicate(b"not an operation".to_vec())
+        .await
+        .unwrap_err();
+    assert!(matches!(err, ReplicationError::QuorumNotMet { .. }));
+    assert_eq!(replica.position(), 3);
+
+    server.abort();
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_tcp_secondary_only_applies_authenticated_messages() {
+    let dir = tempdir().unwrap();
+    let replica = secondary(dir.path(), "r2");
+    let server = ReplicaServer::bind("127.0.0.1:0", replica.clone())
+        .await
+        .unwrap()
+        .with_secret("s3cret");
+    let target = Replica::new(
+        "r2",
+        server.local_addr().unwrap().to_string(),
+        ReplicaRole::Secondary,
+    );
+    let server = tokio::spawn(server.serve());
+
+    let entry = LogEntry::new(
+        1,
+        upsert("p0", [0.0, 0.0]).encode().unwrap(),
+        "r1".to_string(),
+    );
+    let message = || ReplicationMessage::Entries(vec![entry.clone()]);
+    let timeout = Duration::from_millis(500);
+
+    // Without the secret, nothing is applied
+    let unauthenticated = TcpReplicaTransport::new(timeout);
+    assert!(unauthenticated.send(&target, message()).await.is_err());
+    let wrong = TcpReplicaTransport::new(timeout).with_secret("guess");
+    assert!(wrong.send(&target, message()).await.is_err());
+    assert_eq!(replica.position(), 0);
+    assert!(replica.db().is_empty().unwrap());
+
+    let authenticated = TcpReplicaTransport::new(timeout).with_secret("s3cret");
+    let ack = authenticated.send(&target, message()).await.unwrap();
+    assert_eq!(ack.position, 1);
+    assert_eq!(replica.db().len().unwrap(), 1);
+    server.abort();
+
+    // Beyond loopback a secret is required
+    let exposed = ReplicaServer::bind("0.0.0.0:0", replica.clone())
+        .await
+        .unwrap();
+    let err = exposed.serve().await.unwrap_err();
+    assert!(matches!(err, ReplicationError::InvalidState(_)));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_snapshot_larger_than_a_frame_is_sent_in_chunks() {
+    const MAX_FRAME_LEN: usize = 16 * 1024;
+    let dir = tempdir().unwrap();
+    let replica = secondary(dir.path(), "r2");
+    let server = ReplicaServer::bind("127.0.0.1:0", replica.clone())
+        .await
+        .unwrap()
+        .with_max_frame_len(MAX_FRAME_LEN);
+    let address = server.local_addr().unwrap().to_string();
+    let server = tokio::spawn(server.serve());
+
+    // Unreachable until the writes it misses are truncated from the log
+    let mut replica_set = ReplicaSet::new("chunks");
+    replica_set
+        .add_replica("r1", "127.0.0.1:0", ReplicaRole::Primary)
+        .unwrap();
+    replica_set
+        .add_replica("r2", "127.0.0.1:1", ReplicaRole::Secondary)
+        .unwrap();
+    let replica_set = Arc::new(replica_set);
+    let log = Arc::new(ReplicationLog::new("r1"));
+    let manager = SyncManager::new(replica_set.clone(), log.clone())
+        .with_transport(Arc::new(
+            TcpReplicaTransport::default().with_max_frame_len(MAX_FRAME_LEN),
+        ))
+        .with_database(db(&dir.path().join("r1")))
+        .with_snapshot_chunk_bytes(4 * 1024);
+
+    let points = 2_000;
+    let entries: Vec<VectorEntry> = (0..points)
+        .map(|i| VectorEntry {
+            id: Some(format!("p{:04}", i)),
+            vector: vec![i as f32, 1.0],
+            metadata: None,
+        })
+        .collect();
+    let total = entries
+        .iter()
+        .map(|entry| entry.id.as_ref().unwrap().len() + entry.vector.len() * 4)
+        .sum::<usize>();
+    assert!(total > MAX_FRAME_LEN);
+    for chunk in entries.chunks(100) {
+        manager
+            .write(VectorOperation::Upsert(chunk.to_vec()))
+            .await
+            .unwrap();
+    }
+    log.truncate_before(manager.current_position());
+    replica_set
+        .update_replica("r2", |replica| replica.address = address)
+        .unwrap();
+
+    let position = manager.sync_replica("r2").await.unwrap();
+    assert_eq!(position, manager.current_position());
+    assert_eq!(replica.position(), position);
+    assert_eq!(replica.db().len().unwrap(), points);
+    assert_eq!(
+        replica.db().get("p1999").unwrap().unwrap().vector,
+        vec![1999.0, 1.0]
+    );
+
+    server.abort();
+}
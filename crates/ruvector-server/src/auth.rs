@@ -0,0 +1,595 @@
+//! API key and JWT authentication with per-collection authorization
+//!
+//! [`AuthLayer`] wraps both the REST router and the gRPC services. It
+//! authenticates each request from an `api-key` header or an
+//! `Authorization: Bearer` token, applies the caller's rate limit, checks
+//! read-only vs read-write access and, where the collection is part of the
+//! path, the caller's collection scopes. The resolved [`Principal`] is put
+//! in the request extensions so handlers can check scopes for collections
+//! named in the request body.
+//!
+//! Authentication is disabled when no API keys and no JWT key are
+//! configured.
+
+use crate::{error::Error, Result};
+use axum::{
+    body::Body,
+    http::{HeaderMap, Method, Request, Response},
+    response::IntoResponse,
+};
+use governor::{DefaultDirectRateLimiter, DefaultKeyedRateLimiter, Quota, RateLimiter};
+use jsonwebtoken::{Algorithm, DecodingKey, Validation};
+use serde::{Deserialize, Serialize};
+use std::future::Future;
+use std::num::NonZeroU32;
+use std::path::PathBuf;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use tower::{Layer, Service};
+
+/// Authentication settings
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct AuthConfig {
+    /// Accepted API keys
+    #[serde(default)]
+    pub api_keys: Vec<ApiKeyConfig>,
+    /// Accepted JWTs, verified with a local key file
+    pub jwt: Option<JwtConfig>,
+}
+
+/// An accepted API key
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ApiKeyConfig {
+    /// Name identifying the key in logs and rate limits
+    pub name: String,
+    /// Secret sent by clients
+    pub key: String,
+    /// Operations the key allows
+    pub access: Access,
+    /// Collections (names or aliases) the key may use, all if `None`
+    pub collections: Option<Vec<String>>,
+    /// Request rate limit, unlimited if `None`
+    pub rate_limit: Option<RateLimit>,
+}
+
+/// JWT verification settings
+///
+/// Tokens carry the caller's `access` and optional `collections` claims
+/// alongside the standard `sub` and `exp`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JwtConfig {
+    /// Signature algorithm
+    pub algorithm: JwtAlgorithm,
+    /// Shared secret for HS256, PEM public key for RS256
+    pub key_file: PathBuf,
+    /// Request rate limit of each subject, unlimited if `None`
+    pub rate_limit: Option<RateLimit>,
+}
+
+/// Supported JWT signature algorithms
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "UPPERCASE")]
+pub enum JwtAlgorithm {
+    /// HMAC with SHA-256
+    Hs256,
+    /// RSA PKCS#1 v1.5 with SHA-256
+    Rs256,
+}
+
+/// Request rate limit
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct RateLimit {
+    /// Sustained requests per second
+    pub requests_per_second: u32,
+    /// Requests allowed in a burst, `requests_per_second` if `None`
+    pub burst: Option<u32>,
+}
+
+/// Operations a caller may perform
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Access {
+    /// Reads and searches only
+    Read,
+    /// Reads and writes
+    ReadWrite,
+}
+
+/// An authenticated caller
+#[derive(Debug, Clone)]
+pub struct Principal {
+    /// Key name or token subject
+    pub id: String,
+    /// Operations the caller may perform
+    pub access: Access,
+    /// Collections the caller may use, all if `None`
+    pub collections: Option<Vec<String>>,
+}
+
+impl Principal {
+    /// Check that the caller may use `collection`
+    pub fn authorize_collection(&self, collection: &str) -> Result<()> {
+        match &self.collections {
+            Some(collections) if !collections.iter().any(|c| c == collection) => Err(
+                Error::Forbidden(format!("Not authorized for collection: {}", collection)),
+            ),
+            _ => Ok(()),
+        }
+    }
+
+    /// Whether the caller may use `collection`
+    pub fn allows_collection(&self, collection: &str) -> bool {
+        self.authorize_collection(collection).is_ok()
+    }
+
+    fn authorize(&self, permission: &Permission) -> Result<()> {
+        if permission.write && self.access == Access::Read {
+            return Err(Error::Forbidden(format!("Key {} is read-only", self.id)));
+        }
+        match &permission.target {
+            Target::Server => Ok(()),
+            Target::Collection(collection) => self.authorize_collection(collection),
+            Target::AllCollections if self.collections.is_some() => Err(Error::Forbidden(
+                "Managing collections requires a key without collection scopes".to_string(),
+            )),
+            Target::AllCollections => Ok(()),
+        }
+    }
+}
+
+/// Check that the caller of a request may use `collection`
+///
+/// Requests without a [`Principal`] passed an authenticator with
+/// authentication disabled.
+pub fn authorize_collection(extensions: &axum::http::Extensions, collection: &str) -> Result<()> {
+    match extensions.get::<Principal>() {
+        Some(principal) => principal.authorize_collection(collection),
+        None => Ok(()),
+    }
+}
+
+/// JWT claims
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Claims {
+    /// Subject, identifying the caller in logs and rate limits
+    pub sub: String,
+    /// Expiry as seconds since the Unix epoch
+    pub exp: u64,
+    /// Operations the caller may perform
+    pub access: Access,
+    /// Collections the caller may use, all if absent
+    #[serde(default)]
+    pub collections: Option<Vec<String>>,
+}
+
+/// Resolves and authorizes the caller of each request
+pub struct Authenticator {
+    api_keys: Vec<(ApiKeyConfig, Option<DefaultDirectRateLimiter>)>,
+    jwt: Option<JwtVerifier>,
+}
+
+struct JwtVerifier {
+    key: DecodingKey,
+    validation: Validation,
+    limiter: Option<DefaultKeyedRateLimiter<String>>,
+    /// Tokens checked since the limiter last forgot idle subjects
+    checks: AtomicUsize,
+}
+
+/// How many JWT checks pass between prunes of the per-subject limiter
+const PRUNE_LIMITER_EVERY: usize = 1024;
+
+impl Authenticator {
+    /// Create an authenticator, reading the JWT key file if one is
+    /// configured
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the key file can't be read or parsed, or a rate
+    /// limit is zero
+    pub fn new(config: &AuthConfig) -> Result<Self> {
+        let api_keys = config
+            .api_keys
+            .iter()
+            .map(|key| {
+                let limiter = key
+                    .rate_limit
+                    .map(quota)
+                    .transpose()?
+                    .map(RateLimiter::direct);
+                Ok((key.clone(), limiter))
+            })
+            .collect::<Result<_>>()?;
+
+        let jwt = config
+            .jwt
+            .as_ref()
+            .map(|jwt| -> Result<JwtVerifier> {
+                let pem = std::fs::read(&jwt.key_file).map_err(|e| {
+                    Error::Config(format!(
+                        "Failed to read JWT key {}: {}",
+                        jwt.key_file.display(),
+                        e
+                    ))
+                })?;
+                let secret_len = pem
+                    .iter()
+                    .rposition(|b| !b.is_ascii_whitespace())
+                    .map_or(0, |i| i + 1);
+                let (key, algorithm) = match jwt.algorithm {
+                    // Editors end the secret file with a newline that isn't
+                    // part of the secret the tokens were signed with
+                    JwtAlgorithm::Hs256 => (
+                        DecodingKey::from_secret(&pem[..secret_len]),
+                        Algorithm::HS256,
+                    ),
+                    JwtAlgorithm::Rs256 => (
+                        DecodingKey::from_rsa_pem(&pem)
+                            .map_err(|e| Error::Config(format!("Invalid RS256 key: {}", e)))?,
+                        Algorithm::RS256,
+                    ),
+                };
+                Ok(JwtVerifier {
+                    key,
+                    validation: Validation::new(algorithm),
+                    limiter: jwt
+                        .rate_limit
+                        .map(quota)
+                        .transpose()?
+                        .map(RateLimiter::keyed),
+                    checks: AtomicUsize::new(0),
+                })
+            })
+            .transpose()?;
+
+        Ok(Self { api_keys, jwt })
+    }
+
+    /// Whether requests must carry credentials
+    pub fn is_enabled(&self) -> bool {
+        !self.api_keys.is_empty() || self.jwt.is_some()
+    }
+
+    /// Authorize a request, returning its caller
+    ///
+    /// Returns `None` for public endpoints and when authentication is
+    /// disabled.
+    pub fn authorize<B>(&self, request: &Request<B>) -> Result<Option<Principal>> {
+        if !self.is_enabled() {
+            return Ok(None);
+        }
+        let Some(permission) = classify(request.method(), request.uri().path()) else {
+            return Ok(None);
+        };
+
+        let principal = self.authenticate(request.headers())?;
+        principal.authorize(&permission)?;
+        Ok(Some(principal))
+    }
+
+    /// Resolve the caller from its credentials and apply its rate limit
+    fn authenticate(&self, headers: &HeaderMap) -> Result<Principal> {
+        let token = headers
+            .get("api-key")
+            .and_then(|value| value.to_str().ok())
+            .or_else(|| {
+                headers
+                    .get("authorization")
+                    .and_then(|value| value.to_str().ok())
+                    .and_then(|value| value.strip_prefix("Bearer "))
+            })
+            .ok_or_else(|| Error::Unauthorized("Missing API key or bearer token".to_string()))?;
+
+        // Compare every key so timing doesn't reveal which one matched
+        let matched = self
+            .api_keys
+            .iter()
+            .filter(|(key, _)| constant_time_eq(key.key.as_bytes(), token.as_bytes()))
+            .last();
+        if let Some((key, limiter)) = matched {
+            if limiter
+                .as_ref()
+                .is_some_and(|limiter| limiter.check().is_err())
+            {
+                return Err(Error::RateLimited(key.name.clone()));
+            }
+            return Ok(Principal {
+                id: key.name.clone(),
+                access: key.access,
+                collections: key.collections.clone(),
+            });
+        }
+
+        let Some(jwt) = &self.jwt else {
+            return Err(Error::Unauthorized("Invalid API key".to_string()));
+        };
+        let claims = jsonwebtoken::decode::<Claims>(token, &jwt.key, &jwt.validation)
+            .map_err(|e| Error::Unauthorized(format!("Invalid token: {}", e)))?
+            .claims;
+        if let Some(limiter) = &jwt.limiter {
+            // Every subject ever seen keeps a cell until pruned, and subjects
+            // are chosen by whoever issues tokens
+            if jwt.checks.fetch_add(1, Ordering::Relaxed) % PRUNE_LIMITER_EVERY == 0 {
+                limiter.retain_recent();
+                limiter.shrink_to_fit();
+            }
+            if limiter.check_key(&claims.sub).is_err() {
+                return Err(Error::RateLimited(claims.sub));
+            }
+        }
+        Ok(Principal {
+            id: claims.sub,
+            access: claims.access,
+            collections: claims.collections,
+        })
+    }
+}
+
+fn quota(limit: RateLimit) -> Result<Quota> {
+    let rate = NonZeroU32::new(limit.requests_per_second)
+        .ok_or_else(|| Error::Config("Rate limit must be at least 1 request/s".to_string()))?;
+    let burst = match limit.burst {
+        Some(burst) => NonZeroU32::new(burst)
+            .ok_or_else(|| Error::Config("Rate limit burst must be at least 1".to_string()))?,
+        None => rate,
+    };
+    Ok(Quota::per_second(rate).allow_burst(burst))
+}
+
+/// Compare `a` and `b` in time that depends only on their lengths, so a
+/// caller can't learn how much of a key it guessed
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    let len = a.len().max(b.len());
+    let diff = (0..len).fold(u8::from(a.len() != b.len()), |acc, i| {
+        let x = a.get(i).copied().unwrap_or(0);
+        let y = b.get(i).copied().unwrap_or(0);
+        acc | (x ^ y)
+    });
+    diff == 0
+}
+
+/// What a request needs of its caller
+#[derive(Debug, PartialEq)]
+struct Permission {
+    write: bool,
+    target: Target,
+}
+
+#[derive(Debug, PartialEq)]
+enum Target {
+    /// No collection, or collections named in the body and checked by the
+    /// handler
+    Server,
+    /// A collection named in the path
+    Collection(String),
+    /// Creating or dropping collections and aliases
+    AllCollections,
+}
+
+/// Classify a REST or gRPC request, `None` for public endpoints
+fn classify(method: &Method, path: &str) -> Option<Permission> {
+    let read = |target| Permission {
+        write: false,
+        target,
+    };
+    let write = |target| Permission {
+        write: true,
+        target,
+    };
+
+    if let Some(rpc) = path.strip_prefix("/ruvector.v1.") {
+        return Some(match rpc.split_once('/') {
+            Some(("Collections", "Create" | "Delete")) => write(Target::AllCollections),
+            Some(("Collections", _)) => read(Target::Server),
+            Some(("Points", "Search" | "SearchBatch" | "Get" | "Count" | "Scroll")) => {
+                read(Target::Server)
+            }
+            _ => write(Target::Server),
+        });
+    }
+
+    let segments: Vec<&str> = path.trim_matches('/').split('/').collect();
+    let is_read = method == Method::GET || method == Method::HEAD;
+    match segments.as_slice() {
+        ["health"] | ["ready"] => None,
+        // The handlers only show aliases of collections the caller may use
+        ["aliases", ..] if is_read => Some(read(Target::Server)),
+        ["aliases", ..] => Some(write(Target::AllCollections)),
+        ["collections"] if is_read => Some(read(Target::Server)),
+        ["collections"] => Some(write(Target::AllCollections)),
+        ["collections", _] if method == Method::DELETE => Some(write(Target::AllCollections)),
+        ["collections", name, rest @ ..] => {
+            let target = Target::Collection(name.to_string());
+            let searches = matches!(
+                rest,
+                ["points", "search", ..]
+                    | ["points", "scroll" | "get" | "count"]
+                    | ["points", _, "neighbors"]
+            );
+            let reads = is_read || (method == Method::POST && searches);
+            Some(if reads { read(target) } else { write(target) })
+        }
+        _ => Some(write(Target::Server)),
+    }
+}
+
+/// Response body a rejected request is answered with
+pub trait Rejection: Sized {
+    /// Render `error` for the request's transport
+    fn reject(error: Error) -> Response<Self>;
+}
+
+impl Rejection for Body {
+    fn reject(error: Error) -> Response<Self> {
+        error.into_response()
+    }
+}
+
+impl Rejection for tonic::body::BoxBody {
+    fn reject(error: Error) -> Response<Self> {
+        tonic::Status::from(error).into_http()
+    }
+}
+
+/// Tower layer authorizing requests with an [`Authenticator`]
+#[derive(Clone)]
+pub struct AuthLayer {
+    authenticator: Arc<Authenticator>,
+}
+
+impl AuthLayer {
+    /// Create a layer sharing `authenticator`
+    pub fn new(authenticator: Arc<Authenticator>) -> Self {
+        Self { authenticator }
+    }
+}
+
+impl<S> Layer<S> for AuthLayer {
+    type Service = AuthService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        AuthService {
+            inner,
+            authenticator: self.authenticator.clone(),
+        }
+    }
+}
+
+/// Service produced by [`AuthLayer`]
+#[derive(Clone)]
+pub struct AuthService<S> {
+    inner: S,
+    authenticator: Arc<Authenticator>,
+}
+
+impl<S, B, ResBody> Service<Request<B>> for AuthService<S>
+where
+    S: Service<Request<B>, Response = Response<ResBody>>,
+    S::Future: Send + 'static,
+    ResBody: Rejection + Send + 'static,
+{
+    type Response = Response<ResBody>;
+    type Error = S::Error;
+    type Future =
+        Pin<Box<dyn Future<Output = std::result::Result<Self::Response, Self::Error>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<std::result::Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, mut request: Request<B>) -> Self::Future {
+        match self.authenticator.authorize(&request) {
+            Ok(Some(principal)) => {
+                request.extensions_mut().insert(principal);
+            }
+            Ok(None) => {}
+            Err(error) => {
+                tracing::debug!("Rejected {} {}: {}", request.method(), request.uri(), error);
+                return Box::pin(async move { Ok(ResBody::reject(error)) });
+            }
+        }
+        Box::pin(self.inner.call(request))
+    }
+}
+
+impl<S: tonic::server::NamedService> tonic::server::NamedService for AuthService<S> {
+    const NAME: &'static str = S::NAME;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_constant_time_eq() {
+        assert!(constant_time_eq(b"secret", b"secret"));
+        assert!(!constant_time_eq(b"secret", b"secreT"));
+        assert!(!constant_time_eq(b"secret", b"secret-key"));
+        assert!(!constant_time_eq(b"", b"\0"));
+        assert!(constant_time_eq(b"", b""));
+    }
+
+    #[test]
+    fn test_classify_requests() {
+        let check = |method: Method, path: &str, write: bool, target: Target| {
+            let expected = Some(Permission { write, target });
+            assert_eq!(classify(&method, path), expected, "{} {}", method, path);
+        };
+        let docs = || Target::Collection("docs".to_string());
+
+        assert_eq!(classify(&Method::GET, "/health"), None);
+        check(Method::GET, "/collections", false, Target::Server);
+        check(Method::POST, "/collections", true, Target::AllCollections);
+        check(
+            Method::DELETE,
+            "/collections/docs",
+            true,
+            Target::AllCollections,
+        );
+        check(Method::PATCH, "/collections/docs", true, docs());
+        check(Method::PUT, "/collections/docs/points", true, docs());
+        check(
+            Method::POST,
+            "/collections/docs/points/search/batch",
+            false,
+            docs(),
+        );
+        check(
+            Method::POST,
+            "/collections/docs/points/count",
+            false,
+            docs(),
+        );
+        check(
+            Method::POST,
+            "/collections/docs/points/delete",
+            true,
+            docs(),
+        );
+        check(
+            Method::POST,
+            "/collections/docs/points/p1/neighbors",
+            false,
+            docs(),
+        );
+        check(
+            Method::POST,
+            "/collections/docs/points/p1/payload/delete",
+            true,
+            docs(),
+        );
+        check(
+            Method::GET,
+            "/collections/docs/changes/stream",
+            false,
+            docs(),
+        );
+        check(
+            Method::PUT,
+            "/collections/docs/changes/consumers/cache",
+            true,
+            docs(),
+        );
+        check(Method::PUT, "/aliases/live", true, Target::AllCollections);
+        check(
+            Method::POST,
+            "/ruvector.v1.Points/Search",
+            false,
+            Target::Server,
+        );
+        check(
+            Method::POST,
+            "/ruvector.v1.Points/UpsertStream",
+            true,
+            Target::Server,
+        );
+        check(
+            Method::POST,
+            "/ruvector.v1.Collections/Create",
+            true,
+            Target::AllCollections,
+        );
+    }
+}
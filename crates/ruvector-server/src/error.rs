@@ -5,6 +5,7 @@ use axum::{
     response::{IntoResponse, Response},
     Json,
 };
+use ruvector_collections::CollectionError;
 use serde_json::json;
 
 /// Result type for server operations
@@ -25,14 +26,38 @@ pub enum Error {
     #[error("Point not found: {0}")]
     PointNotFound(String),
 
+    /// Vector space not found
+    #[error("Vector space not found: {0}")]
+    VectorSpaceNotFound(String),
+
     /// Invalid request
     #[error("Invalid request: {0}")]
     InvalidRequest(String),
 
+    /// Missing or invalid credentials
+    #[error("Unauthorized: {0}")]
+    Unauthorized(String),
+
+    /// Credentials don't allow the operation
+    #[error("Forbidden: {0}")]
+    Forbidden(String),
+
+    /// Caller exceeded its rate limit
+    #[error("Rate limit exceeded: {0}")]
+    RateLimited(String),
+
     /// Core library error
     #[error("Core error: {0}")]
     Core(#[from] ruvector_core::RuvectorError),
 
+    /// Collection management error
+    #[error("{0}")]
+    Collection(#[from] CollectionError),
+
+    /// Change log error
+    #[error("Change log error: {0}")]
+    Replication(#[from] ruvector_replication::ReplicationError),
+
     /// Server error
     #[error("Server error: {0}")]
     Server(String),
@@ -50,21 +75,48 @@ pub enum Error {
     Internal(String),
 }
 
-impl IntoResponse for Error {
-    fn into_response(self) -> Response {
-        let (status, error_message) = match self {
-            Error::CollectionNotFound(_) | Error::PointNotFound(_) => {
-                (StatusCode::NOT_FOUND, self.to_string())
-            }
+impl Error {
+    /// HTTP status and message reported for this error
+    fn status_and_message(&self) -> (StatusCode, String) {
+        match self {
+            Error::CollectionNotFound(_)
+            | Error::PointNotFound(_)
+            | Error::VectorSpaceNotFound(_) => (StatusCode::NOT_FOUND, self.to_string()),
             Error::CollectionExists(_) => (StatusCode::CONFLICT, self.to_string()),
             Error::InvalidRequest(_) => (StatusCode::BAD_REQUEST, self.to_string()),
+            Error::Unauthorized(_) => (StatusCode::UNAUTHORIZED, self.to_string()),
+            Error::Forbidden(_) => (StatusCode::FORBIDDEN, self.to_string()),
+            Error::RateLimited(_) => (StatusCode::TOO_MANY_REQUESTS, self.to_string()),
             Error::Core(e) => (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()),
+            Error::Collection(e) => {
+                let status = match e {
+                    CollectionError::CollectionNotFound { .. }
+                    | CollectionError::AliasNotFound { .. } => StatusCode::NOT_FOUND,
+                    CollectionError::CollectionAlreadyExists { .. }
+                    | CollectionError::AliasAlreadyExists { .. }
+                    | CollectionError::CollectionHasAliases { .. } => StatusCode::CONFLICT,
+                    CollectionError::InvalidConfiguration { .. }
+                    | CollectionError::InvalidAlias { .. }
+                    | CollectionError::InvalidName { .. } => StatusCode::BAD_REQUEST,
+                    CollectionError::DatabaseError(_)
+                    | CollectionError::IoError(_)
+                    | CollectionError::SerializationError(_) => StatusCode::INTERNAL_SERVER_ERROR,
+                };
+                (status, e.to_string())
+            }
+            Error::Replication(e) => (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()),
             Error::Server(_) | Error::Internal(_) => {
                 (StatusCode::INTERNAL_SERVER_ERROR, self.to_string())
             }
             Error::Config(_) => (StatusCode::INTERNAL_SERVER_ERROR, self.to_string()),
             Error::Serialization(e) => (StatusCode::BAD_REQUEST, e.to_string()),
-        };
+        }
+    }
+}
+
+impl IntoResponse for Error {
+    fn into_response(self) -> Response {
+        let (status, error_message) = self.status_and_message();
 
         let body = Json(json!({
             "error": error_message,
@@ -74,3 +126,19 @@ impl IntoResponse for Error {
         (status, body).into_response()
     }
 }
+
+impl From<Error> for tonic::Status {
+    fn from(error: Error) -> Self {
+        let (status, message) = error.status_and_message();
+        let code = match status {
+            StatusCode::NOT_FOUND => tonic::Code::NotFound,
+            StatusCode::CONFLICT => tonic::Code::AlreadyExists,
+            StatusCode::BAD_REQUEST => tonic::Code::InvalidArgument,
+            StatusCode::UNAUTHORIZED => tonic::Code::Unauthenticated,
+            StatusCode::FORBIDDEN => tonic::Code::PermissionDenied,
+            StatusCode::TOO_MANY_REQUESTS => tonic::Code::ResourceExhausted,
+            _ => tonic::Code::Internal,
+        };
+        tonic::Status::new(code, message)
+    }
+}
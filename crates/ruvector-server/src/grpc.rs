@@ -0,0 +1,442 @@
+//! gRPC API mirroring the REST collections and points routes
+//!
+//! Services are generated from `proto/ruvector.proto` and share the
+//! [`AppState`] of the REST router, so both transports see the same
+//! collections.
+
+use crate::{
+    auth::{self, AuthLayer, Principal},
+    error::Error,
+    routes::points::{apply_score_threshold, embed_missing},
+    state::AppState,
+    Result,
+};
+use ruvector_collections::CollectionConfig;
+use ruvector_core::{
+    DistanceMetric, RuvectorError, ScrollRequest, SearchFilter, SearchQuery, SearchResult,
+    VectorDB, VectorEntry,
+};
+use serde::de::DeserializeOwned;
+use std::collections::HashMap;
+use std::sync::Arc;
+use tonic::{Request, Response, Status, Streaming};
+use tower::Layer;
+
+/// Types and services generated from `proto/ruvector.proto`
+#[allow(missing_docs, clippy::all)]
+pub mod proto {
+    tonic::include_proto!("ruvector.v1");
+}
+
+use proto::{
+    collections_server::{Collections, CollectionsServer},
+    points_server::{Points, PointsServer},
+};
+
+/// Build the gRPC services over `state`, each behind `auth`
+pub fn routes(state: AppState, auth: AuthLayer) -> tonic::service::Routes {
+    tonic::service::Routes::new(auth.layer(CollectionsServer::new(CollectionsService {
+        state: state.clone(),
+    })))
+    .add_service(auth.layer(PointsServer::new(PointsService { state })))
+}
+
+/// Collection management service
+#[derive(Clone)]
+pub struct CollectionsService {
+    state: AppState,
+}
+
+#[tonic::async_trait]
+impl Collections for CollectionsService {
+    async fn create(
+        &self,
+        request: Request<proto::CreateCollectionRequest>,
+    ) -> std::result::Result<Response<proto::CollectionInfo>, Status> {
+        let req = request.into_inner();
+        if self.state.contains_collection(&req.name) {
+            return Err(Error::CollectionExists(req.name).into());
+        }
+
+        let config = CollectionConfig {
+            distance_metric: metric_from_proto(req.metric)?.unwrap_or(DistanceMetric::Cosine),
+            ..CollectionConfig::with_dimensions(req.dimension as usize)
+        };
+        self.state
+            .manager
+            .create_collection(&req.name, config)
+            .map_err(Error::from)?;
+
+        let db = collection(&self.state, &req.name)?;
+        Ok(Response::new(collection_info(req.name, &db)))
+    }
+
+    async fn list(
+        &self,
+        request: Request<proto::ListCollectionsRequest>,
+    ) -> std::result::Result<Response<proto::ListCollectionsResponse>, Status> {
+        let mut collections = self.state.collection_names();
+        if let Some(principal) = request.extensions().get::<Principal>() {
+            collections.retain(|name| principal.allows_collection(name));
+        }
+        Ok(Response::new(proto::ListCollectionsResponse {
+            collections,
+        }))
+    }
+
+    async fn get(
+        &self,
+        request: Request<proto::GetCollectionRequest>,
+    ) -> std::result::Result<Response<proto::CollectionInfo>, Status> {
+        let name = authorized(request, |req| &req.name)?.name;
+        let db = collection(&self.state, &name)?;
+        Ok(Response::new(collection_info(name, &db)))
+    }
+
+    async fn delete(
+        &self,
+        request: Request<proto::DeleteCollectionRequest>,
+    ) -> std::result::Result<Response<proto::DeleteCollectionResponse>, Status> {
+        self.state
+            .manager
+            .delete_collection(&request.into_inner().name)
+            .map_err(Error::from)?;
+        Ok(Response::new(proto::DeleteCollectionResponse {}))
+    }
+}
+
+/// Point operations service
+#[derive(Clone)]
+pub struct PointsService {
+    state: AppState,
+}
+
+#[tonic::async_trait]
+impl Points for PointsService {
+    async fn upsert(
+        &self,
+        request: Request<proto::UpsertPointsRequest>,
+    ) -> std::result::Result<Response<proto::UpsertPointsResponse>, Status> {
+        let ids = upsert(&self.state, authorized(request, |req| &req.collection)?)?;
+        Ok(Response::new(proto::UpsertPointsResponse { ids }))
+    }
+
+    async fn upsert_stream(
+        &self,
+        request: Request<Streaming<proto::UpsertPointsRequest>>,
+    ) -> std::result::Result<Response<proto::UpsertPointsResponse>, Status> {
+        let principal = request.extensions().get::<Principal>().cloned();
+        let mut stream = request.into_inner();
+        let mut ids = Vec::new();
+        while let Some(batch) = stream.message().await? {
+            if let Some(principal) = &principal {
+                principal.authorize_collection(&batch.collection)?;
+            }
+            ids.extend(upsert(&self.state, batch)?);
+        }
+        Ok(Response::new(proto::UpsertPointsResponse { ids }))
+    }
+
+    async fn search(
+        &self,
+        request: Request<proto::SearchRequest>,
+    ) -> std::result::Result<Response<proto::SearchResponse>, Status> {
+        let req = authorized(request, |req| &req.collection)?;
+        let db = collection(&self.state, &req.collection)?;
+        let threshold = req.score_threshold;
+        let query = search_queries(&db, vec![req])?.remove(0);
+
+        let mut results = db.search(query).map_err(Error::Core)?;
+        apply_score_threshold(&mut results, threshold);
+
+        Ok(Response::new(search_response(results)?))
+    }
+
+    async fn search_batch(
+        &self,
+        request: Request<proto::SearchBatchRequest>,
+    ) -> std::result::Result<Response<proto::SearchBatchResponse>, Status> {
+        let req = authorized(request, |req| &req.collection)?;
+        let db = collection(&self.state, &req.collection)?;
+        let thresholds: Vec<Option<f32>> = req.searches.iter().map(|s| s.score_threshold).collect();
+        let queries = search_queries(&db, req.searches)?;
+
+        let results = db
+            .search_batch(queries)
+            .map_err(Error::Core)?
+            .into_iter()
+            .zip(thresholds)
+            .map(|(mut results, threshold)| {
+                apply_score_threshold(&mut results, threshold);
+                search_response(results)
+            })
+            .collect::<Result<_>>()?;
+
+        Ok(Response::new(proto::SearchBatchResponse { results }))
+    }
+
+    async fn get(
+        &self,
+        request: Request<proto::GetPointsRequest>,
+    ) -> std::result::Result<Response<proto::GetPointsResponse>, Status> {
+        let req = authorized(request, |req| &req.collection)?;
+        let db = collection(&self.state, &req.collection)?;
+        let with_vector = req.with_vector.unwrap_or(true);
+        let with_payload = req.with_payload.unwrap_or(true);
+
+        let ids: Vec<&str> = req.ids.iter().map(String::as_str).collect();
+        let points = db
+            .get_batch(&ids)
+            .map_err(Error::Core)?
+            .into_iter()
+            .zip(&req.ids)
+            .filter_map(|(entry, id)| {
+                let entry = entry?;
+                Some(point_record(
+                    id.clone(),
+                    with_vector.then_some(entry.vector),
+                    entry.metadata.filter(|_| with_payload),
+                ))
+            })
+            .collect::<Result<_>>()?;
+
+        Ok(Response::new(proto::GetPointsResponse { points }))
+    }
+
+    async fn delete(
+        &self,
+        request: Request<proto::DeletePointsRequest>,
+    ) -> std::result::Result<Response<proto::DeletePointsResponse>, Status> {
+        let req = authorized(request, |req| &req.collection)?;
+        let db = collection(&self.state, &req.collection)?;
+
+        let deleted = match (req.ids.is_empty(), req.filter) {
+            (false, None) => {
+                let ids: Vec<&str> = req.ids.iter().map(String::as_str).collect();
+                let deleted = db.delete_batch(&ids).map_err(Error::Core)?;
+                deleted.into_iter().filter(|&deleted| deleted).count()
+            }
+            (true, Some(filter)) => {
+                let filter: SearchFilter = parse_json("filter", &filter)?;
+                db.delete_by_filter(&filter.to_expression())
+                    .map_err(Error::Core)?
+            }
+            _ => {
+                return Err(Error::InvalidRequest(
+                    "Exactly one of ids or filter is required".to_string(),
+                )
+                .into())
+            }
+        };
+
+        Ok(Response::new(proto::DeletePointsResponse {
+            deleted: deleted as u64,
+        }))
+    }
+
+    async fn count(
+        &self,
+        request: Request<proto::CountPointsRequest>,
+    ) -> std::result::Result<Response<proto::CountPointsResponse>, Status> {
+        let req = authorized(request, |req| &req.collection)?;
+        let db = collection(&self.state, &req.collection)?;
+
+        let filter = req
+            .filter
+            .map(|filter| parse_json::<SearchFilter>("filter", &filter))
+            .transpose()?
+            .map(|filter| filter.to_expression());
+        let count = db.count(filter.as_ref()).map_err(Error::Core)?;
+
+        Ok(Response::new(proto::CountPointsResponse {
+            count: count as u64,
+        }))
+    }
+
+    async fn scroll(
+        &self,
+        request: Request<proto::ScrollPointsRequest>,
+    ) -> std::result::Result<Response<proto::ScrollPointsResponse>, Status> {
+        let req = authorized(request, |req| &req.collection)?;
+        let db = collection(&self.state, &req.collection)?;
+
+        let page = db
+            .scroll(ScrollRequest {
+                offset: req.offset,
+                limit: if req.limit == 0 {
+                    100
+                } else {
+                    req.limit as usize
+                },
+                filter: req
+                    .filter
+                    .map(|filter| parse_json("filter", &filter))
+                    .transpose()?,
+                with_vector: req.with_vector.unwrap_or(true),
+                with_payload: req.with_payload.unwrap_or(true),
+            })
+            .map_err(|e| match e {
+                RuvectorError::InvalidParameter(message) => Error::InvalidRequest(message),
+                e => Error::Core(e),
+            })?;
+
+        let points = page
+            .points
+            .into_iter()
+            .map(|point| point_record(point.id, point.vector, point.metadata))
+            .collect::<Result<_>>()?;
+
+        Ok(Response::new(proto::ScrollPointsResponse {
+            points,
+            next_offset: page.next_offset,
+        }))
+    }
+}
+
+/// Unwrap a request after checking its caller may use the collection it
+/// names
+fn authorized<T>(request: Request<T>, collection: impl FnOnce(&T) -> &String) -> Result<T> {
+    auth::authorize_collection(request.extensions(), collection(request.get_ref()))?;
+    Ok(request.into_inner())
+}
+
+fn collection(state: &AppState, name: &str) -> Result<Arc<VectorDB>> {
+    state
+        .get_collection(name)
+        .ok_or_else(|| Error::CollectionNotFound(name.to_string()))
+}
+
+/// Upsert one batch of points, embedding the text of those without a vector
+fn upsert(state: &AppState, req: proto::UpsertPointsRequest) -> Result<Vec<String>> {
+    let db = collection(state, &req.collection)?;
+
+    let mut embedded = embed_missing(
+        &db,
+        req.points
+            .iter()
+            .map(|point| (!point.vector.is_empty(), point.text.as_deref())),
+    )?;
+
+    let entries = req
+        .points
+        .into_iter()
+        .map(|point| {
+            let vector = if point.vector.is_empty() {
+                embedded.next().unwrap_or_default()
+            } else {
+                point.vector
+            };
+            Ok(VectorEntry {
+                id: point.id,
+                vector,
+                metadata: point
+                    .metadata
+                    .map(|metadata| parse_json("metadata", &metadata))
+                    .transpose()?,
+            })
+        })
+        .collect::<Result<_>>()?;
+
+    db.insert_batch(entries).map_err(Error::Core)
+}
+
+/// Turn search requests into queries, embedding those given as text
+fn search_queries(db: &VectorDB, searches: Vec<proto::SearchRequest>) -> Result<Vec<SearchQuery>> {
+    let mut embedded = embed_missing(
+        db,
+        searches
+            .iter()
+            .map(|search| (!search.vector.is_empty(), search.text.as_deref())),
+    )?;
+
+    searches
+        .into_iter()
+        .map(|search| {
+            let vector = if search.vector.is_empty() {
+                embedded.next().unwrap_or_default()
+            } else {
+                search.vector
+            };
+            Ok(SearchQuery {
+                vector,
+                k: if search.k == 0 { 10 } else { search.k as usize },
+                filter: search
+                    .filter
+                    .map(|filter| parse_json("filter", &filter))
+                    .transpose()?,
+                ef_search: search.ef_search.map(|ef_search| ef_search as usize),
+            })
+        })
+        .collect()
+}
+
+fn search_response(results: Vec<SearchResult>) -> Result<proto::SearchResponse> {
+    let results = results
+        .into_iter()
+        .map(|result| {
+            Ok(proto::ScoredPoint {
+                id: result.id,
+                score: result.score,
+                vector: result.vector.unwrap_or_default(),
+                metadata: result.metadata.map(|m| to_json(&m)).transpose()?,
+            })
+        })
+        .collect::<Result<_>>()?;
+    Ok(proto::SearchResponse { results })
+}
+
+fn point_record(
+    id: String,
+    vector: Option<Vec<f32>>,
+    metadata: Option<HashMap<String, serde_json::Value>>,
+) -> Result<proto::PointRecord> {
+    Ok(proto::PointRecord {
+        id,
+        vector: vector.unwrap_or_default(),
+        metadata: metadata.map(|m| to_json(&m)).transpose()?,
+    })
+}
+
+fn collection_info(name: String, db: &VectorDB) -> proto::CollectionInfo {
+    let options = db.options();
+    proto::CollectionInfo {
+        name,
+        dimension: options.dimensions as u32,
+        metric: metric_to_proto(options.distance_metric) as i32,
+        ef_search: db.ef_search() as u32,
+    }
+}
+
+/// Parse a JSON-encoded request field
+fn parse_json<T: DeserializeOwned>(field: &str, text: &str) -> Result<T> {
+    serde_json::from_str(text)
+        .map_err(|e| Error::InvalidRequest(format!("Invalid {} JSON: {}", field, e)))
+}
+
+fn to_json(metadata: &HashMap<String, serde_json::Value>) -> Result<String> {
+    Ok(serde_json::to_string(metadata)?)
+}
+
+/// Metric of a request, `None` if unspecified
+fn metric_from_proto(metric: i32) -> Result<Option<DistanceMetric>> {
+    match proto::DistanceMetric::try_from(metric) {
+        Ok(proto::DistanceMetric::Unspecified) => Ok(None),
+        Ok(proto::DistanceMetric::Euclidean) => Ok(Some(DistanceMetric::Euclidean)),
+        Ok(proto::DistanceMetric::Cosine) => Ok(Some(DistanceMetric::Cosine)),
+        Ok(proto::DistanceMetric::DotProduct) => Ok(Some(DistanceMetric::DotProduct)),
+        Ok(proto::DistanceMetric::Manhattan) => Ok(Some(DistanceMetric::Manhattan)),
+        Err(_) => Err(Error::InvalidRequest(format!(
+            "Unknown distance metric: {}",
+            metric
+        ))),
+    }
+}
+
+fn metric_to_proto(metric: DistanceMetric) -> proto::DistanceMetric {
+    match metric {
+        DistanceMetric::Euclidean => proto::DistanceMetric::Euclidean,
+        DistanceMetric::Cosine => proto::DistanceMetric::Cosine,
+        DistanceMetric::DotProduct => proto::DistanceMetric::DotProduct,
+        DistanceMetric::Manhattan => proto::DistanceMetric::Manhattan,
+    }
+}
@@ -1,23 +1,36 @@
-//! ruvector-server: REST API server for rUvector vector database
+//! ruvector-server: REST and gRPC API server for rUvector vector database
 //!
-//! This crate provides a REST API server built on axum for interacting with rUvector.
+//! This crate provides a REST API server built on axum, and a tonic gRPC
+//! server on a second port, for interacting with rUvector.
 
+pub mod auth;
 pub mod error;
+pub mod grpc;
 pub mod routes;
 pub mod state;
 
-use axum::{routing::get, Router};
+use auth::{AuthLayer, Authenticator};
+use axum::{http::HeaderValue, routing::get, Router};
+use ruvector_replication::ChangeLog;
 use serde::{Deserialize, Serialize};
 use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::sync::Arc;
 use tower_http::{
     compression::CompressionLayer,
-    cors::{Any, CorsLayer},
+    cors::{AllowOrigin, Any, CorsLayer},
     trace::TraceLayer,
 };
 
+pub use auth::{Access, ApiKeyConfig, AuthConfig, JwtAlgorithm, JwtConfig, RateLimit};
 pub use error::{Error, Result};
+pub use ruvector_replication::ChangeLogConfig;
 pub use state::AppState;
 
+/// Directory under the data directory holding the change log; collection
+/// names can't start with a dot
+const CHANGES_DIR: &str = ".changes";
+
 /// Server configuration
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Config {
@@ -27,8 +40,39 @@ pub struct Config {
     pub port: u16,
     /// Enable CORS
     pub enable_cors: bool,
+    /// Origins allowed by CORS; if empty, any origin while authentication
+    /// is disabled and none once it's enabled, leaving browsers to the
+    /// same-origin policy
+    #[serde(default)]
+    pub cors_allowed_origins: Vec<String>,
     /// Enable compression
     pub enable_compression: bool,
+    /// Directory holding the collections, reopened on startup
+    #[serde(default = "default_data_dir")]
+    pub data_dir: PathBuf,
+    /// gRPC server port, `None` to serve REST only
+    #[serde(default = "default_grpc_port")]
+    pub grpc_port: Option<u16>,
+    /// API key and JWT authentication, disabled if no credentials are
+    /// configured
+    #[serde(default)]
+    pub auth: AuthConfig,
+    /// Record every change to a collection's points in a durable log under
+    /// the data directory, served by the change feed endpoints; off by
+    /// default, since every write then also syncs the log
+    #[serde(default)]
+    pub capture_changes: bool,
+    /// Which captured changes are retained
+    #[serde(default)]
+    pub change_log: ChangeLogConfig,
+}
+
+fn default_data_dir() -> PathBuf {
+    PathBuf::from("./data")
+}
+
+fn default_grpc_port() -> Option<u16> {
+    Some(6334)
 }
 
 impl Default for Config {
@@ -37,7 +81,13 @@ impl Default for Config {
             host: "127.0.0.1".to_string(),
             port: 6333,
             enable_cors: true,
+            cors_allowed_origins: Vec::new(),
             enable_compression: true,
+            data_dir: default_data_dir(),
+            grpc_port: default_grpc_port(),
+            auth: AuthConfig::default(),
+            capture_changes: false,
+            change_log: ChangeLogConfig::default(),
         }
     }
 }
@@ -46,44 +96,100 @@ impl Default for Config {
 pub struct RuvectorServer {
     config: Config,
     state: AppState,
+    authenticator: Arc<Authenticator>,
+    cors_origins: Vec<HeaderValue>,
 }
 
 impl RuvectorServer {
     /// Create a new server instance with default configuration
-    pub fn new() -> Self {
-        Self {
-            config: Config::default(),
-            state: AppState::new(),
-        }
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the collections in the data directory can't be
+    /// opened
+    pub fn new() -> Result<Self> {
+        Self::with_config(Config::default())
     }
 
-    /// Create a new server instance with custom configuration
-    pub fn with_config(config: Config) -> Self {
-        Self {
-            config,
-            state: AppState::new(),
+    /// Create a new server instance with custom configuration, reopening
+    /// the collections stored in its data directory
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the collections in the data directory can't be
+    /// opened
+    pub fn with_config(config: Config) -> Result<Self> {
+        let mut state = AppState::open(&config.data_dir)?;
+        tracing::info!(
+            "Opened {} collections from {}",
+            state.collection_count(),
+            config.data_dir.display()
+        );
+        if config.capture_changes {
+            let log = Arc::new(ChangeLog::with_config(
+                config.data_dir.join(CHANGES_DIR),
+                config.change_log.clone(),
+            )?);
+            log.start_compaction()?;
+            tracing::info!("Capturing changes after sequence {}", log.last_sequence());
+            state = state.with_change_log(log)?;
+        }
+        let authenticator = Arc::new(Authenticator::new(&config.auth)?);
+        if !authenticator.is_enabled() {
+            tracing::warn!("No API keys or JWT key configured, authentication is disabled");
+        } else if config.enable_cors && config.cors_allowed_origins.is_empty() {
+            tracing::warn!(
+                "Authentication is enabled and no CORS origins are configured, \
+                 cross-origin requests are refused"
+            );
         }
+        let cors_origins = config
+            .cors_allowed_origins
+            .iter()
+            .map(|origin| {
+                origin
+                    .parse()
+                    .map_err(|_| Error::Config(format!("Invalid CORS origin: {}", origin)))
+            })
+            .collect::<Result<_>>()?;
+
+        Ok(Self {
+            config,
+            state,
+            authenticator,
+            cors_origins,
+        })
     }
 
-    /// Build the router with all routes
-    fn build_router(&self) -> Router {
+    /// Shared state behind both transports
+    pub fn state(&self) -> &AppState {
+        &self.state
+    }
+
+    /// Build the REST router with all routes
+    pub fn router(&self) -> Router {
         let mut router = Router::new()
             .route("/health", get(routes::health::health_check))
             .route("/ready", get(routes::health::readiness))
             .nest("/collections", routes::collections::routes())
+            .nest("/aliases", routes::aliases::routes())
             .merge(routes::points::routes())
+            .merge(routes::changes::routes())
             .with_state(self.state.clone());
 
-        // Add middleware layers
-        router = router.layer(TraceLayer::new_for_http());
+        // Add middleware layers; CORS goes outermost so preflight requests
+        // don't need credentials
+        router = router
+            .layer(AuthLayer::new(self.authenticator.clone()))
+            .layer(TraceLayer::new_for_http());
 
         if self.config.enable_compression {
             router = router.layer(CompressionLayer::new());
         }
 
-        if self.config.enable_cors {
+        if let Some(origins) = self.allowed_origins() {
             let cors = CorsLayer::new()
-                .allow_origin(Any)
+                .allow_origin(origins)
                 .allow_methods(Any)
                 .allow_headers(Any);
             router = router.layer(cors);
@@ -92,17 +198,37 @@ impl RuvectorServer {
         router
     }
 
-    /// Start the server
+    /// Origins the CORS layer allows, `None` to leave it out; an
+    /// authenticated server never allows any origin by default
+    fn allowed_origins(&self) -> Option<AllowOrigin> {
+        if !self.config.enable_cors {
+            None
+        } else if !self.cors_origins.is_empty() {
+            Some(AllowOrigin::list(self.cors_origins.clone()))
+        } else if self.authenticator.is_enabled() {
+            None
+        } else {
+            Some(AllowOrigin::any())
+        }
+    }
+
+    /// Build the gRPC services, sharing state with the REST router
+    pub fn grpc_routes(&self) -> tonic::service::Routes {
+        grpc::routes(
+            self.state.clone(),
+            AuthLayer::new(self.authenticator.clone()),
+        )
+    }
+
+    /// Start the server, serving gRPC alongside REST if a gRPC port is
+    /// configured
     ///
     /// # Errors
     ///
-    /// Returns an error if the server fails to bind or start
+    /// Returns an error if either server fails to bind or start
     pub async fn start(self) -> Result<()> {
-        let addr: SocketAddr = format!("{}:{}", self.config.host, self.config.port)
-            .parse()
-            .map_err(|e| Error::Config(format!("Invalid address: {}", e)))?;
-
-        let router = self.build_router();
+        let addr = self.address(self.config.port)?;
+        let router = self.router();
 
         tracing::info!("Starting ruvector-server on {}", addr);
 
@@ -110,16 +236,53 @@ impl RuvectorServer {
             .await
             .map_err(|e| Error::Server(format!("Failed to bind to {}: {}", addr, e)))?;
 
-        axum::serve(listener, router)
-            .await
-            .map_err(|e| Error::Server(format!("Server error: {}", e)))?;
+        let rest = async {
+            axum::serve(listener, router)
+                .await
+                .map_err(|e| Error::Server(format!("Server error: {}", e)))
+        };
+
+        match self.config.grpc_port {
+            Some(port) => {
+                let grpc_addr = self.address(port)?;
+                tracing::info!("Starting ruvector-server gRPC on {}", grpc_addr);
+
+                let grpc = async {
+                    tonic::transport::Server::builder()
+                        .add_routes(self.grpc_routes())
+                        .serve(grpc_addr)
+                        .await
+                        .map_err(|e| Error::Server(format!("gRPC server error: {}", e)))
+                };
+                tokio::try_join!(rest, grpc)?;
+            }
+            None => rest.await?,
+        }
 
         Ok(())
     }
+
+    fn address(&self, port: u16) -> Result<SocketAddr> {
+        format!("{}:{}", self.config.host, port)
+            .parse()
+            .map_err(|e| Error::Config(format!("Invalid address: {}", e)))
+    }
 }
 
-impl Default for RuvectorServer {
-    fn default() -> Self {
-        Self::new()
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_config_without_newer_fields_uses_defaults() {
+        let config: Config = serde_json::from_str(
+            r#"{"host": "0.0.0.0", "port": 8080, "enable_cors": false, "enable_compression": true}"#,
+        )
+        .unwrap();
+        let defaults = Config::default();
+        assert_eq!(config.port, 8080);
+        assert_eq!(config.data_dir, defaults.data_dir);
+        assert_eq!(config.grpc_port, defaults.grpc_port);
+        assert_eq!(config.capture_changes, defaults.capture_changes);
     }
 }
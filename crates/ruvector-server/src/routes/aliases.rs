@@ -0,0 +1,143 @@
+//! Collection alias endpoints
+
+use crate::{auth::Principal, state::AppState, Result};
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::IntoResponse,
+    routing::get,
+    Extension, Json, Router,
+};
+use ruvector_collections::CollectionError;
+use serde::{Deserialize, Serialize};
+
+/// Alias creation request
+#[derive(Debug, Deserialize)]
+pub struct CreateAliasRequest {
+    /// Alias name
+    pub alias: String,
+    /// Collection the alias points to
+    pub collection: String,
+}
+
+/// Alias switch request
+#[derive(Debug, Deserialize)]
+pub struct SwitchAliasRequest {
+    /// Collection the alias should point to
+    pub collection: String,
+}
+
+/// Alias and the collection it points to
+#[derive(Debug, Serialize)]
+pub struct AliasInfo {
+    /// Alias name
+    pub alias: String,
+    /// Target collection name
+    pub collection: String,
+}
+
+/// List of aliases response
+#[derive(Debug, Serialize)]
+pub struct AliasesList {
+    /// Aliases sorted by name
+    pub aliases: Vec<AliasInfo>,
+}
+
+/// Create alias routes
+pub fn routes() -> Router<AppState> {
+    Router::new()
+        .route("/", get(list_aliases).post(create_alias))
+        .route(
+            "/:alias",
+            get(get_alias).put(switch_alias).delete(delete_alias),
+        )
+}
+
+/// Create an alias for a collection
+///
+/// POST /aliases
+async fn create_alias(
+    State(state): State<AppState>,
+    Json(req): Json<CreateAliasRequest>,
+) -> Result<impl IntoResponse> {
+    state.manager.create_alias(&req.alias, &req.collection)?;
+
+    Ok((
+        StatusCode::CREATED,
+        Json(AliasInfo {
+            alias: req.alias,
+            collection: req.collection,
+        }),
+    ))
+}
+
+/// List the aliases of collections the caller may use
+///
+/// GET /aliases
+async fn list_aliases(
+    State(state): State<AppState>,
+    principal: Option<Extension<Principal>>,
+) -> Result<impl IntoResponse> {
+    let mut aliases: Vec<AliasInfo> = state
+        .manager
+        .list_aliases()
+        .into_iter()
+        .map(|(alias, collection)| AliasInfo { alias, collection })
+        .collect();
+    if let Some(Extension(principal)) = principal {
+        aliases.retain(|info| principal.allows_collection(&info.collection));
+    }
+    aliases.sort_by(|a, b| a.alias.cmp(&b.alias));
+
+    Ok(Json(AliasesList { aliases }))
+}
+
+/// Get the collection an alias points to
+///
+/// GET /aliases/:alias
+async fn get_alias(
+    State(state): State<AppState>,
+    principal: Option<Extension<Principal>>,
+    Path(alias): Path<String>,
+) -> Result<impl IntoResponse> {
+    let collection =
+        state
+            .manager
+            .resolve_alias(&alias)
+            .ok_or_else(|| CollectionError::AliasNotFound {
+                alias: alias.clone(),
+            })?;
+    if let Some(Extension(principal)) = principal {
+        principal.authorize_collection(&collection)?;
+    }
+
+    Ok(Json(AliasInfo { alias, collection }))
+}
+
+/// Point an existing alias at another collection
+///
+/// PUT /aliases/:alias
+async fn switch_alias(
+    State(state): State<AppState>,
+    Path(alias): Path<String>,
+    Json(req): Json<SwitchAliasRequest>,
+) -> Result<impl IntoResponse> {
+    state.manager.switch_alias(&alias, &req.collection)?;
+
+    Ok(Json(AliasInfo {
+        alias,
+        collection: req.collection,
+    }))
+}
+
+/// Delete an alias
+///
+/// DELETE /aliases/:alias
+async fn delete_alias(
+    State(state): State<AppState>,
+    Path(alias): Path<String>,
+) -> Result<impl IntoResponse> {
+    state.manager.delete_alias(&alias)?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
@@ -0,0 +1,241 @@
+//! Change feed endpoints
+//!
+//! Followers read the inserts, updates and deletes of a collection's points
+//! by sequence number, either by long-polling or as server-sent events, and
+//! may store how far they got as a named consumer's checkpoint.
+
+use crate::{error::Error, state::AppState, Result};
+use axum::{
+    extract::{Path, Query, State},
+    http::HeaderMap,
+    response::{
+        sse::{Event, KeepAlive, Sse},
+        IntoResponse,
+    },
+    routing::get,
+    Json, Router,
+};
+use futures::stream::{self, Stream, StreamExt};
+use ruvector_replication::{ChangeLog, Checkpoint};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Most events returned by one request, or sent in one go on a stream
+const MAX_LIMIT: usize = 1000;
+
+/// Longest a long-poll waits for an event
+const MAX_WAIT: Duration = Duration::from_secs(60);
+
+/// Long-poll request
+#[derive(Debug, Deserialize)]
+pub struct ChangesQuery {
+    /// Sequence to read after; the consumer's checkpoint if omitted
+    pub after: Option<u64>,
+    /// Maximum number of events to return
+    #[serde(default = "default_limit")]
+    pub limit: usize,
+    /// Milliseconds to wait for an event if there is none yet
+    #[serde(default)]
+    pub timeout_ms: u64,
+    /// Consumer whose checkpoint to start from
+    pub consumer: Option<String>,
+}
+
+fn default_limit() -> usize {
+    100
+}
+
+/// Stream request
+#[derive(Debug, Deserialize)]
+pub struct StreamQuery {
+    /// Sequence to stream after; the `Last-Event-ID` header or the
+    /// consumer's checkpoint if omitted
+    pub after: Option<u64>,
+    /// Consumer whose checkpoint to start from
+    pub consumer: Option<String>,
+}
+
+/// Checkpoint commit request
+#[derive(Debug, Deserialize)]
+pub struct CommitRequest {
+    /// Sequence of the last event the consumer processed
+    pub sequence: u64,
+}
+
+/// A consumer's committed checkpoint
+#[derive(Debug, Serialize)]
+pub struct ConsumerInfo {
+    /// Consumer name
+    pub consumer: String,
+    /// Sequence of the last event the consumer processed, 0 if it never
+    /// committed
+    pub sequence: u64,
+}
+
+/// Create change feed routes
+pub fn routes() -> Router<AppState> {
+    Router::new()
+        .route("/collections/:name/changes", get(poll_changes))
+        .route("/collections/:name/changes/stream", get(stream_changes))
+        .route(
+            "/collections/:name/changes/consumers/:consumer",
+            get(get_consumer).put(commit_consumer),
+        )
+}
+
+/// Long-poll the changes to a collection's points
+///
+/// GET /collections/:name/changes
+async fn poll_changes(
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+    Query(query): Query<ChangesQuery>,
+) -> Result<impl IntoResponse> {
+    let (log, collection) = change_log(&state, name)?;
+    let after = match query.after {
+        Some(after) => after,
+        None => committed(&log, &collection, query.consumer.as_deref()),
+    };
+    let limit = query.limit.clamp(1, MAX_LIMIT);
+    let timeout = Duration::from_millis(query.timeout_ms).min(MAX_WAIT);
+
+    let batch = log
+        .read_wait(after, limit, Some(collection.as_str()), timeout)
+        .await?;
+    Ok(Json(batch))
+}
+
+/// Stream the changes to a collection's points as server-sent events
+///
+/// Each event's id is its sequence, so a reconnecting client resumes with
+/// the `Last-Event-ID` header.
+///
+/// GET /collections/:name/changes/stream
+async fn stream_changes(
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+    Query(query): Query<StreamQuery>,
+    headers: HeaderMap,
+) -> Result<impl IntoResponse> {
+    let (log, collection) = change_log(&state, name)?;
+    let last_event_id = headers
+        .get("last-event-id")
+        .map(|id| {
+            id.to_str()
+                .ok()
+                .and_then(|id| id.parse().ok())
+                .ok_or_else(|| Error::InvalidRequest("Invalid Last-Event-ID".to_string()))
+        })
+        .transpose()?;
+    let after = match query.after.or(last_event_id) {
+        Some(after) => after,
+        None => committed(&log, &collection, query.consumer.as_deref()),
+    };
+
+    let events = change_events(log, collection, after).map(|event| {
+        Event::default()
+            .id(event.sequence.to_string())
+            .event("change")
+            .json_data(&event)
+    });
+    Ok(Sse::new(events).keep_alive(KeepAlive::default()))
+}
+
+/// The events of `collection` after sequence `after`, waiting for each to
+/// be published
+///
+/// Ends if the log can't be read, so the client reconnects and resumes.
+fn change_events(
+    log: Arc<ChangeLog>,
+    collection: String,
+    after: u64,
+) -> impl Stream<Item = ruvector_replication::ChangeEvent> {
+    stream::unfold(after, move |mut after| {
+        let log = log.clone();
+        let collection = collection.clone();
+        async move {
+            loop {
+                let batch = match log.read(after, MAX_LIMIT, Some(collection.as_str())) {
+                    Ok(batch) => batch,
+                    Err(e) => {
+                        tracing::error!("Failed to read changes after {}: {}", after, e);
+                        return None;
+                    }
+                };
+                after = batch.next;
+                if !batch.events.is_empty() {
+                    return Some((stream::iter(batch.events), after));
+                }
+                // Checks the last sequence first, so nothing published since
+                // the read is missed
+                log.wait_for(after, MAX_WAIT).await;
+            }
+        }
+    })
+    .flatten()
+}
+
+/// Get a consumer's committed checkpoint
+///
+/// GET /collections/:name/changes/consumers/:consumer
+async fn get_consumer(
+    State(state): State<AppState>,
+    Path((name, consumer)): Path<(String, String)>,
+) -> Result<impl IntoResponse> {
+    let (log, collection) = change_log(&state, name)?;
+    let sequence = committed(&log, &collection, Some(consumer.as_str()));
+
+    Ok(Json(ConsumerInfo { consumer, sequence }))
+}
+
+/// Commit a consumer's checkpoint
+///
+/// PUT /collections/:name/changes/consumers/:consumer
+async fn commit_consumer(
+    State(state): State<AppState>,
+    Path((name, consumer)): Path<(String, String)>,
+    Json(req): Json<CommitRequest>,
+) -> Result<impl IntoResponse> {
+    let (log, collection) = change_log(&state, name)?;
+    if req.sequence > log.last_sequence() {
+        return Err(Error::InvalidRequest(format!(
+            "Sequence {} is past the last change {}",
+            req.sequence,
+            log.last_sequence()
+        )));
+    }
+    log.commit(
+        &checkpoint_key(&collection, &consumer),
+        Checkpoint::new(req.sequence, consumer.clone()),
+    )?;
+
+    Ok(Json(ConsumerInfo {
+        consumer,
+        sequence: req.sequence,
+    }))
+}
+
+/// The change log and the collection `name` refers to, resolving aliases
+fn change_log(state: &AppState, name: String) -> Result<(Arc<ChangeLog>, String)> {
+    let log = state.changes.clone().ok_or_else(|| {
+        Error::InvalidRequest("Change capture is disabled on this server".to_string())
+    })?;
+    let collection = state.manager.resolve_alias(&name).unwrap_or(name);
+    if !state.contains_collection(&collection) {
+        return Err(Error::CollectionNotFound(collection));
+    }
+    Ok((log, collection))
+}
+
+/// Sequence `consumer` of `collection` last committed, 0 without one
+fn committed(log: &ChangeLog, collection: &str, consumer: Option<&str>) -> u64 {
+    consumer
+        .and_then(|consumer| log.checkpoint(&checkpoint_key(collection, consumer)))
+        .map_or(0, |checkpoint| checkpoint.sequence)
+}
+
+/// Consumers are named per collection
+fn checkpoint_key(collection: &str, consumer: &str) -> String {
+    format!("{}/{}", collection, consumer)
+}
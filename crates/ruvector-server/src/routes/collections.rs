@@ -1,16 +1,17 @@
 //! Collection management endpoints
 
-use crate::{error::Error, state::AppState, Result};
+use crate::{auth::Principal, error::Error, state::AppState, Result};
 use axum::{
-    extract::{Path, State},
+    extract::{Extension, Path, State},
     http::StatusCode,
     response::IntoResponse,
-    routing::{get, post},
+    routing::{get, post, put},
     Json, Router,
 };
-use ruvector_core::{types::DbOptions, DistanceMetric, VectorDB};
+use ruvector_collections::CollectionConfig;
+use ruvector_core::{DistanceMetric, EmbeddingConfig, VectorDB, VectorSpaceConfig};
 use serde::{Deserialize, Serialize};
-use std::sync::Arc;
+use std::collections::BTreeMap;
 
 /// Collection creation request
 #[derive(Debug, Deserialize)]
@@ -21,6 +22,8 @@ pub struct CreateCollectionRequest {
     pub dimension: usize,
     /// Distance metric (optional, defaults to Cosine)
     pub metric: Option<DistanceMetric>,
+    /// Provider embedding text given instead of vectors (optional)
+    pub embedding: Option<EmbeddingConfig>,
 }
 
 /// Collection info response
@@ -32,6 +35,22 @@ pub struct CollectionInfo {
     pub dimension: usize,
     /// Distance metric
     pub metric: DistanceMetric,
+    /// HNSW ef_search used by queries that don't set one
+    pub ef_search: usize,
+}
+
+/// Collection update request
+#[derive(Debug, Deserialize)]
+pub struct UpdateCollectionRequest {
+    /// New default HNSW ef_search
+    pub ef_search: Option<usize>,
+}
+
+/// Named vector spaces of a collection
+#[derive(Debug, Serialize)]
+pub struct VectorSpacesList {
+    /// Space configurations by name
+    pub spaces: BTreeMap<String, VectorSpaceConfig>,
 }
 
 /// List of collections response
@@ -45,7 +64,18 @@ pub struct CollectionsList {
 pub fn routes() -> Router<AppState> {
     Router::new()
         .route("/", post(create_collection).get(list_collections))
-        .route("/:name", get(get_collection).delete(delete_collection))
+        .route(
+            "/:name",
+            get(get_collection)
+                .patch(update_collection)
+                .delete(delete_collection),
+        )
+        .route("/:name/stats", get(get_collection_stats))
+        .route("/:name/spaces", get(list_vector_spaces))
+        .route(
+            "/:name/spaces/:space",
+            put(create_vector_space).delete(delete_vector_space),
+        )
 }
 
 /// Create a new collection
@@ -59,29 +89,30 @@ async fn create_collection(
         return Err(Error::CollectionExists(req.name));
     }
 
-    let mut options = DbOptions::default();
-    options.dimensions = req.dimension;
-    options.distance_metric = req.metric.unwrap_or(DistanceMetric::Cosine);
-    // Use in-memory storage for server (storage path will be ignored for memory storage)
-    options.storage_path = format!("memory://{}", req.name);
-
-    let db = VectorDB::new(options.clone()).map_err(Error::Core)?;
-    state.insert_collection(req.name.clone(), Arc::new(db));
-
-    let info = CollectionInfo {
-        name: req.name,
-        dimension: req.dimension,
-        metric: options.distance_metric,
+    let config = CollectionConfig {
+        distance_metric: req.metric.unwrap_or(DistanceMetric::Cosine),
+        embedding: req.embedding,
+        ..CollectionConfig::with_dimensions(req.dimension)
     };
+    state.manager.create_collection(&req.name, config)?;
 
-    Ok((StatusCode::CREATED, Json(info)))
+    let db = state
+        .get_collection(&req.name)
+        .ok_or_else(|| Error::CollectionNotFound(req.name.clone()))?;
+    Ok((StatusCode::CREATED, Json(collection_info(req.name, &db))))
 }
 
-/// List all collections
+/// List the collections the caller may use
 ///
 /// GET /collections
-async fn list_collections(State(state): State<AppState>) -> Result<impl IntoResponse> {
-    let collections = state.collection_names();
+async fn list_collections(
+    State(state): State<AppState>,
+    principal: Option<Extension<Principal>>,
+) -> Result<impl IntoResponse> {
+    let mut collections = state.collection_names();
+    if let Some(Extension(principal)) = principal {
+        collections.retain(|name| principal.allows_collection(name));
+    }
     Ok(Json(CollectionsList { collections }))
 }
 
@@ -92,18 +123,35 @@ async fn get_collection(
     State(state): State<AppState>,
     Path(name): Path<String>,
 ) -> Result<impl IntoResponse> {
-    let _db = state
+    let db = state
         .get_collection(&name)
         .ok_or_else(|| Error::CollectionNotFound(name.clone()))?;
 
-    // Note: VectorDB doesn't expose config directly, so we return basic info
-    let info = CollectionInfo {
-        name,
-        dimension: 0, // Would need to be stored separately or queried from DB
-        metric: DistanceMetric::Cosine, // Default assumption
-    };
+    Ok(Json(collection_info(name, &db)))
+}
+
+/// Update runtime collection settings
+///
+/// PATCH /collections/:name
+async fn update_collection(
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+    Json(req): Json<UpdateCollectionRequest>,
+) -> Result<impl IntoResponse> {
+    let db = state
+        .get_collection(&name)
+        .ok_or_else(|| Error::CollectionNotFound(name.clone()))?;
+
+    if let Some(ef_search) = req.ef_search {
+        if ef_search == 0 {
+            return Err(Error::InvalidRequest(
+                "ef_search must be at least 1".to_string(),
+            ));
+        }
+        db.set_ef_search(ef_search).map_err(Error::Core)?;
+    }
 
-    Ok(Json(info))
+    Ok(Json(collection_info(name, &db)))
 }
 
 /// Delete a collection
@@ -113,9 +161,91 @@ async fn delete_collection(
     State(state): State<AppState>,
     Path(name): Path<String>,
 ) -> Result<impl IntoResponse> {
-    state
-        .remove_collection(&name)
+    state.manager.delete_collection(&name)?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Get collection statistics
+///
+/// GET /collections/:name/stats
+async fn get_collection_stats(
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+) -> Result<impl IntoResponse> {
+    Ok(Json(state.manager.collection_stats(&name)?))
+}
+
+/// List the named vector spaces of a collection
+///
+/// GET /collections/:name/spaces
+async fn list_vector_spaces(
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+) -> Result<impl IntoResponse> {
+    let db = state
+        .get_collection(&name)
+        .ok_or_else(|| Error::CollectionNotFound(name))?;
+
+    Ok(Json(VectorSpacesList {
+        spaces: db.vector_spaces(),
+    }))
+}
+
+/// Create a named vector space, e.g. a multi-vector space for token
+/// embeddings
+///
+/// PUT /collections/:name/spaces/:space
+async fn create_vector_space(
+    State(state): State<AppState>,
+    Path((name, space)): Path<(String, String)>,
+    Json(config): Json<VectorSpaceConfig>,
+) -> Result<impl IntoResponse> {
+    let db = state
+        .get_collection(&name)
         .ok_or_else(|| Error::CollectionNotFound(name))?;
 
+    if db.vector_spaces().contains_key(&space) {
+        return Err(Error::InvalidRequest(format!(
+            "Vector space already exists: {}",
+            space
+        )));
+    }
+    db.create_vector_space(&space, config)
+        .map_err(Error::Core)?;
+
+    Ok((
+        StatusCode::CREATED,
+        Json(VectorSpacesList {
+            spaces: db.vector_spaces(),
+        }),
+    ))
+}
+
+/// Drop a named vector space and its vectors
+///
+/// DELETE /collections/:name/spaces/:space
+async fn delete_vector_space(
+    State(state): State<AppState>,
+    Path((name, space)): Path<(String, String)>,
+) -> Result<impl IntoResponse> {
+    let db = state
+        .get_collection(&name)
+        .ok_or_else(|| Error::CollectionNotFound(name))?;
+
+    if !db.drop_vector_space(&space).map_err(Error::Core)? {
+        return Err(Error::VectorSpaceNotFound(space));
+    }
+
     Ok(StatusCode::NO_CONTENT)
 }
+
+fn collection_info(name: String, db: &VectorDB) -> CollectionInfo {
+    let options = db.options();
+    CollectionInfo {
+        name,
+        dimension: options.dimensions,
+        metric: options.distance_metric,
+        ef_search: db.ef_search(),
+    }
+}
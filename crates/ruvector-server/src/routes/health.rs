@@ -35,12 +35,17 @@ pub async fn health_check() -> Result<impl IntoResponse> {
 ///
 /// GET /ready
 pub async fn readiness(State(state): State<AppState>) -> Result<impl IntoResponse> {
-    let collections_count = state.collection_count();
+    let names = state.collection_names();
+    let mut total_points = 0;
+    for name in &names {
+        if let Some(db) = state.get_collection(name) {
+            total_points += db.len()?;
+        }
+    }
 
-    // Note: VectorDB doesn't expose count directly, so we report collections only
     Ok(Json(ReadinessStatus {
         status: "ready".to_string(),
-        collections: collections_count,
-        total_points: 0, // Would require tracking or querying each DB
+        collections: names.len(),
+        total_points,
     }))
 }
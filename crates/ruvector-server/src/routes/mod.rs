@@ -1,5 +1,7 @@
 //! API routes
 
+pub mod aliases;
+pub mod changes;
 pub mod collections;
 pub mod health;
 pub mod points;
@@ -8,7 +8,10 @@ use axum::{
     routing::{get, post, put},
     Json, Router,
 };
-use ruvector_core::{SearchQuery, SearchResult, VectorEntry};
+use ruvector_core::{
+    GroupedSearchQuery, PointGroup, PointRecord, RangeSearchQuery, RuvectorError, ScrollRequest,
+    SearchByIdQuery, SearchFilter, SearchQuery, SearchResult, VectorDB, VectorEntry, VectorId,
+};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 
@@ -16,27 +19,188 @@ use std::collections::HashMap;
 #[derive(Debug, Deserialize)]
 pub struct UpsertPointsRequest {
     /// Points to upsert
-    pub points: Vec<VectorEntry>,
+    pub points: Vec<PointInput>,
+}
+
+/// Point to upsert, given either a vector or text to embed with the
+/// collection's embedding provider
+#[derive(Debug, Deserialize)]
+pub struct PointInput {
+    /// Optional ID (auto-generated if not provided)
+    pub id: Option<VectorId>,
+    /// Vector data
+    pub vector: Option<Vec<f32>>,
+    /// Text to embed in place of a vector
+    pub text: Option<String>,
+    /// Optional metadata
+    pub metadata: Option<HashMap<String, serde_json::Value>>,
 }
 
 /// Search request
 #[derive(Debug, Deserialize)]
 pub struct SearchRequest {
     /// Query vector
-    pub vector: Vec<f32>,
+    pub vector: Option<Vec<f32>>,
+    /// Query text to embed in place of a vector
+    pub text: Option<String>,
     /// Number of results to return
     #[serde(default = "default_limit")]
     pub k: usize,
-    /// Optional score threshold
+    /// Optional maximum score; scores are distances, so hits scoring
+    /// above it are dropped
     pub score_threshold: Option<f32>,
-    /// Optional metadata filters
-    pub filter: Option<HashMap<String, serde_json::Value>>,
+    /// Optional metadata filter, either field/value equality pairs or a filter expression
+    pub filter: Option<SearchFilter>,
+    /// Optional HNSW ef_search overriding the collection default
+    pub ef_search: Option<usize>,
+    /// Metadata field to group hits by; `k` then counts groups
+    pub group_by: Option<String>,
+    /// Maximum number of hits per group
+    #[serde(default = "default_group_size")]
+    pub group_size: usize,
+}
+
+/// Batch search request
+#[derive(Debug, Deserialize)]
+pub struct SearchBatchRequest {
+    /// Searches to run; grouping is not supported in batches
+    pub searches: Vec<SearchRequest>,
+}
+
+fn default_group_size() -> usize {
+    1
 }
 
 fn default_limit() -> usize {
     10
 }
 
+/// Range search request
+#[derive(Debug, Deserialize)]
+pub struct RangeSearchRequest {
+    /// Named vector space to search, the default vector if omitted
+    pub space: Option<String>,
+    /// Query vector
+    pub vector: Vec<f32>,
+    /// Largest distance of a returned point
+    pub radius: f32,
+    /// Maximum number of points to return
+    #[serde(default = "default_range_limit")]
+    pub limit: usize,
+    /// Optional metadata filter, either field/value equality pairs or a filter expression
+    pub filter: Option<SearchFilter>,
+    /// Optional HNSW ef_search overriding the collection default
+    pub ef_search: Option<usize>,
+}
+
+fn default_range_limit() -> usize {
+    1000
+}
+
+/// Request for the neighbors of a stored point
+#[derive(Debug, Deserialize)]
+pub struct NeighborsRequest {
+    /// Named vector space to search, the default vector if omitted
+    pub space: Option<String>,
+    /// Number of results to return
+    #[serde(default = "default_limit")]
+    pub k: usize,
+    /// Optional metadata filter, either field/value equality pairs or a filter expression
+    pub filter: Option<SearchFilter>,
+    /// Optional HNSW ef_search overriding the collection default
+    pub ef_search: Option<usize>,
+}
+
+/// Scroll request
+#[derive(Debug, Deserialize)]
+pub struct ScrollPointsRequest {
+    /// `next_offset` of the previous page, omitted for the first page
+    pub offset: Option<String>,
+    /// Maximum number of points in the page
+    #[serde(default = "default_scroll_limit")]
+    pub limit: usize,
+    /// Optional metadata filter, either field/value equality pairs or a filter expression
+    pub filter: Option<SearchFilter>,
+    /// Whether to include vectors
+    #[serde(default = "default_true")]
+    pub with_vector: bool,
+    /// Whether to include metadata
+    #[serde(default = "default_true")]
+    pub with_payload: bool,
+}
+
+fn default_scroll_limit() -> usize {
+    100
+}
+
+fn default_true() -> bool {
+    true
+}
+
+/// Batch delete request, by ids or by filter
+#[derive(Debug, Deserialize)]
+pub struct DeletePointsRequest {
+    /// Ids of the points to delete
+    pub ids: Option<Vec<VectorId>>,
+    /// Metadata filter selecting the points to delete
+    pub filter: Option<SearchFilter>,
+}
+
+/// Batch get request
+#[derive(Debug, Deserialize)]
+pub struct GetPointsRequest {
+    /// Ids of the points to get
+    pub ids: Vec<VectorId>,
+    /// Whether to include vectors
+    #[serde(default = "default_true")]
+    pub with_vector: bool,
+    /// Whether to include metadata
+    #[serde(default = "default_true")]
+    pub with_payload: bool,
+}
+
+/// Count request
+#[derive(Debug, Deserialize)]
+pub struct CountPointsRequest {
+    /// Optional metadata filter; all points are counted without one
+    pub filter: Option<SearchFilter>,
+}
+
+/// Payload replace or merge request
+#[derive(Debug, Deserialize)]
+pub struct PayloadRequest {
+    /// Metadata fields
+    pub payload: HashMap<String, serde_json::Value>,
+}
+
+/// Payload key removal request
+#[derive(Debug, Deserialize)]
+pub struct DeletePayloadKeysRequest {
+    /// Metadata keys to remove
+    pub keys: Vec<String>,
+}
+
+/// Bag of vectors for a point in a multi-vector space
+#[derive(Debug, Deserialize)]
+pub struct SetMultiVectorRequest {
+    /// Equally sized vectors, e.g. one per token
+    pub vectors: Vec<Vec<f32>>,
+}
+
+/// Late-interaction search request
+#[derive(Debug, Deserialize)]
+pub struct MultiVectorSearchRequest {
+    /// Multi-vector space to search
+    pub space: String,
+    /// Query vectors, e.g. one per query token
+    pub vectors: Vec<Vec<f32>>,
+    /// Number of results to return
+    #[serde(default = "default_limit")]
+    pub k: usize,
+    /// Optional metadata filter, either field/value equality pairs or a filter expression
+    pub filter: Option<SearchFilter>,
+}
+
 /// Search response
 #[derive(Debug, Serialize)]
 pub struct SearchResponse {
@@ -44,6 +208,20 @@ pub struct SearchResponse {
     pub results: Vec<SearchResult>,
 }
 
+/// Batch search response
+#[derive(Debug, Serialize)]
+pub struct SearchBatchResponse {
+    /// Results of each search, in request order
+    pub results: Vec<Vec<SearchResult>>,
+}
+
+/// Grouped search response
+#[derive(Debug, Serialize)]
+pub struct GroupedSearchResponse {
+    /// Groups ordered by their best hit
+    pub groups: Vec<PointGroup>,
+}
+
 /// Upsert response
 #[derive(Debug, Serialize)]
 pub struct UpsertResponse {
@@ -51,12 +229,62 @@ pub struct UpsertResponse {
     pub ids: Vec<String>,
 }
 
+/// Batch delete response
+#[derive(Debug, Serialize)]
+pub struct DeletePointsResponse {
+    /// Number of points deleted
+    pub deleted: usize,
+}
+
+/// Batch get response
+#[derive(Debug, Serialize)]
+pub struct GetPointsResponse {
+    /// Points found, in request order; missing ids are left out
+    pub points: Vec<PointRecord>,
+}
+
+/// Count response
+#[derive(Debug, Serialize)]
+pub struct CountResponse {
+    /// Number of matching points
+    pub count: usize,
+}
+
 /// Create point routes
 pub fn routes() -> Router<AppState> {
     Router::new()
         .route("/collections/:name/points", put(upsert_points))
         .route("/collections/:name/points/search", post(search_points))
-        .route("/collections/:name/points/:id", get(get_point))
+        .route("/collections/:name/points/search/batch", post(search_batch))
+        .route("/collections/:name/points/scroll", post(scroll_points))
+        .route("/collections/:name/points/get", post(get_points))
+        .route("/collections/:name/points/delete", post(delete_points))
+        .route("/collections/:name/points/count", post(count_points))
+        .route(
+            "/collections/:name/points/search/multivector",
+            post(search_multivector),
+        )
+        .route("/collections/:name/points/search/range", post(search_range))
+        .route(
+            "/collections/:name/points/:id",
+            get(get_point).delete(delete_point),
+        )
+        .route(
+            "/collections/:name/points/:id/payload",
+            put(set_payload).patch(merge_payload),
+        )
+        .route(
+            "/collections/:name/points/:id/payload/delete",
+            post(delete_payload_keys),
+        )
+        .route(
+            "/collections/:name/points/:id/neighbors",
+            post(search_neighbors),
+        )
+        .route(
+            "/collections/:name/points/:id/multivectors/:space",
+            put(set_multivector),
+        )
 }
 
 /// Upsert points into a collection
@@ -71,12 +299,29 @@ async fn upsert_points(
         .get_collection(&name)
         .ok_or_else(|| Error::CollectionNotFound(name.clone()))?;
 
-    let ids = db.insert_batch(req.points).map_err(Error::Core)?;
+    let mut embedded = embed_missing(
+        &db,
+        req.points
+            .iter()
+            .map(|point| (point.vector.is_some(), point.text.as_deref())),
+    )?;
+
+    let entries = req
+        .points
+        .into_iter()
+        .map(|point| VectorEntry {
+            id: point.id,
+            vector: point.vector.or_else(|| embedded.next()).unwrap_or_default(),
+            metadata: point.metadata,
+        })
+        .collect();
+
+    let ids = db.insert_batch(entries).map_err(Error::Core)?;
 
     Ok((StatusCode::OK, Json(UpsertResponse { ids })))
 }
 
-/// Search for similar points
+/// Search for similar points, optionally grouped by a metadata field
 ///
 /// POST /collections/:name/points/search
 async fn search_points(
@@ -88,23 +333,275 @@ async fn search_points(
         .get_collection(&name)
         .ok_or_else(|| Error::CollectionNotFound(name))?;
 
+    let mut embedded = embed_missing(
+        &db,
+        [(req.vector.is_some(), req.text.as_deref())].into_iter(),
+    )?;
+    let vector = req.vector.or_else(|| embedded.next()).unwrap_or_default();
+    if let Some(group_by) = req.group_by {
+        let mut groups = db
+            .search_groups(GroupedSearchQuery {
+                space: None,
+                vector,
+                group_by,
+                groups: req.k,
+                group_size: req.group_size,
+                filter: req.filter,
+                ef_search: req.ef_search,
+            })
+            .map_err(|e| match e {
+                RuvectorError::InvalidParameter(message) => Error::InvalidRequest(message),
+                e => Error::Core(e),
+            })?;
+
+        for group in &mut groups {
+            apply_score_threshold(&mut group.hits, req.score_threshold);
+        }
+        groups.retain(|group| !group.hits.is_empty());
+
+        return Ok(Json(GroupedSearchResponse { groups }).into_response());
+    }
+
     let query = SearchQuery {
-        vector: req.vector,
+        vector,
         k: req.k,
         filter: req.filter,
-        ef_search: None,
+        ef_search: req.ef_search,
     };
 
     let mut results = db.search(query).map_err(Error::Core)?;
+    apply_score_threshold(&mut results, req.score_threshold);
+
+    Ok(Json(SearchResponse { results }).into_response())
+}
+
+/// Drop the results scoring above `threshold`, if given; scores are
+/// distances, so the nearest results are the ones kept
+pub(crate) fn apply_score_threshold(results: &mut Vec<SearchResult>, threshold: Option<f32>) {
+    if let Some(threshold) = threshold {
+        results.retain(|r| r.score <= threshold);
+    }
+}
+
+/// Embed with the collection's embedding provider, in one batch, the text
+/// of every input that has one instead of a vector
+///
+/// Inputs are `(has_vector, text)` pairs; the embeddings are returned in
+/// input order.
+pub(crate) fn embed_missing<'a>(
+    db: &VectorDB,
+    inputs: impl Iterator<Item = (bool, Option<&'a str>)>,
+) -> Result<std::vec::IntoIter<Vec<f32>>> {
+    let mut texts = Vec::new();
+    for (has_vector, text) in inputs {
+        match (has_vector, text) {
+            (true, None) => {}
+            (false, Some(text)) => texts.push(text),
+            _ => {
+                return Err(Error::InvalidRequest(
+                    "Exactly one of vector or text is required".to_string(),
+                ))
+            }
+        }
+    }
+    if texts.is_empty() {
+        return Ok(Vec::new().into_iter());
+    }
+
+    let embedded = db.embed(&texts).map_err(|e| match e {
+        RuvectorError::InvalidParameter(message) => Error::InvalidRequest(message),
+        e => Error::Core(e),
+    })?;
+    Ok(embedded.into_iter())
+}
+
+/// Run several searches in one request
+///
+/// POST /collections/:name/points/search/batch
+async fn search_batch(
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+    Json(req): Json<SearchBatchRequest>,
+) -> Result<impl IntoResponse> {
+    let db = state
+        .get_collection(&name)
+        .ok_or_else(|| Error::CollectionNotFound(name))?;
+
+    if req.searches.iter().any(|search| search.group_by.is_some()) {
+        return Err(Error::InvalidRequest(
+            "group_by is not supported in batch search".to_string(),
+        ));
+    }
+    let thresholds: Vec<Option<f32>> = req.searches.iter().map(|s| s.score_threshold).collect();
+    let mut embedded = embed_missing(
+        &db,
+        req.searches
+            .iter()
+            .map(|search| (search.vector.is_some(), search.text.as_deref())),
+    )?;
+
+    let queries = req
+        .searches
+        .into_iter()
+        .map(|search| SearchQuery {
+            vector: search
+                .vector
+                .or_else(|| embedded.next())
+                .unwrap_or_default(),
+            k: search.k,
+            filter: search.filter,
+            ef_search: search.ef_search,
+        })
+        .collect();
 
-    // Apply score threshold if provided
-    if let Some(threshold) = req.score_threshold {
-        results.retain(|r| r.score >= threshold);
+    let mut results = db.search_batch(queries).map_err(Error::Core)?;
+    for (results, threshold) in results.iter_mut().zip(thresholds) {
+        apply_score_threshold(results, threshold);
     }
 
+    Ok(Json(SearchBatchResponse { results }))
+}
+
+/// Page through the points of a collection in id order
+///
+/// POST /collections/:name/points/scroll
+async fn scroll_points(
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+    Json(req): Json<ScrollPointsRequest>,
+) -> Result<impl IntoResponse> {
+    let db = state
+        .get_collection(&name)
+        .ok_or_else(|| Error::CollectionNotFound(name))?;
+
+    let page = db
+        .scroll(ScrollRequest {
+            offset: req.offset,
+            limit: req.limit,
+            filter: req.filter,
+            with_vector: req.with_vector,
+            with_payload: req.with_payload,
+        })
+        .map_err(|e| match e {
+            RuvectorError::InvalidParameter(message) => Error::InvalidRequest(message),
+            e => Error::Core(e),
+        })?;
+
+    Ok(Json(page))
+}
+
+/// Search for every point within a radius of the query vector
+///
+/// POST /collections/:name/points/search/range
+async fn search_range(
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+    Json(req): Json<RangeSearchRequest>,
+) -> Result<impl IntoResponse> {
+    let db = state
+        .get_collection(&name)
+        .ok_or_else(|| Error::CollectionNotFound(name))?;
+
+    if let Some(space) = &req.space {
+        if !db.vector_spaces().contains_key(space) {
+            return Err(Error::VectorSpaceNotFound(space.clone()));
+        }
+    }
+    let results = db
+        .search_range(RangeSearchQuery {
+            space: req.space,
+            vector: req.vector,
+            radius: req.radius,
+            max_results: req.limit,
+            filter: req.filter,
+            ef_search: req.ef_search,
+        })
+        .map_err(|e| match e {
+            RuvectorError::InvalidParameter(message) => Error::InvalidRequest(message),
+            e => Error::Core(e),
+        })?;
+
     Ok(Json(SearchResponse { results }))
 }
 
+/// Search for the nearest neighbors of a stored point
+///
+/// POST /collections/:name/points/:id/neighbors
+async fn search_neighbors(
+    State(state): State<AppState>,
+    Path((name, id)): Path<(String, String)>,
+    Json(req): Json<NeighborsRequest>,
+) -> Result<impl IntoResponse> {
+    let db = state
+        .get_collection(&name)
+        .ok_or_else(|| Error::CollectionNotFound(name))?;
+
+    if let Some(space) = &req.space {
+        if !db.vector_spaces().contains_key(space) {
+            return Err(Error::VectorSpaceNotFound(space.clone()));
+        }
+    }
+    let results = db
+        .search_by_id(SearchByIdQuery {
+            id: id.clone(),
+            space: req.space,
+            k: req.k,
+            filter: req.filter,
+            ef_search: req.ef_search,
+        })
+        .map_err(Error::Core)?
+        .ok_or(Error::PointNotFound(id))?;
+
+    Ok(Json(SearchResponse { results }))
+}
+
+/// Late-interaction (MaxSim) search over a multi-vector space
+///
+/// POST /collections/:name/points/search/multivector
+async fn search_multivector(
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+    Json(req): Json<MultiVectorSearchRequest>,
+) -> Result<impl IntoResponse> {
+    let db = state
+        .get_collection(&name)
+        .ok_or_else(|| Error::CollectionNotFound(name))?;
+
+    if !db.vector_spaces().contains_key(&req.space) {
+        return Err(Error::VectorSpaceNotFound(req.space));
+    }
+    let results = db
+        .search_multivector(&req.space, &req.vectors, req.k, req.filter)
+        .map_err(Error::Core)?;
+
+    Ok(Json(SearchResponse { results }))
+}
+
+/// Set the bag of vectors of a point in a multi-vector space
+///
+/// PUT /collections/:name/points/:id/multivectors/:space
+async fn set_multivector(
+    State(state): State<AppState>,
+    Path((name, id, space)): Path<(String, String, String)>,
+    Json(req): Json<SetMultiVectorRequest>,
+) -> Result<impl IntoResponse> {
+    let db = state
+        .get_collection(&name)
+        .ok_or_else(|| Error::CollectionNotFound(name))?;
+
+    if !db.vector_spaces().contains_key(&space) {
+        return Err(Error::VectorSpaceNotFound(space));
+    }
+    if !db
+        .update_multivector(&id, &space, &req.vectors)
+        .map_err(Error::Core)?
+    {
+        return Err(Error::PointNotFound(id));
+    }
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
 /// Get a point by ID
 ///
 /// GET /collections/:name/points/:id
@@ -116,7 +613,193 @@ async fn get_point(
         .get_collection(&name)
         .ok_or_else(|| Error::CollectionNotFound(name))?;
 
-    let entry = db.get(&id).map_err(Error::Core)?;
+    let entry = db
+        .get(&id)
+        .map_err(Error::Core)?
+        .ok_or(Error::PointNotFound(id))?;
 
     Ok(Json(entry))
 }
+
+/// Delete a point
+///
+/// DELETE /collections/:name/points/:id
+async fn delete_point(
+    State(state): State<AppState>,
+    Path((name, id)): Path<(String, String)>,
+) -> Result<impl IntoResponse> {
+    let db = state
+        .get_collection(&name)
+        .ok_or_else(|| Error::CollectionNotFound(name))?;
+
+    if !db.delete(&id).map_err(Error::Core)? {
+        return Err(Error::PointNotFound(id));
+    }
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Delete points by id or by filter
+///
+/// POST /collections/:name/points/delete
+async fn delete_points(
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+    Json(req): Json<DeletePointsRequest>,
+) -> Result<impl IntoResponse> {
+    let db = state
+        .get_collection(&name)
+        .ok_or_else(|| Error::CollectionNotFound(name))?;
+
+    let deleted = match (req.ids, req.filter) {
+        (Some(ids), None) => {
+            let ids: Vec<&str> = ids.iter().map(String::as_str).collect();
+            let deleted = db.delete_batch(&ids).map_err(Error::Core)?;
+            deleted.into_iter().filter(|&deleted| deleted).count()
+        }
+        (None, Some(filter)) => db
+            .delete_by_filter(&filter.to_expression())
+            .map_err(Error::Core)?,
+        _ => {
+            return Err(Error::InvalidRequest(
+                "Exactly one of ids or filter is required".to_string(),
+            ))
+        }
+    };
+
+    Ok(Json(DeletePointsResponse { deleted }))
+}
+
+/// Get several points by id
+///
+/// POST /collections/:name/points/get
+async fn get_points(
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+    Json(req): Json<GetPointsRequest>,
+) -> Result<impl IntoResponse> {
+    let db = state
+        .get_collection(&name)
+        .ok_or_else(|| Error::CollectionNotFound(name))?;
+
+    let ids: Vec<&str> = req.ids.iter().map(String::as_str).collect();
+    let points = db
+        .get_batch(&ids)
+        .map_err(Error::Core)?
+        .into_iter()
+        .zip(&req.ids)
+        .filter_map(|(entry, id)| {
+            let entry = entry?;
+            Some(PointRecord {
+                id: id.clone(),
+                vector: req.with_vector.then_some(entry.vector),
+                metadata: entry.metadata.filter(|_| req.with_payload),
+            })
+        })
+        .collect();
+
+    Ok(Json(GetPointsResponse { points }))
+}
+
+/// Count points, optionally only those matching a filter
+///
+/// POST /collections/:name/points/count
+async fn count_points(
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+    Json(req): Json<CountPointsRequest>,
+) -> Result<impl IntoResponse> {
+    let db = state
+        .get_collection(&name)
+        .ok_or_else(|| Error::CollectionNotFound(name))?;
+
+    let filter = req.filter.as_ref().map(SearchFilter::to_expression);
+    let count = db.count(filter.as_ref()).map_err(Error::Core)?;
+
+    Ok(Json(CountResponse { count }))
+}
+
+/// Replace the metadata of a point
+///
+/// PUT /collections/:name/points/:id/payload
+async fn set_payload(
+    State(state): State<AppState>,
+    Path((name, id)): Path<(String, String)>,
+    Json(req): Json<PayloadRequest>,
+) -> Result<impl IntoResponse> {
+    let db = state
+        .get_collection(&name)
+        .ok_or_else(|| Error::CollectionNotFound(name))?;
+
+    if !db.update_payload(&id, req.payload).map_err(Error::Core)? {
+        return Err(Error::PointNotFound(id));
+    }
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Insert or overwrite metadata keys of a point, keeping its other keys
+///
+/// PATCH /collections/:name/points/:id/payload
+async fn merge_payload(
+    State(state): State<AppState>,
+    Path((name, id)): Path<(String, String)>,
+    Json(req): Json<PayloadRequest>,
+) -> Result<impl IntoResponse> {
+    let db = state
+        .get_collection(&name)
+        .ok_or_else(|| Error::CollectionNotFound(name))?;
+
+    if !db.merge_payload(&id, req.payload).map_err(Error::Core)? {
+        return Err(Error::PointNotFound(id));
+    }
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Remove metadata keys of a point
+///
+/// POST /collections/:name/points/:id/payload/delete
+async fn delete_payload_keys(
+    State(state): State<AppState>,
+    Path((name, id)): Path<(String, String)>,
+    Json(req): Json<DeletePayloadKeysRequest>,
+) -> Result<impl IntoResponse> {
+    let db = state
+        .get_collection(&name)
+        .ok_or_else(|| Error::CollectionNotFound(name))?;
+
+    if !db
+        .delete_payload_keys(&id, &req.keys)
+        .map_err(Error::Core)?
+    {
+        return Err(Error::PointNotFound(id));
+    }
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hit(id: &str, score: f32) -> SearchResult {
+        SearchResult {
+            id: id.to_string(),
+            score,
+            vector: None,
+            metadata: None,
+        }
+    }
+
+    #[test]
+    fn test_score_threshold_keeps_the_nearest_hits() {
+        let mut results = vec![hit("near", 0.1), hit("edge", 0.5), hit("far", 2.0)];
+        apply_score_threshold(&mut results, Some(0.5));
+        let ids: Vec<_> = results.iter().map(|r| r.id.as_str()).collect();
+        assert_eq!(ids, ["near", "edge"]);
+
+        apply_score_threshold(&mut results, None);
+        assert_eq!(results.len(), 2);
+    }
+}
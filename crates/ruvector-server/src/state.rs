@@ -1,60 +1,130 @@
 //! Shared application state
 
-use dashmap::DashMap;
-use ruvector_core::VectorDB;
+use ruvector_collections::{CollectionManager, Result};
+use ruvector_core::{BoxedChangeListener, VectorDB};
+use ruvector_replication::{ChangeCapture, ChangeLog};
+use std::path::PathBuf;
 use std::sync::Arc;
 
 /// Shared application state
 #[derive(Clone)]
 pub struct AppState {
-    /// Map of collection name to VectorDB
-    pub collections: Arc<DashMap<String, Arc<VectorDB>>>,
+    /// Collections and aliases persisted under the data directory
+    pub manager: Arc<CollectionManager>,
+    /// Log of the changes to every collection's points, `None` if change
+    /// capture is disabled
+    pub changes: Option<Arc<ChangeLog>>,
 }
 
 impl AppState {
-    /// Create a new application state
-    pub fn new() -> Self {
+    /// Create a new application state backed by `manager`
+    pub fn new(manager: CollectionManager) -> Self {
         Self {
-            collections: Arc::new(DashMap::new()),
+            manager: Arc::new(manager),
+            changes: None,
         }
     }
 
-    /// Get a collection by name
-    pub fn get_collection(&self, name: &str) -> Option<Arc<VectorDB>> {
-        self.collections.get(name).map(|c| c.clone())
+    /// Open the collections stored under `data_dir`, creating it if needed
+    pub fn open(data_dir: impl Into<PathBuf>) -> Result<Self> {
+        Ok(Self::new(CollectionManager::new(data_dir.into())?))
     }
 
-    /// Insert a collection
-    pub fn insert_collection(&self, name: String, db: Arc<VectorDB>) {
-        self.collections.insert(name, db);
+    /// Publish the changes to every collection's points to `log`, first
+    /// catching up with those the log missed
+    pub fn with_change_log(mut self, log: Arc<ChangeLog>) -> Result<Self> {
+        let capture_log = log.clone();
+        self.manager
+            .set_change_listener(Arc::new(move |collection: &str| {
+                Arc::new(ChangeCapture::new(capture_log.clone(), collection)) as BoxedChangeListener
+            }))?;
+        self.changes = Some(log);
+        Ok(self)
     }
 
-    /// Remove a collection
-    pub fn remove_collection(&self, name: &str) -> Option<Arc<VectorDB>> {
-        self.collections.remove(name).map(|(_, c)| c)
+    /// Get a collection by name or alias
+    pub fn get_collection(&self, name: &str) -> Option<Arc<VectorDB>> {
+        self.manager
+            .get_collection(name)
+            .map(|collection| collection.read().db.clone())
     }
 
     /// Check if a collection exists
     pub fn contains_collection(&self, name: &str) -> bool {
-        self.collections.contains_key(name)
+        self.manager.collection_exists(name)
     }
 
-    /// Get all collection names
+    /// Get all collection names, sorted
     pub fn collection_names(&self) -> Vec<String> {
-        self.collections
-            .iter()
-            .map(|entry| entry.key().clone())
-            .collect()
+        let mut names = self.manager.list_collections();
+        names.sort();
+        names
     }
 
     /// Get the number of collections
     pub fn collection_count(&self) -> usize {
-        self.collections.len()
+        self.manager.collection_count()
     }
 }
 
-impl Default for AppState {
-    fn default() -> Self {
-        Self::new()
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ruvector_collections::CollectionConfig;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_collections_are_recovered_on_open() -> Result<()> {
+        let dir = tempdir().unwrap();
+        let data_dir = dir.path();
+
+        let state = AppState::open(data_dir)?;
+        state
+            .manager
+            .create_collection("docs", CollectionConfig::with_dimensions(2))?;
+        state.manager.create_alias("live", "docs")?;
+        drop(state);
+
+        let state = AppState::open(data_dir)?;
+        assert_eq!(state.collection_names(), vec!["docs".to_string()]);
+        assert!(state.get_collection("live").is_some());
+        assert!(!state.contains_collection("live"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_change_log_covers_every_collection() -> Result<()> {
+        let dir = tempdir().unwrap();
+        let data_dir = dir.path();
+
+        let state = AppState::open(data_dir)?;
+        state
+            .manager
+            .create_collection("docs", CollectionConfig::with_dimensions(2))?;
+        let log = Arc::new(ChangeLog::open(data_dir.join(".changes")).unwrap());
+        let state = state.with_change_log(log.clone())?;
+        state
+            .manager
+            .create_collection("notes", CollectionConfig::with_dimensions(2))?;
+
+        for name in ["docs", "notes"] {
+            state
+                .get_collection(name)
+                .unwrap()
+                .insert(ruvector_core::VectorEntry {
+                    id: Some("a".to_string()),
+                    vector: vec![1.0, 0.0],
+                    metadata: None,
+                })?;
+        }
+        let batch = log.read(0, 10, None).unwrap();
+        let collections: Vec<_> = batch.events.iter().map(|e| e.collection.as_str()).collect();
+        assert_eq!(collections, vec!["docs", "notes"]);
+
+        // The log's directory isn't mistaken for a collection
+        drop(state);
+        let state = AppState::open(data_dir)?;
+        assert_eq!(state.collection_count(), 2);
+        Ok(())
     }
 }
@@ -0,0 +1,370 @@
+//! API key and JWT authorization over REST and gRPC
+
+mod common;
+
+use axum::{
+    body::Body,
+    http::{Request, StatusCode},
+};
+use common::TestServer;
+use jsonwebtoken::{EncodingKey, Header};
+use ruvector_server::auth::Claims;
+use ruvector_server::grpc::proto::{
+    collections_client::CollectionsClient, points_client::PointsClient, ListCollectionsRequest,
+    Point, SearchRequest, UpsertPointsRequest,
+};
+use ruvector_server::{
+    Access, ApiKeyConfig, AuthConfig, Config, JwtAlgorithm, JwtConfig, RateLimit,
+};
+use serde_json::{json, Value};
+use std::time::{SystemTime, UNIX_EPOCH};
+use tower::ServiceExt;
+
+const SECRET: &[u8] = b"test-secret";
+
+fn api_key(name: &str, access: Access, collections: Option<&[&str]>) -> ApiKeyConfig {
+    ApiKeyConfig {
+        name: name.to_string(),
+        key: format!("{}-key", name),
+        access,
+        collections: collections.map(|c| c.iter().map(|c| c.to_string()).collect()),
+        rate_limit: None,
+    }
+}
+
+async fn start() -> TestServer {
+    let key_dir = tempfile::tempdir().unwrap();
+    let key_file = key_dir.path().join("jwt.key");
+    // Saved with a trailing newline, as an editor would
+    std::fs::write(&key_file, [SECRET, b"\n"].concat()).unwrap();
+
+    let mut limited = api_key("limited", Access::Read, None);
+    limited.rate_limit = Some(RateLimit {
+        requests_per_second: 1,
+        burst: Some(2),
+    });
+    let config = Config {
+        auth: AuthConfig {
+            api_keys: vec![
+                api_key("admin", Access::ReadWrite, None),
+                api_key("reader", Access::Read, Some(&["docs"])),
+                limited,
+            ],
+            jwt: Some(JwtConfig {
+                algorithm: JwtAlgorithm::Hs256,
+                key_file,
+                rate_limit: None,
+            }),
+        },
+        ..Config::default()
+    };
+    let server = TestServer::with_config(config).await;
+
+    for collection in ["docs", "other"] {
+        let (status, _) = server
+            .rest_with_headers(
+                "POST",
+                "/collections",
+                json!({"name": collection, "dimension": 2}),
+                &[("api-key", "admin-key")],
+            )
+            .await;
+        assert_eq!(status, StatusCode::CREATED);
+    }
+    server
+}
+
+fn token(access: Access, collections: &[&str], expires_in: i64) -> String {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs() as i64;
+    let claims = Claims {
+        sub: "service".to_string(),
+        exp: (now + expires_in) as u64,
+        access,
+        collections: Some(collections.iter().map(|c| c.to_string()).collect()),
+    };
+    jsonwebtoken::encode(
+        &Header::default(),
+        &claims,
+        &EncodingKey::from_secret(SECRET),
+    )
+    .unwrap()
+}
+
+fn with_key<T>(key: &str, message: T) -> tonic::Request<T> {
+    let mut request = tonic::Request::new(message);
+    request
+        .metadata_mut()
+        .insert("api-key", key.parse().unwrap());
+    request
+}
+
+fn upsert_body() -> Value {
+    json!({"points": [{"id": "a", "vector": [1.0, 0.0]}]})
+}
+
+#[tokio::test]
+async fn test_rest_api_keys_and_scopes() {
+    let server = start().await;
+    let as_key = |key: &'static str| [("api-key", key)];
+
+    let (status, _) = server.rest("GET", "/health", Value::Null).await;
+    assert_eq!(status, StatusCode::OK);
+    let (status, body) = server.rest("GET", "/collections", Value::Null).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+    assert_eq!(body["status"], 401);
+    let (status, _) = server
+        .rest_with_headers("GET", "/collections", Value::Null, &as_key("wrong"))
+        .await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+    // Bearer tokens are accepted as API keys too
+    let (status, _) = server
+        .rest_with_headers(
+            "PUT",
+            "/collections/docs/points",
+            upsert_body(),
+            &[("authorization", "Bearer admin-key")],
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK);
+
+    // A read-only key scoped to docs can search it, nothing more
+    let (status, _) = server
+        .rest_with_headers(
+            "POST",
+            "/collections/docs/points/search",
+            json!({"vector": [1.0, 0.0]}),
+            &as_key("reader-key"),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    let (status, _) = server
+        .rest_with_headers(
+            "PUT",
+            "/collections/docs/points",
+            upsert_body(),
+            &as_key("reader-key"),
+        )
+        .await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    let (status, _) = server
+        .rest_with_headers(
+            "GET",
+            "/collections/other",
+            Value::Null,
+            &as_key("reader-key"),
+        )
+        .await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    let (status, body) = server
+        .rest_with_headers("GET", "/collections", Value::Null, &as_key("reader-key"))
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["collections"], json!(["docs"]));
+
+    // It only sees the aliases of docs
+    for (alias, collection) in [("current", "docs"), ("staging", "other")] {
+        let (status, _) = server
+            .rest_with_headers(
+                "POST",
+                "/aliases",
+                json!({"alias": alias, "collection": collection}),
+                &as_key("admin-key"),
+            )
+            .await;
+        assert_eq!(status, StatusCode::CREATED);
+    }
+    let (status, body) = server
+        .rest_with_headers("GET", "/aliases", Value::Null, &as_key("reader-key"))
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(
+        body["aliases"],
+        json!([{"alias": "current", "collection": "docs"}])
+    );
+    let (status, body) = server
+        .rest_with_headers("GET", "/aliases", Value::Null, &as_key("admin-key"))
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["aliases"].as_array().unwrap().len(), 2);
+    let (status, body) = server
+        .rest_with_headers(
+            "GET",
+            "/aliases/current",
+            Value::Null,
+            &as_key("reader-key"),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["collection"], "docs");
+    let (status, _) = server
+        .rest_with_headers(
+            "GET",
+            "/aliases/staging",
+            Value::Null,
+            &as_key("reader-key"),
+        )
+        .await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+
+    // Requests beyond the burst are rate limited
+    let mut statuses = Vec::new();
+    for _ in 0..3 {
+        let (status, _) = server
+            .rest_with_headers("GET", "/collections", Value::Null, &as_key("limited-key"))
+            .await;
+        statuses.push(status);
+    }
+    assert_eq!(
+        statuses,
+        [
+            StatusCode::OK,
+            StatusCode::OK,
+            StatusCode::TOO_MANY_REQUESTS
+        ]
+    );
+}
+
+#[tokio::test]
+async fn test_rest_jwt() {
+    let server = start().await;
+    let bearer = |token: &str| format!("Bearer {}", token);
+
+    let writer = bearer(&token(Access::ReadWrite, &["other"], 3600));
+    let (status, _) = server
+        .rest_with_headers(
+            "PUT",
+            "/collections/other/points",
+            upsert_body(),
+            &[("authorization", writer.as_str())],
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    let (status, _) = server
+        .rest_with_headers(
+            "PUT",
+            "/collections/docs/points",
+            upsert_body(),
+            &[("authorization", writer.as_str())],
+        )
+        .await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+
+    // Scoped tokens can't manage collections
+    let (status, _) = server
+        .rest_with_headers(
+            "DELETE",
+            "/collections/other",
+            Value::Null,
+            &[("authorization", writer.as_str())],
+        )
+        .await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+
+    let expired = bearer(&token(Access::ReadWrite, &["other"], -3600));
+    let (status, _) = server
+        .rest_with_headers(
+            "GET",
+            "/collections/other",
+            Value::Null,
+            &[("authorization", expired.as_str())],
+        )
+        .await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+}
+
+/// The origin CORS allows a request from `origin`, if any
+async fn allowed_origin(server: &TestServer, origin: &str) -> Option<String> {
+    let request = Request::builder()
+        .uri("/health")
+        .header("origin", origin)
+        .body(Body::empty())
+        .unwrap();
+    let response = server.server.router().oneshot(request).await.unwrap();
+    response
+        .headers()
+        .get("access-control-allow-origin")
+        .map(|value| value.to_str().unwrap().to_string())
+}
+
+#[tokio::test]
+async fn test_cors_origins_with_auth() {
+    let origin = "https://app.example.com";
+
+    // Without credentials any origin is allowed, but not once they're
+    // configured unless it's listed
+    let open = TestServer::start().await;
+    assert_eq!(allowed_origin(&open, origin).await.as_deref(), Some("*"));
+    let server = start().await;
+    assert_eq!(allowed_origin(&server, origin).await, None);
+
+    let listed = TestServer::with_config(Config {
+        auth: AuthConfig {
+            api_keys: vec![api_key("admin", Access::ReadWrite, None)],
+            jwt: None,
+        },
+        cors_allowed_origins: vec![origin.to_string()],
+        ..Config::default()
+    })
+    .await;
+    assert_eq!(
+        allowed_origin(&listed, origin).await.as_deref(),
+        Some(origin)
+    );
+    assert_eq!(
+        allowed_origin(&listed, "https://other.example.com").await,
+        None
+    );
+}
+
+#[tokio::test]
+async fn test_grpc_api_keys_and_scopes() {
+    let server = start().await;
+    let mut collections = CollectionsClient::new(server.grpc.clone());
+    let mut points = PointsClient::new(server.grpc.clone());
+    let search = |collection: &str| SearchRequest {
+        collection: collection.to_string(),
+        vector: vec![1.0, 0.0],
+        ..Default::default()
+    };
+
+    let status = points.search(search("docs")).await.unwrap_err();
+    assert_eq!(status.code(), tonic::Code::Unauthenticated);
+
+    points
+        .search(with_key("reader-key", search("docs")))
+        .await
+        .unwrap();
+    let status = points
+        .search(with_key("reader-key", search("other")))
+        .await
+        .unwrap_err();
+    assert_eq!(status.code(), tonic::Code::PermissionDenied);
+
+    let upsert = UpsertPointsRequest {
+        collection: "docs".to_string(),
+        points: vec![Point {
+            id: Some("a".to_string()),
+            vector: vec![1.0, 0.0],
+            text: None,
+            metadata: None,
+        }],
+    };
+    let status = points
+        .upsert(with_key("reader-key", upsert.clone()))
+        .await
+        .unwrap_err();
+    assert_eq!(status.code(), tonic::Code::PermissionDenied);
+    points.upsert(with_key("admin-key", upsert)).await.unwrap();
+
+    let listed = collections
+        .list(with_key("reader-key", ListCollectionsRequest {}))
+        .await
+        .unwrap()
+        .into_inner()
+        .collections;
+    assert_eq!(listed, vec!["docs".to_string()]);
+}
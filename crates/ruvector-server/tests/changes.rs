@@ -0,0 +1,262 @@
+//! Following the changes to a collection's points by long-polling and
+//! server-sent events
+
+mod common;
+
+use axum::{
+    body::Body,
+    http::{Request, StatusCode},
+};
+use common::TestServer;
+use futures::{Stream, StreamExt};
+use ruvector_server::Config;
+use serde_json::{json, Value};
+use std::time::Duration;
+use tower::ServiceExt;
+
+async fn start() -> TestServer {
+    let config = Config {
+        capture_changes: true,
+        ..Config::default()
+    };
+    let server = TestServer::with_config(config).await;
+    for collection in ["docs", "other"] {
+        let (status, _) = server
+            .rest(
+                "POST",
+                "/collections",
+                json!({"name": collection, "dimension": 2}),
+            )
+            .await;
+        assert_eq!(status, StatusCode::CREATED);
+    }
+    server
+}
+
+async fn upsert(server: &TestServer, collection: &str, id: &str, vector: [f32; 2]) {
+    let (status, _) = server
+        .rest(
+            "PUT",
+            &format!("/collections/{}/points", collection),
+            json!({"points": [{"id": id, "vector": vector}]}),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK);
+}
+
+fn change(operation: &str, id: &str) -> (String, String) {
+    (operation.to_string(), id.to_string())
+}
+
+fn operations(body: &Value) -> Vec<(String, String)> {
+    body["events"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|event| {
+            (
+                event["operation"].as_str().unwrap().to_string(),
+                event["document_id"].as_str().unwrap().to_string(),
+            )
+        })
+        .collect()
+}
+
+#[tokio::test]
+async fn test_long_poll_and_consumer_checkpoints() {
+    let server = start().await;
+    upsert(&server, "docs", "a", [1.0, 0.0]).await;
+    upsert(&server, "other", "x", [1.0, 0.0]).await;
+    upsert(&server, "docs", "b", [0.0, 1.0]).await;
+    let (status, _) = server
+        .rest(
+            "PUT",
+            "/collections/docs/points/a/payload",
+            json!({"payload": {"tag": "x"}}),
+        )
+        .await;
+    assert_eq!(status, StatusCode::NO_CONTENT);
+    let (status, _) = server
+        .rest("DELETE", "/collections/docs/points/b", Value::Null)
+        .await;
+    assert_eq!(status, StatusCode::NO_CONTENT);
+
+    let (status, body) = server
+        .rest("GET", "/collections/docs/changes", Value::Null)
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(
+        operations(&body),
+        vec![
+            change("Insert", "a"),
+            change("Insert", "b"),
+            change("Update", "a"),
+            change("Delete", "b"),
+        ]
+    );
+    let update = &body["events"][2];
+    assert_eq!(update["before"]["metadata"], Value::Null);
+    assert_eq!(update["after"]["metadata"]["tag"], "x");
+    assert_eq!(update["after"]["vector"], json!([1.0, 0.0]));
+    assert_eq!(body["events"][3]["after"], Value::Null);
+    assert_eq!(body["next"], 5);
+
+    // Paged, and by alias
+    server
+        .rest(
+            "POST",
+            "/aliases",
+            json!({"alias": "live", "collection": "docs"}),
+        )
+        .await;
+    let (_, body) = server
+        .rest(
+            "GET",
+            "/collections/live/changes?after=1&limit=1",
+            Value::Null,
+        )
+        .await;
+    assert_eq!(operations(&body), vec![change("Insert", "b")]);
+    assert_eq!(body["next"], 3);
+
+    // A long-poll returns once a change to its collection arrives
+    let poll = server.rest(
+        "GET",
+        "/collections/docs/changes?after=5&timeout_ms=10000",
+        Value::Null,
+    );
+    let writes = async {
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        upsert(&server, "other", "y", [0.0, 1.0]).await;
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        upsert(&server, "docs", "c", [1.0, 1.0]).await;
+    };
+    let ((status, body), ()) = tokio::join!(poll, writes);
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(operations(&body), vec![change("Insert", "c")]);
+    assert_eq!(body["next"], 7);
+
+    let (_, body) = server
+        .rest(
+            "GET",
+            "/collections/docs/changes?after=7&timeout_ms=50",
+            Value::Null,
+        )
+        .await;
+    assert_eq!(body["events"], json!([]));
+    assert_eq!(body["next"], 7);
+
+    // Consumers resume from their committed checkpoints
+    let (_, body) = server
+        .rest(
+            "GET",
+            "/collections/docs/changes/consumers/cache",
+            Value::Null,
+        )
+        .await;
+    assert_eq!(body["sequence"], 0);
+    let (status, _) = server
+        .rest(
+            "PUT",
+            "/collections/docs/changes/consumers/cache",
+            json!({"sequence": 4}),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    let (_, body) = server
+        .rest(
+            "GET",
+            "/collections/docs/changes?consumer=cache",
+            Value::Null,
+        )
+        .await;
+    assert_eq!(
+        operations(&body),
+        vec![change("Delete", "b"), change("Insert", "c")]
+    );
+    let (_, body) = server
+        .rest(
+            "GET",
+            "/collections/other/changes/consumers/cache",
+            Value::Null,
+        )
+        .await;
+    assert_eq!(body["sequence"], 0);
+
+    let (status, _) = server
+        .rest(
+            "PUT",
+            "/collections/docs/changes/consumers/cache",
+            json!({"sequence": 100}),
+        )
+        .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    let (status, _) = server
+        .rest("GET", "/collections/missing/changes", Value::Null)
+        .await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
+
+/// Read an event stream into `received` until it holds `count` events
+async fn read_events<S>(body: &mut S, received: &mut String, count: usize)
+where
+    S: Stream<Item = Result<axum::body::Bytes, axum::Error>> + Unpin,
+{
+    while received.matches("event: change").count() < count {
+        let chunk = tokio::time::timeout(Duration::from_secs(5), body.next())
+            .await
+            .unwrap()
+            .unwrap()
+            .unwrap();
+        received.push_str(std::str::from_utf8(&chunk).unwrap());
+    }
+}
+
+#[tokio::test]
+async fn test_stream_resumes_after_last_event_id() {
+    let server = start().await;
+    upsert(&server, "docs", "a", [1.0, 0.0]).await;
+    upsert(&server, "docs", "b", [0.0, 1.0]).await;
+
+    let request = Request::builder()
+        .uri("/collections/docs/changes/stream")
+        .header("last-event-id", "1")
+        .body(Body::empty())
+        .unwrap();
+    let response = server.server.router().oneshot(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.headers()["content-type"], "text/event-stream");
+    let mut body = response.into_body().into_data_stream();
+    let mut received = String::new();
+
+    read_events(&mut body, &mut received, 1).await;
+    assert!(received.contains("id: 2\n"));
+    assert!(!received.contains("id: 1\n"));
+    assert!(received.contains("\"document_id\":\"b\""));
+
+    // Changes made while the stream is open follow
+    upsert(&server, "other", "x", [1.0, 1.0]).await;
+    upsert(&server, "docs", "c", [1.0, 1.0]).await;
+    read_events(&mut body, &mut received, 2).await;
+    assert!(received.contains("id: 4\n"));
+    assert!(!received.contains("id: 3\n"));
+}
+
+#[tokio::test]
+async fn test_change_capture_is_off_by_default() {
+    let server = TestServer::start().await;
+    let (status, _) = server
+        .rest(
+            "POST",
+            "/collections",
+            json!({"name": "docs", "dimension": 2}),
+        )
+        .await;
+    assert_eq!(status, StatusCode::CREATED);
+
+    let (status, _) = server
+        .rest("GET", "/collections/docs/changes", Value::Null)
+        .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert!(!server.data_dir.join(".changes").exists());
+}
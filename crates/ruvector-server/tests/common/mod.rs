@@ -0,0 +1,105 @@
+//! Server harness shared by the integration tests
+//!
+//! Serves gRPC on a local port and answers REST requests directly through
+//! the router, both over the same `AppState`.
+
+#![allow(dead_code)]
+
+use axum::{
+    body::{to_bytes, Body},
+    http::{Request, StatusCode},
+};
+use ruvector_server::{Config, RuvectorServer};
+use serde_json::Value;
+use std::path::PathBuf;
+use tempfile::TempDir;
+use tokio_stream::wrappers::TcpListenerStream;
+use tonic::transport::Channel;
+use tower::ServiceExt;
+
+pub struct TestServer {
+    pub server: RuvectorServer,
+    pub grpc: Channel,
+    pub data_dir: PathBuf,
+    _dir: TempDir,
+}
+
+impl TestServer {
+    pub async fn start() -> Self {
+        Self::with_config(Config::default()).await
+    }
+
+    /// Start a server with `config`, storing its collections in a fresh
+    /// temporary directory
+    pub async fn with_config(config: Config) -> Self {
+        let dir = tempfile::tempdir().unwrap();
+        let data_dir = dir.path().to_path_buf();
+
+        let server = RuvectorServer::with_config(Config {
+            data_dir: data_dir.clone(),
+            ..config
+        })
+        .unwrap();
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let routes = server.grpc_routes();
+        tokio::spawn(async move {
+            tonic::transport::Server::builder()
+                .add_routes(routes)
+                .serve_with_incoming(TcpListenerStream::new(listener))
+                .await
+                .unwrap();
+        });
+
+        let grpc = Channel::from_shared(format!("http://{}", addr))
+            .unwrap()
+            .connect()
+            .await
+            .unwrap();
+
+        Self {
+            server,
+            grpc,
+            data_dir,
+            _dir: dir,
+        }
+    }
+
+    pub async fn rest(&self, method: &str, uri: &str, body: Value) -> (StatusCode, Value) {
+        self.rest_with_headers(method, uri, body, &[]).await
+    }
+
+    pub async fn rest_with_headers(
+        &self,
+        method: &str,
+        uri: &str,
+        body: Value,
+        headers: &[(&str, &str)],
+    ) -> (StatusCode, Value) {
+        let mut request = Request::builder()
+            .method(method)
+            .uri(uri)
+            .header("content-type", "application/json");
+        for (name, value) in headers {
+            request = request.header(*name, *value);
+        }
+        let request = request.body(Body::from(body.to_string())).unwrap();
+
+        let response = self.server.router().oneshot(request).await.unwrap();
+        let status = response.status();
+        let bytes = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let body = if bytes.is_empty() {
+            Value::Null
+        } else {
+            serde_json::from_slice(&bytes).unwrap()
+        };
+        (status, body)
+    }
+}
+
+impl Drop for TestServer {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.data_dir);
+    }
+}
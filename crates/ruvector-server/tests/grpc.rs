@@ -0,0 +1,276 @@
+//! REST and gRPC served from the same `AppState`
+
+mod common;
+
+use axum::http::StatusCode;
+use common::TestServer;
+use ruvector_server::grpc::proto::{
+    collections_client::CollectionsClient, points_client::PointsClient, CountPointsRequest,
+    CreateCollectionRequest, DistanceMetric, GetCollectionRequest, GetPointsRequest, Point,
+    SearchBatchRequest, SearchRequest, UpsertPointsRequest,
+};
+use serde_json::{json, Value};
+
+fn point(id: &str, vector: Vec<f32>, metadata: Value) -> Point {
+    Point {
+        id: Some(id.to_string()),
+        vector,
+        text: None,
+        metadata: Some(metadata.to_string()),
+    }
+}
+
+#[tokio::test]
+async fn test_transports_share_collections_and_points() {
+    let server = TestServer::start().await;
+    let mut collections = CollectionsClient::new(server.grpc.clone());
+    let mut points = PointsClient::new(server.grpc.clone());
+
+    // Created over gRPC, visible over REST
+    let info = collections
+        .create(CreateCollectionRequest {
+            name: "docs".to_string(),
+            dimension: 2,
+            metric: DistanceMetric::Euclidean as i32,
+        })
+        .await
+        .unwrap()
+        .into_inner();
+    assert_eq!(info.dimension, 2);
+    let (status, body) = server.rest("GET", "/collections/docs", Value::Null).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["metric"], "Euclidean");
+
+    // Upserted over REST, found over gRPC
+    let (status, _) = server
+        .rest(
+            "PUT",
+            "/collections/docs/points",
+            json!({"points": [
+                {"id": "a", "vector": [0.0, 0.0], "metadata": {"kind": "x"}},
+                {"id": "b", "vector": [5.0, 5.0], "metadata": {"kind": "y"}},
+            ]}),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK);
+
+    let results = points
+        .search(SearchRequest {
+            collection: "docs".to_string(),
+            vector: vec![0.1, 0.0],
+            k: 1,
+            ..Default::default()
+        })
+        .await
+        .unwrap()
+        .into_inner()
+        .results;
+    assert_eq!(results.len(), 1);
+    assert_eq!(results[0].id, "a");
+    let metadata: Value = serde_json::from_str(results[0].metadata.as_ref().unwrap()).unwrap();
+    assert_eq!(metadata, json!({"kind": "x"}));
+
+    // Upserted over a gRPC stream, counted and read over REST
+    let batches = vec![
+        UpsertPointsRequest {
+            collection: "docs".to_string(),
+            points: vec![point("c", vec![1.0, 1.0], json!({"kind": "x"}))],
+        },
+        UpsertPointsRequest {
+            collection: "docs".to_string(),
+            points: vec![point("d", vec![9.0, 9.0], json!({"kind": "y"}))],
+        },
+    ];
+    let ids = points
+        .upsert_stream(tokio_stream::iter(batches))
+        .await
+        .unwrap()
+        .into_inner()
+        .ids;
+    assert_eq!(ids, vec!["c".to_string(), "d".to_string()]);
+
+    let (status, body) = server
+        .rest(
+            "POST",
+            "/collections/docs/points/count",
+            json!({"filter": {"kind": "x"}}),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["count"], 2);
+    let (status, body) = server
+        .rest("GET", "/collections/docs/points/d", Value::Null)
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["vector"], json!([9.0, 9.0]));
+
+    // Deleted over REST, gone over gRPC
+    let (status, _) = server
+        .rest("DELETE", "/collections/docs/points/a", Value::Null)
+        .await;
+    assert_eq!(status, StatusCode::NO_CONTENT);
+    let count = points
+        .count(CountPointsRequest {
+            collection: "docs".to_string(),
+            filter: None,
+        })
+        .await
+        .unwrap()
+        .into_inner()
+        .count;
+    assert_eq!(count, 3);
+
+    let found = points
+        .get(GetPointsRequest {
+            collection: "docs".to_string(),
+            ids: vec!["a".to_string(), "b".to_string()],
+            with_vector: Some(false),
+            with_payload: None,
+        })
+        .await
+        .unwrap()
+        .into_inner()
+        .points;
+    assert_eq!(found.len(), 1);
+    assert_eq!(found[0].id, "b");
+    assert!(found[0].vector.is_empty());
+    assert!(found[0].metadata.is_some());
+}
+
+#[tokio::test]
+async fn test_grpc_batch_search_and_errors() {
+    let server = TestServer::start().await;
+    let mut collections = CollectionsClient::new(server.grpc.clone());
+    let mut points = PointsClient::new(server.grpc.clone());
+
+    let (status, _) = server
+        .rest(
+            "POST",
+            "/collections",
+            json!({"name": "docs", "dimension": 2, "metric": "Euclidean"}),
+        )
+        .await;
+    assert_eq!(status, StatusCode::CREATED);
+    points
+        .upsert(UpsertPointsRequest {
+            collection: "docs".to_string(),
+            points: (0..10)
+                .map(|i| point(&format!("p{}", i), vec![i as f32, 0.0], json!({})))
+                .collect(),
+        })
+        .await
+        .unwrap();
+
+    let search = |x: f32, k: u32| SearchRequest {
+        vector: vec![x, 0.0],
+        k,
+        ..Default::default()
+    };
+    let results = points
+        .search_batch(SearchBatchRequest {
+            collection: "docs".to_string(),
+            searches: vec![search(0.0, 2), search(9.0, 1)],
+        })
+        .await
+        .unwrap()
+        .into_inner()
+        .results;
+    let ids: Vec<Vec<String>> = results
+        .into_iter()
+        .map(|response| response.results.into_iter().map(|r| r.id).collect())
+        .collect();
+    assert_eq!(ids, vec![vec!["p0", "p1"], vec!["p9"]]);
+
+    // Errors carry the same classification as the REST status codes
+    let status = collections
+        .get(GetCollectionRequest {
+            name: "missing".to_string(),
+        })
+        .await
+        .unwrap_err();
+    assert_eq!(status.code(), tonic::Code::NotFound);
+    let status = collections
+        .create(CreateCollectionRequest {
+            name: "docs".to_string(),
+            dimension: 2,
+            metric: DistanceMetric::Unspecified as i32,
+        })
+        .await
+        .unwrap_err();
+    assert_eq!(status.code(), tonic::Code::AlreadyExists);
+    let status = points
+        .count(CountPointsRequest {
+            collection: "docs".to_string(),
+            filter: Some("{not json".to_string()),
+        })
+        .await
+        .unwrap_err();
+    assert_eq!(status.code(), tonic::Code::InvalidArgument);
+}
+
+#[tokio::test]
+async fn test_score_threshold_keeps_the_nearest_hits() {
+    let server = TestServer::start().await;
+    let mut points = PointsClient::new(server.grpc.clone());
+
+    let (status, _) = server
+        .rest(
+            "POST",
+            "/collections",
+            json!({"name": "docs", "dimension": 2, "metric": "Euclidean"}),
+        )
+        .await;
+    assert_eq!(status, StatusCode::CREATED);
+    let (status, _) = server
+        .rest(
+            "PUT",
+            "/collections/docs/points",
+            json!({"points": [
+                {"id": "near", "vector": [0.0, 0.0]},
+                {"id": "far", "vector": [5.0, 0.0]},
+            ]}),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK);
+
+    // Scores are distances, so a threshold of 1 keeps only the nearest hit,
+    // over either transport
+    let (status, body) = server
+        .rest(
+            "POST",
+            "/collections/docs/points/search",
+            json!({"vector": [0.0, 0.0], "k": 2, "score_threshold": 1.0}),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    let results = body["results"].as_array().unwrap();
+    assert_eq!(results.len(), 1);
+    assert_eq!(results[0]["id"], "near");
+
+    let request = SearchRequest {
+        collection: "docs".to_string(),
+        vector: vec![0.0, 0.0],
+        k: 2,
+        score_threshold: Some(1.0),
+        ..Default::default()
+    };
+    let results = points
+        .search(request.clone())
+        .await
+        .unwrap()
+        .into_inner()
+        .results;
+    let ids: Vec<String> = results.into_iter().map(|r| r.id).collect();
+    assert_eq!(ids, vec!["near"]);
+    let results = points
+        .search_batch(SearchBatchRequest {
+            collection: "docs".to_string(),
+            searches: vec![request],
+        })
+        .await
+        .unwrap()
+        .into_inner()
+        .results;
+    let ids: Vec<String> = results[0].results.iter().map(|r| r.id.clone()).collect();
+    assert_eq!(ids, vec!["near"]);
+}
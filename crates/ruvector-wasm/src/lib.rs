@@ -206,6 +206,7 @@ impl VectorDB {
             storage_path: ":memory:".to_string(), // Use in-memory for WASM
             hnsw_config,
             quantization: None, // Disable quantization for WASM (for now)
+            ..DbOptions::default()
         };
 
         let db = CoreVectorDB::new(options).map_err(|e| JsValue::from(WasmError::from(e)))?;
@@ -281,7 +282,8 @@ impl VectorDB {
     /// # Arguments
     /// * `query` - Query vector as Float32Array
     /// * `k` - Number of results to return
-    /// * `filter` - Optional metadata filter object
+    /// * `filter` - Optional metadata filter: a field/value equality object or a
+    ///   filter expression such as `{type: "range", field: "price", gte: 10, lte: 20}`
     ///
     /// # Returns
     /// Array of search results
@@ -517,6 +519,7 @@ impl CollectionManager {
             hnsw_config: Some(HnswConfig::default()),
             quantization: None,
             on_disk_payload: false, // Disable for WASM
+            embedding: None,
         };
 
         let manager = self.inner.lock();
@@ -585,6 +588,7 @@ impl CollectionManager {
             storage_path: ":memory:".to_string(),
             hnsw_config: collection.config.hnsw_config.clone(),
             quantization: collection.config.quantization.clone(),
+            ..DbOptions::default()
         };
 
         let db = CoreVectorDB::new(db_options)
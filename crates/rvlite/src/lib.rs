@@ -174,6 +174,7 @@ impl RvLiteConfig {
             storage_path: "memory://".to_string(),
             hnsw_config: None,
             quantization: None,
+            ..DbOptions::default()
         }
     }
 }
@@ -406,7 +407,7 @@ impl RvLite {
         let query = SearchQuery {
             vector: query_vector,
             k,
-            filter: Some(filter_map),
+            filter: Some(filter_map.into()),
             ef_search: None,
         };
 
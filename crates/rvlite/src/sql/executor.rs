@@ -127,6 +127,7 @@ impl SqlEngine {
             storage_path: "memory://".to_string(),
             hnsw_config: None,
             quantization: None,
+            ..ruvector_core::types::DbOptions::default()
         };
 
         let db = VectorDB::new(db_options)
@@ -258,7 +259,7 @@ impl SqlEngine {
 
                 // Build filter from WHERE clause
                 let filter = if let Some(where_expr) = where_clause {
-                    Some(self.build_filter(where_expr)?)
+                    Some(self.build_filter(where_expr)?.into())
                 } else {
                     None
                 };
@@ -318,7 +319,7 @@ impl SqlEngine {
 
         // Build filter from WHERE clause
         let filter = if let Some(where_expr) = where_clause {
-            Some(self.build_filter(where_expr)?)
+            Some(self.build_filter(where_expr)?.into())
         } else {
             None
         };